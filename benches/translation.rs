@@ -0,0 +1,151 @@
+//! Translation throughput on large multi-tool payloads:
+//! `cargo bench --bench translation`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use llm_gateway::config::{Config, EmptyContentPolicy};
+use llm_gateway::models::{AnthropicRequest, OpenAIResponse};
+use llm_gateway::internal::{anthropic_to_openai, openai_to_anthropic, translate_sse_body, AnthropicVersion};
+use serde_json::{json, Value};
+use std::hint::black_box;
+
+const TOOLS: usize = 24;
+const TURNS: usize = 16;
+const CALLS: usize = 8;
+
+fn tool_schema(i: usize) -> Value {
+    let properties: serde_json::Map<String, Value> = (0..12)
+        .map(|p| {
+            (
+                format!("field_{}", p),
+                json!({"type": "string", "description": format!("Parameter {} of tool {}", p, i)}),
+            )
+        })
+        .collect();
+    json!({"type": "object", "properties": properties, "required": ["field_0", "field_1"]})
+}
+
+fn tool_input(i: usize) -> Value {
+    let input: serde_json::Map<String, Value> = (0..12)
+        .map(|p| (format!("field_{}", p), json!(format!("value {} for call {} {}", p, i, "x".repeat(64)))))
+        .collect();
+    Value::Object(input)
+}
+
+/// A long agent conversation: every turn calls several tools and returns
+/// their results, with the full tool list attached.
+fn anthropic_request() -> Value {
+    let tools: Vec<Value> = (0..TOOLS)
+        .map(|i| json!({"name": format!("tool_{}", i), "description": format!("Tool number {}", i), "input_schema": tool_schema(i)}))
+        .collect();
+    let mut messages = vec![json!({"role": "user", "content": "Plan and run the migration."})];
+    for turn in 0..TURNS {
+        let calls: Vec<Value> = (0..4)
+            .map(|c| json!({"type": "tool_use", "id": format!("toolu_{}_{}", turn, c), "name": format!("tool_{}", c), "input": tool_input(c)}))
+            .collect();
+        let mut content = vec![json!({"type": "text", "text": format!("Step {}: running tools.", turn)})];
+        content.extend(calls);
+        messages.push(json!({"role": "assistant", "content": content}));
+        let results: Vec<Value> = (0..4)
+            .map(|c| json!({"type": "tool_result", "tool_use_id": format!("toolu_{}_{}", turn, c), "content": "ok ".repeat(200)}))
+            .collect();
+        messages.push(json!({"role": "user", "content": results}));
+    }
+    json!({
+        "model": "gpt-4o",
+        "max_tokens": 4096,
+        "system": "You are a careful migration assistant.",
+        "tools": tools,
+        "tool_choice": {"type": "auto"},
+        "messages": messages
+    })
+}
+
+fn openai_response() -> Value {
+    let calls: Vec<Value> = (0..CALLS)
+        .map(|i| json!({"id": format!("call_{}", i), "type": "function", "function": {"name": format!("tool_{}", i), "arguments": tool_input(i).to_string()}}))
+        .collect();
+    json!({
+        "id": "chatcmpl-bench",
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "finish_reason": "tool_calls",
+            "message": {
+                "role": "assistant",
+                "content": "Running the next batch of tools.",
+                "reasoning_content": {"type": "thinking", "thinking": "Need more data. ".repeat(200), "signature": "sig"},
+                "tool_calls": calls
+            }
+        }],
+        "usage": {"prompt_tokens": 12000, "completion_tokens": 900, "total_tokens": 12900}
+    })
+}
+
+/// Reasoning and text deltas followed by tool calls whose arguments arrive
+/// a few bytes per chunk.
+fn openai_stream() -> String {
+    let chunk = |delta: Value, finish: Option<&str>| {
+        format!(
+            "data: {}\n\n",
+            json!({"id": "chatcmpl-bench", "model": "gpt-4o", "choices": [{"index": 0, "delta": delta, "finish_reason": finish}]})
+        )
+    };
+    let mut body = String::new();
+    for i in 0..200 {
+        body.push_str(&chunk(json!({"reasoning_content": format!("thought {} ", i)}), None));
+    }
+    for i in 0..200 {
+        body.push_str(&chunk(json!({"content": format!("word{} ", i)}), None));
+    }
+    for i in 0..CALLS {
+        let arguments = tool_input(i).to_string();
+        let head = json!({"tool_calls": [{"index": i, "id": format!("call_{}", i), "type": "function", "function": {"name": format!("tool_{}", i), "arguments": ""}}]});
+        body.push_str(&chunk(head, None));
+        for piece in arguments.as_bytes().chunks(16) {
+            let piece = String::from_utf8_lossy(piece);
+            body.push_str(&chunk(json!({"tool_calls": [{"index": i, "function": {"arguments": piece}}]}), None));
+        }
+    }
+    body.push_str(&chunk(json!({}), Some("tool_calls")));
+    body.push_str("data: [DONE]\n\n");
+    body
+}
+
+fn bench_translation(c: &mut Criterion) {
+    let config = Config::from_yaml("server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\n")
+        .expect("config");
+    let mut group = c.benchmark_group("translation");
+
+    let request = anthropic_request().to_string();
+    group.bench_function("anthropic_to_openai", |b| {
+        b.iter_batched(
+            || serde_json::from_str::<AnthropicRequest>(&request).expect("request"),
+            |request| anthropic_to_openai(black_box(request), &config).expect("translate"),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let response = openai_response().to_string();
+    group.throughput(Throughput::Bytes(response.len() as u64));
+    group.bench_function("openai_to_anthropic", |b| {
+        b.iter(|| {
+            let parsed: OpenAIResponse = serde_json::from_str(black_box(&response)).expect("response");
+            openai_to_anthropic(parsed, EmptyContentPolicy::Error).expect("translate")
+        })
+    });
+
+    let stream = openai_stream();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().expect("runtime");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.bench_function("stream_state_machine", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(translate_sse_body(black_box(stream.as_bytes()), AnthropicVersion::default()))
+                .expect("translate")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_translation);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS request_summaries (
+    request_id TEXT NOT NULL,
+    ts_ms BIGINT NOT NULL,
+    route TEXT NOT NULL,
+    method TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    key_id TEXT,
+    model TEXT,
+    stream BOOLEAN NOT NULL,
+    status INTEGER NOT NULL,
+    error_type TEXT,
+    input_tokens BIGINT,
+    output_tokens BIGINT,
+    cache_hit BOOLEAN,
+    cost_usd DOUBLE PRECISION,
+    latency_ms BIGINT NOT NULL,
+    ttfb_ms BIGINT,
+    downstream_endpoint TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_request_summaries_ts ON request_summaries (ts_ms);
+CREATE INDEX IF NOT EXISTS idx_request_summaries_key_model ON request_summaries (key_id, model);
+
+CREATE TABLE IF NOT EXISTS audit_records (
+    id BIGSERIAL PRIMARY KEY,
+    request_id TEXT NOT NULL,
+    ts_start_ms BIGINT NOT NULL,
+    ts_end_ms BIGINT NOT NULL,
+    route TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    method TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    model TEXT,
+    request JSONB NOT NULL,
+    response JSONB NOT NULL,
+    meta JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_records_ts ON audit_records (ts_start_ms);
+CREATE INDEX IF NOT EXISTS idx_audit_records_request_id ON audit_records (request_id);
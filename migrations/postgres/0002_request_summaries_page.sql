@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS idx_request_summaries_ts_request ON request_summaries (ts_ms, request_id);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    template TEXT NOT NULL,
+    created_ms INTEGER NOT NULL,
+    PRIMARY KEY (id, version)
+);
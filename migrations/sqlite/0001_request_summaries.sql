@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS request_summaries (
+    request_id TEXT NOT NULL,
+    ts_ms INTEGER NOT NULL,
+    route TEXT NOT NULL,
+    method TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    key_id TEXT,
+    model TEXT,
+    stream INTEGER NOT NULL,
+    status INTEGER NOT NULL,
+    error_type TEXT,
+    input_tokens INTEGER,
+    output_tokens INTEGER,
+    cache_hit INTEGER,
+    cost_usd REAL,
+    latency_ms INTEGER NOT NULL,
+    ttfb_ms INTEGER,
+    downstream_endpoint TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_request_summaries_ts ON request_summaries (ts_ms);
+CREATE INDEX IF NOT EXISTS idx_request_summaries_key_model ON request_summaries (key_id, model);
@@ -0,0 +1,369 @@
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::audit_log::now_ms;
+use crate::capture::CapturePolicy;
+
+pub const ACCESS_LOG_FIELDS: &[&str] = &[
+    "ts_ms",
+    "request_id",
+    "route",
+    "method",
+    "mode",
+    "key_id",
+    "model",
+    "stream",
+    "status",
+    "error_type",
+    "input_tokens",
+    "output_tokens",
+    "cache_hit",
+    "service_tier",
+    "cost_usd",
+    "latency_ms",
+    "ttfb_ms",
+    "downstream_ttfb_ms",
+    "downstream_ms",
+    "downstream_endpoint",
+    "experiment",
+    "trace_id",
+    "span_id",
+];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RequestSummary {
+    pub ts_ms: u128,
+    pub request_id: String,
+    pub route: String,
+    pub method: String,
+    pub mode: String,
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub stream: bool,
+    pub status: u16,
+    pub error_type: Option<String>,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cache_hit: Option<bool>,
+    /// Downstream tier that served the request (`usage.service_tier`).
+    pub service_tier: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub latency_ms: u64,
+    pub ttfb_ms: Option<u64>,
+    /// Set when the first downstream attempt is sent; the downstream
+    /// timings below are measured from here.
+    #[serde(skip)]
+    pub downstream_start: Option<Instant>,
+    /// Downstream time to first byte: response headers, or the first event
+    /// when `downstream.first_byte_timeout_ms` is set.
+    pub downstream_ttfb_ms: Option<u64>,
+    /// Downstream time until the response body or stream completed.
+    pub downstream_ms: Option<u64>,
+    pub downstream_endpoint: Option<String>,
+    pub experiment: Option<String>,
+    /// Ids of the request's `ai.gateway.request` span (`/v1/messages`).
+    pub trace_id: Option<String>,
+    pub span_id: Option<String>,
+    /// Last user message, kept only while quality scoring is enabled. Full
+    /// text reaches the scorers only; `apply_capture` runs before the summary
+    /// is logged or retained.
+    #[serde(skip)]
+    pub prompt_text: Option<String>,
+    /// Response text, kept on the same terms as `prompt_text`.
+    #[serde(skip)]
+    pub output_text: Option<String>,
+}
+
+impl RequestSummary {
+    pub fn new(
+        request_id: &str,
+        route: &str,
+        method: &str,
+        mode: &str,
+        headers: &axum::http::HeaderMap,
+    ) -> Self {
+        Self {
+            ts_ms: now_ms(),
+            request_id: request_id.to_string(),
+            route: route.to_string(),
+            method: method.to_string(),
+            mode: mode.to_string(),
+            key_id: key_id_from_headers(headers),
+            model: None,
+            stream: false,
+            status: 200,
+            error_type: None,
+            input_tokens: None,
+            output_tokens: None,
+            cache_hit: None,
+            service_tier: None,
+            cost_usd: None,
+            latency_ms: 0,
+            ttfb_ms: None,
+            downstream_start: None,
+            downstream_ttfb_ms: None,
+            downstream_ms: None,
+            downstream_endpoint: None,
+            experiment: None,
+            trace_id: None,
+            span_id: None,
+            prompt_text: None,
+            output_text: None,
+        }
+    }
+
+    /// Marks the downstream call as sent; retries keep the first attempt's
+    /// start, so backoff counts as downstream time.
+    pub fn downstream_sent(&mut self) {
+        self.downstream_start.get_or_insert_with(Instant::now);
+    }
+
+    pub fn downstream_first_byte(&mut self) {
+        if let Some(start) = self.downstream_start {
+            self.downstream_ttfb_ms = Some(start.elapsed().as_millis() as u64);
+        }
+    }
+
+    pub fn downstream_done(&mut self) {
+        if let Some(start) = self.downstream_start
+            && self.downstream_ms.is_none()
+        {
+            self.downstream_ms = Some(start.elapsed().as_millis() as u64);
+        }
+    }
+
+    /// Runs the prompt and output text through `observability.capture`.
+    pub fn apply_capture(&mut self, capture: CapturePolicy) {
+        for text in [&mut self.prompt_text, &mut self.output_text] {
+            if let Some(text) = text.as_mut() {
+                *text = capture.apply(text);
+            }
+        }
+    }
+
+    /// End-to-end latency not spent waiting on the downstream.
+    pub fn gateway_overhead_ms(&self) -> Option<u64> {
+        Some(self.latency_ms.saturating_sub(self.downstream_ms?))
+    }
+
+    pub fn apply_anthropic_usage(&mut self, usage: &Value) {
+        if let Some(input) = usage.get("input_tokens").and_then(Value::as_u64) {
+            self.input_tokens = Some(input);
+        }
+        if let Some(output) = usage.get("output_tokens").and_then(Value::as_u64) {
+            self.output_tokens = Some(output);
+        }
+        if let Some(cache_read) = usage.get("cache_read_input_tokens").and_then(Value::as_u64) {
+            self.cache_hit = Some(cache_read > 0);
+        }
+        if let Some(tier) = usage.get("service_tier").and_then(Value::as_str) {
+            self.service_tier = Some(tier.to_string());
+        }
+    }
+
+    /// Picks usage and output text out of one Anthropic stream event's data.
+    pub fn apply_anthropic_sse_event(&mut self, data: &str) {
+        let Ok(event) = serde_json::from_str::<Value>(data.trim()) else {
+            return;
+        };
+        match event.get("type").and_then(Value::as_str) {
+            Some("message_start") => {
+                if let Some(usage) = event.get("message").and_then(|m| m.get("usage")) {
+                    self.apply_anthropic_usage(usage);
+                }
+            }
+            Some("content_block_delta") => {
+                if let Some(out) = self.output_text.as_mut()
+                    && let Some(text) = event.pointer("/delta/text").and_then(Value::as_str)
+                {
+                    out.push_str(text);
+                }
+            }
+            Some("message_delta") => {
+                if let Some(usage) = event.get("usage") {
+                    self.apply_anthropic_usage(usage);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AccessLogger {
+    sender: mpsc::Sender<RequestSummary>,
+}
+
+impl AccessLogger {
+    pub fn new(path: Option<String>, fields: Vec<String>) -> Self {
+        let (tx, mut rx) = mpsc::channel::<RequestSummary>(1024);
+        tokio::spawn(async move {
+            let mut sink: Box<dyn tokio::io::AsyncWrite + Unpin + Send> = match path.as_deref() {
+                Some(path) => match open_log_file(path).await {
+                    Ok(file) => Box::new(file),
+                    Err(err) => {
+                        tracing::error!("access log open error: {}", err);
+                        return;
+                    }
+                },
+                None => Box::new(tokio::io::stdout()),
+            };
+            while let Some(summary) = rx.recv().await {
+                let line = format_line(&summary, &fields);
+                if sink.write_all(line.as_bytes()).await.is_err() {
+                    tracing::error!("access log write error");
+                    continue;
+                }
+                let _ = sink.flush().await;
+            }
+        });
+        Self { sender: tx }
+    }
+
+    pub fn log(&self, summary: &RequestSummary) {
+        if self.sender.try_send(summary.clone()).is_err() {
+            tracing::warn!(request_id = %summary.request_id, "access log channel full, record dropped");
+        }
+    }
+}
+
+fn format_line(summary: &RequestSummary, fields: &[String]) -> String {
+    let mut out = serde_json::Map::new();
+    if let Ok(Value::Object(all)) = serde_json::to_value(summary) {
+        for field in fields {
+            if let Some(value) = all.get(field) {
+                out.insert(field.clone(), value.clone());
+            }
+        }
+    }
+    let mut line = Value::Object(out).to_string();
+    line.push('\n');
+    line
+}
+
+pub fn key_id_from_headers(headers: &axum::http::HeaderMap) -> Option<String> {
+    let key = headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| {
+            headers
+                .get("authorization")
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim_start_matches("Bearer ").trim())
+        })?;
+    if key.is_empty() {
+        return None;
+    }
+    let digest = Sha256::digest(key.as_bytes());
+    let hex: String = digest.iter().take(6).map(|b| format!("{:02x}", b)).collect();
+    Some(format!("key-{}", hex))
+}
+
+async fn open_log_file(path: &str) -> Result<tokio::fs::File, std::io::Error> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_line_keeps_selected_fields_only() {
+        let mut summary = RequestSummary::new(
+            "req-1",
+            "/v1/messages",
+            "POST",
+            "passthrough",
+            &axum::http::HeaderMap::new(),
+        );
+        summary.model = Some("claude".to_string());
+        summary.latency_ms = 12;
+        let line = format_line(&summary, &["request_id".to_string(), "latency_ms".to_string()]);
+        let parsed: Value = serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(parsed, serde_json::json!({"request_id": "req-1", "latency_ms": 12}));
+    }
+
+    #[test]
+    fn sse_lines_update_usage() {
+        let mut summary = RequestSummary::new(
+            "req-2",
+            "/v1/messages",
+            "POST",
+            "passthrough",
+            &axum::http::HeaderMap::new(),
+        );
+        summary.apply_anthropic_sse_event(
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":10,"output_tokens":1,"cache_read_input_tokens":4,"service_tier":"priority"}}}"#,
+        );
+        summary.apply_anthropic_sse_event(
+            r#"{"type":"message_delta","delta":{},"usage":{"output_tokens":25}}"#,
+        );
+        assert_eq!(summary.input_tokens, Some(10));
+        assert_eq!(summary.output_tokens, Some(25));
+        assert_eq!(summary.cache_hit, Some(true));
+        assert_eq!(summary.service_tier.as_deref(), Some("priority"));
+    }
+
+    #[test]
+    fn downstream_timings_split_out_gateway_overhead() {
+        let mut summary = RequestSummary::new(
+            "req-3",
+            "/v1/messages",
+            "POST",
+            "translate",
+            &axum::http::HeaderMap::new(),
+        );
+        summary.downstream_first_byte();
+        summary.downstream_done();
+        assert_eq!(summary.downstream_ms, None, "nothing sent yet");
+        assert_eq!(summary.gateway_overhead_ms(), None);
+
+        summary.downstream_start = Some(Instant::now() - std::time::Duration::from_millis(40));
+        summary.downstream_sent();
+        summary.downstream_first_byte();
+        summary.downstream_done();
+        let downstream_ms = summary.downstream_ms.expect("downstream time");
+        assert!(downstream_ms >= 40, "retries keep the first attempt's start");
+        assert!(summary.downstream_ttfb_ms.unwrap() <= downstream_ms);
+
+        summary.latency_ms = downstream_ms + 7;
+        assert_eq!(summary.gateway_overhead_ms(), Some(7));
+        summary.downstream_done();
+        assert_eq!(summary.downstream_ms, Some(downstream_ms), "first completion wins");
+    }
+
+    #[test]
+    fn capture_policy_applies_to_prompt_and_output_text() {
+        let mut summary = RequestSummary::new("req_1", "/v1/messages", "POST", "translate", &Default::default());
+        summary.prompt_text = Some("secret prompt".to_string());
+        summary.output_text = Some("secret output".to_string());
+        summary.apply_capture(CapturePolicy::None);
+        assert_eq!(summary.prompt_text.as_deref(), Some("[omitted]"));
+        assert_eq!(summary.output_text.as_deref(), Some("[omitted]"));
+
+        let mut unscored = RequestSummary::new("req_2", "/v1/messages", "POST", "translate", &Default::default());
+        unscored.apply_capture(CapturePolicy::None);
+        assert!(unscored.prompt_text.is_none());
+    }
+
+    #[test]
+    fn key_id_is_stable_and_opaque() {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert("x-api-key", "sk-secret".parse().unwrap());
+        let id = key_id_from_headers(&headers).expect("key id");
+        assert!(id.starts_with("key-"));
+        assert!(!id.contains("secret"));
+        assert_eq!(Some(id), key_id_from_headers(&headers));
+    }
+}
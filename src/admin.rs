@@ -0,0 +1,416 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
+    Json,
+};
+use std::collections::HashMap;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::audit_log::now_ms;
+use crate::error::AppError;
+use crate::models::AnthropicErrorResponse;
+use crate::state::AppState;
+use crate::tap::SseRedactor;
+use crate::usage::{aggregate, parse_window};
+
+#[utoipa::path(
+    get,
+    path = "/admin/usage",
+    tag = "admin",
+    params(("window" = Option<String>, Query, description = "e.g. `1h`, `24h` (default)")),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_usage(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let window = params.get("window").map(String::as_str).unwrap_or("24h");
+    let window_ms = parse_window(window).map_err(AppError::invalid_request)?;
+    let since_ms = now_ms().saturating_sub(window_ms);
+    let entries = state.usage.since(since_ms).await;
+    let groups = aggregate(&entries, &state.config.models.pricing);
+
+    let mut totals = serde_json::json!({
+        "requests": 0u64,
+        "errors": 0u64,
+        "input_tokens": 0u64,
+        "output_tokens": 0u64,
+        "cost_usd": 0.0,
+    });
+    for group in &groups {
+        for (field, value) in [
+            ("requests", group.requests),
+            ("errors", group.errors),
+            ("input_tokens", group.input_tokens),
+            ("output_tokens", group.output_tokens),
+        ] {
+            totals[field] = (totals[field].as_u64().unwrap_or(0) + value).into();
+        }
+        totals["cost_usd"] = (totals["cost_usd"].as_f64().unwrap_or(0.0) + group.cost_usd).into();
+    }
+
+    Ok(Json(serde_json::json!({
+        "window": window,
+        "since_ms": since_ms,
+        "totals": totals,
+        "groups": groups,
+    }))
+    .into_response())
+}
+
+/// Mirrors the SSE events of an in-flight streaming request. The tap is
+/// read-only and closes when the original stream finishes.
+#[utoipa::path(
+    get,
+    path = "/admin/tap/{request_id}",
+    tag = "admin",
+    params(("request_id" = String, Path)),
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = String),
+        (status = 404, body = AnthropicErrorResponse),
+    )
+)]
+pub async fn get_tap(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(request_id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let Some(mut events) = state.tap.subscribe(&request_id) else {
+        return Err(AppError::not_found(format!(
+            "no in-flight stream for request_id: {}",
+            request_id
+        )));
+    };
+    let mut redactor = SseRedactor::new(state.config.capture_policy());
+    let (tx, rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(64);
+    tokio::spawn(async move {
+        loop {
+            let chunk = match events.recv().await {
+                Ok(chunk) => match redactor.push(&chunk) {
+                    Some(events) => Bytes::from(events),
+                    None => continue,
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    redactor.reset();
+                    Bytes::from(format!(": tap lagged, {} chunks skipped\n\n", skipped))
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if tx.send(Ok(chunk)).await.is_err() {
+                return;
+            }
+        }
+    });
+    let body = axum::body::Body::from_stream(ReceiverStream::new(rx));
+    let mut response = (StatusCode::OK, body).into_response();
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    Ok(response)
+}
+
+/// Re-serves a stored streaming transcript byte for byte (event by event
+/// when it was redacted), pacing chunks by their recorded offsets divided by
+/// `speed` (`0` sends them at once).
+#[utoipa::path(
+    get,
+    path = "/admin/transcripts/{request_id}",
+    tag = "admin",
+    params(
+        ("request_id" = String, Path),
+        ("speed" = Option<f64>, Query, description = "Playback speed 0.01..=100, default 1; 0 disables pacing"),
+    ),
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = String),
+        (status = 404, body = AnthropicErrorResponse),
+    )
+)]
+pub async fn get_transcript(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(request_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let speed = match params.get("speed") {
+        Some(speed) => speed
+            .parse::<f64>()
+            .ok()
+            .filter(|speed| *speed == 0.0 || (0.01..=100.0).contains(speed))
+            .ok_or_else(|| AppError::invalid_request(format!("invalid speed: {}", speed)))?,
+        None => 1.0,
+    };
+    let transcript = match state.transcripts.as_ref() {
+        Some(transcripts) => transcripts.load(&request_id).await.map_err(AppError::api_error)?,
+        None => None,
+    };
+    let Some(transcript) = transcript else {
+        return Err(AppError::not_found(format!(
+            "no transcript for request_id: {}",
+            request_id
+        )));
+    };
+    let content_type = transcript
+        .content_type
+        .as_deref()
+        .and_then(|ct| HeaderValue::from_str(ct).ok())
+        .unwrap_or_else(|| HeaderValue::from_static("text/event-stream"));
+    let (tx, rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(64);
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now();
+        for chunk in transcript.chunks {
+            if speed > 0.0 {
+                let offset = std::time::Duration::from_millis(chunk.offset_ms).div_f64(speed);
+                tokio::time::sleep_until(start + offset).await;
+            }
+            if tx.send(Ok(Bytes::from(chunk.data))).await.is_err() {
+                return;
+            }
+        }
+    });
+    let body = axum::body::Body::from_stream(ReceiverStream::new(rx));
+    let mut response = (StatusCode::OK, body).into_response();
+    response.headers_mut().insert(CONTENT_TYPE, content_type);
+    Ok(response)
+}
+
+/// Lists in-flight `/v1/messages` requests, oldest first.
+#[utoipa::path(
+    get,
+    path = "/admin/inflight",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_inflight(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    Ok(Json(serde_json::json!({ "data": state.inflight_requests.list() })).into_response())
+}
+
+/// Cancels an in-flight request: the downstream call is aborted and a
+/// streaming client receives a final `request_cancelled` error event.
+#[utoipa::path(
+    delete,
+    path = "/admin/inflight/{request_id}",
+    tag = "admin",
+    params(("request_id" = String, Path)),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn cancel_inflight(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(request_id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    if !state.inflight_requests.cancel(&request_id) {
+        return Err(AppError::not_found(format!(
+            "no in-flight request for request_id: {}",
+            request_id
+        )));
+    }
+    tracing::warn!(request_id = %request_id, "in-flight request cancelled by admin");
+    Ok(Json(serde_json::json!({ "request_id": request_id, "cancelled": true })).into_response())
+}
+
+/// Lists `downstream.api_keys` by id with their runtime state.
+#[utoipa::path(
+    get,
+    path = "/admin/downstream-keys",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_downstream_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    Ok(Json(serde_json::json!({ "data": state.downstream_keys.list() })).into_response())
+}
+
+/// Takes a downstream key out of rotation (e.g. a compromised one) until it
+/// is enabled again or the gateway restarts.
+#[utoipa::path(
+    post,
+    path = "/admin/downstream-keys/{id}/disable",
+    tag = "admin",
+    params(("id" = String, Path)),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn disable_downstream_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    set_downstream_key(&state, &headers, &id, false)
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/downstream-keys/{id}/enable",
+    tag = "admin",
+    params(("id" = String, Path)),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn enable_downstream_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    set_downstream_key(&state, &headers, &id, true)
+}
+
+fn set_downstream_key(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: &str,
+    enabled: bool,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(state, headers)?;
+    if !state.downstream_keys.set_enabled(id, enabled) {
+        return Err(AppError::not_found(format!("no downstream key with id: {}", id)));
+    }
+    tracing::warn!(key = %id, enabled, "downstream key state changed by admin");
+    Ok(Json(serde_json::json!({ "id": id, "enabled": enabled })).into_response())
+}
+
+/// Lists client key ids flagged for `debug_capture`.
+#[utoipa::path(
+    get,
+    path = "/admin/debug-keys",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_debug_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let keys = state.debug_capture.as_ref().map(|debug| debug.keys()).unwrap_or_default();
+    Ok(Json(serde_json::json!({ "data": keys })).into_response())
+}
+
+/// Captures every following request from a client key (its `key-…` id from
+/// the access log) into the debug file, until unflagged or restart.
+#[utoipa::path(
+    post,
+    path = "/admin/debug-keys/{key_id}",
+    tag = "admin",
+    params(("key_id" = String, Path)),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn flag_debug_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(key_id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    set_debug_key(&state, &headers, &key_id, true)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/debug-keys/{key_id}",
+    tag = "admin",
+    params(("key_id" = String, Path)),
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, body = serde_json::Value),
+        (status = 401, body = AnthropicErrorResponse),
+        (status = 404, body = AnthropicErrorResponse),
+    )
+)]
+pub async fn unflag_debug_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(key_id): Path<String>,
+) -> Result<axum::response::Response, AppError> {
+    set_debug_key(&state, &headers, &key_id, false)
+}
+
+fn set_debug_key(
+    state: &AppState,
+    headers: &HeaderMap,
+    key_id: &str,
+    flagged: bool,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(state, headers)?;
+    let Some(debug) = state.debug_capture.as_ref() else {
+        return Err(AppError::not_found("debug_capture is disabled"));
+    };
+    if !debug.set_key(key_id, flagged) && !flagged {
+        return Err(AppError::not_found(format!("key is not flagged: {}", key_id)));
+    }
+    tracing::warn!(key = %key_id, flagged, "debug capture flag changed by admin");
+    Ok(Json(serde_json::json!({ "key_id": key_id, "flagged": flagged })).into_response())
+}
+
+/// Drops every pooled downstream connection, e.g. after a provider's DNS
+/// change; requests in flight are not interrupted.
+#[utoipa::path(
+    post,
+    path = "/admin/downstream-pool/flush",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn flush_downstream_pool(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let generation = state.clients.recycle().map_err(AppError::api_error)?;
+    tracing::warn!(generation, "downstream connection pools flushed by admin");
+    Ok(Json(serde_json::json!({ "flushed": true, "generation": generation })).into_response())
+}
+
+/// Route layer for endpoints that are admin-only as a whole, such as the
+/// prompt registry.
+pub async fn admin_auth(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, request.headers())?;
+    Ok(next.run(request).await)
+}
+
+pub fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let Some(expected) = state.config.admin.token.as_deref() else {
+        return Err(AppError::unauthorized("admin api is disabled"));
+    };
+    let provided = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(AppError::unauthorized("invalid admin token"));
+    }
+    Ok(())
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
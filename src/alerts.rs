@@ -0,0 +1,223 @@
+use opentelemetry::metrics::Counter;
+use opentelemetry::KeyValue;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::access_log::RequestSummary;
+use crate::audit_log::now_ms;
+use crate::config::{AlertNotifier, AlertRule, AlertsConfig};
+use crate::usage::{is_error, parse_window, UsageStore};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl AlertStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            AlertStatus::Firing => "firing",
+            AlertStatus::Resolved => "resolved",
+        }
+    }
+}
+
+/// Evaluates alert rules on a timer and notifies on state changes: once when
+/// a rule starts breaching its threshold and once when it recovers.
+pub fn spawn_alerts(config: AlertsConfig, usage: UsageStore, client: reqwest::Client) {
+    tokio::spawn(async move {
+        let notifications = opentelemetry::global::meter("llm-gateway")
+            .u64_counter("ai.gateway.alerts.notifications")
+            .with_description("Alert notifications by rule and status")
+            .build();
+        let mut firing = HashSet::new();
+        let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs));
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let now = now_ms();
+            for rule in &config.rules {
+                let window_ms = parse_window(&rule.window).unwrap_or(300_000);
+                let entries = usage.recent(now.saturating_sub(window_ms));
+                let value = evaluate(rule, &entries);
+                let Some(status) = transition(&mut firing, rule, value) else {
+                    continue;
+                };
+                tracing::warn!(
+                    alert = %rule.name,
+                    status = status.as_str(),
+                    value = value.unwrap_or_default(),
+                    threshold = rule.threshold,
+                    "alert {}",
+                    status.as_str()
+                );
+                notify(&client, &config.notifiers, &notifications, rule, status, value).await;
+            }
+        }
+    });
+}
+
+/// Current value of the rule's metric, or `None` when the window holds too
+/// few requests to judge a rate or percentile.
+fn evaluate(rule: &AlertRule, entries: &[RequestSummary]) -> Option<f64> {
+    let entries: Vec<&RequestSummary> = entries
+        .iter()
+        .filter(|s| rule.model.is_none() || s.model == rule.model)
+        .collect();
+    match rule.metric.as_str() {
+        "spend_usd" => Some(entries.iter().filter_map(|s| s.cost_usd).sum()),
+        _ if (entries.len() as u64) < rule.min_requests.max(1) => None,
+        "error_rate" => {
+            let errors = entries.iter().filter(|s| is_error(s)).count();
+            Some(errors as f64 * 100.0 / entries.len() as f64)
+        }
+        "p95_latency_ms" => {
+            let mut latencies: Vec<u64> = entries.iter().map(|s| s.latency_ms).collect();
+            latencies.sort_unstable();
+            let rank = (latencies.len() as f64 * 0.95).ceil() as usize;
+            Some(latencies[rank.saturating_sub(1)] as f64)
+        }
+        _ => None,
+    }
+}
+
+fn transition(firing: &mut HashSet<String>, rule: &AlertRule, value: Option<f64>) -> Option<AlertStatus> {
+    let breached = value.is_some_and(|v| v > rule.threshold);
+    match (breached, firing.contains(&rule.name)) {
+        (true, false) => {
+            firing.insert(rule.name.clone());
+            Some(AlertStatus::Firing)
+        }
+        (false, true) => {
+            firing.remove(&rule.name);
+            Some(AlertStatus::Resolved)
+        }
+        _ => None,
+    }
+}
+
+async fn notify(
+    client: &reqwest::Client,
+    notifiers: &[AlertNotifier],
+    notifications: &Counter<u64>,
+    rule: &AlertRule,
+    status: AlertStatus,
+    value: Option<f64>,
+) {
+    notifications.add(
+        1,
+        &[
+            KeyValue::new("rule", rule.name.clone()),
+            KeyValue::new("status", status.as_str()),
+        ],
+    );
+    for notifier in notifiers {
+        let body = match notifier.kind.as_str() {
+            "slack" => json!({ "text": slack_text(rule, status, value) }),
+            _ => webhook_body(rule, status, value),
+        };
+        let result = client
+            .post(&notifier.url)
+            .json(&body)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(err) = result {
+            tracing::warn!(alert = %rule.name, "alert notification failed: {}", err);
+        }
+    }
+}
+
+fn webhook_body(rule: &AlertRule, status: AlertStatus, value: Option<f64>) -> Value {
+    json!({
+        "alert": rule.name,
+        "status": status.as_str(),
+        "metric": rule.metric,
+        "value": value,
+        "threshold": rule.threshold,
+        "window": rule.window,
+        "model": rule.model,
+        "ts_ms": now_ms() as u64,
+    })
+}
+
+fn slack_text(rule: &AlertRule, status: AlertStatus, value: Option<f64>) -> String {
+    let scope = rule
+        .model
+        .as_deref()
+        .map(|m| format!(" (model {})", m))
+        .unwrap_or_default();
+    match (status, value) {
+        (AlertStatus::Firing, Some(value)) => format!(
+            ":rotating_light: [llm-gateway] {} firing{}: {} = {:.2} > {} over {}",
+            rule.name, scope, rule.metric, value, rule.threshold, rule.window
+        ),
+        _ => format!(
+            ":white_check_mark: [llm-gateway] {} resolved{}: {} back within {}",
+            rule.name, scope, rule.metric, rule.threshold
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(metric: &str, threshold: f64) -> AlertRule {
+        AlertRule {
+            name: metric.to_string(),
+            metric: metric.to_string(),
+            threshold,
+            window: "5m".to_string(),
+            model: None,
+            min_requests: 2,
+        }
+    }
+
+    fn summary(model: &str, status: u16, latency_ms: u64, cost_usd: f64) -> RequestSummary {
+        let mut summary = RequestSummary::new(
+            "req",
+            "/v1/messages",
+            "POST",
+            "translate",
+            &axum::http::HeaderMap::new(),
+        );
+        summary.model = Some(model.to_string());
+        summary.status = status;
+        summary.latency_ms = latency_ms;
+        summary.cost_usd = Some(cost_usd);
+        summary
+    }
+
+    #[test]
+    fn evaluates_rate_latency_and_spend() {
+        let entries = vec![
+            summary("gpt-4o", 200, 100, 0.5),
+            summary("gpt-4o", 502, 900, 0.0),
+            summary("kimi-k2.5", 200, 300, 1.0),
+            summary("kimi-k2.5", 200, 200, 1.0),
+        ];
+        assert_eq!(evaluate(&rule("error_rate", 10.0), &entries), Some(25.0));
+        assert_eq!(evaluate(&rule("p95_latency_ms", 500.0), &entries), Some(900.0));
+        assert_eq!(evaluate(&rule("spend_usd", 1.0), &entries), Some(2.5));
+
+        let mut scoped = rule("error_rate", 10.0);
+        scoped.model = Some("kimi-k2.5".to_string());
+        assert_eq!(evaluate(&scoped, &entries), Some(0.0));
+        assert_eq!(evaluate(&scoped, &entries[..1]), None);
+    }
+
+    #[test]
+    fn notifies_only_on_state_changes() {
+        let rule = rule("error_rate", 10.0);
+        let mut firing = HashSet::new();
+        assert_eq!(transition(&mut firing, &rule, Some(5.0)), None);
+        assert_eq!(transition(&mut firing, &rule, Some(50.0)), Some(AlertStatus::Firing));
+        assert_eq!(transition(&mut firing, &rule, Some(60.0)), None);
+        assert_eq!(transition(&mut firing, &rule, None), Some(AlertStatus::Resolved));
+        assert!(slack_text(&rule, AlertStatus::Firing, Some(50.0)).contains("50.00 > 10"));
+    }
+}
@@ -1,63 +1,376 @@
+use opentelemetry::metrics::Counter;
+use opentelemetry::KeyValue;
 use serde::Serialize;
 use serde_json::Value;
-use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::io::AsyncWriteExt;
-use tokio::sync::mpsc;
+use tokio::sync::{Notify, Semaphore};
+
+use crate::audit_s3::write_s3;
+use crate::capture::CapturePolicy;
+use crate::config::{AuditOverflowConfig, S3AuditConfig};
+use crate::pg_store::{write_audit_records, PgStore};
+use crate::routing::RouteDecision;
+use crate::tap::redact_sse_chunk;
+use crate::tracing_otlp::TraceIds;
 
 #[derive(Clone)]
 pub struct AuditLogger {
-    sender: mpsc::Sender<AuditLogRecord>,
+    sender: Arc<AuditSender>,
+    overflow: AuditOverflowConfig,
+    capture: CapturePolicy,
+    spill_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl AuditLogger {
-    pub fn new(base_path: String, max_file_bytes: u64) -> Result<Self, String> {
-        let (tx, mut rx) = mpsc::channel::<AuditLogRecord>(256);
-        tokio::spawn(async move {
-            let mut current_path = build_log_path(&base_path);
-            let mut file = match open_log_file(&current_path).await {
-                Ok(file) => file,
-                Err(err) => {
-                    tracing::error!("audit log open error: {}", err);
-                    return;
+    pub fn new(
+        base_path: String,
+        max_file_bytes: u64,
+        overflow: AuditOverflowConfig,
+        capture: CapturePolicy,
+    ) -> Result<Self, String> {
+        Ok(Self::with_writer("file", overflow, capture, move |queue| {
+            write_file(base_path.clone(), max_file_bytes, queue)
+        }))
+    }
+
+    pub fn postgres(store: PgStore, overflow: AuditOverflowConfig, capture: CapturePolicy) -> Self {
+        Self::with_writer("postgres", overflow, capture, move |queue| {
+            write_audit_records(store.clone(), queue)
+        })
+    }
+
+    pub fn s3(
+        config: S3AuditConfig,
+        max_object_bytes: u64,
+        overflow: AuditOverflowConfig,
+        capture: CapturePolicy,
+        client: reqwest::Client,
+    ) -> Self {
+        Self::with_writer("s3", overflow, capture, move |queue| {
+            write_s3(config.clone(), max_object_bytes, client.clone(), queue)
+        })
+    }
+
+    fn with_writer<W, F>(
+        sink: &'static str,
+        overflow: AuditOverflowConfig,
+        capture: CapturePolicy,
+        writer: W,
+    ) -> Self
+    where
+        W: Fn(Arc<AuditQueue>) -> F + Send + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let queue = Arc::new(AuditQueue::new(overflow.queue_capacity));
+        supervise(sink, queue.clone(), writer);
+        Self {
+            sender: Arc::new(AuditSender { queue }),
+            overflow,
+            capture,
+            spill_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    pub async fn push(&self, mut record: AuditLogRecord) {
+        record.request.body = self.capture.apply_value(record.request.body);
+        record.response.body = self.capture.apply_value(record.response.body);
+        for sse in [&mut record.response.downstream_sse, &mut record.response.client_sse] {
+            if let Some(text) = sse.as_mut() {
+                *text = redact_sse_chunk(text.as_bytes(), self.capture);
+            }
+        }
+        let record = Arc::new(record);
+        let queue = &self.sender.queue;
+        match self.overflow.policy.as_str() {
+            "drop_oldest" => {
+                if queue.push_evicting(record).is_some() {
+                    queue.record_dropped("queue_full");
+                }
+            }
+            "spill" => {
+                if let Some(record) = queue.try_push(record) {
+                    self.spill(record).await;
                 }
+            }
+            _ => {
+                let wait = Duration::from_millis(self.overflow.block_timeout_ms);
+                if tokio::time::timeout(wait, queue.push(record)).await.is_err() {
+                    queue.record_dropped("timeout");
+                }
+            }
+        }
+    }
+
+    async fn spill(&self, record: Arc<AuditLogRecord>) {
+        let Ok(mut line) = serde_json::to_vec(&*record) else {
+            return;
+        };
+        line.push(b'\n');
+        let _lock = self.spill_lock.lock().await;
+        let written = match open_log_file(&self.overflow.spill_path).await {
+            Ok(mut file) => match file.write_all(&line).await {
+                Ok(()) => file.flush().await,
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(err),
+        };
+        if let Err(err) = written {
+            tracing::error!("audit overflow spill error: {}", err);
+            self.sender.queue.record_dropped("spill_failed");
+        }
+    }
+}
+
+/// Closes the queue once the last `AuditLogger` clone is dropped, so the
+/// writer drains what is left and exits.
+struct AuditSender {
+    queue: Arc<AuditQueue>,
+}
+
+impl Drop for AuditSender {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
+}
+
+/// Bounded record queue between `AuditLogger::push` and the sink writer.
+/// Shared rather than an mpsc channel so `drop_oldest` can evict from the
+/// front and a restarted writer picks up the records its predecessor left,
+/// including the ones it had received but not yet committed.
+pub struct AuditQueue {
+    records: Mutex<VecDeque<Arc<AuditLogRecord>>>,
+    /// Records handed to the writer since its last `commit`, shared with it
+    /// rather than copied so requeueing never duplicates bodies.
+    uncommitted: Mutex<Vec<Arc<AuditLogRecord>>>,
+    /// One permit per free slot; taken on push and returned on pop.
+    slots: Semaphore,
+    /// Requeued records that found no free slot; their pops repay this
+    /// instead of returning a permit. Only changed under the `records` lock.
+    borrowed: AtomicUsize,
+    ready: Notify,
+    closed: AtomicBool,
+    dropped: Counter<u64>,
+}
+
+impl AuditQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            uncommitted: Mutex::new(Vec::new()),
+            slots: Semaphore::new(capacity),
+            borrowed: AtomicUsize::new(0),
+            ready: Notify::new(),
+            closed: AtomicBool::new(false),
+            dropped: opentelemetry::global::meter("llm-gateway")
+                .u64_counter("ai.gateway.audit_dropped_records")
+                .with_description("Audit records dropped because the writer queue was full")
+                .build(),
+        }
+    }
+
+    fn record_dropped(&self, reason: &'static str) {
+        self.dropped.add(1, &[KeyValue::new("reason", reason)]);
+    }
+
+    async fn push(&self, record: Arc<AuditLogRecord>) {
+        if let Ok(permit) = self.slots.acquire().await {
+            permit.forget();
+            self.records.lock().unwrap().push_back(record);
+            self.ready.notify_one();
+        }
+    }
+
+    /// Pushes `record` if there is room; hands it back if the queue is full.
+    fn try_push(&self, record: Arc<AuditLogRecord>) -> Option<Arc<AuditLogRecord>> {
+        match self.slots.try_acquire() {
+            Ok(permit) => {
+                permit.forget();
+                self.records.lock().unwrap().push_back(record);
+                self.ready.notify_one();
+                None
+            }
+            Err(_) => Some(record),
+        }
+    }
+
+    /// Pushes `record`, evicting and returning the oldest one if the queue
+    /// is full.
+    fn push_evicting(&self, record: Arc<AuditLogRecord>) -> Option<Arc<AuditLogRecord>> {
+        let mut records = self.records.lock().unwrap();
+        let evicted = match self.slots.try_acquire() {
+            Ok(permit) => {
+                permit.forget();
+                None
+            }
+            Err(_) => records.pop_front(),
+        };
+        records.push_back(record);
+        drop(records);
+        self.ready.notify_one();
+        evicted
+    }
+
+    fn pop(&self) -> Option<Arc<AuditLogRecord>> {
+        let mut records = self.records.lock().unwrap();
+        let record = records.pop_front()?;
+        if self.borrowed.load(Ordering::Relaxed) > 0 {
+            self.borrowed.fetch_sub(1, Ordering::Relaxed);
+        } else {
+            self.slots.add_permits(1);
+        }
+        drop(records);
+        self.uncommitted.lock().unwrap().push(record.clone());
+        Some(record)
+    }
+
+    /// Called by the writer once everything it has received so far is
+    /// written, spilled or deliberately given up on.
+    pub fn commit(&self) {
+        self.uncommitted.lock().unwrap().clear();
+    }
+
+    /// Puts the records a failed writer received but never committed back at
+    /// the front, in order, so its successor writes them first.
+    fn requeue_uncommitted(&self) -> usize {
+        let uncommitted = std::mem::take(&mut *self.uncommitted.lock().unwrap());
+        let count = uncommitted.len();
+        let mut records = self.records.lock().unwrap();
+        for record in uncommitted.into_iter().rev() {
+            match self.slots.try_acquire() {
+                Ok(permit) => permit.forget(),
+                Err(_) => {
+                    self.borrowed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            records.push_front(record);
+        }
+        count
+    }
+
+    /// Next record, or `None` once the logger is gone and the queue drained.
+    pub async fn recv(&self) -> Option<Arc<AuditLogRecord>> {
+        loop {
+            if let Some(record) = self.pop() {
+                return Some(record);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return self.pop();
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Waits for one record, then takes whatever else is queued up to
+    /// `limit`; returns how many were appended to `buf`.
+    pub async fn recv_many(&self, buf: &mut Vec<Arc<AuditLogRecord>>, limit: usize) -> usize {
+        let Some(first) = self.recv().await else {
+            return 0;
+        };
+        buf.push(first);
+        let mut count = 1;
+        while count < limit {
+            let Some(record) = self.pop() else {
+                break;
             };
-            let mut current_size = file
-                .metadata()
-                .await
-                .map(|m| m.len())
-                .unwrap_or(0);
-            while let Some(record) = rx.recv().await {
-                if let Ok(line) = serde_json::to_string(&record) {
-                    let projected = current_size + line.len() as u64 + 1;
-                    if projected > max_file_bytes {
-                        current_path = build_log_path(&base_path);
-                        match open_log_file(&current_path).await {
-                            Ok(new_file) => {
-                                file = new_file;
-                                current_size = 0;
-                            }
-                            Err(err) => {
-                                tracing::error!("audit log rotate error: {}", err);
-                            }
-                        }
-                    }
-                    if file.write_all(line.as_bytes()).await.is_err() {
-                        tracing::error!("audit log write error");
-                        continue;
+            buf.push(record);
+            count += 1;
+        }
+        count
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.ready.notify_one();
+    }
+
+    fn is_drained(&self) -> bool {
+        self.closed.load(Ordering::Acquire) && self.records.lock().unwrap().is_empty()
+    }
+}
+
+const WRITER_RESTART_MIN: Duration = Duration::from_secs(1);
+const WRITER_RESTART_MAX: Duration = Duration::from_secs(60);
+
+/// Runs the sink writer and restarts it, with backoff, when it panics or
+/// exits while the logger is still open. Records stay queued in between, and
+/// the ones the writer took without committing are queued again.
+fn supervise<W, F>(sink: &'static str, queue: Arc<AuditQueue>, writer: W)
+where
+    W: Fn(Arc<AuditQueue>) -> F + Send + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = WRITER_RESTART_MIN;
+        loop {
+            let started = Instant::now();
+            let result = tokio::spawn(writer(queue.clone())).await;
+            let requeued = queue.requeue_uncommitted();
+            if requeued > 0 {
+                tracing::warn!("audit {} writer stopped with {} uncommitted records, requeued", sink, requeued);
+            }
+            if queue.is_drained() {
+                return;
+            }
+            if started.elapsed() > WRITER_RESTART_MAX {
+                backoff = WRITER_RESTART_MIN;
+            }
+            match result {
+                Err(err) if err.is_panic() => {
+                    tracing::error!("audit {} writer panicked, restarting in {:?}", sink, backoff)
+                }
+                _ => tracing::error!("audit {} writer exited, restarting in {:?}", sink, backoff),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(WRITER_RESTART_MAX);
+        }
+    });
+}
+
+async fn write_file(base_path: String, max_file_bytes: u64, queue: Arc<AuditQueue>) {
+    let mut current_path = build_log_path(&base_path);
+    let mut file = match open_log_file(&current_path).await {
+        Ok(file) => file,
+        Err(err) => {
+            tracing::error!("audit log open error: {}", err);
+            return;
+        }
+    };
+    let mut current_size = file
+        .metadata()
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    while let Some(record) = queue.recv().await {
+        if let Ok(line) = serde_json::to_string(&*record) {
+            let projected = current_size + line.len() as u64 + 1;
+            if projected > max_file_bytes {
+                current_path = build_log_path(&base_path);
+                match open_log_file(&current_path).await {
+                    Ok(new_file) => {
+                        file = new_file;
+                        current_size = 0;
                     }
-                    if file.write_all(b"\n").await.is_err() {
-                        tracing::error!("audit log write error");
+                    Err(err) => {
+                        tracing::error!("audit log rotate error: {}", err);
                     }
-                    current_size += line.len() as u64 + 1;
                 }
             }
-        });
-        Ok(Self { sender: tx })
-    }
-
-    pub async fn push(&self, record: AuditLogRecord) {
-        let _ = self.sender.send(record).await;
+            if file.write_all(line.as_bytes()).await.is_err() {
+                tracing::error!("audit log write error");
+                queue.commit();
+                continue;
+            }
+            if file.write_all(b"\n").await.is_err() {
+                tracing::error!("audit log write error");
+            }
+            current_size += line.len() as u64 + 1;
+        }
+        queue.commit();
     }
 }
 
@@ -74,6 +387,27 @@ pub struct AuditContext {
 }
 
 impl AuditContext {
+    pub fn with_experiment(mut self, experiment: Option<String>) -> Self {
+        self.meta.experiment = experiment;
+        self
+    }
+
+    pub fn with_routing(mut self, routing: Option<RouteDecision>) -> Self {
+        self.meta.routing = routing;
+        self
+    }
+
+    pub fn with_trace(mut self, trace: &TraceIds) -> Self {
+        self.meta.trace_id = Some(trace.trace_id.to_string());
+        self.meta.span_id = Some(trace.span_id.to_string());
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.meta.warnings = warnings;
+        self
+    }
+
     pub fn finish(
         self,
         status: u16,
@@ -94,16 +428,26 @@ impl AuditContext {
                 headers: self.request_headers,
                 body: self.request_body,
             },
+            downstream: None,
             response: AuditResponse {
                 status,
                 headers: response_headers,
                 body: response_body,
+                downstream_sse: None,
+                client_sse: None,
             },
             meta: AuditMeta {
                 model: self.meta.model,
                 stream: self.meta.stream,
+                experiment: self.meta.experiment,
+                routing: self.meta.routing,
+                warnings: self.meta.warnings,
+                trace_id: self.meta.trace_id,
+                span_id: self.meta.span_id,
                 body_truncated,
                 body_parse_error,
+                error: self.meta.error,
+                debug: self.meta.debug,
             },
         }
     }
@@ -118,29 +462,149 @@ pub struct AuditLogRecord {
     pub mode: String,
     pub method: String,
     pub request: AuditMessage,
+    /// What a translate request actually exchanged with the downstream;
+    /// only kept in debug capture records.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downstream: Option<AuditDownstream>,
     pub response: AuditResponse,
     pub meta: AuditMeta,
 }
 
+impl AuditLogRecord {
+    /// Attaches raw SSE captures; a capture cut off at `max_body_bytes`
+    /// marks the record truncated.
+    pub fn with_stream_sse(
+        mut self,
+        downstream: Option<&SseCapture>,
+        client: Option<&SseCapture>,
+    ) -> Self {
+        for capture in downstream.iter().chain(client.iter()) {
+            self.meta.body_truncated |= capture.truncated;
+        }
+        self.response.downstream_sse = downstream.map(SseCapture::text);
+        self.response.client_sse = client.map(SseCapture::text);
+        self
+    }
+
+    pub fn with_downstream(mut self, downstream: AuditDownstream) -> Self {
+        self.downstream = Some(downstream);
+        self
+    }
+}
+
+/// A copy of raw SSE bytes for the audit record. Like the passthrough body,
+/// it stops at the first chunk that would exceed `max_bytes`.
+#[derive(Debug)]
+pub struct SseCapture {
+    buf: Vec<u8>,
+    max_bytes: usize,
+    truncated: bool,
+}
+
+impl SseCapture {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max_bytes,
+            truncated: false,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        if self.truncated || self.buf.len() + bytes.len() > self.max_bytes {
+            self.truncated = true;
+        } else {
+            self.buf.extend_from_slice(bytes);
+        }
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.buf).into_owned()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+/// One side of a stream as kept for the audit log and, for a request in
+/// debug capture, for the debug file; each copy stops at its own
+/// `max_body_bytes`.
+#[derive(Debug, Default)]
+pub struct SseCaptures {
+    pub audit: Option<SseCapture>,
+    pub debug: Option<SseCapture>,
+}
+
+impl SseCaptures {
+    pub fn push(&mut self, bytes: &[u8]) {
+        for capture in self.audit.iter_mut().chain(self.debug.iter_mut()) {
+            capture.push(bytes);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.audit.is_none() && self.debug.is_none()
+    }
+}
+
 #[derive(Clone, Serialize)]
 pub struct AuditMessage {
     pub headers: HashMap<String, String>,
     pub body: Value,
 }
 
+#[derive(Clone, Serialize)]
+pub struct AuditDownstream {
+    pub url: String,
+    pub request_headers: HashMap<String, String>,
+    pub request_body: Value,
+    pub response_headers: HashMap<String, String>,
+    pub response_body: Value,
+}
+
 #[derive(Clone, Serialize)]
 pub struct AuditResponse {
     pub status: u16,
     pub headers: HashMap<String, String>,
     pub body: Value,
+    /// Raw translate-stream SSE as the downstream sent it and as the client
+    /// received it, per `audit_log.stream_sse`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downstream_sse: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_sse: Option<String>,
 }
 
 #[derive(Clone, Serialize)]
 pub struct AuditMeta {
     pub model: Option<String>,
     pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RouteDecision>,
+    /// Lossy translation events, as sent in `x-gateway-warnings`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
     pub body_truncated: bool,
     pub body_parse_error: bool,
+    /// Why a response that had already started failed, e.g. a panicked
+    /// stream task.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Set for requests in `debug_capture`, whose record also goes to the
+    /// debug file.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub debug: bool,
 }
 
 pub fn headers_to_map(headers: &axum::http::HeaderMap) -> HashMap<String, String> {
@@ -182,3 +646,179 @@ async fn open_log_file(path: &str) -> Result<tokio::fs::File, std::io::Error> {
         .open(path)
         .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn record(id: &str) -> AuditLogRecord {
+        AuditContext {
+            ts_start_ms: 0,
+            request_id: id.to_string(),
+            route: "/v1/messages".to_string(),
+            mode: "translate".to_string(),
+            method: "POST".to_string(),
+            request_headers: HashMap::new(),
+            request_body: Value::Null,
+            meta: AuditMeta {
+                model: None,
+                stream: None,
+                experiment: None,
+                routing: None,
+                warnings: Vec::new(),
+                trace_id: None,
+                span_id: None,
+                body_truncated: false,
+                body_parse_error: false,
+                error: None,
+                debug: false,
+            },
+        }
+        .finish(200, HashMap::new(), Value::Null, false, false, 0)
+    }
+
+    fn stalled_logger(overflow: AuditOverflowConfig) -> AuditLogger {
+        AuditLogger::with_writer("test", overflow, CapturePolicy::Full, |_| {
+            std::future::pending()
+        })
+    }
+
+    fn queued_ids(logger: &AuditLogger) -> Vec<String> {
+        let mut ids = Vec::new();
+        while let Some(record) = logger.sender.queue.pop() {
+            ids.push(record.request_id.clone());
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_evicts_the_front_of_a_full_queue() {
+        let logger = stalled_logger(AuditOverflowConfig {
+            policy: "drop_oldest".to_string(),
+            queue_capacity: 2,
+            ..AuditOverflowConfig::default()
+        });
+        for id in ["a", "b", "c"] {
+            logger.push(record(id)).await;
+        }
+        assert_eq!(queued_ids(&logger), ["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn block_gives_up_after_the_timeout() {
+        let logger = stalled_logger(AuditOverflowConfig {
+            queue_capacity: 1,
+            block_timeout_ms: 20,
+            ..AuditOverflowConfig::default()
+        });
+        logger.push(record("a")).await;
+        let started = Instant::now();
+        logger.push(record("b")).await;
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(queued_ids(&logger), ["a"]);
+    }
+
+    #[tokio::test]
+    async fn spill_appends_overflow_records_to_the_spill_file() {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-audit-{}", std::process::id()));
+        let path = dir.join("overflow.jsonl");
+        let logger = stalled_logger(AuditOverflowConfig {
+            policy: "spill".to_string(),
+            queue_capacity: 1,
+            spill_path: path.to_string_lossy().into_owned(),
+            ..AuditOverflowConfig::default()
+        });
+        logger.push(record("a")).await;
+        logger.push(record("b")).await;
+        let spilled = std::fs::read_to_string(&path).expect("spill file");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(spilled.lines().count(), 1);
+        assert!(spilled.contains("\"request_id\":\"b\""));
+        assert_eq!(queued_ids(&logger), ["a"]);
+    }
+
+    #[tokio::test]
+    async fn panicked_writer_is_restarted_with_the_queue_intact() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let writer_starts = starts.clone();
+        let logger = AuditLogger::with_writer(
+            "test",
+            AuditOverflowConfig {
+                queue_capacity: 2,
+                ..AuditOverflowConfig::default()
+            },
+            CapturePolicy::Full,
+            move |queue| {
+                let first = writer_starts.fetch_add(1, Ordering::SeqCst) == 0;
+                let tx = tx.clone();
+                async move {
+                    if first {
+                        // Buffers two records, like an S3 part, then fails.
+                        let mut buffered = Vec::new();
+                        queue.recv_many(&mut buffered, 2).await;
+                        panic!("writer failed holding {} records", buffered.len());
+                    }
+                    while let Some(record) = queue.recv().await {
+                        let _ = tx.send(record.request_id.clone());
+                        queue.commit();
+                    }
+                }
+            },
+        );
+        for id in ["a", "b"] {
+            logger.push(record(id)).await;
+        }
+        // Queued while the first writer is down; the requeued records keep
+        // their place ahead of it even though the queue is over capacity.
+        logger.push(record("c")).await;
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            let id = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("writer restarted")
+                .unwrap();
+            received.push(id);
+        }
+        assert_eq!(received, vec!["a", "b", "c"]);
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stream_sse_capture_stops_at_the_cap() {
+        let mut downstream = SseCapture::new(16);
+        downstream.push(b"data: {}\n\n");
+        downstream.push(b"data: [DONE]\n\n");
+        let mut client = SseCapture::new(64);
+        client.push(b"event: ping\n\n");
+
+        let captured = record("a").with_stream_sse(Some(&downstream), Some(&client));
+        assert_eq!(captured.response.downstream_sse.as_deref(), Some("data: {}\n\n"));
+        assert_eq!(captured.response.client_sse.as_deref(), Some("event: ping\n\n"));
+        assert!(captured.meta.body_truncated);
+
+        let value = serde_json::to_value(record("b").with_stream_sse(None, None)).unwrap();
+        assert!(value["response"].get("downstream_sse").is_none());
+        assert!(!value["meta"]["body_truncated"].as_bool().unwrap());
+    }
+
+    #[tokio::test]
+    async fn capture_policy_applies_to_stream_sse() {
+        let logger = AuditLogger::with_writer("test", AuditOverflowConfig::default(), CapturePolicy::None, |_| {
+            std::future::pending()
+        });
+        let mut downstream = SseCapture::new(64);
+        downstream.push(b"data: {\"content\":\"secret\"}\n\n");
+        let mut client = SseCapture::new(64);
+        client.push(b"event: content_block_delta\ndata: {\"text\":\"secret\"}\n\n");
+        logger.push(record("a").with_stream_sse(Some(&downstream), Some(&client))).await;
+
+        let queued = logger.sender.queue.pop().expect("queued");
+        assert_eq!(queued.response.downstream_sse.as_deref(), Some("data: [omitted]\n\n"));
+        assert_eq!(
+            queued.response.client_sse.as_deref(),
+            Some("event: content_block_delta\ndata: [omitted]\n\n")
+        );
+    }
+}
@@ -0,0 +1,468 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::audit_log::{now_ms, AuditQueue};
+use crate::config::S3AuditConfig;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Sealed objects waiting for the uploader; past this the writer waits.
+const UPLOAD_QUEUE_OBJECTS: usize = 16;
+
+/// Buffers audit records into JSONL and gzips the buffer on rotation. Sealed
+/// objects go to a separate upload task, so a slow or failing store never
+/// holds up the queue; objects that fail to upload are spilled to
+/// `spill_dir` and retried after the next successful upload. Records are
+/// committed once their object is handed to the uploader, which finishes
+/// what it was given even if this writer is restarted.
+pub async fn write_s3(
+    config: S3AuditConfig,
+    max_object_bytes: u64,
+    client: reqwest::Client,
+    queue: Arc<AuditQueue>,
+) {
+    let uploader = Arc::new(S3Uploader { config, client });
+    let (objects, sealed) = mpsc::channel(UPLOAD_QUEUE_OBJECTS);
+    let upload = tokio::spawn(uploader.clone().run(sealed));
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut seq: u64 = 0;
+    let mut ticker =
+        tokio::time::interval(Duration::from_secs(uploader.config.flush_interval_secs));
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            record = queue.recv() => {
+                let Some(record) = record else {
+                    uploader.rotate(&mut buffer, &mut seq, &objects).await;
+                    queue.commit();
+                    drop(objects);
+                    let _ = upload.await;
+                    return;
+                };
+                if let Ok(line) = serde_json::to_string(&*record) {
+                    buffer.extend_from_slice(line.as_bytes());
+                    buffer.push(b'\n');
+                }
+                if buffer.len() as u64 >= max_object_bytes {
+                    uploader.rotate(&mut buffer, &mut seq, &objects).await;
+                    queue.commit();
+                }
+            }
+            _ = ticker.tick() => {
+                uploader.rotate(&mut buffer, &mut seq, &objects).await;
+                queue.commit();
+            }
+        }
+    }
+}
+
+struct S3Uploader {
+    config: S3AuditConfig,
+    client: reqwest::Client,
+}
+
+impl S3Uploader {
+    /// Seals the buffer into a gzipped object and queues it for upload.
+    async fn rotate(&self, buffer: &mut Vec<u8>, seq: &mut u64, objects: &mpsc::Sender<(String, Vec<u8>)>) {
+        if buffer.is_empty() {
+            return;
+        }
+        *seq += 1;
+        let name = format!("audit-{}-{}.jsonl.gz", now_ms(), seq);
+        let body = match gzip(buffer) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!("audit s3 compress error: {}", err);
+                buffer.clear();
+                return;
+            }
+        };
+        buffer.clear();
+        if let Err(mpsc::error::SendError((name, body))) = objects.send((name, body)).await {
+            tracing::error!("audit s3 uploader stopped, spilling {}", name);
+            self.spill(&name, &body).await;
+        }
+    }
+
+    async fn run(self: Arc<Self>, mut sealed: mpsc::Receiver<(String, Vec<u8>)>) {
+        while let Some((name, body)) = sealed.recv().await {
+            self.upload(&name, body).await;
+        }
+    }
+
+    async fn upload(&self, name: &str, body: Vec<u8>) {
+        if let Err(err) = self.put_object(name, body.clone()).await {
+            tracing::error!("audit s3 upload error, spilling {}: {}", name, err);
+            self.spill(name, &body).await;
+            return;
+        }
+        self.retry_spilled().await;
+    }
+
+    async fn spill(&self, name: &str, body: &[u8]) {
+        let dir = Path::new(&self.config.spill_dir);
+        if let Err(err) = tokio::fs::create_dir_all(dir).await {
+            tracing::error!("audit s3 spill dir error: {}", err);
+            return;
+        }
+        if let Err(err) = tokio::fs::write(dir.join(name), body).await {
+            tracing::error!("audit s3 spill write error: {}", err);
+        }
+    }
+
+    async fn retry_spilled(&self) {
+        let Ok(mut entries) = tokio::fs::read_dir(&self.config.spill_dir).await else {
+            return;
+        };
+        let mut pending: Vec<PathBuf> = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "gz") {
+                pending.push(path);
+            }
+        }
+        pending.sort();
+        for path in pending {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_string) else {
+                continue;
+            };
+            let Ok(body) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            if self.put_object(&name, body).await.is_err() {
+                return;
+            }
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+    }
+
+    async fn put_object(&self, name: &str, body: Vec<u8>) -> Result<(), String> {
+        let key = format!("{}{}", self.config.prefix, name);
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let url = Url::parse(&format!("{}/{}/{}", endpoint, self.config.bucket, key))
+            .map_err(|e| format!("invalid s3 url: {}", e))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            _ => return Err("invalid s3 endpoint host".to_string()),
+        };
+        let amz_date = amz_date(now_ms());
+        let payload_hash = hex(&Sha256::digest(&body));
+        let authorization = sign_put(
+            &self.config,
+            &host,
+            url.path(),
+            &amz_date,
+            &payload_hash,
+        );
+        let resp = self
+            .client
+            .put(url)
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("authorization", authorization)
+            .header("content-type", "application/gzip")
+            .body(body)
+            .timeout(Duration::from_secs(30))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("{} {}", status, text));
+        }
+        Ok(())
+    }
+}
+
+fn sign_put(
+    config: &S3AuditConfig,
+    host: &str,
+    path: &str,
+    amz_date: &str,
+    payload_hash: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        path, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = signing_key(&config.secret_access_key, date, &config.region, "s3");
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        config.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let k_date = hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let k_region = hmac_sha256(&k_date, region.as_bytes());
+    let k_service = hmac_sha256(&k_region, service.as_bytes());
+    hmac_sha256(&k_service, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Formats unix milliseconds as an ISO8601 basic timestamp (`20240131T235959Z`).
+fn amz_date(ts_ms: u128) -> String {
+    let secs = (ts_ms / 1000) as i64;
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit_log::{AuditContext, AuditLogRecord, AuditLogger, AuditMeta};
+    use crate::capture::CapturePolicy;
+    use crate::config::AuditOverflowConfig;
+    use axum::body::Bytes;
+    use axum::extract::{Path as UrlPath, State};
+    use axum::http::StatusCode;
+    use axum::{routing::put, Router};
+    use flate2::read::GzDecoder;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::sync::Mutex;
+    use tokio::sync::Semaphore;
+
+    /// Every PUT the mock store saw: object key, gunzipped body and status.
+    type Puts = Arc<Mutex<Vec<(String, String, u16)>>>;
+
+    struct MockStore {
+        puts: Puts,
+        /// Each PUT waits for a permit, so a test can hold uploads back.
+        gate: Arc<Semaphore>,
+        /// 1-based PUT numbers answered with 500.
+        fail: &'static [usize],
+    }
+
+    async fn spawn_store(gate: usize, fail: &'static [usize]) -> Option<(String, Puts, Arc<Semaphore>)> {
+        let puts: Puts = Default::default();
+        let gate = Arc::new(Semaphore::new(gate));
+        let store = Arc::new(MockStore {
+            puts: puts.clone(),
+            gate: gate.clone(),
+            fail,
+        });
+        let app = Router::new()
+            .route(
+                "/audit/{*key}",
+                put(|State(store): State<Arc<MockStore>>, UrlPath(key): UrlPath<String>, body: Bytes| async move {
+                    store.gate.acquire().await.unwrap().forget();
+                    let mut text = String::new();
+                    GzDecoder::new(&body[..]).read_to_string(&mut text).unwrap();
+                    let mut puts = store.puts.lock().unwrap();
+                    let status = if store.fail.contains(&(puts.len() + 1)) { 500 } else { 200 };
+                    puts.push((key, text, status));
+                    StatusCode::from_u16(status).unwrap()
+                }),
+            )
+            .with_state(store);
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return None,
+            Err(err) => panic!("bind failed: {}", err),
+        };
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        Some((format!("http://{}", addr), puts, gate))
+    }
+
+    fn logger(endpoint: &str, spill_dir: &Path, overflow: AuditOverflowConfig) -> AuditLogger {
+        let config: S3AuditConfig = serde_yaml::from_str(&format!(
+            "endpoint: \"{}\"\nbucket: audit\naccess_key_id: test\nsecret_access_key: secret\nspill_dir: \"{}\"\n",
+            endpoint,
+            spill_dir.display()
+        ))
+        .expect("config");
+        // One record per object.
+        AuditLogger::s3(config, 1, overflow, CapturePolicy::Full, reqwest::Client::new())
+    }
+
+    fn record(id: &str) -> AuditLogRecord {
+        AuditContext {
+            ts_start_ms: 0,
+            request_id: id.to_string(),
+            route: "/v1/messages".to_string(),
+            mode: "translate".to_string(),
+            method: "POST".to_string(),
+            request_headers: HashMap::new(),
+            request_body: Value::Null,
+            meta: AuditMeta {
+                model: None,
+                stream: None,
+                experiment: None,
+                routing: None,
+                warnings: Vec::new(),
+                trace_id: None,
+                span_id: None,
+                body_truncated: false,
+                body_parse_error: false,
+                error: None,
+                debug: false,
+            },
+        }
+        .finish(200, HashMap::new(), Value::Null, false, false, 0)
+    }
+
+    async fn wait_for(puts: &Puts, count: usize) {
+        for _ in 0..150 {
+            if puts.lock().unwrap().len() >= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    fn request_id(line: &str) -> String {
+        let record: Value = serde_json::from_str(line.trim()).expect("jsonl");
+        record["request_id"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn a_stalled_upload_does_not_hold_up_the_queue() {
+        let Some((endpoint, puts, gate)) = spawn_store(0, &[]).await else {
+            return;
+        };
+        let spill_dir = std::env::temp_dir().join(format!("llm-gateway-s3-stall-{}", std::process::id()));
+        // A writer stuck on the upload would leave the one queue slot taken
+        // and the third push would time out and be dropped.
+        let logger = logger(
+            &endpoint,
+            &spill_dir,
+            AuditOverflowConfig {
+                queue_capacity: 1,
+                block_timeout_ms: 200,
+                ..AuditOverflowConfig::default()
+            },
+        );
+        for id in ["a", "b", "c"] {
+            logger.push(record(id)).await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(puts.lock().unwrap().is_empty(), "uploads are still held back");
+
+        gate.add_permits(16);
+        wait_for(&puts, 3).await;
+        let puts = puts.lock().unwrap();
+        let ids: Vec<String> = puts.iter().map(|(_, body, _)| request_id(body)).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert!(puts.iter().all(|(key, _, status)| key.starts_with("audit/audit-") && *status == 200));
+        let _ = std::fs::remove_dir_all(&spill_dir);
+    }
+
+    #[tokio::test]
+    async fn failed_upload_is_spilled_and_retried_after_the_next_upload() {
+        let Some((endpoint, puts, _)) = spawn_store(16, &[1]).await else {
+            return;
+        };
+        let spill_dir = std::env::temp_dir().join(format!("llm-gateway-s3-spill-{}", std::process::id()));
+        let logger = logger(&endpoint, &spill_dir, AuditOverflowConfig::default());
+
+        logger.push(record("a")).await;
+        wait_for(&puts, 1).await;
+        for _ in 0..50 {
+            if std::fs::read_dir(&spill_dir).is_ok_and(|mut entries| entries.next().is_some()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 1);
+
+        logger.push(record("b")).await;
+        wait_for(&puts, 3).await;
+        let puts = puts.lock().unwrap().clone();
+        assert_eq!(puts.len(), 3);
+        assert_eq!((request_id(&puts[0].1), puts[0].2), ("a".to_string(), 500));
+        assert_eq!((request_id(&puts[1].1), puts[1].2), ("b".to_string(), 200));
+        assert_eq!((request_id(&puts[2].1), puts[2].2), ("a".to_string(), 200));
+        assert_eq!(puts[0].0, puts[2].0, "the spilled object keeps its name");
+        for _ in 0..50 {
+            if std::fs::read_dir(&spill_dir).unwrap().count() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&spill_dir);
+    }
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn amz_date_formats_utc() {
+        assert_eq!(amz_date(0), "19700101T000000Z");
+        assert_eq!(amz_date(1_709_251_199_000), "20240229T235959Z");
+    }
+}
@@ -0,0 +1,328 @@
+use axum::body::Bytes;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use opentelemetry::metrics::{Counter, UpDownCounter};
+use opentelemetry::KeyValue;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::config::StreamingConfig;
+
+pub type ChunkSender = mpsc::Sender<Result<Bytes, Infallible>>;
+pub type ClientStream = BoxStream<'static, Result<Bytes, std::io::Error>>;
+
+struct BackpressureMetrics {
+    buffered_bytes: UpDownCounter<i64>,
+    disconnects: Counter<u64>,
+}
+
+fn metrics() -> &'static BackpressureMetrics {
+    static METRICS: OnceLock<BackpressureMetrics> = OnceLock::new();
+    METRICS.get_or_init(|| {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        BackpressureMetrics {
+            buffered_bytes: meter
+                .i64_up_down_counter("ai.gateway.stream.buffered_bytes")
+                .with_unit("By")
+                .with_description("Stream bytes held for slow clients beyond the channel")
+                .build(),
+            disconnects: meter
+                .u64_counter("ai.gateway.stream.slow_client_disconnects")
+                .with_description("Streams cut off because the client fell too far behind")
+                .build(),
+        }
+    })
+}
+
+/// Channel between a stream producer and the client body. The producer
+/// writes chunks into the sender; the returned stream delivers them under the
+/// configured slow-client policy and ends with an error if the client is
+/// disconnected for falling behind.
+pub fn client_channel(config: &StreamingConfig, request_id: &str) -> (ChunkSender, ClientStream) {
+    let (tx, rx) = mpsc::channel::<Result<Bytes, Infallible>>(config.channel_capacity);
+    if config.slow_client == "block" {
+        let body = ReceiverStream::new(rx).map(|chunk| chunk.map_err(|never| match never {}));
+        return (tx, body.boxed());
+    }
+
+    let (client_tx, client_rx) = mpsc::channel(config.channel_capacity);
+    let (abort_tx, abort_rx) = watch::channel(false);
+    let overflow = Overflow::new(config, request_id);
+    tokio::spawn(relay(rx, client_tx, abort_tx, overflow));
+
+    let body = stream::unfold(
+        (ReceiverStream::new(client_rx), abort_rx, false),
+        |(mut rx, mut abort, done)| async move {
+            if done {
+                return None;
+            }
+            let next = tokio::select! {
+                biased;
+                Ok(_) = abort.wait_for(|aborted| *aborted) => None,
+                chunk = rx.next() => Some(chunk),
+            };
+            match next {
+                Some(chunk) => chunk.map(|chunk| (Ok(chunk), (rx, abort, false))),
+                None => Some((
+                    Err(std::io::Error::other("stream disconnected: client too slow")),
+                    (rx, abort, true),
+                )),
+            }
+        },
+    );
+    (tx, body.boxed())
+}
+
+/// Moves chunks from the producer to the client, parking whatever the client
+/// cannot take yet in the overflow buffer so downstream reads never stall.
+async fn relay(
+    mut upstream: mpsc::Receiver<Result<Bytes, Infallible>>,
+    client: mpsc::Sender<Bytes>,
+    abort: watch::Sender<bool>,
+    mut overflow: Overflow,
+) {
+    let mut upstream_open = true;
+    while upstream_open || !overflow.is_empty() {
+        tokio::select! {
+            chunk = upstream.recv(), if upstream_open => match chunk {
+                Some(Ok(bytes)) => {
+                    let bytes = if overflow.is_empty() {
+                        match client.try_send(bytes) {
+                            Ok(()) => continue,
+                            Err(mpsc::error::TrySendError::Full(bytes)) => bytes,
+                            Err(mpsc::error::TrySendError::Closed(_)) => return,
+                        }
+                    } else {
+                        bytes
+                    };
+                    if let Err(err) = overflow.push(bytes).await {
+                        tracing::warn!(
+                            request_id = %overflow.request_id,
+                            policy = overflow.policy(),
+                            "disconnecting slow stream client: {}",
+                            err
+                        );
+                        metrics()
+                            .disconnects
+                            .add(1, &[KeyValue::new("policy", overflow.policy())]);
+                        abort.send_replace(true);
+                        return;
+                    }
+                }
+                None => upstream_open = false,
+            },
+            permit = client.reserve(), if !overflow.is_empty() => {
+                let Ok(permit) = permit else {
+                    return;
+                };
+                match overflow.pop().await {
+                    Ok(bytes) => permit.send(bytes),
+                    Err(err) => {
+                        tracing::warn!(
+                            request_id = %overflow.request_id,
+                            "stream spill read failed: {}",
+                            err
+                        );
+                        abort.send_replace(true);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Chunks the client has not accepted yet: in memory for `disconnect`, in a
+/// per-request spill file for `spill`. Both are capped at `max_buffered_bytes`.
+struct Overflow {
+    request_id: String,
+    max_bytes: u64,
+    queued_bytes: u64,
+    memory: VecDeque<Bytes>,
+    spill_dir: Option<PathBuf>,
+    spill: Option<SpillFile>,
+}
+
+impl Overflow {
+    fn new(config: &StreamingConfig, request_id: &str) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            max_bytes: config.max_buffered_bytes,
+            queued_bytes: 0,
+            memory: VecDeque::new(),
+            spill_dir: (config.slow_client == "spill").then(|| PathBuf::from(&config.spill_dir)),
+            spill: None,
+        }
+    }
+
+    fn policy(&self) -> &'static str {
+        if self.spill_dir.is_some() { "spill" } else { "disconnect" }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.memory.is_empty() && self.spill.as_ref().is_none_or(|spill| spill.lengths.is_empty())
+    }
+
+    async fn push(&mut self, bytes: Bytes) -> Result<(), String> {
+        let len = bytes.len() as u64;
+        if self.queued_bytes + len > self.max_bytes {
+            return Err(format!("more than {} bytes buffered", self.max_bytes));
+        }
+        match &self.spill_dir {
+            Some(dir) => {
+                if self.spill.is_none() {
+                    self.spill = Some(
+                        SpillFile::create(dir, &self.request_id)
+                            .await
+                            .map_err(|e| format!("spill file: {}", e))?,
+                    );
+                }
+                if let Some(spill) = self.spill.as_mut() {
+                    spill.write(&bytes).await.map_err(|e| format!("spill write: {}", e))?;
+                }
+            }
+            None => self.memory.push_back(bytes),
+        }
+        self.queued_bytes += len;
+        metrics()
+            .buffered_bytes
+            .add(len as i64, &[KeyValue::new("policy", self.policy())]);
+        Ok(())
+    }
+
+    async fn pop(&mut self) -> Result<Bytes, String> {
+        let bytes = match self.spill.as_mut() {
+            Some(spill) => spill.read().await.map_err(|e| e.to_string())?,
+            None => self.memory.pop_front().unwrap_or_default(),
+        };
+        self.release(bytes.len() as u64);
+        Ok(bytes)
+    }
+
+    fn release(&mut self, len: u64) {
+        self.queued_bytes -= len;
+        metrics()
+            .buffered_bytes
+            .add(-(len as i64), &[KeyValue::new("policy", self.policy())]);
+    }
+}
+
+impl Drop for Overflow {
+    fn drop(&mut self) {
+        if self.queued_bytes > 0 {
+            self.release(self.queued_bytes);
+        }
+    }
+}
+
+/// Append-only spill file read back in order; rewound whenever it drains and
+/// removed when the stream ends.
+struct SpillFile {
+    path: PathBuf,
+    writer: tokio::fs::File,
+    reader: tokio::fs::File,
+    lengths: VecDeque<usize>,
+}
+
+impl SpillFile {
+    async fn create(dir: &std::path::Path, request_id: &str) -> Result<Self, std::io::Error> {
+        tokio::fs::create_dir_all(dir).await?;
+        let path = dir.join(format!("{}.spill", request_id));
+        let writer = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&path)
+            .await?;
+        let reader = tokio::fs::File::open(&path).await?;
+        Ok(Self {
+            path,
+            writer,
+            reader,
+            lengths: VecDeque::new(),
+        })
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), std::io::Error> {
+        self.writer.write_all(bytes).await?;
+        self.writer.flush().await?;
+        self.lengths.push_back(bytes.len());
+        Ok(())
+    }
+
+    async fn read(&mut self) -> Result<Bytes, std::io::Error> {
+        let len = self.lengths.pop_front().unwrap_or_default();
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf).await?;
+        if self.lengths.is_empty() {
+            self.writer.set_len(0).await?;
+            self.writer.rewind().await?;
+            self.reader.rewind().await?;
+        }
+        Ok(Bytes::from(buf))
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(slow_client: &str, max_buffered_bytes: u64, spill_dir: &str) -> StreamingConfig {
+        StreamingConfig {
+            channel_capacity: 1,
+            slow_client: slow_client.to_string(),
+            max_buffered_bytes,
+            spill_dir: spill_dir.to_string(),
+            coalesce: None,
+            max_event_bytes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn spill_delivers_chunks_in_order_and_removes_file() {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-spill-{}", std::process::id()));
+        let config = config("spill", 1024, dir.to_str().unwrap());
+        let (tx, body) = client_channel(&config, "req-spill");
+        for i in 0..20 {
+            tx.send(Ok(Bytes::from(format!("chunk-{};", i)))).await.unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(dir.join("req-spill.spill").exists());
+        drop(tx);
+
+        let chunks: Vec<Bytes> = body.map(|chunk| chunk.unwrap()).collect().await;
+        let text: String = chunks.iter().map(|c| String::from_utf8_lossy(c).into_owned()).collect();
+        let expected: String = (0..20).map(|i| format!("chunk-{};", i)).collect();
+        assert_eq!(text, expected);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!dir.join("req-spill.spill").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn disconnect_ends_body_with_error_past_the_cap() {
+        let (tx, mut body) = client_channel(&config("disconnect", 16, ""), "req-slow");
+        let mut closed = false;
+        for _ in 0..20 {
+            if tx.send(Ok(Bytes::from_static(b"0123456789"))).await.is_err() {
+                closed = true;
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert!(closed, "producer should observe the disconnect");
+        assert!(body.next().await.expect("error item").is_err());
+        assert!(body.next().await.is_none());
+    }
+}
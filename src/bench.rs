@@ -0,0 +1,295 @@
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::Response;
+use axum::routing::post;
+use axum::{Json, Router};
+use clap::Args;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::metrics::init_metrics_noop;
+use crate::provider::ProviderRegistry;
+use crate::server::{build_router, build_state};
+use crate::tracing_otlp::init_tracer_noop;
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// Total requests to send.
+    #[arg(long, default_value_t = 200)]
+    pub requests: usize,
+    /// Requests in flight at once.
+    #[arg(long, default_value_t = 16)]
+    pub concurrency: usize,
+    /// Send streaming requests.
+    #[arg(long)]
+    pub stream: bool,
+    /// Client model name; defaults to the first `model_map` entry.
+    #[arg(long)]
+    pub model: Option<String>,
+    /// Delay the mock downstream adds before responding.
+    #[arg(long, default_value_t = 0)]
+    pub downstream_latency_ms: u64,
+}
+
+/// Serves the configured gateway and a mock downstream on loopback ports,
+/// drives `/v1/messages` load through the gateway and prints latency
+/// percentiles. The state is built with no-op sinks (see `disable_sinks`) so
+/// the run stays in-process and writes nothing.
+pub async fn run(mut config: Config, args: BenchArgs) -> Result<(), String> {
+    if !matches!(config.forward_mode(), "passthrough" | "translate" | "rewrite") {
+        return Err(format!("bench supports passthrough, translate and rewrite, not {}", config.forward_mode()));
+    }
+    let latency = Duration::from_millis(args.downstream_latency_ms);
+    config.downstream.base_url = spawn(mock_downstream(latency)).await?;
+    disable_sinks(&mut config);
+
+    let inflight_count = Arc::new(AtomicU64::new(0));
+    let state = build_state(
+        &config,
+        init_metrics_noop(inflight_count.clone()),
+        inflight_count,
+        init_tracer_noop(config.observability.service_name.clone()),
+        ProviderRegistry::builtin(),
+    )
+    .await?;
+    let gateway = spawn(build_router(&config, state)?).await?;
+
+    let mut models: Vec<&String> = config.models.model_map.keys().collect();
+    models.sort();
+    let model = args
+        .model
+        .clone()
+        .or_else(|| models.first().map(|m| m.to_string()))
+        .unwrap_or_else(|| "claude-bench".to_string());
+    let payload = json!({
+        "model": model,
+        "max_tokens": 16,
+        "stream": args.stream,
+        "messages": [{"role": "user", "content": "ping"}],
+    });
+    let client = reqwest::Client::new();
+    let url = format!("{}/v1/messages", gateway);
+
+    let start = Instant::now();
+    let results: Vec<(bool, u64)> = futures_util::stream::iter(0..args.requests)
+        .map(|_| {
+            let request = client.post(&url).json(&payload);
+            async move {
+                let start = Instant::now();
+                let ok = match request.send().await {
+                    Ok(resp) => {
+                        let status = resp.status();
+                        let body = resp.bytes().await.unwrap_or_default();
+                        status.is_success()
+                            && (!args.stream || body.windows(12).any(|w| w == b"message_stop"))
+                    }
+                    Err(_) => false,
+                };
+                (ok, start.elapsed().as_millis() as u64)
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+    let elapsed = start.elapsed();
+
+    let mut latencies: Vec<u64> = results.iter().map(|(_, ms)| *ms).collect();
+    latencies.sort_unstable();
+    let errors = results.iter().filter(|(ok, _)| !ok).count();
+    println!(
+        "{} requests ({}, concurrency {}) in {:.2}s: {:.1} req/s, {} errors",
+        results.len(),
+        if args.stream { "stream" } else { "non-stream" },
+        args.concurrency,
+        elapsed.as_secs_f64(),
+        results.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        errors
+    );
+    println!(
+        "latency ms: p50 {} p95 {} p99 {} max {}",
+        percentile(&latencies, 0.50),
+        percentile(&latencies, 0.95),
+        percentile(&latencies, 0.99),
+        latencies.last().copied().unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Turns off every sink that writes outside the process: audit, access and
+/// debug logs, transcripts, persisted usage and prompts, billing, shadow and
+/// scoring. Metrics and traces already use no-op providers.
+fn disable_sinks(config: &mut Config) {
+    config.shadow = None;
+    config.scoring = None;
+    config.billing = None;
+    config.postgres = None;
+    config.usage.sink = None;
+    config.usage.sqlite_path = None;
+    config.prompts.sqlite_path = None;
+    let observability = &mut config.observability;
+    observability.audit_log.enabled = false;
+    observability.access_log.enabled = false;
+    observability.debug_capture.enabled = false;
+    observability.transcripts.enabled = false;
+}
+
+/// Serves `app` on a random loopback port and returns its base URL.
+pub async fn spawn(app: Router) -> Result<String, String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("bind error: {}", e))?;
+    let addr = listener.local_addr().map_err(|e| format!("bind error: {}", e))?;
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    Ok(format!("http://{}", addr))
+}
+
+/// Answers both the Anthropic and the OpenAI chat endpoint with a one-word
+/// reply, as JSON or SSE depending on the request's `stream` flag.
+fn mock_downstream(latency: Duration) -> Router {
+    Router::new()
+        .route(
+            "/v1/messages",
+            post(move |Json(body): Json<Value>| async move {
+                tokio::time::sleep(latency).await;
+                mock_response(&body, anthropic_json, anthropic_sse)
+            }),
+        )
+        .route(
+            "/v1/chat/completions",
+            post(move |Json(body): Json<Value>| async move {
+                tokio::time::sleep(latency).await;
+                mock_response(&body, openai_json, openai_sse)
+            }),
+        )
+}
+
+fn mock_response(body: &Value, json: fn(&str) -> Value, sse: fn(&str) -> String) -> Response {
+    let model = body.get("model").and_then(Value::as_str).unwrap_or("mock");
+    let (content_type, body) = if body.get("stream").and_then(Value::as_bool) == Some(true) {
+        ("text/event-stream", sse(model))
+    } else {
+        ("application/json", json(model).to_string())
+    };
+    Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap_or_default()
+}
+
+fn anthropic_json(model: &str) -> Value {
+    json!({
+        "id": "msg_bench",
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": [{"type": "text", "text": "pong"}],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": {"input_tokens": 8, "output_tokens": 1}
+    })
+}
+
+fn anthropic_sse(model: &str) -> String {
+    let mut message = anthropic_json(model);
+    message["content"] = json!([]);
+    message["stop_reason"] = Value::Null;
+    [
+        ("message_start", json!({"type": "message_start", "message": message})),
+        (
+            "content_block_start",
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        ),
+        (
+            "content_block_delta",
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "pong"}}),
+        ),
+        ("content_block_stop", json!({"type": "content_block_stop", "index": 0})),
+        (
+            "message_delta",
+            json!({"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 1}}),
+        ),
+        ("message_stop", json!({"type": "message_stop"})),
+    ]
+    .iter()
+    .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+    .collect()
+}
+
+fn openai_json(model: &str) -> Value {
+    json!({
+        "id": "chatcmpl-bench",
+        "object": "chat.completion",
+        "model": model,
+        "choices": [{"index": 0, "message": {"role": "assistant", "content": "pong"}, "finish_reason": "stop"}],
+        "usage": {"prompt_tokens": 8, "completion_tokens": 1, "total_tokens": 9}
+    })
+}
+
+fn openai_sse(model: &str) -> String {
+    let chunks = [
+        json!({"id": "chatcmpl-bench", "model": model, "choices": [{"index": 0, "delta": {"role": "assistant", "content": "pong"}}]}),
+        json!({"id": "chatcmpl-bench", "model": model, "choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}),
+        json!({"id": "chatcmpl-bench", "model": model, "choices": [], "usage": {"prompt_tokens": 8, "completion_tokens": 1, "total_tokens": 9}}),
+    ];
+    let mut out: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+    out.push_str("data: [DONE]\n\n");
+    out
+}
+
+/// Nearest-rank percentile (`p` in 0..=1) of an ascending slice; 0 when empty.
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() as f64 * p).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse::SseParser;
+
+    #[test]
+    fn mock_streams_are_well_formed() {
+        let mut parser = SseParser::default();
+        let events = parser.push(anthropic_sse("claude").as_bytes());
+        assert_eq!(events.len(), 6);
+        assert_eq!(events.last().and_then(|e| e.event.as_deref()), Some("message_stop"));
+
+        let events = parser.push(openai_sse("gpt-4o").as_bytes());
+        assert_eq!(events.last().map(|e| e.data.as_str()), Some("[DONE]"));
+        assert_eq!(percentile(&[1, 2, 3, 4, 100], 0.95), 100);
+        assert_eq!(percentile(&[1, 2, 3, 4, 100], 0.50), 3);
+    }
+
+    #[tokio::test]
+    async fn bench_state_has_no_sinks() {
+        let mut config = Config::from_yaml(
+            "server: {}\ndownstream:\n  base_url: http://127.0.0.1:9/v1\nmodels: {}\nlimits: {}\nusage:\n  sqlite_path: ./data/usage.db\nbilling:\n  url: http://127.0.0.1:9/events\nobservability:\n  audit_log:\n    enabled: true\n    path: ./logs/audit.jsonl\n  access_log:\n    enabled: true\n  transcripts:\n    enabled: true\n",
+        )
+        .expect("config");
+        disable_sinks(&mut config);
+        let inflight_count = Arc::new(AtomicU64::new(0));
+        let state = build_state(
+            &config,
+            init_metrics_noop(inflight_count.clone()),
+            inflight_count,
+            init_tracer_noop(config.observability.service_name.clone()),
+            ProviderRegistry::builtin(),
+        )
+        .await
+        .expect("state");
+        assert!(state.audit_logger.is_none());
+        assert!(state.access_logger.is_none());
+        assert!(state.transcripts.is_none());
+        assert!(state.billing.is_none());
+        assert_eq!(config.usage_sink(), "memory");
+    }
+}
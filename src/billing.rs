@@ -0,0 +1,413 @@
+use opentelemetry::metrics::Counter;
+use opentelemetry::KeyValue;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+
+use crate::access_log::RequestSummary;
+use crate::audit_s3::civil_from_days;
+use crate::config::BillingConfig;
+use crate::ids;
+
+/// Usage of one request as reported to the billing system.
+#[derive(Clone, Debug, Serialize)]
+pub struct UsageEvent {
+    /// Idempotency key: assigned once when the event is queued and sent
+    /// unchanged on every retry, so the billing system can drop duplicates.
+    pub event_id: String,
+    pub request_id: String,
+    pub ts_ms: u128,
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub route: String,
+    pub stream: bool,
+    pub status: u16,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: Option<f64>,
+}
+
+impl UsageEvent {
+    /// `None` for requests that reported no token usage (errors before the
+    /// downstream answered, `/v1/models`, ...).
+    pub fn from_summary(summary: &RequestSummary) -> Option<Self> {
+        if summary.input_tokens.is_none() && summary.output_tokens.is_none() {
+            return None;
+        }
+        Some(Self {
+            event_id: ids::usage_event_id(),
+            request_id: summary.request_id.clone(),
+            ts_ms: summary.ts_ms,
+            key_id: summary.key_id.clone(),
+            model: summary.model.clone(),
+            route: summary.route.clone(),
+            stream: summary.stream,
+            status: summary.status,
+            input_tokens: summary.input_tokens.unwrap_or(0),
+            output_tokens: summary.output_tokens.unwrap_or(0),
+            cost_usd: summary.cost_usd,
+        })
+    }
+}
+
+/// Batches being retried at once; past this the consumer waits for one to
+/// finish and new events back up in the queue.
+const MAX_IN_FLIGHT_BATCHES: usize = 8;
+
+enum Command {
+    Event(UsageEvent),
+    /// Sends the pending batch, waits for every batch still being retried
+    /// and stops the consumer.
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// `billing`: queues usage events and posts them in batches of
+/// `batch_size` (or every `flush_interval_secs`) from a background task.
+/// Each batch is sent from its own task, so a failed batch is retried with
+/// exponential backoff without holding up intake, and dropped after
+/// `max_retries`; events arriving while the queue is full are dropped.
+#[derive(Clone)]
+pub struct BillingForwarder {
+    sender: mpsc::Sender<Command>,
+    events: Counter<u64>,
+}
+
+impl BillingForwarder {
+    pub fn new(config: BillingConfig, client: reqwest::Client) -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        let events = meter
+            .u64_counter("ai.gateway.billing.events")
+            .with_description("Usage events forwarded to billing by outcome")
+            .build();
+        let (tx, rx) = mpsc::channel(config.queue_capacity);
+        let sender = BatchSender {
+            config,
+            client,
+            events: events.clone(),
+        };
+        tokio::spawn(Arc::new(sender).run(rx));
+        Self { sender: tx, events }
+    }
+
+    pub fn emit(&self, summary: &RequestSummary) {
+        let Some(event) = UsageEvent::from_summary(summary) else {
+            return;
+        };
+        match self.sender.try_send(Command::Event(event)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!(request_id = %summary.request_id, "billing queue full, usage event dropped");
+                self.events.add(1, &[KeyValue::new("outcome", "queue_full")]);
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::warn!(request_id = %summary.request_id, "billing forwarder stopped, usage event dropped");
+                self.events.add(1, &[KeyValue::new("outcome", "dropped")]);
+            }
+        }
+    }
+
+    /// Delivers everything queued so far, including the batch that has not
+    /// reached `batch_size` yet and batches still being retried, then stops
+    /// forwarding. Later events are dropped.
+    pub async fn shutdown(&self) {
+        let (done, flushed) = oneshot::channel();
+        if self.sender.send(Command::Shutdown(done)).await.is_ok() {
+            let _ = flushed.await;
+        }
+    }
+}
+
+struct BatchSender {
+    config: BillingConfig,
+    client: reqwest::Client,
+    events: Counter<u64>,
+}
+
+impl BatchSender {
+    async fn run(self: Arc<Self>, mut rx: mpsc::Receiver<Command>) {
+        let mut batch: Vec<UsageEvent> = Vec::new();
+        let mut deliveries = JoinSet::new();
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.config.flush_interval_secs));
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                command = rx.recv() => match command {
+                    Some(Command::Event(event)) => {
+                        batch.push(event);
+                        if batch.len() >= self.config.batch_size {
+                            self.spawn_delivery(&mut deliveries, std::mem::take(&mut batch)).await;
+                        }
+                    }
+                    Some(Command::Shutdown(done)) => {
+                        self.spawn_delivery(&mut deliveries, std::mem::take(&mut batch)).await;
+                        deliveries.join_all().await;
+                        let _ = done.send(());
+                        return;
+                    }
+                    None => {
+                        self.spawn_delivery(&mut deliveries, std::mem::take(&mut batch)).await;
+                        deliveries.join_all().await;
+                        return;
+                    }
+                },
+                _ = ticker.tick() => {
+                    self.spawn_delivery(&mut deliveries, std::mem::take(&mut batch)).await;
+                }
+                Some(_) = deliveries.join_next(), if !deliveries.is_empty() => {}
+            }
+        }
+    }
+
+    async fn spawn_delivery(self: &Arc<Self>, deliveries: &mut JoinSet<()>, batch: Vec<UsageEvent>) {
+        if batch.is_empty() {
+            return;
+        }
+        while deliveries.len() >= MAX_IN_FLIGHT_BATCHES {
+            deliveries.join_next().await;
+        }
+        deliveries.spawn(self.clone().deliver(batch));
+    }
+
+    async fn deliver(self: Arc<Self>, batch: Vec<UsageEvent>) {
+        let body = batch_body(&self.config, &batch);
+        let count = batch.len() as u64;
+        let mut attempt = 0;
+        loop {
+            match self.post(&body).await {
+                Ok(()) => {
+                    self.events.add(count, &[KeyValue::new("outcome", "sent")]);
+                    return;
+                }
+                Err(err) if attempt < self.config.max_retries => {
+                    tracing::warn!(attempt = attempt + 1, "billing batch send failed, retrying: {}", err);
+                    let backoff = self.config.retry_backoff_ms.saturating_mul(1 << attempt.min(16));
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                    attempt += 1;
+                }
+                Err(err) => {
+                    tracing::error!(events = count, "billing batch dropped: {}", err);
+                    self.events.add(count, &[KeyValue::new("outcome", "dropped")]);
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn post(&self, body: &Value) -> Result<(), String> {
+        let content_type = match self.config.kind.as_str() {
+            "openmeter" => "application/cloudevents-batch+json",
+            _ => "application/json",
+        };
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("content-type", content_type)
+            .body(body.to_string())
+            .timeout(Duration::from_secs(10));
+        if let Some(api_key) = self.config.api_key.as_deref() {
+            request = request.bearer_auth(api_key);
+        }
+        let resp = request.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("{} {}", status, text));
+        }
+        Ok(())
+    }
+}
+
+/// `{"events": [...]}` for `webhook`; a CloudEvents batch with the key id
+/// as `subject` for `openmeter`.
+fn batch_body(config: &BillingConfig, batch: &[UsageEvent]) -> Value {
+    if config.kind != "openmeter" {
+        return json!({ "events": batch });
+    }
+    let events = batch
+        .iter()
+        .map(|event| {
+            json!({
+                "specversion": "1.0",
+                "id": event.event_id,
+                "source": config.source,
+                "type": config.event_type,
+                "subject": event.key_id.as_deref().unwrap_or("anonymous"),
+                "time": rfc3339(event.ts_ms),
+                "data": {
+                    "request_id": event.request_id,
+                    "model": event.model,
+                    "route": event.route,
+                    "stream": event.stream,
+                    "status": event.status,
+                    "input_tokens": event.input_tokens,
+                    "output_tokens": event.output_tokens,
+                    "total_tokens": event.input_tokens + event.output_tokens,
+                    "cost_usd": event.cost_usd,
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+    Value::Array(events)
+}
+
+fn rfc3339(ts_ms: u128) -> String {
+    let secs = (ts_ms / 1000) as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+        ts_ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::{routing::post, Router};
+    use std::sync::Mutex;
+
+    /// Every attempt the mock billing endpoint saw: content type, body and
+    /// whether it was accepted.
+    type Received = Arc<Mutex<Vec<(String, Value, bool)>>>;
+
+    /// Serves `/events`, failing the attempts whose 1-based number is in
+    /// `fail`; `None` when the sandbox forbids binding.
+    async fn spawn_billing(fail: &'static [usize]) -> Option<(String, Received)> {
+        let received: Received = Default::default();
+        let app = Router::new()
+            .route(
+                "/events",
+                post(move |State(received): State<Received>, headers: HeaderMap, body: String| async move {
+                    let mut received = received.lock().unwrap();
+                    let accepted = !fail.contains(&(received.len() + 1));
+                    let content_type = headers.get("content-type").unwrap().to_str().unwrap().to_string();
+                    received.push((content_type, serde_json::from_str(&body).unwrap(), accepted));
+                    if accepted {
+                        StatusCode::NO_CONTENT
+                    } else {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    }
+                }),
+            )
+            .with_state(received.clone());
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return None,
+            Err(err) => panic!("bind failed: {}", err),
+        };
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        Some((format!("http://{}/events", addr), received))
+    }
+
+    fn forwarder(yaml: &str, url: &str) -> BillingForwarder {
+        let config: BillingConfig = serde_yaml::from_str(&format!("url: {}\n{}", url, yaml)).expect("config");
+        BillingForwarder::new(config, reqwest::Client::new())
+    }
+
+    fn summary(id: &str, tokens: Option<u64>) -> RequestSummary {
+        let mut summary = RequestSummary::new(id, "/v1/messages", "POST", "translate", &HeaderMap::new());
+        summary.ts_ms = 1_709_251_199_250;
+        summary.model = Some("gpt-4o".to_string());
+        summary.input_tokens = tokens;
+        summary.output_tokens = tokens;
+        summary
+    }
+
+    async fn wait_for(received: &Received, attempts: usize) {
+        for _ in 0..100 {
+            if received.lock().unwrap().len() >= attempts {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_openmeter_events_and_retries_failed_sends() {
+        let Some((url, received)) = spawn_billing(&[1]).await else {
+            return;
+        };
+        let forwarder = forwarder("kind: openmeter\nbatch_size: 2\nretry_backoff_ms: 10\n", &url);
+        forwarder.emit(&summary("req-1", Some(10)));
+        forwarder.emit(&summary("req-err", None));
+        forwarder.emit(&summary("req-2", Some(5)));
+        wait_for(&received, 2).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2, "one failed attempt and one retry");
+        let (content_type, body, accepted) = &received[1];
+        assert!(accepted);
+        assert_eq!(content_type, "application/cloudevents-batch+json");
+        let events = body.as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0]["id"].as_str().unwrap().starts_with("evt_01"), "{}", events[0]);
+        assert_ne!(events[0]["id"], events[1]["id"]);
+        // The retry carries the same event ids, so the billing side can
+        // drop whatever the failed attempt already recorded.
+        assert_eq!(received[0].1, received[1].1);
+        assert_eq!(events[0]["subject"], "anonymous");
+        assert_eq!(events[0]["time"], "2024-02-29T23:59:59.250Z");
+        assert_eq!(events[0]["data"]["request_id"], "req-1");
+        assert_eq!(events[1]["data"]["total_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn retries_do_not_hold_up_later_batches() {
+        let Some((url, received)) = spawn_billing(&[1]).await else {
+            return;
+        };
+        let forwarder = forwarder("batch_size: 1\nretry_backoff_ms: 5000\n", &url);
+        forwarder.emit(&summary("req-1", Some(10)));
+        wait_for(&received, 1).await;
+        forwarder.emit(&summary("req-2", Some(5)));
+        wait_for(&received, 2).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2, "req-2 sent while req-1 waits to retry");
+        assert_eq!(received[0].1["events"][0]["request_id"], "req-1");
+        assert!(!received[0].2);
+        assert_eq!(received[1].1["events"][0]["request_id"], "req-2");
+        assert!(received[1].2);
+    }
+
+    #[tokio::test]
+    async fn shutdown_delivers_the_pending_batch() {
+        let Some((url, received)) = spawn_billing(&[]).await else {
+            return;
+        };
+        let forwarder = forwarder("batch_size: 100\nflush_interval_secs: 3600\n", &url);
+        forwarder.emit(&summary("req-1", Some(10)));
+        forwarder.emit(&summary("req-2", Some(5)));
+        forwarder.shutdown().await;
+
+        let events: Vec<Value> = received
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(_, body, _)| body["events"].as_array().unwrap().clone())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["request_id"], "req-2");
+        assert!(events[1]["event_id"].as_str().unwrap().starts_with("evt_01"));
+
+        // Stopped: later events are counted as dropped rather than queued.
+        forwarder.emit(&summary("req-3", Some(1)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+}
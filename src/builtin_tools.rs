@@ -0,0 +1,144 @@
+use serde_json::{json, Value};
+
+use crate::models::{AnthropicBuiltInTool, OpenAIFunctionDef, OpenAITool};
+
+/// The built-in tool family of a versioned `type`, e.g. `bash` for
+/// `bash_20250124`.
+pub fn family(tool_type: &str) -> Option<&'static str> {
+    ["computer", "text_editor", "bash"]
+        .into_iter()
+        .find(|family| tool_type.strip_prefix(family).is_some_and(|rest| rest.starts_with('_')))
+}
+
+/// `models.builtin_tools: map`: a function tool with the name the client's
+/// agent loop expects and a schema for the built-in tool's input. `None` for
+/// types outside the known families.
+pub fn to_function(tool: &AnthropicBuiltInTool) -> Option<OpenAITool> {
+    let (description, parameters) = match family(&tool.tool_type)? {
+        "computer" => (computer_description(tool), computer_schema()),
+        "text_editor" => (
+            "View, create and edit text files. `view` shows a file or directory, `create` writes \
+             `file_text` to a new file, `str_replace` replaces `old_str` with `new_str` exactly \
+             once, `insert` adds `new_str` after line `insert_line`."
+                .to_string(),
+            text_editor_schema(),
+        ),
+        _ => (
+            "Run a command in a persistent bash shell. Set `restart` to start a fresh shell.".to_string(),
+            bash_schema(),
+        ),
+    };
+    Some(OpenAITool {
+        tool_type: "function".to_string(),
+        function: OpenAIFunctionDef {
+            name: tool.name.clone(),
+            description: Some(description),
+            parameters,
+        },
+    })
+}
+
+fn computer_description(tool: &AnthropicBuiltInTool) -> String {
+    let mut description =
+        "Control the computer with mouse and keyboard actions and take screenshots.".to_string();
+    let option = |name: &str| tool.options.get(name).and_then(Value::as_u64);
+    if let (Some(width), Some(height)) = (option("display_width_px"), option("display_height_px")) {
+        description.push_str(&format!(" The display is {}x{} pixels.", width, height));
+    }
+    description
+}
+
+fn computer_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "action": {
+                "type": "string",
+                "enum": [
+                    "key", "hold_key", "type", "cursor_position", "mouse_move",
+                    "left_mouse_down", "left_mouse_up", "left_click", "left_click_drag",
+                    "right_click", "middle_click", "double_click", "triple_click",
+                    "scroll", "wait", "screenshot"
+                ]
+            },
+            "coordinate": {"type": "array", "items": {"type": "integer"}, "minItems": 2, "maxItems": 2},
+            "start_coordinate": {"type": "array", "items": {"type": "integer"}, "minItems": 2, "maxItems": 2},
+            "text": {"type": "string"},
+            "key": {"type": "string"},
+            "scroll_direction": {"type": "string", "enum": ["up", "down", "left", "right"]},
+            "scroll_amount": {"type": "integer"},
+            "duration": {"type": "number"}
+        },
+        "required": ["action"]
+    })
+}
+
+fn text_editor_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "command": {"type": "string", "enum": ["view", "create", "str_replace", "insert", "undo_edit"]},
+            "path": {"type": "string"},
+            "file_text": {"type": "string"},
+            "old_str": {"type": "string"},
+            "new_str": {"type": "string"},
+            "insert_line": {"type": "integer"},
+            "view_range": {"type": "array", "items": {"type": "integer"}, "minItems": 2, "maxItems": 2}
+        },
+        "required": ["command", "path"]
+    })
+}
+
+fn bash_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "command": {"type": "string"},
+            "restart": {"type": "boolean"}
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::AnthropicRequest;
+    use crate::translate::anthropic_to_openai;
+
+    #[test]
+    fn rejects_strips_or_maps_builtin_tools() {
+        let request = || -> AnthropicRequest {
+            serde_json::from_value(json!({
+                "model": "gpt-4o-mini",
+                "max_tokens": 16,
+                "tools": [
+                    {"type": "computer_20250124", "name": "computer", "display_width_px": 1280, "display_height_px": 800},
+                    {"type": "text_editor_20250429", "name": "str_replace_based_edit_tool"},
+                    {"type": "bash_20250124", "name": "bash"}
+                ],
+                "tool_choice": {"type": "auto"},
+                "messages": [{"role": "user", "content": "list files"}]
+            }))
+            .expect("parse")
+        };
+        let mut config = Config::from_yaml("server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\n")
+            .expect("config");
+        let err = anthropic_to_openai(request(), &config).expect_err("rejected");
+        assert!(err.message.contains("computer_20250124"));
+
+        config.models.builtin_tools = "strip".to_string();
+        let out = anthropic_to_openai(request(), &config).expect("translate ok");
+        assert!(out.tools.is_none());
+        assert!(out.tool_choice.is_none());
+
+        config.models.builtin_tools = "map".to_string();
+        let out = anthropic_to_openai(request(), &config).expect("translate ok");
+        let tools = out.tools.expect("tools");
+        let names: Vec<&str> = tools.iter().map(|tool| tool.function.name.as_str()).collect();
+        assert_eq!(names, ["computer", "str_replace_based_edit_tool", "bash"]);
+        assert!(tools[0].function.description.as_deref().unwrap().contains("1280x800"));
+        assert_eq!(tools[1].function.parameters["required"], json!(["command", "path"]));
+        assert_eq!(family("web_search_20250305"), None);
+    }
+}
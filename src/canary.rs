@@ -0,0 +1,184 @@
+use axum::{
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+    Router,
+};
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::KeyValue;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Instant;
+use tower::ServiceExt;
+
+use crate::config::{CanaryConfig, Config};
+use crate::provider::ProviderRegistry;
+use crate::server::{build_routes, build_state};
+use crate::shadow::evenly_sampled;
+use crate::state::AppState;
+
+/// Response header naming the config that served the request.
+pub const CONFIG_HEADER: &str = "x-gateway-config";
+
+/// Serves `canary.percent` of `/v1/*` requests through a router built from
+/// the candidate config; the rest, and every admin or health route, go to
+/// the active one. Both run in this process and share the inflight limit,
+/// loggers, usage store and tap, so admin views cover all traffic.
+#[derive(Clone)]
+pub struct Canary {
+    candidate: Router,
+    percent: f64,
+    counter: Arc<AtomicU64>,
+    requests: Counter<u64>,
+    duration_ms: Histogram<f64>,
+}
+
+impl Canary {
+    /// Loads the candidate config and builds its state next to `active`.
+    pub async fn build(
+        config: &CanaryConfig,
+        active: &AppState,
+        providers: ProviderRegistry,
+    ) -> Result<Self, String> {
+        let candidate_config =
+            Config::from_path(&config.config_path).map_err(|e| format!("canary.config_path: {}", e))?;
+        if candidate_config.canary.is_some() {
+            return Err("canary: the candidate config must not set canary".to_string());
+        }
+        // Both configs sit behind the active `edge_layers`, so a candidate
+        // cannot loosen (or tighten) who may call it.
+        if candidate_config.server.signing != active.config.server.signing {
+            return Err("canary: the candidate config must use the active server.signing".to_string());
+        }
+        if candidate_config.server.ip_access != active.config.server.ip_access {
+            return Err("canary: the candidate config must use the active server.ip_access".to_string());
+        }
+        let mut state = build_state(
+            &candidate_config,
+            active.metrics.clone(),
+            active.inflight_count.clone(),
+            active._tracer_provider.clone(),
+            providers,
+        )
+        .await?;
+        state.inflight = active.inflight.clone();
+        state.inflight_requests = active.inflight_requests.clone();
+        state.audit_logger = active.audit_logger.clone();
+        state.debug_capture = active.debug_capture.clone();
+        state.access_logger = active.access_logger.clone();
+        state.usage = active.usage.clone();
+        state.tap = active.tap.clone();
+        state.transcripts = active.transcripts.clone();
+        tracing::info!(
+            config_path = %config.config_path,
+            percent = config.percent,
+            "canary config loaded"
+        );
+        Ok(Self::new(build_routes(&candidate_config, state)?, config.percent))
+    }
+
+    pub fn new(candidate: Router, percent: f64) -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        Self {
+            candidate,
+            percent,
+            counter: Arc::new(AtomicU64::new(0)),
+            requests: meter
+                .u64_counter("ai.gateway.canary.requests")
+                .with_description("Requests per config (active / candidate) by status class")
+                .build(),
+            duration_ms: meter
+                .f64_histogram("ai.gateway.canary.duration_ms")
+                .with_description("Time to response headers per config (active / candidate)")
+                .build(),
+        }
+    }
+
+    /// Wraps the active routes (before `edge_layers`); signing, IP access and
+    /// CORS then apply once, from the active config, to both variants.
+    pub fn layer(self, active: Router) -> Router {
+        active.layer(axum::middleware::from_fn_with_state(self, route_canary))
+    }
+}
+
+async fn route_canary(State(canary): State<Canary>, request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with("/v1/") {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+    let (variant, mut response) = if evenly_sampled(&canary.counter, canary.percent) {
+        let response = canary.candidate.clone().oneshot(request).await;
+        ("candidate", response.unwrap_or_else(|never| match never {}))
+    } else {
+        ("active", next.run(request).await)
+    };
+    let status = format!("{}xx", response.status().as_u16() / 100);
+    canary
+        .requests
+        .add(1, &[KeyValue::new("config", variant), KeyValue::new("status", status)]);
+    canary.duration_ms.record(
+        started.elapsed().as_secs_f64() * 1000.0,
+        &[KeyValue::new("config", variant)],
+    );
+    response
+        .headers_mut()
+        .insert(CONFIG_HEADER, HeaderValue::from_static(variant));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+
+    fn app(percent: f64) -> Router {
+        let active = Router::new()
+            .route("/v1/models", get(|| async { "active" }))
+            .route("/health", get(|| async { "ok" }));
+        let candidate = Router::new().route("/v1/models", get(|| async { "candidate" }));
+        Canary::new(candidate, percent).layer(active)
+    }
+
+    async fn served_by(app: &Router, path: &str) -> Option<String> {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        response
+            .headers()
+            .get(CONFIG_HEADER)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn routes_share_of_ingress_to_candidate() {
+        let all = app(100.0);
+        assert_eq!(served_by(&all, "/v1/models").await.as_deref(), Some("candidate"));
+        assert_eq!(served_by(&all, "/health").await, None);
+
+        let none = app(0.0);
+        assert_eq!(served_by(&none, "/v1/models").await.as_deref(), Some("active"));
+
+        let half = app(50.0);
+        let mut candidates = 0;
+        for _ in 0..10 {
+            if served_by(&half, "/v1/models").await.as_deref() == Some("candidate") {
+                candidates += 1;
+            }
+        }
+        assert_eq!(candidates, 5);
+    }
+
+    #[tokio::test]
+    async fn candidate_share_is_behind_the_active_signing_layer() {
+        let config = Config::from_yaml(
+            "server:\n  signing: {secret: s3cret}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\n",
+        )
+        .unwrap();
+        let app = crate::server::edge_layers(&config, app(100.0)).unwrap();
+        let request = Request::builder().uri("/v1/models").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::UNAUTHORIZED);
+        assert!(response.headers().get(CONFIG_HEADER).is_none());
+    }
+}
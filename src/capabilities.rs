@@ -0,0 +1,136 @@
+use serde_json::Value;
+
+use crate::config::{Config, ModelCapabilities};
+use crate::models::{AnthropicContent, AnthropicContentBlock, AnthropicRequest};
+use crate::translate::TranslateError;
+
+const fn known(
+    tools: bool,
+    images: bool,
+    json_schema: bool,
+    reasoning: bool,
+    max_context: u64,
+    max_output: u32,
+) -> ModelCapabilities {
+    ModelCapabilities {
+        tools: Some(tools),
+        images: Some(images),
+        json_schema: Some(json_schema),
+        reasoning: Some(reasoning),
+        max_context: Some(max_context),
+        max_output: Some(max_output),
+    }
+}
+
+/// Built-in capabilities by downstream model name prefix; the longest
+/// matching prefix wins.
+const KNOWN: [(&str, ModelCapabilities); 8] = [
+    ("gpt-4o", known(true, true, true, false, 128_000, 16_384)),
+    ("gpt-4.1", known(true, true, true, false, 1_047_576, 32_768)),
+    ("gpt-3.5-turbo", known(true, false, false, false, 16_385, 4_096)),
+    ("o1", known(true, true, true, true, 200_000, 100_000)),
+    ("o1-mini", known(false, false, false, true, 128_000, 65_536)),
+    ("o3", known(true, true, true, true, 200_000, 100_000)),
+    ("o4-mini", known(true, true, true, true, 200_000, 100_000)),
+    ("deepseek-chat", known(true, false, false, false, 65_536, 8_192)),
+];
+
+/// Capabilities of the downstream `model`: `models.capabilities` field by
+/// field over the built-in entry (only with `models.builtin_capabilities`,
+/// as it changes requests for models the operator never configured); fields
+/// neither sets stay unknown.
+pub fn resolve(config: &Config, model: &str) -> ModelCapabilities {
+    let builtin = KNOWN
+        .iter()
+        .filter(|_| config.models.builtin_capabilities)
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, capabilities)| *capabilities)
+        .unwrap_or_default();
+    let Some(configured) = config.models.capabilities.get(model) else {
+        return builtin;
+    };
+    ModelCapabilities {
+        tools: configured.tools.or(builtin.tools),
+        images: configured.images.or(builtin.images),
+        json_schema: configured.json_schema.or(builtin.json_schema),
+        reasoning: configured.reasoning.or(builtin.reasoning),
+        max_context: configured.max_context.or(builtin.max_context),
+        max_output: configured.max_output.or(builtin.max_output),
+    }
+}
+
+/// Rejects a request that uses tools or images the model is known not to
+/// support, before it is sent.
+pub fn check(req: &AnthropicRequest, capabilities: &ModelCapabilities) -> Result<(), TranslateError> {
+    if capabilities.tools == Some(false) && req.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+        return Err(TranslateError::invalid_request(format!(
+            "tools: model {} does not support tools",
+            req.model
+        )));
+    }
+    if capabilities.images == Some(false) && has_images(req) {
+        return Err(TranslateError::invalid_request(format!(
+            "messages: model {} does not support images",
+            req.model
+        )));
+    }
+    Ok(())
+}
+
+/// Image blocks in messages, including inside tool results.
+fn has_images(req: &AnthropicRequest) -> bool {
+    req.messages.iter().any(|message| match &message.content {
+        AnthropicContent::Text(_) => false,
+        AnthropicContent::Blocks(blocks) => blocks.iter().any(|block| match block {
+            AnthropicContentBlock::Image { .. } => true,
+            AnthropicContentBlock::ToolResult { content, .. } => content
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|part| part.get("type").and_then(Value::as_str) == Some("image")),
+            _ => false,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn config_overrides_builtin_fields() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nmodels:\n  builtin_capabilities: true\n  capabilities:\n    o1-mini-2024-09-12:\n      tools: true\n    qwen3-32b:\n      images: false\n      max_context: 32768\nlimits: {}\nobservability: {}\n",
+        )
+        .unwrap();
+        let o1_mini = resolve(&config, "o1-mini-2024-09-12");
+        assert_eq!(o1_mini.tools, Some(true));
+        assert_eq!(o1_mini.images, Some(false));
+        assert_eq!(o1_mini.max_output, Some(65_536));
+        assert_eq!(resolve(&config, "o1-preview").tools, Some(true));
+        let qwen = resolve(&config, "qwen3-32b");
+        assert_eq!((qwen.tools, qwen.images, qwen.max_context), (None, Some(false), Some(32768)));
+
+        let req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "qwen3-32b",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "t1", "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AA=="}}
+                ]}
+            ]}]
+        }))
+        .unwrap();
+        let mut opted_out = config.clone();
+        opted_out.models.builtin_capabilities = false;
+        assert_eq!(resolve(&opted_out, "gpt-4o"), ModelCapabilities::default());
+        assert_eq!(resolve(&opted_out, "o1-mini-2024-09-12").tools, Some(true));
+        assert_eq!(resolve(&opted_out, "o1-mini-2024-09-12").max_output, None);
+
+        let err = check(&req, &qwen).unwrap_err();
+        assert!(err.message.contains("does not support images"), "{}", err.message);
+        assert!(check(&req, &ModelCapabilities::default()).is_ok());
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// How request/response payloads are captured in span attributes and audit
+/// bodies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapturePolicy {
+    None,
+    Hashed,
+    Truncated(usize),
+    Full,
+}
+
+impl CapturePolicy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim().to_lowercase();
+        match value.as_str() {
+            "none" => return Ok(CapturePolicy::None),
+            "hashed" => return Ok(CapturePolicy::Hashed),
+            "full" => return Ok(CapturePolicy::Full),
+            _ => {}
+        }
+        let limit = value
+            .strip_prefix("truncated(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| format!("observability.capture invalid: {}", value))?;
+        let limit = limit
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("observability.capture invalid: {}", value))?;
+        Ok(CapturePolicy::Truncated(limit))
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            CapturePolicy::None => "[omitted]".to_string(),
+            CapturePolicy::Hashed => hash_text(text),
+            CapturePolicy::Truncated(limit) => truncate_text(text, *limit),
+            CapturePolicy::Full => text.to_string(),
+        }
+    }
+
+    /// Like `apply`, but the text is only rendered when the policy keeps any
+    /// of it, and a fully captured text is returned without another copy.
+    pub fn apply_with(&self, render: impl FnOnce() -> String) -> String {
+        match self {
+            CapturePolicy::None => "[omitted]".to_string(),
+            CapturePolicy::Full => render(),
+            _ => self.apply(&render()),
+        }
+    }
+
+    /// Applies the policy to a JSON body; non-full captures replace the value
+    /// with a string so the shape of a partial body is never mistaken for the real one.
+    pub fn apply_value(&self, value: Value) -> Value {
+        match self {
+            CapturePolicy::Full => value,
+            CapturePolicy::None => Value::Null,
+            CapturePolicy::Hashed => Value::String(hash_text(&value.to_string())),
+            CapturePolicy::Truncated(limit) => {
+                let text = value.to_string();
+                if text.len() <= *limit {
+                    value
+                } else {
+                    Value::String(truncate_text(&text, *limit))
+                }
+            }
+        }
+    }
+}
+
+fn hash_text(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+fn truncate_text(text: &str, limit: usize) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...[truncated {} bytes]", &text[..end], text.len() - end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_policies() {
+        assert_eq!(CapturePolicy::parse("full").unwrap(), CapturePolicy::Full);
+        assert_eq!(CapturePolicy::parse("None").unwrap(), CapturePolicy::None);
+        assert_eq!(
+            CapturePolicy::parse("truncated(128)").unwrap(),
+            CapturePolicy::Truncated(128)
+        );
+        assert!(CapturePolicy::parse("truncated").is_err());
+        assert!(CapturePolicy::parse("partial").is_err());
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let out = CapturePolicy::Truncated(4).apply("你好世界");
+        assert_eq!(out, "你...[truncated 9 bytes]");
+    }
+
+    #[test]
+    fn apply_with_renders_only_when_captured() {
+        assert_eq!(CapturePolicy::None.apply_with(|| -> String { unreachable!() }), "[omitted]");
+        assert_eq!(CapturePolicy::Full.apply_with(|| "body".to_string()), "body");
+        assert!(CapturePolicy::Hashed.apply_with(|| "body".to_string()).starts_with("sha256:"));
+    }
+
+    #[test]
+    fn apply_value_by_policy() {
+        let body = json!({"messages": [{"role": "user", "content": "hello"}]});
+        assert_eq!(CapturePolicy::Full.apply_value(body.clone()), body);
+        assert_eq!(CapturePolicy::None.apply_value(body.clone()), Value::Null);
+        let hashed = CapturePolicy::Hashed.apply_value(body.clone());
+        assert!(hashed.as_str().unwrap().starts_with("sha256:"));
+        assert_eq!(CapturePolicy::Truncated(1024).apply_value(body.clone()), body);
+        assert!(CapturePolicy::Truncated(8).apply_value(body).is_string());
+    }
+}
@@ -0,0 +1,75 @@
+use clap::{Parser, Subcommand};
+
+use crate::bench::{self, BenchArgs};
+use crate::config::Config;
+use crate::conformance::{self, ConformanceArgs};
+use crate::cors;
+use crate::provider::ProviderRegistry;
+use crate::replay::{self, ReplayArgs};
+use crate::server;
+use crate::tokenizer::Tokenizers;
+
+#[derive(Debug, Parser)]
+#[command(name = "llm-gateway", version, about = "Anthropic-compatible LLM gateway")]
+pub struct Cli {
+    /// YAML config file, or a directory of fragments.
+    #[arg(long, env = "CONFIG_PATH", global = true)]
+    pub config: Option<String>,
+    /// Defaults to `serve`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the gateway.
+    Serve,
+    /// Load and validate the config, then print a short summary.
+    CheckConfig,
+    /// Re-send `/v1/messages` requests from a JSONL audit log to a gateway.
+    Replay(ReplayArgs),
+    /// Load-test the gateway in-process against a mock downstream.
+    Bench(BenchArgs),
+    /// Replay recorded downstream streams and compare against golden transcripts.
+    Conformance(ConformanceArgs),
+}
+
+pub async fn run(cli: Cli) -> Result<(), String> {
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => server::serve(load_config(cli.config.as_deref())?).await,
+        Command::CheckConfig => check_config(load_config(cli.config.as_deref())?),
+        Command::Replay(args) => replay::run(args).await,
+        Command::Bench(args) => bench::run(load_config(cli.config.as_deref())?, args).await,
+        Command::Conformance(args) => {
+            let config = match cli.config.as_deref() {
+                Some(path) => load_config(Some(path))?,
+                None if !args.record => conformance::default_config(),
+                None => return Err("config error: --record needs CONFIG_PATH for the live downstream".to_string()),
+            };
+            conformance::run(config, args).await
+        }
+    }
+}
+
+fn load_config(path: Option<&str>) -> Result<Config, String> {
+    let path = path.ok_or_else(|| "config error: CONFIG_PATH is required (strict YAML)".to_string())?;
+    Config::from_path(path).map_err(|e| format!("config error: {}", e))
+}
+
+/// Beyond `Config::normalize`, builds the pieces `serve` would fail on
+/// before binding (tokenizers, CORS, provider) without touching external services.
+fn check_config(config: Config) -> Result<(), String> {
+    Tokenizers::from_config(&config.tokenizers).map_err(|e| format!("config error: {}", e))?;
+    if let Some(cors) = config.server.cors.as_ref() {
+        let _ = cors::cors_layer(cors).map_err(|e| format!("config error: {}", e))?;
+    }
+    server::check_providers(&config, &ProviderRegistry::builtin()).map_err(|e| format!("config error: {}", e))?;
+    println!("config ok");
+    println!("  bind_addr: {}", config.server.bind_addr);
+    println!("  forward_mode: {}", config.forward_mode());
+    println!("  downstream: {} ({})", config.downstream.base_url, config.downstream.provider);
+    println!("  model_map: {} entries", config.models.model_map.len());
+    println!("  experiments: {}", config.experiments.len());
+    println!("  admin endpoints: {}", config.admin.token.is_some());
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use axum::http::header::USER_AGENT;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::access_log::key_id_from_headers;
+use crate::config::DownstreamConfig;
+
+/// Template variables besides `header.<name>`.
+const VARIABLES: [&str; 3] = ["request_id", "key_id", "model"];
+
+/// The request a downstream call is made for; fills `downstream.headers`
+/// templates.
+pub struct CallContext<'a> {
+    pub request_id: &'a str,
+    pub model: Option<&'a str>,
+    /// Headers of the client request.
+    pub incoming: &'a HeaderMap,
+}
+
+/// Sets `downstream.user_agent` and `downstream.headers` on a downstream
+/// call, replacing forwarded client values of the same name. A templated
+/// header that renders empty (e.g. `{{ key_id }}` without a key) is left out.
+pub fn apply(config: &DownstreamConfig, call: &CallContext, headers: &mut HeaderMap) {
+    if let Some(agent) = config.user_agent.as_deref()
+        && let Ok(value) = HeaderValue::from_str(agent)
+    {
+        headers.insert(USER_AGENT, value);
+    }
+    for (name, template) in &config.headers {
+        let value = render(template, call);
+        if value.is_empty() {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(&value)) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// Checked by `Config::normalize`: valid header names and values, known
+/// template variables.
+pub fn validate(config: &DownstreamConfig) -> Result<(), String> {
+    if let Some(agent) = config.user_agent.as_deref()
+        && HeaderValue::from_str(agent).is_err()
+    {
+        return Err("downstream.user_agent is not a valid header value".to_string());
+    }
+    for (name, template) in &config.headers {
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(format!("downstream.headers: invalid header name {:?}", name));
+        }
+        let mut rest = template.as_str();
+        while let Some((start, end)) = placeholder(rest) {
+            let variable = rest[start + 2..end].trim();
+            let known = VARIABLES.contains(&variable)
+                || variable
+                    .strip_prefix("header.")
+                    .is_some_and(|header| HeaderName::from_bytes(header.as_bytes()).is_ok());
+            if !known {
+                return Err(format!("downstream.headers.{}: unknown variable {:?}", name, variable));
+            }
+            rest = &rest[end + 2..];
+        }
+        if HeaderValue::from_str(rest).is_err() {
+            return Err(format!("downstream.headers.{}: invalid header value", name));
+        }
+    }
+    Ok(())
+}
+
+fn render(template: &str, call: &CallContext) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((start, end)) = placeholder(rest) {
+        out.push_str(&rest[..start]);
+        let variable = rest[start + 2..end].trim();
+        match variable {
+            "request_id" => out.push_str(call.request_id),
+            "key_id" => out.push_str(&key_id_from_headers(call.incoming).unwrap_or_default()),
+            "model" => out.push_str(call.model.unwrap_or_default()),
+            _ => {
+                let value = variable
+                    .strip_prefix("header.")
+                    .and_then(|header| call.incoming.get(header))
+                    .and_then(|value| value.to_str().ok());
+                out.push_str(value.unwrap_or_default());
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Byte offsets of the next `{{` and its closing `}}`.
+fn placeholder(text: &str) -> Option<(usize, usize)> {
+    let start = text.find("{{")?;
+    let end = text[start + 2..].find("}}")?;
+    Some((start, start + 2 + end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_user_agent_and_renders_templated_headers() {
+        let mut config: DownstreamConfig = serde_yaml::from_str(
+            "user_agent: acme-gateway/1.0\nheaders:\n  x-client-team: search\n  x-request-ref: \"gw-{{ request_id }}/{{ model }}\"\n  x-tenant: \"{{ header.x-tenant }}\"\n  x-caller: \"{{ key_id }}\"\n",
+        )
+        .expect("config");
+        validate(&config).expect("valid");
+
+        let mut incoming = HeaderMap::new();
+        incoming.insert("x-tenant", HeaderValue::from_static("t-7"));
+        let call = CallContext {
+            request_id: "req-1",
+            model: Some("gpt-4o"),
+            incoming: &incoming,
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("client/2.0"));
+        apply(&config, &call, &mut headers);
+        assert_eq!(headers.get(USER_AGENT).unwrap(), "acme-gateway/1.0");
+        assert_eq!(headers.get("x-client-team").unwrap(), "search");
+        assert_eq!(headers.get("x-request-ref").unwrap(), "gw-req-1/gpt-4o");
+        assert_eq!(headers.get("x-tenant").unwrap(), "t-7");
+        assert!(headers.get("x-caller").is_none());
+
+        config.headers.insert("x-bad".to_string(), "{{ tenant }}".to_string());
+        assert!(validate(&config).unwrap_err().contains("unknown variable"));
+    }
+}
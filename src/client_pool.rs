@@ -0,0 +1,247 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// The downstream HTTP clients (`client` for plain calls, `stream_client`
+/// with its own pool and no total timeout for streams) and their connection
+/// pools.
+/// `recycle` swaps in freshly built clients: new requests open new
+/// connections, re-resolving the downstream hosts, while requests already
+/// in flight finish on the old ones.
+#[derive(Clone)]
+pub struct DownstreamClients {
+    current: Arc<RwLock<Clients>>,
+    settings: Arc<ClientSettings>,
+    generation: Arc<AtomicU64>,
+}
+
+struct Clients {
+    client: reqwest::Client,
+    stream_client: reqwest::Client,
+}
+
+struct ClientSettings {
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Duration,
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    stream: StreamSettings,
+    compression: bool,
+}
+
+/// `downstream.stream`, with fallbacks applied.
+struct StreamSettings {
+    pool_max_idle_per_host: usize,
+    pool_idle_timeout: Duration,
+    connect_timeout: Duration,
+    read_idle_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+}
+
+impl DownstreamClients {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let settings = ClientSettings::from_config(config);
+        Ok(Self {
+            current: Arc::new(RwLock::new(settings.build()?)),
+            settings: Arc::new(settings),
+            generation: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Wraps prebuilt clients for tests; `recycle` then builds new ones from
+    /// `config` like `new` would.
+    #[cfg(test)]
+    pub fn from_clients(config: &Config, client: reqwest::Client, stream_client: reqwest::Client) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Clients { client, stream_client })),
+            settings: Arc::new(ClientSettings::from_config(config)),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn client(&self) -> reqwest::Client {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).client.clone()
+    }
+
+    pub fn stream_client(&self) -> reqwest::Client {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).stream_client.clone()
+    }
+
+    /// Replaces both clients, dropping their idle connections, and returns
+    /// how many times the pools have been recycled.
+    pub fn recycle(&self) -> Result<u64, String> {
+        let clients = self.settings.build()?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = clients;
+        Ok(self.generation.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    #[cfg(test)]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Recycles the pools every `interval` (`downstream.pool_recycle_secs`).
+    pub fn spawn_recycler(&self, interval: Duration) {
+        let clients = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match clients.recycle() {
+                    Ok(generation) => tracing::info!(generation, "downstream connection pools recycled"),
+                    Err(err) => tracing::warn!(error = %err, "downstream connection pool recycle failed"),
+                }
+            }
+        });
+    }
+}
+
+impl ClientSettings {
+    fn from_config(config: &Config) -> Self {
+        let downstream = &config.downstream;
+        Self {
+            pool_max_idle_per_host: downstream.pool_max_idle_per_host,
+            pool_idle_timeout: Duration::from_secs(downstream.pool_idle_timeout_secs),
+            connect_timeout: config.connect_timeout(),
+            read_timeout: config.read_timeout(),
+            stream: StreamSettings {
+                pool_max_idle_per_host: downstream
+                    .stream
+                    .pool_max_idle_per_host
+                    .unwrap_or(downstream.pool_max_idle_per_host),
+                pool_idle_timeout: Duration::from_secs(
+                    downstream.stream.pool_idle_timeout_secs.unwrap_or(downstream.pool_idle_timeout_secs),
+                ),
+                connect_timeout: downstream
+                    .stream
+                    .connect_timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or_else(|| config.connect_timeout()),
+                read_idle_timeout: downstream.stream.read_idle_timeout_ms.map(Duration::from_millis),
+                tcp_nodelay: downstream.stream.tcp_nodelay,
+            },
+            compression: config.compression.downstream,
+        }
+    }
+
+    fn build(&self) -> Result<Clients, String> {
+        let builder = |pool_max_idle_per_host: usize, pool_idle_timeout: Duration, connect_timeout: Duration| {
+            reqwest::Client::builder()
+                .pool_max_idle_per_host(pool_max_idle_per_host)
+                .pool_idle_timeout(pool_idle_timeout)
+                .connect_timeout(connect_timeout)
+                .gzip(self.compression)
+                .brotli(self.compression)
+        };
+        let stream = &self.stream;
+        let mut stream_builder = builder(stream.pool_max_idle_per_host, stream.pool_idle_timeout, stream.connect_timeout)
+            .tcp_nodelay(stream.tcp_nodelay);
+        if let Some(read_idle_timeout) = stream.read_idle_timeout {
+            stream_builder = stream_builder.read_timeout(read_idle_timeout);
+        }
+        Ok(Clients {
+            client: builder(self.pool_max_idle_per_host, self.pool_idle_timeout, self.connect_timeout)
+                .timeout(self.read_timeout)
+                .build()
+                .map_err(|e| format!("client build error: {}", e))?,
+            stream_client: stream_builder
+                .build()
+                .map_err(|e| format!("stream client build error: {}", e))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn config(downstream: &str) -> Config {
+        Config::from_yaml(&format!(
+            "server: {{}}\ndownstream: {}\nmodels: {{}}\nlimits: {{}}\nobservability: {{}}\n",
+            downstream
+        ))
+        .unwrap()
+    }
+
+    /// A keep-alive HTTP/1.1 server counting the connections it accepts;
+    /// `/slow` never answers.
+    async fn spawn_counting_server() -> Option<(String, Arc<AtomicUsize>)> {
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return None,
+            Err(err) => panic!("bind: {}", err),
+        };
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let mut request = Vec::new();
+                    loop {
+                        let Ok(n) = socket.read(&mut buf).await else { return };
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        if !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                            continue;
+                        }
+                        if request.starts_with(b"GET /slow") {
+                            std::future::pending::<()>().await;
+                        }
+                        request.clear();
+                        let reply = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+                        if socket.write_all(reply).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        Some((format!("http://{}", addr), connections))
+    }
+
+    #[test]
+    fn recycle_bumps_generation() {
+        let clients =
+            DownstreamClients::from_clients(&config("{}"), reqwest::Client::new(), reqwest::Client::new());
+        assert_eq!(clients.generation(), 0);
+        assert_eq!(clients.recycle().unwrap(), 1);
+        assert_eq!(clients.recycle().unwrap(), 2);
+        assert_eq!(clients.generation(), 2);
+    }
+
+    #[tokio::test]
+    async fn recycle_opens_new_connections_with_the_configured_settings() {
+        let Some((base_url, connections)) = spawn_counting_server().await else { return };
+        let clients = DownstreamClients::from_clients(
+            &config("{read_timeout_ms: 200}"),
+            reqwest::Client::new(),
+            reqwest::Client::new(),
+        );
+        let get = |path: &str| clients.client().get(format!("{}{}", base_url, path)).send();
+
+        for _ in 0..2 {
+            assert_eq!(get("/ok").await.unwrap().text().await.unwrap(), "ok");
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        clients.recycle().unwrap();
+        assert_eq!(get("/ok").await.unwrap().text().await.unwrap(), "ok");
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        // The recycled client keeps `downstream.read_timeout_ms`.
+        let started = std::time::Instant::now();
+        let err = get("/slow").await.unwrap_err();
+        assert!(err.is_timeout(), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
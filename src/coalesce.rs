@@ -0,0 +1,219 @@
+use axum::body::Bytes;
+use futures_util::stream;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::backpressure::ClientStream;
+use crate::config::CoalesceConfig;
+use crate::sse::{event_end, SseParser};
+
+/// `streaming.coalesce`: consecutive `text_delta` / `thinking_delta` events
+/// for the same content block are merged into one client event, flushed at
+/// `max_bytes` of text, after `max_delay_ms`, or when any other event comes
+/// through. Other events pass unchanged and in order.
+pub fn coalesce(inner: ClientStream, config: &CoalesceConfig) -> ClientStream {
+    let state = Coalescer {
+        inner,
+        max_bytes: config.max_bytes,
+        max_delay: Duration::from_millis(config.max_delay_ms),
+        buffer: Vec::new(),
+        pending: None,
+        ready: VecDeque::new(),
+        error: None,
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.ready.pop_front() {
+                return Some((Ok(event), state));
+            }
+            if let Some(err) = state.error.take() {
+                return Some((Err(err), state));
+            }
+            if state.done {
+                return None;
+            }
+            let next = match state.pending.as_ref().map(|p| p.deadline) {
+                Some(deadline) => match tokio::time::timeout_at(deadline, state.inner.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        state.flush_pending();
+                        continue;
+                    }
+                },
+                None => state.inner.next().await,
+            };
+            match next {
+                Some(Ok(bytes)) => {
+                    state.buffer.extend_from_slice(&bytes);
+                    while let Some(end) = event_end(&state.buffer) {
+                        let rest = state.buffer.split_off(end);
+                        let event = std::mem::replace(&mut state.buffer, rest);
+                        state.push_event(Bytes::from(event));
+                    }
+                }
+                Some(Err(err)) => {
+                    state.finish();
+                    state.error = Some(err);
+                }
+                None => state.finish(),
+            }
+        }
+    })
+    .boxed()
+}
+
+struct Coalescer {
+    inner: ClientStream,
+    max_bytes: usize,
+    max_delay: Duration,
+    buffer: Vec<u8>,
+    pending: Option<PendingDelta>,
+    ready: VecDeque<Bytes>,
+    error: Option<std::io::Error>,
+    done: bool,
+}
+
+struct PendingDelta {
+    index: u64,
+    /// `text_delta` or `thinking_delta`.
+    kind: String,
+    text: String,
+    deadline: Instant,
+}
+
+impl Coalescer {
+    fn push_event(&mut self, raw: Bytes) {
+        let Some((index, kind, text)) = text_delta(&raw) else {
+            self.flush_pending();
+            self.ready.push_back(raw);
+            return;
+        };
+        match self.pending.as_mut() {
+            Some(pending) if pending.index == index && pending.kind == kind => pending.text.push_str(&text),
+            _ => {
+                self.flush_pending();
+                self.pending = Some(PendingDelta {
+                    index,
+                    kind,
+                    text,
+                    deadline: Instant::now() + self.max_delay,
+                });
+            }
+        }
+        if self.pending.as_ref().is_some_and(|p| p.text.len() >= self.max_bytes) {
+            self.flush_pending();
+        }
+    }
+
+    fn flush_pending(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let field = if pending.kind == "thinking_delta" { "thinking" } else { "text" };
+        let data = json!({
+            "type": "content_block_delta",
+            "index": pending.index,
+            "delta": {"type": pending.kind, field: pending.text},
+        });
+        self.ready
+            .push_back(Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", data)));
+    }
+
+    /// Flushes the merged delta and any trailing partial event at the end of
+    /// the stream.
+    fn finish(&mut self) {
+        self.flush_pending();
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            self.ready.push_back(Bytes::from(rest));
+        }
+        self.done = true;
+    }
+}
+
+/// Block index, delta type and text of a single text or thinking delta event.
+fn text_delta(raw: &[u8]) -> Option<(u64, String, String)> {
+    let mut parser = SseParser::default();
+    let mut events = parser.push(raw);
+    events.extend(parser.finish());
+    let [event] = events.as_slice() else {
+        return None;
+    };
+    let data: Value = serde_json::from_str(&event.data).ok()?;
+    if data.get("type").and_then(Value::as_str) != Some("content_block_delta") {
+        return None;
+    }
+    let index = data.get("index").and_then(Value::as_u64)?;
+    let delta = data.get("delta")?;
+    let kind = delta.get("type").and_then(Value::as_str)?;
+    let field = match kind {
+        "text_delta" => "text",
+        "thinking_delta" => "thinking",
+        _ => return None,
+    };
+    let text = delta.get(field).and_then(Value::as_str)?;
+    Some((index, kind.to_string(), text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(index: u32, kind: &str, text: &str) -> String {
+        let field = if kind == "thinking_delta" { "thinking" } else { "text" };
+        format!(
+            "event: content_block_delta\ndata: {}\n\n",
+            json!({"type": "content_block_delta", "index": index, "delta": {"type": kind, field: text}})
+        )
+    }
+
+    fn texts(events: &[Bytes]) -> Vec<String> {
+        events
+            .iter()
+            .map(|raw| match text_delta(raw) {
+                Some((index, _, text)) => format!("{}:{}", index, text),
+                None => String::from_utf8_lossy(raw).lines().next().unwrap_or_default().to_string(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn merges_small_deltas_until_limit_or_other_event() {
+        let config = CoalesceConfig {
+            max_bytes: 4,
+            max_delay_ms: 1000,
+        };
+        let chunk = [
+            delta(0, "thinking_delta", "a"),
+            delta(0, "thinking_delta", "b"),
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n".to_string(),
+            delta(1, "text_delta", "he"),
+            delta(1, "text_delta", "ll"),
+            delta(1, "text_delta", "o"),
+        ]
+        .concat();
+        let body: ClientStream = stream::iter(vec![Ok(Bytes::from(chunk))]).boxed();
+        let events: Vec<Bytes> = coalesce(body, &config).map(|chunk| chunk.expect("chunk")).collect().await;
+        assert_eq!(texts(&events), ["0:ab", "event: content_block_stop", "1:hell", "1:o"]);
+
+        // A lone delta is flushed by the timer while the stream stays open.
+        let config = CoalesceConfig {
+            max_bytes: 64,
+            max_delay_ms: 20,
+        };
+        let body: ClientStream = stream::iter(vec![Ok(Bytes::from(delta(0, "text_delta", "hi")))])
+            .chain(stream::pending())
+            .boxed();
+        let mut out = coalesce(body, &config);
+        let first = tokio::time::timeout(Duration::from_secs(1), out.next())
+            .await
+            .expect("flushed")
+            .expect("event")
+            .expect("chunk");
+        assert_eq!(texts(&[first]), ["0:hi"]);
+    }
+}
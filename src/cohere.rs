@@ -0,0 +1,413 @@
+use axum::http::StatusCode;
+use serde_json::{json, Map, Value};
+
+use crate::config::Config;
+use crate::error::{map_downstream_error, AppError};
+use crate::models::{
+    OpenAIContentPart, OpenAIMessage, OpenAIMessageContent, OpenAIRequest, OpenAIResponse, OpenAIToolChoice,
+};
+use crate::provider::{endpoint_url, json_headers, DownstreamRequest, Provider, StreamError, StreamEvent};
+use crate::sse::SseEvent;
+
+/// Cohere's `/v2/chat`. Tool results are sent as documents (`doc_<n>` in
+/// request order) so the model can cite them; response citations come back
+/// as `document_citation` annotations that the translator turns into
+/// Anthropic `char_location` citations. Streamed citations are dropped.
+pub struct CohereProvider;
+
+impl Provider for CohereProvider {
+    fn build_request(&self, req: &OpenAIRequest, config: &Config) -> Result<DownstreamRequest, AppError> {
+        let (base_url, api_key) = config.provider_endpoint("cohere");
+        Ok(DownstreamRequest {
+            url: endpoint_url(base_url, "v2", "chat"),
+            headers: json_headers(&format!("Bearer {}", api_key.unwrap_or_default())),
+            body: request_body(req),
+        })
+    }
+
+    /// The response carries no model; the handler fills in the request's.
+    fn parse_response(&self, body: &str) -> Result<OpenAIResponse, AppError> {
+        let value: Value = serde_json::from_str(body)
+            .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))?;
+        let message = value.get("message").cloned().unwrap_or(Value::Null);
+        let mut text: String = message
+            .get("content")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|part| part.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect();
+        if text.is_empty() {
+            text = message.get("tool_plan").and_then(Value::as_str).unwrap_or_default().to_string();
+        }
+        let tool_calls = message.get("tool_calls").and_then(Value::as_array).map(|calls| {
+            calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "id": call.get("id"),
+                        "type": "function",
+                        "function": {
+                            "name": call.pointer("/function/name"),
+                            "arguments": call.pointer("/function/arguments").and_then(Value::as_str).unwrap_or("{}"),
+                        },
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+        let resp = json!({
+            "id": value.get("id").and_then(Value::as_str).unwrap_or_default(),
+            "model": "",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": if text.is_empty() { Value::Null } else { json!(text) },
+                    "tool_calls": tool_calls,
+                    "annotations": citations(&message),
+                },
+                "finish_reason": finish_reason(value.get("finish_reason")),
+            }],
+            "usage": usage(value.get("usage")),
+        });
+        serde_json::from_value(resp).map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))
+    }
+
+    fn parse_stream_chunk(&self, event: &SseEvent) -> Result<StreamEvent, StreamError> {
+        let value: Value =
+            serde_json::from_str(event.data.trim()).map_err(|e| StreamError::Malformed(e.to_string()))?;
+        let kind = value
+            .get("type")
+            .and_then(Value::as_str)
+            .or(event.event.as_deref())
+            .unwrap_or_default();
+        let message = value.pointer("/delta/message").cloned().unwrap_or(Value::Null);
+        let index = value.get("index").and_then(Value::as_u64);
+        if index.is_none() && kind.starts_with("tool-call-") {
+            // Guessing an index would merge the call into another one.
+            tracing::warn!(event = kind, "cohere tool call event without index skipped");
+            return Ok(StreamEvent::Skip);
+        }
+        let delta = match kind {
+            "message-start" => json!({"role": "assistant"}),
+            "content-start" | "content-delta" => match message.pointer("/content/text").and_then(Value::as_str) {
+                Some(text) if !text.is_empty() => json!({"content": text}),
+                _ => return Ok(StreamEvent::Skip),
+            },
+            "tool-plan-delta" => json!({"content": message.get("tool_plan")}),
+            "tool-call-start" => json!({"tool_calls": [{
+                "index": index,
+                "id": message.pointer("/tool_calls/id"),
+                "type": "function",
+                "function": {
+                    "name": message.pointer("/tool_calls/function/name"),
+                    "arguments": message.pointer("/tool_calls/function/arguments").and_then(Value::as_str).unwrap_or_default(),
+                },
+            }]}),
+            "tool-call-delta" => json!({"tool_calls": [{
+                "index": index,
+                "function": {"arguments": message.pointer("/tool_calls/function/arguments")},
+            }]}),
+            "message-end" => {
+                let chunk = json!({
+                    "id": null,
+                    "model": null,
+                    "choices": [{
+                        "index": 0,
+                        "delta": {},
+                        "finish_reason": finish_reason(value.pointer("/delta/finish_reason")),
+                    }],
+                    "usage": usage(value.pointer("/delta/usage")),
+                });
+                return serde_json::from_value(chunk)
+                    .map(StreamEvent::Final)
+                    .map_err(|e| StreamError::Malformed(e.to_string()));
+            }
+            "error" => {
+                return Err(StreamError::Downstream(AppError::api_error(format!(
+                    "downstream stream error: {}",
+                    event.data.trim()
+                ))));
+            }
+            _ => return Ok(StreamEvent::Skip),
+        };
+        let chunk = json!({
+            "id": value.get("id"),
+            "model": null,
+            "choices": [{"index": 0, "delta": delta}],
+        });
+        serde_json::from_value(chunk)
+            .map(StreamEvent::Chunk)
+            .map_err(|e| StreamError::Malformed(e.to_string()))
+    }
+
+    /// Cohere reports schema violations as 422.
+    fn map_error(&self, status: StatusCode, body: &str) -> AppError {
+        if status == StatusCode::UNPROCESSABLE_ENTITY {
+            let mut err = map_downstream_error(StatusCode::BAD_REQUEST, body);
+            err.downstream_status = Some(status);
+            return err;
+        }
+        map_downstream_error(status, body)
+    }
+}
+
+fn request_body(req: &OpenAIRequest) -> Value {
+    let mut documents = 0usize;
+    let messages: Vec<Value> = req
+        .messages
+        .iter()
+        .map(|message| cohere_message(message, &mut documents))
+        .collect();
+
+    let mut body = Map::new();
+    body.insert("model".to_string(), json!(req.model));
+    body.insert("messages".to_string(), json!(messages));
+    body.insert("max_tokens".to_string(), json!(req.max_completion_tokens));
+    if let Some(v) = req.temperature {
+        body.insert("temperature".to_string(), json!(v));
+    }
+    if let Some(v) = req.top_p {
+        body.insert("p".to_string(), json!(v));
+    }
+    if let Some(stop) = req.stop.as_ref() {
+        body.insert("stop_sequences".to_string(), json!(stop));
+    }
+    if let Some(stream) = req.stream {
+        body.insert("stream".to_string(), json!(stream));
+    }
+    // Cohere cannot force a specific tool; forcing a call and offering only
+    // that tool is equivalent.
+    let forced = match req.tool_choice.as_ref() {
+        Some(OpenAIToolChoice::Tool(choice)) => Some(choice.function.name.as_str()),
+        _ => None,
+    };
+    if let Some(tools) = req.tools.as_ref() {
+        let tools: Vec<&_> = tools
+            .iter()
+            .filter(|tool| forced.is_none_or(|name| tool.function.name == name))
+            .collect();
+        body.insert("tools".to_string(), json!(tools));
+    }
+    match req.tool_choice.as_ref() {
+        Some(OpenAIToolChoice::Mode(mode)) if mode == "required" => {
+            body.insert("tool_choice".to_string(), json!("REQUIRED"));
+        }
+        Some(OpenAIToolChoice::Mode(mode)) if mode == "none" => {
+            body.insert("tool_choice".to_string(), json!("NONE"));
+        }
+        Some(OpenAIToolChoice::Tool(_)) => {
+            body.insert("tool_choice".to_string(), json!("REQUIRED"));
+        }
+        _ => {}
+    }
+    if let Some(format) = req.response_format.as_ref()
+        && format.format_type != "text"
+    {
+        let mut response_format = json!({"type": "json_object"});
+        if let Some(schema) = format.json_schema.as_ref() {
+            response_format["json_schema"] = schema.schema.clone();
+        }
+        body.insert("response_format".to_string(), response_format);
+    }
+    Value::Object(body)
+}
+
+fn cohere_message(message: &OpenAIMessage, documents: &mut usize) -> Value {
+    let text = match message.content.as_ref() {
+        Some(OpenAIMessageContent::Text(text)) => text.clone(),
+        Some(OpenAIMessageContent::Parts(parts)) => parts
+            .iter()
+            .filter_map(|part| match part {
+                OpenAIContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => String::new(),
+    };
+    match message.role.as_str() {
+        "tool" => {
+            let id = format!("doc_{}", *documents);
+            *documents += 1;
+            json!({
+                "role": "tool",
+                "tool_call_id": message.tool_call_id,
+                "content": [{"type": "document", "document": {"id": id, "data": {"text": text}}}],
+            })
+        }
+        "assistant" if message.tool_calls.is_some() => {
+            let mut out = json!({"role": "assistant", "tool_calls": message.tool_calls});
+            if !text.is_empty() {
+                out["tool_plan"] = json!(text);
+            }
+            out
+        }
+        "user" => match message.content.as_ref() {
+            Some(OpenAIMessageContent::Parts(parts)) => json!({"role": "user", "content": parts}),
+            _ => json!({"role": "user", "content": text}),
+        },
+        role => json!({"role": role, "content": text}),
+    }
+}
+
+/// One `document_citation` per cited source whose `doc_<n>` id maps back to
+/// a document's position. Citations without `start`/`end` and sources with
+/// other ids (Cohere-generated) are skipped rather than pointed at a guessed
+/// document.
+fn citations(message: &Value) -> Vec<Value> {
+    let mut annotations = Vec::new();
+    for citation in message.get("citations").and_then(Value::as_array).into_iter().flatten() {
+        let (Some(start), Some(end)) = (
+            citation.get("start").and_then(Value::as_u64),
+            citation.get("end").and_then(Value::as_u64),
+        ) else {
+            continue;
+        };
+        for source in citation.get("sources").and_then(Value::as_array).into_iter().flatten() {
+            let id = source.get("id").and_then(Value::as_str).unwrap_or_default();
+            let Some(document_index) = id.strip_prefix("doc_").and_then(|n| n.parse::<usize>().ok()) else {
+                continue;
+            };
+            let title = source
+                .pointer("/document/title")
+                .and_then(Value::as_str)
+                .unwrap_or(id);
+            annotations.push(json!({
+                "type": "document_citation",
+                "document_citation": {
+                    "start_index": start,
+                    "end_index": end,
+                    "document_index": document_index,
+                    "title": title,
+                },
+            }));
+        }
+    }
+    annotations
+}
+
+fn finish_reason(reason: Option<&Value>) -> &'static str {
+    match reason.and_then(Value::as_str) {
+        Some("MAX_TOKENS") => "length",
+        Some("TOOL_CALL") => "tool_calls",
+        _ => "stop",
+    }
+}
+
+fn usage(usage: Option<&Value>) -> Value {
+    let Some(usage) = usage else {
+        return Value::Null;
+    };
+    let tokens = usage.get("tokens").or_else(|| usage.get("billed_units"));
+    let count = |key: &str| {
+        tokens
+            .and_then(|t| t.get(key))
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0) as u32
+    };
+    let (input, output) = (count("input_tokens"), count("output_tokens"));
+    json!({"prompt_tokens": input, "completion_tokens": output, "total_tokens": input + output})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EmptyContentPolicy;
+    use crate::translate::openai_to_anthropic;
+
+    #[test]
+    fn maps_tool_results_to_documents_and_citations_back() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "command-r-plus",
+            "max_completion_tokens": 128,
+            "top_p": 0.5,
+            "tool_choice": {"type": "function", "function": {"name": "search"}},
+            "tools": [
+                {"type": "function", "function": {"name": "search", "parameters": {}}},
+                {"type": "function", "function": {"name": "other", "parameters": {}}}
+            ],
+            "messages": [
+                {"role": "user", "content": "weather?"},
+                {"role": "assistant", "content": "I will search.", "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "search", "arguments": "{}"}}]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "Sunny in Paris"}
+            ]
+        }))
+        .unwrap();
+        let body = request_body(&req);
+        assert_eq!(body["max_tokens"], json!(128));
+        assert_eq!(body["p"], json!(0.5));
+        assert_eq!(body["tool_choice"], json!("REQUIRED"));
+        assert_eq!(body["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(body["messages"][1]["tool_plan"], json!("I will search."));
+        assert_eq!(body["messages"][2]["content"][0]["document"]["id"], json!("doc_0"));
+
+        let resp = CohereProvider
+            .parse_response(
+                &json!({
+                    "id": "c1",
+                    "finish_reason": "COMPLETE",
+                    "message": {
+                        "role": "assistant",
+                        "content": [{"type": "text", "text": "It is sunny."}],
+                        "citations": [
+                            {"start": 6, "end": 11, "text": "sunny", "sources": [
+                                {"type": "tool", "id": "doc_1", "tool_output": {"text": "Sunny in Paris"}},
+                                {"type": "tool", "id": "cohere-generated", "tool_output": {"text": "?"}}
+                            ]},
+                            {"text": "It", "sources": [{"type": "tool", "id": "doc_0"}]}
+                        ]
+                    },
+                    "usage": {"tokens": {"input_tokens": 20, "output_tokens": 4}}
+                })
+                .to_string(),
+            )
+            .expect("parse");
+        let anthropic = serde_json::to_value(openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate")).unwrap();
+        assert_eq!(anthropic["content"][1]["text"], json!("sunny"));
+        assert_eq!(anthropic["content"][1]["citations"][0]["type"], json!("char_location"));
+        assert_eq!(anthropic["content"][1]["citations"][0]["document_index"], json!(1));
+        assert_eq!(anthropic["content"][1]["citations"].as_array().unwrap().len(), 1);
+        assert_eq!(anthropic["content"].as_array().unwrap().len(), 3);
+        assert_eq!(anthropic["usage"]["output_tokens"], json!(4));
+    }
+
+    #[test]
+    fn maps_stream_events_to_chunks() {
+        let event = |data: Value| SseEvent {
+            event: None,
+            data: data.to_string(),
+            comment: false,
+        };
+        let start = CohereProvider.parse_stream_chunk(&event(json!({
+            "type": "tool-call-start",
+            "index": 1,
+            "delta": {"message": {"tool_calls": {"id": "t1", "type": "function", "function": {"name": "search", "arguments": ""}}}}
+        })));
+        let Ok(StreamEvent::Chunk(chunk)) = start else {
+            panic!("expected chunk");
+        };
+        assert_eq!(chunk.choices[0].delta.tool_calls.as_ref().unwrap()[0].index, 1);
+        assert!(matches!(
+            CohereProvider.parse_stream_chunk(&event(json!({"type": "citation-start", "index": 0}))),
+            Ok(StreamEvent::Skip)
+        ));
+        assert!(matches!(
+            CohereProvider.parse_stream_chunk(&event(json!({
+                "type": "tool-call-delta",
+                "delta": {"message": {"tool_calls": {"function": {"arguments": "{}"}}}}
+            }))),
+            Ok(StreamEvent::Skip)
+        ));
+        let end = CohereProvider.parse_stream_chunk(&event(json!({
+            "type": "message-end",
+            "delta": {"finish_reason": "TOOL_CALL", "usage": {"billed_units": {"input_tokens": 5, "output_tokens": 2}}}
+        })));
+        let Ok(StreamEvent::Final(chunk)) = end else {
+            panic!("expected final chunk");
+        };
+        assert_eq!(chunk.choices[0].finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(chunk.usage.map(|u| u.completion_tokens), Some(2));
+    }
+}
@@ -0,0 +1,258 @@
+use axum::http::HeaderMap;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::KeyValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::CompactionConfig;
+use crate::context::{replace_with_summary, summarize_transcript, transcript};
+use crate::models::{OpenAIMessage, OpenAIRequest};
+use crate::state::AppState;
+
+#[derive(Clone)]
+struct CompactionMetrics {
+    runs: Counter<u64>,
+    cache_hits: Counter<u64>,
+    tokens_saved: Counter<u64>,
+    latency_ms: Histogram<f64>,
+}
+
+impl CompactionMetrics {
+    fn new() -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        Self {
+            runs: meter
+                .u64_counter("ai.gateway.compaction.runs")
+                .with_description("Conversation compactions by result")
+                .build(),
+            cache_hits: meter
+                .u64_counter("ai.gateway.compaction.cache_hits")
+                .with_description("Compactions served from the synopsis cache")
+                .build(),
+            tokens_saved: meter
+                .u64_counter("ai.gateway.compaction.tokens_saved")
+                .with_description("Estimated prompt tokens removed by compaction")
+                .build(),
+            latency_ms: meter
+                .f64_histogram("ai.gateway.compaction.latency_ms")
+                .with_unit("ms")
+                .with_description("Summary model latency in ms")
+                .build(),
+        }
+    }
+}
+
+struct Synopsis {
+    /// Number of non-system messages the synopsis covers.
+    covered: usize,
+    fingerprint: String,
+    text: String,
+    created: Instant,
+}
+
+/// Replaces earlier turns of long conversations with a system-level synopsis
+/// produced by a cheap model. Synopses are cached per conversation id and
+/// extended incrementally as the conversation grows.
+#[derive(Clone)]
+pub struct Compactor {
+    config: CompactionConfig,
+    cache: Arc<Mutex<HashMap<String, Synopsis>>>,
+    metrics: CompactionMetrics,
+}
+
+impl Compactor {
+    pub fn new(config: CompactionConfig) -> Self {
+        Self {
+            config,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            metrics: CompactionMetrics::new(),
+        }
+    }
+
+    /// Compacts `req` in place when it exceeds the threshold. Failures are
+    /// logged and leave the request unchanged.
+    pub async fn compact(&self, state: &AppState, req: &mut OpenAIRequest, headers: &HeaderMap) {
+        let tokenizer = state.tokenizers.for_model(&req.model);
+        let before = tokenizer.count_messages(&req.messages);
+        if before <= self.config.threshold_tokens {
+            return;
+        }
+        let start = req.system_messages;
+        let Some(split) = self.split_point(&req.messages[start..]).map(|i| start + i) else {
+            return;
+        };
+        let conversation_id = self.conversation_id(headers, &req.model, &req.messages[start..]);
+        let history = &req.messages[start..split];
+
+        let cached = self.cached(&conversation_id, history);
+        let synopsis = match cached {
+            Some((covered, text)) if covered == history.len() => {
+                self.metrics.cache_hits.add(1, &[]);
+                text
+            }
+            cached => {
+                let (covered, previous) = cached.unwrap_or_default();
+                let mut input = String::new();
+                if !previous.is_empty() {
+                    input.push_str("Summary of the conversation so far:\n");
+                    input.push_str(&previous);
+                    input.push_str("\n\nLater messages:\n");
+                }
+                input.push_str(&transcript(&history[covered..]));
+                let started = Instant::now();
+                let result = summarize_transcript(
+                    state,
+                    &self.config.model,
+                    self.config.summary_max_tokens,
+                    input,
+                )
+                .await;
+                self.metrics
+                    .latency_ms
+                    .record(started.elapsed().as_millis() as f64, &[]);
+                match result {
+                    Ok(text) => {
+                        self.metrics.runs.add(1, &[KeyValue::new("result", "ok")]);
+                        self.store(conversation_id, history, text.clone());
+                        text
+                    }
+                    Err(err) => {
+                        self.metrics.runs.add(1, &[KeyValue::new("result", "error")]);
+                        tracing::warn!(
+                            conversation_id = %conversation_id,
+                            "conversation compaction failed: {}",
+                            err.message
+                        );
+                        return;
+                    }
+                }
+            }
+        };
+        let role = state.config.system_role(&req.model).to_string();
+        replace_with_summary(req, &role, split, synopsis);
+        let after = tokenizer.count_messages(&req.messages);
+        self.metrics
+            .tokens_saved
+            .add(before.saturating_sub(after), &[]);
+    }
+
+    /// Offset of the first kept message: the last `keep_recent_turns` user
+    /// turns (and everything after them) stay verbatim.
+    fn split_point(&self, messages: &[OpenAIMessage]) -> Option<usize> {
+        let user_turns: Vec<usize> = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.role == "user")
+            .map(|(i, _)| i)
+            .collect();
+        let keep = self.config.keep_recent_turns.max(1);
+        if user_turns.len() <= keep {
+            return None;
+        }
+        Some(user_turns[user_turns.len() - keep])
+    }
+
+    fn conversation_id(&self, headers: &HeaderMap, model: &str, messages: &[OpenAIMessage]) -> String {
+        if let Some(id) = headers
+            .get(self.config.conversation_header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+        {
+            return id.to_string();
+        }
+        let first = messages.first().map(|m| vec![m]).unwrap_or_default();
+        format!("{}:{}", model, fingerprint(&first))
+    }
+
+    fn cached(&self, conversation_id: &str, history: &[OpenAIMessage]) -> Option<(usize, String)> {
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = cache.get(conversation_id)?;
+        if entry.created.elapsed() > ttl {
+            cache.remove(conversation_id);
+            return None;
+        }
+        if entry.covered > history.len() {
+            return None;
+        }
+        let prefix: Vec<&OpenAIMessage> = history[..entry.covered].iter().collect();
+        (fingerprint(&prefix) == entry.fingerprint).then(|| (entry.covered, entry.text.clone()))
+    }
+
+    fn store(&self, conversation_id: String, history: &[OpenAIMessage], text: String) {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.len() >= self.config.cache_capacity && !cache.contains_key(&conversation_id) {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        let all: Vec<&OpenAIMessage> = history.iter().collect();
+        cache.insert(
+            conversation_id,
+            Synopsis {
+                covered: history.len(),
+                fingerprint: fingerprint(&all),
+                text,
+                created: Instant::now(),
+            },
+        );
+    }
+}
+
+fn fingerprint(messages: &[&OpenAIMessage]) -> String {
+    let mut hasher = Sha256::new();
+    for msg in messages {
+        hasher.update(serde_json::to_vec(msg).unwrap_or_default());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OpenAIMessageContent;
+
+    fn message(role: &str, text: &str) -> OpenAIMessage {
+        OpenAIMessage {
+            role: role.to_string(),
+            content: Some(OpenAIMessageContent::Text(text.to_string())),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cache_extends_only_matching_prefix() {
+        let compactor = Compactor::new(CompactionConfig {
+            keep_recent_turns: 1,
+            ..Default::default()
+        });
+        let history = vec![message("user", "a"), message("assistant", "b")];
+        compactor.store("conv".to_string(), &history, "summary".to_string());
+
+        let mut longer = history;
+        longer.push(message("user", "c"));
+        longer.push(message("assistant", "d"));
+        assert_eq!(
+            compactor.cached("conv", &longer),
+            Some((2, "summary".to_string()))
+        );
+
+        let edited = vec![message("user", "changed"), message("assistant", "b")];
+        assert_eq!(compactor.cached("conv", &edited), None);
+        assert_eq!(compactor.split_point(&longer), Some(2));
+        assert_eq!(compactor.split_point(&longer[..1]), None);
+    }
+}
@@ -1,9 +1,16 @@
 use serde::Deserialize;
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use crate::models::AnthropicModel;
+use crate::capture::CapturePolicy;
+use crate::models::{AnthropicModel, PromptTemplate};
+use crate::usage::parse_window;
+
+/// Slowest accepted `limits.output_tokens_per_sec`; anything slower would
+/// hold a stream for hours per token.
+const MIN_OUTPUT_TOKENS_PER_SEC: f64 = 0.01;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
@@ -14,12 +21,517 @@ pub struct Config {
     pub models: ModelsConfig,
     pub limits: LimitsConfig,
     pub observability: ObservabilityConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub usage: UsageConfig,
+    #[serde(default)]
+    pub postgres: Option<PostgresConfig>,
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+    #[serde(default)]
+    pub auxiliary: AuxiliaryConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    #[serde(default)]
+    pub tokenizers: TokenizersConfig,
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
+    #[serde(default)]
+    pub experiments: Vec<ExperimentConfig>,
+    #[serde(default)]
+    pub scoring: Option<ScoringConfig>,
+    #[serde(default)]
+    pub prompts: PromptsConfig,
+    #[serde(default)]
+    pub alerts: Option<AlertsConfig>,
+    #[serde(default)]
+    pub billing: Option<BillingConfig>,
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default)]
+    pub canary: Option<CanaryConfig>,
+}
+
+/// Dual-config mode: `percent` of ingress requests are served with the
+/// candidate config at `config_path` (model maps, downstream, policies)
+/// instead of this one; see `canary`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CanaryConfig {
+    /// A file or fragment directory, like `CONFIG_PATH`.
+    pub config_path: String,
+    #[serde(default)]
+    pub percent: f64,
+}
+
+/// `policy: rules` only applies `rules`; `policy: cost` falls back to the
+/// cheapest `cost.candidates` entry when no rule matches.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoutingConfig {
+    #[serde(default = "default_routing_policy")]
+    pub policy: String,
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+    #[serde(default)]
+    pub cost: Option<CostRoutingConfig>,
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            policy: default_routing_policy(),
+            rules: Vec::new(),
+            cost: None,
+        }
+    }
+}
+
+/// Sends requests matching every set condition to `model`, a downstream
+/// model name that replaces the `models.model_map` mapping. Experiment
+/// variants take precedence over routing.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoutingRule {
+    pub name: String,
+    pub model: String,
+    /// Client models the rule applies to; empty means all.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Estimated prompt tokens (system, messages, tool definitions).
+    #[serde(default)]
+    pub min_prompt_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_prompt_tokens: Option<u64>,
+    #[serde(default)]
+    pub has_tools: Option<bool>,
+    /// Images in messages or tool results.
+    #[serde(default)]
+    pub has_images: Option<bool>,
+    #[serde(default)]
+    pub min_thinking_budget: Option<u32>,
+    #[serde(default)]
+    pub system_regex: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CostRoutingConfig {
+    /// Client models routed by cost; empty means all.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Quality tiers from lowest to highest.
+    pub tiers: Vec<String>,
+    /// Minimum tier when the client sends no tier header; defaults to the
+    /// lowest tier.
+    #[serde(default)]
+    pub default_tier: Option<String>,
+    #[serde(default = "default_tier_header")]
+    pub tier_header: String,
+    pub candidates: Vec<CostCandidate>,
+}
+
+/// A downstream model the cost policy may pick.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CostCandidate {
+    pub model: String,
+    pub tier: String,
+    #[serde(default)]
+    pub input_per_mtok: f64,
+    #[serde(default)]
+    pub output_per_mtok: f64,
+    /// Prompt plus `max_tokens` must fit; unset means unlimited.
+    #[serde(default)]
+    pub context_window: Option<u64>,
+    #[serde(default = "default_candidate_tools")]
+    pub tools: bool,
+}
+
+/// Buffering between a streaming downstream and the client. With `block`
+/// a slow client stalls downstream reads once `channel_capacity` chunks are
+/// queued; `spill` and `disconnect` keep reading downstream and hold the
+/// overflow on disk or in memory, up to `max_buffered_bytes`, after which the
+/// client is disconnected with an error.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StreamingConfig {
+    #[serde(default = "default_stream_channel_capacity")]
+    pub channel_capacity: usize,
+    /// `block`, `spill` or `disconnect`.
+    #[serde(default = "default_slow_client")]
+    pub slow_client: String,
+    #[serde(default = "default_stream_max_buffered_bytes")]
+    pub max_buffered_bytes: u64,
+    #[serde(default = "default_stream_spill_dir")]
+    pub spill_dir: String,
+    /// Merges consecutive small text/thinking deltas into larger client
+    /// events; unset streams deltas as they arrive.
+    #[serde(default)]
+    pub coalesce: Option<CoalesceConfig>,
+    /// Splits text, thinking and tool argument deltas into client events of
+    /// at most this many bytes; unset forwards deltas whatever their size.
+    #[serde(default)]
+    pub max_event_bytes: Option<usize>,
+}
+
+/// A merged delta is sent once it reaches `max_bytes` of text or
+/// `max_delay_ms` after its first piece arrived, whichever comes first.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CoalesceConfig {
+    #[serde(default = "default_coalesce_max_bytes")]
+    pub max_bytes: usize,
+    #[serde(default = "default_coalesce_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: default_stream_channel_capacity(),
+            slow_client: default_slow_client(),
+            max_buffered_bytes: default_stream_max_buffered_bytes(),
+            spill_dir: default_stream_spill_dir(),
+            coalesce: None,
+            max_event_bytes: None,
+        }
+    }
+}
+
+/// Alert rules evaluated every `interval_secs` over the in-memory usage ring
+/// (`usage.ring_capacity` bounds how far back a window can see). Notifiers
+/// are called when a rule starts and stops breaching its threshold.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlertsConfig {
+    #[serde(default = "default_alerts_interval_secs")]
+    pub interval_secs: u64,
+    pub rules: Vec<AlertRule>,
+    pub notifiers: Vec<AlertNotifier>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    /// `error_rate` (percent), `p95_latency_ms` or `spend_usd`.
+    pub metric: String,
+    pub threshold: f64,
+    #[serde(default = "default_alert_window")]
+    pub window: String,
+    /// Restricts the rule to one client model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Rate and latency rules stay quiet below this many requests.
+    #[serde(default = "default_alert_min_requests")]
+    pub min_requests: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AlertNotifier {
+    /// `webhook` (JSON body) or `slack` (incoming webhook).
+    pub kind: String,
+    pub url: String,
+}
+
+/// Per-request usage events for a billing system, sent in batches by a
+/// background task independent of the audit log.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BillingConfig {
+    /// `webhook` (JSON `{"events": [...]}`) or `openmeter` (CloudEvents
+    /// batch, e.g. `https://openmeter.cloud/api/v1/events`).
+    #[serde(default = "default_billing_kind")]
+    pub kind: String,
+    pub url: String,
+    /// Sent as a bearer token.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_billing_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_billing_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+    /// Retries of a failed batch before it is dropped; the delay doubles
+    /// from `retry_backoff_ms` on each attempt.
+    #[serde(default = "default_billing_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_billing_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// Events waiting to be sent; further events are dropped.
+    #[serde(default = "default_billing_queue_capacity")]
+    pub queue_capacity: usize,
+    /// CloudEvents `type` and `source` for `openmeter`.
+    #[serde(default = "default_billing_event_type")]
+    pub event_type: String,
+    #[serde(default = "default_billing_source")]
+    pub source: String,
+}
+
+/// Prompt template registry served under `/v1/prompts`. Templates listed here
+/// are read-only unless `sqlite_path` is set, in which case they seed the
+/// database and new versions can be published through the API.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PromptsConfig {
+    #[serde(default)]
+    pub sqlite_path: Option<String>,
+    #[serde(default)]
+    pub templates: Vec<PromptTemplate>,
+}
+
+/// Quality scoring for shadow and experiment traffic. Shadow responses are
+/// scored against the primary response; experiment responses are scored by
+/// reference-free scorers only.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScoringConfig {
+    #[serde(default = "default_scoring_sample_percent")]
+    pub sample_percent: f64,
+    pub scorers: Vec<ScorerConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScorerConfig {
+    /// `exact_match`, `embedding` or `judge`.
+    pub kind: String,
+    /// Embedding or judge model.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// OpenAI-compatible base URL; defaults to `downstream.base_url`.
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+/// A/B experiment on one client model name. Each variant receives `percent`
+/// of traffic and is served by its own downstream model; unassigned traffic
+/// is the `control` group and keeps the usual model mapping.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExperimentConfig {
+    pub name: String,
+    pub model: String,
+    pub variants: Vec<ExperimentVariant>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExperimentVariant {
+    pub name: String,
+    pub model: String,
+    pub percent: f64,
+}
+
+/// Mirrors a share of `/v1/messages` traffic to a secondary downstream that
+/// speaks the same protocol as the primary one. Shadow responses are
+/// discarded; only metrics and a comparison log line are kept.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShadowConfig {
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Replaces the request model, e.g. a candidate model under evaluation.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default = "default_shadow_percent")]
+    pub percent: f64,
+    #[serde(default = "default_shadow_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Tokenizers used for `/v1/messages/count_tokens` and context limits, keyed
+/// by downstream model. Models without an entry use `default`, or a
+/// four-bytes-per-token heuristic when no default is set.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TokenizersConfig {
+    #[serde(default)]
+    pub default: Option<TokenizerSpec>,
+    #[serde(default)]
+    pub models: HashMap<String, TokenizerSpec>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TokenizerSpec {
+    /// `heuristic`, `tiktoken` or `huggingface`.
+    pub kind: String,
+    /// tiktoken encoding, e.g. `cl100k_base` or `o200k_base`.
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Path to a HuggingFace `tokenizer.json`.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Body compression between client, gateway and downstream. Gzip and brotli
+/// request bodies from clients are always accepted.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompressionConfig {
+    /// Advertise `accept-encoding` downstream and decompress responses before
+    /// translation and audit.
+    #[serde(default = "default_compression_downstream")]
+    pub downstream: bool,
+    /// Compress non-stream responses for clients that accept gzip or brotli.
+    #[serde(default)]
+    pub responses: bool,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            downstream: default_compression_downstream(),
+            responses: false,
+        }
+    }
+}
+
+/// Auxiliary endpoints that SDKs and CLI clients probe (organizations, key
+/// info). `responses` maps a GET path to its stub body; in passthrough mode
+/// the same paths are proxied to the Anthropic base URL instead.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuxiliaryConfig {
+    #[serde(default = "default_auxiliary_mode")]
+    pub mode: String,
+    #[serde(default = "default_auxiliary_responses")]
+    pub responses: HashMap<String, serde_json::Value>,
+}
+
+impl Default for AuxiliaryConfig {
+    fn default() -> Self {
+        Self {
+            mode: default_auxiliary_mode(),
+            responses: default_auxiliary_responses(),
+        }
+    }
+}
+
+/// Summarizes earlier turns of long translate-mode conversations with a
+/// cheap model before forwarding.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompactionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default = "default_compaction_threshold_tokens")]
+    pub threshold_tokens: u64,
+    #[serde(default = "default_compaction_keep_recent_turns")]
+    pub keep_recent_turns: usize,
+    #[serde(default = "default_summary_max_tokens")]
+    pub summary_max_tokens: u32,
+    #[serde(default = "default_compaction_cache_capacity")]
+    pub cache_capacity: usize,
+    #[serde(default = "default_compaction_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    #[serde(default = "default_compaction_conversation_header")]
+    pub conversation_header: String,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: String::new(),
+            threshold_tokens: default_compaction_threshold_tokens(),
+            keep_recent_turns: default_compaction_keep_recent_turns(),
+            summary_max_tokens: default_summary_max_tokens(),
+            cache_capacity: default_compaction_cache_capacity(),
+            cache_ttl_secs: default_compaction_cache_ttl_secs(),
+            conversation_header: default_compaction_conversation_header(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PostgresConfig {
+    pub url: String,
+    #[serde(default = "default_postgres_max_connections")]
+    pub max_connections: u32,
+    /// Most `request_summaries` rows one usage report reads; the newest are
+    /// kept when a window holds more.
+    #[serde(default = "default_postgres_max_usage_rows")]
+    pub max_usage_rows: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Serves the built-in dashboard at `/dashboard`; its data endpoints
+    /// need `token`.
+    #[serde(default)]
+    pub dashboard: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct UsageConfig {
+    #[serde(default = "default_usage_ring_capacity")]
+    pub ring_capacity: usize,
+    #[serde(default)]
+    pub sqlite_path: Option<String>,
+    #[serde(default)]
+    pub sink: Option<String>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            ring_capacity: default_usage_ring_capacity(),
+            sqlite_path: None,
+            sink: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ServerConfig {
     #[serde(default = "default_bind_addr")]
     pub bind_addr: String,
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    #[serde(default)]
+    pub signing: Option<SigningConfig>,
+    #[serde(default)]
+    pub ip_access: Option<IpAccessConfig>,
+}
+
+/// Network access control; see `IpAccess`. Entries are CIDRs or single
+/// addresses.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct IpAccessConfig {
+    /// Empty allows every address not denied.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Proxies whose `X-Forwarded-For` is trusted to name the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    #[serde(default)]
+    pub max_inflight_per_ip: Option<usize>,
+}
+
+/// HMAC request signing required from callers; see `RequestVerifier`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SigningConfig {
+    pub secret: String,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+    /// Accepted clock skew either way; signatures are remembered this long
+    /// to reject replays.
+    #[serde(default = "default_replay_window_secs")]
+    pub replay_window_secs: u64,
+    #[serde(default = "default_signing_exempt_paths")]
+    pub exempt_paths: Vec<String>,
+    /// Largest body buffered for verification; larger requests get a 413
+    /// before any of it is checked.
+    #[serde(default = "default_signing_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+/// CORS for browser clients. `allowed_origins` entries are exact origins or
+/// a single `"*"`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -36,24 +548,292 @@ pub struct DownstreamConfig {
     pub connect_timeout_ms: u64,
     #[serde(default = "default_read_timeout_ms")]
     pub read_timeout_ms: u64,
+    /// Streaming calls fail with a retryable 504 when no body byte has
+    /// arrived this long after the request was sent; unset waits as long as
+    /// the downstream takes.
+    #[serde(default)]
+    pub first_byte_timeout_ms: Option<u64>,
+    /// Extra attempts for a streaming call that fails retryably before any
+    /// event has reached the client.
+    #[serde(default)]
+    pub stream_retries: u32,
+    /// Backoff before the first stream retry, doubled for each further one.
+    #[serde(default = "default_stream_retry_backoff_ms")]
+    pub stream_retry_backoff_ms: u64,
     #[serde(default = "default_pool_max_idle_per_host")]
     pub pool_max_idle_per_host: usize,
+    /// Idle pooled connections are closed after this long.
+    #[serde(default = "default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    /// Rebuild the downstream clients this often, dropping every pooled
+    /// connection so hosts are resolved again (for DNS-based failover).
+    #[serde(default)]
+    pub pool_recycle_secs: Option<u64>,
+    /// Pool and timeouts of the client used for streaming calls.
+    #[serde(default)]
+    pub stream: StreamClientConfig,
+    /// Wire format of the downstream chat API in `translate` mode; a name
+    /// registered in the `ProviderRegistry` (built in: `openai`).
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    /// Base URL and API key per provider name; providers without an entry
+    /// use `base_url` and `api_key` above.
+    #[serde(default)]
+    pub provider_endpoints: HashMap<String, ProviderEndpoint>,
+    /// Replicas serving the same models in `translate` mode. Requests for a
+    /// model one or more entries list go to the healthiest of them instead
+    /// of the provider's base URL.
+    #[serde(default)]
+    pub endpoints: Vec<DownstreamEndpoint>,
+    #[serde(default)]
+    pub endpoint_health: EndpointHealthConfig,
+    /// Downstream stream chunk parsing: `strict` aborts on the first unparseable
+    /// chunk, `lenient` skips it.
+    #[serde(default = "default_stream_parsing")]
+    pub stream_parsing: String,
+    /// Consecutive unparseable chunks `lenient` tolerates before aborting.
+    #[serde(default = "default_max_malformed_chunks")]
+    pub max_malformed_chunks: u32,
+    /// Outbound `User-Agent` on every downstream call, replacing the
+    /// client's in passthrough.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Extra headers on every downstream call. Values may use
+    /// `{{ request_id }}`, `{{ key_id }}`, `{{ model }}` and
+    /// `{{ header.<name> }}` (a client request header).
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Bearer tokens from an OAuth2 token endpoint instead of `api_key`
+    /// (`translate` mode).
+    #[serde(default)]
+    pub oauth: Option<OAuthConfig>,
+    /// Several active keys used in place of `api_key` (`translate` mode);
+    /// see `KeyPool`.
+    #[serde(default)]
+    pub api_keys: Vec<DownstreamKey>,
+    /// `round_robin` or `failover` (next key on 401 / 429).
+    #[serde(default = "default_key_rotation")]
+    pub key_rotation: String,
+    /// Connections opened to every downstream host at startup, before the
+    /// gateway accepts requests; see `warmup`.
+    #[serde(default)]
+    pub warmup: Option<WarmupConfig>,
+    /// Output cap field the `openai` provider sends: `max_completion_tokens`,
+    /// `max_tokens` (older vLLM, llama.cpp, LM Studio) or `both`.
+    #[serde(default = "default_max_tokens_field")]
+    pub max_tokens_field: String,
+}
+
+/// The streaming client: SSE connections stay open for a whole generation,
+/// so they get their own pool and an idle read timeout instead of a total
+/// one. Unset pool sizes and timeouts fall back to the `downstream` ones.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StreamClientConfig {
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Aborts a stream when no bytes arrive for this long between reads;
+    /// unset waits indefinitely.
+    #[serde(default)]
+    pub read_idle_timeout_ms: Option<u64>,
+    #[serde(default = "default_stream_tcp_nodelay")]
+    pub tcp_nodelay: bool,
+}
+
+impl Default for StreamClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: None,
+            pool_idle_timeout_secs: None,
+            connect_timeout_ms: None,
+            read_idle_timeout_ms: None,
+            tcp_nodelay: default_stream_tcp_nodelay(),
+        }
+    }
+}
+
+/// Startup warmup: `connections` concurrent requests per downstream origin
+/// on each HTTP client, so that many handshakes are done and pooled. Without
+/// `probe_path` the request is a `HEAD /`; any response counts.
+#[derive(Clone, Debug, Deserialize)]
+pub struct WarmupConfig {
+    #[serde(default = "default_warmup_connections")]
+    pub connections: usize,
+    /// E.g. `/v1/models`: a `GET` whose status is logged per host.
+    #[serde(default)]
+    pub probe_path: Option<String>,
+    /// Upper bound on the whole warmup; startup continues afterwards.
+    #[serde(default = "default_warmup_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DownstreamKey {
+    /// Name in metrics and the admin API; never the key itself.
+    pub id: String,
+    pub key: String,
+}
+
+/// OAuth2 client-credentials grant against `token_url`. `auth_method` is
+/// `basic` (HTTP Basic client authentication) or `post` (credentials in the
+/// form body).
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthConfig {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+    #[serde(default = "default_oauth_auth_method")]
+    pub auth_method: String,
+    /// Tokens are refreshed this long before they expire.
+    #[serde(default = "default_oauth_refresh_margin_secs")]
+    pub refresh_margin_secs: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProviderEndpoint {
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Overrides `downstream.max_tokens_field` for this provider.
+    #[serde(default)]
+    pub max_tokens_field: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DownstreamEndpoint {
+    pub base_url: String,
+    /// Defaults to the provider's API key.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Downstream models served; empty means all.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Overrides the provider's `max_tokens_field` for this replica.
+    #[serde(default)]
+    pub max_tokens_field: Option<String>,
+}
+
+/// Endpoints are ranked by p95 latency (to response headers) over the last
+/// `window` requests. One whose error rate exceeds `max_error_rate` after
+/// `min_samples` requests is demoted and only receives a probe request every
+/// `probe_interval_secs`; a successful probe restores it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EndpointHealthConfig {
+    #[serde(default = "default_endpoint_window")]
+    pub window: usize,
+    #[serde(default = "default_endpoint_min_samples")]
+    pub min_samples: usize,
+    #[serde(default = "default_endpoint_max_error_rate")]
+    pub max_error_rate: f64,
+    #[serde(default = "default_endpoint_probe_interval_secs")]
+    pub probe_interval_secs: u64,
+}
+
+impl Default for EndpointHealthConfig {
+    fn default() -> Self {
+        Self {
+            window: default_endpoint_window(),
+            min_samples: default_endpoint_min_samples(),
+            max_error_rate: default_endpoint_max_error_rate(),
+            probe_interval_secs: default_endpoint_probe_interval_secs(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct AnthropicConfig {
     #[serde(default = "default_forward_mode")]
     pub forward_mode: String,
+    #[serde(default)]
+    pub vertex: Option<VertexConfig>,
+    /// Proxy any `/v1/*` route the gateway does not handle itself (passthrough only).
+    #[serde(default)]
+    pub passthrough_unknown: bool,
+    /// Re-frame passthrough SSE into spec-shaped Anthropic events (passthrough only).
+    #[serde(default)]
+    pub sse_normalize: bool,
+    /// Apply `models.model_map` and `rewrite.max_tokens` to passthrough
+    /// requests, editing only those two values (passthrough only).
+    #[serde(default)]
+    pub passthrough_rewrite: bool,
+    /// Request rewriting for `forward_mode: rewrite`.
+    #[serde(default)]
+    pub rewrite: RewriteConfig,
+}
+
+/// Rules `forward_mode: rewrite` applies to each parsed Anthropic request
+/// before re-serializing it for the Anthropic downstream.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RewriteConfig {
+    /// Upper bound for `max_tokens`.
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub min_temperature: Option<f32>,
+    #[serde(default)]
+    pub max_temperature: Option<f32>,
+    /// Upper bound for `thinking.budget_tokens`.
+    #[serde(default)]
+    pub max_thinking_budget: Option<u32>,
+    /// Added before / after the request's system prompt.
+    #[serde(default)]
+    pub system_prefix: Option<String>,
+    #[serde(default)]
+    pub system_suffix: Option<String>,
+    /// `document` blocks: pass, reject, strip or text_only.
+    #[serde(default = "default_rewrite_documents")]
+    pub documents: String,
+}
+
+impl Default for RewriteConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: None,
+            min_temperature: None,
+            max_temperature: None,
+            max_thinking_budget: None,
+            system_prefix: None,
+            system_suffix: None,
+            documents: default_rewrite_documents(),
+        }
+    }
 }
 
 impl Default for AnthropicConfig {
     fn default() -> Self {
         Self {
             forward_mode: default_forward_mode(),
+            vertex: None,
+            passthrough_unknown: false,
+            sse_normalize: false,
+            passthrough_rewrite: false,
+            rewrite: RewriteConfig::default(),
         }
     }
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct VertexConfig {
+    pub project_id: String,
+    #[serde(default = "default_vertex_region")]
+    pub region: String,
+    #[serde(default)]
+    pub credentials_path: Option<String>,
+    #[serde(default)]
+    pub access_token: Option<String>,
+    #[serde(default = "default_vertex_anthropic_version")]
+    pub anthropic_version: String,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ModelsConfig {
     #[serde(default)]
@@ -72,34 +852,244 @@ pub struct ModelsConfig {
     pub allow_images: bool,
     #[serde(default = "default_document_policy")]
     pub document_policy: String,
+    #[serde(default = "default_prefill_mode")]
+    pub prefill_mode: String,
+    /// How `is_error: true` on a `tool_result` reaches the tool message:
+    /// `prefix`, `json` or `drop` (translate only).
+    #[serde(default = "default_tool_error")]
+    pub tool_error: String,
+    #[serde(default = "default_tool_error_prefix")]
+    pub tool_error_prefix: String,
+    /// Built-in tools (`computer_*`, `text_editor_*`, `bash_*`) in
+    /// translate mode: `reject`, `strip` or `map` to function tools.
+    #[serde(default = "default_builtin_tools")]
+    pub builtin_tools: String,
+    /// Downstream reply with no text, reasoning or tool calls (translate):
+    /// `error` (400 `api_error`, the default), `empty_text` (one empty text block) or
+    /// `empty` (`content: []`).
+    #[serde(default = "default_empty_content")]
+    pub empty_content: String,
+    /// Appends an empty text block to replies that carry only thinking
+    /// (translate, streaming and not), for clients that expect text.
+    #[serde(default)]
+    pub reasoning_only_text: bool,
+    #[serde(default)]
+    pub sanitize_messages: bool,
+    /// Reissues downstream tool call ids as unique `toolu_` ids (translate).
+    #[serde(default)]
+    pub normalize_tool_ids: bool,
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Gateway text around the client's system prompt (translate only).
+    #[serde(default)]
+    pub system_prompt: SystemPromptConfig,
     #[serde(default)]
     pub models_override: Option<Vec<AnthropicModel>>,
+    #[serde(default)]
+    pub vllm_params: HashMap<String, serde_json::Map<String, serde_json::Value>>,
+    /// Downstream model -> role the Anthropic system prompt is sent as:
+    /// `system` (default), `developer` (OpenAI o-series) or `user` for
+    /// backends without a system role (translate only).
+    #[serde(default)]
+    pub system_role: HashMap<String, String>,
+    /// Downstream model -> `response_format: json_schema` support: `native`,
+    /// `auto` (default; falls back when the downstream rejects it) or `none`
+    /// (always falls back to `json_object` plus the schema in the prompt).
+    #[serde(default)]
+    pub json_schema_support: HashMap<String, String>,
+    /// Downstream model -> what it supports, over the built-in table of
+    /// known models when `builtin_capabilities` is on; see `capabilities`
+    /// (translate only).
+    #[serde(default)]
+    pub capabilities: HashMap<String, ModelCapabilities>,
+    /// Applies the built-in table of known models (limits, clamping,
+    /// dropped `reasoning_effort`) to models the config does not describe.
+    #[serde(default)]
+    pub builtin_capabilities: bool,
+    #[serde(default)]
+    pub overrides: HashMap<String, serde_json::Value>,
+    /// Request fields the gateway does not translate (e.g. `service_tier`)
+    /// copied as is into the downstream request in translate mode; any other
+    /// unknown field is dropped.
+    #[serde(default)]
+    pub forward_fields: Vec<String>,
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPricing>,
+    /// Downstream model -> provider name, for models not served by
+    /// `downstream.provider` (translate only).
+    #[serde(default)]
+    pub provider_map: HashMap<String, String>,
+}
+
+/// Per-model context limits (estimated tokens, prompt plus max_tokens) and the
+/// policy applied when a translated request exceeds them.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ContextConfig {
+    #[serde(default)]
+    pub limits: HashMap<String, u64>,
+    #[serde(default = "default_context_policy")]
+    pub policy: String,
+    #[serde(default)]
+    pub summary_model: Option<String>,
+    #[serde(default = "default_summary_max_tokens")]
+    pub summary_max_tokens: u32,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            limits: HashMap::new(),
+            policy: default_context_policy(),
+            summary_model: None,
+            summary_max_tokens: default_summary_max_tokens(),
+        }
+    }
+}
+
+/// The translated system message is composed as `prefix`, the client's
+/// system prompt (after any prompt template), then `suffix`, in that order
+/// whatever adds them; see `system_prompt`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SystemPromptConfig {
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    /// Upper bound for the composed prompt in bytes; longer requests get a 400.
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
+}
+
+/// What a downstream model accepts; unset fields fall back to the built-in
+/// table, then to unknown (not checked).
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub struct ModelCapabilities {
+    #[serde(default)]
+    pub tools: Option<bool>,
+    #[serde(default)]
+    pub images: Option<bool>,
+    /// `response_format: json_schema`.
+    #[serde(default)]
+    pub json_schema: Option<bool>,
+    /// `reasoning_effort`.
+    #[serde(default)]
+    pub reasoning: Option<bool>,
+    /// Context window in tokens: the estimated prompt plus the requested
+    /// `max_tokens` must fit, as for `models.context.limits`.
+    #[serde(default)]
+    pub max_context: Option<u64>,
+    #[serde(default)]
+    pub max_output: Option<u32>,
+}
+
+/// USD prices per million tokens.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelPricing {
+    #[serde(default)]
+    pub input_per_mtok: f64,
+    #[serde(default)]
+    pub output_per_mtok: f64,
+}
+
+impl ModelPricing {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_mtok + output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct LimitsConfig {
     #[serde(default = "default_max_inflight")]
     pub max_inflight: usize,
+    /// Deadline for non-stream `/v1/messages` requests, on top of
+    /// `downstream.read_timeout_ms`. Unset means no deadline.
+    #[serde(default)]
+    pub max_duration_ms: Option<u64>,
+    /// Per client model deadlines; override `max_duration_ms`.
+    #[serde(default)]
+    pub max_duration_models: HashMap<String, u64>,
+    /// Per key deadlines keyed by `key_id` (as in the access log); override
+    /// model deadlines.
+    #[serde(default)]
+    pub max_duration_keys: HashMap<String, u64>,
+    /// Streamed output cap in tokens per second (at least 0.01), shared by
+    /// each key's concurrent streams. Unset means unpaced.
+    #[serde(default)]
+    pub output_tokens_per_sec: Option<f64>,
+    /// Per key output caps keyed by `key_id`; override
+    /// `output_tokens_per_sec`.
+    #[serde(default)]
+    pub output_tokens_per_sec_keys: HashMap<String, f64>,
+    /// Prompt token budget per minute, counted with `tokenizers` before the
+    /// request is forwarded. Unset means unlimited.
+    #[serde(default)]
+    pub input_tokens_per_minute: Option<u64>,
+    /// Per key budgets keyed by `key_id`; override
+    /// `input_tokens_per_minute`.
+    #[serde(default)]
+    pub input_tokens_per_minute_keys: HashMap<String, u64>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ObservabilityConfig {
     #[serde(default = "default_service_name")]
     pub service_name: String,
-    #[serde(default)]
-    pub dump_downstream: bool,
+    #[serde(default = "default_capture_policy")]
+    pub capture: String,
     #[serde(default)]
     pub audit_log: AuditLogConfig,
     #[serde(default)]
+    pub debug_capture: DebugCaptureConfig,
+    #[serde(default)]
+    pub transcripts: TranscriptsConfig,
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    #[serde(default)]
     pub logging: LoggingConfig,
     #[serde(default)]
     pub otlp_grpc: OtlpGrpcConfig,
     #[serde(default)]
     pub otlp_http: OtlpHttpConfig,
     #[serde(default)]
+    pub langfuse: LangfuseConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
     pub exporters: ExportersConfig,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct MetricsConfig {
+    /// How often the periodic reader exports.
+    #[serde(default = "default_metrics_export_interval_ms")]
+    pub export_interval_ms: u64,
+    /// Bucket boundaries (ms) for `ai.gateway.latency_ms`; the SDK default
+    /// (0 to 10000) when unset.
+    #[serde(default)]
+    pub latency_buckets_ms: Option<Vec<f64>>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            export_interval_ms: default_metrics_export_interval_ms(),
+            latency_buckets_ms: None,
+        }
+    }
+}
+
+/// Langfuse trace attribution beyond what the request payload carries.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LangfuseConfig {
+    /// Request header whose value becomes `langfuse.session.id`, taking
+    /// precedence over `metadata.session_id`.
+    #[serde(default)]
+    pub session_header: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct OtlpGrpcConfig {
     #[serde(default = "default_otlp_endpoint")]
@@ -118,76 +1108,241 @@ impl Default for OtlpGrpcConfig {
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct OtlpHttpConfig {
-    #[serde(default = "default_langfuse_http_base_url")]
-    pub base_url: String,
-    #[serde(default)]
-    pub public_key: String,
+pub struct OtlpHttpConfig {
+    #[serde(default = "default_langfuse_http_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub secret_key: String,
+    #[serde(default = "default_langfuse_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for OtlpHttpConfig {
+    fn default() -> Self {
+        Self {
+            base_url: default_langfuse_http_base_url(),
+            public_key: String::new(),
+            secret_key: String::new(),
+            timeout_ms: default_langfuse_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ExportersConfig {
+    #[serde(default = "default_exporter_kind")]
+    pub tracing: String,
+    #[serde(default = "default_exporter_kind")]
+    pub metrics: String,
+}
+
+impl Default for ExportersConfig {
+    fn default() -> Self {
+        Self {
+            tracing: default_exporter_kind(),
+            metrics: default_exporter_kind(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default = "default_log_format")]
+    pub format: String,
+    #[serde(default = "default_log_stdout")]
+    pub stdout: bool,
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AuditLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default = "default_audit_max_body_bytes")]
+    pub max_body_bytes: usize,
+    #[serde(default = "default_audit_max_file_bytes")]
+    pub max_file_bytes: u64,
+    #[serde(default = "default_audit_sink")]
+    pub sink: String,
+    #[serde(default)]
+    pub s3: Option<S3AuditConfig>,
+    #[serde(default)]
+    pub overflow: AuditOverflowConfig,
+    /// Raw SSE kept for translate streams next to the synthesized body:
+    /// `none`, `downstream`, `client` or `both`, each capped at
+    /// `max_body_bytes`.
+    #[serde(default = "default_audit_stream_sse")]
+    pub stream_sse: String,
+}
+
+/// Full capture of single requests into a dedicated file, triggered by a
+/// secret request header or by flagging a client key via the admin API.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DebugCaptureConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_debug_capture_header")]
+    pub header: String,
+    /// Value `header` must carry; without it only flagged keys are captured.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default = "default_debug_capture_path")]
+    pub path: String,
+    /// Cap for each captured SSE stream.
+    #[serde(default = "default_debug_capture_max_body_bytes")]
+    pub max_body_bytes: usize,
+    #[serde(default = "default_debug_capture_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+impl Default for DebugCaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            header: default_debug_capture_header(),
+            secret: None,
+            path: default_debug_capture_path(),
+            max_body_bytes: default_debug_capture_max_body_bytes(),
+            max_file_bytes: default_debug_capture_max_file_bytes(),
+        }
+    }
+}
+
+/// Stored client SSE transcripts with chunk timing, replayable through
+/// `/admin/transcripts/{request_id}`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TranscriptsConfig {
     #[serde(default)]
-    pub secret_key: String,
-    #[serde(default = "default_langfuse_timeout_ms")]
-    pub timeout_ms: u64,
+    pub enabled: bool,
+    /// One gzip file per recorded stream: `<dir>/<request_id>.json.gz`.
+    #[serde(default = "default_transcripts_dir")]
+    pub dir: String,
+    #[serde(default = "default_transcripts_sample_percent")]
+    pub sample_percent: f64,
+    /// Recording stops (and the transcript is marked truncated) past this.
+    #[serde(default = "default_transcripts_max_bytes")]
+    pub max_bytes: usize,
+    /// Retention, enforced after every write: older files are removed first,
+    /// then the oldest past `max_files`.
+    #[serde(default = "default_transcripts_max_files")]
+    pub max_files: usize,
+    #[serde(default = "default_transcripts_max_age_secs")]
+    pub max_age_secs: u64,
 }
 
-impl Default for OtlpHttpConfig {
+impl Default for TranscriptsConfig {
     fn default() -> Self {
         Self {
-            base_url: default_langfuse_http_base_url(),
-            public_key: String::new(),
-            secret_key: String::new(),
-            timeout_ms: default_langfuse_timeout_ms(),
+            enabled: false,
+            dir: default_transcripts_dir(),
+            sample_percent: default_transcripts_sample_percent(),
+            max_bytes: default_transcripts_max_bytes(),
+            max_files: default_transcripts_max_files(),
+            max_age_secs: default_transcripts_max_age_secs(),
         }
     }
 }
 
+/// What `AuditLogger::push` does when the record queue is full.
 #[derive(Clone, Debug, Deserialize)]
-pub struct ExportersConfig {
-    #[serde(default = "default_exporter_kind")]
-    pub tracing: String,
-    #[serde(default = "default_exporter_kind")]
-    pub metrics: String,
+pub struct AuditOverflowConfig {
+    /// `block` (wait up to `block_timeout_ms`, then drop), `drop_oldest`, or
+    /// `spill` (append the record to `spill_path`).
+    #[serde(default = "default_audit_overflow_policy")]
+    pub policy: String,
+    #[serde(default = "default_audit_queue_capacity")]
+    pub queue_capacity: usize,
+    #[serde(default = "default_audit_block_timeout_ms")]
+    pub block_timeout_ms: u64,
+    #[serde(default = "default_audit_spill_path")]
+    pub spill_path: String,
 }
 
-impl Default for ExportersConfig {
+impl Default for AuditOverflowConfig {
     fn default() -> Self {
         Self {
-            tracing: default_exporter_kind(),
-            metrics: default_exporter_kind(),
+            policy: default_audit_overflow_policy(),
+            queue_capacity: default_audit_queue_capacity(),
+            block_timeout_ms: default_audit_block_timeout_ms(),
+            spill_path: default_audit_spill_path(),
         }
     }
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct LoggingConfig {
-    #[serde(default = "default_log_level")]
-    pub level: String,
-    #[serde(default = "default_log_format")]
-    pub format: String,
-    #[serde(default = "default_log_stdout")]
-    pub stdout: bool,
-    #[serde(default)]
-    pub file: Option<String>,
+pub struct S3AuditConfig {
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    #[serde(default = "default_s3_prefix")]
+    pub prefix: String,
+    #[serde(default = "default_s3_spill_dir")]
+    pub spill_dir: String,
+    #[serde(default = "default_s3_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: None,
+            max_body_bytes: default_audit_max_body_bytes(),
+            max_file_bytes: default_audit_max_file_bytes(),
+            sink: default_audit_sink(),
+            s3: None,
+            overflow: AuditOverflowConfig::default(),
+            stream_sse: default_audit_stream_sse(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
-pub struct AuditLogConfig {
+pub struct AccessLogConfig {
     #[serde(default)]
     pub enabled: bool,
     #[serde(default)]
     pub path: Option<String>,
-    #[serde(default = "default_audit_max_body_bytes")]
-    pub max_body_bytes: usize,
-    #[serde(default = "default_audit_max_file_bytes")]
-    pub max_file_bytes: u64,
+    #[serde(default = "default_access_log_fields")]
+    pub fields: Vec<String>,
 }
 
-impl Default for AuditLogConfig {
+impl Default for AccessLogConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             path: None,
-            max_body_bytes: default_audit_max_body_bytes(),
-            max_file_bytes: default_audit_max_file_bytes(),
+            fields: default_access_log_fields(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TracingConfig {
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+    #[serde(default)]
+    pub stream_sample_ratio: Option<f64>,
+    #[serde(default = "default_always_sample_errors")]
+    pub always_sample_errors: bool,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            sample_ratio: default_sample_ratio(),
+            stream_sample_ratio: None,
+            always_sample_errors: default_always_sample_errors(),
         }
     }
 }
@@ -210,18 +1365,113 @@ pub enum DocumentPolicy {
     TextOnly,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContextPolicy {
+    Reject,
+    DropOldest,
+    Summarize,
+}
+
+/// How a trailing assistant message (prefill) is sent downstream in translate mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefillMode {
+    /// Forward the assistant message as-is.
+    None,
+    /// Forward it with vLLM continuation flags so the model continues the turn.
+    Continue,
+    /// Drop it and ask the model to start its reply with the prefill text.
+    Suffix,
+}
+
+/// How translate sends `output_format` to a downstream model; see
+/// `structured_output`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonSchemaSupport {
+    /// Always `json_schema`; a rejection is returned to the client.
+    Native,
+    /// `json_schema`, resent once as the fallback when rejected.
+    Auto,
+    /// Always the fallback.
+    None,
+}
+
+/// What translate does with Anthropic built-in tools, which OpenAI-compatible
+/// downstreams do not know.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuiltInToolPolicy {
+    Reject,
+    Strip,
+    Map,
+}
+
+/// What translate returns when the downstream message has no content at all,
+/// which some backends send for a bare stop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyContentPolicy {
+    Error,
+    EmptyText,
+    Empty,
+}
+
+/// How a failed tool result (`is_error: true`) is marked in the OpenAI tool
+/// message, which has no error flag of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToolErrorMode {
+    /// Prepend `models.tool_error_prefix` to the content.
+    Prefix,
+    /// Wrap the content as `{"is_error": true, "content": ...}`.
+    Json,
+    /// Forward the content unmarked.
+    Drop,
+}
+
 impl Config {
-    pub fn from_env() -> Result<Self, String> {
-        let path = std::env::var("CONFIG_PATH")
-            .map_err(|_| "CONFIG_PATH is required (strict YAML)".to_string())?;
-        let content = fs::read_to_string(&path)
-            .map_err(|e| format!("CONFIG_PATH read error: {}", e))?;
-        let mut config: Config = serde_yaml::from_str(&content)
-            .map_err(|e| format!("CONFIG_PATH invalid yaml: {}", e))?;
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let path = Path::new(path);
+        let mut config: Config = if path.is_dir() {
+            serde_yaml::from_value(load_fragments(path)?)
+        } else {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("CONFIG_PATH read error: {}", e))?;
+            serde_yaml::from_str(&content)
+        }
+        .map_err(|e| format!("CONFIG_PATH invalid yaml: {}", e))?;
+        config.normalize()?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        let mut config: Config = serde_yaml::from_str(content)
+            .map_err(|e| format!("invalid yaml: {}", e))?;
         config.normalize()?;
         Ok(config)
     }
 
+    /// Provider name for a downstream model.
+    pub fn provider_name(&self, model: &str) -> &str {
+        self.models
+            .provider_map
+            .get(model)
+            .unwrap_or(&self.downstream.provider)
+    }
+
+    /// Base URL and API key the named provider talks to.
+    pub fn provider_endpoint(&self, provider: &str) -> (&str, Option<&str>) {
+        match self.downstream.provider_endpoints.get(provider) {
+            Some(endpoint) => (&endpoint.base_url, endpoint.api_key.as_deref()),
+            None => (&self.downstream.base_url, self.downstream.api_key.as_deref()),
+        }
+    }
+
+    /// `downstream.max_tokens_field`, unless the provider's endpoint overrides it.
+    pub fn max_tokens_field(&self, provider: &str) -> &str {
+        self.downstream
+            .provider_endpoints
+            .get(provider)
+            .and_then(|endpoint| endpoint.max_tokens_field.as_deref())
+            .unwrap_or(&self.downstream.max_tokens_field)
+    }
+
     pub fn chat_completions_url(&self) -> String {
         let base = self.downstream.base_url.trim_end_matches('/');
         if base.ends_with("/v1") {
@@ -249,6 +1499,12 @@ impl Config {
         }
     }
 
+    pub fn anthropic_url(&self, path: &str) -> String {
+        let base = self.downstream.base_url.trim_end_matches('/');
+        let base = base.strip_suffix("/v1").unwrap_or(base);
+        format!("{}{}", base, path)
+    }
+
     pub fn anthropic_models_url(&self) -> String {
         let base = self.downstream.base_url.trim_end_matches('/');
         if base.ends_with("/v1") {
@@ -258,10 +1514,41 @@ impl Config {
         }
     }
 
+    pub fn vertex_predict_url(&self, model: &str, stream: bool) -> Option<String> {
+        let vertex = self.anthropic.vertex.as_ref()?;
+        let endpoint = match vertex.endpoint.as_deref() {
+            Some(endpoint) => endpoint.trim_end_matches('/').to_string(),
+            None if vertex.region == "global" => "https://aiplatform.googleapis.com".to_string(),
+            None => format!("https://{}-aiplatform.googleapis.com", vertex.region),
+        };
+        let method = if stream { "streamRawPredict" } else { "rawPredict" };
+        Some(format!(
+            "{}/v1/projects/{}/locations/{}/publishers/anthropic/models/{}:{}",
+            endpoint, vertex.project_id, vertex.region, model, method
+        ))
+    }
+
     pub fn forward_mode(&self) -> &str {
         self.anthropic.forward_mode.as_str()
     }
 
+    pub fn capture_policy(&self) -> CapturePolicy {
+        CapturePolicy::parse(&self.observability.capture).unwrap_or(CapturePolicy::Full)
+    }
+
+    /// Usage persistence backend; defaults to sqlite when `usage.sqlite_path` is set.
+    pub fn usage_sink(&self) -> &str {
+        match self.usage.sink.as_deref() {
+            Some(sink) => sink,
+            None if self.usage.sqlite_path.is_some() => "sqlite",
+            None => "memory",
+        }
+    }
+
+    pub fn langfuse_tracing(&self) -> bool {
+        self.observability.exporters.tracing == "langfuse_http"
+    }
+
     pub fn document_policy(&self) -> Result<DocumentPolicy, String> {
         match self.models.document_policy.as_str() {
             "reject" => Ok(DocumentPolicy::Reject),
@@ -271,6 +1558,65 @@ impl Config {
         }
     }
 
+    pub fn prefill_mode(&self) -> PrefillMode {
+        match self.models.prefill_mode.as_str() {
+            "continue" => PrefillMode::Continue,
+            "suffix" => PrefillMode::Suffix,
+            _ => PrefillMode::None,
+        }
+    }
+
+    /// Role the system prompt is sent as to `model` (`models.system_role`).
+    pub fn system_role(&self, model: &str) -> &str {
+        self.models.system_role.get(model).map_or("system", String::as_str)
+    }
+
+    /// `models.json_schema_support`, else the model's `json_schema` capability.
+    pub fn json_schema_support(&self, model: &str) -> JsonSchemaSupport {
+        match self.models.json_schema_support.get(model).map(String::as_str) {
+            Some("native") => JsonSchemaSupport::Native,
+            Some("none") => JsonSchemaSupport::None,
+            Some(_) => JsonSchemaSupport::Auto,
+            None => match crate::capabilities::resolve(self, model).json_schema {
+                Some(true) => JsonSchemaSupport::Native,
+                Some(false) => JsonSchemaSupport::None,
+                None => JsonSchemaSupport::Auto,
+            },
+        }
+    }
+
+    pub fn builtin_tool_policy(&self) -> BuiltInToolPolicy {
+        match self.models.builtin_tools.as_str() {
+            "strip" => BuiltInToolPolicy::Strip,
+            "map" => BuiltInToolPolicy::Map,
+            _ => BuiltInToolPolicy::Reject,
+        }
+    }
+
+    pub fn empty_content_policy(&self) -> EmptyContentPolicy {
+        match self.models.empty_content.as_str() {
+            "empty_text" => EmptyContentPolicy::EmptyText,
+            "empty" => EmptyContentPolicy::Empty,
+            _ => EmptyContentPolicy::Error,
+        }
+    }
+
+    pub fn tool_error_mode(&self) -> ToolErrorMode {
+        match self.models.tool_error.as_str() {
+            "json" => ToolErrorMode::Json,
+            "drop" => ToolErrorMode::Drop,
+            _ => ToolErrorMode::Prefix,
+        }
+    }
+
+    pub fn context_policy(&self) -> ContextPolicy {
+        match self.models.context.policy.as_str() {
+            "drop_oldest" => ContextPolicy::DropOldest,
+            "summarize" => ContextPolicy::Summarize,
+            _ => ContextPolicy::Reject,
+        }
+    }
+
     pub fn thinking_map_pairs(&self) -> Vec<(u32, String)> {
         let mut entries: Vec<(u32, String)> = self
             .models
@@ -290,13 +1636,463 @@ impl Config {
         Duration::from_millis(self.downstream.read_timeout_ms)
     }
 
+    /// Deadline for a non-stream request: per key, then per model, then the
+    /// global `limits.max_duration_ms`.
+    pub fn max_request_duration(&self, model: &str, key_id: Option<&str>) -> Option<Duration> {
+        key_id
+            .and_then(|key| self.limits.max_duration_keys.get(key))
+            .or_else(|| self.limits.max_duration_models.get(model))
+            .or(self.limits.max_duration_ms.as_ref())
+            .map(|ms| Duration::from_millis(*ms))
+    }
+
+    pub fn output_tokens_per_sec(&self, key_id: Option<&str>) -> Option<f64> {
+        key_id
+            .and_then(|key| self.limits.output_tokens_per_sec_keys.get(key))
+            .or(self.limits.output_tokens_per_sec.as_ref())
+            .copied()
+    }
+
+    pub fn input_tokens_per_minute(&self, key_id: Option<&str>) -> Option<u64> {
+        key_id
+            .and_then(|key| self.limits.input_tokens_per_minute_keys.get(key))
+            .or(self.limits.input_tokens_per_minute.as_ref())
+            .copied()
+    }
+
     fn normalize(&mut self) -> Result<(), String> {
         self.anthropic.forward_mode = self.anthropic.forward_mode.to_lowercase();
         match self.anthropic.forward_mode.as_str() {
-            "passthrough" | "translate" => {}
+            "passthrough" | "translate" | "vertex" | "rewrite" => {}
             other => return Err(format!("anthropic.forward_mode invalid: {}", other)),
         }
-        if self.anthropic.forward_mode != "passthrough" {
+        if let Some(ip_access) = self.server.ip_access.as_ref() {
+            if ip_access.max_inflight_per_ip == Some(0) {
+                return Err("server.ip_access.max_inflight_per_ip must be > 0".to_string());
+            }
+            crate::ip_access::IpAccess::new(ip_access)?;
+        }
+        if let Some(signing) = self.server.signing.as_mut() {
+            if signing.secret.trim().is_empty() {
+                return Err("server.signing.secret is required".to_string());
+            }
+            if signing.replay_window_secs == 0 {
+                return Err("server.signing.replay_window_secs must be > 0".to_string());
+            }
+            if signing.max_body_bytes == 0 {
+                return Err("server.signing.max_body_bytes must be > 0".to_string());
+            }
+            signing.signature_header = signing.signature_header.to_lowercase();
+            signing.timestamp_header = signing.timestamp_header.to_lowercase();
+        }
+        if let Some(cors) = self.server.cors.as_ref() {
+            if cors.allowed_origins.is_empty() {
+                return Err("server.cors.allowed_origins must not be empty".to_string());
+            }
+            if cors.allowed_origins.len() > 1 && cors.allowed_origins.iter().any(|o| o == "*") {
+                return Err("server.cors.allowed_origins: \"*\" cannot be combined with other origins".to_string());
+            }
+        }
+        let default_tokenizer = self.tokenizers.default.iter_mut().map(|spec| ("default", spec));
+        let model_tokenizers = self.tokenizers.models.iter_mut().map(|(m, spec)| (m.as_str(), spec));
+        for (name, spec) in default_tokenizer.chain(model_tokenizers) {
+            spec.kind = spec.kind.to_lowercase();
+            match spec.kind.as_str() {
+                "heuristic" => {}
+                "tiktoken" if spec.encoding.is_some() => {}
+                "huggingface" if spec.path.is_some() => {}
+                "tiktoken" => return Err(format!("tokenizers.{}.encoding is required", name)),
+                "huggingface" => return Err(format!("tokenizers.{}.path is required", name)),
+                other => return Err(format!("tokenizers.{}.kind invalid: {}", name, other)),
+            }
+        }
+        if let Some(shadow) = self.shadow.as_ref() {
+            if shadow.base_url.trim().is_empty() {
+                return Err("shadow.base_url is required".to_string());
+            }
+            if !(0.0..=100.0).contains(&shadow.percent) {
+                return Err(format!("shadow.percent must be within 0..=100: {}", shadow.percent));
+            }
+        }
+        if self.admin.dashboard && self.admin.token.is_none() {
+            return Err("admin.dashboard requires admin.token".to_string());
+        }
+        if let Some(canary) = self.canary.as_ref() {
+            if canary.config_path.trim().is_empty() {
+                return Err("canary.config_path is required".to_string());
+            }
+            if !(0.0..=100.0).contains(&canary.percent) {
+                return Err(format!("canary.percent must be within 0..=100: {}", canary.percent));
+            }
+        }
+        let mut experiment_models = HashSet::new();
+        for experiment in &self.experiments {
+            if experiment.name.trim().is_empty() || experiment.name.contains('/') {
+                return Err(format!("experiments.name invalid: {:?}", experiment.name));
+            }
+            if !experiment_models.insert(experiment.model.as_str()) {
+                return Err(format!("experiments: model {} is used by more than one experiment", experiment.model));
+            }
+            let mut total = 0.0;
+            for variant in &experiment.variants {
+                if variant.name == "control" || variant.name.trim().is_empty() {
+                    return Err(format!("experiments.{}.variants.name invalid: {:?}", experiment.name, variant.name));
+                }
+                if variant.percent < 0.0 {
+                    return Err(format!("experiments.{}.variants.{}.percent must not be negative", experiment.name, variant.name));
+                }
+                total += variant.percent;
+            }
+            if total > 100.0 {
+                return Err(format!("experiments.{}: variant percents exceed 100", experiment.name));
+            }
+        }
+        if let Some(scoring) = self.scoring.as_mut() {
+            if !(0.0..=100.0).contains(&scoring.sample_percent) {
+                return Err(format!("scoring.sample_percent must be within 0..=100: {}", scoring.sample_percent));
+            }
+            for scorer in &mut scoring.scorers {
+                scorer.kind = scorer.kind.to_lowercase();
+                match scorer.kind.as_str() {
+                    "exact_match" => {}
+                    "embedding" | "judge" if scorer.model.is_some() => {}
+                    "embedding" | "judge" => {
+                        return Err(format!("scoring.scorers.model is required for kind={}", scorer.kind));
+                    }
+                    other => return Err(format!("scoring.scorers.kind invalid: {}", other)),
+                }
+            }
+        }
+        if self
+            .prompts
+            .sqlite_path
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            self.prompts.sqlite_path = None;
+        }
+        // Each database runs its own migration set.
+        if self.prompts.sqlite_path.is_some() && self.prompts.sqlite_path == self.usage.sqlite_path {
+            return Err("prompts.sqlite_path must differ from usage.sqlite_path".to_string());
+        }
+        let mut prompt_versions = HashSet::new();
+        for template in &self.prompts.templates {
+            if template.id.trim().is_empty() || template.id.contains('/') {
+                return Err(format!("prompts.templates.id invalid: {:?}", template.id));
+            }
+            if template.version == 0 {
+                return Err(format!("prompts.templates.{}.version must be > 0", template.id));
+            }
+            if !prompt_versions.insert((template.id.as_str(), template.version)) {
+                return Err(format!("prompts.templates: duplicate {} version {}", template.id, template.version));
+            }
+        }
+        if let Some(alerts) = self.alerts.as_mut() {
+            if alerts.interval_secs == 0 {
+                return Err("alerts.interval_secs must be > 0".to_string());
+            }
+            let mut rule_names = HashSet::new();
+            for rule in &mut alerts.rules {
+                if rule.name.trim().is_empty() || !rule_names.insert(rule.name.clone()) {
+                    return Err(format!("alerts.rules.name invalid or duplicated: {:?}", rule.name));
+                }
+                rule.metric = rule.metric.to_lowercase();
+                if !matches!(rule.metric.as_str(), "error_rate" | "p95_latency_ms" | "spend_usd") {
+                    return Err(format!("alerts.rules.{}.metric invalid: {}", rule.name, rule.metric));
+                }
+                parse_window(&rule.window)
+                    .map_err(|e| format!("alerts.rules.{}.window: {}", rule.name, e))?;
+            }
+            for notifier in &mut alerts.notifiers {
+                notifier.kind = notifier.kind.to_lowercase();
+                if !matches!(notifier.kind.as_str(), "webhook" | "slack") {
+                    return Err(format!("alerts.notifiers.kind invalid: {}", notifier.kind));
+                }
+                if notifier.url.trim().is_empty() {
+                    return Err("alerts.notifiers.url is required".to_string());
+                }
+            }
+        }
+        if let Some(billing) = self.billing.as_mut() {
+            billing.kind = billing.kind.to_lowercase();
+            if !matches!(billing.kind.as_str(), "webhook" | "openmeter") {
+                return Err(format!("billing.kind invalid: {}", billing.kind));
+            }
+            if billing.url.trim().is_empty() {
+                return Err("billing.url is required".to_string());
+            }
+            if billing.batch_size == 0 || billing.flush_interval_secs == 0 || billing.queue_capacity == 0 {
+                return Err("billing.batch_size, flush_interval_secs and queue_capacity must be > 0".to_string());
+            }
+        }
+        if self.limits.max_duration_ms == Some(0)
+            || self.limits.max_duration_models.values().any(|ms| *ms == 0)
+            || self.limits.max_duration_keys.values().any(|ms| *ms == 0)
+        {
+            return Err("limits.max_duration_* must be > 0".to_string());
+        }
+        self.downstream.provider = self.downstream.provider.to_lowercase();
+        self.downstream.provider_endpoints = std::mem::take(&mut self.downstream.provider_endpoints)
+            .into_iter()
+            .map(|(name, endpoint)| (name.to_lowercase(), endpoint))
+            .collect();
+        self.downstream.headers = std::mem::take(&mut self.downstream.headers)
+            .into_iter()
+            .map(|(name, value)| (name.to_lowercase(), value))
+            .collect();
+        crate::client_identity::validate(&self.downstream)?;
+        self.downstream.key_rotation = self.downstream.key_rotation.to_lowercase();
+        if !matches!(self.downstream.key_rotation.as_str(), "round_robin" | "failover") {
+            return Err(format!("downstream.key_rotation invalid: {}", self.downstream.key_rotation));
+        }
+        let mut key_ids = HashSet::new();
+        for key in &self.downstream.api_keys {
+            if key.id.trim().is_empty() || key.key.trim().is_empty() || !key_ids.insert(key.id.as_str()) {
+                return Err("downstream.api_keys: each entry needs a unique id and a key".to_string());
+            }
+        }
+        if self.downstream.oauth.is_some() && !self.downstream.api_keys.is_empty() {
+            return Err("downstream.oauth and downstream.api_keys cannot both be set".to_string());
+        }
+        if let Some(oauth) = self.downstream.oauth.as_mut() {
+            if oauth.token_url.trim().is_empty() || oauth.client_id.trim().is_empty() {
+                return Err("downstream.oauth: token_url and client_id are required".to_string());
+            }
+            oauth.auth_method = oauth.auth_method.to_lowercase();
+            if !matches!(oauth.auth_method.as_str(), "basic" | "post") {
+                return Err(format!("downstream.oauth.auth_method invalid: {}", oauth.auth_method));
+            }
+        }
+        if self.downstream.stream.connect_timeout_ms == Some(0) {
+            return Err("downstream.stream.connect_timeout_ms must be > 0".to_string());
+        }
+        if self.downstream.stream.read_idle_timeout_ms == Some(0) {
+            return Err("downstream.stream.read_idle_timeout_ms must be > 0".to_string());
+        }
+        if self.downstream.first_byte_timeout_ms == Some(0) {
+            return Err("downstream.first_byte_timeout_ms must be > 0".to_string());
+        }
+        if self.downstream.pool_recycle_secs == Some(0) {
+            return Err("downstream.pool_recycle_secs must be > 0".to_string());
+        }
+        if let Some(warmup) = self.downstream.warmup.as_ref() {
+            if warmup.connections == 0 || warmup.connections > self.downstream.pool_max_idle_per_host {
+                return Err(
+                    "downstream.warmup.connections must be within 1..=downstream.pool_max_idle_per_host".to_string(),
+                );
+            }
+            if warmup.probe_path.as_deref().is_some_and(|path| !path.starts_with('/')) {
+                return Err("downstream.warmup.probe_path must start with /".to_string());
+            }
+        }
+        if self.downstream.endpoints.iter().any(|e| e.base_url.trim().is_empty()) {
+            return Err("downstream.endpoints: base_url is required".to_string());
+        }
+        let max_tokens_fields = std::iter::once(&mut self.downstream.max_tokens_field)
+            .chain(
+                self.downstream
+                    .provider_endpoints
+                    .values_mut()
+                    .filter_map(|e| e.max_tokens_field.as_mut()),
+            )
+            .chain(self.downstream.endpoints.iter_mut().filter_map(|e| e.max_tokens_field.as_mut()));
+        for field in max_tokens_fields {
+            *field = field.to_lowercase();
+            if !matches!(field.as_str(), "max_completion_tokens" | "max_tokens" | "both") {
+                return Err(format!("downstream.max_tokens_field invalid: {}", field));
+            }
+        }
+        let health = &self.downstream.endpoint_health;
+        if health.window == 0 || health.min_samples > health.window {
+            return Err("downstream.endpoint_health: window must be > 0 and >= min_samples".to_string());
+        }
+        if !(0.0..=1.0).contains(&health.max_error_rate) {
+            return Err("downstream.endpoint_health.max_error_rate must be within 0..=1".to_string());
+        }
+        for provider in self.models.provider_map.values_mut() {
+            *provider = provider.to_lowercase();
+        }
+        self.downstream.stream_parsing = self.downstream.stream_parsing.to_lowercase();
+        if !matches!(self.downstream.stream_parsing.as_str(), "strict" | "lenient") {
+            return Err(format!(
+                "downstream.stream_parsing invalid: {}",
+                self.downstream.stream_parsing
+            ));
+        }
+        self.streaming.slow_client = self.streaming.slow_client.to_lowercase();
+        if !matches!(self.streaming.slow_client.as_str(), "block" | "spill" | "disconnect") {
+            return Err(format!("streaming.slow_client invalid: {}", self.streaming.slow_client));
+        }
+        if self
+            .limits
+            .output_tokens_per_sec
+            .iter()
+            .chain(self.limits.output_tokens_per_sec_keys.values())
+            .any(|rate| !rate.is_finite() || *rate < MIN_OUTPUT_TOKENS_PER_SEC)
+        {
+            return Err(format!(
+                "limits.output_tokens_per_sec must be a number >= {}",
+                MIN_OUTPUT_TOKENS_PER_SEC
+            ));
+        }
+        if self
+            .limits
+            .input_tokens_per_minute
+            .iter()
+            .chain(self.limits.input_tokens_per_minute_keys.values())
+            .any(|budget| *budget == 0)
+        {
+            return Err("limits.input_tokens_per_minute must be > 0".to_string());
+        }
+        if let Some(coalesce) = self.streaming.coalesce.as_ref()
+            && (coalesce.max_bytes == 0 || coalesce.max_delay_ms == 0)
+        {
+            return Err("streaming.coalesce: max_bytes and max_delay_ms must be > 0".to_string());
+        }
+        if self.streaming.max_event_bytes.is_some_and(|max| max < 256) {
+            return Err("streaming.max_event_bytes must be >= 256".to_string());
+        }
+        if self.streaming.channel_capacity == 0 {
+            return Err("streaming.channel_capacity must be > 0".to_string());
+        }
+        self.routing.policy = self.routing.policy.to_lowercase();
+        match (self.routing.policy.as_str(), self.routing.cost.as_mut()) {
+            ("rules", _) => {}
+            ("cost", Some(cost)) => {
+                cost.tier_header = cost.tier_header.to_lowercase();
+                if cost.tiers.is_empty() || cost.candidates.is_empty() {
+                    return Err("routing.cost: tiers and candidates are required".to_string());
+                }
+                let known = |tier: &str| cost.tiers.iter().any(|t| t == tier);
+                if let Some(tier) = cost.default_tier.as_deref()
+                    && !known(tier)
+                {
+                    return Err(format!("routing.cost.default_tier unknown: {}", tier));
+                }
+                for candidate in &cost.candidates {
+                    if !known(&candidate.tier) {
+                        return Err(format!(
+                            "routing.cost.candidates.{}.tier unknown: {}",
+                            candidate.model, candidate.tier
+                        ));
+                    }
+                }
+            }
+            ("cost", None) => return Err("routing.policy cost requires routing.cost".to_string()),
+            (policy, _) => return Err(format!("routing.policy invalid: {}", policy)),
+        }
+        for rule in &self.routing.rules {
+            if rule.name.trim().is_empty() || rule.model.trim().is_empty() {
+                return Err("routing.rules: name and model are required".to_string());
+            }
+        }
+        crate::routing::ModelRouter::new(&self.routing)?;
+        let rewrite = &mut self.anthropic.rewrite;
+        rewrite.documents = rewrite.documents.to_lowercase();
+        if !matches!(rewrite.documents.as_str(), "pass" | "reject" | "strip" | "text_only") {
+            return Err(format!("anthropic.rewrite.documents invalid: {}", rewrite.documents));
+        }
+        if let (Some(min), Some(max)) = (rewrite.min_temperature, rewrite.max_temperature)
+            && min > max
+        {
+            return Err("anthropic.rewrite.min_temperature must not exceed max_temperature".to_string());
+        }
+        if self.anthropic.passthrough_rewrite && self.anthropic.forward_mode != "passthrough" {
+            return Err("anthropic.passthrough_rewrite requires forward_mode=passthrough".to_string());
+        }
+        if self.anthropic.passthrough_unknown && self.anthropic.forward_mode != "passthrough" {
+            return Err("anthropic.passthrough_unknown requires forward_mode=passthrough".to_string());
+        }
+        if self.anthropic.forward_mode == "vertex" {
+            let vertex = self
+                .anthropic
+                .vertex
+                .as_mut()
+                .ok_or_else(|| "anthropic.vertex is required when forward_mode=vertex".to_string())?;
+            if vertex.project_id.trim().is_empty() {
+                return Err("anthropic.vertex.project_id is required".to_string());
+            }
+            if vertex.access_token.as_deref().is_some_and(|t| t.trim().is_empty()) {
+                vertex.access_token = None;
+            }
+            if vertex.access_token.is_none() && vertex.credentials_path.is_none() {
+                return Err(
+                    "anthropic.vertex.credentials_path or access_token is required".to_string()
+                );
+            }
+        }
+        self.models.prefill_mode = self.models.prefill_mode.to_lowercase();
+        match self.models.prefill_mode.as_str() {
+            "none" | "continue" | "suffix" => {}
+            other => return Err(format!("models.prefill_mode invalid: {}", other)),
+        }
+        self.models.tool_error = self.models.tool_error.to_lowercase();
+        match self.models.tool_error.as_str() {
+            "prefix" | "json" | "drop" => {}
+            other => return Err(format!("models.tool_error invalid: {}", other)),
+        }
+        self.models.builtin_tools = self.models.builtin_tools.to_lowercase();
+        match self.models.builtin_tools.as_str() {
+            "reject" | "strip" | "map" => {}
+            other => return Err(format!("models.builtin_tools invalid: {}", other)),
+        }
+        let system_prompt = &self.models.system_prompt;
+        let gateway_bytes = [&system_prompt.prefix, &system_prompt.suffix]
+            .into_iter()
+            .flatten()
+            .map(String::len)
+            .sum::<usize>();
+        if system_prompt.max_bytes.is_some_and(|max| max < gateway_bytes) {
+            return Err("models.system_prompt.max_bytes is smaller than prefix plus suffix".to_string());
+        }
+        self.models.empty_content = self.models.empty_content.to_lowercase();
+        match self.models.empty_content.as_str() {
+            "error" | "empty_text" | "empty" => {}
+            other => return Err(format!("models.empty_content invalid: {}", other)),
+        }
+        self.auxiliary.mode = self.auxiliary.mode.to_lowercase();
+        match self.auxiliary.mode.as_str() {
+            "disabled" | "stub" | "passthrough" => {}
+            other => return Err(format!("auxiliary.mode invalid: {}", other)),
+        }
+        for path in self.auxiliary.responses.keys() {
+            if !path.starts_with('/')
+                || path.contains('{')
+                || path == "/health"
+                || path.starts_with("/admin")
+                || path.starts_with("/v1/messages")
+                || path.starts_with("/v1/models")
+            {
+                return Err(format!("auxiliary.responses path invalid: {}", path));
+            }
+        }
+        if self.compaction.enabled {
+            if self.compaction.model.trim().is_empty() {
+                return Err("compaction.model is required when compaction.enabled=true".to_string());
+            }
+            if self.compaction.cache_capacity == 0 {
+                return Err("compaction.cache_capacity must be > 0".to_string());
+            }
+            self.compaction.conversation_header =
+                self.compaction.conversation_header.to_lowercase();
+        }
+        self.models.context.policy = self.models.context.policy.to_lowercase();
+        match self.models.context.policy.as_str() {
+            "reject" | "drop_oldest" => {}
+            "summarize" => {
+                if self.models.context.summary_model.is_none() {
+                    return Err(
+                        "models.context.summary_model is required when policy=summarize"
+                            .to_string(),
+                    );
+                }
+            }
+            other => return Err(format!("models.context.policy invalid: {}", other)),
+        }
+        if self.anthropic.forward_mode == "translate"
+            && self.downstream.oauth.is_none()
+            && self.downstream.api_keys.is_empty()
+        {
             match self.downstream.api_key.as_deref() {
                 Some(key) if !key.trim().is_empty() => {}
                 _ => return Err("downstream.api_key is required".to_string()),
@@ -312,9 +2108,70 @@ impl Config {
                 self.downstream.anthropic_version = None;
             }
         }
-        if let Some(beta) = self.downstream.anthropic_beta.as_mut() {
-            if beta.trim().is_empty() {
-                self.downstream.anthropic_beta = None;
+        if let Some(beta) = self.downstream.anthropic_beta.as_mut() {
+            if beta.trim().is_empty() {
+                self.downstream.anthropic_beta = None;
+            }
+        }
+        if self.observability.metrics.export_interval_ms == 0 {
+            return Err("observability.metrics.export_interval_ms must be > 0".to_string());
+        }
+        if let Some(buckets) = self.observability.metrics.latency_buckets_ms.as_ref() {
+            if buckets.is_empty() {
+                return Err("observability.metrics.latency_buckets_ms must not be empty".to_string());
+            }
+            if buckets.iter().any(|b| !b.is_finite()) || buckets.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(
+                    "observability.metrics.latency_buckets_ms must be finite and strictly increasing"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(header) = self.observability.langfuse.session_header.take() {
+            let header = header.trim().to_ascii_lowercase();
+            if !header.is_empty() {
+                if axum::http::HeaderName::from_bytes(header.as_bytes()).is_err() {
+                    return Err(format!("observability.langfuse.session_header invalid: {}", header));
+                }
+                self.observability.langfuse.session_header = Some(header);
+            }
+        }
+        if self.observability.debug_capture.enabled {
+            let debug = &mut self.observability.debug_capture;
+            debug.header = debug.header.trim().to_ascii_lowercase();
+            if axum::http::HeaderName::from_bytes(debug.header.as_bytes()).is_err() {
+                return Err(format!("observability.debug_capture.header invalid: {}", debug.header));
+            }
+            if debug.secret.as_deref().is_some_and(|secret| secret.trim().is_empty()) {
+                return Err("observability.debug_capture.secret must not be empty".to_string());
+            }
+            if debug.path.trim().is_empty() {
+                return Err("observability.debug_capture.path is required".to_string());
+            }
+            if debug.max_body_bytes == 0 || debug.max_file_bytes == 0 {
+                return Err(
+                    "observability.debug_capture.max_body_bytes and max_file_bytes must be > 0".to_string(),
+                );
+            }
+        }
+        if self.observability.transcripts.enabled {
+            let transcripts = &self.observability.transcripts;
+            if transcripts.dir.trim().is_empty() {
+                return Err("observability.transcripts.dir is required".to_string());
+            }
+            if !(0.0..=100.0).contains(&transcripts.sample_percent) {
+                return Err(format!(
+                    "observability.transcripts.sample_percent must be within 0..=100: {}",
+                    transcripts.sample_percent
+                ));
+            }
+            if transcripts.max_bytes == 0 {
+                return Err("observability.transcripts.max_bytes must be > 0".to_string());
+            }
+            if transcripts.max_files == 0 || transcripts.max_age_secs == 0 {
+                return Err(
+                    "observability.transcripts.max_files and max_age_secs must be > 0".to_string(),
+                );
             }
         }
         if self.observability.audit_log.enabled {
@@ -324,14 +2181,164 @@ impl Config {
             if self.observability.audit_log.max_file_bytes == 0 {
                 return Err("audit_log.max_file_bytes must be > 0".to_string());
             }
-            match self.observability.audit_log.path.as_deref() {
-                Some(path) if !path.trim().is_empty() => {}
-                _ => {
-                    return Err(
-                        "audit_log.path is required when dump_downstream=true".to_string()
-                    )
+            let audit = &mut self.observability.audit_log;
+            audit.stream_sse = audit.stream_sse.to_lowercase();
+            if !matches!(audit.stream_sse.as_str(), "none" | "downstream" | "client" | "both") {
+                return Err(format!("audit_log.stream_sse invalid: {}", audit.stream_sse));
+            }
+            let overflow = &mut self.observability.audit_log.overflow;
+            if overflow.queue_capacity == 0 {
+                return Err("audit_log.overflow.queue_capacity must be > 0".to_string());
+            }
+            overflow.policy = overflow.policy.to_lowercase();
+            match overflow.policy.as_str() {
+                "block" => {
+                    if overflow.block_timeout_ms == 0 {
+                        return Err("audit_log.overflow.block_timeout_ms must be > 0".to_string());
+                    }
+                }
+                "drop_oldest" => {}
+                "spill" => {
+                    if overflow.spill_path.trim().is_empty() {
+                        return Err("audit_log.overflow.spill_path is required for policy=spill".to_string());
+                    }
+                }
+                other => return Err(format!("audit_log.overflow.policy invalid: {}", other)),
+            }
+            self.observability.audit_log.sink = self.observability.audit_log.sink.to_lowercase();
+            match self.observability.audit_log.sink.as_str() {
+                "file" => match self.observability.audit_log.path.as_deref() {
+                    Some(path) if !path.trim().is_empty() => {}
+                    _ => {
+                        return Err(
+                            "audit_log.path is required when audit_log.sink=file".to_string()
+                        )
+                    }
+                },
+                "postgres" => {
+                    if self.postgres.is_none() {
+                        return Err("audit_log.sink=postgres requires postgres.url".to_string());
+                    }
+                }
+                "s3" => match self.observability.audit_log.s3.as_ref() {
+                    Some(s3) => {
+                        if s3.endpoint.trim().is_empty() || s3.bucket.trim().is_empty() {
+                            return Err(
+                                "audit_log.s3.endpoint and audit_log.s3.bucket are required"
+                                    .to_string(),
+                            );
+                        }
+                        if s3.flush_interval_secs == 0 {
+                            return Err("audit_log.s3.flush_interval_secs must be > 0".to_string());
+                        }
+                    }
+                    None => return Err("audit_log.sink=s3 requires audit_log.s3".to_string()),
+                },
+                other => return Err(format!("audit_log.sink invalid: {}", other)),
+            }
+        }
+        if let Some(field) = self
+            .models
+            .forward_fields
+            .iter()
+            .find(|field| crate::models::ANTHROPIC_REQUEST_FIELDS.contains(&field.as_str()))
+        {
+            return Err(format!("models.forward_fields: {} is translated by the gateway", field));
+        }
+        for (model, role) in self.models.system_role.iter_mut() {
+            *role = role.to_lowercase();
+            if !matches!(role.as_str(), "system" | "developer" | "user") {
+                return Err(format!("models.system_role.{} invalid: {}", model, role));
+            }
+        }
+        for (model, capabilities) in &self.models.capabilities {
+            if capabilities.max_context == Some(0) || capabilities.max_output == Some(0) {
+                return Err(format!("models.capabilities.{}: limits must be > 0", model));
+            }
+        }
+        for (model, support) in self.models.json_schema_support.iter_mut() {
+            *support = support.to_lowercase();
+            if !matches!(support.as_str(), "native" | "auto" | "none") {
+                return Err(format!("models.json_schema_support.{} invalid: {}", model, support));
+            }
+        }
+        for (model, params) in &self.models.vllm_params {
+            for key in params.keys() {
+                if !crate::translate::VLLM_EXTRA_PARAMS.contains(&key.as_str()) {
+                    return Err(format!(
+                        "models.vllm_params.{} unsupported parameter: {}",
+                        model, key
+                    ));
+                }
+            }
+        }
+        for (model, value) in &self.models.overrides {
+            if !value.is_object() {
+                return Err(format!("models.overrides.{} must be a mapping", model));
+            }
+        }
+        for field in &self.observability.access_log.fields {
+            if !crate::access_log::ACCESS_LOG_FIELDS.contains(&field.as_str()) {
+                return Err(format!("access_log.fields invalid: {}", field));
+            }
+        }
+        if self
+            .observability
+            .access_log
+            .path
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            self.observability.access_log.path = None;
+        }
+        CapturePolicy::parse(&self.observability.capture)?;
+        if self
+            .admin
+            .token
+            .as_deref()
+            .is_some_and(|t| t.trim().is_empty())
+        {
+            self.admin.token = None;
+        }
+        if self
+            .usage
+            .sqlite_path
+            .as_deref()
+            .is_some_and(|p| p.trim().is_empty())
+        {
+            self.usage.sqlite_path = None;
+        }
+        if self.usage.ring_capacity == 0 {
+            return Err("usage.ring_capacity must be > 0".to_string());
+        }
+        if let Some(sink) = self.usage.sink.as_mut() {
+            *sink = sink.to_lowercase();
+        }
+        let usage_sink = self.usage_sink().to_string();
+        match usage_sink.as_str() {
+            "memory" => {}
+            "sqlite" => {
+                if self.usage.sqlite_path.is_none() {
+                    return Err("usage.sink=sqlite requires usage.sqlite_path".to_string());
                 }
             }
+            "postgres" => {
+                if self.postgres.is_none() {
+                    return Err("usage.sink=postgres requires postgres.url".to_string());
+                }
+            }
+            other => return Err(format!("usage.sink invalid: {}", other)),
+        }
+        let tracing = &self.observability.tracing;
+        for (name, ratio) in [
+            ("sample_ratio", Some(tracing.sample_ratio)),
+            ("stream_sample_ratio", tracing.stream_sample_ratio),
+        ] {
+            if let Some(ratio) = ratio
+                && !(0.0..=1.0).contains(&ratio)
+            {
+                return Err(format!("observability.tracing.{} must be within 0..=1", name));
+            }
         }
         self.observability.logging.format =
             self.observability.logging.format.to_lowercase();
@@ -349,6 +2356,47 @@ impl Config {
     }
 }
 
+fn default_cors_allowed_headers() -> Vec<String> {
+    [
+        "content-type",
+        "accept",
+        "authorization",
+        "x-api-key",
+        "anthropic-version",
+        "anthropic-beta",
+        "anthropic-dangerous-direct-browser-access",
+        "cache-control",
+        "last-event-id",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
+fn default_signature_header() -> String {
+    "x-gateway-signature".to_string()
+}
+
+fn default_timestamp_header() -> String {
+    "x-gateway-timestamp".to_string()
+}
+
+fn default_replay_window_secs() -> u64 {
+    300
+}
+
+fn default_signing_exempt_paths() -> Vec<String> {
+    vec!["/health".to_string()]
+}
+
+fn default_signing_max_body_bytes() -> usize {
+    32 * 1024 * 1024
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    600
+}
+
 fn default_bind_addr() -> String {
     "0.0.0.0:8080".to_string()
 }
@@ -369,6 +2417,90 @@ fn default_pool_max_idle_per_host() -> usize {
     64
 }
 
+fn default_endpoint_window() -> usize {
+    100
+}
+
+fn default_endpoint_min_samples() -> usize {
+    20
+}
+
+fn default_endpoint_max_error_rate() -> f64 {
+    0.5
+}
+
+fn default_endpoint_probe_interval_secs() -> u64 {
+    30
+}
+
+fn default_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_warmup_connections() -> usize {
+    2
+}
+
+fn default_warmup_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_routing_policy() -> String {
+    "rules".to_string()
+}
+
+fn default_tier_header() -> String {
+    "x-gateway-tier".to_string()
+}
+
+fn default_candidate_tools() -> bool {
+    true
+}
+
+fn default_coalesce_max_bytes() -> usize {
+    64
+}
+
+fn default_coalesce_max_delay_ms() -> u64 {
+    30
+}
+
+fn default_stream_channel_capacity() -> usize {
+    64
+}
+
+fn default_slow_client() -> String {
+    "block".to_string()
+}
+
+fn default_stream_max_buffered_bytes() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_stream_spill_dir() -> String {
+    "./logs/stream_spill".to_string()
+}
+
+fn default_rewrite_documents() -> String {
+    "pass".to_string()
+}
+
+fn default_provider() -> String {
+    "openai".to_string()
+}
+
+fn default_stream_parsing() -> String {
+    "strict".to_string()
+}
+
+fn default_max_tokens_field() -> String {
+    "max_completion_tokens".to_string()
+}
+
+fn default_max_malformed_chunks() -> u32 {
+    3
+}
+
 fn default_max_inflight() -> usize {
     512
 }
@@ -432,6 +2564,130 @@ fn default_allow_images() -> bool {
     true
 }
 
+fn default_scoring_sample_percent() -> f64 {
+    100.0
+}
+
+fn default_alerts_interval_secs() -> u64 {
+    60
+}
+
+fn default_alert_window() -> String {
+    "5m".to_string()
+}
+
+fn default_alert_min_requests() -> u64 {
+    10
+}
+
+fn default_billing_kind() -> String {
+    "webhook".to_string()
+}
+
+fn default_billing_batch_size() -> usize {
+    100
+}
+
+fn default_billing_flush_interval_secs() -> u64 {
+    10
+}
+
+fn default_billing_max_retries() -> u32 {
+    3
+}
+
+fn default_stream_tcp_nodelay() -> bool {
+    true
+}
+
+fn default_stream_retry_backoff_ms() -> u64 {
+    200
+}
+
+fn default_billing_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_billing_queue_capacity() -> usize {
+    10_000
+}
+
+fn default_billing_event_type() -> String {
+    "llm.usage".to_string()
+}
+
+fn default_billing_source() -> String {
+    "llm-gateway".to_string()
+}
+
+fn default_shadow_percent() -> f64 {
+    10.0
+}
+
+fn default_shadow_timeout_ms() -> u64 {
+    120_000
+}
+
+fn default_compression_downstream() -> bool {
+    true
+}
+
+fn default_auxiliary_mode() -> String {
+    "disabled".to_string()
+}
+
+fn default_auxiliary_responses() -> HashMap<String, serde_json::Value> {
+    HashMap::from([("/api/organizations".to_string(), serde_json::json!([]))])
+}
+
+fn default_compaction_threshold_tokens() -> u64 {
+    64_000
+}
+
+fn default_compaction_keep_recent_turns() -> usize {
+    4
+}
+
+fn default_compaction_cache_capacity() -> usize {
+    1_000
+}
+
+fn default_compaction_cache_ttl_secs() -> u64 {
+    3_600
+}
+
+fn default_compaction_conversation_header() -> String {
+    "x-conversation-id".to_string()
+}
+
+fn default_context_policy() -> String {
+    "reject".to_string()
+}
+
+fn default_summary_max_tokens() -> u32 {
+    1024
+}
+
+fn default_prefill_mode() -> String {
+    "none".to_string()
+}
+
+fn default_tool_error() -> String {
+    "prefix".to_string()
+}
+
+fn default_tool_error_prefix() -> String {
+    "[tool error] ".to_string()
+}
+
+fn default_empty_content() -> String {
+    "error".to_string()
+}
+
+fn default_builtin_tools() -> String {
+    "reject".to_string()
+}
+
 fn default_document_policy() -> String {
     "reject".to_string()
 }
@@ -444,6 +2700,26 @@ fn default_forward_mode() -> String {
     "passthrough".to_string()
 }
 
+fn default_key_rotation() -> String {
+    "round_robin".to_string()
+}
+
+fn default_oauth_auth_method() -> String {
+    "basic".to_string()
+}
+
+fn default_oauth_refresh_margin_secs() -> u64 {
+    60
+}
+
+fn default_vertex_region() -> String {
+    "us-east5".to_string()
+}
+
+fn default_vertex_anthropic_version() -> String {
+    "vertex-2023-10-16".to_string()
+}
+
 fn default_audit_max_body_bytes() -> usize {
     1_048_576
 }
@@ -451,3 +2727,232 @@ fn default_audit_max_body_bytes() -> usize {
 fn default_audit_max_file_bytes() -> u64 {
     1_048_576
 }
+
+fn default_postgres_max_connections() -> u32 {
+    8
+}
+
+fn default_postgres_max_usage_rows() -> usize {
+    100_000
+}
+
+fn default_audit_sink() -> String {
+    "file".to_string()
+}
+
+fn default_metrics_export_interval_ms() -> u64 {
+    60_000
+}
+
+fn default_audit_stream_sse() -> String {
+    "none".to_string()
+}
+
+fn default_audit_overflow_policy() -> String {
+    "block".to_string()
+}
+
+fn default_audit_queue_capacity() -> usize {
+    256
+}
+
+fn default_audit_block_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_audit_spill_path() -> String {
+    "./logs/audit_overflow.jsonl".to_string()
+}
+
+fn default_transcripts_dir() -> String {
+    "./logs/transcripts".to_string()
+}
+
+fn default_transcripts_sample_percent() -> f64 {
+    1.0
+}
+
+fn default_transcripts_max_bytes() -> usize {
+    4 * 1_048_576
+}
+
+fn default_transcripts_max_files() -> usize {
+    1000
+}
+
+fn default_transcripts_max_age_secs() -> u64 {
+    7 * 24 * 3600
+}
+
+fn default_debug_capture_header() -> String {
+    "x-gateway-debug".to_string()
+}
+
+fn default_debug_capture_path() -> String {
+    "./logs/debug_capture.jsonl".to_string()
+}
+
+fn default_debug_capture_max_body_bytes() -> usize {
+    8 * 1_048_576
+}
+
+fn default_debug_capture_max_file_bytes() -> u64 {
+    16 * 1_048_576
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_s3_prefix() -> String {
+    "audit/".to_string()
+}
+
+fn default_s3_spill_dir() -> String {
+    "./logs/audit_spill".to_string()
+}
+
+fn default_s3_flush_interval_secs() -> u64 {
+    60
+}
+
+fn default_usage_ring_capacity() -> usize {
+    10_000
+}
+
+fn default_capture_policy() -> String {
+    "full".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_always_sample_errors() -> bool {
+    true
+}
+
+fn default_access_log_fields() -> Vec<String> {
+    crate::access_log::ACCESS_LOG_FIELDS
+        .iter()
+        .map(|f| f.to_string())
+        .collect()
+}
+
+/// Merges the `*.yaml` / `*.yml` fragments of a `CONFIG_PATH` directory in
+/// path order (searched recursively, hidden entries skipped so Kubernetes
+/// ConfigMap mounts work).
+fn load_fragments(path: &Path) -> Result<serde_yaml::Value, String> {
+    let mut files = Vec::new();
+    collect_fragments(path, &mut files)?;
+    if files.is_empty() {
+        return Err(format!("CONFIG_PATH has no yaml fragments: {}", path.display()));
+    }
+    files.sort();
+    let mut merged = serde_yaml::Value::Null;
+    for file in files {
+        merge_yaml(&mut merged, read_yaml(&file)?);
+    }
+    Ok(merged)
+}
+
+fn read_yaml(path: &Path) -> Result<serde_yaml::Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("CONFIG_PATH read error: {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&content)
+        .map_err(|e| format!("CONFIG_PATH invalid yaml: {}: {}", path.display(), e))
+}
+
+fn collect_fragments(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("CONFIG_PATH read error: {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("CONFIG_PATH read error: {}: {}", dir.display(), e))?
+            .path();
+        if path.file_name().and_then(|n| n.to_str()).is_none_or(|n| n.starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_fragments(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e == "yaml" || e == "yml")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Mappings merge key by key, sequences concatenate and any other value
+/// from a later fragment replaces the earlier one.
+fn merge_yaml(base: &mut serde_yaml::Value, next: serde_yaml::Value) {
+    use serde_yaml::Value;
+    match (base, next) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base), Value::Mapping(next)) => {
+            for (key, value) in next {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(next)) => base.extend(next),
+        (base, next) => *base = next,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_fragments_merge_in_path_order() {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-config-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("tenants")).unwrap();
+        fs::create_dir_all(dir.join("..data")).unwrap();
+        fs::write(
+            dir.join("00-base.yaml"),
+            "server:\n  bind_addr: \"0.0.0.0:8080\"\nmodels:\n  model_map:\n    a: a-1\nexperiments:\n  - name: one\n",
+        )
+        .unwrap();
+        fs::write(dir.join("10-models.yml"), "models:\n  model_map:\n    a: a-2\n    b: b-1\n").unwrap();
+        fs::write(dir.join("tenants/acme.yaml"), "experiments:\n  - name: two\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not: yaml: [").unwrap();
+        fs::write(dir.join("..data/ignored.yaml"), "server: {bind_addr: hidden}\n").unwrap();
+
+        let merged = load_fragments(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            "server:\n  bind_addr: \"0.0.0.0:8080\"\nmodels:\n  model_map:\n    a: a-2\n    b: b-1\nexperiments:\n  - name: one\n  - name: two\n",
+        )
+        .unwrap();
+        assert_eq!(merged, expected);
+    }
+
+    #[test]
+    fn latency_buckets_must_increase() {
+        let base = "server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\n";
+        let config = Config::from_yaml(&format!(
+            "{base}observability:\n  metrics:\n    export_interval_ms: 10000\n    latency_buckets_ms: [1, 5, 25, 100, 60000]\n"
+        ))
+        .unwrap();
+        assert_eq!(config.observability.metrics.export_interval_ms, 10000);
+        assert_eq!(
+            config.observability.metrics.latency_buckets_ms,
+            Some(vec![1.0, 5.0, 25.0, 100.0, 60000.0])
+        );
+        let err = Config::from_yaml(&format!(
+            "{base}observability:\n  metrics:\n    latency_buckets_ms: [5, 5, 10]\n"
+        ))
+        .err()
+        .unwrap();
+        assert!(err.contains("strictly increasing"), "{}", err);
+    }
+}
@@ -0,0 +1,306 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::HeaderMap;
+use axum::response::Response;
+use axum::Router;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+
+use crate::bench::spawn;
+use crate::config::Config;
+use crate::metrics::init_metrics_noop;
+use crate::provider::ProviderRegistry;
+use crate::server::{build_router, build_state};
+use crate::sse::SseParser;
+use crate::tracing_otlp::init_tracer_noop;
+
+#[derive(Debug, Args)]
+pub struct ConformanceArgs {
+    /// Directory of `*.json` fixtures.
+    pub dir: PathBuf,
+    /// Send each fixture's request to the configured downstream and store
+    /// the response and the resulting transcript in the fixture.
+    #[arg(long)]
+    pub record: bool,
+    /// Rewrite `expected` from the replayed transcript instead of comparing.
+    #[arg(long, conflicts_with = "record")]
+    pub update: bool,
+}
+
+/// A recorded OpenAI stream and the Anthropic transcript the gateway
+/// produced for it. `downstream` holds the raw SSE frames without their
+/// trailing blank line, so malformed frames survive as recorded.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Fixture {
+    pub request: Value,
+    #[serde(default)]
+    pub downstream: Vec<String>,
+    #[serde(default)]
+    pub expected: Vec<TranscriptEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TranscriptEvent {
+    pub event: String,
+    pub data: Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Check,
+    Update,
+    Record,
+}
+
+/// Result of one fixture; `mismatch` describes the first differing event.
+#[derive(Debug)]
+pub struct FixtureOutcome {
+    pub name: String,
+    pub mismatch: Option<String>,
+}
+
+/// What the fake downstream answers with: the frames of the fixture being
+/// replayed, or a live downstream to forward to while recording.
+#[derive(Clone)]
+enum Upstream {
+    Replay(Vec<String>),
+    Record { base_url: String, client: reqwest::Client },
+}
+
+type Slot = Arc<Mutex<(Option<Upstream>, Vec<String>)>>;
+
+/// A translate-mode config for replaying fixtures without a config file.
+pub fn default_config() -> Config {
+    Config::from_yaml(
+        "server: {}\nanthropic:\n  forward_mode: translate\ndownstream:\n  api_key: conformance\nmodels: {}\nlimits: {}\nobservability: {}\n",
+    )
+    .expect("built-in conformance config")
+}
+
+/// CLI entry: runs every fixture in `args.dir` and fails if any transcript
+/// differs from its golden copy.
+pub async fn run(config: Config, args: ConformanceArgs) -> Result<(), String> {
+    let mode = match (args.record, args.update) {
+        (true, _) => Mode::Record,
+        (_, true) => Mode::Update,
+        _ => Mode::Check,
+    };
+    let outcomes = run_dir(config, &args.dir, mode).await?;
+    let failed = outcomes.iter().filter(|o| o.mismatch.is_some()).count();
+    for outcome in &outcomes {
+        match &outcome.mismatch {
+            None => println!("ok    {}", outcome.name),
+            Some(mismatch) => println!("FAIL  {}: {}", outcome.name, mismatch),
+        }
+    }
+    println!("{} fixtures, {} failed", outcomes.len(), failed);
+    if failed > 0 {
+        return Err(format!("conformance: {} of {} fixtures failed", failed, outcomes.len()));
+    }
+    Ok(())
+}
+
+/// Serves the gateway against an in-process fake downstream and replays
+/// (or records) the fixtures in `dir` in file-name order. `Update` and
+/// `Record` write the fixtures back.
+pub async fn run_dir(mut config: Config, dir: &Path, mode: Mode) -> Result<Vec<FixtureOutcome>, String> {
+    if config.forward_mode() != "translate" {
+        return Err(format!("conformance needs forward_mode translate, not {}", config.forward_mode()));
+    }
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("conformance read error: {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let slot: Slot = Default::default();
+    let live_base_url = std::mem::replace(
+        &mut config.downstream.base_url,
+        spawn(Router::new().fallback(fake_downstream).with_state(slot.clone())).await?,
+    );
+    config.shadow = None;
+    config.scoring = None;
+    config.billing = None;
+    let inflight_count = Arc::new(AtomicU64::new(0));
+    let state = build_state(
+        &config,
+        init_metrics_noop(inflight_count.clone()),
+        inflight_count,
+        init_tracer_noop(config.observability.service_name.clone()),
+        ProviderRegistry::builtin(),
+    )
+    .await?;
+    let gateway = spawn(build_router(&config, state)?).await?;
+    let client = reqwest::Client::new();
+
+    let mut outcomes = Vec::new();
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| format!("conformance read error: {}: {}", path.display(), e))?;
+        let mut fixture: Fixture =
+            serde_json::from_str(&content).map_err(|e| format!("conformance fixture {}: {}", path.display(), e))?;
+        let upstream = match mode {
+            Mode::Record => Upstream::Record {
+                base_url: live_base_url.clone(),
+                client: client.clone(),
+            },
+            _ => Upstream::Replay(fixture.downstream.clone()),
+        };
+        *slot.lock().unwrap() = (Some(upstream), Vec::new());
+        let transcript = transcript(&client, &gateway, &fixture.request).await?;
+        let recorded = std::mem::take(&mut slot.lock().unwrap().1);
+
+        let mismatch = match mode {
+            Mode::Check => compare(&fixture.expected, &transcript),
+            Mode::Update | Mode::Record => {
+                if mode == Mode::Record {
+                    fixture.downstream = recorded;
+                }
+                fixture.expected = transcript;
+                let json = serde_json::to_string_pretty(&fixture).map_err(|e| e.to_string())?;
+                std::fs::write(&path, json + "\n")
+                    .map_err(|e| format!("conformance write error: {}: {}", path.display(), e))?;
+                None
+            }
+        };
+        outcomes.push(FixtureOutcome { name, mismatch });
+    }
+    Ok(outcomes)
+}
+
+async fn transcript(client: &reqwest::Client, gateway: &str, request: &Value) -> Result<Vec<TranscriptEvent>, String> {
+    let resp = client
+        .post(format!("{}/v1/messages", gateway))
+        .json(request)
+        .send()
+        .await
+        .map_err(|e| format!("conformance request error: {}", e))?;
+    let status = resp.status();
+    let body = resp.bytes().await.map_err(|e| format!("conformance read error: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("gateway returned {}: {}", status, String::from_utf8_lossy(&body)));
+    }
+    let mut parser = SseParser::default();
+    let mut events = parser.push(&body);
+    events.extend(parser.finish());
+    Ok(events
+        .into_iter()
+        .filter(|event| !event.data.is_empty())
+        .map(|event| TranscriptEvent {
+            event: event.event.unwrap_or_else(|| "message".to_string()),
+            data: serde_json::from_str(&event.data).unwrap_or(Value::String(event.data)),
+        })
+        .collect())
+}
+
+fn compare(expected: &[TranscriptEvent], actual: &[TranscriptEvent]) -> Option<String> {
+    let at = expected.iter().zip(actual).position(|(e, a)| e != a);
+    match at {
+        Some(i) => Some(format!(
+            "event {}: expected {} {}, got {} {}",
+            i, expected[i].event, expected[i].data, actual[i].event, actual[i].data
+        )),
+        None if expected.len() != actual.len() => Some(format!(
+            "expected {} events, got {}",
+            expected.len(),
+            actual.len()
+        )),
+        None => None,
+    }
+}
+
+/// Replays the current fixture's frames, or forwards the gateway's request
+/// to the live downstream and keeps the frames of its answer.
+async fn fake_downstream(State(slot): State<Slot>, request: Request) -> Response {
+    let upstream = slot.lock().unwrap().0.clone();
+    let body = match upstream {
+        Some(Upstream::Replay(frames)) => frames.iter().map(|frame| format!("{}\n\n", frame)).collect(),
+        Some(Upstream::Record { base_url, client }) => match forward(&client, &base_url, request).await {
+            Ok(body) => {
+                slot.lock().unwrap().1 = split_frames(&body);
+                body
+            }
+            Err(err) => {
+                return Response::builder()
+                    .status(502)
+                    .body(Body::from(err))
+                    .unwrap_or_default();
+            }
+        },
+        None => String::new(),
+    };
+    Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .body(Body::from(Bytes::from(body)))
+        .unwrap_or_default()
+}
+
+/// Headers the gateway sets for the downstream that must reach it unchanged.
+const FORWARD_HEADERS: &[&str] = &["authorization", "content-type", "api-key", "user-agent"];
+
+async fn forward(client: &reqwest::Client, base_url: &str, request: Request) -> Result<String, String> {
+    let path = request.uri().path_and_query().map(|p| p.to_string()).unwrap_or_default();
+    let mut headers = HeaderMap::new();
+    for (name, value) in request.headers() {
+        if FORWARD_HEADERS.contains(&name.as_str()) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    let body = axum::body::to_bytes(request.into_body(), usize::MAX)
+        .await
+        .map_err(|e| e.to_string())?;
+    let resp = client
+        .post(format!("{}{}", base_url.trim_end_matches('/'), path))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("record request error: {}", e))?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| format!("record read error: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("downstream returned {}: {}", status, text));
+    }
+    Ok(text)
+}
+
+fn split_frames(body: &str) -> Vec<String> {
+    body.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|frame| !frame.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_first_differing_event_and_splits_recorded_frames() {
+        let event = |name: &str, data: Value| TranscriptEvent {
+            event: name.to_string(),
+            data,
+        };
+        let expected = vec![event("message_start", Value::Null), event("message_stop", Value::Null)];
+        assert_eq!(compare(&expected, &expected), None);
+        let mismatch = compare(&expected, &[event("message_start", Value::Null), event("error", Value::Null)]);
+        assert!(mismatch.unwrap().starts_with("event 1: expected message_stop"));
+        assert_eq!(
+            compare(&expected, &expected[..1]).as_deref(),
+            Some("expected 2 events, got 1")
+        );
+        assert_eq!(
+            split_frames("data: {\"a\":1}\r\n\r\ndata: [DONE]\n\n\n"),
+            ["data: {\"a\":1}", "data: [DONE]"]
+        );
+    }
+}
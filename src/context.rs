@@ -0,0 +1,285 @@
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::json;
+
+use crate::capabilities;
+use crate::config::ContextPolicy;
+use crate::error::{map_downstream_error, AppError};
+use crate::models::{OpenAIMessage, OpenAIMessageContent, OpenAIRequest, OpenAIResponse};
+use crate::state::AppState;
+use crate::tokenizer::Tokenizer;
+
+const SUMMARY_PROMPT: &str = "Summarize the earlier part of this conversation so it can replace \
+     the original messages. Keep decisions, facts, file names, tool results and open tasks. \
+     Reply with the summary only.";
+
+fn request_tokens(tokenizer: &Tokenizer, req: &OpenAIRequest) -> u64 {
+    tokenizer.count_request(req) + req.max_completion_tokens as u64
+}
+
+/// Applies the configured context policy when the request (estimated prompt
+/// plus `max_completion_tokens`) exceeds the model's context limit
+/// (`models.context.limits`, else its `max_context` capability). Models
+/// without a limit are left untouched.
+pub async fn fit_context(state: &AppState, req: &mut OpenAIRequest) -> Result<(), AppError> {
+    let limit = state.config.models.context.limits.get(&req.model).copied();
+    let Some(limit) = limit.or_else(|| capabilities::resolve(&state.config, &req.model).max_context) else {
+        return Ok(());
+    };
+    let tokenizer = state.tokenizers.for_model(&req.model);
+    let estimated = request_tokens(tokenizer, req);
+    if estimated <= limit {
+        return Ok(());
+    }
+    match state.config.context_policy() {
+        ContextPolicy::Reject => {}
+        ContextPolicy::DropOldest => {
+            let dropped = drop_oldest(tokenizer, req, limit);
+            tracing::info!(model = %req.model, dropped, estimated, limit, "context truncated");
+        }
+        ContextPolicy::Summarize => {
+            let split = split_point(tokenizer, req, limit);
+            if split > req.system_messages {
+                let history = transcript(&req.messages[req.system_messages..split]);
+                let context = &state.config.models.context;
+                let model = context.summary_model.as_deref().unwrap_or_default();
+                match summarize_transcript(state, model, context.summary_max_tokens, history).await {
+                    Ok(summary) => {
+                        let role = state.config.system_role(&req.model).to_string();
+                        replace_with_summary(req, &role, split, summary);
+                        tracing::info!(model = %req.model, estimated, limit, "context summarized");
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            model = %req.model,
+                            "context summary failed, dropping oldest turns: {}",
+                            err.message
+                        );
+                        drop_oldest(tokenizer, req, limit);
+                    }
+                }
+            }
+        }
+    }
+    let remaining = request_tokens(tokenizer, req);
+    if remaining > limit {
+        return Err(AppError::invalid_request(format!(
+            "prompt is too long: {} tokens > {} maximum",
+            remaining, limit
+        )));
+    }
+    Ok(())
+}
+
+/// Index of the first message to keep so the remaining history fits, always
+/// starting at a user turn and keeping at least the final user turn.
+fn split_point(tokenizer: &Tokenizer, req: &OpenAIRequest, limit: u64) -> usize {
+    let start = req.system_messages;
+    let fixed = request_tokens(tokenizer, req) - tokenizer.count_messages(&req.messages[start..]);
+    let budget = limit.saturating_sub(fixed);
+    let user_turns: Vec<usize> = (start..req.messages.len())
+        .filter(|&i| req.messages[i].role == "user")
+        .collect();
+    let Some(&last_user) = user_turns.last() else {
+        return start;
+    };
+    user_turns
+        .into_iter()
+        .find(|&i| tokenizer.count_messages(&req.messages[i..]) <= budget)
+        .unwrap_or(last_user)
+}
+
+fn drop_oldest(tokenizer: &Tokenizer, req: &mut OpenAIRequest, limit: u64) -> usize {
+    let start = req.system_messages;
+    let split = split_point(tokenizer, req, limit).max(start);
+    req.messages.drain(start..split).count()
+}
+
+/// Replaces the history between the system prompt and `split` with one
+/// summary message, sent as `role` like the system prompt. The summary then
+/// counts as part of the system prompt, so later trimming keeps it.
+pub fn replace_with_summary(req: &mut OpenAIRequest, role: &str, split: usize, summary: String) {
+    let start = req.system_messages;
+    req.messages.drain(start..split);
+    req.system_messages += 1;
+    req.messages.insert(
+        start,
+        OpenAIMessage {
+            role: role.to_string(),
+            content: Some(OpenAIMessageContent::Text(format!(
+                "Summary of the earlier conversation:\n{}",
+                summary
+            ))),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        },
+    );
+}
+
+/// Renders messages as a plain transcript for the summary model.
+pub fn transcript(messages: &[OpenAIMessage]) -> String {
+    let mut out = String::new();
+    for msg in messages {
+        let text = match &msg.content {
+            Some(OpenAIMessageContent::Text(text)) => text.clone(),
+            Some(OpenAIMessageContent::Parts(parts)) => {
+                serde_json::to_string(parts).unwrap_or_default()
+            }
+            None => String::new(),
+        };
+        out.push_str(&msg.role);
+        out.push_str(": ");
+        out.push_str(&text);
+        if let Some(tool_calls) = msg.tool_calls.as_ref() {
+            out.push_str(&serde_json::to_string(tool_calls).unwrap_or_default());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Asks `model` on the downstream to condense a conversation transcript.
+pub async fn summarize_transcript(
+    state: &AppState,
+    model: &str,
+    max_tokens: u32,
+    transcript: String,
+) -> Result<String, AppError> {
+    let body = json!({
+        "model": model,
+        "max_completion_tokens": max_tokens,
+        "messages": [
+            {"role": "system", "content": SUMMARY_PROMPT},
+            {"role": "user", "content": transcript},
+        ],
+    });
+    let resp = state
+        .clients
+        .client()
+        .post(state.config.chat_completions_url())
+        .header(CONTENT_TYPE, "application/json")
+        .header(AUTHORIZATION, format!("Bearer {}", state.downstream_bearer().await?))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| AppError::api_error(format!("summary request failed: {}", e)))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(map_downstream_error(status, &text));
+    }
+    let parsed: OpenAIResponse = resp
+        .json()
+        .await
+        .map_err(|e| AppError::api_error(format!("invalid summary response: {}", e)))?;
+    parsed
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .map(|content| content.joined_text())
+        .filter(|text| !text.trim().is_empty())
+        .ok_or_else(|| AppError::api_error("empty summary response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str) -> OpenAIMessage {
+        OpenAIMessage {
+            role: role.to_string(),
+            content: Some(OpenAIMessageContent::Text(text.to_string())),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        }
+    }
+
+    fn request(messages: Vec<OpenAIMessage>) -> OpenAIRequest {
+        let system_messages = messages_with_role(&messages, "system");
+        OpenAIRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages,
+            max_completion_tokens: 10,
+            temperature: None,
+            top_p: None,
+            stop: None,
+            stream: None,
+            tools: None,
+            tool_choice: None,
+            response_format: None,
+            reasoning_effort: None,
+            stream_options: None,
+            extra: Default::default(),
+            system_messages,
+        }
+    }
+
+    fn messages_with_role(messages: &[OpenAIMessage], role: &str) -> usize {
+        messages.iter().take_while(|m| m.role == role).count()
+    }
+
+    #[test]
+    fn drop_oldest_keeps_system_and_starts_at_user() {
+        let filler = "x".repeat(400);
+        let mut req = request(vec![
+            message("system", "be brief"),
+            message("user", &filler),
+            message("assistant", &filler),
+            message("user", &filler),
+            message("assistant", "ok"),
+            message("user", "last question"),
+        ]);
+        let limit = request_tokens(&Tokenizer::Heuristic, &req) - 150;
+        let dropped = drop_oldest(&Tokenizer::Heuristic, &mut req, limit);
+        assert_eq!(dropped, 2);
+        assert_eq!(req.messages[0].role, "system");
+        assert_eq!(req.messages[1].role, "user");
+        assert!(request_tokens(&Tokenizer::Heuristic, &req) <= limit);
+    }
+
+    #[test]
+    fn requested_output_counts_toward_the_limit() {
+        let mut req = request(vec![message("user", "hi")]);
+        let prompt = Tokenizer::Heuristic.count_request(&req);
+        req.max_completion_tokens = 100;
+        assert_eq!(request_tokens(&Tokenizer::Heuristic, &req), prompt + 100);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_final_user_turn() {
+        let filler = "x".repeat(4000);
+        let mut req = request(vec![message("user", "hi"), message("user", &filler)]);
+        drop_oldest(&Tokenizer::Heuristic, &mut req, 50);
+        assert_eq!(req.messages.len(), 1);
+        assert!(request_tokens(&Tokenizer::Heuristic, &req) > 50);
+    }
+
+    #[test]
+    fn mapped_system_role_is_kept_and_used_for_the_summary() {
+        let filler = "x".repeat(400);
+        for role in ["developer", "user"] {
+            let mut req = request(vec![
+                message(role, "be brief"),
+                message("user", &filler),
+                message("assistant", &filler),
+                message("user", &filler),
+                message("assistant", "ok"),
+                message("user", "last question"),
+            ]);
+            req.system_messages = 1;
+            let limit = request_tokens(&Tokenizer::Heuristic, &req) - 150;
+            assert_eq!(drop_oldest(&Tokenizer::Heuristic, &mut req, limit), 2, "{}", role);
+            assert_eq!(req.messages[0].role, role);
+            assert!(matches!(&req.messages[0].content, Some(OpenAIMessageContent::Text(t)) if t == "be brief"));
+
+            let split = split_point(&Tokenizer::Heuristic, &req, 0);
+            replace_with_summary(&mut req, role, split, "earlier".to_string());
+            assert_eq!(req.system_messages, 2);
+            let roles: Vec<&str> = req.messages.iter().map(|m| m.role.as_str()).collect();
+            assert_eq!(roles, [role, role, "user"], "{}", role);
+            assert!(matches!(&req.messages[1].content, Some(OpenAIMessageContent::Text(t)) if t.ends_with("earlier")));
+        }
+    }
+}
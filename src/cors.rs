@@ -0,0 +1,107 @@
+use axum::http::{HeaderName, HeaderValue};
+use std::time::Duration;
+use tower_http::cors::{AllowMethods, AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+
+/// Builds the CORS layer for `server.cors`. Preflights are answered by the
+/// layer itself, so streaming `POST /v1/messages` requests (which always
+/// preflight because of `content-type: application/json`) never reach the
+/// router; CORS headers are set on the response head and SSE bodies pass
+/// through untouched.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer, String> {
+    let origin = if config.allowed_origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|o| {
+                HeaderValue::from_str(o.trim_end_matches('/'))
+                    .map_err(|_| format!("server.cors.allowed_origins invalid: {}", o))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(AllowMethods::mirror_request())
+        .allow_headers(header_names(&config.allowed_headers, "allowed_headers")?)
+        .expose_headers(header_names(&config.expose_headers, "expose_headers")?)
+        .max_age(Duration::from_secs(config.max_age_secs)))
+}
+
+fn header_names(names: &[String], field: &str) -> Result<Vec<HeaderName>, String> {
+    names
+        .iter()
+        .map(|name| {
+            HeaderName::from_bytes(name.trim().to_ascii_lowercase().as_bytes())
+                .map_err(|_| format!("server.cors.{} invalid: {}", field, name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Router};
+
+    fn config(origins: &[&str]) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            allowed_headers: vec!["content-type".to_string(), "x-api-key".to_string()],
+            expose_headers: vec!["request-id".to_string()],
+            max_age_secs: 600,
+        }
+    }
+
+    #[tokio::test]
+    async fn preflight_for_streaming_post_is_answered() {
+        let layer = cors_layer(&config(&["https://playground.example"])).unwrap();
+        let app = Router::new()
+            .route("/v1/messages", post(|| async { "ok" }))
+            .layer(layer);
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("bind failed: {}", err),
+        };
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        let client = reqwest::Client::new();
+        let resp = client
+            .request(reqwest::Method::OPTIONS, format!("http://{}/v1/messages", addr))
+            .header("origin", "https://playground.example")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type,x-api-key")
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get("access-control-allow-origin").unwrap(),
+            "https://playground.example"
+        );
+        assert_eq!(headers.get("access-control-max-age").unwrap(), "600");
+
+        let denied = client
+            .request(reqwest::Method::OPTIONS, format!("http://{}/v1/messages", addr))
+            .header("origin", "https://other.example")
+            .header("access-control-request-method", "POST")
+            .send()
+            .await
+            .unwrap();
+        assert!(denied.headers().get("access-control-allow-origin").is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_header_names() {
+        let mut cfg = config(&["*"]);
+        cfg.allowed_headers.push("bad header".to_string());
+        assert!(cors_layer(&cfg).is_err());
+    }
+}
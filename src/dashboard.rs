@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::access_log::RequestSummary;
+use crate::admin::require_admin;
+use crate::audit_log::now_ms;
+use crate::bench::percentile;
+use crate::error::AppError;
+use crate::models::AnthropicErrorResponse;
+use crate::state::AppState;
+use crate::usage::{is_error, parse_window};
+
+const DASHBOARD_HTML: &str = include_str!("../assets/dashboard.html");
+
+/// Most recent requests `GET /admin/dashboard/requests` returns at most.
+const MAX_RECENT: usize = 500;
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub window_ms: u64,
+    pub requests: u64,
+    pub errors: u64,
+    pub rps: f64,
+    pub error_rate: f64,
+    pub latency_ms: Percentiles,
+    pub ttfb_ms: Percentiles,
+    pub models: Vec<ModelStats>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ModelStats {
+    pub model: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// The embedded single-page dashboard. It holds no data itself: the page
+/// asks for the admin token and polls the JSON endpoints below.
+pub async fn get_dashboard() -> Html<&'static str> {
+    Html(DASHBOARD_HTML)
+}
+
+/// RPS, error rate, latency percentiles and per-model tokens over `?window=`
+/// (default `5m`) of the in-memory usage ring.
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard/stats",
+    tag = "admin",
+    params(("window" = Option<String>, Query, description = "e.g. `1m`, `5m` (default), `1h`")),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let window = params.get("window").map(String::as_str).unwrap_or("5m");
+    let window_ms = parse_window(window).map_err(AppError::invalid_request)?;
+    let entries = state.usage.recent(now_ms().saturating_sub(window_ms));
+    let mut stats = serde_json::to_value(stats(&entries, window_ms as u64)).unwrap_or(Value::Null);
+    stats["inflight"] = state.inflight_requests.list().len().into();
+    Ok(Json(stats).into_response())
+}
+
+/// The last `?limit=` (default 50) request summaries, newest first. Prompt
+/// and output text, kept only while scoring is enabled, were already run
+/// through the capture policy when the summary was recorded.
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard/requests",
+    tag = "admin",
+    params(("limit" = Option<usize>, Query, description = "default 50, at most 500")),
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn get_recent_requests(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<axum::response::Response, AppError> {
+    require_admin(&state, &headers)?;
+    let limit = match params.get("limit") {
+        Some(limit) => limit
+            .parse::<usize>()
+            .map_err(|_| AppError::invalid_request(format!("invalid limit: {}", limit)))?,
+        None => 50,
+    };
+    let data: Vec<Value> = state
+        .usage
+        .recent(0)
+        .iter()
+        .rev()
+        .take(limit.min(MAX_RECENT))
+        .map(|summary| {
+            let mut entry = serde_json::to_value(summary).unwrap_or(Value::Null);
+            if let Some(prompt) = summary.prompt_text.as_deref() {
+                entry["prompt"] = prompt.into();
+            }
+            if let Some(output) = summary.output_text.as_deref() {
+                entry["output"] = output.into();
+            }
+            entry
+        })
+        .collect();
+    Ok(Json(serde_json::json!({ "data": data })).into_response())
+}
+
+pub fn stats(entries: &[RequestSummary], window_ms: u64) -> Stats {
+    let mut latencies: Vec<u64> = entries.iter().map(|s| s.latency_ms).collect();
+    let mut ttfbs: Vec<u64> = entries.iter().filter_map(|s| s.ttfb_ms).collect();
+    latencies.sort_unstable();
+    ttfbs.sort_unstable();
+    let mut models: BTreeMap<String, ModelStats> = BTreeMap::new();
+    for summary in entries {
+        let model = summary.model.clone().unwrap_or_default();
+        let group = models.entry(model.clone()).or_insert_with(|| ModelStats {
+            model,
+            ..Default::default()
+        });
+        group.requests += 1;
+        group.errors += is_error(summary) as u64;
+        group.input_tokens += summary.input_tokens.unwrap_or(0);
+        group.output_tokens += summary.output_tokens.unwrap_or(0);
+    }
+    let requests = entries.len() as u64;
+    let errors = entries.iter().filter(|s| is_error(s)).count() as u64;
+    Stats {
+        window_ms,
+        requests,
+        errors,
+        rps: requests as f64 * 1000.0 / window_ms.max(1) as f64,
+        error_rate: if requests == 0 { 0.0 } else { errors as f64 / requests as f64 },
+        latency_ms: Percentiles::of(&latencies),
+        ttfb_ms: Percentiles::of(&ttfbs),
+        models: models.into_values().collect(),
+    }
+}
+
+impl Percentiles {
+    fn of(sorted: &[u64]) -> Self {
+        Self {
+            p50: percentile(sorted, 0.50),
+            p95: percentile(sorted, 0.95),
+            p99: percentile(sorted, 0.99),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(model: &str, latency_ms: u64, status: u16) -> RequestSummary {
+        let mut s = RequestSummary::new("req", "/v1/messages", "POST", "translate", &HeaderMap::new());
+        s.model = Some(model.to_string());
+        s.latency_ms = latency_ms;
+        s.status = status;
+        s.input_tokens = Some(10);
+        s.output_tokens = Some(5);
+        s
+    }
+
+    #[test]
+    fn stats_aggregate_rate_latency_and_models() {
+        let mut entries: Vec<_> = (1..=100).map(|ms| summary("kimi", ms, 200)).collect();
+        entries.push(summary("gpt-4o", 1000, 502));
+        let stats = stats(&entries, 10_000);
+        assert_eq!(stats.requests, 101);
+        assert_eq!(stats.errors, 1);
+        assert!((stats.rps - 10.1).abs() < 1e-9);
+        assert_eq!(stats.latency_ms, Percentiles { p50: 51, p95: 96, p99: 100 });
+        assert_eq!(stats.ttfb_ms, Percentiles::default());
+        assert_eq!(stats.models[0].model, "gpt-4o");
+        assert_eq!(stats.models[0].errors, 1);
+        assert_eq!(stats.models[1].input_tokens, 1000);
+    }
+}
@@ -0,0 +1,126 @@
+use axum::http::{HeaderMap, HeaderName};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use crate::access_log::key_id_from_headers;
+use crate::admin::constant_time_eq;
+use crate::audit_log::{AuditLogRecord, AuditLogger};
+use crate::capture::CapturePolicy;
+use crate::config::{AuditOverflowConfig, DebugCaptureConfig};
+
+/// On-demand capture of full request/response exchanges: requests that carry
+/// the debug header with the configured secret, or come from a client key an
+/// admin flagged, get their full audit record (bodies uncapped by the
+/// capture policy, both SSE sides for streams, and the translated downstream
+/// exchange) written to a dedicated file.
+#[derive(Clone)]
+pub struct DebugCapture {
+    logger: AuditLogger,
+    header: HeaderName,
+    secret: Option<String>,
+    max_body_bytes: usize,
+    keys: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl DebugCapture {
+    pub fn new(config: &DebugCaptureConfig) -> Result<Self, String> {
+        let header = HeaderName::from_bytes(config.header.as_bytes())
+            .map_err(|e| format!("debug_capture.header invalid: {}", e))?;
+        Ok(Self {
+            logger: AuditLogger::new(
+                config.path.clone(),
+                config.max_file_bytes,
+                AuditOverflowConfig::default(),
+                CapturePolicy::Full,
+            )?,
+            header,
+            secret: config.secret.clone(),
+            max_body_bytes: config.max_body_bytes,
+            keys: Arc::default(),
+        })
+    }
+
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+
+    pub fn max_body_bytes(&self) -> usize {
+        self.max_body_bytes
+    }
+
+    /// Whether this request should be captured.
+    pub fn requested(&self, headers: &HeaderMap) -> bool {
+        let by_header = self.secret.as_deref().is_some_and(|secret| {
+            headers
+                .get(&self.header)
+                .is_some_and(|value| constant_time_eq(value.as_bytes(), secret.as_bytes()))
+        });
+        by_header
+            || key_id_from_headers(headers).is_some_and(|key_id| self.keys.lock().unwrap().contains(&key_id))
+    }
+
+    /// Flags or unflags a client key id (`key-…`, as in the access log);
+    /// returns whether the set changed.
+    pub fn set_key(&self, key_id: &str, flagged: bool) -> bool {
+        let mut keys = self.keys.lock().unwrap();
+        if flagged {
+            keys.insert(key_id.to_string())
+        } else {
+            keys.remove(key_id)
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.keys.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub async fn push(&self, record: AuditLogRecord) {
+        self.logger.push(record).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn capture(secret: Option<&str>) -> DebugCapture {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-debug-{}", std::process::id()));
+        DebugCapture::new(&DebugCaptureConfig {
+            enabled: true,
+            secret: secret.map(str::to_string),
+            path: dir.join("debug.jsonl").to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requested_by_secret_header_or_flagged_key() {
+        let debug = capture(Some("s3cret"));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("sk-client"));
+        assert!(!debug.requested(&headers));
+
+        headers.insert("x-gateway-debug", HeaderValue::from_static("wrong"));
+        assert!(!debug.requested(&headers));
+        headers.insert("x-gateway-debug", HeaderValue::from_static("s3cret"));
+        assert!(debug.requested(&headers));
+
+        headers.remove("x-gateway-debug");
+        let key_id = key_id_from_headers(&headers).unwrap();
+        assert!(debug.set_key(&key_id, true));
+        assert!(debug.requested(&headers));
+        assert_eq!(debug.keys(), vec![key_id.clone()]);
+        assert!(debug.set_key(&key_id, false));
+        assert!(!debug.requested(&headers));
+    }
+
+    #[tokio::test]
+    async fn header_alone_does_nothing_without_a_secret() {
+        let debug = capture(None);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gateway-debug", HeaderValue::from_static(""));
+        assert!(!debug.requested(&headers));
+    }
+}
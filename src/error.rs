@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     response::IntoResponse,
     Json,
 };
@@ -7,44 +7,138 @@ use axum::{
 use crate::models::{AnthropicErrorBody, AnthropicErrorResponse};
 use crate::translate::TranslateError;
 
-#[derive(Debug)]
+/// Tells clients whether to retry, as the Anthropic and OpenAI SDKs read it.
+pub const SHOULD_RETRY_HEADER: &str = "x-should-retry";
+/// Status of the downstream response an error was mapped from.
+pub const DOWNSTREAM_STATUS_HEADER: &str = "x-gateway-downstream-status";
+
+/// The Anthropic error `type`s the gateway answers with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidRequest,
+    Authentication,
+    Permission,
+    NotFound,
+    RateLimit,
+    RequestTooLarge,
+    Api,
+    Overloaded,
+    Timeout,
+    Cancelled,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request_error",
+            Self::Authentication => "authentication_error",
+            Self::Permission => "permission_error",
+            Self::NotFound => "not_found_error",
+            Self::RateLimit => "rate_limit_error",
+            Self::RequestTooLarge => "request_too_large",
+            Self::Api => "api_error",
+            Self::Overloaded => "overloaded_error",
+            Self::Timeout => "timeout_error",
+            Self::Cancelled => "request_cancelled",
+        }
+    }
+
+    /// Whether an error of this kind answered with `status` is worth
+    /// retrying when no downstream status says otherwise; a 4xx other than
+    /// 408 and 429 is permanent whatever the kind.
+    pub fn retryable(self, status: StatusCode) -> bool {
+        let permanent = status.is_client_error() && !matches!(status.as_u16(), 408 | 429);
+        !permanent && matches!(self, Self::RateLimit | Self::Api | Self::Overloaded | Self::Timeout)
+    }
+
+    fn from_type(error_type: &str) -> Self {
+        match error_type {
+            "invalid_request_error" => Self::InvalidRequest,
+            "authentication_error" => Self::Authentication,
+            "permission_error" => Self::Permission,
+            "not_found_error" => Self::NotFound,
+            "rate_limit_error" => Self::RateLimit,
+            "request_too_large" => Self::RequestTooLarge,
+            "overloaded_error" => Self::Overloaded,
+            "timeout_error" => Self::Timeout,
+            "request_cancelled" => Self::Cancelled,
+            _ => Self::Api,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct AppError {
     pub status: StatusCode,
-    pub error_type: String,
+    pub code: ErrorCode,
     pub message: String,
+    /// Whether sending the same request again may succeed; drives the
+    /// gateway's own retries and `x-should-retry`.
+    pub retryable: bool,
+    /// Status of the downstream response this error was mapped from.
+    pub downstream_status: Option<StatusCode>,
 }
 
 impl AppError {
-    pub fn invalid_request(message: impl Into<String>) -> Self {
+    fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> Self {
         Self {
-            status: StatusCode::BAD_REQUEST,
-            error_type: "invalid_request_error".to_string(),
+            status,
+            code,
             message: message.into(),
+            retryable: code.retryable(status),
+            downstream_status: None,
         }
     }
 
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::InvalidRequest, message)
+    }
+
     pub fn api_error(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::BAD_GATEWAY,
-            error_type: "api_error".to_string(),
-            message: message.into(),
-        }
+        Self::new(StatusCode::BAD_GATEWAY, ErrorCode::Api, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, ErrorCode::Authentication, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, ErrorCode::Permission, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, message)
     }
 
     pub fn rate_limited(message: impl Into<String>) -> Self {
-        Self {
-            status: StatusCode::TOO_MANY_REQUESTS,
-            error_type: "rate_limit_error".to_string(),
-            message: message.into(),
-        }
+        Self::new(StatusCode::TOO_MANY_REQUESTS, ErrorCode::RateLimit, message)
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::PAYLOAD_TOO_LARGE, ErrorCode::RequestTooLarge, message)
+    }
+
+    /// Request cancelled through `DELETE /admin/inflight/{id}`. Uses 499 so
+    /// clients do not retry it.
+    pub fn cancelled() -> Self {
+        Self::new(
+            StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST),
+            ErrorCode::Cancelled,
+            "request cancelled by the gateway operator",
+        )
+    }
+
+    pub fn timeout(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::GATEWAY_TIMEOUT, ErrorCode::Timeout, message)
     }
 
     pub fn from_translate(err: TranslateError) -> Self {
-        Self {
-            status: StatusCode::BAD_REQUEST,
-            error_type: err.error_type,
-            message: err.message,
-        }
+        Self::new(StatusCode::BAD_REQUEST, ErrorCode::from_type(&err.error_type), err.message)
+    }
+
+    /// The Anthropic error `type`.
+    pub fn error_type(&self) -> &'static str {
+        self.code.as_str()
     }
 }
 
@@ -53,24 +147,39 @@ impl IntoResponse for AppError {
         let body = AnthropicErrorResponse {
             response_type: "error".to_string(),
             error: AnthropicErrorBody {
-                error_type: self.error_type,
+                error_type: self.error_type().to_string(),
                 message: self.message,
             },
         };
-        (self.status, Json(body)).into_response()
+        let mut response = (self.status, Json(body)).into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            SHOULD_RETRY_HEADER,
+            HeaderValue::from_static(if self.retryable { "true" } else { "false" }),
+        );
+        if let Some(status) = self.downstream_status {
+            headers.insert(DOWNSTREAM_STATUS_HEADER, HeaderValue::from(status.as_u16()));
+        }
+        response
     }
 }
 
+/// Downstream statuses worth sending the request again for.
+pub fn retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
 pub fn map_downstream_error(status: StatusCode, body: &str) -> AppError {
-    let mapped = match status.as_u16() {
-        400 => "invalid_request_error",
-        401 => "authentication_error",
-        403 => "permission_error",
-        404 => "not_found_error",
-        429 => "rate_limit_error",
-        500 => "api_error",
-        502 | 503 | 504 => "overloaded_error",
-        _ => "api_error",
+    let code = match status.as_u16() {
+        400 => ErrorCode::InvalidRequest,
+        401 => ErrorCode::Authentication,
+        403 => ErrorCode::Permission,
+        404 => ErrorCode::NotFound,
+        408 => ErrorCode::Timeout,
+        429 => ErrorCode::RateLimit,
+        500 => ErrorCode::Api,
+        502 | 503 | 504 | 529 => ErrorCode::Overloaded,
+        _ => ErrorCode::Api,
     };
 
     let message = if body.is_empty() {
@@ -81,7 +190,37 @@ pub fn map_downstream_error(status: StatusCode, body: &str) -> AppError {
 
     AppError {
         status: StatusCode::BAD_GATEWAY,
-        error_type: mapped.to_string(),
+        code,
         message,
+        retryable: retryable_status(status),
+        downstream_status: Some(status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downstream_status_decides_retryability() {
+        let overloaded = map_downstream_error(StatusCode::SERVICE_UNAVAILABLE, "");
+        assert_eq!((overloaded.code, overloaded.retryable), (ErrorCode::Overloaded, true));
+        let unprocessable = map_downstream_error(StatusCode::UNPROCESSABLE_ENTITY, "bad schema");
+        assert_eq!((unprocessable.code, unprocessable.retryable), (ErrorCode::Api, false));
+        assert!(!AppError::cancelled().retryable);
+        assert!(AppError::timeout("slow").retryable);
+        let untranslatable = AppError::from_translate(TranslateError::api_error("missing choices in response"));
+        assert_eq!(
+            (untranslatable.status, untranslatable.code, untranslatable.retryable),
+            (StatusCode::BAD_REQUEST, ErrorCode::Api, false)
+        );
+
+        let response = unprocessable.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(response.headers()[SHOULD_RETRY_HEADER], "false");
+        assert_eq!(response.headers()[DOWNSTREAM_STATUS_HEADER], "422");
+        let response = AppError::rate_limited("busy").into_response();
+        assert_eq!(response.headers()[SHOULD_RETRY_HEADER], "true");
+        assert!(response.headers().get(DOWNSTREAM_STATUS_HEADER).is_none());
     }
 }
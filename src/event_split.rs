@@ -0,0 +1,179 @@
+use axum::body::Bytes;
+use futures_util::stream;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+
+use crate::backpressure::ClientStream;
+use crate::sse::{event_end, SseParser};
+
+/// `streaming.max_event_bytes`: a `text_delta`, `thinking_delta` or
+/// `input_json_delta` event larger than `max_event_bytes` is split into
+/// consecutive deltas for the same block, each within the limit; the client
+/// concatenates them back. Other events pass unchanged, whatever their size.
+pub fn split_oversized(inner: ClientStream, max_event_bytes: usize) -> ClientStream {
+    let state = Splitter {
+        inner,
+        max_event_bytes,
+        buffer: Vec::new(),
+        ready: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.ready.pop_front() {
+                return Some((item, state));
+            }
+            if state.done {
+                return None;
+            }
+            match state.inner.next().await {
+                Some(Ok(bytes)) => {
+                    state.buffer.extend_from_slice(&bytes);
+                    while let Some(end) = event_end(&state.buffer) {
+                        let rest = state.buffer.split_off(end);
+                        let event = std::mem::replace(&mut state.buffer, rest);
+                        state.push_event(Bytes::from(event));
+                    }
+                }
+                Some(Err(err)) => {
+                    state.finish();
+                    state.ready.push_back(Err(err));
+                }
+                None => state.finish(),
+            }
+        }
+    })
+    .boxed()
+}
+
+struct Splitter {
+    inner: ClientStream,
+    max_event_bytes: usize,
+    buffer: Vec<u8>,
+    ready: VecDeque<Result<Bytes, std::io::Error>>,
+    done: bool,
+}
+
+impl Splitter {
+    fn push_event(&mut self, raw: Bytes) {
+        if raw.len() <= self.max_event_bytes {
+            self.ready.push_back(Ok(raw));
+            return;
+        }
+        match split_delta(&raw, self.max_event_bytes) {
+            Some(events) => self.ready.extend(events.into_iter().map(|event| Ok(Bytes::from(event)))),
+            None => self.ready.push_back(Ok(raw)),
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            self.ready.push_back(Ok(Bytes::from(rest)));
+        }
+        self.done = true;
+    }
+}
+
+/// The delta event re-rendered as pieces of at most `max_event_bytes`;
+/// `None` for events that are not splittable deltas.
+fn split_delta(raw: &[u8], max_event_bytes: usize) -> Option<Vec<String>> {
+    let mut parser = SseParser::default();
+    let mut events = parser.push(raw);
+    events.extend(parser.finish());
+    let [event] = events.as_slice() else {
+        return None;
+    };
+    let data: Value = serde_json::from_str(&event.data).ok()?;
+    if data.get("type").and_then(Value::as_str) != Some("content_block_delta") {
+        return None;
+    }
+    let index = data.get("index").and_then(Value::as_u64)?;
+    let kind = data.pointer("/delta/type").and_then(Value::as_str)?;
+    let field = match kind {
+        "text_delta" => "text",
+        "thinking_delta" => "thinking",
+        "input_json_delta" => "partial_json",
+        _ => return None,
+    };
+    let text = data.pointer("/delta").and_then(|delta| delta.get(field)).and_then(Value::as_str)?;
+    let render = |piece: &str| {
+        let data = json!({
+            "type": "content_block_delta",
+            "index": index,
+            "delta": {"type": kind, field: piece},
+        });
+        format!("event: content_block_delta\ndata: {}\n\n", data)
+    };
+    let budget = max_event_bytes.checked_sub(render("").len()).filter(|budget| *budget >= 6)?;
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut size = 0;
+    for (at, c) in text.char_indices() {
+        let escaped = escaped_len(c);
+        if size + escaped > budget {
+            pieces.push(render(&text[start..at]));
+            start = at;
+            size = 0;
+        }
+        size += escaped;
+    }
+    pieces.push(render(&text[start..]));
+    Some(pieces)
+}
+
+/// Bytes `c` takes in a JSON string as serde_json writes it.
+fn escaped_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        c if (c as u32) < 0x20 => 6,
+        c => c.len_utf8(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(index: u32, kind: &str, field: &str, text: &str) -> String {
+        format!(
+            "event: content_block_delta\ndata: {}\n\n",
+            json!({"type": "content_block_delta", "index": index, "delta": {"type": kind, field: text}})
+        )
+    }
+
+    #[tokio::test]
+    async fn splits_large_deltas_within_the_limit() {
+        let text = "héllo \"wörld\"\n".repeat(20);
+        let args = format!("{{\"path\":\"{}\"}}", "x".repeat(300));
+        let ping = "event: ping\ndata: {\"type\":\"ping\"}\n\n";
+        let input = [
+            delta(0, "text_delta", "text", &text),
+            ping.to_string(),
+            delta(1, "input_json_delta", "partial_json", &args),
+        ]
+        .concat();
+        // Chunk boundaries need not match event boundaries.
+        let (head, tail) = input.as_bytes().split_at(37);
+        let body: ClientStream = stream::iter(vec![Ok(Bytes::copy_from_slice(head)), Ok(Bytes::copy_from_slice(tail))]).boxed();
+        let events: Vec<Bytes> = split_oversized(body, 160).map(|chunk| chunk.expect("chunk")).collect().await;
+
+        let mut texts = String::new();
+        let mut partial_json = String::new();
+        for raw in &events {
+            assert!(raw.len() <= 160, "{}", String::from_utf8_lossy(raw));
+            let mut parser = SseParser::default();
+            let event = parser.push(raw).pop().expect("event");
+            let data: Value = serde_json::from_str(&event.data).expect("json");
+            match data.pointer("/delta/type").and_then(Value::as_str) {
+                Some("text_delta") => texts.push_str(data["delta"]["text"].as_str().unwrap()),
+                Some("input_json_delta") => partial_json.push_str(data["delta"]["partial_json"].as_str().unwrap()),
+                _ => assert_eq!(raw.as_ref(), ping.as_bytes()),
+            }
+        }
+        assert!(events.len() > 4);
+        assert_eq!(texts, text);
+        assert_eq!(partial_json, args);
+    }
+}
@@ -0,0 +1,114 @@
+use axum::http::HeaderMap;
+use sha2::{Digest, Sha256};
+
+use crate::access_log::key_id_from_headers;
+use crate::config::ExperimentConfig;
+
+pub const EXPERIMENT_HEADER: &str = "x-gateway-experiment";
+
+/// Buckets per experiment; variant percents resolve to 0.01% granularity.
+const BUCKETS: u64 = 10_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub experiment: String,
+    pub variant: String,
+    /// Downstream model for the variant; `None` for the control group.
+    pub model: Option<String>,
+}
+
+impl Assignment {
+    /// `experiment/variant`, as stamped in headers, logs and metrics.
+    pub fn label(&self) -> String {
+        format!("{}/{}", self.experiment, self.variant)
+    }
+}
+
+/// Assigns a request for `model` to a variant. The same `unit` always lands
+/// in the same bucket of a given experiment, so assignment is sticky.
+pub fn assign(experiments: &[ExperimentConfig], model: &str, unit: &str) -> Option<Assignment> {
+    let experiment = experiments.iter().find(|e| e.model == model)?;
+    let bucket = bucket(&experiment.name, unit);
+    let mut upper = 0.0;
+    for variant in &experiment.variants {
+        upper += variant.percent / 100.0 * BUCKETS as f64;
+        if (bucket as f64) < upper {
+            return Some(Assignment {
+                experiment: experiment.name.clone(),
+                variant: variant.name.clone(),
+                model: Some(variant.model.clone()),
+            });
+        }
+    }
+    Some(Assignment {
+        experiment: experiment.name.clone(),
+        variant: "control".to_string(),
+        model: None,
+    })
+}
+
+/// Sticky assignment unit: `metadata.user_id`, then the API key id, then the
+/// request id (no stickiness).
+pub fn assignment_unit(user_id: Option<&str>, headers: &HeaderMap, request_id: &str) -> String {
+    user_id
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .or_else(|| key_id_from_headers(headers))
+        .unwrap_or_else(|| request_id.to_string())
+}
+
+fn bucket(experiment: &str, unit: &str) -> u64 {
+    let digest = Sha256::digest(format!("{}:{}", experiment, unit).as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix) % BUCKETS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExperimentVariant;
+
+    fn experiment(percent: f64) -> Vec<ExperimentConfig> {
+        vec![ExperimentConfig {
+            name: "kimi-upgrade".to_string(),
+            model: "kimi-k2.5".to_string(),
+            variants: vec![ExperimentVariant {
+                name: "candidate".to_string(),
+                model: "kimi-k2.6".to_string(),
+                percent,
+            }],
+        }]
+    }
+
+    #[test]
+    fn assignment_is_sticky_and_respects_percent() {
+        let experiments = experiment(50.0);
+        let first = assign(&experiments, "kimi-k2.5", "user-1").unwrap();
+        assert_eq!(assign(&experiments, "kimi-k2.5", "user-1"), Some(first));
+        assert!(assign(&experiments, "gpt-4o", "user-1").is_none());
+
+        let candidates = (0..1000)
+            .filter_map(|i| assign(&experiments, "kimi-k2.5", &format!("user-{}", i)))
+            .filter(|a| a.variant == "candidate")
+            .count();
+        assert!((400..600).contains(&candidates), "{}", candidates);
+
+        let all = assign(&experiment(100.0), "kimi-k2.5", "user-1").unwrap();
+        assert_eq!(all.label(), "kimi-upgrade/candidate");
+        assert_eq!(all.model.as_deref(), Some("kimi-k2.6"));
+        let none = assign(&experiment(0.0), "kimi-k2.5", "user-1").unwrap();
+        assert_eq!(none.variant, "control");
+        assert!(none.model.is_none());
+    }
+
+    #[test]
+    fn unit_prefers_metadata_user_id() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "sk-test".parse().unwrap());
+        assert_eq!(assignment_unit(Some("u-42"), &headers, "req-1"), "u-42");
+        let unit = assignment_unit(Some(""), &headers, "req-1");
+        assert_eq!(Some(unit), key_id_from_headers(&headers));
+        assert_eq!(assignment_unit(None, &HeaderMap::new(), "req-1"), "req-1");
+    }
+}
@@ -0,0 +1,252 @@
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::audit_log::now_ms;
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether the OTLP trace and metric exporters are delivering: traces as
+/// seen by the telemetry watchdog's periodic `force_flush`, metrics by the
+/// result of each scheduled export. Reported by `/health` and the
+/// `ai.gateway.exporter.consecutive_failures` gauge.
+#[derive(Clone, Default)]
+pub struct ExporterHealth {
+    pub traces: Arc<ExporterStatus>,
+    pub metrics: Arc<ExporterStatus>,
+}
+
+impl ExporterHealth {
+    /// No enabled exporter is currently failing.
+    pub fn healthy(&self) -> bool {
+        [&self.traces, &self.metrics]
+            .iter()
+            .all(|status| !status.enabled() || status.consecutive_failures() == 0)
+    }
+
+    pub fn snapshot(&self) -> Value {
+        json!({
+            "traces": self.traces.snapshot(),
+            "metrics": self.metrics.snapshot(),
+        })
+    }
+}
+
+pub struct ExporterStatus {
+    exporter: Mutex<String>,
+    /// Unix ms of the last successful flush; 0 until the first one.
+    last_success_ms: AtomicU64,
+    consecutive_failures: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl Default for ExporterStatus {
+    fn default() -> Self {
+        Self {
+            exporter: Mutex::new("noop".to_string()),
+            last_success_ms: AtomicU64::new(0),
+            consecutive_failures: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+}
+
+impl ExporterStatus {
+    /// The configured exporter kind; `noop` (the default) means there is
+    /// nothing to monitor.
+    pub fn set_exporter(&self, kind: &str) {
+        *self.exporter.lock().unwrap() = kind.to_string();
+    }
+
+    pub fn enabled(&self) -> bool {
+        *self.exporter.lock().unwrap() != "noop"
+    }
+
+    pub fn record(&self, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.last_success_ms.store(now_ms() as u64, Ordering::Relaxed);
+                self.consecutive_failures.store(0, Ordering::Relaxed);
+                *self.last_error.lock().unwrap() = None;
+            }
+            Err(err) => {
+                self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+                *self.last_error.lock().unwrap() = Some(err);
+            }
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures.load(Ordering::Relaxed)
+    }
+
+    fn snapshot(&self) -> Value {
+        let last_success_ms = self.last_success_ms.load(Ordering::Relaxed);
+        let failures = self.consecutive_failures();
+        let status = if !self.enabled() {
+            "disabled"
+        } else if failures > 0 {
+            "failing"
+        } else if last_success_ms == 0 {
+            "pending"
+        } else {
+            "ok"
+        };
+        json!({
+            "exporter": *self.exporter.lock().unwrap(),
+            "status": status,
+            "last_success_ms": (last_success_ms > 0).then_some(last_success_ms),
+            "consecutive_failures": failures,
+            "last_error": *self.last_error.lock().unwrap(),
+        })
+    }
+}
+
+/// Flushes the trace provider every 30s and records the result in
+/// `health`; a failing flush means the batch worker or the collector is
+/// down. Logs on the first failure and on recovery. Metrics are left to
+/// `ReportingExporter`, so `metrics.export_interval_ms` stays the only
+/// export schedule.
+pub fn spawn_telemetry_watchdog(
+    tracer: SdkTracerProvider,
+    health: ExporterHealth,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(WATCHDOG_INTERVAL);
+        if health.traces.enabled() {
+            check("traces", &health.traces, tracer.force_flush().map_err(|e| e.to_string()));
+        }
+    })
+}
+
+/// Wraps the metric exporter and records every export the periodic reader
+/// makes in `status`.
+pub struct ReportingExporter<E> {
+    inner: E,
+    status: Arc<ExporterStatus>,
+}
+
+impl<E> ReportingExporter<E> {
+    pub fn new(inner: E, status: Arc<ExporterStatus>) -> Self {
+        Self { inner, status }
+    }
+}
+
+impl<E: PushMetricExporter> PushMetricExporter for ReportingExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let result = self.inner.export(metrics).await;
+        let outcome = result.as_ref().map(|_| ()).map_err(|e| e.to_string());
+        check("metrics", &self.status, outcome);
+        result
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+fn check(signal: &str, status: &ExporterStatus, result: Result<(), String>) {
+    let failures = status.consecutive_failures();
+    match &result {
+        Err(err) if failures == 0 => {
+            warn!("{} exporter force_flush failed (batch worker may be down): {}", signal, err)
+        }
+        Ok(()) if failures > 0 => {
+            info!("{} exporter recovered after {} failed flushes", signal, failures)
+        }
+        _ => {}
+    }
+    status.record(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_tracks_failures_and_recovery() {
+        let status = ExporterStatus::default();
+        assert_eq!(status.snapshot()["status"], "disabled");
+
+        status.set_exporter("otlp_grpc");
+        assert_eq!(status.snapshot()["status"], "pending");
+        assert_eq!(status.snapshot()["last_success_ms"], Value::Null);
+
+        status.record(Err("connection refused".to_string()));
+        status.record(Err("connection refused".to_string()));
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot["status"], "failing");
+        assert_eq!(snapshot["consecutive_failures"], 2);
+        assert_eq!(snapshot["last_error"], "connection refused");
+
+        status.record(Ok(()));
+        let snapshot = status.snapshot();
+        assert_eq!(snapshot["status"], "ok");
+        assert_eq!(snapshot["consecutive_failures"], 0);
+        assert_eq!(snapshot["last_error"], Value::Null);
+        assert!(snapshot["last_success_ms"].as_u64().unwrap() > 0);
+    }
+
+    struct FlakyExporter {
+        fail: std::sync::atomic::AtomicBool,
+    }
+
+    impl PushMetricExporter for FlakyExporter {
+        async fn export(&self, _metrics: &ResourceMetrics) -> OTelSdkResult {
+            if self.fail.load(Ordering::SeqCst) {
+                Err(opentelemetry_sdk::error::OTelSdkError::InternalFailure("collector down".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn temporality(&self) -> Temporality {
+            Temporality::Cumulative
+        }
+    }
+
+    #[tokio::test]
+    async fn reporting_exporter_records_each_scheduled_export() {
+        let health = ExporterHealth::default();
+        health.metrics.set_exporter("otlp_http");
+        let exporter = ReportingExporter::new(
+            FlakyExporter { fail: std::sync::atomic::AtomicBool::new(true) },
+            health.metrics.clone(),
+        );
+        assert!(health.healthy());
+
+        assert!(exporter.export(&ResourceMetrics::default()).await.is_err());
+        assert!(!health.healthy());
+        let snapshot = health.snapshot();
+        assert_eq!(snapshot["metrics"]["status"], "failing");
+        assert_eq!(snapshot["traces"]["status"], "disabled");
+
+        exporter.inner.fail.store(false, Ordering::SeqCst);
+        assert!(exporter.export(&ResourceMetrics::default()).await.is_ok());
+        assert!(health.healthy());
+        assert_eq!(health.snapshot()["metrics"]["status"], "ok");
+    }
+}
@@ -0,0 +1,157 @@
+use axum::Router;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+use crate::alerts;
+use crate::canary::Canary;
+use crate::config::Config;
+use crate::metrics::init_metrics_noop;
+use crate::provider::{Provider, ProviderRegistry};
+use crate::server::{build_routes, build_state, edge_layers};
+use crate::state::AppState;
+use crate::tracing_otlp::init_tracer_noop;
+
+/// Builds the gateway for running inside another process. Unlike `serve`, it
+/// installs no log subscriber and no exporters: metrics go through the host's
+/// global OpenTelemetry meter provider, and spans through the tracer provider
+/// passed in (noop by default).
+pub struct GatewayBuilder {
+    config: Config,
+    tracer_provider: Option<SdkTracerProvider>,
+    alerts: bool,
+    providers: ProviderRegistry,
+}
+
+impl GatewayBuilder {
+    /// Takes a config that has already been validated, e.g. by
+    /// `Config::from_path` or `Config::from_yaml`.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            tracer_provider: None,
+            alerts: true,
+            providers: ProviderRegistry::builtin(),
+        }
+    }
+
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        Config::from_path(path).map(Self::new)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        Config::from_yaml(content).map(Self::new)
+    }
+
+    pub fn tracer_provider(mut self, provider: SdkTracerProvider) -> Self {
+        self.tracer_provider = Some(provider);
+        self
+    }
+
+    /// Whether to start the `alerts` evaluator (when configured); on by default.
+    pub fn alerts(mut self, enabled: bool) -> Self {
+        self.alerts = enabled;
+        self
+    }
+
+    /// Registers a downstream provider that `downstream.provider` can then
+    /// select by `name`; replaces a built-in one of the same name.
+    pub fn provider(mut self, name: &str, provider: Arc<dyn Provider>) -> Self {
+        self.providers.register(name, provider);
+        self
+    }
+
+    /// Connects the configured stores and returns the gateway. Must run inside
+    /// a Tokio runtime, which also hosts the background tasks.
+    pub async fn build(self) -> Result<Gateway, String> {
+        let inflight_count = Arc::new(AtomicU64::new(0));
+        let tracer_provider = self
+            .tracer_provider
+            .unwrap_or_else(|| init_tracer_noop(self.config.observability.service_name.clone()));
+        let state = build_state(
+            &self.config,
+            init_metrics_noop(inflight_count.clone()),
+            inflight_count,
+            tracer_provider,
+            self.providers.clone(),
+        )
+        .await?;
+        if self.alerts
+            && let Some(alerts) = self.config.alerts.clone()
+        {
+            alerts::spawn_alerts(alerts, state.usage.clone(), state.clients.client());
+        }
+        let mut router = build_routes(&self.config, state.clone())?;
+        if let Some(canary) = self.config.canary.as_ref() {
+            router = Canary::build(canary, &state, self.providers).await?.layer(router);
+        }
+        let router = edge_layers(&self.config, router)?;
+        Ok(Gateway { state, router })
+    }
+}
+
+/// A built gateway: its router, with the same routes and layers as the
+/// binary.
+pub struct Gateway {
+    state: AppState,
+    router: Router,
+}
+
+impl Gateway {
+    /// The gateway's routes, ready to `merge` into a host app or serve as is.
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    pub fn into_router(self) -> Router {
+        self.router
+    }
+
+    /// Delivers the usage events still queued for `billing`, including a
+    /// batch that is being retried. Call it before the host exits; events
+    /// emitted afterwards are dropped.
+    pub async fn shutdown(&self) {
+        if let Some(billing) = self.state.billing.as_ref() {
+            billing.shutdown().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+
+    #[tokio::test]
+    async fn router_mounts_inside_a_host_app() {
+        let gateway = GatewayBuilder::from_yaml(
+            "server: {}\ndownstream:\n  base_url: \"http://127.0.0.1:9\"\nmodels:\n  model_map:\n    claude: claude\nlimits: {}\nobservability: {}\n",
+        )
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+        assert!(gateway.state.inflight_requests.list().is_empty());
+
+        let app = Router::new()
+            .route("/host", get(|| async { "host" }))
+            .merge(gateway.into_router());
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("bind failed: {}", err),
+        };
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let host = client.get(format!("{}/host", base)).send().await.unwrap();
+        assert_eq!(host.text().await.unwrap(), "host");
+        let health = client.get(format!("{}/health", base)).send().await.unwrap();
+        assert!(health.status().is_success());
+        let health: serde_json::Value = health.json().await.unwrap();
+        assert_eq!(health["exporters_healthy"], true);
+    }
+}
@@ -1,203 +1,460 @@
 use axum::{
     body::{Body, Bytes},
-    extract::State,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    extract::{OriginalUri, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
     response::IntoResponse,
     Json,
 };
+use futures_util::StreamExt;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use reqwest::Url;
 use serde_json::Value;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::HashMap;
 use std::time::Instant;
 use tracing::info;
 use opentelemetry::KeyValue;
 use opentelemetry::global;
-use opentelemetry::trace::{Span, Tracer};
+use opentelemetry::trace::{Span, SpanBuilder, Tracer};
 
+use crate::config::JsonSchemaSupport;
 use crate::error::{map_downstream_error, AppError};
 use crate::models::*;
-use crate::streaming::{stream_anthropic_passthrough, stream_messages};
+use crate::capture::CapturePolicy;
+use crate::raw_body::{RawJson, RequestHead};
+use crate::streaming::{stream_anthropic_passthrough, stream_messages, StreamContext};
+use crate::experiments::{assign, assignment_unit, Assignment, EXPERIMENT_HEADER};
+use crate::routing::{estimate_prompt_tokens, RouteDecision};
+use crate::ids;
+use crate::tool_ids;
+use crate::translation_events;
+use crate::prompts::template_variables;
+use crate::rewrite::{rewrite_passthrough, rewrite_request};
+use crate::shadow::ShadowProtocol;
+use crate::inflight::InflightTicket;
 use crate::state::{AppState, InflightGuard};
-use crate::translate::{anthropic_to_openai, openai_to_anthropic};
+use crate::structured_output;
+use crate::context::fit_context;
+use crate::translate::{
+    anthropic_to_openai, append_text_to_reasoning_only, assistant_prefill, normalize_response, openai_to_anthropic,
+    strip_prefill_echo, AnthropicVersion,
+};
 use crate::translate::openai_models_to_anthropic;
-use crate::audit_log::{AuditContext, AuditMeta, headers_to_map, now_ms};
+use crate::audit_log::{AuditContext, AuditDownstream, AuditMeta, headers_to_map, now_ms};
+use crate::validation::parse_request;
+use crate::vertex::vertex_body;
+use crate::access_log::{key_id_from_headers, RequestSummary};
+use crate::admin::require_admin;
+use crate::client_identity::{self, CallContext};
+use crate::tracing_otlp::{
+    langfuse_generation_input, set_langfuse_generation_input, set_langfuse_generation_output, set_downstream_timing,
+    LangfuseAttribution, TraceIds, TRACE_HEADER,
+};
 
+#[utoipa::path(
+    post,
+    path = "/v1/messages",
+    tag = "messages",
+    request_body = AnthropicRequest,
+    responses(
+        (status = 200, description = "Message, or an SSE stream of message events when `stream` is true",
+            content((AnthropicResponse = "application/json"), (String = "text/event-stream"))),
+        (status = 400, body = AnthropicErrorResponse),
+        (status = 429, body = AnthropicErrorResponse),
+        (status = 502, body = AnthropicErrorResponse),
+    )
+)]
 pub async fn post_messages(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<Value>,
+    body: RawJson,
+) -> Result<axum::response::Response, AppError> {
+    let start = Instant::now();
+    let request_id = ids::request_id();
+    let trace = TraceIds::generate();
+    let model = body.head.model().filter(|model| !model.is_empty()).map(str::to_string);
+    let assignment = model.as_ref().and_then(|model| {
+        let unit = assignment_unit(body.head.user_id(), &headers, &request_id);
+        assign(&state.config.experiments, model, &unit)
+    });
+    let key_id = key_id_from_headers(&headers);
+    let stream = body.head.stream() == Some(true);
+    let deadline = if stream {
+        None
+    } else {
+        model
+            .as_deref()
+            .and_then(|model| state.config.max_request_duration(model, key_id.as_deref()))
+    };
+    let ticket = state
+        .inflight_requests
+        .register(&request_id, key_id, model.clone(), stream);
+    let result = tokio::select! {
+        result = handle_messages(
+            state.clone(),
+            headers.clone(),
+            body,
+            request_id.clone(),
+            trace,
+            assignment.clone(),
+            ticket.clone(),
+        ) => result,
+        _ = ticket.cancelled() => {
+            let err = AppError::cancelled();
+            state.metrics.errors.add(1, &[KeyValue::new("type", err.error_type())]);
+            Err(abandon_request(&state, &headers, &request_id, &trace, model.as_deref(), start, err))
+        }
+        _ = sleep_until_deadline(deadline) => {
+            let limit_ms = deadline.unwrap_or_default().as_millis();
+            let err = AppError::timeout(format!("request exceeded max duration of {} ms", limit_ms));
+            state.metrics.errors.add(1, &[KeyValue::new("type", "timeout")]);
+            Err(abandon_request(&state, &headers, &request_id, &trace, model.as_deref(), start, err))
+        }
+    };
+    let mut resp = result.unwrap_or_else(IntoResponse::into_response);
+    if let Ok(value) = HeaderValue::from_str(&trace.trace_id.to_string()) {
+        resp.headers_mut().insert(TRACE_HEADER, value);
+    }
+    if let Some(assignment) = assignment
+        && let Ok(value) = HeaderValue::from_str(&assignment.label())
+    {
+        resp.headers_mut().insert(EXPERIMENT_HEADER, value);
+    }
+    Ok(resp)
+}
+
+/// Records a `/v1/messages` request abandoned before `handle_messages`
+/// finished; dropping that future aborts the downstream call.
+fn abandon_request(
+    state: &AppState,
+    headers: &HeaderMap,
+    request_id: &str,
+    trace: &TraceIds,
+    model: Option<&str>,
+    start: Instant,
+    err: AppError,
+) -> AppError {
+    let mut summary = RequestSummary::new(
+        request_id,
+        "/v1/messages",
+        "POST",
+        state.config.forward_mode(),
+        headers,
+    );
+    set_trace_ids(&mut summary, trace);
+    log_error(state, &summary, model.unwrap_or_default(), start.elapsed().as_millis(), &err);
+    err
+}
+
+async fn sleep_until_deadline(deadline: Option<std::time::Duration>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn handle_messages(
+    state: AppState,
+    headers: HeaderMap,
+    body: RawJson,
+    request_id: String,
+    trace: TraceIds,
+    assignment: Option<Assignment>,
+    ticket: InflightTicket,
 ) -> Result<axum::response::Response, AppError> {
-    let request_id = next_request_id();
     let start = Instant::now();
-    let payload = payload;
-    let upstream_payload = payload.clone();
-    let model = extract_model(&payload)?;
+    let capture = state.config.capture_policy();
+    let mut summary = RequestSummary::new(
+        &request_id,
+        "/v1/messages",
+        "POST",
+        state.config.forward_mode(),
+        &headers,
+    );
+    summary.experiment = assignment.as_ref().map(Assignment::label);
+    set_trace_ids(&mut summary, &trace);
+    // A plain passthrough call is forwarded on its head alone; `payload`
+    // then stays `Null` because nothing below reads it.
+    let head_only = forwards_head_only(&state, &headers, &body.head, assignment.as_ref());
+    let mut payload = if head_only {
+        Value::Null
+    } else {
+        body.value().inspect_err(|err| {
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, "", start.elapsed().as_millis(), err);
+        })?
+    };
+    let RawJson { head, mut raw } = body;
+    let prompt = state
+        .prompts
+        .render_request(&mut payload)
+        .await
+        .inspect_err(|err| {
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, "", start.elapsed().as_millis(), err);
+        })?;
+    if let Some(prompt) = prompt.as_ref() {
+        info!(request_id = %request_id, prompt = %prompt, "prompt template rendered");
+    }
+    if state.scoring.is_some() {
+        summary.prompt_text = Some(last_user_text(&payload));
+        summary.output_text = Some(String::new());
+    }
+    let experiment = summary.experiment.clone();
+    let variant_model = assignment.and_then(|a| a.model);
+    let model = if head_only { required_model(head.model()) } else { extract_model(&payload) };
+    let model = model.inspect_err(|err| {
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, "", start.elapsed().as_millis(), err);
+    })?;
     let model_before_map = model.clone();
+    summary.model = Some(model.clone());
     if !state.config.models.allowlist.is_empty()
         && !state.config.models.allowlist.contains(&model)
     {
         let err = AppError::invalid_request("model not in allowlist");
-        let error_type = err.error_type.clone();
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &model, start.elapsed().as_millis(), &err);
+        log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
         return Err(err);
     }
     if state.config.models.blocklist.contains(&model) {
         let err = AppError::invalid_request("model is blocked");
-        let error_type = err.error_type.clone();
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &model, start.elapsed().as_millis(), &err);
+        log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
         return Err(err);
     }
-
-    let stream = extract_stream(&payload);
-    let input_messages = extract_messages_for_trace(&payload);
-    let downstream_request = serialize_for_trace(&payload);
+    // Experiment variants take precedence over routing.
+    let routing = if variant_model.is_some() || state.router.is_empty() {
+        None
+    } else {
+        let mapped = state.config.models.model_map.get(&model).unwrap_or(&model);
+        let tier = state
+            .config
+            .routing
+            .cost
+            .as_ref()
+            .and_then(|cost| headers.get(cost.tier_header.as_str()))
+            .and_then(|value| value.to_str().ok());
+        match state.router.route(&payload, &model, tier, state.tokenizers.for_model(mapped)) {
+            Ok(decision) => decision,
+            Err(message) => {
+                let err = AppError::invalid_request(message);
+                let error_type = err.error_type();
+                state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
+                return Err(err);
+            }
+        }
+    };
+    if let Some(decision) = routing.as_ref() {
+        info!(
+            request_id = %request_id,
+            policy = decision.policy,
+            rule = ?decision.rule,
+            model = %decision.model,
+            tier = ?decision.tier,
+            estimated_cost_usd = ?decision.estimated_cost_usd,
+            "request routed"
+        );
+    }
+    let variant_model = variant_model.or_else(|| routing.as_ref().map(|d| d.model.clone()));
+
+    let stream = if head_only { head.stream() } else { extract_stream(&payload) };
+    summary.stream = stream == Some(true);
+
+    if state.config.input_tokens_per_minute(summary.key_id.as_deref()).is_some() {
+        let mapped = state.config.models.model_map.get(&model).unwrap_or(&model);
+        let tokens = estimate_prompt_tokens(&payload, state.tokenizers.for_model(mapped));
+        if let Err(wait) = state.input_limiter.check(&state.config, summary.key_id.as_deref(), tokens) {
+            let err = AppError::rate_limited(format!(
+                "input token budget exceeded; retry in {}s",
+                wait.as_secs() + 1
+            ));
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
+            return Err(err);
+        }
+    }
 
     let inflight = match state.inflight.clone().try_acquire_owned() {
-        Ok(p) => InflightGuard::new(p, state.inflight_count.clone()),
+        Ok(p) => InflightGuard::new(p, state.inflight_count.clone()).with_ticket(ticket),
         Err(_) => {
             let err = AppError::rate_limited("too many in-flight requests");
-            let error_type = err.error_type.clone();
+            let error_type = err.error_type();
             state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-            log_error(&request_id, &model, start.elapsed().as_millis(), &err);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
             return Err(err);
         }
     };
 
-    if state.config.forward_mode() == "passthrough" {
+    if matches!(state.config.forward_mode(), "passthrough" | "vertex" | "rewrite") {
+        // Trace views are rendered before the payload is rewritten and moved
+        // into the downstream request.
+        let input_messages = capture.apply_with(|| extract_messages_for_trace(&payload));
+        let downstream_request = capture.apply_with(|| serialize_for_trace(&payload));
+        let langfuse_input = state
+            .config
+            .langfuse_tracing()
+            .then(|| {
+                let attribution =
+                    LangfuseAttribution::from_headers(&headers, &state.config.observability.langfuse);
+                langfuse_generation_input(&model, &payload, &attribution, capture)
+            });
+        if let Some(shadow) = state.shadow.as_ref().filter(|shadow| shadow.sample()) {
+            shadow.mirror(&request_id, ShadowProtocol::Anthropic, payload.clone(), &headers);
+        }
         let audit_ctx = build_audit_context(
             &state,
             &request_id,
             "/v1/messages",
             "POST",
             &headers,
-            upstream_payload.clone(),
+            &payload,
             Some(model.clone()),
             stream,
-        );
-        if state.config.observability.dump_downstream {
-            info!(
-                request_id = %request_id,
-                "upstream request headers: {}",
-                headers_for_trace(&headers)
-            );
-            info!(
-                request_id = %request_id,
-                "upstream request body: {}",
-                truncate_for_trace(&downstream_request)
-            );
+        )
+        .map(|ctx| {
+            ctx.with_experiment(experiment.clone())
+                .with_routing(routing.clone())
+                .with_trace(&trace)
+        });
+        let downstream_request = if state.config.forward_mode() == "rewrite" {
+            rewrite_request(&mut payload, &state.config).map_err(|e| {
+                let err = AppError::from_translate(e);
+                let error_type = err.error_type();
+                state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
+                err
+            })?;
+            capture.apply_with(|| serialize_for_trace(&payload))
+        } else if state.config.anthropic.passthrough_rewrite
+            && let Some(rewritten) = rewrite_passthrough(&raw, &mut payload, &state.config)
+        {
+            raw = rewritten;
+            capture.apply_with(|| serialize_for_trace(&payload))
+        } else {
+            downstream_request
+        };
+        // The client's bytes are forwarded as they arrived (with
+        // `passthrough_rewrite` edits) unless the gateway changed the body
+        // (prompt template, rewrite, vertex or a variant model).
+        let verbatim =
+            state.config.forward_mode() == "passthrough" && prompt.is_none() && variant_model.is_none();
+        if let Some(variant) = variant_model.as_ref() {
+            payload["model"] = Value::String(variant.clone());
         }
-        let forward_headers = build_passthrough_headers(&headers, &state.config.downstream.base_url);
+        let downstream_model = variant_model.as_deref().unwrap_or(&model);
+        let (downstream_url, mut forward_headers, payload) =
+            match prepare_anthropic_downstream(&state, &headers, payload, downstream_model, stream).await {
+                Ok(prepared) => prepared,
+                Err(err) => {
+                    let error_type = err.error_type();
+                    state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                    log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
+                    return Err(err);
+                }
+            };
+        let call = CallContext {
+            request_id: &request_id,
+            model: Some(downstream_model),
+            incoming: &headers,
+        };
+        client_identity::apply(&state.config.downstream, &call, &mut forward_headers);
+        let body = if verbatim {
+            raw
+        } else {
+            Bytes::from(serde_json::to_vec(&payload).unwrap_or_default())
+        };
+        forward_headers
+            .entry(CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/json"));
         if stream == Some(true) {
-            if state.config.observability.dump_downstream {
-                info!(
-                    request_id = %request_id,
-                    "downstream request headers: {}",
-                    headers_for_trace(&forward_headers)
-                );
-                info!(
-                    request_id = %request_id,
-                    "downstream request body: {}",
-                    truncate_for_trace(&downstream_request)
-                );
-            }
-            let span = start_trace_span(
-                &request_id,
-                &model,
-                input_messages,
-                downstream_request,
-                None,
-                None,
+            let mut span = start_trace_span(
+                &trace,
+                SpanAttributes {
+                    request_id: &request_id,
+                    model: &model,
+                    stream: true,
+                    input: input_messages,
+                    downstream_request,
+                    ..Default::default()
+                },
             );
-            state.metrics.requests.add(1, &[KeyValue::new("stream", "true")]);
-            if !state.config.observability.dump_downstream {
-                info!(
-                    request_id = %request_id,
-                    model = %model,
-                    "stream request accepted"
-                );
+            set_routing_attributes(&mut span, routing.as_ref());
+            if let Some(attributes) = langfuse_input {
+                span.set_attributes(attributes);
             }
-            return stream_anthropic_passthrough(
+            info!(
+                request_id = %request_id,
+                model = %model,
+                "stream request accepted"
+            );
+            summary.downstream_endpoint = Some(downstream_url.clone());
+            let ctx = StreamContext {
                 state,
-                payload,
-                forward_headers,
                 model,
-                audit_ctx,
-                inflight,
                 request_id,
+                guard: inflight,
                 start,
                 span,
-            )
-            .await;
-        }
-
-        if state.config.observability.dump_downstream {
-            info!(
-                request_id = %request_id,
-                "downstream request: {}",
-                downstream_request
-            );
-            info!(
-                request_id = %request_id,
-                "downstream request headers: {}",
-                headers_for_trace(&forward_headers)
-            );
-            info!(
-                request_id = %request_id,
-                "downstream request url: {}",
-                state.config.anthropic_messages_url()
-            );
+                audit_ctx,
+                summary,
+            };
+            return stream_anthropic_passthrough(ctx, downstream_url, body, forward_headers).await;
         }
-        state.metrics.requests.add(1, &[KeyValue::new("stream", "false")]);
 
-        let span = start_trace_span(
-            &request_id,
-            &model,
-            input_messages,
-            downstream_request,
-            None,
-            None,
+        let mut span = start_trace_span(
+            &trace,
+            SpanAttributes {
+                request_id: &request_id,
+                model: &model,
+                stream: false,
+                input: input_messages,
+                downstream_request,
+                ..Default::default()
+            },
         );
+        set_routing_attributes(&mut span, routing.as_ref());
+        if let Some(attributes) = langfuse_input {
+            span.set_attributes(attributes);
+        }
 
+        summary.downstream_endpoint = Some(downstream_url.clone());
         let request = state
-            .client
-            .post(state.config.anthropic_messages_url())
+            .clients
+            .client()
+            .post(downstream_url)
             .headers(forward_headers);
-        let resp = request.json(&payload).send().await.map_err(|e| {
+        summary.downstream_sent();
+        let resp = request.body(body).send().await.map_err(|e| {
                 let err = AppError::api_error(format!("downstream request failed: {}", e));
-                let error_type = err.error_type.clone();
+                let error_type = err.error_type();
                 state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                log_error(&request_id, &model, start.elapsed().as_millis(), &err);
+                log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
                 err
             })?;
+        summary.ttfb_ms = Some(start.elapsed().as_millis() as u64);
+        summary.downstream_first_byte();
 
         let status = resp.status();
         let headers = resp.headers().clone();
         let raw_body = resp.bytes().await.map_err(|e| {
             let err = AppError::api_error(format!("invalid downstream response: {}", e));
-            let error_type = err.error_type.clone();
+            let error_type = err.error_type();
             state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-            log_error(&request_id, &model, start.elapsed().as_millis(), &err);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
             err
         })?;
+        summary.downstream_done();
 
-        if state.config.observability.dump_downstream {
-            info!(
-                request_id = %request_id,
-                "downstream response headers: {}",
-                headers_for_trace(&headers)
-            );
-            if let Ok(text) = std::str::from_utf8(&raw_body) {
-                info!("downstream response: {}", text);
-            }
-        }
-
-        let mut span = span;
         span.set_attribute(KeyValue::new(
             "downstream.response",
-            truncate_for_trace(&String::from_utf8_lossy(&raw_body)),
+            capture.apply(&String::from_utf8_lossy(&raw_body)),
         ));
         state.metrics.latency_ms.record(
             start.elapsed().as_millis() as f64,
@@ -210,11 +467,40 @@ pub async fn post_messages(
             status = status.as_u16(),
             "request completed"
         );
+        summary.status = status.as_u16();
+        summary.latency_ms = start.elapsed().as_millis() as u64;
+        let body = serde_json::from_slice::<Value>(&raw_body).ok();
+        if let Some(body) = &body {
+            if let Some(usage) = body.get("usage") {
+                summary.apply_anthropic_usage(usage);
+            }
+            if let Some(out) = summary.output_text.as_mut()
+                && let Some(blocks) = body.get("content").and_then(Value::as_array)
+            {
+                out.push_str(&anthropic_text(blocks));
+            }
+            if !status.is_success() {
+                summary.error_type = body
+                    .get("error")
+                    .and_then(|e| e.get("type"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+            }
+        }
+        if let Some(error_type) = &summary.error_type {
+            span.set_attribute(KeyValue::new("error.type", error_type.clone()));
+        }
+        if state.config.langfuse_tracing() {
+            let output = body.as_ref().and_then(|b| b.get("content"));
+            set_langfuse_generation_output(&mut span, output, &summary, capture);
+        }
+        set_downstream_timing(&mut span, &summary);
         tokio::spawn(async move {
             span.end();
         });
+        state.record_summary(summary);
 
-        if let Some((logger, ctx)) = state.audit_logger.clone().zip(audit_ctx) {
+        if let Some(ctx) = audit_ctx {
             let (body_value, parse_error) = parse_body_value(&raw_body);
             let record = ctx.finish(
                 status.as_u16(),
@@ -224,32 +510,98 @@ pub async fn post_messages(
                 false,
                 now_ms(),
             );
-            logger.push(record).await;
+            state.push_audit(record).await;
         }
 
         return Ok(response_from_bytes(status, headers.get(CONTENT_TYPE), raw_body));
     }
 
-    let mut anthropic_req: AnthropicRequest = serde_json::from_value(payload).map_err(|e| {
-        let err = AppError::invalid_request(format!("invalid request: {}", e));
-        let error_type = err.error_type.clone();
+    let api_version = AnthropicVersion::from_header(
+        headers.get("anthropic-version").and_then(|v| v.to_str().ok()),
+    )
+    .map_err(|e| {
+        let err = AppError::from_translate(e);
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, &model_before_map, start.elapsed().as_millis(), &err);
+        err
+    })?;
+    // Deserialized from a borrow: the original payload stays available for
+    // translation events, audit and Langfuse without a full clone.
+    let mut anthropic_req = parse_request(&payload).map_err(|e| {
+        let err = AppError::invalid_request(e);
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, &model_before_map, start.elapsed().as_millis(), &err);
+        err
+    })?;
+    api_version.check_request(&anthropic_req).map_err(|e| {
+        let err = AppError::from_translate(e);
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &model_before_map, start.elapsed().as_millis(), &err);
+        log_error(&state, &summary, &model_before_map, start.elapsed().as_millis(), &err);
         err
     })?;
     if let Some(mapped) = state.config.models.model_map.get(&model) {
         anthropic_req.model = mapped.clone();
     }
+    if let Some(variant) = variant_model {
+        anthropic_req.model = variant;
+    }
+    let prefill = assistant_prefill(&anthropic_req);
 
-    let openai_req = anthropic_to_openai(anthropic_req, &state.config).map_err(|e| {
+    let mut openai_req = anthropic_to_openai(anthropic_req, &state.config).map_err(|e| {
         let err = AppError::from_translate(e);
-        let error_type = err.error_type.clone();
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &model_before_map, start.elapsed().as_millis(), &err);
+        log_error(&state, &summary, &model_before_map, start.elapsed().as_millis(), &err);
         err
     })?;
-    let input_messages = serialize_json_for_trace(&openai_req.messages);
-    let downstream_request = serialize_for_trace(&openai_req);
+    let request_events = translation_events::request_events(&payload, &openai_req, &state.config);
+    translation_events::record(&state.metrics, &request_events);
+    let mut warnings = translation_events::warnings(&request_events);
+    if let Some(compactor) = state.compaction.as_ref() {
+        compactor.compact(&state, &mut openai_req, &headers).await;
+    }
+    fit_context(&state, &mut openai_req).await.inspect_err(|err| {
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), err);
+    })?;
+    if let Some(shadow) = state.shadow.as_ref().filter(|shadow| shadow.sample())
+        && let Ok(body) = serde_json::to_value(&openai_req)
+    {
+        shadow.mirror(&request_id, ShadowProtocol::OpenAI, body, &headers);
+    }
+    let provider = state.provider_for(&openai_req.model);
+    let mut downstream = provider.build_request(&openai_req, &state.config).inspect_err(|err| {
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), err);
+    })?;
+    state.endpoints.apply(&state.config, &openai_req.model, &mut downstream);
+    let call = CallContext {
+        request_id: &request_id,
+        model: Some(&openai_req.model),
+        incoming: &headers,
+    };
+    client_identity::apply(&state.config.downstream, &call, &mut downstream.headers);
+    let json_schema_support = state.config.json_schema_support(&openai_req.model);
+    let mut output_format_downgraded = false;
+    if json_schema_support == JsonSchemaSupport::None
+        && let Some(body) = structured_output::fallback_body(&downstream.body)
+    {
+        downstream.body = body;
+        output_format_downgraded = true;
+        if openai_req.stream == Some(true) {
+            let events = translation_events::output_format_fallback(false);
+            translation_events::record(&state.metrics, &events);
+            warnings.extend(translation_events::warnings(&events));
+        }
+    }
+    summary.downstream_endpoint = Some(downstream.url.clone());
+    let input_messages = capture.apply_with(|| serialize_json_for_trace(&openai_req.messages));
+    let downstream_request = capture.apply_with(|| serialize_for_trace(&openai_req));
 
     if openai_req.stream == Some(true) {
         let audit_ctx = build_audit_context(
@@ -258,163 +610,142 @@ pub async fn post_messages(
             "/v1/messages",
             "POST",
             &headers,
-            upstream_payload.clone(),
+            &payload,
             Some(openai_req.model.clone()),
             openai_req.stream,
+        )
+        .map(|ctx| {
+            ctx.with_experiment(experiment.clone())
+                .with_routing(routing.clone())
+                .with_warnings(warnings.clone())
+                .with_trace(&trace)
+        });
+        let mut span = start_trace_span(
+            &trace,
+            SpanAttributes {
+                request_id: &request_id,
+                model: &openai_req.model,
+                stream: true,
+                input: input_messages,
+                downstream_request,
+                ..Default::default()
+            },
         );
-        let span = start_trace_span(
-            &request_id,
-            &openai_req.model,
-            input_messages,
-            downstream_request,
-            None,
-            None,
-        );
-        state.metrics.requests.add(1, &[KeyValue::new("stream", "true")]);
-        if !state.config.observability.dump_downstream {
-            info!(
-                request_id = %request_id,
-                model = %openai_req.model,
-                "stream request accepted"
-            );
+        set_routing_attributes(&mut span, routing.as_ref());
+        if state.config.langfuse_tracing() {
+            let attribution =
+                LangfuseAttribution::from_headers(&headers, &state.config.observability.langfuse);
+            set_langfuse_generation_input(&mut span, &openai_req.model, &payload, &attribution, capture);
         }
-        return stream_messages(
+        info!(
+            request_id = %request_id,
+            model = %openai_req.model,
+            "stream request accepted"
+        );
+        summary.stream = true;
+        let ctx = StreamContext {
             state,
-            openai_req,
-            inflight,
+            model: openai_req.model,
             request_id,
+            guard: inflight,
             start,
             span,
             audit_ctx,
-        )
-        .await;
-    }
-    if state.config.observability.dump_downstream {
-        info!(
-            request_id = %request_id,
-            "downstream request: {}",
-            downstream_request
-        );
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!(
-                "Bearer {}",
-                state.config.downstream.api_key.as_deref().unwrap_or_default()
-            ))
-            .unwrap_or_else(|_| HeaderValue::from_static("[invalid]")),
-        );
-        headers.insert(
-            CONTENT_TYPE,
-            HeaderValue::from_static("application/json"),
-        );
-        info!(
-            request_id = %request_id,
-            "downstream request headers: {}",
-            headers_for_trace(&headers)
-        );
-        info!(
-            request_id = %request_id,
-            "downstream request url: {}",
-            state.config.chat_completions_url()
-        );
+            summary,
+        };
+        let mut resp = stream_messages(ctx, provider, downstream, api_version, prefill).await?;
+        set_warnings_header(&mut resp, &warnings);
+        return Ok(resp);
     }
-    state.metrics.requests.add(1, &[KeyValue::new("stream", "false")]);
-
-    let resp = state
-        .client
-        .post(state.config.chat_completions_url())
-        .header(CONTENT_TYPE, "application/json")
-        .header(
-            AUTHORIZATION,
-            format!(
-                "Bearer {}",
-                state.config.downstream.api_key.as_deref().unwrap_or_default()
-            ),
-        )
-        .json(&openai_req)
-        .send()
+    let client = state.clients.client();
+    let mut body = downstream.encoded_body();
+    summary.downstream_sent();
+    let (resp, retried) = structured_output::send(&state, &client, &mut downstream, &mut body, json_schema_support)
         .await
-        .map_err(|e| {
-        let err = AppError::api_error(format!("downstream request failed: {}", e));
-        let error_type = err.error_type.clone();
-        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &openai_req.model, start.elapsed().as_millis(), &err);
-        err
-    })?;
+        .inspect_err(|err| {
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), err);
+        })?;
+    output_format_downgraded |= retried;
+    summary.ttfb_ms = Some(start.elapsed().as_millis() as u64);
+    summary.downstream_first_byte();
 
     if !resp.status().is_success() {
         let status = resp.status();
         let text = resp.text().await.unwrap_or_default();
-        let mapped = map_downstream_error(status, &text);
-        let error_type = mapped.error_type.clone();
+        let mapped = provider.map_error(status, &text);
+        let error_type = mapped.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &openai_req.model, start.elapsed().as_millis(), &mapped);
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), &mapped);
         return Err(mapped);
     }
 
-    let headers = resp.headers().clone();
+    let downstream_headers = resp.headers().clone();
     let raw_body = resp.text().await.map_err(|e| {
         let err = AppError::api_error(format!("invalid downstream response: {}", e));
-        let error_type = err.error_type.clone();
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &openai_req.model, start.elapsed().as_millis(), &err);
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), &err);
         err
     })?;
+    summary.downstream_done();
 
-    if state.config.observability.dump_downstream {
-        info!(
-            request_id = %request_id,
-            "downstream response headers: {}",
-            headers_for_trace(&headers)
-        );
-        info!("downstream response: {}", raw_body);
-    }
-
-    let openai_resp: OpenAIResponse = serde_json::from_str(&raw_body).map_err(|e| {
-        let err = AppError::api_error(format!("invalid downstream response: {}", e));
-        let error_type = err.error_type.clone();
+    let mut openai_resp = provider.parse_response(&raw_body).inspect_err(|err| {
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &openai_req.model, start.elapsed().as_millis(), &err);
-        err
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), err);
     })?;
+    normalize_response(&mut openai_resp, &openai_req.model);
+    let mut response_events = translation_events::response_events(&openai_resp);
+    if output_format_downgraded {
+        let repaired = structured_output::repair(&mut openai_resp);
+        response_events.extend(translation_events::output_format_fallback(repaired));
+    }
+    translation_events::record(&state.metrics, &response_events);
+    warnings.extend(translation_events::warnings(&response_events));
 
-    let downstream_response = truncate_for_trace(&raw_body);
+    let downstream_response = capture.apply(&raw_body);
     let output_messages = openai_output_messages(&openai_resp);
-    let output_trace = serialize_json_for_trace(&output_messages);
+    let output_trace = capture.apply_with(|| serialize_json_for_trace(&output_messages));
     let mut span = start_trace_span(
-        &request_id,
-        &openai_req.model,
-        input_messages,
-        downstream_request,
-        Some(output_trace),
-        Some(downstream_response),
+        &trace,
+        SpanAttributes {
+            request_id: &request_id,
+            model: &openai_req.model,
+            stream: false,
+            input: input_messages,
+            downstream_request,
+            output: Some(output_trace),
+            downstream_response: Some(downstream_response),
+        },
     );
+    set_routing_attributes(&mut span, routing.as_ref());
+    if state.config.langfuse_tracing() {
+        let attribution =
+            LangfuseAttribution::from_headers(&headers, &state.config.observability.langfuse);
+        set_langfuse_generation_input(&mut span, &openai_req.model, &payload, &attribution, capture);
+    }
 
-    let anthropic_resp = openai_to_anthropic(openai_resp).map_err(|e| {
+    let mut anthropic_resp = openai_to_anthropic(openai_resp, state.config.empty_content_policy()).map_err(|e| {
         let err = AppError::from_translate(e);
-        let error_type = err.error_type.clone();
+        let error_type = err.error_type();
         state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-        log_error(&request_id, &openai_req.model, start.elapsed().as_millis(), &err);
-        span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
+        log_error(&state, &summary, &openai_req.model, start.elapsed().as_millis(), &err);
+        span.set_attribute(KeyValue::new("error.type", err.error_type()));
         err
     })?;
-    if state.config.observability.dump_downstream {
-        if output_messages.as_array().map(|arr| arr.is_empty()).unwrap_or(false) {
-            info!(
-                request_id = %request_id,
-                "upstream response has no output content"
-            );
-        }
+    if let Some(prefill) = prefill.as_deref() {
+        strip_prefill_echo(&mut anthropic_resp, prefill);
     }
-    if state.config.observability.dump_downstream {
-        let upstream = serde_json::to_string(&anthropic_resp).unwrap_or_else(|_| "[unserializable]".to_string());
-        info!(
-            request_id = %request_id,
-            "upstream response: {}",
-            upstream
-        );
+    if state.config.models.normalize_tool_ids {
+        tool_ids::normalize_response(&mut anthropic_resp);
     }
+    if state.config.models.reasoning_only_text {
+        append_text_to_reasoning_only(&mut anthropic_resp);
+    }
+    let mut anthropic_resp = serde_json::to_value(&anthropic_resp).unwrap_or(Value::Null);
+    api_version.shape_response(&mut anthropic_resp);
 
     state.metrics.latency_ms.record(
         start.elapsed().as_millis() as f64,
@@ -427,42 +758,124 @@ pub async fn post_messages(
         status = 200,
         "request completed"
     );
+    summary.model = Some(openai_req.model.clone());
+    summary.latency_ms = start.elapsed().as_millis() as u64;
+    if let Some(usage) = anthropic_resp.get("usage") {
+        summary.apply_anthropic_usage(usage);
+    }
+    if let Some(out) = summary.output_text.as_mut()
+        && let Some(blocks) = anthropic_resp.get("content").and_then(Value::as_array)
+    {
+        out.push_str(&anthropic_text(blocks));
+    }
+    if state.config.langfuse_tracing() {
+        set_langfuse_generation_output(&mut span, anthropic_resp.get("content"), &summary, capture);
+    }
+    set_downstream_timing(&mut span, &summary);
     tokio::spawn(async move {
         span.end();
     });
+    state.record_summary(summary);
 
-    if let Some(logger) = state.audit_logger.clone() {
-        let ctx = build_audit_context(
-            &state,
-            &request_id,
-            "/v1/messages",
-            "POST",
-            &headers,
-            upstream_payload.clone(),
-            Some(openai_req.model.clone()),
-            openai_req.stream,
+    let ctx = build_audit_context(
+        &state,
+        &request_id,
+        "/v1/messages",
+        "POST",
+        &headers,
+        &payload,
+        Some(openai_req.model.clone()),
+        openai_req.stream,
+    )
+    .map(|ctx| {
+        ctx.with_experiment(experiment.clone())
+            .with_routing(routing.clone())
+            .with_warnings(warnings.clone())
+            .with_trace(&trace)
+    });
+    if let Some(ctx) = ctx {
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let record = ctx.finish(
+            200,
+            headers_to_map(&response_headers),
+            anthropic_resp.clone(),
+            false,
+            false,
+            now_ms(),
         );
-        if let Some(ctx) = ctx {
-            let mut response_headers = HeaderMap::new();
-            response_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-            let record = ctx.finish(
-                200,
-                headers_to_map(&response_headers),
-                serde_json::to_value(&anthropic_resp).unwrap_or(Value::Null),
-                false,
-                false,
-                now_ms(),
-            );
-            logger.push(record).await;
-        }
+        let debug_record = record.meta.debug.then(|| {
+            record.clone().with_downstream(AuditDownstream {
+                url: downstream.url.clone(),
+                request_headers: headers_to_map(&downstream.headers),
+                request_body: downstream.body.clone(),
+                response_headers: headers_to_map(&downstream_headers),
+                response_body: serde_json::from_str(&raw_body).unwrap_or_else(|_| Value::String(raw_body.clone())),
+            })
+        });
+        state.push_audit_records(record, debug_record).await;
+    }
+    let mut resp = Json(anthropic_resp).into_response();
+    set_warnings_header(&mut resp, &warnings);
+    Ok(resp)
+}
+
+/// Tells the client what `translate` dropped or downgraded (`x-gateway-warnings`).
+fn set_warnings_header(resp: &mut axum::response::Response, warnings: &[String]) {
+    if warnings.is_empty() {
+        return;
+    }
+    let mut warnings = warnings.to_vec();
+    if let Some(existing) = resp.headers().get(translation_events::WARNINGS_HEADER).and_then(|v| v.to_str().ok()) {
+        warnings.extend(existing.split(", ").map(str::to_string));
+    }
+    if let Ok(value) = HeaderValue::from_str(&warnings.join(", ")) {
+        resp.headers_mut().insert(translation_events::WARNINGS_HEADER, value);
     }
-    Ok(Json(anthropic_resp).into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/models",
+    tag = "models",
+    responses((status = 200, body = AnthropicModelsResponse), (status = 502, body = AnthropicErrorResponse))
+)]
 pub async fn get_models(
     State(state): State<AppState>,
     headers: HeaderMap,
 ) -> Result<axum::response::Response, AppError> {
+    let request_id = ids::request_id();
+    let start = Instant::now();
+    let mut summary = RequestSummary::new(
+        &request_id,
+        "/v1/models",
+        "GET",
+        state.config.forward_mode(),
+        &headers,
+    );
+    let result = list_models(&state, &request_id, &headers).await;
+    summary.latency_ms = start.elapsed().as_millis() as u64;
+    match &result {
+        Ok(resp) => summary.status = resp.status().as_u16(),
+        Err(err) => {
+            summary.status = err.status.as_u16();
+            summary.error_type = Some(err.error_type().to_string());
+        }
+    }
+    state.record_summary(summary);
+    result
+}
+
+async fn list_models(
+    state: &AppState,
+    request_id: &str,
+    headers: &HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    let call = CallContext {
+        request_id,
+        model: None,
+        incoming: headers,
+    };
     if let Some(override_models) = &state.config.models.models_override {
         let resp = AnthropicModelsResponse {
             data: override_models.clone(),
@@ -470,27 +883,22 @@ pub async fn get_models(
         return Ok(Json(resp).into_response());
     }
 
-    if state.config.forward_mode() == "passthrough" {
+    if matches!(state.config.forward_mode(), "passthrough" | "rewrite") {
         let audit_ctx = build_audit_context(
-            &state,
+            state,
             "models",
             "/v1/models",
             "GET",
-            &headers,
-            Value::Null,
+            headers,
+            &Value::Null,
             None,
             None,
         );
-        if state.config.observability.dump_downstream {
-            info!(
-                request_id = "models",
-                "upstream request headers: {}",
-                headers_for_trace(&headers)
-            );
-        }
-        let forward_headers = build_passthrough_headers(&headers, &state.config.downstream.base_url);
+        let mut forward_headers = passthrough_headers(state, headers);
+        client_identity::apply(&state.config.downstream, &call, &mut forward_headers);
         let request = state
-            .client
+            .clients
+            .client()
             .get(state.config.anthropic_models_url())
             .headers(forward_headers);
         let resp = request
@@ -504,7 +912,7 @@ pub async fn get_models(
             .bytes()
             .await
             .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))?;
-        if let Some((logger, ctx)) = state.audit_logger.clone().zip(audit_ctx) {
+        if let Some(ctx) = audit_ctx {
             let (body_value, parse_error) = parse_body_value(&raw_body);
             let record = ctx.finish(
                 status.as_u16(),
@@ -514,7 +922,7 @@ pub async fn get_models(
                 false,
                 now_ms(),
             );
-            logger.push(record).await;
+            state.push_audit(record).await;
         }
         return Ok(response_from_bytes(
             status,
@@ -523,16 +931,15 @@ pub async fn get_models(
         ));
     }
 
+    let mut forward_headers = HeaderMap::new();
+    client_identity::apply(&state.config.downstream, &call, &mut forward_headers);
+    let bearer = state.downstream_bearer().await?;
     let resp = state
-        .client
+        .clients
+        .client()
         .get(state.config.models_url())
-        .header(
-            AUTHORIZATION,
-            format!(
-                "Bearer {}",
-                state.config.downstream.api_key.as_deref().unwrap_or_default()
-            ),
-        )
+        .headers(forward_headers)
+        .header(AUTHORIZATION, format!("Bearer {}", bearer))
         .send()
         .await
         .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))?;
@@ -552,85 +959,503 @@ pub async fn get_models(
     let anthropic_resp = openai_models_to_anthropic(openai_resp, &state.config.models.display_map)
         .map_err(AppError::from_translate)?;
 
-    if let Some(logger) = state.audit_logger.clone() {
-        let ctx = build_audit_context(
-            &state,
-            "models",
-            "/v1/models",
-            "GET",
-            &headers,
-            Value::Null,
-            None,
-            None,
+    let ctx = build_audit_context(
+        state,
+        "models",
+        "/v1/models",
+        "GET",
+        headers,
+        &Value::Null,
+        None,
+        None,
+    );
+    if let Some(ctx) = ctx {
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let record = ctx.finish(
+            200,
+            headers_to_map(&response_headers),
+            serde_json::to_value(&anthropic_resp).unwrap_or(Value::Null),
+            false,
+            false,
+            now_ms(),
         );
-        if let Some(ctx) = ctx {
-            let mut response_headers = HeaderMap::new();
-            response_headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-            let record = ctx.finish(
-                200,
-                headers_to_map(&response_headers),
-                serde_json::to_value(&anthropic_resp).unwrap_or(Value::Null),
-                false,
-                false,
-                now_ms(),
-            );
-            logger.push(record).await;
-        }
+        state.push_audit(record).await;
     }
     Ok(Json(anthropic_resp).into_response())
 }
 
-pub async fn health() -> impl IntoResponse {
-    axum::Json(serde_json::json!({
-        "status": "ok"
-    }))
+/// Counts prompt tokens locally with the tokenizer configured for the
+/// downstream model; the request is translated first so the count matches
+/// what context limits see.
+#[utoipa::path(
+    post,
+    path = "/v1/messages/count_tokens",
+    tag = "messages",
+    request_body = AnthropicRequest,
+    responses((status = 200, body = CountTokensResponse), (status = 400, body = AnthropicErrorResponse))
+)]
+pub async fn count_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<Value>,
+) -> Result<axum::response::Response, AppError> {
+    let start = Instant::now();
+    let mut summary = RequestSummary::new(
+        &ids::request_id(),
+        "/v1/messages/count_tokens",
+        "POST",
+        state.config.forward_mode(),
+        &headers,
+    );
+    let model = extract_model(&payload).unwrap_or_default();
+    let rendered = state.prompts.render_request(&mut payload).await;
+    if let Some(obj) = payload.as_object_mut() {
+        obj.entry("max_tokens").or_insert(Value::from(1));
+    }
+    let result = rendered
+        .and_then(|_| {
+            parse_request(&payload).map_err(AppError::invalid_request)
+        })
+        .and_then(|mut req| {
+            if let Some(mapped) = state.config.models.model_map.get(&req.model) {
+                req.model = mapped.clone();
+            }
+            anthropic_to_openai(req, &state.config).map_err(AppError::from_translate)
+        });
+    let openai_req = result.inspect_err(|err| {
+        let error_type = err.error_type();
+        state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+        log_error(&state, &summary, &model, start.elapsed().as_millis(), err);
+    })?;
+    let input_tokens = state
+        .tokenizers
+        .for_model(&openai_req.model)
+        .count_request(&openai_req);
+    summary.model = Some(model);
+    summary.latency_ms = start.elapsed().as_millis() as u64;
+    state.record_summary(summary);
+    Ok(Json(CountTokensResponse { input_tokens }).into_response())
+}
+
+/// Lists every version of every prompt template, with the placeholder names
+/// each one expects in `prompt_variables`.
+#[utoipa::path(
+    get,
+    path = "/v1/prompts",
+    tag = "prompts",
+    security(("admin_token" = [])),
+    responses((status = 200, body = serde_json::Value), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn list_prompts(
+    State(state): State<AppState>,
+) -> Result<axum::response::Response, AppError> {
+    let templates = state.prompts.list().await.map_err(AppError::api_error)?;
+    let data: Vec<Value> = templates.iter().map(prompt_json).collect();
+    Ok(Json(serde_json::json!({ "data": data })).into_response())
+}
+
+/// Returns the latest version of a template, or the one given by `?version=`.
+#[utoipa::path(
+    get,
+    path = "/v1/prompts/{id}",
+    tag = "prompts",
+    params(("id" = String, Path), ("version" = Option<u32>, Query)),
+    security(("admin_token" = [])),
+    responses((status = 200, body = PromptTemplate), (status = 404, body = AnthropicErrorResponse))
+)]
+pub async fn get_prompt(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<axum::response::Response, AppError> {
+    let version = params
+        .get("version")
+        .map(|v| {
+            v.parse::<u32>()
+                .map_err(|_| AppError::invalid_request(format!("invalid version: {}", v)))
+        })
+        .transpose()?;
+    let template = state
+        .prompts
+        .get(&id, version)
+        .await
+        .map_err(AppError::api_error)?
+        .ok_or_else(|| AppError::not_found(format!("prompt not found: {}", id)))?;
+    Ok(Json(prompt_json(&template)).into_response())
+}
+
+/// Publishes a new version of a template; the version number is assigned by
+/// the registry. Like the other prompt routes, it sits behind `admin_auth`.
+#[utoipa::path(
+    post,
+    path = "/v1/prompts",
+    tag = "prompts",
+    request_body = PromptTemplate,
+    security(("admin_token" = [])),
+    responses((status = 201, body = PromptTemplate), (status = 401, body = AnthropicErrorResponse))
+)]
+pub async fn publish_prompt(
+    State(state): State<AppState>,
+    Json(template): Json<PromptTemplate>,
+) -> Result<axum::response::Response, AppError> {
+    if template.id.trim().is_empty() || template.id.contains('/') {
+        return Err(AppError::invalid_request(format!("invalid prompt id: {:?}", template.id)));
+    }
+    let template = state
+        .prompts
+        .publish(template)
+        .await
+        .map_err(AppError::invalid_request)?;
+    info!(prompt = %template.id, version = template.version, "prompt template published");
+    Ok((StatusCode::CREATED, Json(prompt_json(&template))).into_response())
+}
+
+fn prompt_json(template: &PromptTemplate) -> Value {
+    let mut value = serde_json::to_value(template).unwrap_or(Value::Null);
+    value["variables"] = serde_json::json!(template_variables(template));
+    value
 }
 
-static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(1);
+/// Serves configured auxiliary endpoints (organization / key info probes)
+/// either from stub bodies or, in passthrough mode, from Anthropic.
+pub async fn get_auxiliary(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    let request_id = ids::request_id();
+    let start = Instant::now();
+    let path = uri.path().to_string();
+    let mut summary = RequestSummary::new(
+        &request_id,
+        &path,
+        "GET",
+        state.config.forward_mode(),
+        &headers,
+    );
+    let result = auxiliary_response(&state, &request_id, &path, uri.query(), &headers).await;
+    summary.latency_ms = start.elapsed().as_millis() as u64;
+    match &result {
+        Ok(resp) => summary.status = resp.status().as_u16(),
+        Err(err) => {
+            summary.status = err.status.as_u16();
+            summary.error_type = Some(err.error_type().to_string());
+        }
+    }
+    state.record_summary(summary);
+    result
+}
 
-fn next_request_id() -> String {
-    let seq = REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed);
-    let ts = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_millis())
-        .unwrap_or(0);
-    format!("req-{}-{}", ts, seq)
+async fn auxiliary_response(
+    state: &AppState,
+    request_id: &str,
+    path: &str,
+    query: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<axum::response::Response, AppError> {
+    let auxiliary = &state.config.auxiliary;
+    if auxiliary.mode == "passthrough" && matches!(state.config.forward_mode(), "passthrough" | "rewrite") {
+        let mut url = state.config.anthropic_url(path);
+        if let Some(query) = query {
+            url.push('?');
+            url.push_str(query);
+        }
+        let mut forward_headers = passthrough_headers(state, headers);
+        let call = CallContext {
+            request_id,
+            model: None,
+            incoming: headers,
+        };
+        client_identity::apply(&state.config.downstream, &call, &mut forward_headers);
+        let resp = state
+            .clients
+            .client()
+            .get(url)
+            .headers(forward_headers)
+            .send()
+            .await
+            .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))?;
+        let status = resp.status();
+        let content_type = resp.headers().get(CONTENT_TYPE).cloned();
+        let raw_body = resp
+            .bytes()
+            .await
+            .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))?;
+        return Ok(response_from_bytes(status, content_type.as_ref(), raw_body));
+    }
+    match auxiliary.responses.get(path) {
+        Some(body) => Ok(Json(body.clone()).into_response()),
+        None => Err(AppError::not_found(format!("unknown endpoint: {}", path))),
+    }
 }
 
-fn log_error(request_id: &str, model: &str, latency_ms: u128, err: &AppError) {
+/// Proxies `/v1/*` routes the gateway has no dedicated handler for, so new
+/// provider endpoints keep working in passthrough mode. Event streams are
+/// relayed as they arrive and audited without a response body.
+pub async fn proxy_unknown(
+    State(state): State<AppState>,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<axum::response::Response, AppError> {
+    let request_id = ids::request_id();
+    let start = Instant::now();
+    let path = uri.path().to_string();
+    let mut summary = RequestSummary::new(
+        &request_id,
+        &path,
+        method.as_str(),
+        state.config.forward_mode(),
+        &headers,
+    );
+    let (payload, _) = parse_body_value(&body);
+    let model = extract_model(&payload).unwrap_or_default();
+    let stream = extract_stream(&payload);
+    if !model.is_empty() {
+        summary.model = Some(model.clone());
+    }
+    summary.stream = stream == Some(true);
+    let stream_label = if summary.stream { "true" } else { "false" };
+
+    let inflight = match state.inflight.clone().try_acquire_owned() {
+        Ok(p) => InflightGuard::new(p, state.inflight_count.clone()),
+        Err(_) => {
+            let err = AppError::rate_limited("too many in-flight requests");
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), &err);
+            return Err(err);
+        }
+    };
+    let audit_ctx = build_audit_context(
+        &state,
+        &request_id,
+        &path,
+        method.as_str(),
+        &headers,
+        &payload,
+        (!model.is_empty()).then(|| model.clone()),
+        stream,
+    );
+
+    let mut url = state.config.anthropic_url(&path);
+    if let Some(query) = uri.query() {
+        url.push('?');
+        url.push_str(query);
+    }
+    summary.downstream_endpoint = Some(url.clone());
+    let client = if summary.stream {
+        state.clients.stream_client()
+    } else {
+        state.clients.client()
+    };
+    let mut forward_headers = passthrough_headers(&state, &headers);
+    let call = CallContext {
+        request_id: &request_id,
+        model: (!model.is_empty()).then_some(model.as_str()),
+        incoming: &headers,
+    };
+    client_identity::apply(&state.config.downstream, &call, &mut forward_headers);
+    let resp = client
+        .request(method, url)
+        .headers(forward_headers)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))
+        .inspect_err(|err| {
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), err);
+        })?;
+    summary.ttfb_ms = Some(start.elapsed().as_millis() as u64);
+    let status = resp.status();
+    let response_headers = resp.headers().clone();
+    summary.status = status.as_u16();
+
+    let is_event_stream = response_headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"));
+    if is_event_stream {
+        summary.latency_ms = start.elapsed().as_millis() as u64;
+        state.record_summary(summary);
+        if let Some(ctx) = audit_ctx {
+            let record = ctx.finish(
+                status.as_u16(),
+                headers_to_map(&response_headers),
+                Value::Null,
+                false,
+                false,
+                now_ms(),
+            );
+            state.push_audit(record).await;
+        }
+        let body = resp.bytes_stream().map(move |chunk| {
+            let _ = &inflight;
+            chunk
+        });
+        let mut builder = axum::response::Response::builder().status(status);
+        if let Some(ct) = response_headers.get(CONTENT_TYPE) {
+            builder = builder.header(CONTENT_TYPE, ct);
+        }
+        return builder
+            .body(Body::from_stream(body))
+            .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)));
+    }
+
+    let raw_body = resp
+        .bytes()
+        .await
+        .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))
+        .inspect_err(|err| {
+            let error_type = err.error_type();
+            state.metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+            log_error(&state, &summary, &model, start.elapsed().as_millis(), err);
+        })?;
+    drop(inflight);
+    state.metrics.latency_ms.record(
+        start.elapsed().as_millis() as f64,
+        &[KeyValue::new("stream", stream_label)],
+    );
+    let (body_value, parse_error) = parse_body_value(&raw_body);
+    if let Some(usage) = body_value.get("usage") {
+        summary.apply_anthropic_usage(usage);
+    }
+    if !status.is_success() {
+        summary.error_type = body_value
+            .get("error")
+            .and_then(|e| e.get("type"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+    summary.latency_ms = start.elapsed().as_millis() as u64;
     info!(
         request_id = %request_id,
+        route = %path,
+        latency_ms = start.elapsed().as_millis(),
+        status = status.as_u16(),
+        "request completed"
+    );
+    state.record_summary(summary);
+    if let Some(ctx) = audit_ctx {
+        let record = ctx.finish(
+            status.as_u16(),
+            headers_to_map(&response_headers),
+            body_value,
+            parse_error,
+            false,
+            now_ms(),
+        );
+        state.push_audit(record).await;
+    }
+    Ok(response_from_bytes(status, response_headers.get(CONTENT_TYPE), raw_body))
+}
+
+/// Liveness plus whether the telemetry exporters are delivering; a failing
+/// exporter does not change `status`, since the gateway still serves
+/// requests. Per-exporter details (including the last error) are only
+/// included for callers presenting the admin token.
+#[utoipa::path(get, path = "/health", tag = "health", responses((status = 200, body = serde_json::Value)))]
+pub async fn health(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let exporters = &state.metrics.exporter_health;
+    let mut body = serde_json::json!({
+        "status": "ok",
+        "exporters_healthy": exporters.healthy(),
+    });
+    if require_admin(&state, &headers).is_ok() {
+        body["exporters"] = exporters.snapshot();
+    }
+    axum::Json(body)
+}
+
+fn log_error(
+    state: &AppState,
+    summary: &RequestSummary,
+    model: &str,
+    latency_ms: u128,
+    err: &AppError,
+) {
+    info!(
+        request_id = %summary.request_id,
         model = %model,
         latency_ms = latency_ms,
         status = err.status.as_u16(),
-        error_type = %err.error_type,
+        error_type = %err.error_type(),
         "request failed"
     );
+    let mut summary = summary.clone();
+    if !model.is_empty() {
+        summary.model = Some(model.to_string());
+    }
+    summary.status = err.status.as_u16();
+    summary.error_type = Some(err.error_type().to_string());
+    summary.latency_ms = latency_ms as u64;
+    summary.downstream_done();
+    state.record_summary(summary);
 }
 
-fn start_trace_span(
-    request_id: &str,
-    model: &str,
-    input_messages: String,
+/// What `start_trace_span` records on the request span; the output and
+/// downstream response are only known up front for non-streaming calls.
+#[derive(Default)]
+struct SpanAttributes<'a> {
+    request_id: &'a str,
+    model: &'a str,
+    stream: bool,
+    input: String,
     downstream_request: String,
-    output_messages: Option<String>,
+    output: Option<String>,
     downstream_response: Option<String>,
+}
+
+fn start_trace_span(
+    trace: &TraceIds,
+    attributes: SpanAttributes,
 ) -> opentelemetry::global::BoxedSpan {
     let tracer = global::tracer("llm-gateway");
-    let mut span = tracer.start("ai.gateway.request");
-    span.set_attribute(KeyValue::new("request.id", request_id.to_string()));
-    span.set_attribute(KeyValue::new("model", model.to_string()));
-    span.set_attribute(KeyValue::new("input", input_messages));
-    if let Some(output) = output_messages {
+    let mut span = tracer.build(
+        SpanBuilder::from_name("ai.gateway.request")
+            .with_trace_id(trace.trace_id)
+            .with_span_id(trace.span_id),
+    );
+    span.set_attribute(KeyValue::new("request.id", attributes.request_id.to_string()));
+    span.set_attribute(KeyValue::new("model", attributes.model.to_string()));
+    span.set_attribute(KeyValue::new("stream", attributes.stream));
+    span.set_attribute(KeyValue::new("input", attributes.input));
+    if let Some(output) = attributes.output {
         span.set_attribute(KeyValue::new("output", output));
     }
-    span.set_attribute(KeyValue::new("downstream.request", downstream_request));
-    if let Some(resp) = downstream_response {
+    span.set_attribute(KeyValue::new("downstream.request", attributes.downstream_request));
+    if let Some(resp) = attributes.downstream_response {
         span.set_attribute(KeyValue::new("downstream.response", resp));
     }
     span
 }
 
+fn set_trace_ids(summary: &mut RequestSummary, trace: &TraceIds) {
+    summary.trace_id = Some(trace.trace_id.to_string());
+    summary.span_id = Some(trace.span_id.to_string());
+}
+
+fn set_routing_attributes(span: &mut opentelemetry::global::BoxedSpan, routing: Option<&RouteDecision>) {
+    let Some(decision) = routing else {
+        return;
+    };
+    span.set_attribute(KeyValue::new("routing.policy", decision.policy));
+    span.set_attribute(KeyValue::new("routing.model", decision.model.clone()));
+    if let Some(rule) = decision.rule.as_ref() {
+        span.set_attribute(KeyValue::new("routing.rule", rule.clone()));
+    }
+    if let Some(tier) = decision.tier.as_ref() {
+        span.set_attribute(KeyValue::new("routing.tier", tier.clone()));
+    }
+    if let Some(cost) = decision.estimated_cost_usd {
+        span.set_attribute(KeyValue::new("routing.estimated_cost_usd", cost));
+    }
+}
+
 fn serialize_for_trace<T: serde::Serialize>(value: &T) -> String {
     match serde_json::to_string(value) {
         Ok(s) => s,
@@ -645,8 +1470,18 @@ fn serialize_json_for_trace<T: serde::Serialize>(value: &T) -> String {
     }
 }
 
-fn truncate_for_trace(value: &str) -> String {
-    value.to_string()
+/// Whether the request gets an audit record: the audit log is on, or the
+/// caller asked for a debug capture.
+fn audits_request(state: &AppState, headers: &HeaderMap) -> bool {
+    debug_capture_requested(state, headers)
+        || (state.config.observability.audit_log.enabled && state.audit_logger.is_some())
+}
+
+fn debug_capture_requested(state: &AppState, headers: &HeaderMap) -> bool {
+    state
+        .debug_capture
+        .as_ref()
+        .is_some_and(|debug| debug.requested(headers))
 }
 
 fn build_audit_context(
@@ -655,15 +1490,16 @@ fn build_audit_context(
     route: &str,
     method: &str,
     headers: &HeaderMap,
-    body: Value,
+    body: &Value,
     model: Option<String>,
     stream: Option<bool>,
 ) -> Option<AuditContext> {
-    if !state.config.observability.audit_log.enabled {
+    if !audits_request(state, headers) {
         return None;
     }
-    if state.audit_logger.is_none() {
-        return None;
+    let mut request_headers = headers_to_map(headers);
+    if let Some(debug) = state.debug_capture.as_ref() {
+        request_headers.remove(debug.header().as_str());
     }
     Some(AuditContext {
         ts_start_ms: now_ms(),
@@ -671,17 +1507,55 @@ fn build_audit_context(
         route: route.to_string(),
         mode: state.config.forward_mode().to_string(),
         method: method.to_string(),
-        request_headers: headers_to_map(headers),
-        request_body: body,
+        request_headers,
+        request_body: body.clone(),
         meta: AuditMeta {
             model,
             stream,
+            experiment: None,
+            routing: None,
+            warnings: Vec::new(),
+            trace_id: None,
+            span_id: None,
             body_truncated: false,
             body_parse_error: false,
+            error: None,
+            debug: debug_capture_requested(state, headers),
         },
     })
 }
 
+/// Plain text of the last user message, for quality scoring.
+fn last_user_text(payload: &Value) -> String {
+    let Some(content) = payload
+        .get("messages")
+        .and_then(Value::as_array)
+        .and_then(|messages| {
+            messages
+                .iter()
+                .rev()
+                .find(|m| m.get("role").and_then(Value::as_str) == Some("user"))
+        })
+        .and_then(|m| m.get("content"))
+    else {
+        return String::new();
+    };
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => anthropic_text(blocks),
+        _ => String::new(),
+    }
+}
+
+fn anthropic_text(blocks: &[Value]) -> String {
+    blocks
+        .iter()
+        .filter(|b| b.get("type").and_then(Value::as_str) == Some("text"))
+        .filter_map(|b| b.get("text").and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
 fn parse_body_value(bytes: &[u8]) -> (Value, bool) {
     match serde_json::from_slice::<Value>(bytes) {
         Ok(value) => (value, false),
@@ -689,26 +1563,40 @@ fn parse_body_value(bytes: &[u8]) -> (Value, bool) {
     }
 }
 
-fn headers_for_trace(headers: &HeaderMap) -> String {
-    let mut out = serde_json::Map::new();
-    for (name, value) in headers.iter() {
-        let value = value.to_str().unwrap_or("[invalid]");
-        out.insert(name.to_string(), serde_json::Value::String(value.to_string()));
+fn extract_model(payload: &Value) -> Result<String, AppError> {
+    required_model(payload.get("model").and_then(|v| v.as_str()))
+}
+
+fn required_model(model: Option<&str>) -> Result<String, AppError> {
+    match model {
+        Some(model) if !model.is_empty() => Ok(model.to_string()),
+        _ => Err(AppError::invalid_request("model is required")),
     }
-    serde_json::Value::Object(out)
-        .to_string()
 }
 
-fn extract_model(payload: &Value) -> Result<String, AppError> {
-    let model = payload
-        .get("model")
-        .and_then(|v| v.as_str())
-        .map(str::to_string)
-        .ok_or_else(|| AppError::invalid_request("model is required"))?;
-    if model.is_empty() {
-        return Err(AppError::invalid_request("model is required"));
-    }
-    Ok(model)
+/// Whether a `/v1/messages` call can be forwarded without parsing its body:
+/// plain passthrough with nothing configured that reads or rewrites it
+/// (prompt templates, experiment variants, routing, scoring, the input token
+/// budget, captured payloads, Langfuse, shadow traffic, audit or
+/// `passthrough_rewrite`).
+fn forwards_head_only(
+    state: &AppState,
+    headers: &HeaderMap,
+    head: &RequestHead,
+    assignment: Option<&Assignment>,
+) -> bool {
+    let config = &state.config;
+    config.forward_mode() == "passthrough"
+        && !head.has_prompt()
+        && assignment.is_none_or(|a| a.model.is_none())
+        && state.router.is_empty()
+        && state.scoring.is_none()
+        && config.input_tokens_per_minute(key_id_from_headers(headers).as_deref()).is_none()
+        && config.capture_policy() == CapturePolicy::None
+        && !config.langfuse_tracing()
+        && state.shadow.is_none()
+        && !audits_request(state, headers)
+        && !config.anthropic.passthrough_rewrite
 }
 
 fn extract_stream(payload: &Value) -> Option<bool> {
@@ -716,8 +1604,7 @@ fn extract_stream(payload: &Value) -> Option<bool> {
 }
 
 fn extract_messages_for_trace(payload: &Value) -> String {
-    let messages = payload.get("messages").cloned().unwrap_or(Value::Null);
-    serialize_json_for_trace(&messages)
+    serialize_json_for_trace(&payload.get("messages").unwrap_or(&Value::Null))
 }
 
 fn response_from_bytes(
@@ -734,11 +1621,25 @@ fn response_from_bytes(
         .unwrap_or_else(|_| axum::response::Response::builder().status(status).body(Body::empty()).unwrap())
 }
 
+/// Client headers for the downstream, minus the `debug_capture` header so
+/// its secret never leaves the gateway.
+fn passthrough_headers(state: &AppState, incoming: &HeaderMap) -> HeaderMap {
+    let mut headers = build_passthrough_headers(incoming, &state.config.downstream.base_url);
+    if let Some(debug) = state.debug_capture.as_ref() {
+        headers.remove(debug.header());
+    }
+    headers
+}
+
 fn build_passthrough_headers(incoming: &HeaderMap, base_url: &str) -> HeaderMap {
     let mut headers = HeaderMap::new();
     for (name, value) in incoming.iter() {
         let key = name.as_str();
-        if key == "host" || key == "content-length" {
+        // Bodies are decoded by the gateway, so encodings are renegotiated per hop.
+        if matches!(
+            key,
+            "host" | "content-length" | "content-encoding" | "accept-encoding"
+        ) {
             continue;
         }
         headers.insert(name.clone(), value.clone());
@@ -757,6 +1658,55 @@ fn build_passthrough_headers(incoming: &HeaderMap, base_url: &str) -> HeaderMap
     headers
 }
 
+async fn prepare_anthropic_downstream(
+    state: &AppState,
+    incoming: &HeaderMap,
+    payload: Value,
+    model: &str,
+    stream: Option<bool>,
+) -> Result<(String, HeaderMap, Value), AppError> {
+    let vertex = match state.config.anthropic.vertex.as_ref() {
+        Some(vertex) if state.config.forward_mode() == "vertex" => vertex,
+        _ => {
+            return Ok((
+                state.config.anthropic_messages_url(),
+                passthrough_headers(state, incoming),
+                payload,
+            ))
+        }
+    };
+    let auth = state
+        .vertex_auth
+        .as_ref()
+        .ok_or_else(|| AppError::api_error("vertex credentials not configured"))?;
+    let downstream_model = state
+        .config
+        .models
+        .model_map
+        .get(model)
+        .map(String::as_str)
+        .unwrap_or(model);
+    let url = state
+        .config
+        .vertex_predict_url(downstream_model, stream == Some(true))
+        .ok_or_else(|| AppError::api_error("vertex endpoint not configured"))?;
+    let token = auth.token().await.map_err(AppError::api_error)?;
+    let headers = build_vertex_headers(incoming, &token);
+    Ok((url, headers, vertex_body(payload, &vertex.anthropic_version)))
+}
+
+fn build_vertex_headers(incoming: &HeaderMap, token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+        headers.insert(AUTHORIZATION, value);
+    }
+    if let Some(beta) = incoming.get("anthropic-beta") {
+        headers.insert("anthropic-beta", beta.clone());
+    }
+    headers
+}
+
 fn openai_output_messages(resp: &OpenAIResponse) -> serde_json::Value {
     let messages: Vec<serde_json::Value> = resp
         .choices
@@ -768,12 +1718,12 @@ fn openai_output_messages(resp: &OpenAIResponse) -> serde_json::Value {
             }
             obj.insert("role".to_string(), serde_json::Value::String(choice.message.role.clone()));
             if let Some(reasoning) = &choice.message.reasoning_content {
-                obj.insert("reasoning_content".to_string(), reasoning.clone());
+                obj.insert("reasoning_content".to_string(), serde_json::json!(reasoning));
             }
             if let Some(content) = &choice.message.content {
                 obj.insert(
                     "content".to_string(),
-                    serde_json::Value::String(content.clone()),
+                    serde_json::Value::String(content.joined_text()),
                 );
             }
             serde_json::Value::Object(obj)
@@ -784,7 +1734,7 @@ fn openai_output_messages(resp: &OpenAIResponse) -> serde_json::Value {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use axum::{body::Bytes, routing::post, Router};
     use axum::response::Response;
@@ -814,12 +1764,38 @@ mod tests {
         Ok(format!("http://{}", addr))
     }
 
-    fn test_state(base_url: String, model_map: HashMap<String, String>) -> AppState {
+    /// An upstream `/v1/messages` that answers `response` and keeps the last
+    /// request it saw; `None` where the sandbox forbids binding a socket.
+    async fn capture_upstream(response: Value) -> Option<(String, Arc<Mutex<Option<Capture>>>)> {
+        let captured: Arc<Mutex<Option<Capture>>> = Arc::new(Mutex::new(None));
+        let captured_handler = captured.clone();
+        let app = Router::new().route(
+            "/v1/messages",
+            post(move |headers: HeaderMap, Json(body): Json<Value>| {
+                let captured = captured_handler.clone();
+                let response = response.clone();
+                async move {
+                    *captured.lock().await = Some(Capture { headers, body });
+                    Json(response)
+                }
+            }),
+        );
+        match spawn_upstream(app).await {
+            Ok(url) => Some((url, captured)),
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => None,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        }
+    }
+
+    pub(crate) fn test_state(base_url: String, model_map: HashMap<String, String>) -> AppState {
         let inflight_count = Arc::new(std::sync::atomic::AtomicU64::new(0));
         let metrics = init_metrics_noop(inflight_count.clone());
         let config = Config {
             server: crate::config::ServerConfig {
                 bind_addr: "127.0.0.1:0".to_string(),
+                cors: None,
+                signing: None,
+                ip_access: None,
             },
             downstream: crate::config::DownstreamConfig {
                 base_url,
@@ -828,10 +1804,34 @@ mod tests {
                 anthropic_beta: None,
                 connect_timeout_ms: 5000,
                 read_timeout_ms: 30000,
+                first_byte_timeout_ms: None,
+                stream_retries: 0,
+                stream_retry_backoff_ms: 200,
                 pool_max_idle_per_host: 8,
+                pool_idle_timeout_secs: 90,
+                pool_recycle_secs: None,
+                stream: Default::default(),
+                provider: "openai".to_string(),
+                provider_endpoints: HashMap::new(),
+                endpoints: Vec::new(),
+                endpoint_health: Default::default(),
+                stream_parsing: "strict".to_string(),
+                max_malformed_chunks: 3,
+                user_agent: None,
+                headers: HashMap::new(),
+                oauth: None,
+                api_keys: Vec::new(),
+                key_rotation: "round_robin".to_string(),
+                warmup: None,
+                max_tokens_field: "max_completion_tokens".to_string(),
             },
             anthropic: crate::config::AnthropicConfig {
                 forward_mode: "passthrough".to_string(),
+                vertex: None,
+                passthrough_unknown: false,
+                sse_normalize: false,
+                passthrough_rewrite: false,
+                rewrite: Default::default(),
             },
             models: crate::config::ModelsConfig {
                 model_map,
@@ -842,36 +1842,107 @@ mod tests {
                 output_strict: true,
                 allow_images: true,
                 document_policy: "reject".to_string(),
+                prefill_mode: "none".to_string(),
+                tool_error: "prefix".to_string(),
+                tool_error_prefix: "[tool error] ".to_string(),
+                builtin_tools: "reject".to_string(),
+                empty_content: "error".to_string(),
+                reasoning_only_text: false,
+                sanitize_messages: false,
+                normalize_tool_ids: false,
+                context: Default::default(),
+                system_prompt: Default::default(),
                 models_override: None,
+                vllm_params: HashMap::new(),
+                overrides: HashMap::new(),
+                forward_fields: Vec::new(),
+                system_role: HashMap::new(),
+                json_schema_support: HashMap::new(),
+                capabilities: HashMap::new(),
+                builtin_capabilities: false,
+                pricing: HashMap::new(),
+                provider_map: HashMap::new(),
             },
-            limits: crate::config::LimitsConfig { max_inflight: 8 },
+            limits: crate::config::LimitsConfig {
+                max_inflight: 8,
+                max_duration_ms: None,
+                max_duration_models: HashMap::new(),
+                max_duration_keys: HashMap::new(),
+                output_tokens_per_sec: None,
+                output_tokens_per_sec_keys: Default::default(),
+                input_tokens_per_minute: None,
+                input_tokens_per_minute_keys: Default::default(),
+            },
+            admin: crate::config::AdminConfig::default(),
+            usage: crate::config::UsageConfig::default(),
+            postgres: None,
+            compaction: Default::default(),
+            auxiliary: Default::default(),
+            compression: Default::default(),
+            tokenizers: Default::default(),
+            shadow: None,
+            experiments: Vec::new(),
+            routing: Default::default(),
+            canary: None,
+            scoring: None,
+            prompts: Default::default(),
+            alerts: None,
+            billing: None,
+            streaming: Default::default(),
             observability: crate::config::ObservabilityConfig {
                 service_name: "llm-gateway".to_string(),
-                dump_downstream: false,
+                capture: "full".to_string(),
                 audit_log: crate::config::AuditLogConfig::default(),
+                debug_capture: crate::config::DebugCaptureConfig::default(),
+                transcripts: crate::config::TranscriptsConfig::default(),
+                access_log: crate::config::AccessLogConfig::default(),
+                tracing: crate::config::TracingConfig::default(),
                 logging: crate::config::LoggingConfig::default(),
                 otlp_grpc: crate::config::OtlpGrpcConfig::default(),
                 otlp_http: crate::config::OtlpHttpConfig::default(),
+                langfuse: crate::config::LangfuseConfig::default(),
+                metrics: crate::config::MetricsConfig::default(),
                 exporters: crate::config::ExportersConfig::default(),
             },
         };
         let tracer = init_tracer_noop(config.observability.service_name.clone());
         AppState {
-            client: reqwest::Client::builder().build().unwrap(),
-            stream_client: reqwest::Client::builder().build().unwrap(),
+            clients: crate::client_pool::DownstreamClients::from_clients(
+                &config,
+                reqwest::Client::builder().build().unwrap(),
+                reqwest::Client::builder().build().unwrap(),
+            ),
             config: config.clone(),
             inflight: std::sync::Arc::new(tokio::sync::Semaphore::new(config.limits.max_inflight)),
             inflight_count,
+            inflight_requests: Default::default(),
             metrics,
             audit_logger: None,
+            debug_capture: None,
+            access_logger: None,
+            usage: crate::usage::UsageStore::new(16),
+            tap: crate::tap::TapRegistry::default(),
+            transcripts: None,
+            throttle: Default::default(),
+            input_limiter: Default::default(),
+            compaction: None,
+            tokenizers: Default::default(),
+            shadow: None,
+            scoring: None,
+            billing: None,
+            prompts: Default::default(),
+            providers: Default::default(),
+            endpoints: Default::default(),
+            downstream_keys: Default::default(),
+            router: Default::default(),
+            vertex_auth: None,
+            oauth: None,
             _tracer_provider: tracer,
         }
     }
 
     #[tokio::test]
     async fn passthrough_non_stream_forwards_body_and_headers() {
-        let captured: Arc<Mutex<Option<Capture>>> = Arc::new(Mutex::new(None));
-        let captured_handler = captured.clone();
         let response_json = serde_json::json!({
             "id": "msg_01",
             "type": "message",
@@ -882,22 +1953,8 @@ mod tests {
             "stop_sequence": null,
             "usage": {"input_tokens": 1, "output_tokens": 1, "cache_creation_input_tokens": 0, "cache_read_input_tokens": 0}
         });
-        let response_clone = response_json.clone();
-        let app = Router::new().route(
-            "/v1/messages",
-            post(move |headers: HeaderMap, Json(body): Json<Value>| {
-                let captured = captured_handler.clone();
-                let response = response_clone.clone();
-                async move {
-                    *captured.lock().await = Some(Capture { headers, body });
-                    Json(response)
-                }
-            }),
-        );
-        let base_url = match spawn_upstream(app).await {
-            Ok(url) => url,
-            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
-            Err(err) => panic!("spawn upstream failed: {}", err),
+        let Some((base_url, captured)) = capture_upstream(response_json.clone()).await else {
+            return;
         };
 
         let state = test_state(
@@ -912,7 +1969,7 @@ mod tests {
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", HeaderValue::from_static("sk-upstream"));
         headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
-        let resp = post_messages(State(state), headers, Json(payload))
+        let resp = post_messages(State(state), headers, RawJson::from(payload))
             .await
             .expect("response ok");
 
@@ -941,67 +1998,494 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn passthrough_error_status_transparent() {
-        let error_json = serde_json::json!({
-            "type": "error",
-            "error": {"type": "authentication_error", "message": "bad key"}
-        });
-        let error_clone = error_json.clone();
-        let app = Router::new().route(
-            "/v1/messages",
-            post(move || {
-                let err = error_clone.clone();
-                async move { (StatusCode::UNAUTHORIZED, Json(err)) }
-            }),
-        );
-        let base_url = match spawn_upstream(app).await {
-            Ok(url) => url,
-            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
-            Err(err) => panic!("spawn upstream failed: {}", err),
+    async fn rewrite_mode_maps_model_and_injects_system() {
+        let Some((base_url, captured)) =
+            capture_upstream(serde_json::json!({"type": "message", "content": []})).await
+        else {
+            return;
         };
 
-        let state = test_state(base_url, HashMap::new());
+        let mut state = test_state(
+            base_url,
+            HashMap::from([("claude-opus".to_string(), "mapped-model".to_string())]),
+        );
+        state.config.anthropic.forward_mode = "rewrite".to_string();
+        state.config.anthropic.rewrite.max_tokens = Some(4);
+        state.config.anthropic.rewrite.system_suffix = Some("Answer in English.".to_string());
         let payload = serde_json::json!({
             "model": "claude-opus",
             "max_tokens": 8,
+            "top_k": 5,
             "messages": [{"role":"user","content":"hi"}]
         });
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", HeaderValue::from_static("sk-upstream"));
-        headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
-        let resp = post_messages(State(state), headers, Json(payload))
+        let resp = post_messages(State(state), headers, RawJson::from(payload))
             .await
             .expect("response ok");
+        assert_eq!(resp.status(), StatusCode::OK);
 
-        let status = resp.status();
-        let body = resp.into_body().collect().await.unwrap().to_bytes();
-        let parsed: Value = serde_json::from_slice(&body).unwrap();
-        assert_eq!(status, StatusCode::UNAUTHORIZED);
-        assert_eq!(parsed, error_json);
+        let capture = captured.lock().await.take().expect("capture");
+        assert_eq!(capture.headers.get("x-api-key").unwrap(), "sk-upstream");
+        assert_eq!(capture.body["model"], "mapped-model");
+        assert_eq!(capture.body["max_tokens"], 4);
+        assert_eq!(capture.body["top_k"], 5);
+        assert_eq!(capture.body["system"], "Answer in English.");
     }
 
     #[tokio::test]
-    async fn passthrough_stream_forwards_sse() {
-        let app = Router::new().route(
-            "/v1/messages",
-            post(|| async move {
-                let chunks = vec![
-                    Ok::<Bytes, Infallible>(Bytes::from("event: message_start\n\n")),
-                    Ok::<Bytes, Infallible>(Bytes::from("data: test\n\n")),
-                ];
-                let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, Infallible>>(4);
-                tokio::spawn(async move {
-                    for chunk in chunks {
-                        let _ = tx.send(chunk).await;
-                    }
-                });
-                let body = axum::body::Body::from_stream(ReceiverStream::new(rx));
-                Response::builder()
-                    .status(StatusCode::OK)
-                    .header(CONTENT_TYPE, "text/event-stream")
-                    .body(body)
-                    .unwrap()
-            }),
+    async fn experiment_variant_routes_model_and_stamps_header() {
+        let Some((base_url, captured)) =
+            capture_upstream(serde_json::json!({"type": "message", "content": []})).await
+        else {
+            return;
+        };
+
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.experiments = vec![crate::config::ExperimentConfig {
+            name: "opus-next".to_string(),
+            model: "claude-opus".to_string(),
+            variants: vec![crate::config::ExperimentVariant {
+                name: "candidate".to_string(),
+                model: "claude-opus-next".to_string(),
+                percent: 100.0,
+            }],
+        }];
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "metadata": {"user_id": "u-1"},
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        assert_eq!(
+            resp.headers().get(crate::experiments::EXPERIMENT_HEADER).unwrap(),
+            "opus-next/candidate"
+        );
+        let capture = captured.lock().await.take().expect("capture");
+        assert_eq!(capture.body["model"], "claude-opus-next");
+    }
+
+    #[tokio::test]
+    async fn prompt_template_is_rendered_before_forwarding() {
+        let Some((base_url, captured)) =
+            capture_upstream(serde_json::json!({"type": "message", "content": []})).await
+        else {
+            return;
+        };
+
+        let mut state = test_state(base_url, HashMap::new());
+        state.prompts = crate::prompts::PromptRegistry::from_templates(vec![PromptTemplate {
+            id: "summarize".to_string(),
+            version: 1,
+            description: None,
+            system: Some("Summarize for {{audience}}.".to_string()),
+            messages: Vec::new(),
+        }]);
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "prompt_id": "summarize",
+            "prompt_variables": {"audience": "executives"},
+            "messages": [{"role":"user","content":"report"}]
+        });
+        post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        let capture = captured.lock().await.take().expect("capture");
+        assert_eq!(capture.body["system"], "Summarize for executives.");
+        assert!(capture.body.get("prompt_id").is_none());
+        assert!(capture.body.get("prompt_variables").is_none());
+    }
+
+    #[tokio::test]
+    async fn prompt_routes_require_the_admin_token() {
+        use tower::ServiceExt;
+
+        let mut state = test_state("http://127.0.0.1:9".to_string(), HashMap::new());
+        state.config.admin.token = Some("admin-secret".to_string());
+        state.prompts = crate::prompts::PromptRegistry::from_templates(vec![PromptTemplate {
+            id: "summarize".to_string(),
+            version: 1,
+            description: None,
+            system: Some("internal".to_string()),
+            messages: Vec::new(),
+        }]);
+        let app = crate::server::build_router(&state.config, state.clone()).unwrap();
+        let get = |uri: &str, token: Option<&str>| {
+            let mut request = axum::http::Request::get(uri);
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            request.body(axum::body::Body::empty()).unwrap()
+        };
+
+        for uri in ["/v1/prompts", "/v1/prompts/summarize"] {
+            let response = app.clone().oneshot(get(uri, None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            let response = app.clone().oneshot(get(uri, Some("wrong"))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            let response = app.clone().oneshot(get(uri, Some("admin-secret"))).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn health_hides_exporter_details_without_the_admin_token() {
+        use tower::ServiceExt;
+
+        let mut state = test_state("http://127.0.0.1:9".to_string(), HashMap::new());
+        state.config.admin.token = Some("admin-secret".to_string());
+        state.metrics.exporter_health.metrics.set_exporter("otlp_grpc");
+        state
+            .metrics
+            .exporter_health
+            .metrics
+            .record(Err("dial tcp 10.0.0.5:4317: connection refused".to_string()));
+        let app = crate::server::build_router(&state.config, state.clone()).unwrap();
+        let get = |token: Option<&str>| {
+            let mut request = axum::http::Request::get("/health");
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            request.body(axum::body::Body::empty()).unwrap()
+        };
+
+        let response = app.clone().oneshot(get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"status": "ok", "exporters_healthy": false}));
+
+        let response = app.oneshot(get(Some("admin-secret"))).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["exporters_healthy"], false);
+        assert_eq!(body["exporters"]["metrics"]["last_error"], "dial tcp 10.0.0.5:4317: connection refused");
+    }
+
+    #[tokio::test]
+    async fn admin_flush_recycles_the_downstream_pools() {
+        use tower::ServiceExt;
+
+        let mut state = test_state("http://127.0.0.1:9".to_string(), HashMap::new());
+        state.config.admin.token = Some("admin-secret".to_string());
+        let app = crate::server::build_router(&state.config, state.clone()).unwrap();
+        let flush = |token: &str| {
+            axum::http::Request::post("/admin/downstream-pool/flush")
+                .header("authorization", format!("Bearer {}", token))
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(flush("wrong")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(state.clients.generation(), 0);
+
+        let response = app.oneshot(flush("admin-secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!({"flushed": true, "generation": 1}));
+        assert_eq!(state.clients.generation(), 1);
+    }
+
+    #[tokio::test]
+    async fn plain_passthrough_is_forwarded_on_the_request_head() {
+        use axum::extract::FromRequest;
+
+        let received: Arc<Mutex<Option<Bytes>>> = Arc::new(Mutex::new(None));
+        let received_handler = received.clone();
+        let app = Router::new().route(
+            "/v1/messages",
+            post(move |body: Bytes| {
+                let received = received_handler.clone();
+                async move {
+                    *received.lock().await = Some(body);
+                    Json(serde_json::json!({"type": "message", "content": []}))
+                }
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.observability.capture = "none".to_string();
+        let raw_body = |body: &'static str| async move {
+            let request = axum::http::Request::post("/v1/messages")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            RawJson::from_request(request, &()).await.unwrap()
+        };
+
+        let body = "{\"max_tokens\": 8,\n \"messages\": [{\"role\":\"user\",\"content\":\"hi\"}], \"model\": \"claude\"}";
+        let request = raw_body(body).await;
+        assert!(forwards_head_only(&state, &HeaderMap::new(), &request.head, None));
+        let resp = post_messages(State(state.clone()), HeaderMap::new(), request).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(received.lock().await.take().unwrap(), body.as_bytes());
+
+        let resp = post_messages(State(state.clone()), HeaderMap::new(), raw_body(r#"{"model": ""}"#).await)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Captured payloads need the parsed body.
+        state.config.observability.capture = "full".to_string();
+        assert!(!forwards_head_only(&state, &HeaderMap::new(), &raw_body(body).await.head, None));
+    }
+
+    #[tokio::test]
+    async fn passthrough_error_status_transparent() {
+        let error_json = serde_json::json!({
+            "type": "error",
+            "error": {"type": "authentication_error", "message": "bad key"}
+        });
+        let error_clone = error_json.clone();
+        let app = Router::new().route(
+            "/v1/messages",
+            post(move || {
+                let err = error_clone.clone();
+                async move { (StatusCode::UNAUTHORIZED, Json(err)) }
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let state = test_state(base_url, HashMap::new());
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("sk-upstream"));
+        headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+        let resp = post_messages(State(state), headers, RawJson::from(payload))
+            .await
+            .expect("response ok");
+
+        let status = resp.status();
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let parsed: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(parsed, error_json);
+    }
+
+    #[tokio::test]
+    async fn auxiliary_stub_and_passthrough() {
+        let app = Router::new().route(
+            "/api/organizations",
+            axum::routing::get(|| async { Json(serde_json::json!([{"uuid": "org-1"}])) }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(format!("{}/v1", base_url), HashMap::new());
+        state.config.auxiliary.mode = "stub".to_string();
+        let uri: axum::http::Uri = "/api/organizations".parse().unwrap();
+        let resp = get_auxiliary(State(state.clone()), OriginalUri(uri.clone()), HeaderMap::new())
+            .await
+            .expect("stub ok");
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), serde_json::json!([]));
+
+        let unknown: axum::http::Uri = "/api/unknown".parse().unwrap();
+        let err = get_auxiliary(State(state.clone()), OriginalUri(unknown), HeaderMap::new())
+            .await
+            .expect_err("not configured");
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        state.config.auxiliary.mode = "passthrough".to_string();
+        let resp = get_auxiliary(State(state), OriginalUri(uri), HeaderMap::new())
+            .await
+            .expect("passthrough ok");
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            serde_json::json!([{"uuid": "org-1"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn proxy_unknown_forwards_method_path_and_query() {
+        let app = Router::new().route(
+            "/v1/messages/count_tokens",
+            post(|uri: axum::http::Uri, headers: HeaderMap, body: Bytes| async move {
+                let payload: Value = serde_json::from_slice(&body).unwrap();
+                Json(serde_json::json!({
+                    "query": uri.query(),
+                    "api_key": headers.get("x-api-key").and_then(|v| v.to_str().ok()),
+                    "model": payload["model"],
+                    "input_tokens": 12
+                }))
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let state = test_state(format!("{}/v1", base_url), HashMap::new());
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("sk-test"));
+        let uri: axum::http::Uri = "/v1/messages/count_tokens?beta=true".parse().unwrap();
+        let resp = proxy_unknown(
+            State(state),
+            Method::POST,
+            OriginalUri(uri),
+            headers,
+            Bytes::from_static(br#"{"model":"claude-test","messages":[]}"#),
+        )
+        .await
+        .expect("proxied");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            serde_json::json!({
+                "query": "beta=true",
+                "api_key": "sk-test",
+                "model": "claude-test",
+                "input_tokens": 12
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn count_tokens_without_max_tokens() {
+        let state = test_state("http://127.0.0.1:9".to_string(), HashMap::new());
+        let payload = serde_json::json!({
+            "model": "claude-test",
+            "system": "be brief",
+            "messages": [{"role": "user", "content": "hello world"}]
+        });
+        let resp = count_tokens(State(state), HeaderMap::new(), Json(payload))
+            .await
+            .expect("counted");
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["input_tokens"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn passthrough_headers_drop_encodings() {
+        let mut incoming = HeaderMap::new();
+        incoming.insert("accept-encoding", HeaderValue::from_static("zstd"));
+        incoming.insert("content-encoding", HeaderValue::from_static("gzip"));
+        incoming.insert("x-api-key", HeaderValue::from_static("sk-test"));
+        let headers = build_passthrough_headers(&incoming, "https://api.anthropic.com");
+        assert!(headers.get("accept-encoding").is_none());
+        assert!(headers.get("content-encoding").is_none());
+        assert_eq!(headers.get("x-api-key").unwrap(), "sk-test");
+        assert_eq!(headers.get("host").unwrap(), "api.anthropic.com");
+    }
+
+    #[tokio::test]
+    async fn vertex_rewrites_url_body_and_auth() {
+        let captured: Arc<Mutex<Option<(String, Capture)>>> = Arc::new(Mutex::new(None));
+        let captured_handler = captured.clone();
+        let app = Router::new().route(
+            "/v1/projects/{project}/locations/{region}/publishers/anthropic/models/{method}",
+            post(
+                move |axum::extract::Path((_, _, method)): axum::extract::Path<(String, String, String)>,
+                      headers: HeaderMap,
+                      Json(body): Json<Value>| {
+                    let captured = captured_handler.clone();
+                    async move {
+                        *captured.lock().await = Some((method, Capture { headers, body }));
+                        Json(serde_json::json!({"type": "message", "content": []}))
+                    }
+                },
+            ),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(
+            "https://unused.example".to_string(),
+            HashMap::from([(
+                "claude-sonnet-4-5".to_string(),
+                "claude-sonnet-4-5@20250929".to_string(),
+            )]),
+        );
+        let vertex = crate::config::VertexConfig {
+            project_id: "proj".to_string(),
+            region: "us-east5".to_string(),
+            credentials_path: None,
+            access_token: Some("ya29.test".to_string()),
+            anthropic_version: "vertex-2023-10-16".to_string(),
+            endpoint: Some(base_url),
+        };
+        state.vertex_auth = Some(
+            crate::vertex::VertexAuth::from_config(&vertex, reqwest::Client::new()).unwrap(),
+        );
+        state.config.anthropic.forward_mode = "vertex".to_string();
+        state.config.anthropic.vertex = Some(vertex);
+
+        let payload = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 8,
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("sk-client"));
+        let resp = post_messages(State(state), headers, RawJson::from(payload))
+            .await
+            .expect("response ok");
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let (method, capture) = captured.lock().await.take().expect("capture");
+        assert_eq!(method, "claude-sonnet-4-5@20250929:rawPredict");
+        assert_eq!(
+            capture.headers.get(AUTHORIZATION).unwrap(),
+            "Bearer ya29.test"
+        );
+        assert!(capture.headers.get("x-api-key").is_none());
+        assert!(capture.body.get("model").is_none());
+        assert_eq!(
+            capture.body.get("anthropic_version").and_then(|v| v.as_str()),
+            Some("vertex-2023-10-16")
+        );
+    }
+
+    #[tokio::test]
+    async fn passthrough_stream_forwards_sse() {
+        let app = Router::new().route(
+            "/v1/messages",
+            post(|| async move {
+                let chunks = vec![
+                    Ok::<Bytes, Infallible>(Bytes::from("event: message_start\n\n")),
+                    Ok::<Bytes, Infallible>(Bytes::from("data: test\n\n")),
+                ];
+                let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, Infallible>>(4);
+                tokio::spawn(async move {
+                    for chunk in chunks {
+                        let _ = tx.send(chunk).await;
+                    }
+                });
+                let body = axum::body::Body::from_stream(ReceiverStream::new(rx));
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .body(body)
+                    .unwrap()
+            }),
         );
         let base_url = match spawn_upstream(app).await {
             Ok(url) => url,
@@ -1019,7 +2503,7 @@ mod tests {
         let mut headers = HeaderMap::new();
         headers.insert("x-api-key", HeaderValue::from_static("sk-upstream"));
         headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
-        let resp = post_messages(State(state), headers, Json(payload))
+        let resp = post_messages(State(state), headers, RawJson::from(payload))
             .await
             .expect("response ok");
 
@@ -1028,4 +2512,414 @@ mod tests {
         let text = String::from_utf8_lossy(&body);
         assert_eq!(text, "event: message_start\n\ndata: test\n\n");
     }
+
+    #[tokio::test]
+    async fn lenient_stream_parsing_skips_vendor_frames() {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|| async move {
+                let body = concat!(
+                    "data: {\"id\":\"c1\",\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"}}]}\n\n",
+                    "data: {\"object\":\"vendor.stats\",\"queue_ms\":12}\n\n",
+                    "data: {\"id\":\"c1\",\"model\":\"gpt-4o\",\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+                    "data: [DONE]\n\n",
+                );
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .body(axum::body::Body::from(body))
+                    .unwrap()
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        for (mode, expect_error) in [("strict", true), ("lenient", false)] {
+            let mut state = test_state(
+                base_url.clone(),
+                HashMap::from([("claude-opus".to_string(), "gpt-4o".to_string())]),
+            );
+            state.config.anthropic.forward_mode = "translate".to_string();
+            state.config.downstream.stream_parsing = mode.to_string();
+            let payload = serde_json::json!({
+                "model": "claude-opus",
+                "max_tokens": 8,
+                "stream": true,
+                "messages": [{"role":"user","content":"hi"}]
+            });
+            let resp = post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+                .await
+                .expect("response ok");
+            let body = resp.into_body().collect().await.unwrap().to_bytes();
+            let text = String::from_utf8_lossy(&body);
+            assert_eq!(text.contains("invalid stream chunk"), expect_error, "{}", mode);
+            assert_eq!(text.contains("event: message_stop"), !expect_error, "{}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn lossy_translation_is_listed_in_warnings_header() {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|| async move {
+                Json(serde_json::json!({
+                    "id": "c1",
+                    "model": "gpt-4o",
+                    "choices": [
+                        {"index": 0, "message": {"role": "assistant", "content": "a"}, "finish_reason": "stop"},
+                        {"index": 1, "message": {"role": "assistant", "content": "b"}, "finish_reason": "stop"}
+                    ]
+                }))
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(
+            base_url,
+            HashMap::from([("claude-opus".to_string(), "gpt-4o".to_string())]),
+        );
+        state.config.anthropic.forward_mode = "translate".to_string();
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "top_k": 5,
+            "metadata": {"user_id": "u-1"},
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(translation_events::WARNINGS_HEADER).unwrap(),
+            "top_k_dropped, metadata_dropped, choices_dropped"
+        );
+    }
+
+    /// OpenAI chunks as usual, but panics on one whose data says "boom".
+    struct PanickingProvider;
+
+    impl crate::provider::Provider for PanickingProvider {
+        fn build_request(
+            &self,
+            req: &crate::models::OpenAIRequest,
+            config: &Config,
+        ) -> Result<crate::provider::DownstreamRequest, AppError> {
+            crate::provider::OpenAIProvider.build_request(req, config)
+        }
+
+        fn parse_response(&self, body: &str) -> Result<crate::models::OpenAIResponse, AppError> {
+            crate::provider::OpenAIProvider.parse_response(body)
+        }
+
+        fn parse_stream_chunk(
+            &self,
+            event: &crate::sse::SseEvent,
+        ) -> Result<crate::provider::StreamEvent, crate::provider::StreamError> {
+            assert!(!event.data.contains("boom"), "provider exploded");
+            crate::provider::OpenAIProvider.parse_stream_chunk(event)
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_task_panic_mid_stream_keeps_streamed_usage_and_records_an_error() {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|| async {
+                let first = serde_json::json!({
+                    "id": "c1",
+                    "model": "local",
+                    "choices": [{"index": 0, "delta": {"role": "assistant", "content": "po"}}],
+                    "usage": {"prompt_tokens": 7, "completion_tokens": 1, "total_tokens": 8}
+                });
+                let second = serde_json::json!({
+                    "id": "c1",
+                    "model": "local",
+                    "choices": [{"index": 0, "delta": {"content": "boom"}}]
+                });
+                let body = format!("data: {}\n\ndata: {}\n\ndata: [DONE]\n\n", first, second);
+                ([(CONTENT_TYPE, "text/event-stream")], body)
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let dir = std::env::temp_dir().join(format!("llm-gateway-panic-audit-{}", std::process::id()));
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.anthropic.forward_mode = "translate".to_string();
+        state.config.observability.audit_log.enabled = true;
+        state.audit_logger = Some(
+            crate::audit_log::AuditLogger::new(
+                dir.join("audit.jsonl").to_string_lossy().into_owned(),
+                1 << 20,
+                Default::default(),
+                crate::capture::CapturePolicy::Full,
+            )
+            .unwrap(),
+        );
+        state.providers.register("openai", Arc::new(PanickingProvider));
+        let payload = serde_json::json!({
+            "model": "local",
+            "max_tokens": 8,
+            "stream": true,
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state.clone()), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("\"text\":\"po\""), "{}", body);
+        let last = body.trim_end().rsplit("\n\n").next().unwrap();
+        assert!(last.starts_with("event: error"), "{}", body);
+        assert!(last.contains("internal error while streaming the response"), "{}", body);
+        assert!(!body.contains("message_stop"), "{}", body);
+
+        let summaries = state.usage.recent(0);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].input_tokens, Some(7));
+        assert_eq!(summaries[0].output_tokens, Some(1));
+        assert_eq!(summaries[0].error_type.as_deref(), Some("api_error"));
+
+        let record = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                let text: String = std::fs::read_dir(&dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+                    .collect();
+                if let Some(line) = text.lines().next() {
+                    return serde_json::from_str::<Value>(line).unwrap();
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("audit record written");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(record["meta"]["body_truncated"], false);
+        assert_eq!(record["meta"]["error"], "stream task panicked: provider exploded");
+    }
+
+    #[tokio::test]
+    async fn rejected_json_schema_is_retried_as_json_object() {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|Json(body): Json<Value>| async move {
+                if body["response_format"]["type"] == "json_schema" {
+                    let error = serde_json::json!({"error": {"message": "response_format json_schema unsupported"}});
+                    return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+                }
+                Json(serde_json::json!({
+                    "id": "c1",
+                    "model": "local",
+                    "choices": [{"message": {"role": "assistant", "content": "```json\n{\"ok\": true}\n```"}, "finish_reason": "stop"}]
+                }))
+                .into_response()
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.anthropic.forward_mode = "translate".to_string();
+        let payload = serde_json::json!({
+            "model": "local",
+            "max_tokens": 8,
+            "output_format": {"type": "json_schema", "schema": {"type": "object"}},
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state.clone()), HeaderMap::new(), RawJson::from(payload.clone()))
+            .await
+            .expect("response ok");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(translation_events::WARNINGS_HEADER).unwrap(),
+            "output_format_downgraded"
+        );
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["content"][0]["text"], "{\"ok\": true}");
+
+        state.config.models.json_schema_support.insert("local".to_string(), "native".to_string());
+        let resp = post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        assert!(!resp.status().is_success());
+        assert!(resp.headers().get(translation_events::WARNINGS_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn non_stream_request_times_out_at_max_duration() {
+        let app = Router::new().route(
+            "/v1/messages",
+            post(|| async move {
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                Json(serde_json::json!({"type": "message", "content": []}))
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.limits.max_duration_ms = Some(60_000);
+        state
+            .config
+            .limits
+            .max_duration_models
+            .insert("claude-opus".to_string(), 100);
+        state
+            .config
+            .limits
+            .max_duration_keys
+            .insert("key-vip".to_string(), 1_000);
+        assert_eq!(
+            state.config.max_request_duration("claude-opus", Some("key-vip")),
+            Some(std::time::Duration::from_millis(1_000))
+        );
+        assert_eq!(
+            state.config.max_request_duration("claude-haiku", None),
+            Some(std::time::Duration::from_millis(60_000))
+        );
+
+        let registry = state.inflight_requests.clone();
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state.clone()), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response");
+        assert_eq!(resp.status(), StatusCode::GATEWAY_TIMEOUT);
+        let trace_id = resp.headers().get(TRACE_HEADER).expect("trace header").to_str().unwrap().to_string();
+        assert_eq!(trace_id.len(), 32);
+        let summary = state.usage.recent(0).pop().expect("summary");
+        assert_eq!(summary.error_type.as_deref(), Some("timeout_error"));
+        assert_eq!(summary.trace_id.as_deref(), Some(trace_id.as_str()));
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn input_token_budget_rejects_requests_once_spent() {
+        let app = Router::new().route(
+            "/v1/messages",
+            post(|| async move {
+                Json(serde_json::json!({
+                    "id": "msg_01",
+                    "type": "message",
+                    "role": "assistant",
+                    "model": "claude-opus",
+                    "content": [{"type": "text", "text": "ok"}],
+                    "stop_reason": "end_turn",
+                    "usage": {"input_tokens": 1, "output_tokens": 1}
+                }))
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let mut state = test_state(base_url, HashMap::new());
+        state.config.limits.input_tokens_per_minute = Some(100);
+        let request = |text: String, key: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-api-key", HeaderValue::from_static(key));
+            let payload = serde_json::json!({
+                "model": "claude-opus",
+                "max_tokens": 8,
+                "messages": [{"role": "user", "content": text}]
+            });
+            post_messages(State(state.clone()), headers, RawJson::from(payload))
+        };
+
+        // About 60 of the 100 tokens, then 60 more than what is left.
+        let resp = request("x".repeat(240), "sk-a").await.expect("response");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = request("x".repeat(240), "sk-a").await.expect("response");
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[crate::error::SHOULD_RETRY_HEADER], "true");
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+        assert!(body["error"]["message"].as_str().unwrap().contains("input token budget"), "{}", body);
+
+        let resp = request("x".repeat(240), "sk-b").await.expect("response");
+        assert_eq!(resp.status(), StatusCode::OK);
+        let summary = state.usage.recent(0).into_iter().find(|s| s.error_type.is_some()).expect("rejection");
+        assert_eq!(summary.error_type.as_deref(), Some("rate_limit_error"));
+    }
+
+    #[tokio::test]
+    async fn cancelled_stream_ends_with_error_event() {
+        let app = Router::new().route(
+            "/v1/messages",
+            post(|| async move {
+                let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, Infallible>>(4);
+                tokio::spawn(async move {
+                    let _ = tx.send(Ok(Bytes::from("event: message_start\n\n"))).await;
+                    tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+                });
+                let body = axum::body::Body::from_stream(ReceiverStream::new(rx));
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, "text/event-stream")
+                    .body(body)
+                    .unwrap()
+            }),
+        );
+        let base_url = match spawn_upstream(app).await {
+            Ok(url) => url,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("spawn upstream failed: {}", err),
+        };
+
+        let state = test_state(base_url, HashMap::new());
+        let registry = state.inflight_requests.clone();
+        let payload = serde_json::json!({
+            "model": "claude-opus",
+            "max_tokens": 8,
+            "stream": true,
+            "messages": [{"role":"user","content":"hi"}]
+        });
+        let resp = post_messages(State(state), HeaderMap::new(), RawJson::from(payload))
+            .await
+            .expect("response ok");
+        let mut body = resp.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(first, Bytes::from("event: message_start\n\n"));
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].stream);
+        assert!(registry.cancel(&listed[0].request_id));
+
+        let rest = tokio::time::timeout(std::time::Duration::from_secs(5), body.collect())
+            .await
+            .expect("stream ended")
+            .unwrap()
+            .to_bytes();
+        let text = String::from_utf8_lossy(&rest);
+        assert!(text.contains("request_cancelled"), "{}", text);
+        assert!(registry.list().is_empty());
+    }
 }
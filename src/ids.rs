@@ -0,0 +1,66 @@
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator};
+
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Base62 digits needed for any `u128`.
+const PAYLOAD_LEN: usize = 22;
+
+/// Id of a message the gateway answers for, e.g. when the downstream sent
+/// none: `msg_01` and 22 base62 characters, like Anthropic's.
+pub fn message_id() -> String {
+    random_id("msg")
+}
+
+/// Id of a tool call the downstream returned without one.
+pub fn tool_use_id() -> String {
+    random_id("toolu")
+}
+
+/// Id of an incoming request, used in logs, traces and `x-request-id`.
+pub fn request_id() -> String {
+    random_id("req")
+}
+
+/// Id of a usage event sent to billing; the system's idempotency key.
+pub fn usage_event_id() -> String {
+    random_id("evt")
+}
+
+fn random_id(prefix: &str) -> String {
+    let random = u128::from_be_bytes(RandomIdGenerator::default().new_trace_id().to_bytes());
+    format!("{}_01{}", prefix, base62(random))
+}
+
+/// Fixed-width, most significant digit first.
+fn base62(mut value: u128) -> String {
+    let mut digits = [b'0'; PAYLOAD_LEN];
+    for digit in digits.iter_mut().rev() {
+        *digit = BASE62[(value % 62) as usize];
+        value /= 62;
+    }
+    digits.iter().map(|&d| d as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_prefixed_base62() {
+        assert_eq!(base62(0), "0".repeat(PAYLOAD_LEN));
+        assert_eq!(base62(61), format!("{}z", "0".repeat(PAYLOAD_LEN - 1)));
+        assert_eq!(base62(u128::MAX), "7n42DGM5Tflk9n8mt7Fhc7");
+
+        for (id, prefix) in [
+            (message_id(), "msg_01"),
+            (tool_use_id(), "toolu_01"),
+            (request_id(), "req_01"),
+            (usage_event_id(), "evt_01"),
+        ] {
+            let payload = id.strip_prefix(prefix).expect(&id);
+            assert_eq!(payload.len(), PAYLOAD_LEN, "{}", id);
+            assert!(payload.bytes().all(|b| b.is_ascii_alphanumeric()), "{}", id);
+        }
+        assert_ne!(message_id(), message_id());
+    }
+}
@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::watch;
+
+use crate::audit_log::now_ms;
+
+/// One in-flight request as listed by `GET /admin/inflight`.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct InflightInfo {
+    pub request_id: String,
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub stream: bool,
+    pub started_ms: u64,
+    pub age_ms: u64,
+    pub cancelled: bool,
+}
+
+struct Entry {
+    key_id: Option<String>,
+    model: Option<String>,
+    stream: bool,
+    started_ms: u64,
+    started: Instant,
+    cancel: watch::Sender<bool>,
+}
+
+/// Tracks `/v1/messages` requests from arrival until their response (or
+/// stream) finishes, so operators can list them and cancel runaway ones.
+#[derive(Clone, Default)]
+pub struct InflightRegistry {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl InflightRegistry {
+    pub fn register(
+        &self,
+        request_id: &str,
+        key_id: Option<String>,
+        model: Option<String>,
+        stream: bool,
+    ) -> InflightTicket {
+        let (tx, rx) = watch::channel(false);
+        let entry = Entry {
+            key_id,
+            model,
+            stream,
+            started_ms: now_ms() as u64,
+            started: Instant::now(),
+            cancel: tx,
+        };
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request_id.to_string(), entry);
+        InflightTicket {
+            inner: Arc::new(TicketInner {
+                registry: self.clone(),
+                request_id: request_id.to_string(),
+                cancelled: rx,
+            }),
+        }
+    }
+
+    /// In-flight requests, oldest first.
+    pub fn list(&self) -> Vec<InflightInfo> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut list: Vec<InflightInfo> = entries
+            .iter()
+            .map(|(request_id, entry)| InflightInfo {
+                request_id: request_id.clone(),
+                key_id: entry.key_id.clone(),
+                model: entry.model.clone(),
+                stream: entry.stream,
+                started_ms: entry.started_ms,
+                age_ms: entry.started.elapsed().as_millis() as u64,
+                cancelled: *entry.cancel.borrow(),
+            })
+            .collect();
+        list.sort_by_key(|info| info.started_ms);
+        list
+    }
+
+    /// Signals cancellation; returns `false` when the request is not in flight.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(request_id) {
+            Some(entry) => {
+                entry.cancel.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Keeps a request listed while any clone is alive; clones travel with the
+/// response so streaming requests stay listed until the stream ends.
+#[derive(Clone)]
+pub struct InflightTicket {
+    inner: Arc<TicketInner>,
+}
+
+struct TicketInner {
+    registry: InflightRegistry,
+    request_id: String,
+    cancelled: watch::Receiver<bool>,
+}
+
+impl InflightTicket {
+    /// Resolves once the request has been cancelled.
+    pub async fn cancelled(&self) {
+        let mut rx = self.inner.cancelled.clone();
+        if rx.wait_for(|cancelled| *cancelled).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.cancelled.borrow()
+    }
+}
+
+impl Drop for TicketInner {
+    fn drop(&mut self) {
+        self.registry
+            .entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.request_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ticket_lists_until_dropped_and_observes_cancel() {
+        let registry = InflightRegistry::default();
+        let ticket = registry.register("req-1", None, Some("gpt-4o".to_string()), true);
+        let clone = ticket.clone();
+        let listed = registry.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].model.as_deref(), Some("gpt-4o"));
+        assert!(!listed[0].cancelled);
+
+        assert!(registry.cancel("req-1"));
+        assert!(!registry.cancel("req-2"));
+        tokio::time::timeout(std::time::Duration::from_secs(1), clone.cancelled())
+            .await
+            .expect("cancelled");
+        assert!(ticket.is_cancelled());
+        assert!(registry.list()[0].cancelled);
+
+        drop(ticket);
+        assert_eq!(registry.list().len(), 1);
+        drop(clone);
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,212 @@
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use ipnet::IpNet;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use crate::config::IpAccessConfig;
+use crate::error::AppError;
+
+/// `server.ip_access`: CIDR allow / deny lists and a per-address in-flight
+/// cap, checked before a request reaches the handlers (and the global
+/// `limits.max_inflight` semaphore). The client address is the peer, or,
+/// when the peer is a trusted proxy, the right-most `X-Forwarded-For` entry
+/// that is not a trusted proxy.
+#[derive(Clone)]
+pub struct IpAccess {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    trusted_proxies: Vec<IpNet>,
+    max_inflight_per_ip: Option<usize>,
+    inflight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpAccess {
+    pub fn new(config: &IpAccessConfig) -> Result<Self, String> {
+        Ok(Self {
+            allow: parse_nets(&config.allow, "allow")?,
+            deny: parse_nets(&config.deny, "deny")?,
+            trusted_proxies: parse_nets(&config.trusted_proxies, "trusted_proxies")?,
+            max_inflight_per_ip: config.max_inflight_per_ip,
+            inflight: Default::default(),
+        })
+    }
+
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !contains(&self.trusted_proxies, peer) {
+            return peer;
+        }
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|entry| entry.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        let mut client = peer;
+        for hop in forwarded.into_iter().rev() {
+            client = hop;
+            if !contains(&self.trusted_proxies, hop) {
+                break;
+            }
+        }
+        client
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        !contains(&self.deny, ip) && (self.allow.is_empty() || contains(&self.allow, ip))
+    }
+
+    /// Counts a request against `ip`'s cap; `None` when the cap is reached.
+    pub fn acquire(&self, ip: IpAddr) -> Option<IpPermit> {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        let count = inflight.entry(ip).or_insert(0);
+        if self.max_inflight_per_ip.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+        Some(IpPermit {
+            ip,
+            inflight: self.inflight.clone(),
+        })
+    }
+}
+
+/// Releases one in-flight slot of an address when dropped.
+pub struct IpPermit {
+    ip: IpAddr,
+    inflight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for IpPermit {
+    fn drop(&mut self) {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = inflight.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                inflight.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Middleware for `IpAccess`. The permit is held until the response body
+/// is finished, so streams count for their whole duration. Without the
+/// peer address (a host app serving the router without connect info) no
+/// rule can be checked, so every request is rejected.
+pub async fn check_ip(State(access): State<IpAccess>, request: Request, next: Next) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let Some(peer) = peer else {
+        tracing::error!(
+            path = %request.uri().path(),
+            "server.ip_access is set but the peer address is unavailable; serve with connect info"
+        );
+        return AppError::forbidden("client address unavailable").into_response();
+    };
+    let ip = access.client_ip(peer, request.headers());
+    if !access.allows(ip) {
+        tracing::warn!(ip = %ip, path = %request.uri().path(), "request from disallowed address");
+        return AppError::forbidden("client address not allowed").into_response();
+    }
+    let Some(permit) = access.acquire(ip) else {
+        return AppError::rate_limited("too many in-flight requests from this address").into_response();
+    };
+    let response = next.run(request).await;
+    response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _held = &permit;
+            chunk
+        }))
+    })
+}
+
+fn parse_nets(entries: &[String], field: &str) -> Result<Vec<IpNet>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let entry = entry.trim();
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("server.ip_access.{} invalid: {}", field, entry))
+        })
+        .collect()
+}
+
+fn contains(nets: &[IpNet], ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+    nets.iter().any(|net| net.contains(&ip))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, StatusCode};
+    use tower::ServiceExt;
+
+    #[test]
+    fn resolves_forwarded_client_and_enforces_lists_and_caps() {
+        let config: IpAccessConfig = serde_yaml::from_str(
+            "allow: [10.0.0.0/8, 192.168.1.7]\ndeny: [10.9.0.0/16]\ntrusted_proxies: [172.16.0.1]\nmax_inflight_per_ip: 1\n",
+        )
+        .unwrap();
+        let access = IpAccess::new(&config).unwrap();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("1.2.3.4, 10.1.2.3, 172.16.0.1"));
+        assert_eq!(access.client_ip(ip("172.16.0.1"), &headers), ip("10.1.2.3"));
+        assert_eq!(access.client_ip(ip("10.5.5.5"), &headers), ip("10.5.5.5"));
+
+        assert!(access.allows(ip("10.1.2.3")));
+        assert!(access.allows(ip("::ffff:192.168.1.7")));
+        assert!(!access.allows(ip("10.9.1.1")));
+        assert!(!access.allows(ip("8.8.8.8")));
+
+        let permit = access.acquire(ip("10.1.2.3")).expect("first");
+        assert!(access.acquire(ip("10.1.2.3")).is_none());
+        assert!(access.acquire(ip("10.1.2.4")).is_some());
+        drop(permit);
+        assert!(access.acquire(ip("10.1.2.3")).is_some());
+        assert!(IpAccess::new(&serde_yaml::from_str("allow: [10.0.0.0/33]").unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn middleware_rejects_requests_without_a_peer_address() {
+        let router = |config: &str| {
+            let access = IpAccess::new(&serde_yaml::from_str(config).unwrap()).unwrap();
+            axum::Router::new()
+                .route("/", axum::routing::get(|| async { "ok" }))
+                .layer(axum::middleware::from_fn_with_state(access, check_ip))
+        };
+        let request = |peer: Option<&str>| {
+            let mut request = Request::get("/").body(Body::empty()).unwrap();
+            if let Some(peer) = peer {
+                request
+                    .extensions_mut()
+                    .insert(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 40000)));
+            }
+            request
+        };
+
+        for config in ["allow: [10.0.0.0/8]", "deny: [10.9.0.0/16]"] {
+            let response = router(config).oneshot(request(None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", config);
+        }
+        let response = router("allow: [10.0.0.0/8]").oneshot(request(Some("10.1.2.3"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router("allow: [10.0.0.0/8]").oneshot(request(Some("8.8.8.8"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,161 @@
+use opentelemetry::metrics::Counter;
+use opentelemetry::KeyValue;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{Config, DownstreamKey};
+
+/// `downstream.api_keys`: several active downstream keys used in place of
+/// `downstream.api_key`. `round_robin` spreads requests over the enabled
+/// keys; `failover` sends to the first enabled key and moves on to the next
+/// on 401 or 429. Keys can be disabled at runtime through the admin API.
+#[derive(Clone)]
+pub struct KeyPool {
+    keys: Arc<Vec<DownstreamKey>>,
+    failover: bool,
+    next: Arc<AtomicUsize>,
+    disabled: Arc<Mutex<HashSet<String>>>,
+    requests: Counter<u64>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct KeyStatus {
+    pub id: String,
+    pub enabled: bool,
+}
+
+impl Default for KeyPool {
+    fn default() -> Self {
+        Self::with_keys(Vec::new(), false)
+    }
+}
+
+impl KeyPool {
+    pub fn new(config: &Config) -> Self {
+        Self::with_keys(
+            config.downstream.api_keys.clone(),
+            config.downstream.key_rotation == "failover",
+        )
+    }
+
+    fn with_keys(keys: Vec<DownstreamKey>, failover: bool) -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        Self {
+            keys: Arc::new(keys),
+            failover,
+            next: Arc::new(AtomicUsize::new(0)),
+            disabled: Arc::new(Mutex::new(HashSet::new())),
+            requests: meter
+                .u64_counter("ai.gateway.downstream_keys.requests")
+                .with_description("Downstream calls per API key by outcome")
+                .build(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Keys to try for one call, in order: the next one in rotation for
+    /// `round_robin`, every enabled key for `failover`. Empty when all keys
+    /// are disabled.
+    pub fn attempts(&self) -> Vec<DownstreamKey> {
+        let disabled = self.disabled.lock().unwrap_or_else(|e| e.into_inner());
+        let enabled: Vec<&DownstreamKey> = self.keys.iter().filter(|key| !disabled.contains(&key.id)).collect();
+        if self.failover || enabled.is_empty() {
+            return enabled.into_iter().cloned().collect();
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % enabled.len();
+        vec![enabled[index].clone()]
+    }
+
+    /// Whether a response with `status` should be retried with the next key.
+    pub fn should_fail_over(&self, status: reqwest::StatusCode) -> bool {
+        self.failover && matches!(status.as_u16(), 401 | 429)
+    }
+
+    pub fn record(&self, key_id: &str, result: &Result<reqwest::Response, crate::error::AppError>) {
+        let outcome = match result {
+            Ok(resp) if resp.status().as_u16() == 401 => "unauthorized",
+            Ok(resp) if resp.status().as_u16() == 429 => "rate_limited",
+            Ok(resp) if resp.status().is_success() => "ok",
+            _ => "error",
+        };
+        self.requests.add(
+            1,
+            &[KeyValue::new("key", key_id.to_string()), KeyValue::new("outcome", outcome)],
+        );
+    }
+
+    /// Enables or disables a key; false for an unknown id.
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> bool {
+        if !self.keys.iter().any(|key| key.id == id) {
+            return false;
+        }
+        let mut disabled = self.disabled.lock().unwrap_or_else(|e| e.into_inner());
+        if enabled {
+            disabled.remove(id);
+        } else {
+            disabled.insert(id.to_string());
+        }
+        true
+    }
+
+    pub fn list(&self) -> Vec<KeyStatus> {
+        let disabled = self.disabled.lock().unwrap_or_else(|e| e.into_inner());
+        self.keys
+            .iter()
+            .map(|key| KeyStatus {
+                id: key.id.clone(),
+                enabled: !disabled.contains(&key.id),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(ids: &[&str]) -> Vec<DownstreamKey> {
+        ids.iter()
+            .map(|id| DownstreamKey {
+                id: id.to_string(),
+                key: format!("sk-{}", id),
+            })
+            .collect()
+    }
+
+    fn ids(attempts: Vec<DownstreamKey>) -> Vec<String> {
+        attempts.into_iter().map(|key| key.id).collect()
+    }
+
+    #[test]
+    fn rotates_fails_over_and_skips_disabled_keys() {
+        let pool = KeyPool::with_keys(keys(&["a", "b", "c"]), false);
+        assert_eq!(ids(pool.attempts()), ["a"]);
+        assert_eq!(ids(pool.attempts()), ["b"]);
+        assert!(pool.set_enabled("c", false));
+        assert!(!pool.set_enabled("missing", false));
+        assert_eq!(ids(pool.attempts()), ["a"]);
+        assert!(!pool.should_fail_over(reqwest::StatusCode::TOO_MANY_REQUESTS));
+
+        let pool = KeyPool::with_keys(keys(&["a", "b"]), true);
+        assert_eq!(ids(pool.attempts()), ["a", "b"]);
+        assert!(pool.should_fail_over(reqwest::StatusCode::UNAUTHORIZED));
+        assert!(!pool.should_fail_over(reqwest::StatusCode::BAD_GATEWAY));
+        pool.set_enabled("a", false);
+        pool.set_enabled("b", false);
+        assert!(pool.attempts().is_empty());
+        pool.set_enabled("a", true);
+        assert_eq!(
+            pool.list(),
+            [
+                KeyStatus { id: "a".to_string(), enabled: true },
+                KeyStatus { id: "b".to_string(), enabled: false },
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Anthropic-compatible LLM gateway. The `llm-gateway` binary is a thin CLI
+//! over this crate; other services can embed the gateway in-process with
+//! [`GatewayBuilder`] and mount its router into their own axum app.
+//!
+//! Only the builder, the config and the types a custom [`provider::Provider`]
+//! works with are public; everything else is internal to the crate.
+
+pub(crate) mod access_log;
+pub(crate) mod admin;
+pub(crate) mod alerts;
+pub(crate) mod audit_log;
+pub(crate) mod audit_s3;
+pub(crate) mod backpressure;
+pub(crate) mod bench;
+pub(crate) mod billing;
+pub(crate) mod builtin_tools;
+pub(crate) mod canary;
+pub(crate) mod capabilities;
+pub(crate) mod capture;
+pub(crate) mod cli;
+pub(crate) mod client_identity;
+pub(crate) mod client_pool;
+pub(crate) mod coalesce;
+pub(crate) mod cohere;
+pub(crate) mod compaction;
+pub mod config;
+pub(crate) mod conformance;
+pub(crate) mod context;
+pub(crate) mod cors;
+pub(crate) mod dashboard;
+pub(crate) mod debug_capture;
+pub mod error;
+pub(crate) mod event_split;
+pub(crate) mod experiments;
+pub(crate) mod exporter_health;
+pub mod gateway;
+pub(crate) mod handlers;
+pub(crate) mod ids;
+pub(crate) mod inflight;
+pub(crate) mod ip_access;
+pub(crate) mod key_pool;
+pub(crate) mod metrics;
+pub(crate) mod mistral;
+pub mod models;
+pub(crate) mod oauth;
+pub(crate) mod openapi;
+pub(crate) mod partial_json;
+pub(crate) mod pg_store;
+pub(crate) mod prompts;
+pub mod provider;
+pub(crate) mod raw_body;
+pub(crate) mod replay;
+pub(crate) mod rewrite;
+pub(crate) mod routing;
+pub(crate) mod scoring;
+pub(crate) mod server;
+pub(crate) mod shadow;
+pub(crate) mod signing;
+pub mod sse;
+pub(crate) mod sse_normalize;
+pub(crate) mod state;
+pub(crate) mod streaming;
+pub(crate) mod structured_output;
+pub(crate) mod system_prompt;
+pub(crate) mod tap;
+pub(crate) mod throttle;
+pub(crate) mod tokenizer;
+pub(crate) mod tool_ids;
+pub(crate) mod tracing_otlp;
+pub(crate) mod transcripts;
+pub(crate) mod translate;
+pub(crate) mod translation_events;
+pub(crate) mod usage;
+pub(crate) mod usage_sqlite;
+pub(crate) mod validation;
+pub(crate) mod vertex;
+pub(crate) mod warmup;
+
+pub use config::Config;
+pub use gateway::{Gateway, GatewayBuilder};
+
+/// What the `llm-gateway` binary, the translation bench and the conformance
+/// suite drive directly. Not part of the embedding API.
+#[doc(hidden)]
+pub mod internal {
+    pub use crate::cli::{run as run_cli, Cli};
+    pub use crate::conformance::{default_config, run_dir, Mode};
+    pub use crate::streaming::translate_sse_body;
+    pub use crate::translate::{anthropic_to_openai, openai_to_anthropic, AnthropicVersion};
+}
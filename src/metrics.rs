@@ -1,21 +1,40 @@
-use opentelemetry::metrics::{Counter, Histogram, ObservableGauge};
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::{Counter, Histogram, Meter, ObservableGauge};
 use opentelemetry::metrics::MeterProvider;
 use opentelemetry_otlp::{MetricExporter, Protocol, WithExportConfig, WithHttpConfig};
 use std::time::Duration;
 use std::collections::HashMap;
 use base64::Engine;
 use opentelemetry_sdk::metrics::periodic_reader_with_async_runtime::PeriodicReader;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
 use opentelemetry_sdk::metrics::SdkMeterProvider;
 use opentelemetry_sdk::runtime;
 use opentelemetry_sdk::Resource;
 use std::sync::{atomic::AtomicU64, Arc};
 
+use crate::exporter_health::{ExporterHealth, ReportingExporter};
+
 #[derive(Clone)]
 pub struct Metrics {
     pub requests: Counter<u64>,
     pub errors: Counter<u64>,
     pub latency_ms: Histogram<f64>,
+    /// Downstream time to first byte, total downstream time, and the rest
+    /// of the end-to-end latency (gateway overhead), by `stream`.
+    pub downstream_ttfb_ms: Histogram<f64>,
+    pub downstream_ms: Histogram<f64>,
+    pub overhead_ms: Histogram<f64>,
+    pub translation_events: Counter<u64>,
+    /// Unparseable downstream stream chunks skipped in lenient mode, by
+    /// `model`.
+    pub malformed_chunks: Counter<u64>,
+    pub exporter_health: ExporterHealth,
     _inflight: ObservableGauge<i64>,
+    _exporter_failures: ObservableGauge<i64>,
 }
 
 pub fn init_metrics(
@@ -23,6 +42,9 @@ pub fn init_metrics(
     exporter: MetricsExporterConfig,
     inflight_count: Arc<AtomicU64>,
 ) -> Result<Metrics, String> {
+    let exporter_kind = exporter.kind.clone();
+    let export_interval_ms = exporter.export_interval_ms;
+    let latency_buckets_ms = exporter.latency_buckets_ms.clone();
     let exporter = match exporter.kind.as_str() {
         "langfuse_http" => {
             let auth = base64::engine::general_purpose::STANDARD.encode(format!(
@@ -48,7 +70,9 @@ pub fn init_metrics(
             .map_err(|e| format!("metrics exporter init error: {}", e))?,
     };
 
-    let reader = PeriodicReader::builder(exporter, runtime::Tokio).build();
+    let exporter_health = ExporterHealth::default();
+    exporter_health.metrics.set_exporter(&exporter_kind);
+    let reader = metrics_reader(exporter, &exporter_health, export_interval_ms);
     let provider = SdkMeterProvider::builder()
         .with_reader(reader)
         .with_resource(Resource::builder().with_service_name(service_name).build())
@@ -59,16 +83,40 @@ pub fn init_metrics(
 
     let requests = meter
         .u64_counter("ai.gateway.requests")
-        .with_description("Total requests")
+        .with_description("Requests by route, method and status")
         .build();
     let errors = meter
         .u64_counter("ai.gateway.errors")
         .with_description("Total errors")
         .build();
-    let latency_ms = meter
-        .f64_histogram("ai.gateway.latency_ms")
-        .with_unit("ms")
-        .with_description("Request latency in ms")
+    let buckets = latency_buckets_ms.as_ref();
+    let latency_ms =
+        ms_histogram(&meter, "ai.gateway.latency_ms", "Request latency in ms", buckets);
+    let downstream_ttfb_ms = ms_histogram(
+        &meter,
+        "ai.gateway.downstream.ttfb_ms",
+        "Downstream time to first byte in ms",
+        buckets,
+    );
+    let downstream_ms = ms_histogram(
+        &meter,
+        "ai.gateway.downstream.duration_ms",
+        "Downstream time until the response completed in ms",
+        buckets,
+    );
+    let overhead_ms = ms_histogram(
+        &meter,
+        "ai.gateway.overhead_ms",
+        "End-to-end latency not spent waiting on the downstream in ms",
+        buckets,
+    );
+    let translation_events = meter
+        .u64_counter("ai.gateway.translation_events")
+        .with_description("Translate-mode conversions, downgrades and drops by event")
+        .build();
+    let malformed_chunks = meter
+        .u64_counter("ai.gateway.stream.malformed_chunks")
+        .with_description("Unparseable downstream stream chunks skipped in lenient mode")
         .build();
     let inflight = meter
         .i64_observable_gauge("ai.gateway.inflight")
@@ -78,20 +126,45 @@ pub fn init_metrics(
             observer.observe(value, &[]);
         })
         .build();
+    let exporter_failures = exporter_failures_gauge(&meter, &exporter_health);
 
     Ok(Metrics {
         requests,
         errors,
         latency_ms,
+        downstream_ttfb_ms,
+        downstream_ms,
+        overhead_ms,
+        translation_events,
+        malformed_chunks,
+        exporter_health,
         _inflight: inflight,
+        _exporter_failures: exporter_failures,
     })
 }
 
+/// Exports on `export_interval_ms` alone; each export's outcome is recorded
+/// in `health.metrics`.
+fn metrics_reader<E: PushMetricExporter>(
+    exporter: E,
+    health: &ExporterHealth,
+    export_interval_ms: u64,
+) -> PeriodicReader<ReportingExporter<E>> {
+    PeriodicReader::builder(ReportingExporter::new(exporter, health.metrics.clone()), runtime::Tokio)
+        .with_interval(Duration::from_millis(export_interval_ms))
+        .build()
+}
+
 pub fn init_metrics_noop(inflight_count: Arc<AtomicU64>) -> Metrics {
     let meter = opentelemetry::global::meter("llm-gateway");
     let requests = meter.u64_counter("ai.gateway.requests").build();
     let errors = meter.u64_counter("ai.gateway.errors").build();
     let latency_ms = meter.f64_histogram("ai.gateway.latency_ms").build();
+    let downstream_ttfb_ms = meter.f64_histogram("ai.gateway.downstream.ttfb_ms").build();
+    let downstream_ms = meter.f64_histogram("ai.gateway.downstream.duration_ms").build();
+    let overhead_ms = meter.f64_histogram("ai.gateway.overhead_ms").build();
+    let translation_events = meter.u64_counter("ai.gateway.translation_events").build();
+    let malformed_chunks = meter.u64_counter("ai.gateway.stream.malformed_chunks").build();
     let inflight = meter
         .i64_observable_gauge("ai.gateway.inflight")
         .with_callback(move |observer| {
@@ -99,19 +172,186 @@ pub fn init_metrics_noop(inflight_count: Arc<AtomicU64>) -> Metrics {
             observer.observe(value, &[]);
         })
         .build();
+    let exporter_health = ExporterHealth::default();
+    let exporter_failures = exporter_failures_gauge(&meter, &exporter_health);
 
     Metrics {
         requests,
         errors,
         latency_ms,
+        downstream_ttfb_ms,
+        downstream_ms,
+        overhead_ms,
+        translation_events,
+        malformed_chunks,
+        exporter_health,
         _inflight: inflight,
+        _exporter_failures: exporter_failures,
+    }
+}
+
+/// A millisecond histogram with `observability.metrics.latency_buckets_ms`
+/// when configured.
+fn ms_histogram(
+    meter: &Meter,
+    name: &'static str,
+    description: &'static str,
+    buckets: Option<&Vec<f64>>,
+) -> Histogram<f64> {
+    let histogram = meter.f64_histogram(name).with_unit("ms").with_description(description);
+    match buckets {
+        Some(buckets) => histogram.with_boundaries(buckets.clone()).build(),
+        None => histogram.build(),
     }
 }
 
+/// `ai.gateway.exporter.consecutive_failures` by `signal` (traces /
+/// metrics), from the telemetry watchdog.
+fn exporter_failures_gauge(meter: &Meter, health: &ExporterHealth) -> ObservableGauge<i64> {
+    let health = health.clone();
+    meter
+        .i64_observable_gauge("ai.gateway.exporter.consecutive_failures")
+        .with_description("Consecutive failed exporter flushes by signal")
+        .with_callback(move |observer| {
+            for (signal, status) in [("traces", &health.traces), ("metrics", &health.metrics)] {
+                if status.enabled() {
+                    observer.observe(
+                        status.consecutive_failures() as i64,
+                        &[KeyValue::new("signal", signal)],
+                    );
+                }
+            }
+        })
+        .build()
+}
+
 pub struct MetricsExporterConfig {
     pub kind: String,
     pub endpoint: String,
     pub timeout_ms: u64,
     pub public_key: String,
     pub secret_key: String,
+    pub export_interval_ms: u64,
+    pub latency_buckets_ms: Option<Vec<f64>>,
+}
+
+/// Route-level middleware counting every request in `ai.gateway.requests`,
+/// labelled with the route template (`/admin/tap/{request_id}`, not the
+/// path), method and status, plus `stream` (event-stream responses) and
+/// `experiment` (the `x-gateway-experiment` response header).
+pub async fn record_request(State(metrics): State<Metrics>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let response = next.run(request).await;
+    metrics.requests.add(1, &request_attributes(route, method, &response));
+    response
+}
+
+fn request_attributes(route: String, method: String, response: &Response) -> Vec<KeyValue> {
+    let stream = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"));
+    let mut attributes = vec![
+        KeyValue::new("route", route),
+        KeyValue::new("method", method),
+        KeyValue::new("status", i64::from(response.status().as_u16())),
+        KeyValue::new("stream", if stream { "true" } else { "false" }),
+    ];
+    if let Some(experiment) = response
+        .headers()
+        .get(crate::experiments::EXPERIMENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        attributes.push(KeyValue::new("experiment", experiment.to_string()));
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    #[test]
+    fn labels_route_status_stream_and_experiment() {
+        let response = Response::builder()
+            .status(200)
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(crate::experiments::EXPERIMENT_HEADER, "prompt-v2/b")
+            .body(Body::empty())
+            .unwrap();
+        let attributes = request_attributes("/v1/messages".to_string(), "POST".to_string(), &response);
+        let get = |key: &str| {
+            attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(get("route").as_deref(), Some("/v1/messages"));
+        assert_eq!(get("method").as_deref(), Some("POST"));
+        assert_eq!(get("status").as_deref(), Some("200"));
+        assert_eq!(get("stream").as_deref(), Some("true"));
+        assert_eq!(get("experiment").as_deref(), Some("prompt-v2/b"));
+
+        let response = Response::builder().status(404).body(Body::empty()).unwrap();
+        let attributes = request_attributes("/v1/models".to_string(), "GET".to_string(), &response);
+        assert!(attributes.iter().any(|kv| kv.key.as_str() == "stream" && kv.value.as_str() == "false"));
+        assert!(!attributes.iter().any(|kv| kv.key.as_str() == "experiment"));
+    }
+
+    struct CountingExporter(Arc<AtomicU64>);
+
+    impl PushMetricExporter for CountingExporter {
+        async fn export(
+            &self,
+            _metrics: &opentelemetry_sdk::metrics::data::ResourceMetrics,
+        ) -> opentelemetry_sdk::error::OTelSdkResult {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn force_flush(&self) -> opentelemetry_sdk::error::OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> opentelemetry_sdk::error::OTelSdkResult {
+            Ok(())
+        }
+
+        fn temporality(&self) -> opentelemetry_sdk::metrics::Temporality {
+            opentelemetry_sdk::metrics::Temporality::Cumulative
+        }
+    }
+
+    async fn exports_within(export_interval_ms: u64, window: Duration) -> u64 {
+        let exports = Arc::new(AtomicU64::new(0));
+        let health = ExporterHealth::default();
+        health.metrics.set_exporter("otlp_grpc");
+        let reader = metrics_reader(CountingExporter(exports.clone()), &health, export_interval_ms);
+        let provider = SdkMeterProvider::builder().with_reader(reader).build();
+        provider.meter("test").u64_counter("test.requests").build().add(1, &[]);
+        tokio::time::sleep(window).await;
+        let count = exports.load(std::sync::atomic::Ordering::SeqCst);
+        if count > 0 {
+            assert_eq!(health.snapshot()["metrics"]["status"], "ok");
+        }
+        // Shutdown waits on the reader task, so it must not block this runtime's only thread.
+        tokio::task::spawn_blocking(move || provider.shutdown()).await.unwrap().unwrap();
+        count
+    }
+
+    #[tokio::test]
+    async fn exports_follow_the_configured_interval() {
+        let fast = exports_within(100, Duration::from_millis(650)).await;
+        assert!((3..=7).contains(&fast), "expected ~6 exports at 100ms, got {}", fast);
+
+        let slow = exports_within(60_000, Duration::from_millis(650)).await;
+        assert_eq!(slow, 0, "nothing but the interval should trigger an export");
+    }
 }
@@ -0,0 +1,253 @@
+use axum::http::StatusCode;
+use serde_json::{json, Map, Value};
+
+use crate::config::Config;
+use crate::error::{map_downstream_error, AppError};
+use crate::models::{OpenAIRequest, OpenAIResponse};
+use crate::provider::{
+    endpoint_url, json_headers, request_body, DownstreamRequest, Provider, StreamError, StreamEvent,
+};
+use crate::sse::SseEvent;
+
+const TOOL_CALL_ID_LEN: usize = 9;
+const TOOL_CALL_ID_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Mistral's `/v1/chat/completions`. Close to OpenAI, but it rejects unknown
+/// fields, takes `max_tokens`, requires 9-character alphanumeric tool call
+/// ids, and may send tool call arguments as objects, content as typed chunks
+/// (including `thinking`) and `finish_reason: model_length`.
+pub struct MistralProvider;
+
+impl Provider for MistralProvider {
+    fn build_request(&self, req: &OpenAIRequest, config: &Config) -> Result<DownstreamRequest, AppError> {
+        let (base_url, api_key) = config.provider_endpoint("mistral");
+        let mut body = request_body(req)?;
+        if let Some(obj) = body.as_object_mut() {
+            shape_request(obj);
+        }
+        Ok(DownstreamRequest {
+            url: endpoint_url(base_url, "v1", "chat/completions"),
+            headers: json_headers(&format!("Bearer {}", api_key.unwrap_or_default())),
+            body,
+        })
+    }
+
+    fn parse_response(&self, body: &str) -> Result<OpenAIResponse, AppError> {
+        let mut value: Value = serde_json::from_str(body)
+            .map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))?;
+        for choice in choices_mut(&mut value) {
+            normalize_finish_reason(choice);
+            if let Some(message) = choice.get_mut("message") {
+                normalize_message(message);
+            }
+        }
+        serde_json::from_value(value).map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))
+    }
+
+    fn parse_stream_chunk(&self, event: &SseEvent) -> Result<StreamEvent, StreamError> {
+        let data = event.data.trim();
+        if data == "[DONE]" {
+            return Ok(StreamEvent::Done);
+        }
+        let mut value: Value = serde_json::from_str(data).map_err(|e| StreamError::Malformed(e.to_string()))?;
+        if value.get("object").and_then(Value::as_str) == Some("error") {
+            return Err(StreamError::Downstream(AppError::api_error(format!(
+                "downstream stream error: {}",
+                data
+            ))));
+        }
+        for choice in choices_mut(&mut value) {
+            normalize_finish_reason(choice);
+            if let Some(delta) = choice.get_mut("delta") {
+                normalize_message(delta);
+                if let Some(calls) = delta.get_mut("tool_calls").and_then(Value::as_array_mut) {
+                    for (position, call) in calls.iter_mut().enumerate() {
+                        if let Some(call) = call.as_object_mut() {
+                            call.entry("index").or_insert_with(|| json!(position));
+                        }
+                    }
+                }
+            }
+        }
+        serde_json::from_value(value)
+            .map(StreamEvent::Chunk)
+            .map_err(|e| StreamError::Malformed(e.to_string()))
+    }
+
+    /// Mistral reports schema violations as 422.
+    fn map_error(&self, status: StatusCode, body: &str) -> AppError {
+        if status == StatusCode::UNPROCESSABLE_ENTITY {
+            let mut err = map_downstream_error(StatusCode::BAD_REQUEST, body);
+            err.downstream_status = Some(status);
+            return err;
+        }
+        map_downstream_error(status, body)
+    }
+}
+
+fn shape_request(obj: &mut Map<String, Value>) {
+    if let Some(max_tokens) = obj.remove("max_completion_tokens") {
+        obj.insert("max_tokens".to_string(), max_tokens);
+    }
+    obj.remove("stream_options");
+    obj.remove("reasoning_effort");
+    obj.remove("add_generation_prompt");
+    let prefix = obj.remove("continue_final_message").and_then(|v| v.as_bool()) == Some(true);
+    if obj.get("tool_choice").and_then(Value::as_str) == Some("required") {
+        obj.insert("tool_choice".to_string(), json!("any"));
+    }
+
+    let Some(messages) = obj.get_mut("messages").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for message in messages.iter_mut() {
+        let Some(message) = message.as_object_mut() else {
+            continue;
+        };
+        message.remove("reasoning_content");
+        if let Some(id) = message.get_mut("tool_call_id") {
+            *id = json!(tool_call_id(id.as_str().unwrap_or_default()));
+        }
+        if let Some(calls) = message.get_mut("tool_calls").and_then(Value::as_array_mut) {
+            for call in calls {
+                if let Some(id) = call.get_mut("id") {
+                    *id = json!(tool_call_id(id.as_str().unwrap_or_default()));
+                }
+            }
+        }
+    }
+    // The vLLM prefill flag maps to Mistral's `prefix` on the final
+    // assistant message.
+    if prefix
+        && let Some(last) = messages.last_mut()
+        && last.get("role").and_then(Value::as_str) == Some("assistant")
+    {
+        last["prefix"] = json!(true);
+    }
+}
+
+/// Mistral only accepts ids of 9 characters from `[a-zA-Z0-9]`. Other ids
+/// (e.g. Anthropic's `toolu_...`) are hashed, so a call and its result still
+/// map to the same id.
+pub fn tool_call_id(id: &str) -> String {
+    if id.len() == TOOL_CALL_ID_LEN && id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return id.to_string();
+    }
+    // FNV-1a
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in id.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (0..TOOL_CALL_ID_LEN)
+        .map(|_| {
+            let c = TOOL_CALL_ID_ALPHABET[(hash % TOOL_CALL_ID_ALPHABET.len() as u64) as usize] as char;
+            hash /= TOOL_CALL_ID_ALPHABET.len() as u64;
+            c
+        })
+        .collect()
+}
+
+fn choices_mut(value: &mut Value) -> impl Iterator<Item = &mut Value> {
+    value
+        .get_mut("choices")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+}
+
+fn normalize_finish_reason(choice: &mut Value) {
+    if choice.get("finish_reason").and_then(Value::as_str) == Some("model_length") {
+        choice["finish_reason"] = json!("length");
+    }
+}
+
+/// Flattens chunked content into text plus `reasoning_content`, and fills in
+/// the tool call fields Mistral leaves out or sends as objects.
+fn normalize_message(message: &mut Value) {
+    if let Some(chunks) = message.get("content").and_then(Value::as_array) {
+        let mut text = String::new();
+        let mut thinking = String::new();
+        for chunk in chunks {
+            match chunk.get("type").and_then(Value::as_str) {
+                Some("text") => text.push_str(chunk.get("text").and_then(Value::as_str).unwrap_or_default()),
+                Some("thinking") => {
+                    for part in chunk.get("thinking").and_then(Value::as_array).into_iter().flatten() {
+                        thinking.push_str(part.get("text").and_then(Value::as_str).unwrap_or_default());
+                    }
+                }
+                _ => {}
+            }
+        }
+        message["content"] = json!(text);
+        if !thinking.is_empty() {
+            message["reasoning_content"] = json!(thinking);
+        }
+    }
+    if let Some(calls) = message.get_mut("tool_calls").and_then(Value::as_array_mut) {
+        for call in calls {
+            if let Some(call) = call.as_object_mut() {
+                call.entry("type").or_insert_with(|| json!("function"));
+            }
+            if let Some(arguments) = call.pointer_mut("/function/arguments")
+                && !arguments.is_string()
+            {
+                *arguments = json!(arguments.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OpenAIReasoning;
+
+    #[test]
+    fn adapts_tool_calls_and_chunked_content() {
+        let mut body = json!({
+            "model": "mistral-large-latest",
+            "max_completion_tokens": 64,
+            "stream_options": {"include_usage": true},
+            "tool_choice": "required",
+            "continue_final_message": true,
+            "add_generation_prompt": false,
+            "messages": [
+                {"role": "assistant", "tool_calls": [{"id": "toolu_01ABC", "type": "function", "function": {"name": "f", "arguments": "{}"}}]},
+                {"role": "tool", "tool_call_id": "toolu_01ABC", "content": "ok"},
+                {"role": "assistant", "content": "The answer is"}
+            ]
+        });
+        shape_request(body.as_object_mut().unwrap());
+        let id = body["messages"][0]["tool_calls"][0]["id"].as_str().unwrap().to_string();
+        assert_eq!(id.len(), 9);
+        assert!(id.bytes().all(|b| b.is_ascii_alphanumeric()));
+        assert_eq!(body["messages"][1]["tool_call_id"], json!(id));
+        assert_eq!(body["messages"][2]["prefix"], json!(true));
+        assert_eq!(body["max_tokens"], json!(64));
+        assert_eq!(body["tool_choice"], json!("any"));
+        assert!(body.get("stream_options").is_none() && body.get("continue_final_message").is_none());
+        assert_eq!(tool_call_id("D681PevKs"), "D681PevKs");
+
+        let resp = MistralProvider
+            .parse_response(
+                r#"{"id":"r1","model":"magistral-medium","choices":[{"index":0,"finish_reason":"model_length","message":{"role":"assistant","content":[{"type":"thinking","thinking":[{"type":"text","text":"hmm"}]},{"type":"text","text":"Hi"}],"tool_calls":[{"id":"D681PevKs","function":{"name":"f","arguments":{"x":1}}}]}}]}"#,
+            )
+            .expect("parse");
+        let choice = &resp.choices[0];
+        assert_eq!(choice.finish_reason.as_deref(), Some("length"));
+        assert_eq!(choice.message.reasoning_content, Some(OpenAIReasoning::Text("hmm".to_string())));
+        assert_eq!(choice.message.content.as_ref().map(|c| c.joined_text()).as_deref(), Some("Hi"));
+        assert_eq!(choice.message.tool_calls.as_ref().unwrap()[0].function.arguments, r#"{"x":1}"#);
+
+        let event = SseEvent {
+            event: None,
+            data: r#"{"id":"r1","model":"m","choices":[{"index":0,"delta":{"tool_calls":[{"id":"D681PevKs","function":{"name":"f","arguments":"{}"}}]}}]}"#.to_string(),
+            comment: false,
+        };
+        let Ok(StreamEvent::Chunk(chunk)) = MistralProvider.parse_stream_chunk(&event) else {
+            panic!("expected chunk");
+        };
+        assert_eq!(chunk.choices[0].delta.tool_calls.as_ref().unwrap()[0].index, 0);
+    }
+}
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicRequest {
     pub model: String,
     pub max_tokens: u32,
@@ -26,26 +27,58 @@ pub struct AnthropicRequest {
     pub output_format: Option<AnthropicOutputFormat>,
     #[serde(default)]
     pub thinking: Option<AnthropicThinking>,
-}
-
-#[derive(Debug, Deserialize)]
+    #[serde(default)]
+    pub vllm_params: Option<serde_json::Map<String, Value>>,
+    /// Top-level fields this struct does not know (e.g. `metadata`, or ones
+    /// newer than the gateway); only `models.forward_fields` reach the
+    /// downstream in translate mode.
+    #[serde(flatten)]
+    #[schema(ignore)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// Top-level `AnthropicRequest` fields; every other one lands in `extra`.
+pub const ANTHROPIC_REQUEST_FIELDS: &[&str] = &[
+    "model",
+    "max_tokens",
+    "messages",
+    "system",
+    "temperature",
+    "top_p",
+    "top_k",
+    "stop_sequences",
+    "stream",
+    "tools",
+    "tool_choice",
+    "output_format",
+    "thinking",
+    "vllm_params",
+];
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicMessage {
     pub role: String,
     pub content: AnthropicContent,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 #[serde(untagged)]
 pub enum AnthropicContent {
     Text(String),
     Blocks(Vec<AnthropicContentBlock>),
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 #[serde(tag = "type")]
 pub enum AnthropicContentBlock {
     #[serde(rename = "text")]
-    Text { text: String, #[serde(default)] cache_control: Option<Value> },
+    Text {
+        text: String,
+        #[serde(default)]
+        cache_control: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<Vec<Value>>,
+    },
     #[serde(rename = "image")]
     Image { source: AnthropicSource },
     #[serde(rename = "document")]
@@ -65,7 +98,7 @@ pub enum AnthropicContentBlock {
     RedactedThinking { data: String },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct AnthropicSource {
     #[serde(rename = "type")]
     pub source_type: String,
@@ -77,14 +110,14 @@ pub struct AnthropicSource {
     pub cache_control: Option<Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 #[serde(untagged)]
 pub enum AnthropicSystem {
     Text(String),
     Blocks(Vec<AnthropicSystemBlock>),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicSystemBlock {
     #[serde(rename = "type")]
     pub block_type: String,
@@ -92,15 +125,35 @@ pub struct AnthropicSystemBlock {
     pub text: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct AnthropicTool {
+/// An entry of `tools`: a custom tool with an input schema, or one of
+/// Anthropic's built-in tools (`computer_*`, `text_editor_*`, `bash_*`),
+/// which have a versioned `type` and no schema.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum AnthropicTool {
+    Custom(AnthropicCustomTool),
+    BuiltIn(AnthropicBuiltInTool),
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnthropicCustomTool {
     pub name: String,
     #[serde(default)]
     pub description: Option<String>,
     pub input_schema: Value,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnthropicBuiltInTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: String,
+    /// Tool options such as `display_width_px`.
+    #[serde(flatten)]
+    pub options: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicToolChoice {
     #[serde(rename = "type")]
     pub choice_type: String,
@@ -108,14 +161,14 @@ pub struct AnthropicToolChoice {
     pub name: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicOutputFormat {
     #[serde(rename = "type")]
     pub format_type: String,
     pub schema: Value,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AnthropicThinking {
     #[serde(rename = "type")]
     pub thinking_type: String,
@@ -123,7 +176,7 @@ pub struct AnthropicThinking {
     pub budget_tokens: Option<u32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AnthropicResponse {
     pub id: String,
     #[serde(rename = "type")]
@@ -136,29 +189,40 @@ pub struct AnthropicResponse {
     pub usage: AnthropicUsage,
 }
 
-#[derive(Debug, Serialize)]
+/// `service_tier` and any usage fields newer than this struct (e.g.
+/// `server_tool_use`) are kept in `extra` and passed through as is.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct AnthropicUsage {
+    #[serde(default)]
     pub input_tokens: u32,
+    #[serde(default)]
     pub output_tokens: u32,
+    #[serde(default)]
     pub cache_creation_input_tokens: u32,
+    #[serde(default)]
     pub cache_read_input_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    #[serde(flatten)]
+    #[schema(ignore)]
+    pub extra: serde_json::Map<String, Value>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AnthropicErrorResponse {
     #[serde(rename = "type")]
     pub response_type: String,
     pub error: AnthropicErrorBody,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AnthropicErrorBody {
     #[serde(rename = "type")]
     pub error_type: String,
     pub message: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIRequest {
     pub model: String,
     pub messages: Vec<OpenAIMessage>,
@@ -181,15 +245,21 @@ pub struct OpenAIRequest {
     pub reasoning_effort: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_options: Option<OpenAIStreamOptions>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+    /// Leading messages that carry the system prompt, whatever role
+    /// `models.system_role` gave them; context trimming keeps them.
+    #[serde(skip)]
+    pub system_messages: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIStreamOptions {
     #[serde(default)]
     pub include_usage: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIMessage {
     pub role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -202,14 +272,14 @@ pub struct OpenAIMessage {
     pub reasoning_content: Option<Value>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum OpenAIMessageContent {
     Text(String),
     Parts(Vec<OpenAIContentPart>),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum OpenAIContentPart {
     #[serde(rename = "text")]
@@ -218,21 +288,21 @@ pub enum OpenAIContentPart {
     ImageUrl { image_url: OpenAIImageUrl },
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIImageUrl {
     pub url: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub detail: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAITool {
     #[serde(rename = "type")]
     pub tool_type: String,
     pub function: OpenAIFunctionDef,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIFunctionDef {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -240,27 +310,29 @@ pub struct OpenAIFunctionDef {
     pub parameters: Value,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum OpenAIToolChoice {
     Mode(String),
     Tool(OpenAIToolChoiceFunction),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIToolChoiceFunction {
     #[serde(rename = "type")]
     pub choice_type: String,
     pub function: OpenAIToolChoiceName,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIToolChoiceName {
     pub name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIToolCall {
+    /// Empty when the downstream omits it; the translator issues a `toolu_` id.
+    #[serde(default)]
     pub id: String,
     #[serde(rename = "type")]
     pub call_type: String,
@@ -273,7 +345,7 @@ pub struct OpenAIToolCallFunction {
     pub arguments: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIResponseFormat {
     #[serde(rename = "type")]
     pub format_type: String,
@@ -281,7 +353,7 @@ pub struct OpenAIResponseFormat {
     pub json_schema: Option<OpenAIJsonSchema>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct OpenAIJsonSchema {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -292,11 +364,17 @@ pub struct OpenAIJsonSchema {
 
 #[derive(Debug, Deserialize)]
 pub struct OpenAIResponse {
+    /// Empty when the downstream omits it; see `translate::normalize_response`.
+    #[serde(default)]
     pub id: String,
+    #[serde(default)]
     pub model: String,
     pub choices: Vec<OpenAIChoice>,
     #[serde(default)]
     pub usage: Option<OpenAIUsage>,
+    /// Tier that served the request (`default`, `flex`, `priority`, ...).
+    #[serde(default)]
+    pub service_tier: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -315,12 +393,17 @@ pub struct OpenAIModel {
     pub owned_by: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CountTokensResponse {
+    pub input_tokens: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct AnthropicModelsResponse {
     pub data: Vec<AnthropicModel>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct AnthropicModel {
     pub id: String,
     #[serde(rename = "type")]
@@ -329,6 +412,27 @@ pub struct AnthropicModel {
     pub created_at: String,
 }
 
+/// A named, versioned prompt template. `{{name}}` placeholders in `system`
+/// and message contents are filled from the request's `prompt_variables`.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PromptTemplate {
+    pub id: String,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<PromptMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PromptMessage {
+    pub role: String,
+    pub content: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OpenAIChoice {
     pub message: OpenAIChoiceMessage,
@@ -340,14 +444,79 @@ pub struct OpenAIChoice {
 pub struct OpenAIChoiceMessage {
     pub role: String,
     #[serde(default)]
-    pub content: Option<String>,
+    pub content: Option<OpenAIResponseContent>,
     #[serde(default)]
     pub tool_calls: Option<Vec<OpenAIToolCall>>,
     #[serde(default)]
-    pub reasoning_content: Option<Value>,
+    pub reasoning_content: Option<OpenAIReasoning<OpenAIReasoningContent>>,
+    #[serde(default)]
+    pub annotations: Vec<OpenAIAnnotation>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum OpenAIResponseContent {
+    Text(String),
+    Parts(Vec<OpenAIResponseContentPart>),
+}
+
+impl OpenAIResponseContent {
+    /// Concatenated text of all text parts.
+    pub fn joined_text(&self) -> String {
+        match self {
+            OpenAIResponseContent::Text(text) => text.clone(),
+            OpenAIResponseContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| part.text.as_deref())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenAIResponseContentPart {
+    #[serde(rename = "type")]
+    pub part_type: String,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub annotations: Vec<OpenAIAnnotation>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenAIAnnotation {
+    #[serde(rename = "type")]
+    pub annotation_type: String,
+    #[serde(default)]
+    pub url_citation: Option<OpenAIUrlCitation>,
+    /// Gateway extension (`type: document_citation`) for providers that
+    /// cite request documents, e.g. Cohere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_citation: Option<OpenAIDocumentCitation>,
+}
+
+/// Character offsets into the text the citation applies to.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenAIUrlCitation {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// Character offsets into the text, and the cited document's position among
+/// the request's documents.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenAIDocumentCitation {
+    pub start_index: usize,
+    pub end_index: usize,
+    pub document_index: usize,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct OpenAIReasoningContent {
     #[serde(rename = "type")]
     pub kind: String,
@@ -355,6 +524,18 @@ pub struct OpenAIReasoningContent {
     pub signature: String,
 }
 
+/// `reasoning_content` of a response message (`T` = `OpenAIReasoningContent`)
+/// or a stream delta (`T` = `OpenAIReasoningContentDelta`): plain text or an
+/// object with the thinking text and its signature. Other shapes land in
+/// `Other` and are ignored by the translation.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum OpenAIReasoning<T> {
+    Text(String),
+    Structured(T),
+    Other(Value),
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OpenAIUsage {
     pub prompt_tokens: u32,
@@ -367,6 +548,10 @@ pub struct OpenAIStreamChunk {
     pub id: Option<String>,
     pub model: Option<String>,
     pub choices: Vec<OpenAIStreamChoice>,
+    #[serde(default)]
+    pub usage: Option<OpenAIUsage>,
+    #[serde(default)]
+    pub service_tier: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -375,6 +560,9 @@ pub struct OpenAIStreamChoice {
     pub delta: OpenAIStreamDelta,
     #[serde(default)]
     pub finish_reason: Option<String>,
+    /// vLLM extension: the stop string (or token id) that ended generation.
+    #[serde(default)]
+    pub stop_reason: Option<Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -386,7 +574,7 @@ pub struct OpenAIStreamDelta {
     #[serde(default)]
     pub tool_calls: Option<Vec<OpenAIToolCallDelta>>,
     #[serde(default)]
-    pub reasoning_content: Option<Value>,
+    pub reasoning_content: Option<OpenAIReasoning<OpenAIReasoningContentDelta>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -409,7 +597,7 @@ pub struct OpenAIToolCallFunctionDelta {
     pub arguments: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct OpenAIReasoningContentDelta {
     #[serde(default)]
     pub thinking: Option<String>,
@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::config::OAuthConfig;
+
+/// Bearer tokens for `downstream.oauth` (OAuth2 client-credentials grant).
+/// A token is reused until `refresh_margin_secs` before it expires; callers
+/// that get a 401 with it `invalidate` it so the next `token` fetches anew.
+#[derive(Clone)]
+pub struct OAuthTokens {
+    config: Arc<OAuthConfig>,
+    cached: Arc<Mutex<Option<CachedToken>>>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+impl OAuthTokens {
+    pub fn new(config: &OAuthConfig, client: reqwest::Client) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            cached: Arc::new(Mutex::new(None)),
+            client,
+        }
+    }
+
+    pub async fn token(&self) -> Result<String, String> {
+        let mut cached = self.cached.lock().await;
+        let margin = Duration::from_secs(self.config.refresh_margin_secs);
+        if let Some(token) = cached.as_ref()
+            && token.expires_at > Instant::now() + margin
+        {
+            return Ok(token.access_token.clone());
+        }
+        let fresh = self.fetch().await?;
+        let access_token = fresh.access_token.clone();
+        *cached = Some(fresh);
+        Ok(access_token)
+    }
+
+    /// Drops `token` after the downstream rejected it. A token another
+    /// request has already replaced is kept.
+    pub async fn invalidate(&self, token: &str) {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|cached| cached.access_token == token) {
+            *cached = None;
+        }
+    }
+
+    async fn fetch(&self) -> Result<CachedToken, String> {
+        let config = self.config.as_ref();
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = config.scope.as_deref() {
+            form.push(("scope", scope));
+        }
+        if let Some(audience) = config.audience.as_deref() {
+            form.push(("audience", audience));
+        }
+        let mut request = self.client.post(&config.token_url);
+        if config.auth_method == "post" {
+            form.push(("client_id", &config.client_id));
+            form.push(("client_secret", &config.client_secret));
+        } else {
+            request = request.basic_auth(&config.client_id, Some(&config.client_secret));
+        }
+        let requested = Instant::now();
+        let resp = request
+            .form(&form)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| format!("oauth token request failed: {}", e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("oauth token request failed: {} {}", status, text));
+        }
+        let body: TokenResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("oauth token response invalid: {}", e))?;
+        Ok(CachedToken {
+            access_token: body.access_token,
+            expires_at: requested + Duration::from_secs(body.expires_in.unwrap_or(3600)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::{routing::post, Form, Json, Router};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    async fn caches_tokens_and_refetches_after_invalidate() {
+        let issued = Arc::new(AtomicU64::new(0));
+        let app = Router::new()
+            .route(
+                "/token",
+                post(
+                    |State(issued): State<Arc<AtomicU64>>,
+                     headers: HeaderMap,
+                     Form(form): Form<HashMap<String, String>>| async move {
+                        assert_eq!(form["grant_type"], "client_credentials");
+                        assert_eq!(form["scope"], "models.invoke");
+                        assert!(headers.get("authorization").unwrap().to_str().unwrap().starts_with("Basic "));
+                        let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                        Json(serde_json::json!({"access_token": format!("tok-{}", n), "expires_in": 3600}))
+                    },
+                ),
+            )
+            .with_state(issued.clone());
+        let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(err) => panic!("bind failed: {}", err),
+        };
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config: OAuthConfig = serde_yaml::from_str(&format!(
+            "token_url: http://{}/token\nclient_id: gateway\nclient_secret: s3cret\nscope: models.invoke\n",
+            addr
+        ))
+        .expect("config");
+        let tokens = OAuthTokens::new(&config, reqwest::Client::new());
+        assert_eq!(tokens.token().await.unwrap(), "tok-1");
+        assert_eq!(tokens.token().await.unwrap(), "tok-1");
+        tokens.invalidate("tok-0").await;
+        assert_eq!(tokens.token().await.unwrap(), "tok-1");
+        tokens.invalidate("tok-1").await;
+        assert_eq!(tokens.token().await.unwrap(), "tok-2");
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+    }
+}
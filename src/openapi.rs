@@ -0,0 +1,79 @@
+use axum::Json;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::{admin, dashboard, handlers, models};
+
+/// The gateway's ingress API: the Anthropic-compatible routes, prompts and
+/// the admin API. Schemas come from the `models` types.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "llm-gateway", description = "Anthropic-compatible LLM gateway"),
+    paths(
+        handlers::post_messages,
+        handlers::count_tokens,
+        handlers::get_models,
+        handlers::list_prompts,
+        handlers::get_prompt,
+        handlers::publish_prompt,
+        handlers::health,
+        admin::get_usage,
+        admin::get_tap,
+        admin::get_transcript,
+        admin::get_inflight,
+        admin::cancel_inflight,
+        admin::get_downstream_keys,
+        admin::disable_downstream_key,
+        admin::enable_downstream_key,
+        admin::flush_downstream_pool,
+        admin::get_debug_keys,
+        admin::flag_debug_key,
+        admin::unflag_debug_key,
+        dashboard::get_stats,
+        dashboard::get_recent_requests,
+    ),
+    components(schemas(
+        models::AnthropicRequest,
+        models::AnthropicResponse,
+        models::AnthropicErrorResponse,
+        models::AnthropicModelsResponse,
+        models::CountTokensResponse,
+        models::PromptTemplate,
+    )),
+    modifiers(&AdminToken),
+)]
+pub struct ApiDoc;
+
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// `GET /openapi.json`, for client code generation.
+pub async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_ingress_and_admin_routes() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        for path in ["/v1/messages", "/v1/messages/count_tokens", "/v1/models", "/admin/usage"] {
+            assert!(doc["paths"].get(path).is_some(), "{}", path);
+        }
+        let request = &doc["components"]["schemas"]["AnthropicRequest"];
+        assert!(request["required"].as_array().unwrap().contains(&"messages".into()));
+        assert!(doc["components"]["schemas"].get("AnthropicContentBlock").is_some());
+        assert!(doc["components"]["securitySchemes"].get("admin_token").is_some());
+    }
+}
@@ -0,0 +1,235 @@
+/// Incremental JSON syntax check for streamed tool arguments. Chunks are fed
+/// as they arrive and the first byte that no continuation could make valid
+/// is reported (a mismatched bracket, a stray character, a broken literal),
+/// so a stream can fail before the whole argument string is buffered.
+/// Incomplete but still repairable input is not an error; `is_complete`
+/// tells whether a full value has been seen.
+#[derive(Debug, Default)]
+pub struct PartialJson {
+    stack: Vec<Container>,
+    expect: Expect,
+    token: Token,
+    offset: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Expect {
+    #[default]
+    Value,
+    /// After `[`: a value or `]`.
+    ValueOrEnd,
+    /// After `{`: a key or `}`.
+    KeyOrEnd,
+    /// After `,` in an object.
+    Key,
+    Colon,
+    CommaOrEnd,
+    Done,
+}
+
+#[derive(Debug, Default)]
+enum Token {
+    #[default]
+    None,
+    String {
+        key: bool,
+        escape: bool,
+        unicode: u8,
+    },
+    Number(String),
+    Literal(String),
+}
+
+const LITERALS: [&str; 3] = ["true", "false", "null"];
+
+impl PartialJson {
+    /// Feeds the next chunk; errors name the byte offset and what went
+    /// wrong.
+    pub fn feed(&mut self, chunk: &str) -> Result<(), String> {
+        for c in chunk.chars() {
+            self.push(c).map_err(|reason| format!("{} at byte {}", reason, self.offset))?;
+            self.offset += c.len_utf8();
+        }
+        Ok(())
+    }
+
+    /// Whether a complete top-level value has been read. A trailing number
+    /// counts once it parses.
+    pub fn is_complete(&self) -> bool {
+        match &self.token {
+            Token::Number(number) => self.stack.is_empty() && valid_number(number),
+            Token::None => self.expect == Expect::Done,
+            _ => false,
+        }
+    }
+
+    fn push(&mut self, c: char) -> Result<(), String> {
+        match &mut self.token {
+            Token::String { key, escape, unicode } => {
+                if *unicode > 0 {
+                    if !c.is_ascii_hexdigit() {
+                        return Err(format!("invalid unicode escape {:?}", c));
+                    }
+                    *unicode -= 1;
+                } else if *escape {
+                    match c {
+                        'u' => *unicode = 4,
+                        '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => {}
+                        _ => return Err(format!("invalid escape \\{}", c)),
+                    }
+                    *escape = false;
+                } else if c == '\\' {
+                    *escape = true;
+                } else if c == '"' {
+                    let key = *key;
+                    self.token = Token::None;
+                    self.expect = if key { Expect::Colon } else { self.after_value() };
+                } else if (c as u32) < 0x20 {
+                    return Err("unescaped control character in string".to_string());
+                }
+                return Ok(());
+            }
+            Token::Number(number) => {
+                if matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+                    number.push(c);
+                    return Ok(());
+                }
+                if !valid_number(number) {
+                    return Err(format!("invalid number {:?}", number));
+                }
+                self.token = Token::None;
+                self.expect = self.after_value();
+            }
+            Token::Literal(literal) => {
+                if c.is_ascii_alphabetic() {
+                    literal.push(c);
+                    if !LITERALS.iter().any(|l| l.starts_with(literal.as_str())) {
+                        return Err(format!("invalid literal {:?}", literal));
+                    }
+                    return Ok(());
+                }
+                if !LITERALS.contains(&literal.as_str()) {
+                    return Err(format!("invalid literal {:?}", literal));
+                }
+                self.token = Token::None;
+                self.expect = self.after_value();
+            }
+            Token::None => {}
+        }
+
+        if c.is_ascii_whitespace() {
+            return Ok(());
+        }
+        match self.expect {
+            Expect::Value | Expect::ValueOrEnd => match c {
+                ']' if self.expect == Expect::ValueOrEnd => self.close(Container::Array)?,
+                '{' => {
+                    self.stack.push(Container::Object);
+                    self.expect = Expect::KeyOrEnd;
+                }
+                '[' => {
+                    self.stack.push(Container::Array);
+                    self.expect = Expect::ValueOrEnd;
+                }
+                '"' => {
+                    self.token = Token::String {
+                        key: false,
+                        escape: false,
+                        unicode: 0,
+                    }
+                }
+                '-' | '0'..='9' => self.token = Token::Number(c.to_string()),
+                't' | 'f' | 'n' => self.token = Token::Literal(c.to_string()),
+                _ => return Err(format!("expected a value but found {:?}", c)),
+            },
+            Expect::KeyOrEnd | Expect::Key => match c {
+                '}' if self.expect == Expect::KeyOrEnd => self.close(Container::Object)?,
+                '"' => {
+                    self.token = Token::String {
+                        key: true,
+                        escape: false,
+                        unicode: 0,
+                    }
+                }
+                _ => return Err(format!("expected an object key but found {:?}", c)),
+            },
+            Expect::Colon => match c {
+                ':' => self.expect = Expect::Value,
+                _ => return Err(format!("expected ':' but found {:?}", c)),
+            },
+            Expect::CommaOrEnd => match (c, self.stack.last()) {
+                (',', Some(Container::Object)) => self.expect = Expect::Key,
+                (',', Some(Container::Array)) => self.expect = Expect::Value,
+                ('}', _) => self.close(Container::Object)?,
+                (']', _) => self.close(Container::Array)?,
+                _ => return Err(format!("expected ',' or a closing bracket but found {:?}", c)),
+            },
+            Expect::Done => return Err(format!("unexpected {:?} after the end of the value", c)),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self, container: Container) -> Result<(), String> {
+        match self.stack.pop() {
+            Some(open) if open == container => {
+                self.expect = self.after_value();
+                Ok(())
+            }
+            Some(open) => Err(format!(
+                "mismatched {:?} closing an open {}",
+                if container == Container::Object { '}' } else { ']' },
+                if open == Container::Object { "object" } else { "array" }
+            )),
+            None => Err("closing bracket without an open container".to_string()),
+        }
+    }
+
+    fn after_value(&self) -> Expect {
+        if self.stack.is_empty() {
+            Expect::Done
+        } else {
+            Expect::CommaOrEnd
+        }
+    }
+}
+
+fn valid_number(number: &str) -> bool {
+    serde_json::from_str::<serde_json::Number>(number).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(chunks: &[&str]) -> Result<PartialJson, String> {
+        let mut json = PartialJson::default();
+        for chunk in chunks {
+            json.feed(chunk)?;
+        }
+        Ok(json)
+    }
+
+    #[test]
+    fn accepts_valid_prefixes_and_rejects_irrecoverable_input() {
+        let json = feed_all(&["{\"loc", "ation\": \"北\\u4eac\\n\", \"n\": [1, -2.5e3, tr", "ue, null]}"]).unwrap();
+        assert!(json.is_complete());
+        let partial = feed_all(&["{\"a\": [1, {\"b\": fal"]).unwrap();
+        assert!(!partial.is_complete());
+        assert!(feed_all(&["12"]).unwrap().is_complete());
+
+        let err = feed_all(&["{\"a\": [1, 2}"]).unwrap_err();
+        assert_eq!(err, "mismatched '}' closing an open array at byte 11");
+        assert!(feed_all(&["{\"a\" 1}"]).unwrap_err().starts_with("expected ':'"));
+        assert!(feed_all(&["{\"a\": tru", "th}"]).unwrap_err().starts_with("invalid literal"));
+        assert!(feed_all(&["{\"a\": 1.2.3,"]).unwrap_err().starts_with("invalid number"));
+        assert!(feed_all(&["{}", " {}"]).unwrap_err().starts_with("unexpected '{'"));
+        assert!(feed_all(&["{\"a\": \"\\x\"}"]).unwrap_err().starts_with("invalid escape"));
+        assert!(feed_all(&["{,"]).unwrap_err().starts_with("expected an object key"));
+    }
+}
@@ -0,0 +1,388 @@
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::{Postgres, QueryBuilder, Row};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::access_log::RequestSummary;
+use crate::audit_log::{AuditLogRecord, AuditQueue};
+use crate::config::PostgresConfig;
+
+const WRITE_BATCH_SIZE: usize = 256;
+/// Rows per usage query; a report pages through its window in these.
+const USAGE_PAGE_SIZE: usize = 1_000;
+
+/// Shared Postgres pool for audit records and usage rows. The schema is
+/// embedded and migrated on connect.
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    max_usage_rows: usize,
+}
+
+/// The last row of a usage page; the next page starts below it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UsageCursor {
+    pub ts_ms: u128,
+    pub request_id: String,
+}
+
+impl PgStore {
+    pub async fn connect(config: &PostgresConfig) -> Result<Self, String> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(5))
+            .connect(&config.url)
+            .await
+            .map_err(|e| format!("postgres connect error: {}", e))?;
+        sqlx::migrate!("migrations/postgres")
+            .run(&pool)
+            .await
+            .map_err(|e| format!("postgres migrate error: {}", e))?;
+        Ok(Self {
+            pool,
+            max_usage_rows: config.max_usage_rows,
+        })
+    }
+
+    pub async fn insert_usage_batch(&self, batch: &[RequestSummary]) -> Result<(), sqlx::Error> {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO request_summaries (request_id, ts_ms, route, method, mode, key_id, \
+             model, stream, status, error_type, input_tokens, output_tokens, cache_hit, cost_usd, \
+             latency_ms, ttfb_ms, downstream_endpoint) ",
+        );
+        builder.push_values(batch, |mut row, summary| {
+            row.push_bind(summary.request_id.clone())
+                .push_bind(summary.ts_ms as i64)
+                .push_bind(summary.route.clone())
+                .push_bind(summary.method.clone())
+                .push_bind(summary.mode.clone())
+                .push_bind(summary.key_id.clone())
+                .push_bind(summary.model.clone())
+                .push_bind(summary.stream)
+                .push_bind(summary.status as i32)
+                .push_bind(summary.error_type.clone())
+                .push_bind(summary.input_tokens.map(|v| v as i64))
+                .push_bind(summary.output_tokens.map(|v| v as i64))
+                .push_bind(summary.cache_hit)
+                .push_bind(summary.cost_usd)
+                .push_bind(summary.latency_ms as i64)
+                .push_bind(summary.ttfb_ms.map(|v| v as i64))
+                .push_bind(summary.downstream_endpoint.clone());
+        });
+        builder.build().execute(&self.pool).await.map(|_| ())
+    }
+
+    pub async fn insert_audit_batch(&self, batch: &[Arc<AuditLogRecord>]) -> Result<(), sqlx::Error> {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO audit_records (request_id, ts_start_ms, ts_end_ms, route, mode, method, \
+             status, model, request, response, meta) ",
+        );
+        builder.push_values(batch, |mut row, record| {
+            row.push_bind(record.request_id.clone())
+                .push_bind(record.ts_start_ms as i64)
+                .push_bind(record.ts_end_ms as i64)
+                .push_bind(record.route.clone())
+                .push_bind(record.mode.clone())
+                .push_bind(record.method.clone())
+                .push_bind(record.response.status as i32)
+                .push_bind(record.meta.model.clone())
+                .push_bind(json_text(&record.request))
+                .push_unseparated("::jsonb")
+                .push_bind(json_text(&record.response))
+                .push_unseparated("::jsonb")
+                .push_bind(json_text(&record.meta))
+                .push_unseparated("::jsonb");
+        });
+        builder.build().execute(&self.pool).await.map(|_| ())
+    }
+
+    /// Usage rows at or after `ts_ms`, oldest first, read in pages of
+    /// `USAGE_PAGE_SIZE` and capped at `postgres.max_usage_rows` (the newest
+    /// are kept).
+    pub async fn usage_since(&self, ts_ms: u128) -> Result<Vec<RequestSummary>, String> {
+        let (rows, capped) = read_pages(self.max_usage_rows, USAGE_PAGE_SIZE, |before, limit| {
+            self.usage_page(ts_ms, before, limit)
+        })
+        .await?;
+        if capped {
+            tracing::warn!(
+                max_usage_rows = self.max_usage_rows,
+                "usage window holds more rows than postgres.max_usage_rows, older rows left out"
+            );
+        }
+        Ok(rows)
+    }
+
+    /// Up to `limit` rows at or after `ts_ms`, newest first, starting below
+    /// `before`.
+    pub async fn usage_page(
+        &self,
+        ts_ms: u128,
+        before: Option<UsageCursor>,
+        limit: usize,
+    ) -> Result<Vec<RequestSummary>, String> {
+        let rows = usage_page_query(ts_ms, before, limit)
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("postgres usage query error: {}", e))?;
+        Ok(rows.iter().map(summary_from_row).collect())
+    }
+}
+
+fn usage_page_query(ts_ms: u128, before: Option<UsageCursor>, limit: usize) -> QueryBuilder<'static, Postgres> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT request_id, ts_ms, route, method, mode, key_id, model, stream, status, \
+         error_type, input_tokens, output_tokens, cache_hit, cost_usd, latency_ms, ttfb_ms, \
+         downstream_endpoint FROM request_summaries WHERE ts_ms >= ",
+    );
+    builder.push_bind(ts_ms as i64);
+    if let Some(cursor) = before {
+        builder
+            .push(" AND (ts_ms, request_id) < (")
+            .push_bind(cursor.ts_ms as i64)
+            .push(", ")
+            .push_bind(cursor.request_id)
+            .push(")");
+    }
+    builder
+        .push(" ORDER BY ts_ms DESC, request_id DESC LIMIT ")
+        .push_bind(limit as i64);
+    builder
+}
+
+/// Fetches pages newest first until one comes back short or `max_rows` are
+/// read. Returns the rows oldest first and whether `max_rows` cut the
+/// window short.
+async fn read_pages<F, Fut>(max_rows: usize, page_size: usize, mut fetch: F) -> Result<(Vec<RequestSummary>, bool), String>
+where
+    F: FnMut(Option<UsageCursor>, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<RequestSummary>, String>>,
+{
+    let mut rows: Vec<RequestSummary> = Vec::new();
+    let mut before = None;
+    loop {
+        let limit = page_size.min(max_rows - rows.len());
+        if limit == 0 {
+            rows.reverse();
+            return Ok((rows, true));
+        }
+        let page = fetch(before, limit).await?;
+        let short = page.len() < limit;
+        rows.extend(page);
+        match rows.last() {
+            Some(last) if !short => {
+                before = Some(UsageCursor {
+                    ts_ms: last.ts_ms,
+                    request_id: last.request_id.clone(),
+                })
+            }
+            _ => {
+                rows.reverse();
+                return Ok((rows, false));
+            }
+        }
+    }
+}
+
+fn summary_from_row(row: &PgRow) -> RequestSummary {
+    RequestSummary {
+        ts_ms: row.get::<i64, _>("ts_ms") as u128,
+        request_id: row.get("request_id"),
+        route: row.get("route"),
+        method: row.get("method"),
+        mode: row.get("mode"),
+        key_id: row.get("key_id"),
+        model: row.get("model"),
+        stream: row.get("stream"),
+        status: row.get::<i32, _>("status") as u16,
+        error_type: row.get("error_type"),
+        input_tokens: row.get::<Option<i64>, _>("input_tokens").map(|v| v as u64),
+        output_tokens: row.get::<Option<i64>, _>("output_tokens").map(|v| v as u64),
+        cache_hit: row.get("cache_hit"),
+        service_tier: None,
+        cost_usd: row.get("cost_usd"),
+        latency_ms: row.get::<i64, _>("latency_ms") as u64,
+        ttfb_ms: row.get::<Option<i64>, _>("ttfb_ms").map(|v| v as u64),
+        downstream_start: None,
+        downstream_ttfb_ms: None,
+        downstream_ms: None,
+        downstream_endpoint: row.get("downstream_endpoint"),
+        experiment: None,
+        trace_id: None,
+        span_id: None,
+        prompt_text: None,
+        output_text: None,
+    }
+}
+
+/// Batches usage rows onto the shared pool from a background task.
+#[derive(Clone)]
+pub struct PgUsageStore {
+    store: PgStore,
+    sender: mpsc::Sender<RequestSummary>,
+}
+
+impl PgUsageStore {
+    pub fn new(store: PgStore) -> Self {
+        let (tx, mut rx) = mpsc::channel::<RequestSummary>(4096);
+        let writer = store.clone();
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+            while rx.recv_many(&mut batch, WRITE_BATCH_SIZE).await > 0 {
+                if let Err(err) = writer.insert_usage_batch(&batch).await {
+                    tracing::error!("postgres usage write error: {}", err);
+                }
+                batch.clear();
+            }
+        });
+        Self { store, sender: tx }
+    }
+
+    pub fn record(&self, summary: &RequestSummary) {
+        if self.sender.try_send(summary.clone()).is_err() {
+            tracing::warn!(request_id = %summary.request_id, "postgres usage channel full, record dropped");
+        }
+    }
+
+    pub async fn since(&self, ts_ms: u128) -> Result<Vec<RequestSummary>, String> {
+        self.store.usage_since(ts_ms).await
+    }
+}
+
+/// Writes queued audit records in batches until the logger closes.
+pub async fn write_audit_records(store: PgStore, queue: Arc<AuditQueue>) {
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+    while queue.recv_many(&mut batch, WRITE_BATCH_SIZE).await > 0 {
+        if let Err(err) = store.insert_audit_batch(&batch).await {
+            tracing::error!("postgres audit write error: {}", err);
+        }
+        queue.commit();
+        batch.clear();
+    }
+}
+
+fn json_text<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use std::sync::Mutex;
+
+    fn summary(request_id: &str, ts_ms: u128) -> RequestSummary {
+        let mut summary = RequestSummary::new(request_id, "/v1/messages", "POST", "translate", &HeaderMap::new());
+        summary.ts_ms = ts_ms;
+        summary.input_tokens = Some(3);
+        summary
+    }
+
+    /// Serves pages from `rows` (newest first) the way `usage_page` does and
+    /// records the cursor and limit of every call.
+    async fn paged(
+        rows: &[RequestSummary],
+        max_rows: usize,
+        page_size: usize,
+    ) -> (Vec<RequestSummary>, bool, Vec<(Option<UsageCursor>, usize)>) {
+        let calls = Mutex::new(Vec::new());
+        let (read, capped) = read_pages(max_rows, page_size, |before, limit| {
+            calls.lock().unwrap().push((before.clone(), limit));
+            let page: Vec<RequestSummary> = rows
+                .iter()
+                .rev()
+                .filter(|row| {
+                    before
+                        .as_ref()
+                        .is_none_or(|c| (row.ts_ms, row.request_id.as_str()) < (c.ts_ms, c.request_id.as_str()))
+                })
+                .take(limit)
+                .cloned()
+                .collect();
+            async move { Ok(page) }
+        })
+        .await
+        .expect("pages");
+        (read, capped, calls.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn reads_the_window_in_pages_and_keeps_the_newest_rows() {
+        // Two rows share each timestamp, so the cursor needs the request id.
+        let rows: Vec<RequestSummary> = (0..25).map(|i| summary(&format!("req-{:02}", i), 1_000 + i / 2)).collect();
+
+        let (read, capped, calls) = paged(&rows, 100, 10).await;
+        assert!(!capped);
+        let ids = |rows: &[RequestSummary]| rows.iter().map(|r| r.request_id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&read), ids(&rows));
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0], (None, 10));
+        assert_eq!(
+            calls[1].0,
+            Some(UsageCursor {
+                ts_ms: 1_007,
+                request_id: "req-15".to_string()
+            })
+        );
+
+        // An exact multiple of the page size needs one more, empty, page.
+        let (read, capped, calls) = paged(&rows[..20], 100, 10).await;
+        assert_eq!((read.len(), capped, calls.len()), (20, false, 3));
+
+        let (read, capped, calls) = paged(&rows, 15, 10).await;
+        assert!(capped);
+        assert_eq!(read.len(), 15);
+        assert_eq!(read.first().unwrap().request_id, "req-10");
+        assert_eq!(read.last().unwrap().request_id, "req-24");
+        assert_eq!(calls.iter().map(|(_, limit)| *limit).collect::<Vec<_>>(), vec![10, 5]);
+    }
+
+    #[test]
+    fn usage_page_query_uses_a_keyset_cursor_and_a_limit() {
+        let first = usage_page_query(1_000, None, 50);
+        assert!(
+            first.sql().ends_with("WHERE ts_ms >= $1 ORDER BY ts_ms DESC, request_id DESC LIMIT $2"),
+            "{}",
+            first.sql()
+        );
+        let next = usage_page_query(
+            1_000,
+            Some(UsageCursor {
+                ts_ms: 2_000,
+                request_id: "req-1".to_string(),
+            }),
+            50,
+        );
+        assert!(
+            next.sql().ends_with(
+                "WHERE ts_ms >= $1 AND (ts_ms, request_id) < ($2, $3) ORDER BY ts_ms DESC, request_id DESC LIMIT $4"
+            ),
+            "{}",
+            next.sql()
+        );
+    }
+
+    /// Round trip against a real database; set `LLM_GATEWAY_TEST_POSTGRES_URL`
+    /// to run it.
+    #[tokio::test]
+    async fn writes_and_pages_usage_rows_in_postgres() {
+        let Ok(url) = std::env::var("LLM_GATEWAY_TEST_POSTGRES_URL") else {
+            return;
+        };
+        let config: PostgresConfig =
+            serde_yaml::from_str(&format!("url: \"{}\"\nmax_usage_rows: 3\n", url)).expect("config");
+        let store = PgStore::connect(&config).await.expect("connect");
+        let since = crate::audit_log::now_ms() + 1_000_000;
+        let prefix = format!("pg-test-{}", std::process::id());
+        let batch: Vec<RequestSummary> = (0..5).map(|i| summary(&format!("{}-{}", prefix, i), since + i)).collect();
+        store.insert_usage_batch(&batch).await.expect("insert");
+
+        let rows = store.usage_since(since).await.expect("query");
+        let ids: Vec<String> = rows.iter().map(|r| r.request_id.clone()).collect();
+        assert_eq!(ids, (2..5).map(|i| format!("{}-{}", prefix, i)).collect::<Vec<_>>());
+        assert_eq!(rows[0].input_tokens, Some(3));
+    }
+}
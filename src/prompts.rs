@@ -0,0 +1,360 @@
+use serde_json::{Map, Value};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audit_log::now_ms;
+use crate::config::PromptsConfig;
+use crate::error::AppError;
+use crate::models::PromptTemplate;
+
+/// Request extension fields; stripped before the request is forwarded.
+const PROMPT_ID_FIELD: &str = "prompt_id";
+const PROMPT_VERSION_FIELD: &str = "prompt_version";
+const PROMPT_VARIABLES_FIELD: &str = "prompt_variables";
+
+#[derive(Clone)]
+enum PromptBackend {
+    Config(Arc<Vec<PromptTemplate>>),
+    Sqlite(SqlitePool),
+}
+
+/// Named, versioned prompt templates, either fixed by the config file or
+/// stored in SQLite (seeded from the config file).
+#[derive(Clone)]
+pub struct PromptRegistry {
+    backend: PromptBackend,
+}
+
+impl Default for PromptRegistry {
+    fn default() -> Self {
+        Self::from_templates(Vec::new())
+    }
+}
+
+impl PromptRegistry {
+    pub fn from_templates(templates: Vec<PromptTemplate>) -> Self {
+        Self {
+            backend: PromptBackend::Config(Arc::new(templates)),
+        }
+    }
+
+    pub async fn open(config: &PromptsConfig) -> Result<Self, String> {
+        let Some(path) = config.sqlite_path.as_deref() else {
+            return Ok(Self::from_templates(config.templates.clone()));
+        };
+        if let Some(parent) = std::path::Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("prompts sqlite dir error: {}", e))?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .map_err(|e| format!("prompts sqlite open error: {}", e))?;
+        sqlx::migrate!("migrations/prompts")
+            .run(&pool)
+            .await
+            .map_err(|e| format!("prompts sqlite migrate error: {}", e))?;
+        for template in &config.templates {
+            sqlx::query(
+                "INSERT OR IGNORE INTO prompt_templates (id, version, template, created_ms) \
+                 VALUES (?, ?, ?, ?)",
+            )
+            .bind(&template.id)
+            .bind(template.version as i64)
+            .bind(serde_json::to_string(template).unwrap_or_default())
+            .bind(now_ms() as i64)
+            .execute(&pool)
+            .await
+            .map_err(|e| format!("prompts sqlite seed error: {}", e))?;
+        }
+        Ok(Self {
+            backend: PromptBackend::Sqlite(pool),
+        })
+    }
+
+    /// All templates, every version, ordered by id then version.
+    pub async fn list(&self) -> Result<Vec<PromptTemplate>, String> {
+        match &self.backend {
+            PromptBackend::Config(templates) => {
+                let mut templates = templates.as_ref().clone();
+                templates.sort_by(|a, b| a.id.cmp(&b.id).then(a.version.cmp(&b.version)));
+                Ok(templates)
+            }
+            PromptBackend::Sqlite(pool) => {
+                let rows = sqlx::query(
+                    "SELECT version, template FROM prompt_templates ORDER BY id, version",
+                )
+                .fetch_all(pool)
+                .await
+                .map_err(|e| format!("prompts sqlite query error: {}", e))?;
+                rows.into_iter().map(|row| template_from_row(&row)).collect()
+            }
+        }
+    }
+
+    /// The requested version of `id`, or its latest version.
+    pub async fn get(&self, id: &str, version: Option<u32>) -> Result<Option<PromptTemplate>, String> {
+        match &self.backend {
+            PromptBackend::Config(templates) => Ok(templates
+                .iter()
+                .filter(|t| t.id == id && version.is_none_or(|v| t.version == v))
+                .max_by_key(|t| t.version)
+                .cloned()),
+            PromptBackend::Sqlite(pool) => {
+                let row = sqlx::query(
+                    "SELECT version, template FROM prompt_templates \
+                     WHERE id = ? AND (? IS NULL OR version = ?) ORDER BY version DESC LIMIT 1",
+                )
+                .bind(id)
+                .bind(version.map(i64::from))
+                .bind(version.map(i64::from))
+                .fetch_optional(pool)
+                .await
+                .map_err(|e| format!("prompts sqlite query error: {}", e))?;
+                row.map(|row| template_from_row(&row)).transpose()
+            }
+        }
+    }
+
+    /// Stores `template` as the next version of its id. Only the SQLite
+    /// backend is writable.
+    pub async fn publish(&self, mut template: PromptTemplate) -> Result<PromptTemplate, String> {
+        let PromptBackend::Sqlite(pool) = &self.backend else {
+            return Err("prompt registry is read-only; set prompts.sqlite_path".to_string());
+        };
+        let row = sqlx::query(
+            "INSERT INTO prompt_templates (id, version, template, created_ms) \
+             SELECT ?, COALESCE(MAX(version), 0) + 1, ?, ? FROM prompt_templates WHERE id = ? \
+             RETURNING version",
+        )
+        .bind(&template.id)
+        .bind(serde_json::to_string(&template).unwrap_or_default())
+        .bind(now_ms() as i64)
+        .bind(&template.id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| format!("prompts sqlite insert error: {}", e))?;
+        template.version = row.get::<i64, _>("version") as u32;
+        Ok(template)
+    }
+
+    /// Renders the template referenced by `prompt_id` into `payload`: the
+    /// template system prompt goes before the request's own, and template
+    /// messages are prepended to the request messages. Returns the rendered
+    /// template as `id@version`, or `None` when the request references none.
+    pub async fn render_request(&self, payload: &mut Value) -> Result<Option<String>, AppError> {
+        let Some(obj) = payload.as_object_mut() else {
+            return Ok(None);
+        };
+        let Some(id) = obj.remove(PROMPT_ID_FIELD) else {
+            return Ok(None);
+        };
+        let version = obj.remove(PROMPT_VERSION_FIELD);
+        let variables = obj.remove(PROMPT_VARIABLES_FIELD);
+        let id = id
+            .as_str()
+            .ok_or_else(|| AppError::invalid_request("prompt_id must be a string"))?;
+        let version = match version {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(
+                v.as_u64()
+                    .and_then(|v| u32::try_from(v).ok())
+                    .ok_or_else(|| AppError::invalid_request("prompt_version must be a positive integer"))?,
+            ),
+        };
+        let variables = match variables {
+            None | Some(Value::Null) => Map::new(),
+            Some(Value::Object(map)) => map,
+            Some(_) => return Err(AppError::invalid_request("prompt_variables must be an object")),
+        };
+        let template = self
+            .get(id, version)
+            .await
+            .map_err(AppError::api_error)?
+            .ok_or_else(|| AppError::not_found(format!("prompt not found: {}", id)))?;
+        let (system, messages) = render(&template, &variables).map_err(AppError::invalid_request)?;
+
+        if let Some(system) = system {
+            let merged = match obj.remove("system") {
+                None | Some(Value::Null) => Value::String(system),
+                Some(Value::String(own)) if own.is_empty() => Value::String(system),
+                Some(Value::String(own)) => Value::String(format!("{}\n\n{}", system, own)),
+                Some(Value::Array(mut blocks)) => {
+                    blocks.insert(0, serde_json::json!({"type": "text", "text": system}));
+                    Value::Array(blocks)
+                }
+                Some(_) => return Err(AppError::invalid_request("system must be a string or array")),
+            };
+            obj.insert("system".to_string(), merged);
+        }
+        let mut all = messages;
+        match obj.remove("messages") {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(own)) => all.extend(own),
+            Some(_) => return Err(AppError::invalid_request("messages must be an array")),
+        }
+        obj.insert("messages".to_string(), Value::Array(all));
+        Ok(Some(format!("{}@{}", template.id, template.version)))
+    }
+}
+
+fn template_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<PromptTemplate, String> {
+    let mut template: PromptTemplate = serde_json::from_str(row.get("template"))
+        .map_err(|e| format!("prompts sqlite decode error: {}", e))?;
+    template.version = row.get::<i64, _>("version") as u32;
+    Ok(template)
+}
+
+/// Placeholder names used by a template, in order of first use.
+pub fn template_variables(template: &PromptTemplate) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let texts = template
+        .system
+        .iter()
+        .chain(template.messages.iter().map(|m| &m.content));
+    for text in texts {
+        for name in placeholders(text) {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Renders the template system prompt and messages (as Anthropic messages).
+pub fn render(
+    template: &PromptTemplate,
+    variables: &Map<String, Value>,
+) -> Result<(Option<String>, Vec<Value>), String> {
+    let system = template
+        .system
+        .as_deref()
+        .map(|s| substitute(s, variables))
+        .transpose()?;
+    let messages = template
+        .messages
+        .iter()
+        .map(|m| {
+            substitute(&m.content, variables)
+                .map(|content| serde_json::json!({"role": m.role, "content": content}))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((system, messages))
+}
+
+fn placeholders(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let start = rest.find("{{")?;
+        let end = rest[start + 2..].find("}}")?;
+        let name = rest[start + 2..start + 2 + end].trim();
+        rest = &rest[start + 4 + end..];
+        Some(name)
+    })
+}
+
+fn substitute(text: &str, variables: &Map<String, Value>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + end].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| format!("missing prompt variable: {}", name))?;
+        out.push_str(&rest[..start]);
+        match value {
+            Value::String(s) => out.push_str(s),
+            other => out.push_str(&other.to_string()),
+        }
+        rest = &rest[start + 4 + end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PromptMessage;
+    use serde_json::json;
+
+    fn template(version: u32, system: &str) -> PromptTemplate {
+        PromptTemplate {
+            id: "triage".to_string(),
+            version,
+            description: None,
+            system: Some(system.to_string()),
+            messages: vec![PromptMessage {
+                role: "user".to_string(),
+                content: "Ticket: {{ ticket }}".to_string(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn renders_latest_template_into_request() {
+        let registry = PromptRegistry::from_templates(vec![
+            template(1, "old"),
+            template(2, "You support {{company}}."),
+        ]);
+        let mut payload = json!({
+            "model": "kimi-k2.5",
+            "system": "Be brief.",
+            "prompt_id": "triage",
+            "prompt_variables": {"company": "Acme", "ticket": "login fails"},
+            "messages": [{"role": "assistant", "content": "ok"}]
+        });
+        let rendered = registry.render_request(&mut payload).await.unwrap();
+        assert_eq!(rendered.as_deref(), Some("triage@2"));
+        assert_eq!(payload["system"], "You support Acme.\n\nBe brief.");
+        assert_eq!(payload["messages"][0]["content"], "Ticket: login fails");
+        assert_eq!(payload["messages"][1]["role"], "assistant");
+        assert!(payload.get("prompt_id").is_none());
+        assert!(payload.get("prompt_variables").is_none());
+
+        let mut missing = json!({"prompt_id": "triage", "prompt_version": 1, "messages": []});
+        let err = registry.render_request(&mut missing).await.unwrap_err();
+        assert_eq!(err.message, "missing prompt variable: ticket");
+        let mut unknown = json!({"prompt_id": "nope"});
+        let err = registry.render_request(&mut unknown).await.unwrap_err();
+        assert_eq!(err.status, axum::http::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn sqlite_registry_publishes_new_versions() {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-prompts-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("prompts.db");
+        let config = PromptsConfig {
+            sqlite_path: Some(path.to_str().unwrap().to_string()),
+            templates: vec![template(1, "seed")],
+        };
+        let registry = PromptRegistry::open(&config).await.expect("open");
+        let published = registry.publish(template(0, "v2 {{company}}")).await.unwrap();
+        assert_eq!(published.version, 2);
+        assert_eq!(template_variables(&published), vec!["company", "ticket"]);
+
+        let reopened = PromptRegistry::open(&config).await.expect("reopen");
+        let latest = reopened.get("triage", None).await.unwrap().unwrap();
+        assert_eq!(latest.version, 2);
+        let first = reopened.get("triage", Some(1)).await.unwrap().unwrap();
+        assert_eq!(first.system.as_deref(), Some("seed"));
+        assert_eq!(reopened.list().await.unwrap().len(), 2);
+        assert!(PromptRegistry::default().publish(template(0, "x")).await.is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,245 @@
+use axum::body::Bytes;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::cohere::CohereProvider;
+use crate::config::Config;
+use crate::error::{map_downstream_error, AppError};
+use crate::mistral::MistralProvider;
+use crate::models::{OpenAIRequest, OpenAIResponse, OpenAIStreamChunk};
+use crate::sse::SseEvent;
+
+/// One downstream chat call: where to POST and what to send.
+pub struct DownstreamRequest {
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl DownstreamRequest {
+    /// The body as sent. Encode it once and reuse the bytes for every
+    /// attempt of the same call.
+    pub fn encoded_body(&self) -> Bytes {
+        Bytes::from(self.body.to_string())
+    }
+}
+
+/// What one downstream SSE event contributes to the translated stream.
+#[derive(Debug)]
+pub enum StreamEvent {
+    Chunk(OpenAIStreamChunk),
+    /// Last chunk of a stream that has no separate end marker; handled as
+    /// `Chunk` followed by `Done`.
+    Final(OpenAIStreamChunk),
+    /// Keepalives and provider bookkeeping with nothing to forward.
+    Skip,
+    /// End of the response: open blocks are closed and `message_stop` sent.
+    Done,
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    /// Unparseable event; `downstream.stream_parsing: lenient` skips these.
+    Malformed(String),
+    /// The downstream reported an error mid-stream.
+    Downstream(AppError),
+}
+
+/// A downstream chat API for `forward_mode: translate`. The translator works
+/// in OpenAI chat types, so a provider only converts those to and from its
+/// wire format and the handlers and streaming code stay provider-agnostic.
+/// Passthrough and Vertex forward Anthropic bodies unchanged and do not use
+/// providers.
+pub trait Provider: Send + Sync {
+    fn build_request(&self, req: &OpenAIRequest, config: &Config) -> Result<DownstreamRequest, AppError>;
+
+    fn parse_response(&self, body: &str) -> Result<OpenAIResponse, AppError>;
+
+    fn parse_stream_chunk(&self, event: &SseEvent) -> Result<StreamEvent, StreamError>;
+
+    /// Maps a non-2xx downstream response to an Anthropic-style error.
+    fn map_error(&self, status: StatusCode, body: &str) -> AppError {
+        map_downstream_error(status, body)
+    }
+}
+
+/// OpenAI-compatible `/v1/chat/completions`, the default provider.
+pub struct OpenAIProvider;
+
+impl Provider for OpenAIProvider {
+    fn build_request(&self, req: &OpenAIRequest, config: &Config) -> Result<DownstreamRequest, AppError> {
+        let (base_url, api_key) = config.provider_endpoint("openai");
+        let mut body = request_body(req)?;
+        set_max_tokens_field(&mut body, config.max_tokens_field("openai"));
+        Ok(DownstreamRequest {
+            url: endpoint_url(base_url, "v1", "chat/completions"),
+            headers: json_headers(&format!("Bearer {}", api_key.unwrap_or_default())),
+            body,
+        })
+    }
+
+    fn parse_response(&self, body: &str) -> Result<OpenAIResponse, AppError> {
+        serde_json::from_str(body).map_err(|e| AppError::api_error(format!("invalid downstream response: {}", e)))
+    }
+
+    fn parse_stream_chunk(&self, event: &SseEvent) -> Result<StreamEvent, StreamError> {
+        let data = event.data.trim();
+        if data == "[DONE]" {
+            return Ok(StreamEvent::Done);
+        }
+        if event.event.as_deref() == Some("error") {
+            return Err(StreamError::Downstream(AppError::api_error(format!(
+                "downstream stream error: {}",
+                data
+            ))));
+        }
+        serde_json::from_str(data)
+            .map(StreamEvent::Chunk)
+            .map_err(|e| StreamError::Malformed(e.to_string()))
+    }
+}
+
+/// `path` under the API version root; `base_url` may already end with it.
+pub fn endpoint_url(base_url: &str, version: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    if base.ends_with(&format!("/{}", version)) {
+        format!("{}/{}", base, path)
+    } else {
+        format!("{}/{}/{}", base, version, path)
+    }
+}
+
+pub fn request_body(req: &OpenAIRequest) -> Result<Value, AppError> {
+    serde_json::to_value(req).map_err(|e| AppError::api_error(format!("invalid downstream request: {}", e)))
+}
+
+/// Moves the output cap to `field` (`max_completion_tokens`, `max_tokens`
+/// or `both`), whichever of the two the body carries it in.
+pub fn set_max_tokens_field(body: &mut Value, field: &str) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    let Some(cap) = obj
+        .remove("max_completion_tokens")
+        .or_else(|| obj.remove("max_tokens"))
+    else {
+        return;
+    };
+    obj.remove("max_tokens");
+    if field != "max_tokens" {
+        obj.insert("max_completion_tokens".to_string(), cap.clone());
+    }
+    if field != "max_completion_tokens" {
+        obj.insert("max_tokens".to_string(), cap);
+    }
+}
+
+/// `Content-Type: application/json` plus the given `Authorization` value.
+pub fn json_headers(authorization: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(authorization).unwrap_or_else(|_| HeaderValue::from_static("[invalid]")),
+    );
+    headers
+}
+
+/// Providers by the name `downstream.provider` and `models.provider_map`
+/// select. Embedders register
+/// their own through `GatewayBuilder::provider`.
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: HashMap<String, Arc<dyn Provider>>,
+}
+
+impl ProviderRegistry {
+    pub fn builtin() -> Self {
+        let mut registry = Self {
+            providers: HashMap::new(),
+        };
+        registry.register("openai", Arc::new(OpenAIProvider));
+        registry.register("mistral", Arc::new(MistralProvider));
+        registry.register("cohere", Arc::new(CohereProvider));
+        registry
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, provider: Arc<dyn Provider>) {
+        self.providers.insert(name.into().to_lowercase(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Provider>> {
+        self.providers.get(&name.to_lowercase()).cloned()
+    }
+
+    /// Fails on a name that is not registered.
+    pub fn check(&self, name: &str) -> Result<(), String> {
+        if self.get(name).is_some() {
+            return Ok(());
+        }
+        let mut names: Vec<&str> = self.providers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        Err(format!(
+            "unknown provider: {} (registered: {})",
+            name,
+            names.join(", ")
+        ))
+    }
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: Option<&str>, data: &str) -> SseEvent {
+        SseEvent {
+            event: event.map(str::to_string),
+            data: data.to_string(),
+            comment: false,
+        }
+    }
+
+    #[test]
+    fn openai_provider_parses_stream_events() {
+        let provider = ProviderRegistry::builtin().get("OpenAI").expect("builtin");
+        let chunk = r#"{"id":"c1","model":"gpt-4o","choices":[{"index":0,"delta":{"content":"hi"}}]}"#;
+        assert!(matches!(provider.parse_stream_chunk(&event(None, chunk)), Ok(StreamEvent::Chunk(_))));
+        assert!(matches!(provider.parse_stream_chunk(&event(None, " [DONE]")), Ok(StreamEvent::Done)));
+        assert!(matches!(
+            provider.parse_stream_chunk(&event(None, "{not json")),
+            Err(StreamError::Malformed(_))
+        ));
+        assert!(matches!(
+            provider.parse_stream_chunk(&event(Some("error"), r#"{"message":"boom"}"#)),
+            Err(StreamError::Downstream(_))
+        ));
+
+        let err = ProviderRegistry::builtin().check("bedrock").unwrap_err();
+        assert!(err.contains("registered: cohere, mistral, openai"), "{}", err);
+    }
+
+    #[test]
+    fn max_tokens_field_selects_emitted_fields() {
+        let cases = [
+            ("max_completion_tokens", serde_json::json!({"max_completion_tokens": 64})),
+            ("max_tokens", serde_json::json!({"max_tokens": 64})),
+            ("both", serde_json::json!({"max_completion_tokens": 64, "max_tokens": 64})),
+        ];
+        for (field, expected) in cases {
+            let mut body = serde_json::json!({"max_completion_tokens": 64});
+            set_max_tokens_field(&mut body, field);
+            assert_eq!(body, expected, "{}", field);
+            set_max_tokens_field(&mut body, "max_tokens");
+            assert_eq!(body, serde_json::json!({"max_tokens": 64}), "{}", field);
+        }
+    }
+}
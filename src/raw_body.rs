@@ -0,0 +1,192 @@
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::{IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt;
+
+use crate::error::AppError;
+
+/// A `/v1/messages` body kept as the bytes the client sent, plus the few
+/// fields the gateway reads before deciding whether it needs the rest.
+/// Passthrough forwards `raw` verbatim (key order and whitespace included)
+/// and only calls `value` when a feature reads or rewrites the body.
+/// Rejections are the same as for `Json<Value>`, plus a 422 for a body that
+/// is not a JSON object.
+#[derive(Clone, Debug)]
+pub struct RawJson {
+    pub head: RequestHead,
+    pub raw: Bytes,
+}
+
+/// The top-level fields peeked at by a partial deserialize; every other
+/// field is skipped without being built. A repeated key keeps its last
+/// value, as `Value` does.
+#[derive(Clone, Debug, Default)]
+pub struct RequestHead {
+    model: Option<Value>,
+    stream: Option<Value>,
+    metadata: Option<Value>,
+    prompt_id: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum HeadField {
+    Model,
+    Stream,
+    Metadata,
+    PromptId,
+    #[serde(other)]
+    Other,
+}
+
+impl<'de> Deserialize<'de> for RequestHead {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HeadVisitor;
+
+        impl<'de> Visitor<'de> for HeadVisitor {
+            type Value = RequestHead;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RequestHead, A::Error> {
+                let mut head = RequestHead::default();
+                while let Some(field) = map.next_key()? {
+                    match field {
+                        HeadField::Model => head.model = Some(map.next_value()?),
+                        HeadField::Stream => head.stream = Some(map.next_value()?),
+                        HeadField::Metadata => head.metadata = Some(map.next_value()?),
+                        HeadField::PromptId => {
+                            map.next_value::<IgnoredAny>()?;
+                            head.prompt_id = true;
+                        }
+                        HeadField::Other => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(head)
+            }
+        }
+
+        deserializer.deserialize_map(HeadVisitor)
+    }
+}
+
+impl RequestHead {
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_ref().and_then(Value::as_str)
+    }
+
+    pub fn stream(&self) -> Option<bool> {
+        self.stream.as_ref().and_then(Value::as_bool)
+    }
+
+    /// `metadata.user_id`, the experiment assignment unit.
+    pub fn user_id(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("user_id")?.as_str()
+    }
+
+    /// Whether the body names a prompt template to render.
+    pub fn has_prompt(&self) -> bool {
+        self.prompt_id
+    }
+}
+
+impl RawJson {
+    /// Parses the whole body.
+    pub fn value(&self) -> Result<Value, AppError> {
+        serde_json::from_slice(&self.raw).map_err(|e| AppError::invalid_request(format!("invalid JSON body: {}", e)))
+    }
+}
+
+impl<S: Send + Sync> FromRequest<S> for RawJson {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let raw = Bytes::from_request(Request::from_parts(parts.clone(), body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Json(head) = Json::<RequestHead>::from_request(Request::from_parts(parts, raw.clone().into()), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Self { head, raw })
+    }
+}
+
+/// For callers that already hold a parsed body; `raw` is its compact
+/// serialization.
+impl From<Value> for RawJson {
+    fn from(value: Value) -> Self {
+        let raw = Bytes::from(serde_json::to_vec(&value).unwrap_or_default());
+        let head = serde_json::from_value(value).unwrap_or_default();
+        Self { head, raw }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+
+    #[tokio::test]
+    async fn keeps_original_bytes_and_json_rejections() {
+        let body = "{ \"stream\": true,\n  \"messages\": [{\"role\": \"user\", \"content\": \"hi\"}],\n  \"metadata\": {\"user_id\": \"u-1\"},\n  \"model\": \"claude\" }";
+        let req = Request::post("/v1/messages")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let parsed = RawJson::from_request(req, &()).await.expect("parsed");
+        assert_eq!(parsed.raw, body.as_bytes());
+        assert_eq!(parsed.head.model(), Some("claude"));
+        assert_eq!(parsed.head.stream(), Some(true));
+        assert_eq!(parsed.head.user_id(), Some("u-1"));
+        assert!(!parsed.head.has_prompt());
+        assert_eq!(parsed.value().unwrap()["messages"][0]["content"], "hi");
+
+        // Mistyped fields are left to the handler, as with `Json<Value>`.
+        let req = Request::post("/v1/messages")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"model": 1, "stream": "yes", "prompt_id": 7}"#))
+            .unwrap();
+        let parsed = RawJson::from_request(req, &()).await.expect("parsed");
+        assert_eq!(parsed.head.model(), None);
+        assert_eq!(parsed.head.stream(), None);
+        assert!(parsed.head.has_prompt());
+
+        // A repeated key keeps its last value, and a `null` prompt_id still
+        // goes to the template renderer.
+        let req = Request::post("/v1/messages")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"model": "a", "stream": true, "model": "b", "prompt_id": null}"#))
+            .unwrap();
+        let parsed = RawJson::from_request(req, &()).await.expect("duplicate keys");
+        assert_eq!(parsed.head.model(), Some("b"));
+        assert!(parsed.head.has_prompt());
+
+        let req = Request::post("/v1/messages")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"["claude", true]"#))
+            .unwrap();
+        let rejection = RawJson::from_request(req, &()).await.expect_err("array");
+        assert_eq!(rejection.status(), 422);
+
+        let req = Request::post("/v1/messages")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"model": "claude""#))
+            .unwrap();
+        let rejection = RawJson::from_request(req, &()).await.expect_err("truncated");
+        assert_eq!(rejection.status(), 400);
+
+        let req = Request::post("/v1/messages").body(Body::from(body)).unwrap();
+        let rejection = RawJson::from_request(req, &()).await.expect_err("no content type");
+        assert_eq!(rejection.status(), 415);
+    }
+}
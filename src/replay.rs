@@ -0,0 +1,160 @@
+use clap::Args;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// Audit log written with `observability.audit_log.path` (JSONL).
+    pub file: PathBuf,
+    /// Gateway base URL the requests are sent to.
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    pub target: String,
+    /// Overrides the recorded `x-api-key`.
+    #[arg(long)]
+    pub api_key: Option<String>,
+    /// Stop after this many requests.
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+/// Recorded request headers worth replaying; auth is redacted in the log
+/// except for `x-api-key`.
+const REPLAY_HEADERS: &[&str] = &["x-api-key", "anthropic-version", "anthropic-beta"];
+
+#[derive(Debug, PartialEq)]
+struct ReplayRequest {
+    request_id: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+/// `/v1/messages` records with a JSON request body; other routes and
+/// truncated bodies are skipped.
+fn replayable(record: &Value) -> Option<ReplayRequest> {
+    if record.get("route").and_then(Value::as_str) != Some("/v1/messages")
+        || record.get("method").and_then(Value::as_str) != Some("POST")
+        || record.pointer("/meta/body_truncated").and_then(Value::as_bool) == Some(true)
+    {
+        return None;
+    }
+    let body = record.pointer("/request/body").filter(|b| b.is_object())?.clone();
+    let headers = record
+        .pointer("/request/headers")
+        .and_then(Value::as_object)
+        .map(|headers| {
+            headers
+                .iter()
+                .filter(|(name, _)| REPLAY_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+                .filter_map(|(name, value)| Some((name.to_ascii_lowercase(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(ReplayRequest {
+        request_id: record.get("request_id").and_then(Value::as_str).unwrap_or("-").to_string(),
+        status: record.pointer("/response/status").and_then(Value::as_u64).unwrap_or(0) as u16,
+        headers,
+        body,
+    })
+}
+
+/// Sends the requests one at a time and prints one tab-separated line per
+/// request (`request_id`, recorded status, replay status, latency) followed
+/// by a summary.
+pub async fn run(args: ReplayArgs) -> Result<(), String> {
+    let content = tokio::fs::read_to_string(&args.file)
+        .await
+        .map_err(|e| format!("replay read error: {}: {}", args.file.display(), e))?;
+    let requests: Vec<ReplayRequest> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|record| replayable(&record))
+        .take(args.limit.unwrap_or(usize::MAX))
+        .collect();
+    let url = format!("{}/v1/messages", args.target.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+
+    let (mut matched, mut failed) = (0, 0);
+    for request in &requests {
+        let mut builder = client.post(&url).json(&request.body);
+        for (name, value) in &request.headers {
+            if name == "x-api-key" && args.api_key.is_some() {
+                continue;
+            }
+            builder = builder.header(name, value);
+        }
+        if let Some(key) = args.api_key.as_deref() {
+            builder = builder.header("x-api-key", key);
+        }
+        let start = Instant::now();
+        let status = match builder.send().await {
+            Ok(resp) => {
+                let status = resp.status().as_u16();
+                let _ = resp.bytes().await;
+                status
+            }
+            Err(err) => {
+                eprintln!("{}\trequest failed: {}", request.request_id, err);
+                failed += 1;
+                continue;
+            }
+        };
+        if status == request.status {
+            matched += 1;
+        }
+        println!(
+            "{}\t{}\t{}\t{}ms",
+            request.request_id,
+            request.status,
+            status,
+            start.elapsed().as_millis()
+        );
+    }
+    println!(
+        "replayed {} requests: {} status matched, {} failed",
+        requests.len(),
+        matched,
+        failed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn replays_messages_records_with_selected_headers() {
+        let record = json!({
+            "request_id": "req-1",
+            "route": "/v1/messages",
+            "method": "POST",
+            "request": {
+                "headers": {"x-api-key": "sk-1", "authorization": "[redacted]", "anthropic-version": "2023-06-01"},
+                "body": {"model": "claude", "messages": []}
+            },
+            "response": {"status": 200},
+            "meta": {"body_truncated": false}
+        });
+        let request = replayable(&record).expect("replayable");
+        assert_eq!(request.status, 200);
+        let mut headers = request.headers.clone();
+        headers.sort();
+        assert_eq!(
+            headers,
+            vec![
+                ("anthropic-version".to_string(), "2023-06-01".to_string()),
+                ("x-api-key".to_string(), "sk-1".to_string()),
+            ]
+        );
+
+        let mut models = record.clone();
+        models["route"] = json!("/v1/models");
+        assert_eq!(replayable(&models), None);
+        let mut truncated = record;
+        truncated["meta"]["body_truncated"] = json!(true);
+        assert_eq!(replayable(&truncated), None);
+    }
+}
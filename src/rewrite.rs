@@ -0,0 +1,304 @@
+use axum::body::Bytes;
+use serde_json::{json, Value};
+use std::ops::Range;
+
+use crate::config::Config;
+use crate::validation::parse_request;
+use crate::translate::{merge_patch, TranslateError};
+
+/// `forward_mode: rewrite`: the request is parsed as an Anthropic request
+/// (rejecting malformed ones), then rewritten in place so fields the gateway
+/// does not model (`metadata`, `top_k`, betas...) survive re-serialization.
+/// Applies `models.model_map`, the `anthropic.rewrite` clamps and system
+/// injection, the image/document policies and `models.overrides` (merged into
+/// the Anthropic body).
+pub fn rewrite_request(payload: &mut Value, config: &Config) -> Result<(), TranslateError> {
+    let req = parse_request(payload).map_err(TranslateError::invalid_request)?;
+    let rules = &config.anthropic.rewrite;
+
+    let model = config
+        .models
+        .model_map
+        .get(&req.model)
+        .cloned()
+        .unwrap_or(req.model);
+    payload["model"] = json!(model);
+
+    let max_tokens = match rules.max_tokens {
+        Some(cap) if req.max_tokens > cap => cap,
+        _ => req.max_tokens,
+    };
+    payload["max_tokens"] = json!(max_tokens);
+    if let Some(temperature) = req.temperature {
+        let clamped = temperature
+            .max(rules.min_temperature.unwrap_or(f32::MIN))
+            .min(rules.max_temperature.unwrap_or(f32::MAX));
+        if clamped != temperature {
+            payload["temperature"] = json!(clamped);
+        }
+    }
+    // The budget has to stay below `max_tokens`, which may just have shrunk.
+    if let Some(budget) = req.thinking.as_ref().and_then(|t| t.budget_tokens) {
+        let cap = rules
+            .max_thinking_budget
+            .unwrap_or(u32::MAX)
+            .min(max_tokens.saturating_sub(1));
+        if budget > cap {
+            payload["thinking"]["budget_tokens"] = json!(cap);
+        }
+    }
+
+    inject_system(payload, rules.system_prefix.as_deref(), rules.system_suffix.as_deref());
+    apply_content_policies(payload, config)?;
+
+    if let Some(overrides) = config.models.overrides.get(&model) {
+        merge_patch(payload, overrides);
+    }
+    Ok(())
+}
+
+/// `anthropic.passthrough_rewrite`: applies `models.model_map` and the
+/// `anthropic.rewrite.max_tokens` cap to a passthrough request by replacing
+/// just those values in the client's bytes, so key order, whitespace and
+/// every other field reach the downstream as sent. `payload` is updated to
+/// match. `None` when nothing changes.
+pub fn rewrite_passthrough(raw: &[u8], payload: &mut Value, config: &Config) -> Option<Bytes> {
+    let mut edits = Vec::new();
+    let model = payload.get("model").and_then(Value::as_str);
+    if let Some(mapped) = model.and_then(|model| config.models.model_map.get(model))
+        && model != Some(mapped.as_str())
+    {
+        edits.push(("model", json!(mapped)));
+    }
+    let max_tokens = payload.get("max_tokens").and_then(Value::as_u64);
+    if let (Some(cap), Some(max_tokens)) = (config.anthropic.rewrite.max_tokens, max_tokens)
+        && max_tokens > u64::from(cap)
+    {
+        edits.push(("max_tokens", json!(cap)));
+    }
+    if edits.is_empty() {
+        return None;
+    }
+    let spans: Option<Vec<_>> = edits
+        .iter()
+        .map(|(key, value)| top_level_value(raw, key).map(|span| (span, value.to_string())))
+        .collect();
+    for (key, value) in edits {
+        payload[key] = value;
+    }
+    let Some(mut spans) = spans else {
+        return Some(Bytes::from(serde_json::to_vec(payload).unwrap_or_default()));
+    };
+    spans.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+    let mut body = raw.to_vec();
+    for (span, value) in spans {
+        body.splice(span, value.into_bytes());
+    }
+    Some(Bytes::from(body))
+}
+
+/// Byte range of the value of `key` in the top-level JSON object `raw`.
+fn top_level_value(raw: &[u8], key: &str) -> Option<Range<usize>> {
+    let mut at = skip_whitespace(raw, 0);
+    if raw.get(at) != Some(&b'{') {
+        return None;
+    }
+    at = skip_whitespace(raw, at + 1);
+    loop {
+        let key_end = value_end(raw, at)?;
+        let name: String = serde_json::from_slice(&raw[at..key_end]).ok()?;
+        at = skip_whitespace(raw, key_end);
+        if raw.get(at) != Some(&b':') {
+            return None;
+        }
+        let start = skip_whitespace(raw, at + 1);
+        let end = value_end(raw, start)?;
+        if name == key {
+            return Some(start..end);
+        }
+        at = skip_whitespace(raw, end);
+        if raw.get(at) != Some(&b',') {
+            return None;
+        }
+        at = skip_whitespace(raw, at + 1);
+    }
+}
+
+/// End of the JSON value starting at `start`.
+fn value_end(raw: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (at, &byte) in raw.iter().enumerate().skip(start) {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+                if depth == 0 {
+                    return Some(at + 1);
+                }
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r' if depth == 0 => return Some(at),
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(at + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    (depth == 0 && !in_string && start < raw.len()).then_some(raw.len())
+}
+
+fn skip_whitespace(raw: &[u8], mut at: usize) -> usize {
+    while raw.get(at).is_some_and(u8::is_ascii_whitespace) {
+        at += 1;
+    }
+    at
+}
+
+fn inject_system(payload: &mut Value, prefix: Option<&str>, suffix: Option<&str>) {
+    if prefix.is_none() && suffix.is_none() {
+        return;
+    }
+    let text_block = |text: &str| json!({"type": "text", "text": text});
+    match payload.get_mut("system") {
+        Some(Value::Array(blocks)) => {
+            if let Some(prefix) = prefix {
+                blocks.insert(0, text_block(prefix));
+            }
+            if let Some(suffix) = suffix {
+                blocks.push(text_block(suffix));
+            }
+        }
+        system => {
+            let existing = system.as_ref().and_then(|s| s.as_str()).filter(|s| !s.is_empty());
+            let parts: Vec<&str> = [prefix, existing, suffix].into_iter().flatten().collect();
+            payload["system"] = json!(parts.join("\n\n"));
+        }
+    }
+}
+
+/// `models.allow_images: false` rejects images in messages and replaces them
+/// inside tool results, as translate does; `anthropic.rewrite.documents`
+/// decides what happens to `document` blocks.
+fn apply_content_policies(payload: &mut Value, config: &Config) -> Result<(), TranslateError> {
+    let documents = config.anthropic.rewrite.documents.as_str();
+    let allow_images = config.models.allow_images;
+    if allow_images && documents == "pass" {
+        return Ok(());
+    }
+    let Some(messages) = payload.get_mut("messages").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for message in messages {
+        let Some(blocks) = message.get_mut("content").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        let mut kept = Vec::with_capacity(blocks.len());
+        for mut block in blocks.drain(..) {
+            match block.get("type").and_then(Value::as_str) {
+                Some("image") if !allow_images => {
+                    return Err(TranslateError::invalid_request("image content not allowed"));
+                }
+                Some("document") => match documents {
+                    "reject" => return Err(TranslateError::invalid_request("document content not supported")),
+                    "strip" => continue,
+                    "text_only" => block = json!({"type": "text", "text": "[document omitted]"}),
+                    _ => {}
+                },
+                Some("tool_result") if !allow_images => {
+                    if let Some(parts) = block.get_mut("content").and_then(Value::as_array_mut) {
+                        for part in parts {
+                            if part.get("type").and_then(Value::as_str) == Some("image") {
+                                *part = json!({"type": "text", "text": "[image omitted]"});
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+            kept.push(block);
+        }
+        *blocks = kept;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_anthropic_request_in_place() {
+        let mut config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nanthropic:\n  forward_mode: rewrite\n  rewrite:\n    max_tokens: 2048\n    max_temperature: 0.7\n    system_prefix: \"Be brief.\"\n    documents: strip\nmodels:\n  model_map:\n    claude-latest: claude-sonnet-4-5\n  overrides:\n    claude-sonnet-4-5:\n      top_k: 20\nlimits: {}\nobservability: {}\n",
+        )
+        .expect("config");
+        let mut payload = json!({
+            "model": "claude-latest",
+            "max_tokens": 8000,
+            "temperature": 1.0,
+            "thinking": {"type": "enabled", "budget_tokens": 4000},
+            "metadata": {"user_id": "u1"},
+            "system": "You are helpful.",
+            "messages": [{"role": "user", "content": [
+                {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "doc"}},
+                {"type": "text", "text": "hi"}
+            ]}]
+        });
+        rewrite_request(&mut payload, &config).expect("rewrite");
+        assert_eq!(payload["model"], json!("claude-sonnet-4-5"));
+        assert_eq!(payload["max_tokens"], json!(2048));
+        assert_eq!(payload["thinking"]["budget_tokens"], json!(2047));
+        assert!((payload["temperature"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+        assert_eq!(payload["system"], json!("Be brief.\n\nYou are helpful."));
+        assert_eq!(payload["messages"][0]["content"].as_array().unwrap().len(), 1);
+        assert_eq!(payload["metadata"]["user_id"], json!("u1"));
+        assert_eq!(payload["top_k"], json!(20));
+
+        config.models.allow_images = false;
+        let mut payload = json!({
+            "model": "claude",
+            "max_tokens": 16,
+            "messages": [{"role": "user", "content": [{"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AA=="}}]}]
+        });
+        let err = rewrite_request(&mut payload, &config).unwrap_err();
+        assert_eq!(err.error_type, "invalid_request_error");
+    }
+
+    #[test]
+    fn passthrough_rewrite_replaces_only_model_and_max_tokens() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nanthropic:\n  passthrough_rewrite: true\n  rewrite:\n    max_tokens: 2048\nmodels:\n  model_map:\n    claude-latest: claude-sonnet-4-5\nlimits: {}\nobservability: {}\n",
+        )
+        .expect("config");
+        let raw = br#"{
+  "metadata": {"user_id": "u\"1", "tags": [1, {"model": "x"}]},
+  "model" : "claude-latest",
+  "messages": [{"role": "user", "content": "hi"}],
+  "max_tokens":8000
+}"#;
+        let mut payload: Value = serde_json::from_slice(raw).unwrap();
+        let body = rewrite_passthrough(raw, &mut payload, &config).expect("rewritten");
+        let expected = String::from_utf8_lossy(raw)
+            .replace("\"claude-latest\"", "\"claude-sonnet-4-5\"")
+            .replace("8000", "2048");
+        assert_eq!(String::from_utf8_lossy(&body), expected);
+        assert_eq!(payload["model"], json!("claude-sonnet-4-5"));
+        assert_eq!(payload["max_tokens"], json!(2048));
+
+        let raw = br#"{"model":"claude-sonnet-4-5","max_tokens":64,"messages":[]}"#;
+        let mut payload: Value = serde_json::from_slice(raw).unwrap();
+        assert!(rewrite_passthrough(raw, &mut payload, &config).is_none());
+    }
+}
@@ -0,0 +1,312 @@
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::{CostCandidate, CostRoutingConfig, RoutingConfig, RoutingRule};
+use crate::tokenizer::Tokenizer;
+
+/// Where a request was routed and why: the request goes to `model` (a
+/// downstream model name, bypassing `models.model_map`). Recorded in logs,
+/// traces and audit records.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RouteDecision {
+    /// `rules` or `cost`.
+    pub policy: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_usd: Option<f64>,
+}
+
+/// `routing.rules` with their system prompt regexes compiled; the first
+/// rule whose conditions all hold wins. Under `policy: cost`, requests no
+/// rule matched go to the cheapest eligible candidate.
+#[derive(Clone, Default)]
+pub struct ModelRouter {
+    rules: Vec<(RoutingRule, Option<Regex>)>,
+    cost: Option<CostRoutingConfig>,
+}
+
+impl ModelRouter {
+    pub fn new(config: &RoutingConfig) -> Result<Self, String> {
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                let regex = rule
+                    .system_regex
+                    .as_deref()
+                    .map(Regex::new)
+                    .transpose()
+                    .map_err(|e| format!("routing.rules.{}.system_regex invalid: {}", rule.name, e))?;
+                Ok((rule.clone(), regex))
+            })
+            .collect::<Result<_, String>>()?;
+        let cost = (config.policy == "cost").then(|| config.cost.clone()).flatten();
+        Ok(Self { rules, cost })
+    }
+
+    /// Routes a raw Anthropic `/v1/messages` body for the client `model`;
+    /// `tier` is the client's tier header. Prompt tokens are estimated only
+    /// when a rule or the cost policy needs them. Errors on an unknown tier.
+    pub fn route(
+        &self,
+        payload: &Value,
+        model: &str,
+        tier: Option<&str>,
+        tokenizer: &Tokenizer,
+    ) -> Result<Option<RouteDecision>, String> {
+        let mut prompt_tokens = None;
+        let system = system_text(payload);
+        for (rule, regex) in &self.rules {
+            if !rule.models.is_empty() && !rule.models.iter().any(|m| m == model) {
+                continue;
+            }
+            if rule.has_tools.is_some_and(|want| want != has_tools(payload))
+                || rule.has_images.is_some_and(|want| want != has_images(payload))
+                || rule.min_thinking_budget.is_some_and(|min| thinking_budget(payload) < min)
+                || regex.as_ref().is_some_and(|re| !re.is_match(&system))
+            {
+                continue;
+            }
+            if rule.min_prompt_tokens.is_some() || rule.max_prompt_tokens.is_some() {
+                let tokens = *prompt_tokens.get_or_insert_with(|| estimate_prompt_tokens(payload, tokenizer));
+                if rule.min_prompt_tokens.is_some_and(|min| tokens < min)
+                    || rule.max_prompt_tokens.is_some_and(|max| tokens > max)
+                {
+                    continue;
+                }
+            }
+            return Ok(Some(RouteDecision {
+                policy: "rules",
+                rule: Some(rule.name.clone()),
+                model: rule.model.clone(),
+                tier: None,
+                prompt_tokens,
+                estimated_cost_usd: None,
+            }));
+        }
+        match self.cost.as_ref() {
+            Some(cost) if cost.models.is_empty() || cost.models.iter().any(|m| m == model) => {
+                let tokens = *prompt_tokens.get_or_insert_with(|| estimate_prompt_tokens(payload, tokenizer));
+                cheapest(cost, payload, tier, tokens)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty() && self.cost.is_none()
+    }
+}
+
+/// The cheapest candidate at or above the requested tier that supports the
+/// request's tools and fits prompt plus `max_tokens` in its context window.
+/// The estimate prices the prompt and the full `max_tokens`; ties go to the
+/// first listed candidate. `None` when nothing qualifies.
+fn cheapest(
+    cost: &CostRoutingConfig,
+    payload: &Value,
+    tier: Option<&str>,
+    prompt_tokens: u64,
+) -> Result<Option<RouteDecision>, String> {
+    let rank = |tier: &str| cost.tiers.iter().position(|t| t.eq_ignore_ascii_case(tier));
+    let min_rank = match tier.or(cost.default_tier.as_deref()) {
+        Some(tier) => rank(tier).ok_or_else(|| format!("unknown tier: {}", tier))?,
+        None => 0,
+    };
+    let max_tokens = payload.get("max_tokens").and_then(Value::as_u64).unwrap_or(0);
+    let tools = has_tools(payload);
+    let estimate = |c: &CostCandidate| {
+        (prompt_tokens as f64 * c.input_per_mtok + max_tokens as f64 * c.output_per_mtok) / 1_000_000.0
+    };
+    let choice = cost
+        .candidates
+        .iter()
+        .filter(|c| rank(&c.tier).is_some_and(|r| r >= min_rank))
+        .filter(|c| !tools || c.tools)
+        .filter(|c| c.context_window.is_none_or(|window| prompt_tokens + max_tokens <= window))
+        .map(|c| (c, estimate(c)))
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(choice.map(|(candidate, estimated)| RouteDecision {
+        policy: "cost",
+        rule: None,
+        model: candidate.model.clone(),
+        tier: Some(candidate.tier.clone()),
+        prompt_tokens: Some(prompt_tokens),
+        estimated_cost_usd: Some(estimated),
+    }))
+}
+
+fn has_tools(payload: &Value) -> bool {
+    payload
+        .get("tools")
+        .and_then(Value::as_array)
+        .is_some_and(|tools| !tools.is_empty())
+}
+
+/// Images anywhere in the messages, including inside tool results.
+fn has_images(payload: &Value) -> bool {
+    fn contains_image(blocks: &[Value]) -> bool {
+        blocks.iter().any(|block| match block.get("type").and_then(Value::as_str) {
+            Some("image") => true,
+            Some("tool_result") => block
+                .get("content")
+                .and_then(Value::as_array)
+                .is_some_and(|inner| contains_image(inner)),
+            _ => false,
+        })
+    }
+    messages(payload).any(|message| {
+        message
+            .get("content")
+            .and_then(Value::as_array)
+            .is_some_and(|blocks| contains_image(blocks))
+    })
+}
+
+/// `thinking.budget_tokens` when extended thinking is enabled, else 0.
+fn thinking_budget(payload: &Value) -> u32 {
+    let Some(thinking) = payload.get("thinking") else {
+        return 0;
+    };
+    if thinking.get("type").and_then(Value::as_str) != Some("enabled") {
+        return 0;
+    }
+    thinking
+        .get("budget_tokens")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32
+}
+
+fn system_text(payload: &Value) -> String {
+    match payload.get("system") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// System prompt, message text, tool inputs/results and tool definitions.
+pub fn estimate_prompt_tokens(payload: &Value, tokenizer: &Tokenizer) -> u64 {
+    fn collect(content: &Value, out: &mut String) {
+        match content {
+            Value::String(text) => {
+                out.push_str(text);
+                out.push('\n');
+            }
+            Value::Array(blocks) => {
+                for block in blocks {
+                    match block.get("type").and_then(Value::as_str) {
+                        Some("text") => collect(block.get("text").unwrap_or(&Value::Null), out),
+                        Some("thinking") => collect(block.get("thinking").unwrap_or(&Value::Null), out),
+                        Some("tool_use") => {
+                            out.push_str(&block.get("input").map(Value::to_string).unwrap_or_default());
+                            out.push('\n');
+                        }
+                        Some("tool_result") => collect(block.get("content").unwrap_or(&Value::Null), out),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut text = system_text(payload);
+    text.push('\n');
+    for message in messages(payload) {
+        collect(message.get("content").unwrap_or(&Value::Null), &mut text);
+    }
+    if let Some(tools) = payload.get("tools") {
+        text.push_str(&tools.to_string());
+    }
+    tokenizer.count_text(&text)
+}
+
+fn messages(payload: &Value) -> impl Iterator<Item = &Value> {
+    payload.get("messages").and_then(Value::as_array).into_iter().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use serde_json::json;
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\nrouting:\n  rules:\n    - name: legal\n      system_regex: \"(?i)contract\"\n      model: legal-model\n    - name: long\n      min_prompt_tokens: 1000\n      model: long-model\n    - name: tools\n      models: [claude]\n      has_tools: true\n      model: tool-model\n    - name: vision\n      has_images: true\n      model: vision-model\n    - name: think\n      min_thinking_budget: 8000\n      model: think-model\n",
+        )
+        .expect("config");
+        let router = ModelRouter::new(&config.routing).expect("router");
+        let tokenizer = Tokenizer::Heuristic;
+        let route = |payload: Value, model: &str| {
+            router.route(&payload, model, None, &tokenizer).expect("route").and_then(|d| d.rule)
+        };
+
+        let base = json!({"model": "claude", "max_tokens": 8, "messages": [{"role": "user", "content": "hi"}]});
+        assert_eq!(route(base.clone(), "claude"), None);
+
+        let mut legal = base.clone();
+        legal["system"] = json!([{"type": "text", "text": "Review this CONTRACT"}]);
+        legal["tools"] = json!([{"name": "t", "input_schema": {}}]);
+        assert_eq!(route(legal, "claude").as_deref(), Some("legal"));
+
+        let mut long = base.clone();
+        long["messages"][0]["content"] = json!("word ".repeat(1000));
+        assert_eq!(route(long, "claude").as_deref(), Some("long"));
+
+        let mut tools = base.clone();
+        tools["tools"] = json!([{"name": "t", "input_schema": {}}]);
+        assert_eq!(route(tools.clone(), "claude").as_deref(), Some("tools"));
+        assert_eq!(route(tools, "other"), None);
+
+        let mut vision = base.clone();
+        vision["messages"][0]["content"] = json!([{"type": "tool_result", "tool_use_id": "t", "content": [{"type": "image", "source": {}}]}]);
+        assert_eq!(route(vision, "claude").as_deref(), Some("vision"));
+
+        let mut think = base;
+        think["thinking"] = json!({"type": "enabled", "budget_tokens": 10000});
+        assert_eq!(route(think, "claude").as_deref(), Some("think"));
+    }
+
+    #[test]
+    fn cost_policy_picks_cheapest_eligible_candidate() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\nrouting:\n  policy: cost\n  cost:\n    tiers: [economy, standard, premium]\n    candidates:\n      - model: small\n        tier: economy\n        input_per_mtok: 0.1\n        output_per_mtok: 0.4\n        context_window: 1000\n        tools: false\n      - model: medium\n        tier: standard\n        input_per_mtok: 1.0\n        output_per_mtok: 4.0\n      - model: large\n        tier: premium\n        input_per_mtok: 3.0\n        output_per_mtok: 15.0\n",
+        )
+        .expect("config");
+        let router = ModelRouter::new(&config.routing).expect("router");
+        let tokenizer = Tokenizer::Heuristic;
+        let route = |payload: &Value, tier: Option<&str>| {
+            router.route(payload, "claude", tier, &tokenizer).map(|d| d.map(|d| d.model))
+        };
+
+        let base = json!({"model": "claude", "max_tokens": 100, "messages": [{"role": "user", "content": "hi"}]});
+        assert_eq!(route(&base, None), Ok(Some("small".to_string())));
+        assert_eq!(route(&base, Some("Premium")), Ok(Some("large".to_string())));
+        assert!(route(&base, Some("gold")).is_err());
+
+        let mut tools = base.clone();
+        tools["tools"] = json!([{"name": "t", "input_schema": {}}]);
+        assert_eq!(route(&tools, None), Ok(Some("medium".to_string())));
+
+        let mut long = base;
+        long["max_tokens"] = json!(4000);
+        let decision = router.route(&long, "claude", None, &tokenizer).unwrap().unwrap();
+        assert_eq!(decision.model, "medium");
+        assert_eq!(decision.policy, "cost");
+        assert_eq!(decision.tier.as_deref(), Some("standard"));
+        assert!(decision.estimated_cost_usd.unwrap() > 0.0);
+    }
+}
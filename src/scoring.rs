@@ -0,0 +1,290 @@
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::KeyValue;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::{json, Value};
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+use crate::access_log::RequestSummary;
+use crate::config::{ScorerConfig, ScoringConfig};
+use crate::shadow::evenly_sampled;
+
+const JUDGE_PROMPT: &str = "You grade answers produced by an AI assistant. Reply with a single \
+     integer from 1 (useless or wrong) to 10 (complete and correct) and nothing else.";
+
+/// Text pair handed to scorers. `reference` is the primary response when
+/// scoring shadow traffic and absent for experiment traffic.
+#[derive(Clone, Debug, Default)]
+pub struct ScoreSample {
+    pub prompt: String,
+    pub output: String,
+    pub reference: Option<String>,
+}
+
+/// A quality scorer producing values in `0.0..=1.0`.
+pub enum Scorer {
+    ExactMatch,
+    Embedding { url: String, api_key: String, model: String },
+    Judge { url: String, api_key: String, model: String },
+}
+
+impl Scorer {
+    fn from_config(config: &ScorerConfig, default_base_url: &str, default_api_key: &str) -> Self {
+        let base = config
+            .base_url
+            .as_deref()
+            .unwrap_or(default_base_url)
+            .trim_end_matches('/');
+        let base = base.strip_suffix("/v1").unwrap_or(base);
+        let api_key = config.api_key.as_deref().unwrap_or(default_api_key).to_string();
+        let model = config.model.clone().unwrap_or_default();
+        match config.kind.as_str() {
+            "embedding" => Self::Embedding {
+                url: format!("{}/v1/embeddings", base),
+                api_key,
+                model,
+            },
+            "judge" => Self::Judge {
+                url: format!("{}/v1/chat/completions", base),
+                api_key,
+                model,
+            },
+            _ => Self::ExactMatch,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ExactMatch => "exact_match",
+            Self::Embedding { .. } => "embedding",
+            Self::Judge { .. } => "judge",
+        }
+    }
+
+    pub fn needs_reference(&self) -> bool {
+        !matches!(self, Self::Judge { .. })
+    }
+
+    pub async fn score(&self, client: &reqwest::Client, sample: &ScoreSample) -> Result<f64, String> {
+        match self {
+            Self::ExactMatch => {
+                let reference = sample.reference.as_deref().unwrap_or_default();
+                Ok(if reference.trim() == sample.output.trim() { 1.0 } else { 0.0 })
+            }
+            Self::Embedding { url, api_key, model } => {
+                let reference = sample.reference.clone().unwrap_or_default();
+                let body = json!({"model": model, "input": [reference, sample.output]});
+                let resp = post_json(client, url, api_key, &body).await?;
+                let vectors: Vec<Vec<f64>> = resp
+                    .get("data")
+                    .and_then(Value::as_array)
+                    .map(|data| {
+                        data.iter()
+                            .filter_map(|item| item.get("embedding"))
+                            .filter_map(|e| serde_json::from_value(e.clone()).ok())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                match vectors.as_slice() {
+                    [a, b] => Ok(cosine_similarity(a, b)),
+                    _ => Err("invalid embeddings response".to_string()),
+                }
+            }
+            Self::Judge { url, api_key, model } => {
+                let mut content = format!("Question:\n{}\n\n", sample.prompt);
+                if let Some(reference) = sample.reference.as_ref() {
+                    content.push_str(&format!("Reference answer:\n{}\n\n", reference));
+                }
+                content.push_str(&format!("Answer to grade:\n{}", sample.output));
+                let body = json!({
+                    "model": model,
+                    "max_completion_tokens": 8,
+                    "messages": [
+                        {"role": "system", "content": JUDGE_PROMPT},
+                        {"role": "user", "content": content},
+                    ],
+                });
+                let resp = post_json(client, url, api_key, &body).await?;
+                let text = resp
+                    .pointer("/choices/0/message/content")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                parse_judge_score(text).ok_or_else(|| format!("invalid judge reply: {}", text))
+            }
+        }
+    }
+}
+
+async fn post_json(
+    client: &reqwest::Client,
+    url: &str,
+    api_key: &str,
+    body: &Value,
+) -> Result<Value, String> {
+    let resp = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(AUTHORIZATION, format!("Bearer {}", api_key))
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("{} {}", status, text));
+    }
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Takes the first integer in the judge reply and maps 1..=10 to 0.1..=1.0.
+fn parse_judge_score(text: &str) -> Option<f64> {
+    let digits: String = text
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let score: u32 = digits.parse().ok()?;
+    Some(score.clamp(1, 10) as f64 / 10.0)
+}
+
+/// Runs the configured scorers in the background and records the results as
+/// `ai.gateway.quality.score`, labelled by scorer, source and variant.
+#[derive(Clone)]
+pub struct Scoring {
+    scorers: Arc<Vec<Scorer>>,
+    client: reqwest::Client,
+    sample_percent: f64,
+    counter: Arc<AtomicU64>,
+    score: Histogram<f64>,
+    failures: Counter<u64>,
+}
+
+impl Scoring {
+    pub fn new(
+        config: &ScoringConfig,
+        default_base_url: &str,
+        default_api_key: &str,
+        client: reqwest::Client,
+    ) -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        Self {
+            scorers: Arc::new(
+                config
+                    .scorers
+                    .iter()
+                    .map(|s| Scorer::from_config(s, default_base_url, default_api_key))
+                    .collect(),
+            ),
+            client,
+            sample_percent: config.sample_percent,
+            counter: Arc::new(AtomicU64::new(0)),
+            score: meter
+                .f64_histogram("ai.gateway.quality.score")
+                .with_description("Response quality scores (0-1) by scorer, source and variant")
+                .build(),
+            failures: meter
+                .u64_counter("ai.gateway.quality.score_failures")
+                .with_description("Scorer calls that failed, by scorer")
+                .build(),
+        }
+    }
+
+    /// Scores a shadow response against the primary response.
+    pub fn score_shadow(&self, primary: &RequestSummary, shadow_output: String) {
+        let Some(reference) = primary.output_text.clone() else {
+            return;
+        };
+        let sample = ScoreSample {
+            prompt: primary.prompt_text.clone().unwrap_or_default(),
+            output: shadow_output,
+            reference: Some(reference),
+        };
+        self.spawn(sample, "shadow", "shadow".to_string(), true);
+    }
+
+    /// Scores an experiment response with the reference-free scorers.
+    pub fn score_experiment(&self, summary: &RequestSummary) {
+        let (Some(variant), Some(output)) = (summary.experiment.clone(), summary.output_text.clone())
+        else {
+            return;
+        };
+        if output.is_empty() || summary.status >= 400 {
+            return;
+        }
+        let sample = ScoreSample {
+            prompt: summary.prompt_text.clone().unwrap_or_default(),
+            output,
+            reference: None,
+        };
+        self.spawn(sample, "experiment", variant, false);
+    }
+
+    fn spawn(&self, sample: ScoreSample, source: &'static str, variant: String, with_reference: bool) {
+        if !evenly_sampled(&self.counter, self.sample_percent) {
+            return;
+        }
+        let scoring = self.clone();
+        tokio::spawn(async move {
+            for scorer in scoring.scorers.iter() {
+                if scorer.needs_reference() && !with_reference {
+                    continue;
+                }
+                let attrs = [
+                    KeyValue::new("scorer", scorer.name()),
+                    KeyValue::new("source", source),
+                    KeyValue::new("variant", variant.clone()),
+                ];
+                match scorer.score(&scoring.client, &sample).await {
+                    Ok(score) => scoring.score.record(score, &attrs),
+                    Err(err) => {
+                        tracing::warn!(scorer = scorer.name(), "quality scoring failed: {}", err);
+                        scoring
+                            .failures
+                            .add(1, &[KeyValue::new("scorer", scorer.name())]);
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn exact_match_compares_trimmed_text() {
+        let client = reqwest::Client::new();
+        let sample = ScoreSample {
+            prompt: "2+2?".to_string(),
+            output: "4\n".to_string(),
+            reference: Some("4".to_string()),
+        };
+        assert_eq!(Scorer::ExactMatch.score(&client, &sample).await, Ok(1.0));
+        let other = ScoreSample {
+            output: "5".to_string(),
+            ..sample
+        };
+        assert_eq!(Scorer::ExactMatch.score(&client, &other).await, Ok(0.0));
+    }
+
+    #[test]
+    fn judge_score_and_cosine_helpers() {
+        assert_eq!(parse_judge_score("Score: 8/10"), Some(0.8));
+        assert_eq!(parse_judge_score("42"), Some(1.0));
+        assert_eq!(parse_judge_score("great"), None);
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    }
+}
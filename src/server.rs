@@ -0,0 +1,551 @@
+use axum::{routing::post, Router};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::fs::OpenOptions;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use crate::access_log::AccessLogger;
+use crate::admin;
+use crate::alerts;
+use crate::audit_log::AuditLogger;
+use crate::billing::BillingForwarder;
+use crate::canary::Canary;
+use crate::client_pool::DownstreamClients;
+use crate::compaction::Compactor;
+use crate::config::Config;
+use crate::cors;
+use crate::dashboard;
+use crate::debug_capture::DebugCapture;
+use crate::exporter_health::spawn_telemetry_watchdog;
+use crate::handlers::{self, post_messages};
+use crate::ip_access::{self, IpAccess};
+use crate::key_pool::KeyPool;
+use crate::metrics::{self, init_metrics, init_metrics_noop, Metrics, MetricsExporterConfig};
+use crate::oauth::OAuthTokens;
+use crate::openapi;
+use crate::pg_store::{PgStore, PgUsageStore};
+use crate::prompts::PromptRegistry;
+use crate::provider::ProviderRegistry;
+use crate::routing::ModelRouter;
+use crate::scoring::Scoring;
+use crate::shadow::Shadow;
+use crate::signing::{self, RequestVerifier};
+use crate::state::{AppState, EndpointPool};
+use crate::tap::TapRegistry;
+use crate::tokenizer::Tokenizers;
+use crate::transcripts::TranscriptStore;
+use crate::tracing_otlp::{init_tracer_grpc, init_tracer_langfuse_http, init_tracer_noop};
+use crate::usage::{UsageBackend, UsageStore};
+use crate::usage_sqlite::SqliteUsageStore;
+use crate::vertex::VertexAuth;
+use crate::warmup;
+
+fn parse_level(level: &str) -> LevelFilter {
+    match level {
+        "trace" => LevelFilter::TRACE,
+        "debug" => LevelFilter::DEBUG,
+        "warn" => LevelFilter::WARN,
+        "error" => LevelFilter::ERROR,
+        _ => LevelFilter::INFO,
+    }
+}
+
+fn open_log_file(path: &str) -> Option<std::fs::File> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent()
+        && let Err(err) = std::fs::create_dir_all(parent)
+    {
+        eprintln!("log file create dir error: {}", err);
+        return None;
+    }
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            eprintln!("log file open error: {}", err);
+            None
+        }
+    }
+}
+
+/// Runs the gateway until the listener fails: telemetry from
+/// `observability`, shared state, background tasks and the router.
+pub async fn serve(config: Config) -> Result<(), String> {
+    let inflight_count = Arc::new(AtomicU64::new(0));
+    let (metrics, tracer_provider) = init_telemetry(&config, inflight_count.clone());
+    let _telemetry_watchdog =
+        spawn_telemetry_watchdog(tracer_provider.clone(), metrics.exporter_health.clone());
+
+    let providers = ProviderRegistry::builtin();
+    let state = build_state(
+        &config,
+        metrics,
+        inflight_count,
+        tracer_provider,
+        providers.clone(),
+    )
+    .await?;
+    if let Some(alerts) = config.alerts.clone() {
+        alerts::spawn_alerts(
+            alerts,
+            state.usage.clone(),
+            reqwest::Client::builder()
+                .connect_timeout(config.connect_timeout())
+                .build()
+                .unwrap_or_default(),
+        );
+    }
+    let mut app = build_routes(&config, state.clone())?;
+    if let Some(canary) = config.canary.as_ref() {
+        app = Canary::build(canary, &state, providers).await?.layer(app);
+    }
+    let app = edge_layers(&config, app)?;
+
+    let listener = tokio::net::TcpListener::bind(&config.server.bind_addr)
+        .await
+        .map_err(|e| format!("bind error: {}", e))?;
+
+    tracing::info!("listening on {}", config.server.bind_addr);
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .map_err(|e| format!("server error: {}", e))
+}
+
+/// Metrics and trace exporters plus the log subscriber; exporter failures
+/// fall back to noop providers.
+fn init_telemetry(config: &Config, inflight_count: Arc<AtomicU64>) -> (Metrics, SdkTracerProvider) {
+    let metrics_exporter = MetricsExporterConfig {
+        kind: config.observability.exporters.metrics.clone(),
+        endpoint: if config.observability.exporters.metrics == "langfuse_http" {
+            config.observability.otlp_http.metrics_endpoint()
+        } else {
+            config.observability.otlp_grpc.endpoint.clone()
+        },
+        timeout_ms: if config.observability.exporters.metrics == "langfuse_http" {
+            config.observability.otlp_http.timeout_ms
+        } else {
+            config.observability.otlp_grpc.timeout_ms
+        },
+        public_key: config.observability.otlp_http.public_key.clone(),
+        secret_key: config.observability.otlp_http.secret_key.clone(),
+        export_interval_ms: config.observability.metrics.export_interval_ms,
+        latency_buckets_ms: config.observability.metrics.latency_buckets_ms.clone(),
+    };
+
+    let metrics = match init_metrics(
+        config.observability.service_name.clone(),
+        metrics_exporter,
+        inflight_count.clone(),
+    ) {
+        Ok(m) => m,
+        Err(err) => {
+            eprintln!("metrics init error (fallback to noop): {}", err);
+            init_metrics_noop(inflight_count.clone())
+        }
+    };
+    let tracer_provider = match config.observability.exporters.tracing.as_str() {
+        "langfuse_http" => init_tracer_langfuse_http(
+            config.observability.otlp_http.traces_endpoint(),
+            config.observability.service_name.clone(),
+            config.observability.otlp_http.timeout_ms,
+            config.observability.otlp_http.public_key.clone(),
+            config.observability.otlp_http.secret_key.clone(),
+            &config.observability.tracing,
+        ),
+        _ => init_tracer_grpc(
+            config.observability.otlp_grpc.endpoint.clone(),
+            config.observability.service_name.clone(),
+            config.observability.otlp_grpc.timeout_ms,
+            &config.observability.tracing,
+        ),
+    };
+    let tracer_provider = match tracer_provider {
+        Ok(provider) => {
+            metrics
+                .exporter_health
+                .traces
+                .set_exporter(&config.observability.exporters.tracing);
+            provider
+        }
+        Err(err) => {
+            eprintln!("tracing init error (fallback to noop): {}", err);
+            init_tracer_noop(config.observability.service_name.clone())
+        }
+    };
+
+    let log_level = parse_level(config.observability.logging.level.as_str());
+    let log_format = config.observability.logging.format.as_str();
+    let file_writer = config
+        .observability
+        .logging
+        .file
+        .as_deref()
+        .and_then(open_log_file)
+        .map(Arc::new);
+
+    let writer = match (config.observability.logging.stdout, file_writer) {
+        (true, Some(file)) => BoxMakeWriter::new(std::io::stdout.and(file)),
+        (true, None) => BoxMakeWriter::new(std::io::stdout),
+        (false, Some(file)) => BoxMakeWriter::new(file),
+        (false, None) => BoxMakeWriter::new(std::io::stdout),
+    };
+
+    if log_format == "json" {
+        eprintln!("logging.format=json is not enabled; falling back to text");
+    }
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_filter(log_level);
+
+    let telemetry = tracing_opentelemetry::layer();
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(telemetry)
+        .init();
+
+    let tracing_exporter_kind = config.observability.exporters.tracing.as_str();
+    let tracing_endpoint = if tracing_exporter_kind == "langfuse_http" {
+        config.observability.otlp_http.traces_endpoint()
+    } else {
+        config.observability.otlp_grpc.endpoint.clone()
+    };
+    tracing::info!(
+        tracing_exporter = tracing_exporter_kind,
+        tracing_endpoint = %tracing_endpoint,
+        tracing_batch = true,
+        "tracing exporter configured"
+    );
+    (metrics, tracer_provider)
+}
+
+/// Every provider name the config refers to must be registered.
+pub fn check_providers(config: &Config, providers: &ProviderRegistry) -> Result<(), String> {
+    providers
+        .check(&config.downstream.provider)
+        .map_err(|e| format!("downstream.provider: {}", e))?;
+    for (model, name) in &config.models.provider_map {
+        providers
+            .check(name)
+            .map_err(|e| format!("models.provider_map.{}: {}", model, e))?;
+    }
+    Ok(())
+}
+
+/// Shared state for the router: downstream clients, stores, loggers and the
+/// optional shadow, scoring and compaction components. With
+/// `downstream.warmup`, downstream connections are opened before returning.
+pub async fn build_state(
+    config: &Config,
+    metrics: Metrics,
+    inflight_count: Arc<AtomicU64>,
+    tracer_provider: SdkTracerProvider,
+    providers: ProviderRegistry,
+) -> Result<AppState, String> {
+    check_providers(config, &providers)?;
+    let vertex_auth = match config.anthropic.vertex.as_ref() {
+        Some(vertex) if config.forward_mode() == "vertex" => {
+            let client = reqwest::Client::builder()
+                .connect_timeout(config.connect_timeout())
+                .build()
+                .map_err(|e| format!("vertex token client build error: {}", e))?;
+            Some(
+                VertexAuth::from_config(vertex, client)
+                    .map_err(|e| format!("vertex auth error: {}", e))?,
+            )
+        }
+        _ => None,
+    };
+
+    let oauth = match config.downstream.oauth.as_ref() {
+        Some(oauth) => {
+            let client = reqwest::Client::builder()
+                .connect_timeout(config.connect_timeout())
+                .build()
+                .map_err(|e| format!("oauth token client build error: {}", e))?;
+            Some(OAuthTokens::new(oauth, client))
+        }
+        None => None,
+    };
+
+    let pg_store = match config.postgres.as_ref() {
+        Some(pg) => Some(PgStore::connect(pg).await?),
+        None => None,
+    };
+
+    let tokenizers = Tokenizers::from_config(&config.tokenizers)?;
+    let prompts = PromptRegistry::open(&config.prompts).await?;
+
+    let mut usage = UsageStore::new(config.usage.ring_capacity);
+    match config.usage_sink() {
+        "sqlite" => {
+            let path = config.usage.sqlite_path.as_deref().unwrap_or_default();
+            let sqlite = SqliteUsageStore::open(path).await?;
+            usage = usage.with_backend(UsageBackend::Sqlite(sqlite));
+        }
+        "postgres" => {
+            if let Some(store) = pg_store.clone() {
+                usage = usage.with_backend(UsageBackend::Postgres(PgUsageStore::new(store)));
+            }
+        }
+        _ => {}
+    }
+
+    let scoring = config.scoring.as_ref().map(|scoring| {
+        Scoring::new(
+            scoring,
+            &config.downstream.base_url,
+            config.downstream.api_key.as_deref().unwrap_or_default(),
+            reqwest::Client::builder()
+                .connect_timeout(config.connect_timeout())
+                .timeout(config.read_timeout())
+                .build()
+                .unwrap_or_default(),
+        )
+    });
+
+    let debug_capture = if config.observability.debug_capture.enabled {
+        Some(DebugCapture::new(&config.observability.debug_capture)?)
+    } else {
+        None
+    };
+
+    let state = AppState {
+        clients: DownstreamClients::new(config)?,
+        config: config.clone(),
+        inflight: Arc::new(tokio::sync::Semaphore::new(config.limits.max_inflight)),
+        inflight_count,
+        inflight_requests: Default::default(),
+        metrics,
+        audit_logger: if config.observability.audit_log.enabled {
+            match (
+                config.observability.audit_log.sink.as_str(),
+                config.observability.audit_log.path.as_deref(),
+            ) {
+                ("postgres", _) => pg_store
+                    .clone()
+                    .map(|store| {
+                        AuditLogger::postgres(
+                            store,
+                            config.observability.audit_log.overflow.clone(),
+                            config.capture_policy(),
+                        )
+                    }),
+                ("s3", _) => config.observability.audit_log.s3.clone().map(|s3| {
+                    AuditLogger::s3(
+                        s3,
+                        config.observability.audit_log.max_file_bytes,
+                        config.observability.audit_log.overflow.clone(),
+                        config.capture_policy(),
+                        reqwest::Client::builder()
+                            .connect_timeout(config.connect_timeout())
+                            .build()
+                            .unwrap_or_default(),
+                    )
+                }),
+                (_, Some(path)) => AuditLogger::new(
+                    path.to_string(),
+                    config.observability.audit_log.max_file_bytes,
+                    config.observability.audit_log.overflow.clone(),
+                    config.capture_policy(),
+                )
+                .ok(),
+                (_, None) => None,
+            }
+        } else {
+            None
+        },
+        debug_capture,
+        access_logger: if config.observability.access_log.enabled {
+            Some(AccessLogger::new(
+                config.observability.access_log.path.clone(),
+                config.observability.access_log.fields.clone(),
+            ))
+        } else {
+            None
+        },
+        usage,
+        tap: TapRegistry::default(),
+        transcripts: config
+            .observability
+            .transcripts
+            .enabled
+            .then(|| {
+                TranscriptStore::new(&config.observability.transcripts, config.capture_policy())
+            }),
+        throttle: Default::default(),
+        input_limiter: Default::default(),
+        compaction: config
+            .compaction
+            .enabled
+            .then(|| Compactor::new(config.compaction.clone())),
+        tokenizers,
+        shadow: config.shadow.clone().map(|shadow| {
+            Shadow::new(
+                shadow,
+                reqwest::Client::builder()
+                    .connect_timeout(config.connect_timeout())
+                    .build()
+                    .unwrap_or_default(),
+                scoring.clone(),
+            )
+        }),
+        scoring,
+        billing: config.billing.clone().map(|billing| {
+            BillingForwarder::new(
+                billing,
+                reqwest::Client::builder()
+                    .connect_timeout(config.connect_timeout())
+                    .build()
+                    .unwrap_or_default(),
+            )
+        }),
+        prompts,
+        providers,
+        endpoints: EndpointPool::new(config),
+        downstream_keys: KeyPool::new(config),
+        router: ModelRouter::new(&config.routing)?,
+        vertex_auth,
+        oauth,
+        _tracer_provider: tracer_provider,
+    };
+    if let Some(warmup) = config.downstream.warmup.as_ref() {
+        let origins = warmup::origins(config);
+        warmup::warm_up(
+            warmup,
+            &origins,
+            &[&state.clients.client(), &state.clients.stream_client()],
+        )
+        .await;
+    }
+    if let Some(secs) = config.downstream.pool_recycle_secs {
+        state.clients.spawn_recycler(std::time::Duration::from_secs(secs));
+    }
+    Ok(state)
+}
+
+/// Routes enabled by `config`, with decompression, compression and CORS
+/// layers applied.
+pub fn build_router(config: &Config, state: AppState) -> Result<Router, String> {
+    edge_layers(config, build_routes(config, state)?)
+}
+
+/// Routes enabled by `config` with request decompression, but none of the
+/// `edge_layers`.
+pub fn build_routes(config: &Config, state: AppState) -> Result<Router, String> {
+    let mut app = Router::new()
+        .route("/v1/messages", post(post_messages))
+        .route("/v1/models", axum::routing::get(handlers::get_models))
+        .route("/health", axum::routing::get(handlers::health))
+        .route("/openapi.json", axum::routing::get(openapi::get_openapi));
+    // Templates can embed internal instructions, so reading them needs the
+    // admin token as well as publishing.
+    let prompts = Router::new()
+        .route(
+            "/v1/prompts",
+            axum::routing::get(handlers::list_prompts).post(handlers::publish_prompt),
+        )
+        .route("/v1/prompts/{id}", axum::routing::get(handlers::get_prompt))
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), admin::admin_auth));
+    app = app.merge(prompts);
+    if config.auxiliary.mode != "disabled" {
+        for path in config.auxiliary.responses.keys() {
+            app = app.route(path, axum::routing::get(handlers::get_auxiliary));
+        }
+    }
+    if !(config.forward_mode() == "passthrough" && config.anthropic.passthrough_unknown) {
+        app = app.route(
+            "/v1/messages/count_tokens",
+            post(handlers::count_tokens),
+        );
+    }
+    if config.anthropic.passthrough_unknown {
+        app = app.route("/v1/{*rest}", axum::routing::any(handlers::proxy_unknown));
+    }
+    if config.admin.token.is_some() {
+        app = app
+            .route("/admin/usage", axum::routing::get(admin::get_usage))
+            .route("/admin/tap/{request_id}", axum::routing::get(admin::get_tap))
+            .route("/admin/inflight", axum::routing::get(admin::get_inflight))
+            .route(
+                "/admin/inflight/{request_id}",
+                axum::routing::delete(admin::cancel_inflight),
+            )
+            .route("/admin/downstream-keys", axum::routing::get(admin::get_downstream_keys))
+            .route(
+                "/admin/downstream-keys/{id}/disable",
+                post(admin::disable_downstream_key),
+            )
+            .route(
+                "/admin/downstream-keys/{id}/enable",
+                post(admin::enable_downstream_key),
+            )
+            .route("/admin/downstream-pool/flush", post(admin::flush_downstream_pool));
+        if config.observability.transcripts.enabled {
+            app = app.route(
+                "/admin/transcripts/{request_id}",
+                axum::routing::get(admin::get_transcript),
+            );
+        }
+        if config.observability.debug_capture.enabled {
+            app = app
+                .route("/admin/debug-keys", axum::routing::get(admin::get_debug_keys))
+                .route(
+                    "/admin/debug-keys/{key_id}",
+                    post(admin::flag_debug_key).delete(admin::unflag_debug_key),
+                );
+        }
+        if config.admin.dashboard {
+            app = app
+                .route("/dashboard", axum::routing::get(dashboard::get_dashboard))
+                .route("/admin/dashboard/stats", axum::routing::get(dashboard::get_stats))
+                .route(
+                    "/admin/dashboard/requests",
+                    axum::routing::get(dashboard::get_recent_requests),
+                );
+        }
+    }
+    // A route layer, so the middleware sees the matched route template.
+    Ok(app
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.metrics.clone(),
+            metrics::record_request,
+        ))
+        .with_state(state)
+        .layer(RequestDecompressionLayer::new()))
+}
+
+/// Signing, IP access, response compression and CORS. Applied once around
+/// everything `config` serves, a canary split included, so both configs
+/// share one replay set and one per-IP inflight count.
+pub fn edge_layers(config: &Config, mut app: Router) -> Result<Router, String> {
+    // Outside decompression, so signatures cover the body as sent.
+    if let Some(signing) = config.server.signing.as_ref() {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            RequestVerifier::new(signing),
+            signing::verify_signature,
+        ));
+    }
+    if let Some(ip_access) = config.server.ip_access.as_ref() {
+        app = app.layer(axum::middleware::from_fn_with_state(
+            IpAccess::new(ip_access)?,
+            ip_access::check_ip,
+        ));
+    }
+    if config.compression.responses {
+        app = app.layer(CompressionLayer::new());
+    }
+    if let Some(cors) = config.server.cors.as_ref() {
+        let layer = cors::cors_layer(cors).map_err(|e| format!("config error: {}", e))?;
+        app = app.layer(layer);
+    }
+    Ok(app)
+}
@@ -0,0 +1,329 @@
+use axum::http::HeaderMap;
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry::KeyValue;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::access_log::RequestSummary;
+use crate::config::ShadowConfig;
+use crate::scoring::Scoring;
+
+/// How long a shadow result waits for the primary request to finish before
+/// the comparison is abandoned.
+const PRIMARY_WAIT: Duration = Duration::from_secs(600);
+
+/// Spreads sampled events evenly: event `n` is picked when
+/// `n * percent / 100` crosses an integer boundary.
+pub fn evenly_sampled(counter: &AtomicU64, percent: f64) -> bool {
+    let n = counter.fetch_add(1, Ordering::Relaxed) as f64;
+    let ratio = percent / 100.0;
+    ((n + 1.0) * ratio).floor() > (n * ratio).floor()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadowProtocol {
+    Anthropic,
+    OpenAI,
+}
+
+#[derive(Clone)]
+struct ShadowMetrics {
+    requests: Counter<u64>,
+    latency_ms: Histogram<f64>,
+    output_tokens: Counter<u64>,
+    status_match: Counter<u64>,
+}
+
+impl ShadowMetrics {
+    fn new() -> Self {
+        let meter = opentelemetry::global::meter("llm-gateway");
+        Self {
+            requests: meter
+                .u64_counter("ai.gateway.shadow.requests")
+                .with_description("Shadow requests by result")
+                .build(),
+            latency_ms: meter
+                .f64_histogram("ai.gateway.shadow.latency_ms")
+                .with_unit("ms")
+                .with_description("Latency of shadowed requests by target (primary/shadow)")
+                .build(),
+            output_tokens: meter
+                .u64_counter("ai.gateway.shadow.output_tokens")
+                .with_description("Output tokens of shadowed requests by target (primary/shadow)")
+                .build(),
+            status_match: meter
+                .u64_counter("ai.gateway.shadow.status_match")
+                .with_description("Shadowed requests by whether primary and shadow status classes agree")
+                .build(),
+        }
+    }
+}
+
+/// Outcome of a shadow request, compared against the primary summary.
+#[derive(Debug, Default)]
+struct ShadowResult {
+    status: u16,
+    latency_ms: u64,
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    output: Option<String>,
+}
+
+/// Samples requests and mirrors them to the shadow downstream in the
+/// background. The primary request's summary is handed over through
+/// [`Shadow::complete`] so both sides can be compared.
+#[derive(Clone)]
+pub struct Shadow {
+    config: ShadowConfig,
+    client: reqwest::Client,
+    counter: Arc<AtomicU64>,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<RequestSummary>>>>,
+    metrics: ShadowMetrics,
+    scoring: Option<Scoring>,
+}
+
+impl Shadow {
+    pub fn new(config: ShadowConfig, client: reqwest::Client, scoring: Option<Scoring>) -> Self {
+        Self {
+            config,
+            client,
+            counter: Arc::new(AtomicU64::new(0)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            metrics: ShadowMetrics::new(),
+            scoring,
+        }
+    }
+
+    /// Whether this request is mirrored; decided before the body is copied
+    /// so unsampled requests never pay for it.
+    pub fn sample(&self) -> bool {
+        evenly_sampled(&self.counter, self.config.percent)
+    }
+
+    /// Mirrors a request picked by [`Shadow::sample`].
+    pub fn mirror(
+        &self,
+        request_id: &str,
+        protocol: ShadowProtocol,
+        mut body: Value,
+        headers: &HeaderMap,
+    ) {
+        if let Some(obj) = body.as_object_mut() {
+            obj.insert("stream".to_string(), Value::Bool(false));
+            obj.remove("stream_options");
+            if let Some(model) = self.config.model.as_ref() {
+                obj.insert("model".to_string(), Value::String(model.clone()));
+            }
+        }
+        let request = self.build_request(protocol, headers).json(&body);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request_id.to_string(), tx);
+
+        let shadow = self.clone();
+        let request_id = request_id.to_string();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = match request.send().await {
+                Ok(resp) => {
+                    let status = resp.status().as_u16();
+                    let body = resp.json::<Value>().await.unwrap_or(Value::Null);
+                    let mut result = ShadowResult {
+                        status,
+                        latency_ms: started.elapsed().as_millis() as u64,
+                        ..Default::default()
+                    };
+                    result.apply_body(protocol, &body);
+                    result
+                }
+                Err(err) => {
+                    tracing::warn!(request_id = %request_id, "shadow request failed: {}", err);
+                    ShadowResult {
+                        latency_ms: started.elapsed().as_millis() as u64,
+                        ..Default::default()
+                    }
+                }
+            };
+            let outcome = match result.status {
+                0 => "error",
+                200..=299 => "ok",
+                _ => "http_error",
+            };
+            shadow
+                .metrics
+                .requests
+                .add(1, &[KeyValue::new("result", outcome)]);
+            let primary = match tokio::time::timeout(PRIMARY_WAIT, rx).await {
+                Ok(Ok(primary)) => primary,
+                _ => {
+                    shadow
+                        .pending
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .remove(&request_id);
+                    return;
+                }
+            };
+            shadow.compare(&primary, &result);
+            if let Some((scoring, output)) = shadow.scoring.as_ref().zip(result.output) {
+                scoring.score_shadow(&primary, output);
+            }
+        });
+    }
+
+    /// Hands the finished primary request to its pending shadow comparison.
+    pub fn complete(&self, summary: &RequestSummary) {
+        let sender = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&summary.request_id);
+        if let Some(sender) = sender {
+            let _ = sender.send(summary.clone());
+        }
+    }
+
+    fn build_request(&self, protocol: ShadowProtocol, headers: &HeaderMap) -> reqwest::RequestBuilder {
+        let base = self.config.base_url.trim_end_matches('/');
+        let base = base.strip_suffix("/v1").unwrap_or(base);
+        let api_key = self.config.api_key.as_deref().unwrap_or_default();
+        let builder = match protocol {
+            ShadowProtocol::Anthropic => {
+                let mut builder = self
+                    .client
+                    .post(format!("{}/v1/messages", base))
+                    .header("x-api-key", api_key)
+                    .header(
+                        "anthropic-version",
+                        headers
+                            .get("anthropic-version")
+                            .and_then(|v| v.to_str().ok())
+                            .unwrap_or("2023-06-01"),
+                    );
+                if let Some(beta) = headers.get("anthropic-beta") {
+                    builder = builder.header("anthropic-beta", beta.clone());
+                }
+                builder
+            }
+            ShadowProtocol::OpenAI => self
+                .client
+                .post(format!("{}/v1/chat/completions", base))
+                .header(AUTHORIZATION, format!("Bearer {}", api_key)),
+        };
+        builder
+            .header(CONTENT_TYPE, "application/json")
+            .timeout(Duration::from_millis(self.config.timeout_ms))
+    }
+
+    fn compare(&self, primary: &RequestSummary, shadow: &ShadowResult) {
+        let primary_target = [KeyValue::new("target", "primary")];
+        let shadow_target = [KeyValue::new("target", "shadow")];
+        self.metrics
+            .latency_ms
+            .record(primary.latency_ms as f64, &primary_target);
+        self.metrics
+            .latency_ms
+            .record(shadow.latency_ms as f64, &shadow_target);
+        if let Some(tokens) = primary.output_tokens {
+            self.metrics.output_tokens.add(tokens, &primary_target);
+        }
+        if let Some(tokens) = shadow.output_tokens {
+            self.metrics.output_tokens.add(tokens, &shadow_target);
+        }
+        let status_match = primary.status / 100 == shadow.status / 100;
+        self.metrics
+            .status_match
+            .add(1, &[KeyValue::new("match", status_match)]);
+        tracing::info!(
+            request_id = %primary.request_id,
+            primary_status = primary.status,
+            shadow_status = shadow.status,
+            primary_latency_ms = primary.latency_ms,
+            shadow_latency_ms = shadow.latency_ms,
+            primary_input_tokens = primary.input_tokens.unwrap_or_default(),
+            shadow_input_tokens = shadow.input_tokens.unwrap_or_default(),
+            primary_output_tokens = primary.output_tokens.unwrap_or_default(),
+            shadow_output_tokens = shadow.output_tokens.unwrap_or_default(),
+            "shadow comparison"
+        );
+    }
+}
+
+impl ShadowResult {
+    fn apply_body(&mut self, protocol: ShadowProtocol, body: &Value) {
+        self.output = match protocol {
+            ShadowProtocol::Anthropic => body.get("content").and_then(Value::as_array).map(|blocks| {
+                blocks
+                    .iter()
+                    .filter_map(|b| b.get("text").and_then(Value::as_str))
+                    .collect::<Vec<_>>()
+                    .join("")
+            }),
+            ShadowProtocol::OpenAI => body
+                .pointer("/choices/0/message/content")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        };
+        self.apply_usage(protocol, body);
+    }
+
+    fn apply_usage(&mut self, protocol: ShadowProtocol, body: &Value) {
+        let Some(usage) = body.get("usage") else {
+            return;
+        };
+        let (input, output) = match protocol {
+            ShadowProtocol::Anthropic => ("input_tokens", "output_tokens"),
+            ShadowProtocol::OpenAI => ("prompt_tokens", "completion_tokens"),
+        };
+        self.input_tokens = usage.get(input).and_then(Value::as_u64);
+        self.output_tokens = usage.get(output).and_then(Value::as_u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shadow(percent: f64) -> Shadow {
+        Shadow::new(
+            ShadowConfig {
+                base_url: "http://127.0.0.1:9".to_string(),
+                api_key: None,
+                model: None,
+                percent,
+                timeout_ms: 1_000,
+            },
+            reqwest::Client::new(),
+            None,
+        )
+    }
+
+    #[test]
+    fn sampling_spreads_requests_evenly() {
+        let quarter = shadow(25.0);
+        let picked: Vec<bool> = (0..8).map(|_| quarter.sample()).collect();
+        assert_eq!(picked.iter().filter(|p| **p).count(), 2);
+        assert!(!shadow(0.0).sample());
+        let all = shadow(100.0);
+        assert!((0..5).all(|_| all.sample()));
+    }
+
+    #[test]
+    fn usage_and_output_are_read_per_protocol() {
+        let mut result = ShadowResult::default();
+        let body = serde_json::json!({
+            "choices": [{"message": {"content": "hi"}}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 3}
+        });
+        result.apply_body(ShadowProtocol::OpenAI, &body);
+        assert_eq!((result.input_tokens, result.output_tokens), (Some(12), Some(3)));
+        assert_eq!(result.output.as_deref(), Some("hi"));
+    }
+}
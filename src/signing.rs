@@ -0,0 +1,228 @@
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, Method};
+use futures_util::StreamExt;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::SigningConfig;
+use crate::error::AppError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `server.signing`: callers sign each request with a shared secret as
+/// hex HMAC-SHA256 over `"{timestamp}\n{METHOD}\n{path and query}\n{body}"`,
+/// sending the unix timestamp and signature in headers. Requests outside the
+/// replay window, with a bad signature, or repeating a signature already
+/// seen inside the window are rejected with 401.
+#[derive(Clone)]
+pub struct RequestVerifier {
+    config: Arc<SigningConfig>,
+    /// Signatures accepted within the window, with their timestamps.
+    seen: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl RequestVerifier {
+    pub fn new(config: &SigningConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            seen: Default::default(),
+        }
+    }
+
+    pub fn verify(
+        &self,
+        method: &Method,
+        path_and_query: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<(), String> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .ok_or_else(|| format!("missing {} header", name))
+        };
+        let timestamp = header(&self.config.timestamp_header)?;
+        let signature = header(&self.config.signature_header)?.to_ascii_lowercase();
+        let ts: u64 = timestamp
+            .parse()
+            .map_err(|_| format!("invalid {} header", self.config.timestamp_header))?;
+        if ts.abs_diff(now) > self.config.replay_window_secs {
+            return Err("request timestamp outside the replay window".to_string());
+        }
+        let mut mac = HmacSha256::new_from_slice(self.config.secret.as_bytes()).expect("hmac accepts any key length");
+        mac.update(format!("{}\n{}\n{}\n", timestamp, method, path_and_query).as_bytes());
+        mac.update(body);
+        let expected = decode_hex(&signature).ok_or("invalid request signature")?;
+        mac.verify_slice(&expected).map_err(|_| "invalid request signature".to_string())?;
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let window = self.config.replay_window_secs;
+        seen.retain(|_, ts| ts.abs_diff(now) <= window);
+        if seen.insert(signature, ts).is_some() {
+            return Err("request signature already used".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Middleware that buffers the body (up to `max_body_bytes`, else 413) to
+/// check its signature and passes the request on unchanged. `exempt_paths`
+/// (by default `/health`) skip the check.
+pub async fn verify_signature(State(verifier): State<RequestVerifier>, request: Request, next: Next) -> Response {
+    if verifier.config.exempt_paths.iter().any(|path| path == request.uri().path()) {
+        return next.run(request).await;
+    }
+    let (parts, body) = request.into_parts();
+    let body = match read_capped(&parts.headers, body, verifier.config.max_body_bytes).await {
+        Ok(body) => body,
+        Err(err) => return err.into_response(),
+    };
+    let path_and_query = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    if let Err(reason) = verifier.verify(&parts.method, path_and_query, &parts.headers, &body, unix_now()) {
+        tracing::warn!(method = %parts.method, path = %parts.uri.path(), reason = %reason, "request signature rejected");
+        return AppError::unauthorized(reason).into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+/// Reads the body, stopping as soon as it (or its declared length) is over
+/// `max` so an unauthenticated caller cannot make the gateway buffer more.
+async fn read_capped(headers: &HeaderMap, body: Body, max: usize) -> Result<Bytes, AppError> {
+    let too_large = || AppError::payload_too_large(format!("request body exceeds {} bytes", max));
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > max as u64) {
+        return Err(too_large());
+    }
+    let mut stream = body.into_data_stream();
+    let mut buf = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|err| AppError::invalid_request(format!("invalid request body: {}", err)))?;
+        if buf.len() + chunk.len() > max {
+            return Err(too_large());
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buf))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderValue, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn sign(secret: &str, timestamp: u64, method: &str, path: &str, body: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}\n{}\n{}\n{}", timestamp, method, path, body).as_bytes());
+        mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn headers(timestamp: u64, signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-gateway-timestamp", HeaderValue::from_str(&timestamp.to_string()).unwrap());
+        headers.insert("x-gateway-signature", HeaderValue::from_str(signature).unwrap());
+        headers
+    }
+
+    #[test]
+    fn accepts_signed_requests_once_within_window() {
+        let config: SigningConfig = serde_yaml::from_str("secret: s3cret\nreplay_window_secs: 300\n").unwrap();
+        let verifier = RequestVerifier::new(&config);
+        let body = "{\"model\":\"claude\"}";
+        let now = 1_700_000_000;
+        let signature = sign("s3cret", now - 10, "POST", "/v1/messages?beta=true", body);
+        let post = Method::POST;
+
+        assert_eq!(
+            verifier.verify(&post, "/v1/messages?beta=true", &headers(now - 10, &signature), body.as_bytes(), now),
+            Ok(())
+        );
+        assert_eq!(
+            verifier.verify(&post, "/v1/messages?beta=true", &headers(now - 10, &signature), body.as_bytes(), now),
+            Err("request signature already used".to_string())
+        );
+        let tampered = verifier.verify(&post, "/v1/messages", &headers(now, &signature), b"{}", now);
+        assert_eq!(tampered, Err("invalid request signature".to_string()));
+        let stale = sign("s3cret", now - 301, "POST", "/v1/messages", body);
+        assert!(verifier
+            .verify(&post, "/v1/messages", &headers(now - 301, &stale), body.as_bytes(), now)
+            .unwrap_err()
+            .contains("replay window"));
+        assert_eq!(
+            verifier.verify(&post, "/v1/messages", &HeaderMap::new(), b"", now),
+            Err("missing x-gateway-timestamp header".to_string())
+        );
+    }
+
+    fn router(max_body_bytes: usize) -> Router {
+        let config: SigningConfig =
+            serde_yaml::from_str(&format!("secret: s3cret\nmax_body_bytes: {}\n", max_body_bytes)).unwrap();
+        Router::new()
+            .route("/v1/messages", post(|body: Bytes| async move { body }))
+            .layer(axum::middleware::from_fn_with_state(RequestVerifier::new(&config), verify_signature))
+    }
+
+    fn signed_request(body: &str) -> Request {
+        let now = unix_now();
+        let signature = sign("s3cret", now, "POST", "/v1/messages", body);
+        let mut request = Request::post("/v1/messages").body(Body::from(body.to_string())).unwrap();
+        request.headers_mut().extend(headers(now, &signature));
+        request.headers_mut().insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        request
+    }
+
+    #[tokio::test]
+    async fn middleware_passes_signed_requests_and_rejects_oversized_bodies() {
+        let body = "{\"model\":\"claude\"}";
+        let response = router(64).oneshot(signed_request(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let echoed = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(echoed, body.as_bytes());
+
+        let response = router(8).oneshot(signed_request(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // A body longer than it declared is cut off while it is read.
+        let chunks = futures_util::stream::iter([Ok::<_, std::io::Error>(Bytes::from(body))]);
+        let mut request = signed_request("");
+        *request.body_mut() = Body::from_stream(chunks);
+        let response = router(8).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut unsigned = signed_request(body);
+        unsigned.headers_mut().remove("x-gateway-signature");
+        let response = router(64).oneshot(unsigned).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,132 @@
+/// One dispatched server-sent event. `data` joins multi-line `data:` fields
+/// with `\n`; `comment` marks blocks that carried `:` comment lines (commonly
+/// keepalives), which are dispatched even without data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: String,
+    pub comment: bool,
+}
+
+/// Incremental SSE parser following the WHATWG event-stream rules: `\n`,
+/// `\r\n` and `\r` line endings, `event:` names, multi-line `data:` fields
+/// and comment lines. Bytes are buffered until a full line arrives, so
+/// multi-byte characters split across chunks decode correctly.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    skip_lf: bool,
+    event: Option<String>,
+    data: Vec<String>,
+    comment: bool,
+}
+
+impl SseParser {
+    /// Feeds a raw chunk and returns the events completed by it.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if std::mem::take(&mut self.skip_lf) && byte == b'\n' {
+                continue;
+            }
+            match byte {
+                b'\n' | b'\r' => {
+                    self.skip_lf = byte == b'\r';
+                    let line = std::mem::take(&mut self.buffer);
+                    self.line(&String::from_utf8_lossy(&line), &mut events);
+                }
+                _ => self.buffer.push(byte),
+            }
+        }
+        events
+    }
+
+    /// Dispatches whatever is left when the stream ends without a final
+    /// blank line.
+    pub fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let line = std::mem::take(&mut self.buffer);
+        if !line.is_empty() {
+            self.line(&String::from_utf8_lossy(&line), &mut events);
+        }
+        self.dispatch(&mut events);
+        events
+    }
+
+    fn line(&mut self, line: &str, events: &mut Vec<SseEvent>) {
+        if line.is_empty() {
+            self.dispatch(events);
+            return;
+        }
+        if line.starts_with(':') {
+            self.comment = true;
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self, events: &mut Vec<SseEvent>) {
+        let event = SseEvent {
+            event: self.event.take().filter(|name| !name.is_empty()),
+            data: std::mem::take(&mut self.data).join("\n"),
+            comment: std::mem::take(&mut self.comment),
+        };
+        if event != SseEvent::default() {
+            events.push(event);
+        }
+    }
+}
+
+/// End of the first complete event in raw stream bytes (after its blank
+/// line), for splitting a stream into events without re-serializing them.
+pub fn event_end(buffer: &[u8]) -> Option<usize> {
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|i| i + 2);
+    let crlf = buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4);
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: Option<&str>, data: &str) -> SseEvent {
+        SseEvent {
+            event: name.map(str::to_string),
+            data: data.to_string(),
+            comment: false,
+        }
+    }
+
+    #[test]
+    fn parses_events_across_chunks_and_line_endings() {
+        let mut parser = SseParser::default();
+        let mut events = parser.push(b"event: message_start\r\ndata: {\"a\":\r");
+        events.extend(parser.push(b"\ndata: 1}\r\n\r\n: keepalive\n\ndata:x\rdata\r\r"));
+        events.extend(parser.push("data: é".as_bytes().split_at(7).0));
+        events.extend(parser.push(&"data: é".as_bytes()[7..]));
+        events.extend(parser.finish());
+        assert_eq!(
+            events,
+            vec![
+                event(Some("message_start"), "{\"a\":\n1}"),
+                SseEvent {
+                    comment: true,
+                    ..SseEvent::default()
+                },
+                event(None, "x\n"),
+                event(None, "é"),
+            ]
+        );
+    }
+}
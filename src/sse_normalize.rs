@@ -0,0 +1,140 @@
+use serde_json::{json, Map, Value};
+
+use crate::sse::SseEvent;
+
+/// Top-level fields kept for each Anthropic stream event type; anything else
+/// is provider-specific and dropped.
+const EVENT_FIELDS: &[(&str, &[&str])] = &[
+    ("message_start", &["type", "message"]),
+    ("content_block_start", &["type", "index", "content_block"]),
+    ("content_block_delta", &["type", "index", "delta"]),
+    ("content_block_stop", &["type", "index"]),
+    ("message_delta", &["type", "delta", "usage"]),
+    ("message_stop", &["type"]),
+    ("ping", &["type"]),
+    ("error", &["type", "error"]),
+];
+
+/// Re-frames one passthrough event as a well-formed Anthropic event: it is
+/// emitted as an `event:`/`data:` pair named after its `type`, keepalive
+/// comments become `ping` events, non-standard error payloads become `error`
+/// events, and unknown events or fields are dropped (`None`).
+pub fn normalize(event: &SseEvent) -> Option<String> {
+    if event.data.is_empty() {
+        return (event.comment || event.event.as_deref() == Some("ping"))
+            .then(|| frame(json!({"type": "ping"})));
+    }
+    normalize_event(event.event.as_deref(), &event.data).map(frame)
+}
+
+fn normalize_event(event: Option<&str>, data: &str) -> Option<Value> {
+    if data.trim() == "[DONE]" {
+        return None;
+    }
+    let value = match serde_json::from_str::<Value>(data) {
+        Ok(Value::Object(obj)) => obj,
+        _ if event == Some("error") => return Some(error_body("api_error", data.trim())),
+        _ => return None,
+    };
+    let kind = value
+        .get("type")
+        .and_then(Value::as_str)
+        .or(event)
+        .unwrap_or_default()
+        .to_string();
+    if kind == "error" || (value.get("error").is_some() && !is_known(&kind)) {
+        return Some(normalize_error(&value));
+    }
+    let fields = EVENT_FIELDS.iter().find(|(name, _)| *name == kind)?.1;
+    let mut body: Map<String, Value> = value
+        .into_iter()
+        .filter(|(key, _)| fields.contains(&key.as_str()))
+        .collect();
+    body.insert("type".to_string(), Value::String(kind.clone()));
+    if kind == "message_delta"
+        && let Some(delta) = body.get_mut("delta").and_then(Value::as_object_mut)
+    {
+        delta.entry("stop_sequence").or_insert(Value::Null);
+    }
+    Some(Value::Object(body))
+}
+
+fn is_known(kind: &str) -> bool {
+    EVENT_FIELDS.iter().any(|(name, _)| *name == kind)
+}
+
+/// Accepts `{"type":"error","error":{...}}`, `{"error":{...}}` and
+/// `{"error":"message"}` shapes.
+fn normalize_error(value: &Map<String, Value>) -> Value {
+    match value.get("error") {
+        Some(Value::Object(error)) => error_body(
+            error.get("type").and_then(Value::as_str).unwrap_or("api_error"),
+            error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("upstream stream error"),
+        ),
+        Some(Value::String(message)) => error_body("api_error", message),
+        _ => error_body(
+            "api_error",
+            value
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("upstream stream error"),
+        ),
+    }
+}
+
+fn error_body(error_type: &str, message: &str) -> Value {
+    json!({"type": "error", "error": {"type": error_type, "message": message}})
+}
+
+fn frame(body: Value) -> String {
+    let event = body.get("type").and_then(Value::as_str).unwrap_or("ping").to_string();
+    format!("event: {}\ndata: {}\n\n", event, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sse::SseParser;
+
+    fn normalize_all(chunks: &[&[u8]]) -> String {
+        let mut parser = SseParser::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            events.extend(parser.push(chunk));
+        }
+        events.extend(parser.finish());
+        events.iter().filter_map(normalize).collect()
+    }
+
+    #[test]
+    fn reframes_events_and_strips_unknown_fields() {
+        let out = normalize_all(&[
+            b"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"hi\"},\"provider_seq\":7}\r\n\r\n: keepalive\n\nevent: message_delta\ndata: {\"type\":\"message_delta\",",
+            b"\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":3}}\n\n",
+            b"event: vendor_stats\ndata: {\"type\":\"vendor_stats\"}\n\ndata: [DONE]\n\n",
+        ]);
+        assert_eq!(
+            out,
+            "event: content_block_delta\ndata: {\"delta\":{\"text\":\"hi\",\"type\":\"text_delta\"},\"index\":0,\"type\":\"content_block_delta\"}\n\n\
+             event: ping\ndata: {\"type\":\"ping\"}\n\n\
+             event: message_delta\ndata: {\"delta\":{\"stop_reason\":\"end_turn\",\"stop_sequence\":null},\"type\":\"message_delta\",\"usage\":{\"output_tokens\":3}}\n\n"
+        );
+    }
+
+    #[test]
+    fn normalizes_error_payloads() {
+        let out = normalize_all(&[
+            b"event: error\ndata: upstream overloaded\n\ndata: {\"error\":{\"message\":\"quota\",\"code\":429}}\n\n",
+            b"event: message_stop\ndata: {\"type\":\"message_stop\"}",
+        ]);
+        assert_eq!(
+            out,
+            "event: error\ndata: {\"error\":{\"message\":\"upstream overloaded\",\"type\":\"api_error\"},\"type\":\"error\"}\n\n\
+             event: error\ndata: {\"error\":{\"message\":\"quota\",\"type\":\"api_error\"},\"type\":\"error\"}\n\n\
+             event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n"
+        );
+    }
+}
@@ -1,30 +1,255 @@
-use crate::config::Config;
-use crate::audit_log::AuditLogger;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use crate::client_pool::DownstreamClients;
+use crate::compaction::Compactor;
+use crate::debug_capture::DebugCapture;
+use crate::config::{Config, DownstreamEndpoint, EndpointHealthConfig};
+use crate::access_log::{AccessLogger, RequestSummary};
+use crate::audit_log::{AuditLogRecord, AuditLogger};
+use crate::billing::BillingForwarder;
+use crate::inflight::{InflightRegistry, InflightTicket};
+use crate::key_pool::KeyPool;
+use crate::prompts::PromptRegistry;
+use crate::provider::{set_max_tokens_field, DownstreamRequest, OpenAIProvider, Provider, ProviderRegistry};
+use crate::routing::ModelRouter;
+use crate::scoring::Scoring;
+use crate::shadow::Shadow;
+use crate::tap::TapRegistry;
+use crate::throttle::{InputLimiter, OutputThrottle};
+use crate::tokenizer::Tokenizers;
+use crate::transcripts::TranscriptStore;
+use crate::usage::{summary_cost, UsageStore};
+use crate::vertex::VertexAuth;
+use axum::body::Bytes;
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}};
+use std::time::{Duration, Instant};
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use crate::metrics::Metrics;
+use crate::error::{retryable_status, AppError};
+use crate::oauth::OAuthTokens;
+use opentelemetry::KeyValue;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub client: reqwest::Client,
-    pub stream_client: reqwest::Client,
+    pub clients: DownstreamClients,
     pub config: Config,
     pub inflight: Arc<Semaphore>,
     pub inflight_count: Arc<AtomicU64>,
+    pub inflight_requests: InflightRegistry,
     pub metrics: Metrics,
     pub audit_logger: Option<AuditLogger>,
+    pub debug_capture: Option<DebugCapture>,
+    pub access_logger: Option<AccessLogger>,
+    pub usage: UsageStore,
+    pub tap: TapRegistry,
+    pub transcripts: Option<TranscriptStore>,
+    pub throttle: OutputThrottle,
+    pub input_limiter: InputLimiter,
+    pub compaction: Option<Compactor>,
+    pub tokenizers: Tokenizers,
+    pub shadow: Option<Shadow>,
+    pub scoring: Option<Scoring>,
+    pub billing: Option<BillingForwarder>,
+    pub prompts: PromptRegistry,
+    pub providers: ProviderRegistry,
+    pub endpoints: EndpointPool,
+    pub router: ModelRouter,
+    pub vertex_auth: Option<VertexAuth>,
+    pub oauth: Option<OAuthTokens>,
+    pub downstream_keys: KeyPool,
     pub _tracer_provider: opentelemetry_sdk::trace::SdkTracerProvider,
 }
 
+impl AppState {
+    /// The provider serving a downstream model; `build_state` has checked
+    /// that every configured name is registered.
+    pub fn provider_for(&self, model: &str) -> Arc<dyn Provider> {
+        self.providers
+            .get(self.config.provider_name(model))
+            .unwrap_or_else(|| Arc::new(OpenAIProvider))
+    }
+
+    /// Sends a finished audit record to the audit log and, for a request in
+    /// debug capture, to the debug file.
+    pub async fn push_audit(&self, record: AuditLogRecord) {
+        let debug_record = record.meta.debug.then(|| record.clone());
+        self.push_audit_records(record, debug_record).await;
+    }
+
+    /// Like `push_audit`, for a request whose debug capture kept its own
+    /// copy of the exchange (its own body limit, both SSE sides, the
+    /// downstream side of a translation).
+    pub async fn push_audit_records(&self, record: AuditLogRecord, debug_record: Option<AuditLogRecord>) {
+        if let (Some(debug), Some(debug_record)) = (self.debug_capture.as_ref(), debug_record) {
+            debug.push(debug_record).await;
+        }
+        if let Some(logger) = self.audit_logger.as_ref() {
+            logger.push(record).await;
+        }
+    }
+
+    pub fn record_summary(&self, mut summary: RequestSummary) {
+        if summary.cost_usd.is_none() {
+            summary.cost_usd = Some(summary_cost(&summary, &self.config.models.pricing));
+        }
+        if let Some(downstream_ms) = summary.downstream_ms {
+            let attrs = [KeyValue::new("stream", summary.stream.to_string())];
+            if let Some(ttfb_ms) = summary.downstream_ttfb_ms {
+                self.metrics.downstream_ttfb_ms.record(ttfb_ms as f64, &attrs);
+            }
+            self.metrics.downstream_ms.record(downstream_ms as f64, &attrs);
+            let overhead_ms = summary.latency_ms.saturating_sub(downstream_ms);
+            self.metrics.overhead_ms.record(overhead_ms as f64, &attrs);
+        }
+        if let Some(scoring) = self.scoring.as_ref() {
+            scoring.score_experiment(&summary);
+        }
+        if let Some(shadow) = self.shadow.as_ref() {
+            shadow.complete(&summary);
+        }
+        // Scorers have their copies; everything past here keeps only what
+        // the capture policy allows.
+        summary.apply_capture(self.config.capture_policy());
+        if let Some(logger) = self.access_logger.as_ref() {
+            logger.log(&summary);
+        }
+        if let Some(billing) = self.billing.as_ref() {
+            billing.emit(&summary);
+        }
+        self.usage.record(summary);
+    }
+
+    /// Bearer credential for gateway-built downstream calls: the OAuth token
+    /// when `downstream.oauth` is set, the next `downstream.api_keys` entry,
+    /// else `api_key`.
+    pub async fn downstream_bearer(&self) -> Result<String, AppError> {
+        if let Some(oauth) = self.oauth.as_ref() {
+            return oauth.token().await.map_err(AppError::api_error);
+        }
+        if !self.downstream_keys.is_empty() {
+            return match self.downstream_keys.attempts().into_iter().next() {
+                Some(key) => Ok(key.key),
+                None => Err(AppError::api_error("all downstream api keys are disabled")),
+            };
+        }
+        Ok(self.config.downstream.api_key.clone().unwrap_or_default())
+    }
+
+    /// Sends a `translate` downstream call and reports the outcome to
+    /// endpoint health. Calls that would use `api_key` (no key of their own
+    /// from `provider_endpoints` or `endpoints`) get the managed credential:
+    /// with `downstream.oauth` the token, where a 401 drops it and retries
+    /// once with a fresh one; with `downstream.api_keys` the pool's key,
+    /// failing over to the next key when configured. `body` is
+    /// `downstream.encoded_body()`, sent as is on every attempt.
+    pub async fn send_downstream(
+        &self,
+        client: &reqwest::Client,
+        downstream: &DownstreamRequest,
+        body: &Bytes,
+    ) -> Result<reqwest::Response, AppError> {
+        if let Some(oauth) = self.oauth.as_ref()
+            && self.uses_default_key(downstream)
+        {
+            let token = oauth.token().await.map_err(AppError::api_error)?;
+            let resp = self.post_downstream(client, downstream, body, Some(&token)).await?;
+            if resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+                return Ok(resp);
+            }
+            oauth.invalidate(&token).await;
+            let token = oauth.token().await.map_err(AppError::api_error)?;
+            return self.post_downstream(client, downstream, body, Some(&token)).await;
+        }
+        if self.downstream_keys.is_empty() || !self.uses_default_key(downstream) {
+            return self.post_downstream(client, downstream, body, None).await;
+        }
+        let attempts = self.downstream_keys.attempts();
+        let Some((last, rest)) = attempts.split_last() else {
+            return Err(AppError::api_error("all downstream api keys are disabled"));
+        };
+        for key in rest {
+            let resp = self.post_downstream(client, downstream, body, Some(&key.key)).await;
+            self.downstream_keys.record(&key.id, &resp);
+            match resp {
+                Ok(resp) if self.downstream_keys.should_fail_over(resp.status()) => {
+                    tracing::warn!(key = %key.id, status = resp.status().as_u16(), "downstream key rejected, failing over");
+                }
+                other => return other,
+            }
+        }
+        let resp = self.post_downstream(client, downstream, body, Some(&last.key)).await;
+        self.downstream_keys.record(&last.id, &resp);
+        resp
+    }
+
+    /// Whether the provider set `downstream.api_key` rather than a key of its
+    /// own (`provider_endpoints`, `endpoints`). An unset key and a missing or
+    /// empty credential count as the same.
+    fn uses_default_key(&self, downstream: &DownstreamRequest) -> bool {
+        let default = self.config.downstream.api_key.as_deref().unwrap_or_default().trim();
+        bearer_key(&downstream.headers).unwrap_or_default() == default
+    }
+
+    async fn post_downstream(
+        &self,
+        client: &reqwest::Client,
+        downstream: &DownstreamRequest,
+        body: &Bytes,
+        token: Option<&str>,
+    ) -> Result<reqwest::Response, AppError> {
+        let mut headers = downstream.headers.clone();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if let Some(token) = token
+            && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token))
+        {
+            headers.insert(AUTHORIZATION, value);
+        }
+        let sent = Instant::now();
+        let resp = client
+            .post(&downstream.url)
+            .headers(headers)
+            .body(body.clone())
+            .send()
+            .await;
+        self.endpoints.observe(&downstream.url, sent, &resp);
+        resp.map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))
+    }
+}
+
 pub struct InflightGuard {
     _permit: OwnedSemaphorePermit,
     counter: Arc<AtomicU64>,
+    ticket: Option<InflightTicket>,
 }
 
 impl InflightGuard {
     pub fn new(permit: OwnedSemaphorePermit, counter: Arc<AtomicU64>) -> Self {
         counter.fetch_add(1, Ordering::Relaxed);
-        Self { _permit: permit, counter }
+        Self {
+            _permit: permit,
+            counter,
+            ticket: None,
+        }
+    }
+
+    /// Keeps the request listed in the inflight registry for as long as the
+    /// guard lives.
+    pub fn with_ticket(mut self, ticket: InflightTicket) -> Self {
+        self.ticket = Some(ticket);
+        self
+    }
+
+    /// Resolves once the request is cancelled; never for untracked requests.
+    pub async fn cancelled(&self) {
+        match self.ticket.as_ref() {
+            Some(ticket) => ticket.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.ticket.as_ref().is_some_and(InflightTicket::is_cancelled)
     }
 }
 
@@ -33,3 +258,214 @@ impl Drop for InflightGuard {
         self.counter.fetch_sub(1, Ordering::Relaxed);
     }
 }
+
+/// `downstream.endpoints` with rolling latency and error stats per endpoint.
+#[derive(Clone, Default)]
+pub struct EndpointPool {
+    endpoints: Vec<DownstreamEndpoint>,
+    health: EndpointHealthConfig,
+    stats: Arc<Mutex<HashMap<String, EndpointStats>>>,
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    /// Latency to response headers and whether the request succeeded.
+    samples: VecDeque<(u64, bool)>,
+    demoted: bool,
+    last_probe: Option<Instant>,
+}
+
+impl EndpointStats {
+    fn p95_ms(&self) -> u64 {
+        let mut latencies: Vec<u64> = self.samples.iter().map(|(ms, _)| *ms).collect();
+        latencies.sort_unstable();
+        let rank = (latencies.len() * 95).div_ceil(100);
+        latencies.get(rank.saturating_sub(1)).copied().unwrap_or(0)
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples.iter().filter(|(_, ok)| !ok).count() as f64 / self.samples.len() as f64
+    }
+}
+
+impl EndpointPool {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            endpoints: config.downstream.endpoints.clone(),
+            health: config.downstream.endpoint_health.clone(),
+            stats: Default::default(),
+        }
+    }
+
+    /// Points a request for the downstream `model` at the healthiest
+    /// endpoint serving it; requests no endpoint serves are left alone.
+    pub fn apply(&self, config: &Config, model: &str, downstream: &mut DownstreamRequest) {
+        let candidates: Vec<&DownstreamEndpoint> = self
+            .endpoints
+            .iter()
+            .filter(|e| e.models.is_empty() || e.models.iter().any(|m| m == model))
+            .collect();
+        let provider_base = endpoint_key(config.provider_endpoint(config.provider_name(model)).0);
+        let Some(path) = downstream.url.strip_prefix(provider_base) else {
+            return;
+        };
+        let Some(endpoint) = self.choose(&candidates) else {
+            return;
+        };
+        downstream.url = format!("{}{}", endpoint_key(&endpoint.base_url), path);
+        if let Some(field) = endpoint.max_tokens_field.as_deref() {
+            set_max_tokens_field(&mut downstream.body, field);
+        }
+        if let Some(api_key) = endpoint.api_key.as_deref()
+            && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key))
+        {
+            downstream.headers.insert(AUTHORIZATION, value);
+        }
+    }
+
+    /// A demoted endpoint due for a probe first, then the lowest p95 among
+    /// healthy ones (endpoints without samples rank first), then the lowest
+    /// error rate when all are demoted.
+    fn choose<'a>(&self, candidates: &[&'a DownstreamEndpoint]) -> Option<&'a DownstreamEndpoint> {
+        let now = Instant::now();
+        let interval = Duration::from_secs(self.health.probe_interval_secs);
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        for endpoint in candidates {
+            let entry = stats.entry(endpoint_key(&endpoint.base_url).to_string()).or_default();
+            if entry.demoted && entry.last_probe.is_none_or(|at| now.duration_since(at) >= interval) {
+                entry.last_probe = Some(now);
+                return Some(endpoint);
+            }
+        }
+        let stats_for = |endpoint: &DownstreamEndpoint| &stats[endpoint_key(&endpoint.base_url)];
+        candidates
+            .iter()
+            .filter(|e| !stats_for(e).demoted)
+            .min_by_key(|e| stats_for(e).p95_ms())
+            .or_else(|| {
+                candidates
+                    .iter()
+                    .min_by(|a, b| stats_for(a).error_rate().total_cmp(&stats_for(b).error_rate()))
+            })
+            .copied()
+    }
+
+    /// Records a downstream response for the endpoint serving `url`;
+    /// transport errors, 429 and 5xx count as failures.
+    pub fn observe(&self, url: &str, sent: Instant, result: &Result<reqwest::Response, reqwest::Error>) {
+        let ok = result.as_ref().is_ok_and(|resp| !retryable_status(resp.status()));
+        self.record(url, sent.elapsed().as_millis() as u64, ok);
+    }
+
+    fn record(&self, url: &str, latency_ms: u64, ok: bool) {
+        let Some(endpoint) = self
+            .endpoints
+            .iter()
+            .map(|e| endpoint_key(&e.base_url))
+            .find(|key| url.starts_with(key))
+        else {
+            return;
+        };
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = stats.entry(endpoint.to_string()).or_default();
+        if entry.demoted {
+            if ok {
+                *entry = EndpointStats::default();
+                tracing::info!(endpoint, "downstream endpoint restored");
+            }
+            return;
+        }
+        entry.samples.push_back((latency_ms, ok));
+        while entry.samples.len() > self.health.window {
+            entry.samples.pop_front();
+        }
+        if entry.samples.len() >= self.health.min_samples && entry.error_rate() > self.health.max_error_rate {
+            tracing::warn!(endpoint, error_rate = entry.error_rate(), "downstream endpoint demoted");
+            entry.demoted = true;
+            entry.last_probe = Some(Instant::now());
+        }
+    }
+}
+
+/// Base URL without trailing slash or API version segment (`/v1`), so
+/// endpoints and provider base URLs line up however they are written.
+fn endpoint_key(base_url: &str) -> &str {
+    let base = base_url.trim_end_matches('/');
+    match base.rsplit_once('/') {
+        Some((rest, segment))
+            if segment.len() > 1
+                && segment.starts_with('v')
+                && segment[1..].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            rest
+        }
+        _ => base,
+    }
+}
+
+/// The credential of a `Bearer` `Authorization` header; `None` when there is
+/// no such header.
+fn bearer_key(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, key) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| key.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_fast_endpoint_and_probes_demoted_one() {
+        let mut config = Config::from_yaml(
+            "server: {}\ndownstream:\n  base_url: http://default/v1\n  endpoints:\n    - base_url: http://a:8000/v1\n      api_key: key-a\n    - base_url: http://b:8000\n  endpoint_health:\n    window: 4\n    min_samples: 2\n    max_error_rate: 0.5\n    probe_interval_secs: 3600\nmodels: {}\nlimits: {}\nobservability: {}\n",
+        )
+        .expect("config");
+        let request = || DownstreamRequest {
+            url: "http://default/v1/chat/completions".to_string(),
+            headers: Default::default(),
+            body: serde_json::Value::Null,
+        };
+        let pool = EndpointPool::new(&config);
+        let routed = |pool: &EndpointPool, config: &Config| {
+            let mut downstream = request();
+            pool.apply(config, "m", &mut downstream);
+            downstream
+        };
+
+        pool.record("http://a:8000/v1/chat/completions", 500, true);
+        pool.record("http://b:8000/v1/chat/completions", 50, true);
+        let downstream = routed(&pool, &config);
+        assert_eq!(downstream.url, "http://b:8000/v1/chat/completions");
+        assert!(downstream.headers.get(AUTHORIZATION).is_none());
+
+        pool.record("http://b:8000/v1/chat/completions", 60, false);
+        pool.record("http://b:8000/v1/chat/completions", 70, false);
+        let downstream = routed(&pool, &config);
+        assert_eq!(downstream.url, "http://a:8000/v1/chat/completions");
+        assert_eq!(downstream.headers[AUTHORIZATION], "Bearer key-a");
+
+        config.downstream.endpoint_health.probe_interval_secs = 0;
+        let pool = EndpointPool { health: config.downstream.endpoint_health.clone(), ..pool };
+        assert_eq!(routed(&pool, &config).url, "http://b:8000/v1/chat/completions");
+        pool.record("http://b:8000/v1/chat/completions", 40, true);
+        assert_eq!(routed(&pool, &config).url, "http://b:8000/v1/chat/completions");
+    }
+
+    #[test]
+    fn bearer_key_is_parsed_from_the_header() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(bearer_key(&headers("Bearer sk-a")), Some("sk-a"));
+        assert_eq!(bearer_key(&headers("bearer  sk-a ")), Some("sk-a"));
+        assert_eq!(bearer_key(&headers("Bearer ")), Some(""));
+        assert_eq!(bearer_key(&headers("Basic c2stYQ==")), None);
+        assert_eq!(bearer_key(&HeaderMap::new()), None);
+    }
+}
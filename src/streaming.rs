@@ -3,21 +3,38 @@ use axum::{
     http::{HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
-use futures_util::StreamExt;
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use futures_util::{FutureExt, StreamExt};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
 use serde_json::Value;
 use serde_json::json;
 use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tokio::sync::mpsc;
 use opentelemetry::KeyValue;
 use opentelemetry::trace::Span;
-use tokio_stream::wrappers::ReceiverStream;
 
-use crate::audit_log::{AuditContext, headers_to_map, now_ms};
+use crate::access_log::RequestSummary;
+use crate::backpressure::{client_channel, ChunkSender};
+use crate::coalesce::coalesce;
+use crate::event_split::split_oversized;
+use crate::partial_json::PartialJson;
+use crate::audit_log::{AuditContext, SseCapture, SseCaptures, headers_to_map, now_ms};
 use crate::error::{map_downstream_error, AppError};
-use crate::models::{AnthropicUsage, OpenAIRequest, OpenAIStreamChunk};
+use crate::ids;
+use crate::models::{AnthropicUsage, OpenAIReasoning, OpenAIStreamChunk};
+use crate::metrics::Metrics;
+use crate::provider::{DownstreamRequest, OpenAIProvider, Provider, StreamError, StreamEvent};
+use crate::sse::SseParser;
+use crate::sse_normalize;
 use crate::state::{AppState, InflightGuard};
+use crate::structured_output;
+use crate::tool_ids;
+use crate::tracing_otlp::{set_downstream_timing, set_langfuse_generation_output};
+use crate::translate::AnthropicVersion;
+use crate::translation_events;
 
 struct StreamState {
     started: bool,
@@ -30,96 +47,225 @@ struct StreamState {
     output_text: String,
     reasoning_text: String,
     reasoning_signature: Option<String>,
+    segments: Vec<OutputSegment>,
+    prefill: Option<PrefillFilter>,
+    api_version: AnthropicVersion,
+    /// `models.normalize_tool_ids`.
+    normalize_tool_ids: bool,
+    /// `models.reasoning_only_text`.
+    reasoning_only_text: bool,
+    stop_reason: Option<String>,
+    stop_sequence: Option<String>,
+    usage: AnthropicUsage,
+}
+
+/// Holds back leading text deltas until it is clear whether the downstream
+/// echoed the assistant prefill, then drops the echoed prefix.
+struct PrefillFilter {
+    prefill: String,
+    buffered: String,
+    done: bool,
+}
+
+impl PrefillFilter {
+    fn new(prefill: String) -> Self {
+        Self {
+            prefill,
+            buffered: String::new(),
+            done: false,
+        }
+    }
+
+    fn filter(&mut self, delta: String) -> String {
+        if self.done {
+            return delta;
+        }
+        self.buffered.push_str(&delta);
+        if self.buffered.len() >= self.prefill.len() {
+            self.done = true;
+            let buffered = std::mem::take(&mut self.buffered);
+            return match buffered.strip_prefix(self.prefill.as_str()) {
+                Some(rest) => rest.to_string(),
+                None => buffered,
+            };
+        }
+        if self.prefill.starts_with(self.buffered.as_str()) {
+            return String::new();
+        }
+        self.done = true;
+        std::mem::take(&mut self.buffered)
+    }
+
+    fn flush(&mut self) -> String {
+        self.done = true;
+        std::mem::take(&mut self.buffered)
+    }
+}
+
+/// Content blocks in emission order, so interleaved thinking/text bursts are
+/// reconstructed as separate blocks for traces.
+enum OutputSegment {
+    Thinking {
+        thinking: String,
+        signature: Option<String>,
+    },
+    Text(String),
+}
+
+impl StreamState {
+    fn new(api_version: AnthropicVersion) -> Self {
+        Self {
+            started: false,
+            message_id: None,
+            model: None,
+            next_index: 0,
+            text_block_index: None,
+            thinking_block_index: None,
+            tool_calls: HashMap::new(),
+            output_text: String::new(),
+            reasoning_text: String::new(),
+            reasoning_signature: None,
+            segments: Vec::new(),
+            prefill: None,
+            api_version,
+            normalize_tool_ids: false,
+            reasoning_only_text: false,
+            stop_reason: None,
+            stop_sequence: None,
+            usage: usage_zero(),
+        }
+    }
+}
+
+/// `content_block_delta` and `content_block_stop` payloads. Deltas are most
+/// of a stream, so they are serialized from borrowed text instead of being
+/// built as `json!` values first.
+#[derive(Serialize)]
+struct BlockDelta<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    index: u32,
+    delta: Delta<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Delta<'a> {
+    #[serde(rename = "text_delta")]
+    Text { text: &'a str },
+    #[serde(rename = "thinking_delta")]
+    Thinking { thinking: &'a str },
+    #[serde(rename = "signature_delta")]
+    Signature { signature: &'a str },
+    #[serde(rename = "input_json_delta")]
+    InputJson { partial_json: &'a str },
+}
+
+#[derive(Serialize)]
+struct BlockStop {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    index: u32,
 }
 
 struct ToolCallState {
     id: Option<String>,
     name: Option<String>,
     arguments: String,
+    /// Checks `arguments` as chunks arrive so broken JSON fails the stream
+    /// before the block is flushed.
+    validator: PartialJson,
     block_index: u32,
     started: bool,
     stopped: bool,
 }
 
+/// What a handler hands over to `stream_messages` or
+/// `stream_anthropic_passthrough` for one request; the stream task owns it
+/// from then on.
+pub struct StreamContext {
+    pub state: AppState,
+    /// The downstream model, as reported in logs and spans.
+    pub model: String,
+    pub request_id: String,
+    pub guard: InflightGuard,
+    pub start: Instant,
+    pub span: opentelemetry::global::BoxedSpan,
+    pub audit_ctx: Option<AuditContext>,
+    pub summary: RequestSummary,
+}
+
 pub async fn stream_messages(
-    state: AppState,
-    openai_req: OpenAIRequest,
-    guard: InflightGuard,
-    request_id: String,
-    start: Instant,
-    span: opentelemetry::global::BoxedSpan,
-    audit_ctx: Option<AuditContext>,
+    ctx: StreamContext,
+    provider: Arc<dyn Provider>,
+    mut downstream: DownstreamRequest,
+    api_version: AnthropicVersion,
+    prefill: Option<String>,
 ) -> Result<Response, AppError> {
-    let _ = request_id;
-    let span = span;
-    if state.config.observability.dump_downstream {
-        let body = serde_json::to_string(&openai_req).unwrap_or_else(|_| "[unserializable]".to_string());
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request: {}",
-            body
-        );
-        let mut headers = axum::http::HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            axum::http::HeaderValue::from_str(&format!(
-                "Bearer {}",
-                state.config.downstream.api_key.as_deref().unwrap_or_default()
-            ))
-            .unwrap_or_else(|_| axum::http::HeaderValue::from_static("[invalid]")),
-        );
-        headers.insert(
-            CONTENT_TYPE,
-            axum::http::HeaderValue::from_static("application/json"),
-        );
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request headers: {}",
-            headers_for_trace(&headers)
-        );
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request url: {}",
-            state.config.chat_completions_url()
-        );
-    }
-    let resp = state
-        .stream_client
-        .post(state.config.chat_completions_url())
-        .header(CONTENT_TYPE, "application/json")
-        .header(
-            AUTHORIZATION,
-            format!(
-                "Bearer {}",
-                state.config.downstream.api_key.as_deref().unwrap_or_default()
-            ),
+    let StreamContext {
+        state,
+        model,
+        request_id,
+        guard,
+        start,
+        mut span,
+        audit_ctx,
+        mut summary,
+    } = ctx;
+    let capture = state.config.capture_policy();
+    let support = state.config.json_schema_support(&model);
+    let client = state.clients.stream_client();
+    let mut body = downstream.encoded_body();
+    let mut output_format_downgraded = false;
+    let mut attempt = 0;
+    summary.downstream_sent();
+    let (content_type, mut stream) = loop {
+        let deadline = first_byte_deadline(&state);
+        let sent = before_first_byte(
+            &state,
+            deadline,
+            structured_output::send(&state, &client, &mut downstream, &mut body, support),
         )
-        .json(&openai_req)
-        .send()
-        .await
-        .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))?;
-
-    if state.config.observability.dump_downstream {
-        tracing::info!(
-            request_id = %request_id,
-            "downstream response headers: {}",
-            headers_for_trace(resp.headers())
-        );
-    }
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        let mapped = map_downstream_error(status, &text);
-        return Err(mapped);
-    }
-
-    let content_type = resp.headers().get(CONTENT_TYPE).cloned();
-    let mut stream = resp.bytes_stream();
-    let (tx, rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(64);
+        .await;
+        let err = match sent {
+            Ok((resp, downgraded)) => {
+                output_format_downgraded |= downgraded;
+                match open_stream(&state, resp, deadline).await {
+                    Ok(Opened::Stream(content_type, stream)) => break (content_type, stream),
+                    Ok(Opened::Rejected(resp)) => {
+                        let status = resp.status();
+                        let text = resp.text().await.unwrap_or_default();
+                        provider.map_error(status, &text)
+                    }
+                    Err(err) => err,
+                }
+            }
+            Err(err) => err,
+        };
+        if !err.retryable || !retry_stream(&state, &request_id, &mut attempt, &err.message).await {
+            finish_summary(&state, &mut span, summary, start, Some(&err), None);
+            return Err(err);
+        }
+    };
+    summary.ttfb_ms = Some(start.elapsed().as_millis() as u64);
+    summary.downstream_first_byte();
+    let (tx, body_stream) = client_channel(&state.config.streaming, &request_id);
+    let body_stream = match state.config.streaming.coalesce.as_ref() {
+        Some(config) => coalesce(body_stream, config),
+        None => body_stream,
+    };
+    let body_stream = match state.config.streaming.max_event_bytes {
+        Some(max_event_bytes) => split_oversized(body_stream, max_event_bytes),
+        None => body_stream,
+    };
+    let body_stream = state.throttle.pace(&state.config, summary.key_id.as_deref(), body_stream);
+    let tap = state.tap.register(&request_id);
+    let mut transcript = state
+        .transcripts
+        .as_ref()
+        .and_then(|transcripts| transcripts.record(&request_id, content_type.as_ref()));
 
     let metrics = state.metrics.clone();
-    let dump_downstream = state.config.observability.dump_downstream;
-    let audit_logger = state.audit_logger.clone();
     let response_headers = {
         let mut headers = axum::http::HeaderMap::new();
         if let Some(ct) = content_type.clone() {
@@ -127,239 +273,223 @@ pub async fn stream_messages(
         }
         headers
     };
-    let model = openai_req.model.clone();
+    let mut audit = StreamAudit::new(&state, audit_ctx, &response_headers);
+    let tx = ClientSender {
+        tx,
+        capture: audit.as_ref().and_then(|audit| audit.client_sse.clone()),
+    };
+    let app_state = state.clone();
     tokio::spawn(async move {
-        let _guard = guard;
         let mut span = span;
-        let mut buffer = String::new();
-        let mut response_trace = String::new();
-        let mut state = StreamState {
-            started: false,
-            message_id: None,
-            model: None,
-            next_index: 0,
-            text_block_index: None,
-            thinking_block_index: None,
-            tool_calls: HashMap::new(),
-            output_text: String::new(),
-            reasoning_text: String::new(),
-            reasoning_signature: None,
+        let on_panic = TaskPanic {
+            state: app_state.clone(),
+            tx: tx.clone(),
+            summary: summary.clone(),
+            usage: LiveUsage::default(),
+            start,
+            audit: audit.as_ref().map(|audit| audit.ctx.clone()),
+            response_headers: response_headers.clone(),
+            request_id: request_id.clone(),
         };
-
-        while let Some(chunk) = stream.next().await {
-            let chunk = match chunk {
-                Ok(bytes) => bytes,
-                Err(err) => {
-                    let err = AppError::api_error(format!("stream error: {}", err));
-                    let error_type = err.error_type.clone();
-                    metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                    span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
-                    let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
-                    if let Some(logger) = audit_logger.clone() {
-                        if let Some(ctx) = audit_ctx.clone() {
-                            let record = ctx.finish(
-                                StatusCode::OK.as_u16(),
-                                headers_to_map(&response_headers),
-                                Value::Null,
-                                true,
-                                false,
-                                now_ms(),
-                            );
-                            logger.push(record).await;
+        let usage = on_panic.usage.clone();
+        let task = async {
+            let span = &mut span;
+            let guard = guard;
+            let mut summary = summary;
+            let mut parser = SseParser::default();
+            let mut response_trace = String::new();
+            let mut state = StreamState::new(api_version);
+            state.model = Some(model.clone());
+            state.prefill = prefill.map(PrefillFilter::new);
+            state.normalize_tool_ids = app_state.config.models.normalize_tool_ids;
+            state.reasoning_only_text = app_state.config.models.reasoning_only_text;
+            let lenient = app_state.config.downstream.stream_parsing == "lenient";
+            let max_malformed = app_state.config.downstream.max_malformed_chunks;
+            let mut malformed = 0u32;
+
+            while let Some(chunk) = next_chunk(&mut stream, &guard).await {
+                let chunk = match chunk {
+                    Ok(bytes) => {
+                        if let Some(audit) = audit.as_mut() {
+                            audit.downstream_chunk(&bytes);
                         }
+                        bytes
                     }
-                    span.end();
-                    return;
-                }
-            };
-
-            let text = String::from_utf8_lossy(&chunk);
-            buffer.push_str(&text);
-
-            while let Some(pos) = buffer.find('\n') {
-                let line = buffer[..pos].trim_end_matches('\r').to_string();
-                buffer = buffer[pos + 1..].to_string();
-
-                if line.is_empty() || !line.starts_with("data:") {
-                    continue;
-                }
-
-                let data = line.trim_start_matches("data:").trim();
-                if dump_downstream {
-                    tracing::info!(
-                        request_id = %request_id,
-                        "downstream stream chunk: {}",
-                        data
-                    );
-                }
-                append_trace(&mut response_trace, data);
-                if data == "[DONE]" {
-                    if let Err(err) = flush_open_blocks(&mut state, &tx).await {
-                        let error_type = err.error_type.clone();
+                    Err(err) => {
+                        let error_type = err.error_type();
                         metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                        span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
+                        span.set_attribute(KeyValue::new("error.type", err.error_type()));
+                        usage.finish(&app_state, span, summary, start, Some(&err), None);
                         let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
-                        if dump_downstream {
-                            if let Some(upstream) = stream_upstream_response(&state) {
-                                tracing::info!(
-                                    request_id = %request_id,
-                                    "upstream response: {}",
-                                    upstream
-                                );
+                        if let Some(audit) = audit.as_ref() {
+                            audit.push(Value::Null, true).await;
+                        }
+                        span.end();
+                        return;
+                    }
+                };
+
+                for event in parser.push(&chunk) {
+                    if event.data.is_empty() {
+                        continue;
+                    }
+
+                    let data = event.data.trim();
+                    append_trace(&mut response_trace, data);
+                    let (parsed, done) = match provider.parse_stream_chunk(&event) {
+                        Ok(StreamEvent::Chunk(v)) => {
+                            malformed = 0;
+                            (Some(Ok(v)), false)
+                        }
+                        Ok(StreamEvent::Final(v)) => (Some(Ok(v)), true),
+                        Ok(StreamEvent::Skip) => continue,
+                        Ok(StreamEvent::Done) => (None, true),
+                        Err(StreamError::Malformed(err)) if lenient && malformed < max_malformed => {
+                            malformed += 1;
+                            skip_malformed_chunk(&metrics, &model, &request_id, data, &err, malformed);
+                            continue;
+                        }
+                        Err(StreamError::Malformed(err)) => {
+                            (Some(Err(AppError::api_error(format!("invalid stream chunk: {}", err)))), false)
+                        }
+                        Err(StreamError::Downstream(err)) => (Some(Err(err)), false),
+                    };
+                    if let Some(parsed) = parsed {
+                        let parsed = match parsed {
+                            Ok(v) => v,
+                            Err(err) => {
+                                let error_type = err.error_type();
+                                metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                                span.set_attribute(KeyValue::new("error.type", err.error_type()));
+                                usage.finish(&app_state, span, summary, start, Some(&err), None);
+                                let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
+                                if let Some(audit) = audit.as_ref() {
+                                    audit.push(Value::Null, true).await;
+                                }
+                                span.end();
+                                return;
                             }
-                            tracing::info!(
-                                request_id = %request_id,
-                                "downstream response: {}",
-                                response_trace
-                            );
+                        };
+                        if let Some(parsed_usage) = &parsed.usage {
+                            summary.input_tokens = Some(parsed_usage.prompt_tokens as u64);
+                            summary.output_tokens = Some(parsed_usage.completion_tokens as u64);
+                            usage.track(&summary);
                         }
-                        if let Some(logger) = audit_logger.clone() {
-                            if let Some(ctx) = audit_ctx.clone() {
-                                let record = ctx.finish(
-                                    StatusCode::OK.as_u16(),
-                                    headers_to_map(&response_headers),
-                                    Value::Null,
-                                    true,
-                                    false,
-                                    now_ms(),
-                                );
-                                logger.push(record).await;
+
+                        if let Err(err) = handle_openai_chunk(parsed, &mut state, &tx).await {
+                            let error_type = err.error_type();
+                            metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                            span.set_attribute(KeyValue::new("error.type", err.error_type()));
+                            usage.finish(&app_state, span, summary, start, Some(&err), None);
+                            let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
+                            if let Some(output) = stream_output_messages(&state) {
+                                let output = serialize_json_for_trace(&output);
+                                span.set_attribute(KeyValue::new("output", capture.apply(&output)));
+                            }
+                            span.set_attribute(KeyValue::new(
+                                "downstream.response",
+                                capture.apply(&response_trace),
+                            ));
+                            if let Some(audit) = audit.as_ref() {
+                                audit.push(Value::Null, true).await;
                             }
+                            span.end();
+                            return;
                         }
-                        span.end();
-                        return;
                     }
-                    let _ = tx
-                        .send(Ok(Bytes::from(sse_event(
-                            "message_stop",
-                            json!({"type":"message_stop"}),
-                        ))))
-                        .await;
-                    metrics.latency_ms.record(
-                        start.elapsed().as_millis() as f64,
-                        &[KeyValue::new("stream", "true")],
-                    );
-                    if let Some(output) = stream_output_messages(&state) {
-                        let output = serialize_json_for_trace(&output);
-                        span.set_attribute(KeyValue::new("output", output));
-                    } else if dump_downstream {
-                        tracing::info!(
-                            request_id = %request_id,
-                            "upstream response has no output content"
+                    if done {
+                        if let Err(err) = flush_open_blocks(&mut state, &tx).await {
+                            let error_type = err.error_type();
+                            metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                            span.set_attribute(KeyValue::new("error.type", err.error_type()));
+                            usage.finish(&app_state, span, summary, start, Some(&err), None);
+                            let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
+                            if let Some(audit) = audit.as_ref() {
+                                audit.push(Value::Null, true).await;
+                            }
+                            span.end();
+                            return;
+                        }
+                        send_message_delta(&mut state, &tx).await;
+                        let _ = tx
+                            .send(Ok(Bytes::from(sse_event(
+                                "message_stop",
+                                json!({"type":"message_stop"}),
+                            ))))
+                            .await;
+                        metrics.latency_ms.record(
+                            start.elapsed().as_millis() as f64,
+                            &[KeyValue::new("stream", "true")],
                         );
-                    }
-                    if dump_downstream {
-                        if let Some(upstream) = stream_upstream_response(&state) {
-                            tracing::info!(
-                                request_id = %request_id,
-                                "upstream response: {}",
-                                upstream
-                            );
+                        if let Some(out) = summary.output_text.as_mut() {
+                            out.push_str(&state.output_text);
                         }
-                        tracing::info!(
-                            request_id = %request_id,
-                            "downstream response: {}",
-                            response_trace
+                        usage.finish(
+                            &app_state,
+                            span,
+                            summary,
+                            start,
+                            None,
+                            stream_output_messages(&state).as_ref(),
                         );
-                    }
-                    span.set_attribute(KeyValue::new(
-                        "downstream.response",
-                        response_trace.clone(),
-                    ));
-                    if let Some(logger) = audit_logger.clone() {
-                        if let Some(ctx) = audit_ctx.clone() {
+                        if let Some(output) = stream_output_messages(&state) {
+                            let output = serialize_json_for_trace(&output);
+                            span.set_attribute(KeyValue::new("output", capture.apply(&output)));
+                        }
+                        span.set_attribute(KeyValue::new(
+                            "downstream.response",
+                            capture.apply(&response_trace),
+                        ));
+                        if let Some(audit) = audit.as_ref() {
                             let (body_value, parse_error) = match stream_upstream_response(&state) {
                                 Some(body) => parse_body_value(body.as_bytes()),
                                 None => (Value::Null, true),
                             };
-                            let record = ctx.finish(
-                                StatusCode::OK.as_u16(),
-                                headers_to_map(&response_headers),
-                                body_value,
-                                parse_error,
-                                false,
-                                now_ms(),
-                            );
-                            logger.push(record).await;
+                            audit.push(body_value, parse_error).await;
                         }
+                        span.end();
+                        return;
                     }
-                    span.end();
-                    return;
-                }
-
-                let parsed: OpenAIStreamChunk = match serde_json::from_str(data) {
-                    Ok(v) => v,
-                    Err(err) => {
-                    let err = AppError::api_error(format!("invalid stream chunk: {}", err));
-                    let error_type = err.error_type.clone();
-                    metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                    span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
-                    let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
-                    span.end();
-                    return;
                 }
+            }
+            usage.finish(&app_state, span, summary, start, None, None);
+            // Downstream closed without `[DONE]`; the audit keeps what it sent.
+            if let Some(audit) = audit.as_ref() {
+                let (body_value, parse_error) = match stream_upstream_response(&state) {
+                    Some(body) => parse_body_value(body.as_bytes()),
+                    None => (Value::Null, true),
                 };
-
-                if let Err(err) = handle_openai_chunk(parsed, &mut state, &tx).await {
-                    let error_type = err.error_type.clone();
-                    metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                    span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
-                    let _ = tx.send(Ok(Bytes::from(error_event(err)))).await;
-                    if let Some(output) = stream_output_messages(&state) {
-                        let output = serialize_json_for_trace(&output);
-                        span.set_attribute(KeyValue::new("output", output));
-                    } else if dump_downstream {
-                        tracing::info!(
-                            request_id = %request_id,
-                            "upstream response has no output content"
-                        );
-                    }
-                    if dump_downstream {
-                        if let Some(upstream) = stream_upstream_response(&state) {
-                            tracing::info!(
-                                request_id = %request_id,
-                                "upstream response: {}",
-                                upstream
-                            );
-                        }
-                        tracing::info!(
-                            request_id = %request_id,
-                            "downstream response: {}",
-                            response_trace
-                        );
-                    }
-                    span.set_attribute(KeyValue::new(
-                        "downstream.response",
-                        response_trace.clone(),
-                    ));
-                    if let Some(logger) = audit_logger.clone() {
-                        if let Some(ctx) = audit_ctx.clone() {
-                            let record = ctx.finish(
-                                StatusCode::OK.as_u16(),
-                                headers_to_map(&response_headers),
-                                Value::Null,
-                                true,
-                                false,
-                                now_ms(),
-                            );
-                            logger.push(record).await;
-                        }
-                    }
-                    span.end();
-                    return;
-                }
+                audit.push(body_value, parse_error).await;
             }
+            let _ = model;
+            let _ = request_id;
+        };
+        if let Some(message) = catch_panic(task).await {
+            on_panic.finish(&mut span, message).await;
         }
-        let _ = model;
-        let _ = request_id;
     });
 
-    let body_stream = ReceiverStream::new(rx);
+    let body_stream = body_stream.inspect(move |chunk| {
+        if let Ok(bytes) = chunk {
+            tap.publish(bytes);
+            if let Some(transcript) = transcript.as_mut() {
+                transcript.push(bytes);
+            }
+        }
+    });
     let body = axum::body::Body::from_stream(body_stream);
     let mut builder = Response::builder().status(StatusCode::OK);
     if let Some(ct) = content_type {
         builder = builder.header(CONTENT_TYPE, ct);
     }
+    if output_format_downgraded {
+        let events = translation_events::output_format_fallback(false);
+        translation_events::record(&state.metrics, &events);
+        builder = builder.header(
+            translation_events::WARNINGS_HEADER,
+            translation_events::warnings(&events).join(", "),
+        );
+    }
     Ok(builder.body(body).unwrap_or_else(|_| {
         Response::builder()
             .status(StatusCode::OK)
@@ -368,182 +498,662 @@ pub async fn stream_messages(
     }))
 }
 
+/// Translates a complete OpenAI SSE body with the same state machine as
+/// `stream_messages` and returns the Anthropic SSE frames it would send,
+/// ending with `message_stop` once `[DONE]` is seen. No downstream, inflight
+/// or telemetry state is involved, which makes it the benchmark entry point.
+pub async fn translate_sse_body(body: &[u8], api_version: AnthropicVersion) -> Result<Vec<Bytes>, AppError> {
+    let mut parser = SseParser::default();
+    let mut events = parser.push(body);
+    events.extend(parser.finish());
+    let (tx, mut rx) = mpsc::channel(64);
+    let tx = ClientSender::from(tx);
+    let translate = async move {
+        let mut state = StreamState::new(api_version);
+        for event in events.iter().filter(|event| !event.data.is_empty()) {
+            let (chunk, done) = match OpenAIProvider.parse_stream_chunk(event) {
+                Ok(StreamEvent::Chunk(chunk)) => (Some(chunk), false),
+                Ok(StreamEvent::Final(chunk)) => (Some(chunk), true),
+                Ok(StreamEvent::Skip) => continue,
+                Ok(StreamEvent::Done) => (None, true),
+                Err(StreamError::Malformed(err)) => {
+                    return Err(AppError::api_error(format!("invalid stream chunk: {}", err)));
+                }
+                Err(StreamError::Downstream(err)) => return Err(err),
+            };
+            if let Some(chunk) = chunk {
+                handle_openai_chunk(chunk, &mut state, &tx).await?;
+            }
+            if done {
+                flush_open_blocks(&mut state, &tx).await?;
+                send_message_delta(&mut state, &tx).await;
+                let _ = tx
+                    .send(Ok(Bytes::from(sse_event("message_stop", json!({"type":"message_stop"})))))
+                    .await;
+                break;
+            }
+        }
+        Ok(())
+    };
+    let collect = async {
+        let mut frames = Vec::new();
+        while let Some(Ok(frame)) = rx.recv().await {
+            frames.push(frame);
+        }
+        frames
+    };
+    let (result, frames) = tokio::join!(translate, collect);
+    result.map(|()| frames)
+}
+
 pub async fn stream_anthropic_passthrough(
-    state: AppState,
-    payload: Value,
+    ctx: StreamContext,
+    downstream_url: String,
+    body: Bytes,
     forward_headers: axum::http::HeaderMap,
-    model: String,
-    audit_ctx: Option<AuditContext>,
-    guard: InflightGuard,
-    request_id: String,
-    start: Instant,
-    span: opentelemetry::global::BoxedSpan,
 ) -> Result<Response, AppError> {
-    if state.config.observability.dump_downstream {
-        let body = serde_json::to_string(&payload).unwrap_or_else(|_| "[unserializable]".to_string());
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request: {}",
-            body
-        );
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request headers: {}",
-            headers_for_trace(&forward_headers)
-        );
-        tracing::info!(
-            request_id = %request_id,
-            "downstream request url: {}",
-            state.config.anthropic_messages_url()
-        );
-    }
-
-    let request = state
-        .stream_client
-        .post(state.config.anthropic_messages_url())
-        .headers(forward_headers);
-
-    let resp = request
-        .json(&payload)
-        .send()
-        .await
-        .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))?;
-
-    if state.config.observability.dump_downstream {
-        tracing::info!(
-            request_id = %request_id,
-            "downstream response headers: {}",
-            headers_for_trace(resp.headers())
-        );
-    }
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let headers = resp.headers().clone();
-        let raw_body = resp.bytes().await.unwrap_or_default();
-        if state.config.observability.dump_downstream {
-            if let Ok(text) = std::str::from_utf8(&raw_body) {
-                tracing::info!(
-                    request_id = %request_id,
-                    "downstream response: {}",
-                    text
+    let StreamContext {
+        state,
+        model,
+        request_id,
+        guard,
+        start,
+        mut span,
+        audit_ctx,
+        mut summary,
+    } = ctx;
+    let client = state.clients.stream_client();
+    let mut attempt = 0;
+    summary.downstream_sent();
+    let opened = loop {
+        let deadline = first_byte_deadline(&state);
+        let sent = before_first_byte(&state, deadline, async {
+            client
+                .post(&downstream_url)
+                .headers(forward_headers.clone())
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|e| AppError::api_error(format!("downstream request failed: {}", e)))
+        })
+        .await;
+        let err = match sent {
+            Ok(resp) => match open_stream(&state, resp, deadline).await {
+                Ok(Opened::Rejected(resp))
+                    if map_downstream_error(resp.status(), "").retryable
+                        && retry_stream(&state, &request_id, &mut attempt, resp.status().as_str()).await =>
+                {
+                    continue;
+                }
+                Ok(opened) => break opened,
+                Err(err) => err,
+            },
+            Err(err) => err,
+        };
+        if !err.retryable || !retry_stream(&state, &request_id, &mut attempt, &err.message).await {
+            finish_summary(&state, &mut span, summary, start, Some(&err), None);
+            return Err(err);
+        }
+    };
+    summary.ttfb_ms = Some(start.elapsed().as_millis() as u64);
+    summary.downstream_first_byte();
+
+    let (content_type, mut stream) = match opened {
+        Opened::Stream(content_type, stream) => (content_type, stream),
+        Opened::Rejected(resp) => {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let raw_body = resp.bytes().await.unwrap_or_default();
+            summary.status = status.as_u16();
+            summary.error_type = serde_json::from_slice::<Value>(&raw_body)
+                .ok()
+                .and_then(|body| body.pointer("/error/type").and_then(Value::as_str).map(str::to_string));
+            summary.latency_ms = start.elapsed().as_millis() as u64;
+            summary.downstream_done();
+            state.record_summary(summary);
+            if let Some(ctx) = audit_ctx {
+                let (body_value, parse_error) = parse_body_value(&raw_body);
+                let record = ctx.finish(
+                    status.as_u16(),
+                    headers_to_map(&headers),
+                    body_value,
+                    parse_error,
+                    false,
+                    now_ms(),
                 );
+                state.push_audit(record).await;
             }
+            return Ok(response_from_bytes(status, headers.get(CONTENT_TYPE), raw_body));
         }
-        if let Some((logger, ctx)) = state.audit_logger.clone().zip(audit_ctx) {
-            let (body_value, parse_error) = parse_body_value(&raw_body);
-            let record = ctx.finish(
-                status.as_u16(),
-                headers_to_map(&headers),
-                body_value,
-                parse_error,
-                false,
-                now_ms(),
-            );
-            logger.push(record).await;
-        }
-        return Ok(response_from_bytes(status, headers.get(CONTENT_TYPE), raw_body));
-    }
-
-    let response_headers = match resp.headers().get(CONTENT_TYPE) {
+    };
+    let response_headers = match content_type {
         Some(ct) => {
             let mut headers = axum::http::HeaderMap::new();
-            headers.insert(CONTENT_TYPE, ct.clone());
+            headers.insert(CONTENT_TYPE, ct);
             headers
         }
         None => axum::http::HeaderMap::new(),
     };
-    let mut stream = resp.bytes_stream();
-    let (tx, rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(64);
+    let (tx, body_stream) = client_channel(&state.config.streaming, &request_id);
+    let body_stream = match state.config.streaming.coalesce.as_ref() {
+        Some(config) => coalesce(body_stream, config),
+        None => body_stream,
+    };
+    let body_stream = match state.config.streaming.max_event_bytes {
+        Some(max_event_bytes) => split_oversized(body_stream, max_event_bytes),
+        None => body_stream,
+    };
+    let body_stream = state.throttle.pace(&state.config, summary.key_id.as_deref(), body_stream);
+    let tap = state.tap.register(&request_id);
+    let mut transcript = state
+        .transcripts
+        .as_ref()
+        .and_then(|transcripts| transcripts.record(&request_id, response_headers.get(CONTENT_TYPE)));
 
     let metrics = state.metrics.clone();
-    let dump_downstream = state.config.observability.dump_downstream;
-    let audit_logger = state.audit_logger.clone();
-    let max_body_bytes = state.config.observability.audit_log.max_body_bytes;
+    let mut body_capture = SseCaptures {
+        audit: Some(SseCapture::new(state.config.observability.audit_log.max_body_bytes)),
+        debug: debug_max_body_bytes(&state, audit_ctx.as_ref()).map(SseCapture::new),
+    };
+    let app_state = state.clone();
     tokio::spawn(async move {
-        let _guard = guard;
         let mut span = span;
-        let mut audit_buf: Vec<u8> = Vec::new();
-        let mut audit_truncated = false;
-        while let Some(chunk) = stream.next().await {
-            match chunk {
-                Ok(bytes) => {
-                    if dump_downstream {
-                        if let Ok(text) = std::str::from_utf8(&bytes) {
-                            tracing::info!(
-                                request_id = %request_id,
-                                "downstream stream chunk: {}",
-                                text
-                            );
+        let on_panic = TaskPanic {
+            state: app_state.clone(),
+            tx: tx.clone().into(),
+            summary: summary.clone(),
+            usage: LiveUsage::default(),
+            start,
+            audit: audit_ctx.clone(),
+            response_headers: response_headers.clone(),
+            request_id: request_id.clone(),
+        };
+        let usage = on_panic.usage.clone();
+        let task = async {
+            let span = &mut span;
+            let guard = guard;
+            let mut parser = SseParser::default();
+            let mut stream_error: Option<AppError> = None;
+            let sse_normalize = app_state.config.anthropic.sse_normalize;
+            while let Some(chunk) = next_chunk(&mut stream, &guard).await {
+                match chunk {
+                    Ok(bytes) => {
+                        let events = parser.push(&bytes);
+                        for event in &events {
+                            summary.apply_anthropic_sse_event(&event.data);
+                        }
+                        usage.track(&summary);
+                        body_capture.push(&bytes);
+                        let out = if sse_normalize {
+                            let normalized: String =
+                                events.iter().filter_map(sse_normalize::normalize).collect();
+                            Bytes::from(normalized)
+                        } else {
+                            bytes
+                        };
+                        if !out.is_empty() && tx.send(Ok(out)).await.is_err() {
+                            break;
                         }
                     }
-                    if !audit_truncated && audit_buf.len() + bytes.len() <= max_body_bytes {
-                        audit_buf.extend_from_slice(&bytes);
-                    } else {
-                        audit_truncated = true;
-                    }
-                    if tx.send(Ok(bytes)).await.is_err() {
+                    Err(err) => {
+                        let error_type = err.error_type();
+                        metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
+                        span.set_attribute(KeyValue::new("error.type", err.error_type()));
+                        if guard.is_cancelled() {
+                            let _ = tx.send(Ok(Bytes::from(error_event(err.clone())))).await;
+                        }
+                        stream_error = Some(err);
                         break;
                     }
                 }
-                Err(err) => {
-                    let err = AppError::api_error(format!("stream error: {}", err));
-                    let error_type = err.error_type.clone();
-                    metrics.errors.add(1, &[KeyValue::new("type", error_type)]);
-                    span.set_attribute(KeyValue::new("error.type", err.error_type.clone()));
-                    break;
+            }
+            let events = parser.finish();
+            for event in &events {
+                summary.apply_anthropic_sse_event(&event.data);
+            }
+            if sse_normalize && stream_error.is_none() {
+                let rest: String = events.iter().filter_map(sse_normalize::normalize).collect();
+                if !rest.is_empty() {
+                    let _ = tx.send(Ok(Bytes::from(rest))).await;
                 }
             }
+            usage.finish(&app_state, span, summary, start, stream_error.as_ref(), None);
+            metrics.latency_ms.record(
+                start.elapsed().as_millis() as f64,
+                &[KeyValue::new("stream", "true")],
+            );
+            tracing::info!(
+                request_id = %request_id,
+                model = %model,
+                latency_ms = start.elapsed().as_millis(),
+                status = 200,
+                "request completed"
+            );
+            if let Some(ctx) = audit_ctx.clone() {
+                let finish = |body: &SseCapture| {
+                    let (body_value, parse_error) = parse_body_value(body.bytes());
+                    ctx.clone().finish(
+                        StatusCode::OK.as_u16(),
+                        headers_to_map(&response_headers),
+                        body_value,
+                        parse_error,
+                        body.truncated(),
+                        now_ms(),
+                    )
+                };
+                let record = body_capture.audit.as_ref().map(finish);
+                let debug_record = body_capture.debug.as_ref().map(finish);
+                if let Some(record) = record {
+                    app_state.push_audit_records(record, debug_record).await;
+                }
+            }
+            span.end();
+        };
+        if let Some(message) = catch_panic(task).await {
+            on_panic.finish(&mut span, message).await;
+        }
+    });
+
+    let body_stream = body_stream.inspect(move |chunk| {
+        if let Ok(bytes) = chunk {
+            tap.publish(bytes);
+            if let Some(transcript) = transcript.as_mut() {
+                transcript.push(bytes);
+            }
+        }
+    });
+    let body = axum::body::Body::from_stream(body_stream);
+    Ok((StatusCode::OK, body).into_response())
+}
+
+/// A stream task's handle on the client channel. With `audit_log.stream_sse`
+/// set to `client` or `both`, or in debug capture, it also copies every
+/// event the client is sent.
+#[derive(Clone)]
+struct ClientSender {
+    tx: ChunkSender,
+    capture: Option<Arc<Mutex<SseCaptures>>>,
+}
+
+impl From<ChunkSender> for ClientSender {
+    fn from(tx: ChunkSender) -> Self {
+        Self { tx, capture: None }
+    }
+}
+
+impl ClientSender {
+    async fn send(
+        &self,
+        chunk: Result<Bytes, Infallible>,
+    ) -> Result<(), mpsc::error::SendError<Result<Bytes, Infallible>>> {
+        if let (Some(capture), Ok(bytes)) = (&self.capture, &chunk) {
+            capture.lock().unwrap().push(bytes);
+        }
+        self.tx.send(chunk).await
+    }
+}
+
+/// Where a translate stream task sends its audit record, with the raw SSE
+/// captures `audit_log.stream_sse` asked for; a debug capture keeps both
+/// sides in a record of its own.
+struct StreamAudit {
+    state: AppState,
+    ctx: AuditContext,
+    response_headers: axum::http::HeaderMap,
+    downstream_sse: SseCaptures,
+    client_sse: Option<Arc<Mutex<SseCaptures>>>,
+}
+
+impl StreamAudit {
+    fn new(
+        state: &AppState,
+        ctx: Option<AuditContext>,
+        response_headers: &axum::http::HeaderMap,
+    ) -> Option<Self> {
+        let ctx = ctx?;
+        let config = &state.config.observability.audit_log;
+        let debug_max_body_bytes = debug_max_body_bytes(state, Some(&ctx));
+        let capture = |sides: [&str; 2]| SseCaptures {
+            audit: sides
+                .contains(&config.stream_sse.as_str())
+                .then(|| SseCapture::new(config.max_body_bytes)),
+            debug: debug_max_body_bytes.map(SseCapture::new),
+        };
+        let client_sse = capture(["client", "both"]);
+        Some(Self {
+            state: state.clone(),
+            response_headers: response_headers.clone(),
+            downstream_sse: capture(["downstream", "both"]),
+            client_sse: (!client_sse.is_empty()).then(|| Arc::new(Mutex::new(client_sse))),
+            ctx,
+        })
+    }
+
+    fn downstream_chunk(&mut self, bytes: &[u8]) {
+        self.downstream_sse.push(bytes);
+    }
+
+    async fn push(&self, body: Value, parse_error: bool) {
+        let record = self.ctx.clone().finish(
+            StatusCode::OK.as_u16(),
+            headers_to_map(&self.response_headers),
+            body,
+            parse_error,
+            false,
+            now_ms(),
+        );
+        let (record, debug_record) = {
+            let client = self.client_sse.as_ref().map(|client| client.lock().unwrap());
+            let client = client.as_deref();
+            let downstream = &self.downstream_sse;
+            let debug_record = self.ctx.meta.debug.then(|| {
+                record
+                    .clone()
+                    .with_stream_sse(downstream.debug.as_ref(), client.and_then(|c| c.debug.as_ref()))
+            });
+            let record = record.with_stream_sse(downstream.audit.as_ref(), client.and_then(|c| c.audit.as_ref()));
+            (record, debug_record)
+        };
+        self.state.push_audit_records(record, debug_record).await;
+    }
+}
+
+/// The debug file's body limit, for a request in debug capture.
+fn debug_max_body_bytes(state: &AppState, ctx: Option<&AuditContext>) -> Option<usize> {
+    state
+        .debug_capture
+        .as_ref()
+        .filter(|_| ctx.is_some_and(|ctx| ctx.meta.debug))
+        .map(|debug| debug.max_body_bytes())
+}
+
+/// Lenient parsing: counts the skipped chunk and logs the first one of each
+/// run as a sample, since a misbehaving provider tends to repeat itself.
+fn skip_malformed_chunk(metrics: &Metrics, model: &str, request_id: &str, data: &str, err: &str, run: u32) {
+    metrics
+        .malformed_chunks
+        .add(1, &[KeyValue::new("model", model.to_string())]);
+    if run == 1 {
+        let sample: String = data.chars().take(256).collect();
+        tracing::warn!(
+            request_id = %request_id,
+            model = %model,
+            "skipping malformed stream chunk: {}: {}",
+            err,
+            sample
+        );
+    }
+}
+
+/// Next downstream chunk. Cancelling the request through the inflight
+/// registry yields a `request_cancelled` error instead; the caller then drops
+/// the downstream stream, which aborts the call.
+async fn next_chunk<S>(stream: &mut S, guard: &InflightGuard) -> Option<Result<Bytes, AppError>>
+where
+    S: futures_util::Stream<Item = reqwest::Result<Bytes>> + Unpin,
+{
+    tokio::select! {
+        chunk = stream.next() => {
+            chunk.map(|chunk| chunk.map_err(|e| AppError::api_error(format!("stream error: {}", e))))
+        }
+        _ = guard.cancelled() => Some(Err(AppError::cancelled())),
+    }
+}
+
+/// A streaming downstream response once its first body chunk is in, or the
+/// response itself when its status is not a success.
+enum Opened {
+    Stream(Option<HeaderValue>, DownstreamStream),
+    Rejected(reqwest::Response),
+}
+
+type DownstreamStream = futures_util::stream::BoxStream<'static, reqwest::Result<Bytes>>;
+
+async fn open_stream(
+    state: &AppState,
+    resp: reqwest::Response,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<Opened, AppError> {
+    if !resp.status().is_success() {
+        return Ok(Opened::Rejected(resp));
+    }
+    let content_type = resp.headers().get(CONTENT_TYPE).cloned();
+    let stream = first_chunk(state, deadline, resp.bytes_stream()).await?;
+    Ok(Opened::Stream(content_type, stream))
+}
+
+/// Waits out the backoff before re-issuing a streaming call that failed
+/// before any event reached the client; false once `downstream.stream_retries`
+/// are used up.
+async fn retry_stream(state: &AppState, request_id: &str, attempt: &mut u32, reason: &str) -> bool {
+    let downstream = &state.config.downstream;
+    if *attempt >= downstream.stream_retries {
+        return false;
+    }
+    let backoff = downstream.stream_retry_backoff_ms.saturating_mul(1 << (*attempt).min(16));
+    *attempt += 1;
+    tracing::warn!(
+        request_id = %request_id,
+        attempt = *attempt,
+        "streaming downstream call failed before the first event, retrying: {}",
+        reason
+    );
+    tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+    true
+}
+
+/// When the first body chunk must have arrived, per
+/// `downstream.first_byte_timeout_ms`, counted from before the request is sent.
+fn first_byte_deadline(state: &AppState) -> Option<tokio::time::Instant> {
+    state
+        .config
+        .downstream
+        .first_byte_timeout_ms
+        .map(|ms| tokio::time::Instant::now() + std::time::Duration::from_millis(ms))
+}
+
+fn first_byte_timeout(state: &AppState) -> AppError {
+    AppError::timeout(format!(
+        "downstream sent no data within {} ms",
+        state.config.downstream.first_byte_timeout_ms.unwrap_or_default()
+    ))
+}
+
+/// Runs `sending`, failing with a retryable timeout once `deadline` passes.
+async fn before_first_byte<T>(
+    state: &AppState,
+    deadline: Option<tokio::time::Instant>,
+    sending: impl std::future::Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, sending)
+            .await
+            .unwrap_or_else(|_| Err(first_byte_timeout(state))),
+        None => sending.await,
+    }
+}
+
+/// Waits for the first body chunk until `deadline` and puts it back in front
+/// of the stream, so a stalled downstream fails before any event is sent to
+/// the client.
+async fn first_chunk<S>(
+    state: &AppState,
+    deadline: Option<tokio::time::Instant>,
+    mut stream: S,
+) -> Result<DownstreamStream, AppError>
+where
+    S: futures_util::Stream<Item = reqwest::Result<Bytes>> + Send + Unpin + 'static,
+{
+    let first = match deadline {
+        Some(_) => before_first_byte(state, deadline, async { Ok(stream.next().await) }).await?,
+        None => None,
+    };
+    Ok(futures_util::stream::iter(first).chain(stream).boxed())
+}
+
+fn finish_summary(
+    state: &AppState,
+    span: &mut opentelemetry::global::BoxedSpan,
+    mut summary: RequestSummary,
+    start: Instant,
+    err: Option<&AppError>,
+    output: Option<&Value>,
+) {
+    if let Some(err) = err {
+        summary.error_type = Some(err.error_type().to_string());
+        if summary.ttfb_ms.is_none() {
+            summary.status = err.status.as_u16();
+        }
+    }
+    summary.latency_ms = start.elapsed().as_millis() as u64;
+    summary.downstream_done();
+    if state.config.langfuse_tracing() {
+        set_langfuse_generation_output(span, output, &summary, state.config.capture_policy());
+    }
+    set_downstream_timing(span, &summary);
+    state.record_summary(summary);
+}
+
+/// Awaits a spawned stream task and returns the panic message if it
+/// panicked, so the task can still end its request.
+async fn catch_panic(task: impl std::future::Future<Output = ()>) -> Option<String> {
+    let panic = std::panic::AssertUnwindSafe(task).catch_unwind().await.err()?;
+    let message = match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    };
+    Some(message)
+}
+
+/// What a stream task needs to end its request after a panic; cloned before
+/// the task body runs, since the body's own copies are lost when it unwinds.
+/// `usage` is the exception: the task keeps it current as usage arrives.
+struct TaskPanic {
+    state: AppState,
+    tx: ClientSender,
+    summary: RequestSummary,
+    usage: LiveUsage,
+    start: Instant,
+    audit: Option<AuditContext>,
+    response_headers: axum::http::HeaderMap,
+    request_id: String,
+}
+
+impl TaskPanic {
+    /// Sends a final `error` event, counts `ai.gateway.errors{type=panic}`,
+    /// and completes the summary (with the usage streamed so far), audit
+    /// record and span. A task that panicked after finishing its summary has
+    /// already recorded and billed the request, so only the span is ended.
+    async fn finish(self, span: &mut opentelemetry::global::BoxedSpan, message: String) {
+        tracing::error!(request_id = %self.request_id, "stream task panicked: {}", message);
+        let mut err = AppError::api_error("internal error while streaming the response");
+        err.retryable = false;
+        self.state.metrics.errors.add(1, &[KeyValue::new("type", "panic")]);
+        span.set_attribute(KeyValue::new("error.type", "panic"));
+        span.set_attribute(KeyValue::new("error.message", message.clone()));
+        if self.usage.is_finished() {
+            span.end();
+            return;
         }
-        metrics.latency_ms.record(
-            start.elapsed().as_millis() as f64,
-            &[KeyValue::new("stream", "true")],
-        );
-        tracing::info!(
-            request_id = %request_id,
-            model = %model,
-            latency_ms = start.elapsed().as_millis(),
-            status = 200,
-            "request completed"
-        );
-        if let Some(logger) = audit_logger.clone() {
-            if let Some(ctx) = audit_ctx.clone() {
-                let (body_value, parse_error) = parse_body_value(&audit_buf);
-                let record = ctx.finish(
-                    StatusCode::OK.as_u16(),
-                    headers_to_map(&response_headers),
-                    body_value,
-                    parse_error,
-                    audit_truncated,
-                    now_ms(),
-                );
-                logger.push(record).await;
-            }
+        let mut summary = self.summary;
+        self.usage.apply(&mut summary);
+        finish_summary(&self.state, span, summary, self.start, Some(&err), None);
+        let _ = self.tx.send(Ok(Bytes::from(error_event(err)))).await;
+        if let Some(ctx) = self.audit {
+            let mut record = ctx.finish(
+                StatusCode::OK.as_u16(),
+                headers_to_map(&self.response_headers),
+                Value::Null,
+                false,
+                false,
+                now_ms(),
+            );
+            record.meta.error = Some(format!("stream task panicked: {}", message));
+            self.state.push_audit(record).await;
         }
         span.end();
-    });
+    }
+}
 
-    let body_stream = ReceiverStream::new(rx);
-    let body = axum::body::Body::from_stream(body_stream);
-    Ok((StatusCode::OK, body).into_response())
+/// Token usage a stream task has seen so far, and whether it has finished
+/// its summary, shared with its `TaskPanic`.
+#[derive(Clone, Default)]
+struct LiveUsage(Arc<Mutex<StreamedUsage>>);
+
+#[derive(Default)]
+struct StreamedUsage {
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_hit: Option<bool>,
+    service_tier: Option<String>,
+    finished: bool,
+}
+
+impl LiveUsage {
+    /// `finish_summary` for the task itself; marks the request finished
+    /// first so a later panic does not record it again.
+    fn finish(
+        &self,
+        state: &AppState,
+        span: &mut opentelemetry::global::BoxedSpan,
+        summary: RequestSummary,
+        start: Instant,
+        err: Option<&AppError>,
+        output: Option<&Value>,
+    ) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).finished = true;
+        finish_summary(state, span, summary, start, err, output);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).finished
+    }
+
+    fn track(&self, summary: &RequestSummary) {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.input_tokens = summary.input_tokens;
+        usage.output_tokens = summary.output_tokens;
+        usage.cache_hit = summary.cache_hit;
+        usage.service_tier.clone_from(&summary.service_tier);
+    }
+
+    fn apply(&self, summary: &mut RequestSummary) {
+        let usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        summary.input_tokens = usage.input_tokens.or(summary.input_tokens);
+        summary.output_tokens = usage.output_tokens.or(summary.output_tokens);
+        summary.cache_hit = usage.cache_hit.or(summary.cache_hit);
+        if usage.service_tier.is_some() {
+            summary.service_tier = usage.service_tier.clone();
+        }
+    }
 }
 
 async fn handle_openai_chunk(
     parsed: OpenAIStreamChunk,
     state: &mut StreamState,
-    tx: &mpsc::Sender<Result<Bytes, std::convert::Infallible>>,
+    tx: &ClientSender,
 ) -> Result<(), AppError> {
     if !state.started {
         state.started = true;
-        state.message_id = parsed.id.clone();
-        state.model = parsed.model.clone();
+        if let Some(id) = parsed.id.clone().filter(|id| !id.is_empty()) {
+            state.message_id = Some(id);
+        }
+        if let Some(model) = parsed.model.clone().filter(|model| !model.is_empty()) {
+            state.model = Some(model);
+        }
 
-        let message = json!({
-            "id": state.message_id.clone().unwrap_or_else(|| "msg_stream".to_string()),
+        let mut message = json!({
+            "id": state.message_id.get_or_insert_with(ids::message_id).clone(),
             "type": "message",
             "role": "assistant",
+            "model": state.model.clone().unwrap_or_default(),
             "content": [],
             "usage": usage_zero(),
         });
+        state.api_version.shape_response(&mut message);
         let _ = tx
             .send(Ok(Bytes::from(sse_event(
                 "message_start",
@@ -552,75 +1162,51 @@ async fn handle_openai_chunk(
             .await;
     }
 
+    if let Some(usage) = parsed.usage.as_ref() {
+        state.usage.input_tokens = usage.prompt_tokens;
+        state.usage.output_tokens = usage.completion_tokens;
+    }
+    if let Some(tier) = parsed.service_tier {
+        state.usage.service_tier = Some(tier);
+    }
+
     if let Some(choice) = parsed.choices.into_iter().next() {
-        if let Some(delta) = choice.delta.content {
-            if !delta.is_empty() {
-                state.output_text.push_str(&delta);
-                let index = ensure_text_block(state, tx).await;
-                let _ = tx
-                    .send(Ok(Bytes::from(sse_event(
-                        "content_block_delta",
-                        json!({
-                            "type":"content_block_delta",
-                            "index": index,
-                            "delta": {"type":"text_delta","text": delta}
-                        }),
-                    ))))
-                    .await;
+        if let Some(mut delta) = choice.delta.content {
+            if let Some(filter) = state.prefill.as_mut() {
+                delta = filter.filter(delta);
             }
+            send_text_delta(state, tx, delta).await;
         }
 
-        if let Some(reasoning) = choice.delta.reasoning_content {
-            if reasoning.is_object() {
-                let parsed: Result<crate::models::OpenAIReasoningContentDelta, _> =
-                    serde_json::from_value(reasoning);
-                if let Ok(delta) = parsed {
-                    let index = ensure_thinking_block(state, tx).await;
-                    if let Some(thinking) = delta.thinking {
-                        state.reasoning_text.push_str(&thinking);
-                        let _ = tx
-                            .send(Ok(Bytes::from(sse_event(
-                                "content_block_delta",
-                                json!({
-                                    "type":"content_block_delta",
-                                    "index": index,
-                                    "delta": {"type":"thinking_delta","thinking": thinking}
-                                }),
-                            ))))
-                            .await;
-                    }
-                    if let Some(signature) = delta.signature {
-                        state.reasoning_signature = Some(signature.clone());
-                        let _ = tx
-                            .send(Ok(Bytes::from(sse_event(
-                                "content_block_delta",
-                                json!({
-                                    "type":"content_block_delta",
-                                    "index": index,
-                                    "delta": {"type":"signature_delta","signature": signature}
-                                }),
-                            ))))
-                            .await;
-                    }
+        match choice.delta.reasoning_content {
+            Some(OpenAIReasoning::Structured(delta)) => {
+                let index = ensure_thinking_block(state, tx).await;
+                if let Some(thinking) = delta.thinking {
+                    state.reasoning_text.push_str(&thinking);
+                    push_thinking_segment(state, &thinking, None);
+                    send_block_delta(tx, index, Delta::Thinking { thinking: &thinking }).await;
+                }
+                if let Some(signature) = delta.signature {
+                    push_thinking_segment(state, "", Some(&signature));
+                    send_block_delta(tx, index, Delta::Signature { signature: &signature }).await;
+                    state.reasoning_signature = Some(signature);
                 }
-            } else if let Some(thinking) = reasoning.as_str() {
-                state.reasoning_text.push_str(thinking);
+            }
+            Some(OpenAIReasoning::Text(thinking)) => {
+                state.reasoning_text.push_str(&thinking);
                 let index = ensure_thinking_block(state, tx).await;
-                let _ = tx
-                    .send(Ok(Bytes::from(sse_event(
-                        "content_block_delta",
-                        json!({
-                            "type":"content_block_delta",
-                            "index": index,
-                            "delta": {"type":"thinking_delta","thinking": thinking}
-                        }),
-                    ))))
-                    .await;
+                push_thinking_segment(state, &thinking, None);
+                send_block_delta(tx, index, Delta::Thinking { thinking: &thinking }).await;
             }
+            Some(OpenAIReasoning::Other(_)) | None => {}
         }
 
         if let Some(tool_calls) = choice.delta.tool_calls {
+            let normalize_ids = state.normalize_tool_ids;
             for call in tool_calls {
+                if !state.tool_calls.contains_key(&call.index) {
+                    close_finished_tools(state, tx).await;
+                }
                 let entry = state.tool_calls.entry(call.index).or_insert_with(|| {
                     let index = state.next_index;
                     state.next_index += 1;
@@ -628,6 +1214,7 @@ async fn handle_openai_chunk(
                         id: None,
                         name: None,
                         arguments: String::new(),
+                        validator: PartialJson::default(),
                         block_index: index,
                         started: false,
                         stopped: false,
@@ -635,7 +1222,12 @@ async fn handle_openai_chunk(
                 });
 
                 if let Some(id) = call.id {
-                    entry.id = Some(id);
+                    if !normalize_ids {
+                        entry.id = Some(id);
+                    } else if entry.id.is_none() {
+                        // Some downstreams repeat the id on every chunk.
+                        entry.id = Some(tool_ids::to_client(&id));
+                    }
                 }
                 if let Some(call_type) = call.call_type {
                     let _ = call_type;
@@ -645,24 +1237,24 @@ async fn handle_openai_chunk(
                         entry.name = Some(name);
                     }
                     if let Some(args) = function.arguments {
+                        entry.validator.feed(&args).map_err(|reason| {
+                            AppError::invalid_request(format!(
+                                "tool_use arguments invalid json ({}): {}",
+                                entry.name.as_deref().unwrap_or("unknown tool"),
+                                reason
+                            ))
+                        })?;
                         entry.arguments.push_str(&args);
-                        if entry.started {
-                            let _ = tx
-                                .send(Ok(Bytes::from(sse_event(
-                                    "content_block_delta",
-                                    json!({
-                                        "type":"content_block_delta",
-                                        "index": entry.block_index,
-                                        "delta": {"type":"input_json_delta","partial_json": args}
-                                    }),
-                                ))))
-                                .await;
+                        if entry.started && !entry.stopped {
+                            send_block_delta(tx, entry.block_index, Delta::InputJson { partial_json: &args }).await;
                         }
                     }
                 }
 
-                if !entry.started && entry.id.is_some() && entry.name.is_some() {
+                if !entry.started && entry.name.is_some() {
                     entry.started = true;
+                    // Downstreams that omit ids send them with the name, if at all.
+                    entry.id.get_or_insert_with(ids::tool_use_id);
                     let _ = tx
                         .send(Ok(Bytes::from(sse_event(
                             "content_block_start",
@@ -679,41 +1271,53 @@ async fn handle_openai_chunk(
                         ))))
                         .await;
                     if !entry.arguments.is_empty() {
-                        let buffered = entry.arguments.clone();
-                        let _ = tx
-                            .send(Ok(Bytes::from(sse_event(
-                                "content_block_delta",
-                                json!({
-                                    "type":"content_block_delta",
-                                    "index": entry.block_index,
-                                    "delta": {"type":"input_json_delta","partial_json": buffered}
-                                }),
-                            ))))
-                            .await;
+                        let buffered = Delta::InputJson {
+                            partial_json: &entry.arguments,
+                        };
+                        send_block_delta(tx, entry.block_index, buffered).await;
                     }
                 }
             }
         }
 
         if let Some(finish) = choice.finish_reason {
+            if let Some(pending) = state.prefill.as_mut().map(PrefillFilter::flush) {
+                send_text_delta(state, tx, pending).await;
+            }
             flush_open_blocks(state, tx).await?;
-            let stop_reason = map_finish_reason(&finish);
-            let _ = tx
-                .send(Ok(Bytes::from(sse_event(
-                    "message_delta",
-                    json!({
-                        "type":"message_delta",
-                        "delta": {"stop_reason": stop_reason},
-                        "usage": {"output_tokens": 0}
-                    }),
-                ))))
-                .await;
+            // The final usage usually arrives in a trailing chunk, so the
+            // message_delta is sent on `[DONE]`.
+            state.stop_reason = Some(map_finish_reason(&finish).to_string());
+            if let Some(sequence) = choice.stop_reason.as_ref().and_then(Value::as_str) {
+                state.stop_reason = Some("stop_sequence".to_string());
+                state.stop_sequence = Some(sequence.to_string());
+            }
         }
     }
 
     Ok(())
 }
 
+/// Sends the final `message_delta` with the stop reason, the matched stop
+/// sequence and the cumulative usage.
+async fn send_message_delta(
+    state: &mut StreamState,
+    tx: &ClientSender,
+) {
+    let mut body = json!({
+        "type":"message_delta",
+        "delta": {
+            "stop_reason": state.stop_reason.take().unwrap_or_else(|| "end_turn".to_string()),
+            "stop_sequence": state.stop_sequence.take(),
+        },
+        "usage": state.usage,
+    });
+    state.api_version.shape_response(&mut body);
+    let _ = tx
+        .send(Ok(Bytes::from(sse_event("message_delta", body))))
+        .await;
+}
+
 fn stream_output_messages(state: &StreamState) -> Option<serde_json::Value> {
     let mut msg = serde_json::Map::new();
     if !state.reasoning_text.is_empty() {
@@ -727,10 +1331,7 @@ fn stream_output_messages(state: &StreamState) -> Option<serde_json::Value> {
         if tool.name.is_none() {
             continue;
         }
-        let id = tool
-            .id
-            .clone()
-            .unwrap_or_else(|| "tool_call".to_string());
+        let id = tool.id.clone().unwrap_or_default();
         let name = tool.name.clone().unwrap_or_default();
         tool_calls.push(serde_json::json!({
             "id": id,
@@ -768,19 +1369,22 @@ fn stream_output_messages(state: &StreamState) -> Option<serde_json::Value> {
 fn stream_upstream_response(state: &StreamState) -> Option<String> {
     let mut content: Vec<serde_json::Value> = Vec::new();
 
-    if !state.reasoning_text.is_empty() || state.reasoning_signature.is_some() {
-        content.push(serde_json::json!({
-            "type": "thinking",
-            "thinking": state.reasoning_text,
-            "signature": state.reasoning_signature.clone().unwrap_or_else(|| "auto".to_string())
-        }));
-    }
-
-    if !state.output_text.is_empty() {
-        content.push(serde_json::json!({
-            "type": "text",
-            "text": state.output_text
-        }));
+    for segment in &state.segments {
+        match segment {
+            OutputSegment::Thinking { thinking, signature } => {
+                content.push(serde_json::json!({
+                    "type": "thinking",
+                    "thinking": thinking,
+                    "signature": signature.clone().unwrap_or_else(|| "auto".to_string())
+                }));
+            }
+            OutputSegment::Text(text) => {
+                content.push(serde_json::json!({
+                    "type": "text",
+                    "text": text
+                }));
+            }
+        }
     }
 
     for tool in state.tool_calls.values() {
@@ -815,13 +1419,34 @@ fn stream_upstream_response(state: &StreamState) -> Option<String> {
     serde_json::to_string(&message).ok()
 }
 
-async fn ensure_text_block(state: &mut StreamState, tx: &mpsc::Sender<Result<Bytes, std::convert::Infallible>>) -> u32 {
+async fn send_text_delta(
+    state: &mut StreamState,
+    tx: &ClientSender,
+    delta: String,
+) {
+    if delta.is_empty() {
+        return;
+    }
+    state.output_text.push_str(&delta);
+    let index = ensure_text_block(state, tx).await;
+    if let Some(OutputSegment::Text(text)) = state.segments.last_mut() {
+        text.push_str(&delta);
+    }
+    send_block_delta(tx, index, Delta::Text { text: &delta }).await;
+}
+
+async fn ensure_text_block(state: &mut StreamState, tx: &ClientSender) -> u32 {
     if let Some(index) = state.text_block_index {
         return index;
     }
+    if let Some(thinking_index) = state.thinking_block_index.take() {
+        close_block(tx, thinking_index).await;
+    }
+    close_finished_tools(state, tx).await;
     let index = state.next_index;
     state.next_index += 1;
     state.text_block_index = Some(index);
+    state.segments.push(OutputSegment::Text(String::new()));
     let _ = tx
         .send(Ok(Bytes::from(sse_event(
             "content_block_start",
@@ -837,14 +1462,22 @@ async fn ensure_text_block(state: &mut StreamState, tx: &mpsc::Sender<Result<Byt
 
 async fn ensure_thinking_block(
     state: &mut StreamState,
-    tx: &mpsc::Sender<Result<Bytes, std::convert::Infallible>>,
+    tx: &ClientSender,
 ) -> u32 {
     if let Some(index) = state.thinking_block_index {
         return index;
     }
+    if let Some(text_index) = state.text_block_index.take() {
+        close_block(tx, text_index).await;
+    }
+    close_finished_tools(state, tx).await;
     let index = state.next_index;
     state.next_index += 1;
     state.thinking_block_index = Some(index);
+    state.segments.push(OutputSegment::Thinking {
+        thinking: String::new(),
+        signature: None,
+    });
     let _ = tx
         .send(Ok(Bytes::from(sse_event(
             "content_block_start",
@@ -860,24 +1493,17 @@ async fn ensure_thinking_block(
 
 async fn flush_open_blocks(
     state: &mut StreamState,
-    tx: &mpsc::Sender<Result<Bytes, std::convert::Infallible>>,
+    tx: &ClientSender,
 ) -> Result<(), AppError> {
+    if state.reasoning_only_text && thinking_only(state) {
+        ensure_text_block(state, tx).await;
+    }
     if let Some(index) = state.text_block_index.take() {
-        let _ = tx
-            .send(Ok(Bytes::from(sse_event(
-                "content_block_stop",
-                json!({"type":"content_block_stop","index": index}),
-            ))))
-            .await;
+        close_block(tx, index).await;
     }
 
     if let Some(index) = state.thinking_block_index.take() {
-        let _ = tx
-            .send(Ok(Bytes::from(sse_event(
-                "content_block_stop",
-                json!({"type":"content_block_stop","index": index}),
-            ))))
-            .await;
+        close_block(tx, index).await;
     }
 
     for tool in state.tool_calls.values_mut() {
@@ -885,17 +1511,14 @@ async fn flush_open_blocks(
             if tool.arguments.is_empty() {
                 return Err(AppError::invalid_request("tool_use arguments empty"));
             }
-            if serde_json::from_str::<serde_json::Value>(&tool.arguments).is_err() {
+            // Every chunk already went through the validator, so a complete
+            // value means the arguments parse.
+            if !tool.validator.is_complete() {
                 return Err(AppError::invalid_request("tool_use arguments invalid json"));
             }
         }
         if !tool.stopped {
-            let _ = tx
-                .send(Ok(Bytes::from(sse_event(
-                    "content_block_stop",
-                    json!({"type":"content_block_stop","index": tool.block_index}),
-                ))))
-                .await;
+            close_block(tx, tool.block_index).await;
             tool.stopped = true;
         }
     }
@@ -903,25 +1526,78 @@ async fn flush_open_blocks(
     Ok(())
 }
 
-fn sse_event(event: &str, data: serde_json::Value) -> String {
+/// Nothing but thinking was streamed so far.
+fn thinking_only(state: &StreamState) -> bool {
+    !state.segments.is_empty()
+        && state.segments.iter().all(|segment| matches!(segment, OutputSegment::Thinking { .. }))
+        && state.tool_calls.values().all(|tool| !tool.started)
+}
+
+/// Stops started tool blocks whose arguments are already a complete JSON
+/// value. Called when a block of another kind or another tool call starts,
+/// since some downstreams send no finish_reason between a tool call and the
+/// content after it; incomplete tool blocks stay open.
+async fn close_finished_tools(state: &mut StreamState, tx: &ClientSender) {
+    let mut finished: Vec<&mut ToolCallState> = state
+        .tool_calls
+        .values_mut()
+        .filter(|tool| tool.started && !tool.stopped && tool.validator.is_complete())
+        .collect();
+    finished.sort_by_key(|tool| tool.block_index);
+    for tool in finished {
+        close_block(tx, tool.block_index).await;
+        tool.stopped = true;
+    }
+}
+
+async fn close_block(tx: &ClientSender, index: u32) {
+    let stop = BlockStop {
+        kind: "content_block_stop",
+        index,
+    };
+    let _ = tx.send(Ok(Bytes::from(sse_event("content_block_stop", stop)))).await;
+}
+
+async fn send_block_delta(tx: &ClientSender, index: u32, delta: Delta<'_>) {
+    let event = BlockDelta {
+        kind: "content_block_delta",
+        index,
+        delta,
+    };
+    let _ = tx.send(Ok(Bytes::from(sse_event("content_block_delta", event)))).await;
+}
+
+fn push_thinking_segment(state: &mut StreamState, thinking: &str, signature: Option<&str>) {
+    if let Some(OutputSegment::Thinking {
+        thinking: text,
+        signature: sig,
+    }) = state.segments.last_mut()
+    {
+        text.push_str(thinking);
+        if let Some(signature) = signature {
+            *sig = Some(signature.to_string());
+        }
+    }
+}
+
+fn sse_event(event: &str, data: impl Serialize) -> String {
+    let data = serde_json::to_string(&data).unwrap_or_default();
     format!("event: {}\ndata: {}\n\n", event, data)
 }
 
+/// The SSE `error` event; headers are already sent, so `retryable` and the
+/// downstream status travel in the event.
 fn error_event(err: AppError) -> String {
-    let body = json!({
-        "type": "error",
-        "error": {"type": err.error_type, "message": err.message}
-    });
+    let mut error = json!({"type": err.error_type(), "message": err.message, "retryable": err.retryable});
+    if let Some(status) = err.downstream_status {
+        error["downstream_status"] = json!(status.as_u16());
+    }
+    let body = json!({"type": "error", "error": error});
     sse_event("error", body)
 }
 
 fn usage_zero() -> AnthropicUsage {
-    AnthropicUsage {
-        input_tokens: 0,
-        output_tokens: 0,
-        cache_creation_input_tokens: 0,
-        cache_read_input_tokens: 0,
-    }
+    AnthropicUsage::default()
 }
 
 fn map_finish_reason(reason: &str) -> &str {
@@ -944,15 +1620,6 @@ fn serialize_json_for_trace(value: &serde_json::Value) -> String {
     }
 }
 
-fn headers_for_trace(headers: &axum::http::HeaderMap) -> String {
-    let mut out = serde_json::Map::new();
-    for (name, value) in headers.iter() {
-        let value = value.to_str().unwrap_or("[invalid]");
-        out.insert(name.to_string(), serde_json::Value::String(value.to_string()));
-    }
-    serde_json::Value::Object(out).to_string()
-}
-
 fn parse_body_value(bytes: &[u8]) -> (Value, bool) {
     match serde_json::from_slice::<Value>(bytes) {
         Ok(value) => (value, false),
@@ -980,21 +1647,52 @@ fn response_from_bytes(
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn catch_panic_returns_the_panic_message() {
+        assert_eq!(catch_panic(async {}).await, None);
+        let message = catch_panic(async { panic!("stream task {}", "exploded") }).await;
+        assert_eq!(message.as_deref(), Some("stream task exploded"));
+        let message = catch_panic(async { panic!("static") }).await;
+        assert_eq!(message.as_deref(), Some("static"));
+    }
+
+    #[tokio::test]
+    async fn panic_after_the_summary_does_not_record_the_request_again() {
+        use opentelemetry::trace::Tracer;
+
+        let state = crate::handlers::tests::test_state("http://127.0.0.1:9".to_string(), Default::default());
+        let summary = RequestSummary::new("req-1", "/v1/messages", "POST", "translate", &axum::http::HeaderMap::new());
+        let on_panic = |usage: &LiveUsage| {
+            let (tx, _rx) = mpsc::channel(8);
+            TaskPanic {
+                state: state.clone(),
+                tx: ClientSender::from(tx),
+                summary: summary.clone(),
+                usage: usage.clone(),
+                start: Instant::now(),
+                audit: None,
+                response_headers: Default::default(),
+                request_id: "req-1".to_string(),
+            }
+        };
+        let mut span = opentelemetry::global::tracer("test").start("stream");
+
+        let finished = LiveUsage::default();
+        finished.finish(&state, &mut span, summary.clone(), Instant::now(), None, None);
+        on_panic(&finished).finish(&mut span, "late".to_string()).await;
+        assert_eq!(state.usage.recent(0).len(), 1);
+
+        on_panic(&LiveUsage::default()).finish(&mut span, "early".to_string()).await;
+        let summaries = state.usage.recent(0);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[1].error_type.as_deref(), Some("api_error"));
+    }
+
     #[tokio::test]
     async fn stream_chunk_emits_message_and_text_delta() {
         let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(8);
-        let mut state = StreamState {
-            started: false,
-            message_id: None,
-            model: None,
-            next_index: 0,
-            text_block_index: None,
-            thinking_block_index: None,
-            tool_calls: HashMap::new(),
-            output_text: String::new(),
-            reasoning_text: String::new(),
-            reasoning_signature: None,
-        };
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
 
         let chunk = OpenAIStreamChunk {
             id: Some("chatcmpl-test".to_string()),
@@ -1008,7 +1706,10 @@ mod tests {
                     reasoning_content: None,
                 },
                 finish_reason: None,
+                stop_reason: None,
             }],
+            usage: None,
+            service_tier: None,
         };
 
         handle_openai_chunk(chunk, &mut state, &tx)
@@ -1027,21 +1728,214 @@ mod tests {
         assert!(output.contains("text_delta"));
     }
 
+    #[tokio::test]
+    async fn stream_interleaved_thinking_opens_new_blocks() {
+        let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(32);
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
+
+        let deltas = [
+            (None, Some("plan")),
+            (Some("answer"), None),
+            (None, Some("check")),
+            (Some("done"), None),
+        ];
+        for (content, reasoning) in deltas {
+            let chunk = OpenAIStreamChunk {
+                id: Some("chatcmpl-think".to_string()),
+                model: Some("qwen3".to_string()),
+                choices: vec![crate::models::OpenAIStreamChoice {
+                    index: 0,
+                    delta: crate::models::OpenAIStreamDelta {
+                        role: None,
+                        content: content.map(str::to_string),
+                        tool_calls: None,
+                        reasoning_content: reasoning.map(|r| OpenAIReasoning::Text(r.to_string())),
+                    },
+                    finish_reason: None,
+                    stop_reason: None,
+                }],
+                usage: None,
+                service_tier: None,
+            };
+            handle_openai_chunk(chunk, &mut state, &tx)
+                .await
+                .expect("ok");
+        }
+        flush_open_blocks(&mut state, &tx).await.expect("ok");
+        drop(tx);
+
+        let mut events = Vec::new();
+        while let Some(Ok(bytes)) = rx.recv().await {
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            let data = text.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+            events.push(serde_json::from_str::<Value>(data).unwrap());
+        }
+        let sequence: Vec<(String, u64)> = events
+            .iter()
+            .filter(|e| e["type"] == "content_block_start" || e["type"] == "content_block_stop")
+            .map(|e| (e["type"].as_str().unwrap().to_string(), e["index"].as_u64().unwrap()))
+            .collect();
+        let expected: Vec<(String, u64)> = [
+            ("content_block_start", 0),
+            ("content_block_stop", 0),
+            ("content_block_start", 1),
+            ("content_block_stop", 1),
+            ("content_block_start", 2),
+            ("content_block_stop", 2),
+            ("content_block_start", 3),
+            ("content_block_stop", 3),
+        ]
+        .iter()
+        .map(|(t, i)| (t.to_string(), *i))
+        .collect();
+        assert_eq!(sequence, expected);
+
+        let upstream: Value =
+            serde_json::from_str(&stream_upstream_response(&state).unwrap()).unwrap();
+        let types: Vec<_> = upstream["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b["type"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(types, vec!["thinking", "text", "thinking", "text"]);
+        assert_eq!(upstream["content"][2]["thinking"], "check");
+    }
+
+    #[tokio::test]
+    async fn reasoning_only_stream_can_end_with_an_empty_text_block() {
+        for reasoning_only_text in [false, true] {
+            let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(32);
+            let tx = ClientSender::from(tx);
+            let mut state = StreamState::new(AnthropicVersion::default());
+            state.reasoning_only_text = reasoning_only_text;
+
+            for finish_reason in [None, Some("stop".to_string())] {
+                let chunk = OpenAIStreamChunk {
+                    id: Some("chatcmpl-think".to_string()),
+                    model: Some("qwen3".to_string()),
+                    choices: vec![crate::models::OpenAIStreamChoice {
+                        index: 0,
+                        delta: crate::models::OpenAIStreamDelta {
+                            role: None,
+                            content: None,
+                            tool_calls: None,
+                            reasoning_content: finish_reason
+                                .is_none()
+                                .then(|| OpenAIReasoning::Text("plan".to_string())),
+                        },
+                        finish_reason,
+                        stop_reason: None,
+                    }],
+                    usage: None,
+                    service_tier: None,
+                };
+                handle_openai_chunk(chunk, &mut state, &tx).await.expect("ok");
+            }
+            // `[DONE]` flushes again; the text block is not added twice.
+            flush_open_blocks(&mut state, &tx).await.expect("ok");
+            drop(tx);
+
+            let mut starts = Vec::new();
+            while let Some(Ok(bytes)) = rx.recv().await {
+                let text = String::from_utf8_lossy(&bytes).to_string();
+                let data = text.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+                let event: Value = serde_json::from_str(data).unwrap();
+                if event["type"] == "content_block_start" {
+                    starts.push(event["content_block"]["type"].as_str().unwrap().to_string());
+                }
+            }
+            let expected = if reasoning_only_text {
+                vec!["thinking", "text"]
+            } else {
+                vec!["thinking"]
+            };
+            assert_eq!(starts, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn message_delta_carries_stop_sequence_and_final_usage() {
+        let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(16);
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
+
+        let chunks = [
+            OpenAIStreamChunk {
+                id: Some("chatcmpl-stop".to_string()),
+                model: Some("qwen3".to_string()),
+                choices: vec![crate::models::OpenAIStreamChoice {
+                    index: 0,
+                    delta: crate::models::OpenAIStreamDelta {
+                        role: None,
+                        content: Some("1, 2".to_string()),
+                        tool_calls: None,
+                        reasoning_content: None,
+                    },
+                    finish_reason: Some("stop".to_string()),
+                    stop_reason: Some(json!("END")),
+                }],
+                usage: None,
+                service_tier: None,
+            },
+            OpenAIStreamChunk {
+                id: Some("chatcmpl-stop".to_string()),
+                model: Some("qwen3".to_string()),
+                choices: Vec::new(),
+                usage: Some(crate::models::OpenAIUsage {
+                    prompt_tokens: 11,
+                    completion_tokens: 4,
+                    total_tokens: 15,
+                }),
+                service_tier: None,
+            },
+        ];
+        for chunk in chunks {
+            handle_openai_chunk(chunk, &mut state, &tx).await.expect("ok");
+        }
+        send_message_delta(&mut state, &tx).await;
+        drop(tx);
+
+        let mut deltas = Vec::new();
+        while let Some(Ok(bytes)) = rx.recv().await {
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            let data = text.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+            let event: Value = serde_json::from_str(data).unwrap();
+            if event["type"] == "message_delta" {
+                deltas.push(event);
+            }
+        }
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0]["delta"]["stop_reason"], "stop_sequence");
+        assert_eq!(deltas[0]["delta"]["stop_sequence"], "END");
+        assert_eq!(deltas[0]["usage"]["input_tokens"], 11);
+        assert_eq!(deltas[0]["usage"]["output_tokens"], 4);
+        assert_eq!(deltas[0]["usage"]["cache_read_input_tokens"], 0);
+    }
+
+    #[test]
+    fn prefill_filter_drops_echoed_prefix() {
+        let mut filter = PrefillFilter::new("Hello wor".to_string());
+        assert_eq!(filter.filter("Hel".to_string()), "");
+        assert_eq!(filter.filter("lo world".to_string()), "ld");
+        assert_eq!(filter.filter("!".to_string()), "!");
+
+        let mut filter = PrefillFilter::new("Hello".to_string());
+        assert_eq!(filter.filter("He".to_string()), "");
+        assert_eq!(filter.filter("y there".to_string()), "Hey there");
+        assert_eq!(filter.flush(), "");
+
+        let mut filter = PrefillFilter::new("Hello".to_string());
+        assert_eq!(filter.filter("Hel".to_string()), "");
+        assert_eq!(filter.flush(), "Hel");
+    }
+
     #[tokio::test]
     async fn stream_chunk_emits_tool_use_with_input_json() {
         let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(16);
-        let mut state = StreamState {
-            started: false,
-            message_id: None,
-            model: None,
-            next_index: 0,
-            text_block_index: None,
-            thinking_block_index: None,
-            tool_calls: HashMap::new(),
-            output_text: String::new(),
-            reasoning_text: String::new(),
-            reasoning_signature: None,
-        };
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
 
         let chunk = OpenAIStreamChunk {
             id: Some("chatcmpl-tool".to_string()),
@@ -1063,12 +1957,16 @@ mod tests {
                     reasoning_content: None,
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                stop_reason: None,
             }],
+            usage: None,
+            service_tier: None,
         };
 
         handle_openai_chunk(chunk, &mut state, &tx)
             .await
             .expect("ok");
+        send_message_delta(&mut state, &tx).await;
         drop(tx);
 
         let mut output = String::new();
@@ -1080,24 +1978,79 @@ mod tests {
 
         assert!(output.contains("tool_use"));
         assert!(output.contains("input_json_delta"));
-        assert!(output.contains("message_delta"));
+        assert!(output.contains("\"stop_reason\":\"tool_use\""));
+    }
+
+    #[tokio::test]
+    async fn finished_tool_block_closes_when_text_follows_without_finish_reason() {
+        let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(32);
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
+        let chunk = |content: Option<&str>, call: Option<(u32, &str, &str)>, finish: Option<&str>| OpenAIStreamChunk {
+            id: Some("chatcmpl-tool".to_string()),
+            model: Some("gpt-4o-mini".to_string()),
+            choices: vec![crate::models::OpenAIStreamChoice {
+                index: 0,
+                delta: crate::models::OpenAIStreamDelta {
+                    role: None,
+                    content: content.map(str::to_string),
+                    tool_calls: call.map(|(index, name, arguments)| {
+                        vec![crate::models::OpenAIToolCallDelta {
+                            index,
+                            id: Some(format!("call_{}", index)),
+                            call_type: Some("function".to_string()),
+                            function: Some(crate::models::OpenAIToolCallFunctionDelta {
+                                name: Some(name.to_string()),
+                                arguments: Some(arguments.to_string()),
+                            }),
+                        }]
+                    }),
+                    reasoning_content: None,
+                },
+                finish_reason: finish.map(str::to_string),
+                stop_reason: None,
+            }],
+            usage: None,
+            service_tier: None,
+        };
+
+        for parsed in [
+            chunk(None, Some((0, "get_weather", "{\"location\":\"Paris\"}")), None),
+            chunk(Some("Checking."), None, None),
+            chunk(None, Some((1, "get_time", "{\"tz\":")), None),
+            chunk(None, Some((2, "get_date", "{}")), None),
+            chunk(None, Some((1, "get_time", "\"UTC\"}")), None),
+            chunk(None, None, Some("tool_calls")),
+        ] {
+            handle_openai_chunk(parsed, &mut state, &tx).await.expect("ok");
+        }
+        drop(tx);
+
+        let mut events: Vec<String> = Vec::new();
+        while let Some(Ok(bytes)) = rx.recv().await {
+            for line in String::from_utf8_lossy(&bytes).lines() {
+                if let Some(data) = line.strip_prefix("data: ") {
+                    let event: Value = serde_json::from_str(data).unwrap();
+                    if let Some(index) = event["index"].as_u64() {
+                        let kind = event["type"].as_str().unwrap().trim_start_matches("content_block_");
+                        events.push(format!("{}:{}", kind, index));
+                    }
+                }
+            }
+        }
+        let position = |event: &str| events.iter().position(|e| e == event).expect(event);
+        assert!(position("stop:0") < position("start:1"));
+        assert!(position("start:1") < position("stop:1"));
+        // The incomplete call stays open until the stream ends.
+        assert!(position("start:3") < position("stop:2"));
+        assert_eq!(events.iter().filter(|e| e.starts_with("stop:")).count(), 4);
     }
 
     #[tokio::test]
     async fn stream_invalid_tool_use_arguments_emits_error() {
         let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(16);
-        let mut state = StreamState {
-            started: false,
-            message_id: None,
-            model: None,
-            next_index: 0,
-            text_block_index: None,
-            thinking_block_index: None,
-            tool_calls: HashMap::new(),
-            output_text: String::new(),
-            reasoning_text: String::new(),
-            reasoning_signature: None,
-        };
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
 
         let chunk = OpenAIStreamChunk {
             id: Some("chatcmpl-tool".to_string()),
@@ -1119,7 +2072,10 @@ mod tests {
                     reasoning_content: None,
                 },
                 finish_reason: Some("tool_calls".to_string()),
+                stop_reason: None,
             }],
+            usage: None,
+            service_tier: None,
         };
 
         let err = handle_openai_chunk(chunk, &mut state, &tx)
@@ -1142,6 +2098,53 @@ mod tests {
         assert!(!output.contains("message_delta"));
     }
 
+    #[tokio::test]
+    async fn stream_tool_use_arguments_fail_before_flush() {
+        let (tx, mut rx) = mpsc::channel::<Result<Bytes, std::convert::Infallible>>(16);
+        let tx = ClientSender::from(tx);
+        let mut state = StreamState::new(AnthropicVersion::default());
+        let chunk = |arguments: &str| OpenAIStreamChunk {
+            id: Some("chatcmpl-tool".to_string()),
+            model: Some("gpt-4o-mini".to_string()),
+            choices: vec![crate::models::OpenAIStreamChoice {
+                index: 0,
+                delta: crate::models::OpenAIStreamDelta {
+                    role: None,
+                    content: None,
+                    tool_calls: Some(vec![crate::models::OpenAIToolCallDelta {
+                        index: 0,
+                        id: Some("call_1".to_string()),
+                        call_type: Some("function".to_string()),
+                        function: Some(crate::models::OpenAIToolCallFunctionDelta {
+                            name: Some("get_weather".to_string()),
+                            arguments: Some(arguments.to_string()),
+                        }),
+                    }]),
+                    reasoning_content: None,
+                },
+                finish_reason: None,
+                stop_reason: None,
+            }],
+            usage: None,
+            service_tier: None,
+        };
+
+        handle_openai_chunk(chunk("{\"days\": [1, 2"), &mut state, &tx).await.expect("valid prefix");
+        let err = handle_openai_chunk(chunk("}"), &mut state, &tx)
+            .await
+            .expect_err("mismatched bracket");
+        assert_eq!(err.error_type(), "invalid_request_error");
+        assert!(err.message.contains("get_weather"), "{}", err.message);
+        assert!(err.message.contains("at byte 14"), "{}", err.message);
+
+        drop(tx);
+        let mut output = String::new();
+        while let Some(Ok(bytes)) = rx.recv().await {
+            output.push_str(&String::from_utf8_lossy(&bytes));
+        }
+        assert!(!output.contains("\"partial_json\":\"}\""));
+    }
+
     #[test]
     fn stream_output_messages_includes_tool_calls() {
         let mut state = StreamState {
@@ -1157,6 +2160,7 @@ mod tests {
                     id: Some("call_1".to_string()),
                     name: Some("get_weather".to_string()),
                     arguments: "{\"location\":\"Beijing\"}".to_string(),
+                    validator: PartialJson::default(),
                     block_index: 0,
                     started: true,
                     stopped: true,
@@ -1165,6 +2169,14 @@ mod tests {
             output_text: String::new(),
             reasoning_text: String::new(),
             reasoning_signature: None,
+            segments: Vec::new(),
+            prefill: None,
+            api_version: AnthropicVersion::default(),
+            normalize_tool_ids: false,
+            reasoning_only_text: false,
+            stop_reason: None,
+            stop_sequence: None,
+            usage: usage_zero(),
         };
 
         let output = stream_output_messages(&state).expect("output");
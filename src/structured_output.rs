@@ -0,0 +1,155 @@
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+
+use crate::config::JsonSchemaSupport;
+use crate::error::AppError;
+use crate::models::{OpenAIResponse, OpenAIResponseContent};
+use crate::provider::DownstreamRequest;
+use crate::state::AppState;
+
+/// The downstream body with `response_format: json_schema` replaced by
+/// `json_object` and the schema appended to the last user message as an
+/// instruction; `None` when the body does not ask for a schema.
+pub fn fallback_body(body: &Value) -> Option<Value> {
+    let mut body = body.clone();
+    let obj = body.as_object_mut()?;
+    let format = obj.get_mut("response_format")?;
+    if format.get("type").and_then(Value::as_str) != Some("json_schema") {
+        return None;
+    }
+    let schema = format.pointer("/json_schema/schema").cloned().unwrap_or(Value::Null);
+    *format = json!({"type": "json_object"});
+    let instruction = format!(
+        "Respond with a single JSON object that conforms to this JSON schema and nothing else:\n{}",
+        schema
+    );
+    let messages = obj.get_mut("messages")?.as_array_mut()?;
+    let last_user = messages
+        .iter_mut()
+        .rev()
+        .find(|message| message.get("role").and_then(Value::as_str) == Some("user"));
+    match last_user.and_then(|message| message.get_mut("content")) {
+        Some(Value::String(text)) => {
+            text.push_str("\n\n");
+            text.push_str(&instruction);
+        }
+        Some(Value::Array(parts)) => parts.push(json!({"type": "text", "text": instruction})),
+        _ => messages.push(json!({"role": "user", "content": instruction})),
+    }
+    Some(body)
+}
+
+/// Whether a downstream error response rejects the `json_schema` format
+/// rather than something else in the request.
+pub fn rejects_json_schema(status: StatusCode, body: &str) -> bool {
+    matches!(status.as_u16(), 400 | 422) && (body.contains("response_format") || body.contains("json_schema"))
+}
+
+/// Sends `downstream` with its encoded `body`; with `JsonSchemaSupport::Auto`,
+/// a rejected `json_schema` is resent once as `fallback_body`, which then
+/// replaces both. Returns whether the fallback was sent.
+pub async fn send(
+    state: &AppState,
+    client: &reqwest::Client,
+    downstream: &mut DownstreamRequest,
+    body: &mut Bytes,
+    support: JsonSchemaSupport,
+) -> Result<(reqwest::Response, bool), AppError> {
+    let resp = state.send_downstream(client, downstream, body).await?;
+    if support != JsonSchemaSupport::Auto || !matches!(resp.status().as_u16(), 400 | 422) {
+        return Ok((resp, false));
+    }
+    let Some(fallback) = fallback_body(&downstream.body) else {
+        return Ok((resp, false));
+    };
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let text = resp.text().await.unwrap_or_default();
+    if !rejects_json_schema(status, &text) {
+        let mut rebuilt = axum::http::Response::new(text);
+        *rebuilt.status_mut() = status;
+        *rebuilt.headers_mut() = headers;
+        return Ok((reqwest::Response::from(rebuilt), false));
+    }
+    tracing::warn!(
+        status = status.as_u16(),
+        "downstream rejected json_schema, retrying with json_object"
+    );
+    downstream.body = fallback;
+    *body = downstream.encoded_body();
+    Ok((state.send_downstream(client, downstream, body).await?, true))
+}
+
+/// Makes the first choice's text parse as JSON where possible by dropping
+/// markdown fences and prose around the outermost object. Returns whether
+/// the text changed.
+pub fn repair(resp: &mut OpenAIResponse) -> bool {
+    let Some(content) = resp.choices.first_mut().and_then(|choice| choice.message.content.as_mut()) else {
+        return false;
+    };
+    let text = content.joined_text();
+    let Some(repaired) = repaired_json(&text) else {
+        return false;
+    };
+    if repaired == text {
+        return false;
+    }
+    *content = OpenAIResponseContent::Text(repaired);
+    true
+}
+
+fn repaired_json(text: &str) -> Option<String> {
+    let trimmed = text.trim();
+    if serde_json::from_str::<Value>(trimmed).is_ok() {
+        return Some(trimmed.to_string());
+    }
+    let start = trimmed.find('{')?;
+    let end = trimmed.rfind('}')?;
+    let candidate = trimmed.get(start..=end)?;
+    serde_json::from_str::<Value>(candidate).ok()?;
+    Some(candidate.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_json_object_with_schema_instruction() {
+        let body = json!({
+            "model": "m",
+            "messages": [
+                {"role": "system", "content": "sys"},
+                {"role": "user", "content": "Extract the name."}
+            ],
+            "response_format": {"type": "json_schema", "json_schema": {"schema": {"type": "object"}, "strict": true}}
+        });
+        let fallback = fallback_body(&body).expect("fallback");
+        assert_eq!(fallback["response_format"], json!({"type": "json_object"}));
+        let text = fallback["messages"][1]["content"].as_str().unwrap();
+        assert!(text.starts_with("Extract the name.\n\n"), "{}", text);
+        assert!(text.ends_with(r#"{"type":"object"}"#), "{}", text);
+        assert_eq!(fallback["messages"][0]["content"], "sys");
+
+        assert!(fallback_body(&fallback).is_none());
+        assert!(rejects_json_schema(
+            StatusCode::BAD_REQUEST,
+            r#"{"error":{"message":"response_format json_schema is not supported"}}"#
+        ));
+        assert!(!rejects_json_schema(StatusCode::BAD_REQUEST, "max_tokens too large"));
+    }
+
+    #[test]
+    fn repairs_fenced_json() {
+        let mut resp: OpenAIResponse = serde_json::from_value(json!({
+            "id": "r", "model": "m",
+            "choices": [{"message": {"role": "assistant", "content": "Sure:\n```json\n{\"name\": \"Ada\"}\n```"}}]
+        }))
+        .unwrap();
+        assert!(repair(&mut resp));
+        let content = resp.choices[0].message.content.as_ref().unwrap().joined_text();
+        assert_eq!(content, r#"{"name": "Ada"}"#);
+        assert!(!repair(&mut resp));
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::SystemPromptConfig;
+use crate::translate::TranslateError;
+
+/// Where a part goes in the composed system prompt. Parts are emitted in
+/// slot order, then in the order they were added within a slot, so a source
+/// added late (a tenant policy, say) cannot end up before the gateway prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SystemSlot {
+    GatewayPrefix,
+    Client,
+    GatewaySuffix,
+}
+
+/// Collects every source of system text for one translated request and
+/// collapses them into the single system message the downstream gets.
+#[derive(Debug, Default)]
+pub struct SystemPrompt {
+    parts: Vec<(SystemSlot, String)>,
+}
+
+impl SystemPrompt {
+    /// `models.system_prompt` around the client's own system text.
+    pub fn new(config: &SystemPromptConfig, client: Option<String>) -> Self {
+        let mut prompt = Self::default();
+        if let Some(prefix) = &config.prefix {
+            prompt.push(SystemSlot::GatewayPrefix, prefix.clone());
+        }
+        if let Some(client) = client {
+            prompt.push(SystemSlot::Client, client);
+        }
+        if let Some(suffix) = &config.suffix {
+            prompt.push(SystemSlot::GatewaySuffix, suffix.clone());
+        }
+        prompt
+    }
+
+    /// Empty text is dropped rather than leaving a blank paragraph.
+    pub fn push(&mut self, slot: SystemSlot, text: String) {
+        if !text.is_empty() {
+            self.parts.push((slot, text));
+        }
+    }
+
+    /// Parts joined by blank lines; `None` when there is no system text.
+    pub fn compose(mut self, max_bytes: Option<usize>) -> Result<Option<String>, TranslateError> {
+        if self.parts.is_empty() {
+            return Ok(None);
+        }
+        self.parts.sort_by_key(|(slot, _)| *slot);
+        let composed = self
+            .parts
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if let Some(max) = max_bytes
+            && composed.len() > max
+        {
+            return Err(TranslateError::invalid_request(format!(
+                "system prompt is too long: {} bytes > {} maximum",
+                composed.len(),
+                max
+            )));
+        }
+        Ok(Some(composed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_are_ordered_by_slot_not_insertion() {
+        let mut prompt = SystemPrompt::default();
+        prompt.push(SystemSlot::GatewaySuffix, "suffix".to_string());
+        prompt.push(SystemSlot::Client, "client".to_string());
+        prompt.push(SystemSlot::GatewayPrefix, "prefix".to_string());
+        prompt.push(SystemSlot::Client, String::new());
+        prompt.push(SystemSlot::Client, "more client".to_string());
+        assert_eq!(
+            prompt.compose(None).unwrap().as_deref(),
+            Some("prefix\n\nclient\n\nmore client\n\nsuffix")
+        );
+        assert_eq!(SystemPrompt::default().compose(Some(0)).unwrap(), None);
+    }
+
+    #[test]
+    fn composed_prompt_over_the_limit_is_rejected() {
+        let config = SystemPromptConfig {
+            prefix: Some("Be safe.".to_string()),
+            suffix: None,
+            max_bytes: Some(16),
+        };
+        let fits = SystemPrompt::new(&config, Some("Hi".to_string()));
+        assert_eq!(fits.compose(config.max_bytes).unwrap().as_deref(), Some("Be safe.\n\nHi"));
+
+        let err = SystemPrompt::new(&config, Some("Be very brief.".to_string()))
+            .compose(config.max_bytes)
+            .unwrap_err();
+        assert_eq!(err.error_type, "invalid_request_error");
+        assert_eq!(err.message, "system prompt is too long: 24 bytes > 16 maximum");
+    }
+}
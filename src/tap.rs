@@ -0,0 +1,178 @@
+use axum::body::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::capture::CapturePolicy;
+
+const TAP_BUFFER: usize = 256;
+
+/// Tracks in-flight streaming responses by request id so admin clients can
+/// mirror their SSE events while they are being sent downstream.
+#[derive(Clone, Default)]
+pub struct TapRegistry {
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<Bytes>>>>,
+}
+
+impl TapRegistry {
+    pub fn register(&self, request_id: &str) -> TapHandle {
+        let (tx, _) = broadcast::channel(TAP_BUFFER);
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        channels.insert(request_id.to_string(), tx.clone());
+        TapHandle {
+            registry: self.clone(),
+            request_id: request_id.to_string(),
+            sender: tx,
+        }
+    }
+
+    pub fn subscribe(&self, request_id: &str) -> Option<broadcast::Receiver<Bytes>> {
+        let channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        channels.get(request_id).map(|tx| tx.subscribe())
+    }
+}
+
+/// Publishing side of a tap; unregisters the request when the stream ends.
+pub struct TapHandle {
+    registry: TapRegistry,
+    request_id: String,
+    sender: broadcast::Sender<Bytes>,
+}
+
+impl TapHandle {
+    pub fn publish(&self, chunk: &Bytes) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(chunk.clone());
+        }
+    }
+}
+
+impl Drop for TapHandle {
+    fn drop(&mut self) {
+        let mut channels = self
+            .registry
+            .channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if channels
+            .get(&self.request_id)
+            .is_some_and(|tx| tx.same_channel(&self.sender))
+        {
+            channels.remove(&self.request_id);
+        }
+    }
+}
+
+/// Reassembles tapped chunks into whole SSE events before redacting them: a
+/// `data:` line split across two network chunks would otherwise match in
+/// neither and reach the subscriber unredacted.
+pub struct SseRedactor {
+    capture: CapturePolicy,
+    pending: Vec<u8>,
+    /// Discarding up to the next boundary after chunks were skipped.
+    resync: bool,
+}
+
+impl SseRedactor {
+    pub fn new(capture: CapturePolicy) -> Self {
+        Self {
+            capture,
+            pending: Vec::new(),
+            resync: false,
+        }
+    }
+
+    /// The redacted events completed by `chunk`; `None` while the current
+    /// event is still incomplete.
+    pub fn push(&mut self, chunk: &[u8]) -> Option<String> {
+        self.pending.extend_from_slice(chunk);
+        if self.resync {
+            let start = self.pending.windows(2).position(|w| w == b"\n\n")? + 2;
+            self.pending.drain(..start);
+            self.resync = false;
+        }
+        let end = self.pending.windows(2).rposition(|w| w == b"\n\n")? + 2;
+        let events: Vec<u8> = self.pending.drain(..end).collect();
+        Some(redact_sse_chunk(&events, self.capture))
+    }
+
+    /// The redacted trailing partial event, if the stream ended inside one.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.resync || self.pending.is_empty() {
+            return None;
+        }
+        let rest = std::mem::take(&mut self.pending);
+        Some(redact_sse_chunk(&rest, self.capture))
+    }
+
+    /// After skipped chunks: drops the partial event and whatever arrives
+    /// before the next boundary, which is the tail of an unseen event.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.resync = true;
+    }
+}
+
+/// Applies the capture policy to every `data:` payload in complete SSE
+/// events while keeping event names intact.
+pub fn redact_sse_chunk(chunk: &[u8], capture: CapturePolicy) -> String {
+    let text = String::from_utf8_lossy(chunk);
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (body, newline) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        match body.strip_prefix("data:") {
+            Some(data) => {
+                out.push_str("data: ");
+                out.push_str(&capture.apply(data.trim_start()));
+            }
+            None => out.push_str(body),
+        }
+        out.push_str(newline);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscriber_receives_until_handle_dropped() {
+        let registry = TapRegistry::default();
+        assert!(registry.subscribe("req-1").is_none());
+        let handle = registry.register("req-1");
+        let mut rx = registry.subscribe("req-1").expect("registered");
+        handle.publish(&Bytes::from_static(b"event: ping\n\n"));
+        assert_eq!(rx.recv().await.unwrap(), Bytes::from_static(b"event: ping\n\n"));
+        drop(handle);
+        assert!(registry.subscribe("req-1").is_none());
+        assert!(rx.recv().await.is_err());
+    }
+
+    #[test]
+    fn redact_keeps_event_lines() {
+        let chunk = b"event: content_block_delta\ndata: {\"text\":\"secret\"}\n\n";
+        let redacted = redact_sse_chunk(chunk, CapturePolicy::None);
+        assert_eq!(redacted, "event: content_block_delta\ndata: [omitted]\n\n");
+        let full = redact_sse_chunk(chunk, CapturePolicy::Full);
+        assert_eq!(full, String::from_utf8_lossy(chunk));
+    }
+
+    #[test]
+    fn redactor_waits_for_the_event_boundary() {
+        let mut redactor = SseRedactor::new(CapturePolicy::None);
+        assert_eq!(redactor.push(b"event: content_block_delta\nda"), None);
+        assert_eq!(redactor.push(b"ta: {\"text\":\"sec"), None);
+        assert_eq!(
+            redactor.push(b"ret\"}\n\nevent: ping\n").as_deref(),
+            Some("event: content_block_delta\ndata: [omitted]\n\n")
+        );
+        assert_eq!(redactor.push(b"data: {}\n\n").as_deref(), Some("event: ping\ndata: [omitted]\n\n"));
+
+        redactor.reset();
+        assert_eq!(redactor.push(b"ret\"}\n\nevent: ping\ndata: {}\n\n").as_deref(), Some("event: ping\ndata: [omitted]\n\n"));
+    }
+}
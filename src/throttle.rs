@@ -0,0 +1,305 @@
+use axum::body::Bytes;
+use futures_util::stream;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::backpressure::ClientStream;
+use crate::config::Config;
+use crate::sse::{event_end, SseParser};
+use crate::tokenizer::Tokenizer;
+
+/// Longest single wait a bucket hands out.
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+/// Output pacing per API key (`limits.output_tokens_per_sec`): streamed
+/// `content_block_delta` events are released at no more than the key's
+/// tokens per second, with up to one second of burst. The budget is shared by
+/// all concurrent streams of a key; requests without a key share one budget.
+#[derive(Clone, Default)]
+pub struct OutputThrottle {
+    buckets: Arc<Mutex<HashMap<String, Arc<Mutex<Bucket>>>>>,
+}
+
+impl OutputThrottle {
+    /// Paces a client stream when the key has an output rate configured.
+    pub fn pace(&self, config: &Config, key_id: Option<&str>, body: ClientStream) -> ClientStream {
+        let Some(rate) = config.output_tokens_per_sec(key_id) else {
+            return body;
+        };
+        let bucket = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key_id.unwrap_or_default().to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(Bucket::new(rate))))
+            .clone();
+        bucket.lock().unwrap_or_else(|e| e.into_inner()).rate = rate;
+        paced(body, bucket)
+    }
+}
+
+/// Input token budget per API key (`limits.input_tokens_per_minute`): each
+/// request spends its estimated prompt tokens from a bucket that refills at
+/// the key's rate and holds at most one minute of it. A request the bucket
+/// cannot cover is rejected rather than queued; requests without a key share
+/// one budget.
+#[derive(Clone, Default)]
+pub struct InputLimiter {
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl InputLimiter {
+    /// Spends `tokens` from the key's budget when it has one configured, or
+    /// returns how long until the budget covers them.
+    pub fn check(&self, config: &Config, key_id: Option<&str>, tokens: u64) -> Result<(), Duration> {
+        let Some(per_minute) = config.input_tokens_per_minute(key_id) else {
+            return Ok(());
+        };
+        let rate = per_minute as f64 / 60.0;
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets
+            .entry(key_id.unwrap_or_default().to_string())
+            .or_insert_with(|| Bucket::with_capacity(rate, per_minute as f64));
+        bucket.rate = rate;
+        bucket.capacity = per_minute as f64;
+        bucket.try_take(tokens)
+    }
+}
+
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    allowance: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: f64) -> Self {
+        Self::with_capacity(rate, rate)
+    }
+
+    fn with_capacity(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            allowance: capacity,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.allowance = (self.allowance + refill).min(self.capacity);
+        self.last = now;
+    }
+
+    /// Spends `tokens` and returns how long to wait before sending them. The
+    /// allowance may go negative; the wait brings it back to zero.
+    fn take(&mut self, tokens: u64) -> Duration {
+        self.refill();
+        self.allowance -= tokens as f64;
+        if self.allowance >= 0.0 {
+            Duration::ZERO
+        } else {
+            wait(-self.allowance / self.rate)
+        }
+    }
+
+    /// Spends `tokens` only when the allowance covers them, otherwise returns
+    /// how long until it will. More than the capacity needs a full bucket,
+    /// which it then overdraws, so oversized requests are slowed rather than
+    /// refused forever.
+    fn try_take(&mut self, tokens: u64) -> Result<(), Duration> {
+        self.refill();
+        let needed = (tokens as f64).min(self.capacity);
+        if self.allowance >= needed {
+            self.allowance -= tokens as f64;
+            Ok(())
+        } else {
+            Err(wait((needed - self.allowance) / self.rate))
+        }
+    }
+}
+
+/// A bucket wait in seconds as a `Duration`, capped at `MAX_WAIT`.
+fn wait(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs).map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT))
+}
+
+struct Paced {
+    inner: ClientStream,
+    bucket: Arc<Mutex<Bucket>>,
+    buffer: Vec<u8>,
+    ready: VecDeque<Bytes>,
+    error: Option<std::io::Error>,
+    done: bool,
+}
+
+impl Paced {
+    /// Moves every complete event out of the buffer so each one is paced on
+    /// its own, however the producer chunked them.
+    fn split_events(&mut self) {
+        while let Some(end) = event_end(&self.buffer) {
+            let rest = self.buffer.split_off(end);
+            let event = std::mem::replace(&mut self.buffer, rest);
+            self.ready.push_back(Bytes::from(event));
+        }
+    }
+}
+
+fn paced(inner: ClientStream, bucket: Arc<Mutex<Bucket>>) -> ClientStream {
+    let state = Paced {
+        inner,
+        bucket,
+        buffer: Vec::new(),
+        ready: VecDeque::new(),
+        error: None,
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.ready.pop_front() {
+                let tokens = delta_tokens(&event);
+                if tokens > 0 {
+                    let delay = state.bucket.lock().unwrap_or_else(|e| e.into_inner()).take(tokens);
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+                return Some((Ok(event), state));
+            }
+            if let Some(err) = state.error.take() {
+                return Some((Err(err), state));
+            }
+            if state.done {
+                return None;
+            }
+            match state.inner.next().await {
+                Some(Ok(bytes)) => {
+                    state.buffer.extend_from_slice(&bytes);
+                    state.split_events();
+                }
+                Some(Err(err)) => {
+                    state.error = Some(err);
+                    state.done = true;
+                    flush(&mut state);
+                }
+                None => {
+                    state.done = true;
+                    flush(&mut state);
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+fn flush(state: &mut Paced) {
+    if !state.buffer.is_empty() {
+        let rest = std::mem::take(&mut state.buffer);
+        state.ready.push_back(Bytes::from(rest));
+    }
+}
+
+/// Estimated tokens carried by the `content_block_delta` events in `event`;
+/// other events are free.
+fn delta_tokens(event: &[u8]) -> u64 {
+    let mut parser = SseParser::default();
+    let mut events = parser.push(event);
+    events.extend(parser.finish());
+    events
+        .iter()
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .filter(|data| data.get("type").and_then(Value::as_str) == Some("content_block_delta"))
+        .map(|data| {
+            let delta = data.get("delta").unwrap_or(&Value::Null);
+            let text = ["text", "thinking", "partial_json"]
+                .iter()
+                .find_map(|field| delta.get(*field).and_then(Value::as_str))
+                .unwrap_or_default();
+            Tokenizer::Heuristic.count_text(text).max(1)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(text: &str) -> String {
+        format!(
+            "event: content_block_delta\ndata: {}\n\n",
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}})
+        )
+    }
+
+    #[tokio::test]
+    async fn paces_deltas_to_configured_rate() {
+        // 100 tokens per second with 100 of burst; four 30-token deltas
+        // overdraw by 20, a 200ms wait.
+        let chunk = format!("event: ping\ndata: {{\"type\":\"ping\"}}\n\n{}", delta(&"x".repeat(120)).repeat(4));
+        let body: ClientStream = stream::iter(vec![Ok(Bytes::from(chunk)), Ok(Bytes::from("data: [tail]"))]).boxed();
+        let bucket = Arc::new(Mutex::new(Bucket::new(100.0)));
+        let start = Instant::now();
+        let events: Vec<Bytes> = paced(body, bucket).map(|chunk| chunk.expect("chunk")).collect().await;
+
+        assert_eq!(events.len(), 6);
+        assert!(events[0].starts_with(b"event: ping"));
+        assert_eq!(&events[5][..], b"data: [tail]");
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(180) && elapsed < Duration::from_millis(600), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn input_budget_rejects_requests_it_cannot_cover() {
+        let mut config = Config::from_yaml(
+            "server: {}\ndownstream:\n  base_url: \"http://127.0.0.1:9\"\nmodels: {}\nlimits:\n  input_tokens_per_minute: 600\n  input_tokens_per_minute_keys:\n    small: 60\nobservability: {}\n",
+        )
+        .expect("config");
+        let limiter = InputLimiter::default();
+
+        assert!(limiter.check(&config, None, 400).is_ok());
+        let wait = limiter.check(&config, None, 400).expect_err("budget spent");
+        // 200 tokens short at 10 tokens per second.
+        assert!(wait > Duration::from_secs(19) && wait <= Duration::from_secs(20), "{:?}", wait);
+        assert!(limiter.check(&config, None, 150).is_ok());
+
+        // Keys have their own buckets and overrides.
+        assert!(limiter.check(&config, Some("small"), 60).is_ok());
+        assert!(limiter.check(&config, Some("small"), 1).is_err());
+        assert!(limiter.check(&config, Some("other"), 600).is_ok());
+
+        config.limits.input_tokens_per_minute = None;
+        assert!(limiter.check(&config, None, 10_000).is_ok());
+    }
+
+    #[test]
+    fn waits_are_capped() {
+        let mut bucket = Bucket::new(1e-20);
+        assert_eq!(bucket.take(10), MAX_WAIT);
+        let mut bucket = Bucket::new(f64::NAN);
+        assert_eq!(bucket.take(10), MAX_WAIT);
+
+        let config = |rate: &str| {
+            Config::from_yaml(&format!(
+                "server: {{}}\ndownstream: {{}}\nmodels: {{}}\nlimits:\n  output_tokens_per_sec: {}\nobservability: {{}}\n",
+                rate
+            ))
+        };
+        assert!(config(".nan").is_err());
+        assert!(config("1e-20").is_err());
+        assert!(config("0.5").is_ok());
+    }
+
+    #[test]
+    fn oversized_requests_wait_for_a_full_bucket() {
+        let mut bucket = Bucket::with_capacity(10.0, 100.0);
+        assert!(bucket.try_take(250).is_ok());
+        assert!(bucket.allowance < 0.0);
+        assert!(bucket.try_take(250).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tiktoken_rs::CoreBPE;
+
+use crate::config::{TokenizerSpec, TokenizersConfig};
+use crate::models::{OpenAIContentPart, OpenAIMessage, OpenAIMessageContent, OpenAIRequest};
+
+/// Per-message framing overhead (role, separators) added on top of content.
+const MESSAGE_OVERHEAD: u64 = 4;
+/// Flat cost charged per image part; real cost depends on resolution.
+const IMAGE_TOKENS: u64 = 1_600;
+
+/// Counts tokens for one model family. `Heuristic` assumes about four bytes
+/// per token of the serialized message and is used when nothing is configured.
+#[derive(Clone, Default)]
+pub enum Tokenizer {
+    #[default]
+    Heuristic,
+    Tiktoken(&'static CoreBPE),
+    HuggingFace(Arc<tokenizers::Tokenizer>),
+}
+
+impl Tokenizer {
+    pub fn load(spec: &TokenizerSpec) -> Result<Self, String> {
+        match spec.kind.as_str() {
+            "tiktoken" => {
+                let bpe = match spec.encoding.as_deref().unwrap_or_default() {
+                    "cl100k_base" => tiktoken_rs::cl100k_base_singleton(),
+                    "o200k_base" => tiktoken_rs::o200k_base_singleton(),
+                    "p50k_base" => tiktoken_rs::p50k_base_singleton(),
+                    "r50k_base" => tiktoken_rs::r50k_base_singleton(),
+                    other => return Err(format!("unknown tiktoken encoding: {}", other)),
+                };
+                Ok(Self::Tiktoken(bpe))
+            }
+            "huggingface" => {
+                let path = spec.path.as_deref().unwrap_or_default();
+                tokenizers::Tokenizer::from_file(path)
+                    .map(|t| Self::HuggingFace(Arc::new(t)))
+                    .map_err(|e| format!("tokenizer load error ({}): {}", path, e))
+            }
+            _ => Ok(Self::Heuristic),
+        }
+    }
+
+    pub fn count_text(&self, text: &str) -> u64 {
+        match self {
+            Self::Heuristic => text.len() as u64 / 4,
+            Self::Tiktoken(bpe) => bpe.encode_with_special_tokens(text).len() as u64,
+            Self::HuggingFace(tokenizer) => match tokenizer.encode_fast(text, false) {
+                Ok(encoding) => encoding.len() as u64,
+                Err(_) => text.len() as u64 / 4,
+            },
+        }
+    }
+
+    pub fn count_messages(&self, messages: &[OpenAIMessage]) -> u64 {
+        messages.iter().map(|msg| self.count_message(msg)).sum()
+    }
+
+    /// Prompt tokens for a request: messages plus tool definitions.
+    pub fn count_request(&self, req: &OpenAIRequest) -> u64 {
+        let tools = req
+            .tools
+            .as_ref()
+            .map(|tools| self.count_text(&serde_json::to_string(tools).unwrap_or_default()))
+            .unwrap_or(0);
+        self.count_messages(&req.messages) + tools
+    }
+
+    fn count_message(&self, msg: &OpenAIMessage) -> u64 {
+        if let Self::Heuristic = self {
+            let bytes = serde_json::to_string(msg).map(|s| s.len()).unwrap_or(0);
+            return bytes as u64 / 4 + MESSAGE_OVERHEAD;
+        }
+        let content = match &msg.content {
+            Some(OpenAIMessageContent::Text(text)) => self.count_text(text),
+            Some(OpenAIMessageContent::Parts(parts)) => parts
+                .iter()
+                .map(|part| match part {
+                    OpenAIContentPart::Text { text } => self.count_text(text),
+                    OpenAIContentPart::ImageUrl { .. } => IMAGE_TOKENS,
+                })
+                .sum(),
+            None => 0,
+        };
+        let tool_calls = msg
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| {
+                self.count_text(&call.function.name) + self.count_text(&call.function.arguments)
+            })
+            .sum::<u64>();
+        let reasoning = msg
+            .reasoning_content
+            .as_ref()
+            .and_then(|v| v.as_str())
+            .map(|text| self.count_text(text))
+            .unwrap_or(0);
+        content + tool_calls + reasoning + MESSAGE_OVERHEAD
+    }
+}
+
+/// Tokenizers resolved per downstream model, with a configurable default.
+#[derive(Clone, Default)]
+pub struct Tokenizers {
+    default: Tokenizer,
+    models: HashMap<String, Tokenizer>,
+}
+
+impl Tokenizers {
+    pub fn from_config(config: &TokenizersConfig) -> Result<Self, String> {
+        let default = match config.default.as_ref() {
+            Some(spec) => Tokenizer::load(spec)?,
+            None => Tokenizer::Heuristic,
+        };
+        let models = config
+            .models
+            .iter()
+            .map(|(model, spec)| Tokenizer::load(spec).map(|t| (model.clone(), t)))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        Ok(Self { default, models })
+    }
+
+    pub fn for_model(&self, model: &str) -> &Tokenizer {
+        self.models.get(model).unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str) -> OpenAIMessage {
+        OpenAIMessage {
+            role: role.to_string(),
+            content: Some(OpenAIMessageContent::Text(text.to_string())),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        }
+    }
+
+    #[test]
+    fn tiktoken_counts_text_and_messages() {
+        let spec = TokenizerSpec {
+            kind: "tiktoken".to_string(),
+            encoding: Some("cl100k_base".to_string()),
+            path: None,
+        };
+        let tokenizer = Tokenizer::load(&spec).unwrap();
+        assert_eq!(tokenizer.count_text("hello world"), 2);
+        assert_eq!(
+            tokenizer.count_messages(&[message("user", "hello world")]),
+            2 + MESSAGE_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn unknown_models_fall_back_to_default() {
+        let config = TokenizersConfig {
+            default: None,
+            models: HashMap::from([(
+                "gpt-4o".to_string(),
+                TokenizerSpec {
+                    kind: "tiktoken".to_string(),
+                    encoding: Some("o200k_base".to_string()),
+                    path: None,
+                },
+            )]),
+        };
+        let tokenizers = Tokenizers::from_config(&config).unwrap();
+        assert!(matches!(tokenizers.for_model("gpt-4o"), Tokenizer::Tiktoken(_)));
+        assert!(matches!(tokenizers.for_model("kimi-k2.5"), Tokenizer::Heuristic));
+        assert_eq!(tokenizers.for_model("kimi-k2.5").count_text("abcdefgh"), 2);
+    }
+}
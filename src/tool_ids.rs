@@ -0,0 +1,82 @@
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator};
+
+use crate::models::{AnthropicContentBlock, AnthropicResponse};
+
+/// Marks ids issued by the gateway; the rest of the id is a random nonce and
+/// the hex of the downstream id.
+const PREFIX: &str = "toolu_gw";
+const NONCE_LEN: usize = 16;
+
+/// `models.normalize_tool_ids`: the client-facing id for a downstream tool
+/// call id. It has Anthropic's `toolu_` shape and is unique per call even
+/// when the downstream reuses ids across turns. The downstream id is encoded
+/// in it, so `to_downstream` needs no table shared between replicas.
+pub fn to_client(downstream: &str) -> String {
+    let nonce = RandomIdGenerator::default().new_span_id().to_string();
+    let hex: String = downstream.bytes().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}{}", PREFIX, nonce, hex)
+}
+
+/// The downstream id a client-facing id was issued for; other ids (the
+/// client's own, or any id while normalization is off) are returned as-is.
+pub fn to_downstream(id: &str) -> String {
+    decode(id).unwrap_or_else(|| id.to_string())
+}
+
+fn decode(id: &str) -> Option<String> {
+    let hex = id.strip_prefix(PREFIX)?.get(NONCE_LEN..)?;
+    if hex.is_empty() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Replaces the `tool_use` ids of a translated response.
+pub fn normalize_response(resp: &mut AnthropicResponse) {
+    for block in &mut resp.content {
+        if let AnthropicContentBlock::ToolUse { id, .. } = block {
+            *id = to_client(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::models::AnthropicRequest;
+    use crate::translate::anthropic_to_openai;
+    use serde_json::json;
+
+    #[test]
+    fn issues_unique_client_ids_and_restores_downstream_ids() {
+        let first = to_client("call_0");
+        let second = to_client("call_0");
+        assert_ne!(first, second);
+        assert!(first.starts_with("toolu_"));
+        assert!(first.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'));
+        assert_eq!(to_downstream(&first), "call_0");
+        assert_eq!(to_downstream(&second), "call_0");
+        assert_eq!(to_downstream("toolu_01A09q90qw90lq917835lq9"), "toolu_01A09q90qw90lq917835lq9");
+        assert_eq!(to_downstream("toolu_gwshort"), "toolu_gwshort");
+
+        let config = Config::from_yaml("server: {}\ndownstream: {}\nmodels: {}\nlimits: {}\nobservability: {}\n")
+            .expect("config");
+        let req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 16,
+            "messages": [
+                {"role": "assistant", "content": [{"type": "tool_use", "id": first, "name": "f", "input": {}}]},
+                {"role": "user", "content": [{"type": "tool_result", "tool_use_id": first, "content": "ok"}]}
+            ]
+        }))
+        .expect("parse");
+        let out = anthropic_to_openai(req, &config).expect("translate ok");
+        assert_eq!(out.messages[0].tool_calls.as_ref().unwrap()[0].id, "call_0");
+        assert_eq!(out.messages[1].tool_call_id.as_deref(), Some("call_0"));
+    }
+}
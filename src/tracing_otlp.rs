@@ -4,16 +4,33 @@ use opentelemetry_sdk::trace::SdkTracerProvider;
 use opentelemetry_sdk::trace::span_processor_with_async_runtime::BatchSpanProcessor;
 use opentelemetry_sdk::runtime;
 use std::sync::OnceLock;
-use tracing::warn;
 use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig, Protocol};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 use base64::Engine;
+use axum::http::HeaderMap;
+use opentelemetry::{Array, KeyValue, StringValue, Value as OtelValue};
+use opentelemetry::trace::Span;
+use serde::Serialize;
+use serde_json::Value;
+
+use opentelemetry::Context;
+use opentelemetry::trace::Status;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{SpanData, SpanProcessor};
+
+use opentelemetry::trace::{SpanId, TraceId};
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator};
+
+use crate::access_log::RequestSummary;
+use crate::capture::CapturePolicy;
+use crate::config::{LangfuseConfig, TracingConfig};
 
 pub fn init_tracer_grpc(
     otlp_endpoint: String,
     service_name: String,
     otlp_timeout_ms: u64,
+    sampling: &TracingConfig,
 ) -> Result<SdkTracerProvider, String> {
     let exporter = SpanExporter::builder()
         .with_tonic()
@@ -24,7 +41,7 @@ pub fn init_tracer_grpc(
 
     let batch = BatchSpanProcessor::builder(exporter, runtime::Tokio).build();
     let provider = SdkTracerProvider::builder()
-        .with_span_processor(batch)
+        .with_span_processor(SamplingSpanProcessor::new(batch, sampling))
         .with_resource(Resource::builder().with_service_name(service_name).build())
         .build();
 
@@ -38,6 +55,7 @@ pub fn init_tracer_langfuse_http(
     timeout_ms: u64,
     public_key: String,
     secret_key: String,
+    sampling: &TracingConfig,
 ) -> Result<SdkTracerProvider, String> {
     let auth = base64::engine::general_purpose::STANDARD.encode(format!(
         "{}:{}",
@@ -56,7 +74,7 @@ pub fn init_tracer_langfuse_http(
 
     let batch = BatchSpanProcessor::builder(exporter, runtime::Tokio).build();
     let provider = SdkTracerProvider::builder()
-        .with_span_processor(batch)
+        .with_span_processor(SamplingSpanProcessor::new(batch, sampling))
         .with_resource(Resource::builder().with_service_name(service_name).build())
         .build();
 
@@ -72,20 +90,411 @@ pub fn init_tracer_noop(service_name: String) -> SdkTracerProvider {
     provider
 }
 
+/// Drops finished spans before export according to `observability.tracing`.
+/// The decision is made at span end so failed requests can always be kept;
+/// the ratio check is keyed on the trace id so it is stable per trace.
+#[derive(Debug)]
+pub struct SamplingSpanProcessor<P> {
+    inner: P,
+    sample_ratio: f64,
+    stream_sample_ratio: f64,
+    always_sample_errors: bool,
+}
+
+impl<P: SpanProcessor> SamplingSpanProcessor<P> {
+    pub fn new(inner: P, config: &TracingConfig) -> Self {
+        Self {
+            inner,
+            sample_ratio: config.sample_ratio,
+            stream_sample_ratio: config.stream_sample_ratio.unwrap_or(config.sample_ratio),
+            always_sample_errors: config.always_sample_errors,
+        }
+    }
+
+    fn should_export(&self, span: &SpanData) -> bool {
+        if self.always_sample_errors && span_has_error(span) {
+            return true;
+        }
+        let is_stream = span
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "stream" && kv.value.as_str() == "true");
+        let ratio = if is_stream {
+            self.stream_sample_ratio
+        } else {
+            self.sample_ratio
+        };
+        trace_id_below_ratio(span.span_context.trace_id().to_bytes(), ratio)
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for SamplingSpanProcessor<P> {
+    fn on_start(&self, span: &mut opentelemetry_sdk::trace::Span, cx: &Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if self.should_export(&span) {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+fn span_has_error(span: &SpanData) -> bool {
+    matches!(span.status, Status::Error { .. })
+        || span.attributes.iter().any(|kv| kv.key.as_str() == "error.type")
+}
+
+fn trace_id_below_ratio(trace_id: [u8; 16], ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    if ratio <= 0.0 {
+        return false;
+    }
+    let mut low = [0u8; 8];
+    low.copy_from_slice(&trace_id[8..]);
+    let value = u64::from_be_bytes(low) >> 1;
+    (value as f64) < ratio * (1u64 << 63) as f64
+}
+
 fn hold_tracer_provider(provider: SdkTracerProvider) {
     static GLOBAL_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
     let _ = GLOBAL_PROVIDER.set(provider.clone());
     global::set_tracer_provider(provider);
 }
 
-pub fn spawn_tracer_watchdog(provider: SdkTracerProvider) -> std::thread::JoinHandle<()> {
-    std::thread::spawn(move || loop {
-        std::thread::sleep(Duration::from_secs(30));
-        if let Err(err) = provider.force_flush() {
-            warn!(
-                "tracer provider force_flush failed (batch worker may be down): {}",
-                err
-            );
+/// Response header carrying the request's trace id.
+pub const TRACE_HEADER: &str = "x-gateway-trace-id";
+
+/// Ids of a request's `ai.gateway.request` span, chosen when the request
+/// arrives so access logs, audit records and the `x-gateway-trace-id`
+/// header carry them on every path, including failures before the span
+/// starts.
+#[derive(Clone, Copy, Debug)]
+pub struct TraceIds {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+}
+
+impl TraceIds {
+    pub fn generate() -> Self {
+        let ids = RandomIdGenerator::default();
+        Self {
+            trace_id: ids.new_trace_id(),
+            span_id: ids.new_span_id(),
+        }
+    }
+}
+
+/// Request header with comma-separated Langfuse trace tags.
+pub const LANGFUSE_TAGS_HEADER: &str = "langfuse-tags";
+
+/// Langfuse grouping taken from request headers rather than the payload:
+/// the session id from `observability.langfuse.session_header` and the
+/// `langfuse-tags` header.
+#[derive(Clone, Debug, Default)]
+pub struct LangfuseAttribution {
+    pub session_id: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl LangfuseAttribution {
+    pub fn from_headers(headers: &HeaderMap, config: &LangfuseConfig) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let session_id = config
+            .session_header
+            .as_deref()
+            .and_then(header)
+            .map(str::to_string);
+        let mut tags: Vec<String> = Vec::new();
+        for tag in header(LANGFUSE_TAGS_HEADER).unwrap_or_default().split(',') {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                tags.push(tag.to_string());
+            }
         }
-    })
+        Self { session_id, tags }
+    }
+}
+
+/// Tags the span as a Langfuse generation with model, parameters, input and
+/// user/session ids taken from the Anthropic request payload.
+pub fn set_langfuse_generation_input<S: Span>(
+    span: &mut S,
+    model: &str,
+    payload: &Value,
+    attribution: &LangfuseAttribution,
+    capture: CapturePolicy,
+) {
+    span.set_attributes(langfuse_generation_input(model, payload, attribution, capture));
+}
+
+/// The attributes `set_langfuse_generation_input` sets, for callers that go
+/// on to rewrite or move the payload before the span exists. The session
+/// header wins over `metadata.session_id`.
+pub fn langfuse_generation_input(
+    model: &str,
+    payload: &Value,
+    attribution: &LangfuseAttribution,
+    capture: CapturePolicy,
+) -> Vec<KeyValue> {
+    #[derive(Serialize)]
+    struct Input<'a> {
+        messages: &'a Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        system: Option<&'a Value>,
+    }
+
+    let mut attributes = vec![
+        KeyValue::new("langfuse.observation.type", "generation"),
+        KeyValue::new("langfuse.observation.model.name", model.to_string()),
+        KeyValue::new("gen_ai.request.model", model.to_string()),
+    ];
+    let input = Input {
+        messages: payload.get("messages").unwrap_or(&Value::Null),
+        system: payload.get("system"),
+    };
+    attributes.push(KeyValue::new(
+        "langfuse.observation.input",
+        capture.apply_with(|| serde_json::to_string(&input).unwrap_or_default()),
+    ));
+
+    let parameters: BTreeMap<&str, &Value> = ["max_tokens", "temperature", "top_p", "top_k", "stream"]
+        .into_iter()
+        .filter_map(|key| Some((key, payload.get(key).filter(|v| !v.is_null())?)))
+        .collect();
+    attributes.push(KeyValue::new(
+        "langfuse.observation.model.parameters",
+        serde_json::to_string(&parameters).unwrap_or_default(),
+    ));
+
+    let metadata = payload.get("metadata");
+    if let Some(user_id) = metadata.and_then(|m| m.get("user_id")).and_then(Value::as_str) {
+        attributes.push(KeyValue::new("langfuse.user.id", user_id.to_string()));
+    }
+    let session_id = attribution.session_id.as_deref().or_else(|| {
+        metadata
+            .and_then(|m| m.get("session_id"))
+            .and_then(Value::as_str)
+    });
+    if let Some(session_id) = session_id {
+        attributes.push(KeyValue::new("langfuse.session.id", session_id.to_string()));
+    }
+    if !attribution.tags.is_empty() {
+        let tags = attribution.tags.iter().cloned().map(StringValue::from).collect();
+        attributes.push(KeyValue::new("langfuse.trace.tags", OtelValue::Array(Array::String(tags))));
+    }
+    attributes
+}
+
+/// Downstream time to first byte, total downstream time and gateway
+/// overhead, once the request is done.
+pub fn set_downstream_timing<S: Span>(span: &mut S, summary: &RequestSummary) {
+    if let Some(ttfb_ms) = summary.downstream_ttfb_ms {
+        span.set_attribute(KeyValue::new("downstream.ttfb_ms", ttfb_ms as i64));
+    }
+    if let Some(downstream_ms) = summary.downstream_ms {
+        span.set_attribute(KeyValue::new("downstream.duration_ms", downstream_ms as i64));
+    }
+    if let Some(overhead_ms) = summary.gateway_overhead_ms() {
+        span.set_attribute(KeyValue::new("gateway.overhead_ms", overhead_ms as i64));
+    }
+}
+
+/// Records the generation output, usage and error level once the request is done.
+pub fn set_langfuse_generation_output<S: Span>(
+    span: &mut S,
+    output: Option<&Value>,
+    summary: &RequestSummary,
+    capture: CapturePolicy,
+) {
+    if let Some(output) = output {
+        span.set_attribute(KeyValue::new(
+            "langfuse.observation.output",
+            capture.apply(&output.to_string()),
+        ));
+    }
+    let mut usage = serde_json::Map::new();
+    if let Some(input) = summary.input_tokens {
+        usage.insert("input".to_string(), input.into());
+    }
+    if let Some(output) = summary.output_tokens {
+        usage.insert("output".to_string(), output.into());
+    }
+    if let (Some(input), Some(output)) = (summary.input_tokens, summary.output_tokens) {
+        usage.insert("total".to_string(), (input + output).into());
+    }
+    if !usage.is_empty() {
+        span.set_attribute(KeyValue::new(
+            "langfuse.observation.usage_details",
+            Value::Object(usage).to_string(),
+        ));
+    }
+    if let Some(error_type) = &summary.error_type {
+        span.set_attribute(KeyValue::new("langfuse.observation.level", "ERROR"));
+        span.set_attribute(KeyValue::new(
+            "langfuse.observation.status_message",
+            error_type.clone(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{Tracer, TracerProvider};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Default, Clone)]
+    struct RecordingExporter(Arc<Mutex<Vec<SpanData>>>);
+
+    impl opentelemetry_sdk::trace::SpanExporter for RecordingExporter {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    /// Runs `record` on a fresh span and returns its attributes once ended.
+    fn recorded(record: impl FnOnce(&mut opentelemetry_sdk::trace::Span)) -> HashMap<String, OtelValue> {
+        let spans = RecordingExporter::default();
+        let provider = SdkTracerProvider::builder().with_simple_exporter(spans.clone()).build();
+        let mut span = provider.tracer("test").start("ai.gateway.request");
+        record(&mut span);
+        span.end();
+        let span = spans.0.lock().unwrap().pop().expect("span exported");
+        span.attributes.into_iter().map(|kv| (kv.key.to_string(), kv.value)).collect()
+    }
+
+    fn json_attribute(attributes: &HashMap<String, OtelValue>, key: &str) -> Value {
+        serde_json::from_str(attributes[key].as_str().as_ref()).expect(key)
+    }
+
+    #[test]
+    fn trace_id_ratio_bounds() {
+        let id = [0xffu8; 16];
+        assert!(trace_id_below_ratio(id, 1.0));
+        assert!(!trace_id_below_ratio(id, 0.0));
+        assert!(!trace_id_below_ratio(id, 0.5));
+        assert!(trace_id_below_ratio([0u8; 16], 0.01));
+    }
+
+    #[test]
+    fn session_header_and_tags_feed_langfuse_attributes() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-session-id", "conv-42".parse().unwrap());
+        headers.insert(LANGFUSE_TAGS_HEADER, " prod, agent ,,prod".parse().unwrap());
+        let config = LangfuseConfig {
+            session_header: Some("x-session-id".to_string()),
+        };
+        let attribution = LangfuseAttribution::from_headers(&headers, &config);
+        assert_eq!(attribution.tags, ["prod", "agent"]);
+
+        let payload = serde_json::json!({
+            "messages": [],
+            "metadata": {"user_id": "user-7", "session_id": "from-metadata"},
+        });
+        let attributes = langfuse_generation_input("m", &payload, &attribution, CapturePolicy::Full);
+        let get = |key: &str| {
+            attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.to_string())
+        };
+        assert_eq!(get("langfuse.user.id").as_deref(), Some("user-7"));
+        assert_eq!(get("langfuse.session.id").as_deref(), Some("conv-42"));
+        assert_eq!(get("langfuse.trace.tags").as_deref(), Some("[\"prod\",\"agent\"]"));
+
+        let without_header = LangfuseAttribution::default();
+        let attributes = langfuse_generation_input("m", &payload, &without_header, CapturePolicy::Full);
+        assert!(attributes.iter().any(|kv| kv.key.as_str() == "langfuse.session.id"
+            && kv.value.as_str() == "from-metadata"));
+        assert!(!attributes.iter().any(|kv| kv.key.as_str() == "langfuse.trace.tags"));
+    }
+
+    #[test]
+    fn generation_input_is_structured_json_with_model_parameters() {
+        let payload = serde_json::json!({
+            "model": "claude-test",
+            "system": "be brief",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_tokens": 64,
+            "temperature": 0.2,
+            "top_p": null,
+            "stream": true,
+        });
+        let attributes = recorded(|span| {
+            set_langfuse_generation_input(
+                span,
+                "mapped-model",
+                &payload,
+                &LangfuseAttribution::default(),
+                CapturePolicy::Full,
+            )
+        });
+        assert_eq!(attributes["langfuse.observation.type"].as_str(), "generation");
+        assert_eq!(attributes["langfuse.observation.model.name"].as_str(), "mapped-model");
+        assert_eq!(attributes["gen_ai.request.model"].as_str(), "mapped-model");
+        assert_eq!(
+            json_attribute(&attributes, "langfuse.observation.input"),
+            serde_json::json!({"messages": [{"role": "user", "content": "hi"}], "system": "be brief"})
+        );
+        // Unset and null parameters are left out.
+        assert_eq!(
+            json_attribute(&attributes, "langfuse.observation.model.parameters"),
+            serde_json::json!({"max_tokens": 64, "temperature": 0.2, "stream": true})
+        );
+        assert!(!attributes.contains_key("langfuse.user.id"));
+
+        let attributes =
+            langfuse_generation_input("m", &payload, &LangfuseAttribution::default(), CapturePolicy::None);
+        let input = attributes.iter().find(|kv| kv.key.as_str() == "langfuse.observation.input").unwrap();
+        assert_eq!(input.value.as_str(), "[omitted]");
+    }
+
+    #[test]
+    fn generation_output_records_usage_and_error_level() {
+        let mut summary = RequestSummary::new("req-1", "/v1/messages", "POST", "translate", &HeaderMap::new());
+        summary.input_tokens = Some(3);
+        summary.output_tokens = Some(1);
+        let output = serde_json::json!([{"type": "text", "text": "pong"}]);
+        let attributes =
+            recorded(|span| set_langfuse_generation_output(span, Some(&output), &summary, CapturePolicy::Full));
+        assert_eq!(json_attribute(&attributes, "langfuse.observation.output"), output);
+        assert_eq!(
+            json_attribute(&attributes, "langfuse.observation.usage_details"),
+            serde_json::json!({"input": 3, "output": 1, "total": 4})
+        );
+        assert!(!attributes.contains_key("langfuse.observation.level"));
+
+        summary.output_tokens = None;
+        summary.error_type = Some("overloaded_error".to_string());
+        let attributes = recorded(|span| set_langfuse_generation_output(span, None, &summary, CapturePolicy::Full));
+        assert!(!attributes.contains_key("langfuse.observation.output"));
+        assert_eq!(
+            json_attribute(&attributes, "langfuse.observation.usage_details"),
+            serde_json::json!({"input": 3})
+        );
+        assert_eq!(attributes["langfuse.observation.level"].as_str(), "ERROR");
+        assert_eq!(attributes["langfuse.observation.status_message"].as_str(), "overloaded_error");
+    }
 }
@@ -0,0 +1,361 @@
+use axum::body::Bytes;
+use axum::http::HeaderValue;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::audit_log::now_ms;
+use crate::capture::CapturePolicy;
+use crate::config::TranscriptsConfig;
+use crate::shadow::evenly_sampled;
+use crate::tap::SseRedactor;
+
+/// A client stream as the gateway sent it: every body chunk with its offset
+/// from the first byte, so a replay reproduces the original pacing.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub request_id: String,
+    pub ts_start_ms: u128,
+    pub content_type: Option<String>,
+    /// Recording stopped at `transcripts.max_bytes`.
+    pub truncated: bool,
+    /// `data:` payloads went through a non-full `observability.capture`;
+    /// chunks are then whole redacted events rather than the bytes sent.
+    #[serde(default)]
+    pub redacted: bool,
+    pub chunks: Vec<TranscriptChunk>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptChunk {
+    pub offset_ms: u64,
+    /// Raw chunk bytes, base64 encoded: a chunk may end inside a UTF-8
+    /// sequence, so it is not stored as text.
+    #[serde(with = "base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Persists sampled streaming transcripts as `<dir>/<request_id>.json.gz`.
+#[derive(Clone)]
+pub struct TranscriptStore {
+    config: TranscriptsConfig,
+    capture: CapturePolicy,
+    counter: Arc<AtomicU64>,
+}
+
+impl TranscriptStore {
+    pub fn new(config: &TranscriptsConfig, capture: CapturePolicy) -> Self {
+        Self {
+            config: config.clone(),
+            capture,
+            counter: Arc::default(),
+        }
+    }
+
+    /// Starts recording a client stream when it is sampled; the transcript
+    /// is written once the recorder is dropped with the response body.
+    pub fn record(&self, request_id: &str, content_type: Option<&HeaderValue>) -> Option<TranscriptRecorder> {
+        if !evenly_sampled(&self.counter, self.config.sample_percent) {
+            return None;
+        }
+        let redactor = (self.capture != CapturePolicy::Full).then(|| SseRedactor::new(self.capture));
+        Some(TranscriptRecorder {
+            path: self.path(request_id)?,
+            start: Instant::now(),
+            bytes: 0,
+            max_bytes: self.config.max_bytes,
+            retention: Retention {
+                max_files: self.config.max_files,
+                max_age: Duration::from_secs(self.config.max_age_secs),
+            },
+            transcript: Transcript {
+                request_id: request_id.to_string(),
+                ts_start_ms: now_ms(),
+                content_type: content_type.and_then(|v| v.to_str().ok()).map(str::to_string),
+                truncated: false,
+                redacted: redactor.is_some(),
+                chunks: Vec::new(),
+            },
+            redactor,
+        })
+    }
+
+    /// `Ok(None)` when no transcript was stored for `request_id`.
+    pub async fn load(&self, request_id: &str) -> Result<Option<Transcript>, String> {
+        let Some(path) = self.path(request_id) else {
+            return Ok(None);
+        };
+        tokio::task::spawn_blocking(move || read_transcript(&path))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// `None` for ids that could escape `dir`; gateway ids are
+    /// `req_` plus base62.
+    fn path(&self, request_id: &str) -> Option<PathBuf> {
+        let valid = !request_id.is_empty()
+            && request_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+        valid.then(|| Path::new(&self.config.dir).join(format!("{}.json.gz", request_id)))
+    }
+}
+
+pub struct TranscriptRecorder {
+    path: PathBuf,
+    start: Instant,
+    bytes: usize,
+    max_bytes: usize,
+    retention: Retention,
+    transcript: Transcript,
+    /// Set unless the capture policy is `full`.
+    redactor: Option<SseRedactor>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Retention {
+    max_files: usize,
+    max_age: Duration,
+}
+
+impl TranscriptRecorder {
+    pub fn push(&mut self, chunk: &Bytes) {
+        if self.transcript.truncated {
+            return;
+        }
+        if self.bytes + chunk.len() > self.max_bytes {
+            self.transcript.truncated = true;
+            return;
+        }
+        self.bytes += chunk.len();
+        let data = match self.redactor.as_mut() {
+            Some(redactor) => match redactor.push(chunk) {
+                Some(events) => events.into_bytes(),
+                None => return,
+            },
+            None => chunk.to_vec(),
+        };
+        self.transcript.chunks.push(TranscriptChunk {
+            offset_ms: self.start.elapsed().as_millis() as u64,
+            data,
+        });
+    }
+}
+
+impl Drop for TranscriptRecorder {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if let Some(rest) = self.redactor.as_mut().and_then(SseRedactor::finish) {
+            self.transcript.chunks.push(TranscriptChunk {
+                offset_ms: self.start.elapsed().as_millis() as u64,
+                data: rest.into_bytes(),
+            });
+        }
+        let path = std::mem::take(&mut self.path);
+        let transcript = std::mem::take(&mut self.transcript);
+        let retention = self.retention;
+        runtime.spawn_blocking(move || {
+            if let Err(err) = write_transcript(&path, &transcript) {
+                tracing::warn!(request_id = %transcript.request_id, "transcript write failed: {}", err);
+            }
+            if let Some(dir) = path.parent()
+                && let Err(err) = prune(dir, retention)
+            {
+                tracing::warn!("transcript pruning failed: {}", err);
+            }
+        });
+    }
+}
+
+fn write_transcript(path: &Path, transcript: &Transcript) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, transcript)?;
+    // Renamed into place so a concurrent replay never reads half a file.
+    let tmp = path.with_extension("gz.tmp");
+    std::fs::File::create(&tmp)?.write_all(&encoder.finish()?)?;
+    std::fs::rename(tmp, path)
+}
+
+/// Removes transcripts older than `max_age`, then the oldest ones past
+/// `max_files`.
+fn prune(dir: &Path, retention: Retention) -> std::io::Result<()> {
+    let now = SystemTime::now();
+    let mut stored = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().ends_with(".json.gz") {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        if now.duration_since(modified).unwrap_or_default() > retention.max_age {
+            std::fs::remove_file(entry.path())?;
+        } else {
+            stored.push((modified, entry.path()));
+        }
+    }
+    if stored.len() > retention.max_files {
+        stored.sort();
+        for (_, path) in &stored[..stored.len() - retention.max_files] {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_transcript(path: &Path) -> Result<Option<Transcript>, String> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut json = Vec::new();
+    GzDecoder::new(file).read_to_end(&mut json).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&json).map(Some).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &Path, sample_percent: f64, capture: CapturePolicy) -> TranscriptStore {
+        TranscriptStore::new(
+            &TranscriptsConfig {
+                enabled: true,
+                dir: dir.to_string_lossy().into_owned(),
+                sample_percent,
+                max_bytes: 32,
+                max_files: 2,
+                max_age_secs: 3600,
+            },
+            capture,
+        )
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("llm-gateway-transcripts-{}-{}", name, std::process::id()))
+    }
+
+    async fn wait_for(store: &TranscriptStore, request_id: &str) -> Transcript {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(transcript) = store.load(request_id).await.unwrap() {
+                    return transcript;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("transcript written")
+    }
+
+    #[tokio::test]
+    async fn recorded_stream_round_trips_through_gzip() {
+        let dir = temp_dir("roundtrip");
+        let store = store(&dir, 100.0, CapturePolicy::Full);
+        let mut recorder = store
+            .record("req_01abc", Some(&HeaderValue::from_static("text/event-stream")))
+            .unwrap();
+        // "é" split across two chunks must come back as the same bytes.
+        recorder.push(&Bytes::from_static(b"data: caf\xc3"));
+        recorder.push(&Bytes::from_static(b"\xa9\n\n"));
+        recorder.push(&Bytes::from_static(b"event: message_stop\n\n"));
+        recorder.push(&Bytes::from_static(b"event: ping\n\n"));
+        drop(recorder);
+
+        let transcript = wait_for(&store, "req_01abc").await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(transcript.content_type.as_deref(), Some("text/event-stream"));
+        let data: Vec<&[u8]> = transcript.chunks.iter().map(|c| c.data.as_slice()).collect();
+        assert_eq!(data, vec![&b"data: caf\xc3"[..], &b"\xa9\n\n"[..]]);
+        assert!(transcript.truncated);
+        assert!(!transcript.redacted);
+    }
+
+    #[tokio::test]
+    async fn capture_policy_redacts_whole_events() {
+        let dir = temp_dir("redacted");
+        let store = store(&dir, 100.0, CapturePolicy::None);
+        let mut recorder = store.record("req_01abc", None).unwrap();
+        recorder.push(&Bytes::from_static(b"event: d\ndata: {\"t"));
+        recorder.push(&Bytes::from_static(b"\":\"secret\"}\n\n"));
+        drop(recorder);
+
+        let transcript = wait_for(&store, "req_01abc").await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(transcript.redacted);
+        assert_eq!(transcript.chunks.len(), 1);
+        assert_eq!(transcript.chunks[0].data, b"event: d\ndata: [omitted]\n\n");
+    }
+
+    #[tokio::test]
+    async fn oldest_transcripts_past_max_files_are_pruned() {
+        let dir = temp_dir("prune");
+        let store = store(&dir, 100.0, CapturePolicy::Full);
+        for request_id in ["req_a", "req_b", "req_c"] {
+            let mut recorder = store.record(request_id, None).unwrap();
+            recorder.push(&Bytes::from_static(b"event: ping\n\n"));
+            drop(recorder);
+            wait_for(&store, request_id).await;
+            // Distinct modification times so "oldest" is well defined.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // Pruning runs right after the last write lands.
+        let remaining = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let mut remaining: Vec<String> = std::fs::read_dir(&dir)
+                    .unwrap()
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .collect();
+                if remaining.len() <= 2 {
+                    remaining.sort();
+                    return remaining;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("pruned");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(remaining, vec!["req_b.json.gz", "req_c.json.gz"]);
+    }
+
+    #[tokio::test]
+    async fn unsampled_or_unsafe_ids_are_not_recorded() {
+        let dir = std::env::temp_dir();
+        assert!(store(&dir, 0.0, CapturePolicy::Full).record("req_01abc", None).is_none());
+        let store = store(&dir, 100.0, CapturePolicy::Full);
+        assert!(store.record("../etc/passwd", None).is_none());
+        assert_eq!(store.load("../etc/passwd").await.unwrap(), None);
+    }
+}
@@ -1,5 +1,10 @@
-use crate::config::{Config, DocumentPolicy};
+use crate::capabilities;
+use crate::builtin_tools;
+use crate::ids;
+use crate::config::{BuiltInToolPolicy, Config, DocumentPolicy, EmptyContentPolicy, PrefillMode, ToolErrorMode};
 use crate::models::*;
+use crate::system_prompt::SystemPrompt;
+use crate::tool_ids;
 use serde_json::{json, Value};
 
 #[derive(Debug)]
@@ -24,26 +29,80 @@ impl TranslateError {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AnthropicVersion {
+    V2023_01_01,
+    #[default]
+    V2023_06_01,
+}
+
+impl AnthropicVersion {
+    pub const SUPPORTED: &'static [&'static str] = &["2023-06-01", "2023-01-01"];
+
+    pub fn from_header(value: Option<&str>) -> Result<Self, TranslateError> {
+        match value.map(str::trim) {
+            None | Some("") | Some("2023-06-01") => Ok(Self::V2023_06_01),
+            Some("2023-01-01") => Ok(Self::V2023_01_01),
+            Some(other) => Err(TranslateError::invalid_request(format!(
+                "anthropic-version not supported: {} (supported: {})",
+                other,
+                Self::SUPPORTED.join(", ")
+            ))),
+        }
+    }
+
+    pub fn supports_cache_usage(self) -> bool {
+        self >= Self::V2023_06_01
+    }
+
+    pub fn supports_output_format(self) -> bool {
+        self >= Self::V2023_06_01
+    }
+
+    pub fn check_request(self, req: &AnthropicRequest) -> Result<(), TranslateError> {
+        if req.output_format.is_some() && !self.supports_output_format() {
+            return Err(TranslateError::invalid_request(
+                "output_format requires anthropic-version 2023-06-01",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn shape_response(self, body: &mut Value) {
+        if self.supports_cache_usage() {
+            return;
+        }
+        if let Some(usage) = body.get_mut("usage").and_then(Value::as_object_mut) {
+            usage.remove("cache_creation_input_tokens");
+            usage.remove("cache_read_input_tokens");
+        }
+    }
+}
+
 pub fn anthropic_to_openai(req: AnthropicRequest, config: &Config) -> Result<OpenAIRequest, TranslateError> {
+    let capabilities = capabilities::resolve(config, &req.model);
+    capabilities::check(&req, &capabilities)?;
     let mut messages = Vec::new();
+    let prefill = assistant_prefill(&req);
     let reasoning_effort = req
         .thinking
         .as_ref()
+        .filter(|_| capabilities.reasoning != Some(false))
         .and_then(|thinking| map_reasoning_effort(thinking, config));
     let include_reasoning = reasoning_effort.is_some();
 
-    if let Some(system) = req.system {
-        let system_text = extract_system_text(system)?;
-        if !system_text.is_empty() {
-            messages.push(OpenAIMessage {
-                role: "system".to_string(),
-                content: Some(OpenAIMessageContent::Text(system_text)),
-                tool_calls: None,
-                tool_call_id: None,
-                reasoning_content: None,
-            });
-        }
+    let client_system = req.system.map(extract_system_text).transpose()?;
+    let system_prompt = &config.models.system_prompt;
+    if let Some(system_text) = SystemPrompt::new(system_prompt, client_system).compose(system_prompt.max_bytes)? {
+        messages.push(OpenAIMessage {
+            role: config.system_role(&req.model).to_string(),
+            content: Some(OpenAIMessageContent::Text(system_text)),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        });
     }
+    let system_messages = messages.len();
 
     for msg in req.messages {
         if msg.role != "user" && msg.role != "assistant" {
@@ -56,15 +115,35 @@ pub fn anthropic_to_openai(req: AnthropicRequest, config: &Config) -> Result<Ope
         messages.extend(converted);
     }
 
-    let tools = req.tools.map(anthropic_tools_to_openai_tools);
-    let tool_choice = req.tool_choice.map(anthropic_tool_choice_to_openai);
+    let tools = match req.tools {
+        Some(tools) => anthropic_tools_to_openai_tools(tools, config)?,
+        None => None,
+    };
+    // Every tool may have been stripped; OpenAI rejects a choice without tools.
+    let tool_choice = req
+        .tool_choice
+        .filter(|_| tools.is_some())
+        .map(anthropic_tool_choice_to_openai);
     let response_format = req
         .output_format
         .map(|format| anthropic_output_format_to_openai(format, config.models.output_strict));
-    Ok(OpenAIRequest {
+    let mut extra = vllm_extra_params(&req.model, req.vllm_params, config)?;
+    let mut unknown = req.extra;
+    for field in &config.models.forward_fields {
+        if let Some(value) = unknown.remove(field) {
+            extra.entry(field.clone()).or_insert(value);
+        }
+    }
+    if let Some(prefill) = prefill.as_deref() {
+        apply_prefill_mode(&mut messages, &mut extra, prefill, config.prefill_mode());
+    }
+    if config.models.sanitize_messages {
+        messages = sanitize_messages(messages);
+    }
+    let openai_req = OpenAIRequest {
         model: req.model,
         messages,
-        max_completion_tokens: req.max_tokens,
+        max_completion_tokens: capabilities.max_output.map_or(req.max_tokens, |max| req.max_tokens.min(max)),
         temperature: req.temperature,
         top_p: req.top_p,
         stop: req.stop_sequences,
@@ -76,10 +155,269 @@ pub fn anthropic_to_openai(req: AnthropicRequest, config: &Config) -> Result<Ope
         stream_options: req.stream.map(|stream| OpenAIStreamOptions {
             include_usage: stream,
         }),
-    })
+        extra,
+        system_messages,
+    };
+    apply_model_overrides(openai_req, config)
 }
 
-pub fn openai_to_anthropic(resp: OpenAIResponse) -> Result<AnthropicResponse, TranslateError> {
+/// Returns the text of a trailing assistant message that the model is
+/// expected to continue (Anthropic prefill).
+pub fn assistant_prefill(req: &AnthropicRequest) -> Option<String> {
+    let last = req.messages.last()?;
+    if last.role != "assistant" {
+        return None;
+    }
+    let text = match &last.content {
+        AnthropicContent::Text(text) => text.clone(),
+        AnthropicContent::Blocks(blocks) => {
+            let mut text = String::new();
+            for block in blocks {
+                match block {
+                    AnthropicContentBlock::Text { text: part, .. } => text.push_str(part),
+                    AnthropicContentBlock::Thinking { .. }
+                    | AnthropicContentBlock::RedactedThinking { .. } => {}
+                    _ => return None,
+                }
+            }
+            text
+        }
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn apply_prefill_mode(
+    messages: &mut Vec<OpenAIMessage>,
+    extra: &mut serde_json::Map<String, Value>,
+    prefill: &str,
+    mode: PrefillMode,
+) {
+    match mode {
+        PrefillMode::None => {}
+        PrefillMode::Continue => {
+            extra
+                .entry("continue_final_message")
+                .or_insert(Value::Bool(true));
+            extra
+                .entry("add_generation_prompt")
+                .or_insert(Value::Bool(false));
+        }
+        PrefillMode::Suffix => {
+            if messages.last().is_some_and(|m| m.role == "assistant") {
+                messages.pop();
+            }
+            let instruction = format!(
+                "Begin your response with exactly the following text and continue from there:\n{}",
+                prefill
+            );
+            if let Some(OpenAIMessage {
+                role,
+                content: Some(OpenAIMessageContent::Text(text)),
+                ..
+            }) = messages.last_mut()
+                && role == "user"
+            {
+                text.push_str("\n\n");
+                text.push_str(&instruction);
+                return;
+            }
+            messages.push(OpenAIMessage {
+                role: "user".to_string(),
+                content: Some(OpenAIMessageContent::Text(instruction)),
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_content: None,
+            });
+        }
+    }
+}
+
+/// Normalizes messages for backends that enforce strict alternation: merges
+/// consecutive user turns (and assistant turns without tool calls), moves tool
+/// results directly after the assistant message that issued the call, and
+/// drops tool results that answer no pending tool call.
+fn sanitize_messages(messages: Vec<OpenAIMessage>) -> Vec<OpenAIMessage> {
+    let mut out: Vec<OpenAIMessage> = Vec::with_capacity(messages.len());
+    let mut pending_calls: Vec<String> = Vec::new();
+    let mut tool_anchor = 0;
+    for msg in messages {
+        if msg.role == "tool" {
+            let id = msg.tool_call_id.clone().unwrap_or_default();
+            let Some(pos) = pending_calls.iter().position(|pending| *pending == id) else {
+                tracing::warn!(tool_call_id = %id, "dropping orphan tool result");
+                continue;
+            };
+            pending_calls.remove(pos);
+            out.insert(tool_anchor, msg);
+            tool_anchor += 1;
+            continue;
+        }
+        let mergeable = out.len() > tool_anchor;
+        if let Some(last) = out.last_mut()
+            && mergeable
+            && last.role == msg.role
+            && (msg.role == "user" || (msg.role == "assistant" && last.tool_calls.is_none()))
+        {
+            last.content = merge_message_content(last.content.take(), msg.content);
+            if last.reasoning_content.is_none() {
+                last.reasoning_content = msg.reasoning_content;
+            }
+            if let Some(tool_calls) = msg.tool_calls {
+                pending_calls = tool_calls.iter().map(|call| call.id.clone()).collect();
+                last.tool_calls = Some(tool_calls);
+                tool_anchor = out.len();
+            }
+            continue;
+        }
+        let issues_calls = msg.tool_calls.is_some();
+        if let Some(tool_calls) = msg.tool_calls.as_ref() {
+            pending_calls = tool_calls.iter().map(|call| call.id.clone()).collect();
+        }
+        out.push(msg);
+        if issues_calls {
+            tool_anchor = out.len();
+        }
+    }
+    out
+}
+
+fn merge_message_content(
+    first: Option<OpenAIMessageContent>,
+    second: Option<OpenAIMessageContent>,
+) -> Option<OpenAIMessageContent> {
+    match (first, second) {
+        (None, other) | (other, None) => other,
+        (Some(OpenAIMessageContent::Text(a)), Some(OpenAIMessageContent::Text(b))) => {
+            Some(OpenAIMessageContent::Text(format!("{}\n\n{}", a, b)))
+        }
+        (Some(a), Some(b)) => {
+            let mut parts = into_parts(a);
+            parts.extend(into_parts(b));
+            Some(OpenAIMessageContent::Parts(parts))
+        }
+    }
+}
+
+fn into_parts(content: OpenAIMessageContent) -> Vec<OpenAIContentPart> {
+    match content {
+        OpenAIMessageContent::Text(text) => vec![OpenAIContentPart::Text { text }],
+        OpenAIMessageContent::Parts(parts) => parts,
+    }
+}
+
+/// Removes the prefill from the first text block when the downstream echoes
+/// it, so clients receive only the continuation as with Anthropic.
+pub fn strip_prefill_echo(resp: &mut AnthropicResponse, prefill: &str) {
+    for block in resp.content.iter_mut() {
+        if let AnthropicContentBlock::Text { text, .. } = block {
+            if let Some(rest) = text.strip_prefix(prefill) {
+                *text = rest.to_string();
+            }
+            return;
+        }
+    }
+}
+
+fn apply_model_overrides(
+    req: OpenAIRequest,
+    config: &Config,
+) -> Result<OpenAIRequest, TranslateError> {
+    let Some(overrides) = config.models.overrides.get(&req.model) else {
+        return Ok(req);
+    };
+    let mut body = serde_json::to_value(&req)
+        .map_err(|e| TranslateError::api_error(format!("request serialize error: {}", e)))?;
+    merge_patch(&mut body, overrides);
+    let mut patched: OpenAIRequest = serde_json::from_value(body).map_err(|e| {
+        TranslateError::api_error(format!("models.overrides.{} invalid: {}", req.model, e))
+    })?;
+    patched.system_messages = req.system_messages;
+    Ok(patched)
+}
+
+/// RFC 7386 JSON merge patch.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+pub const VLLM_EXTRA_PARAMS: &[&str] = &[
+    "best_of",
+    "use_beam_search",
+    "top_k",
+    "min_p",
+    "repetition_penalty",
+    "length_penalty",
+    "stop_token_ids",
+    "include_stop_str_in_output",
+    "ignore_eos",
+    "min_tokens",
+    "skip_special_tokens",
+    "spaces_between_special_tokens",
+    "truncate_prompt_tokens",
+    "allowed_token_ids",
+    "bad_words",
+    "seed",
+    "presence_penalty",
+    "frequency_penalty",
+    "guided_json",
+    "guided_regex",
+    "guided_choice",
+    "guided_grammar",
+    "guided_decoding_backend",
+    "guided_whitespace_pattern",
+    "structural_tag",
+    "chat_template",
+    "chat_template_kwargs",
+    "add_generation_prompt",
+    "continue_final_message",
+    "add_special_tokens",
+    "priority",
+];
+
+fn vllm_extra_params(
+    model: &str,
+    request_params: Option<serde_json::Map<String, Value>>,
+    config: &Config,
+) -> Result<serde_json::Map<String, Value>, TranslateError> {
+    let mut params = config
+        .models
+        .vllm_params
+        .get(model)
+        .cloned()
+        .unwrap_or_default();
+    if let Some(request_params) = request_params {
+        for (key, value) in request_params {
+            if !VLLM_EXTRA_PARAMS.contains(&key.as_str()) {
+                return Err(TranslateError::invalid_request(format!(
+                    "vllm_params: unsupported parameter \"{}\"",
+                    key
+                )));
+            }
+            params.insert(key, value);
+        }
+    }
+    Ok(params)
+}
+
+pub fn openai_to_anthropic(
+    resp: OpenAIResponse,
+    empty_content: EmptyContentPolicy,
+) -> Result<AnthropicResponse, TranslateError> {
     let choice = resp
         .choices
         .into_iter()
@@ -88,21 +426,16 @@ pub fn openai_to_anthropic(resp: OpenAIResponse) -> Result<AnthropicResponse, Tr
 
     let mut content_blocks: Vec<AnthropicContentBlock> = Vec::new();
 
-    if let Some(reasoning) = choice.message.reasoning_content {
-        if reasoning.is_object() {
-            let parsed: Result<OpenAIReasoningContent, _> = serde_json::from_value(reasoning);
-            if let Ok(reasoning) = parsed {
-                content_blocks.push(AnthropicContentBlock::Thinking {
-                    thinking: reasoning.thinking,
-                    signature: reasoning.signature,
-                });
-            }
-        } else if let Some(thinking) = reasoning.as_str() {
-            content_blocks.push(AnthropicContentBlock::Thinking {
-                thinking: thinking.to_string(),
-                signature: "auto".to_string(),
-            });
-        }
+    match choice.message.reasoning_content {
+        Some(OpenAIReasoning::Structured(reasoning)) => content_blocks.push(AnthropicContentBlock::Thinking {
+            thinking: reasoning.thinking,
+            signature: reasoning.signature,
+        }),
+        Some(OpenAIReasoning::Text(thinking)) => content_blocks.push(AnthropicContentBlock::Thinking {
+            thinking,
+            signature: "auto".to_string(),
+        }),
+        Some(OpenAIReasoning::Other(_)) | None => {}
     }
 
     if let Some(tool_calls) = choice.message.tool_calls {
@@ -111,22 +444,40 @@ pub fn openai_to_anthropic(resp: OpenAIResponse) -> Result<AnthropicResponse, Tr
                 TranslateError::api_error(format!("invalid tool call arguments: {}", e))
             })?;
             content_blocks.push(AnthropicContentBlock::ToolUse {
-                id: call.id,
+                id: if call.id.is_empty() { ids::tool_use_id() } else { call.id },
                 name: call.function.name,
                 input,
             });
         }
     }
 
-    if let Some(content) = choice.message.content {
-        content_blocks.push(AnthropicContentBlock::Text {
-            text: content,
-            cache_control: None,
-        });
+    match choice.message.content {
+        Some(OpenAIResponseContent::Text(text)) => {
+            content_blocks.extend(text_blocks_with_citations(text, &choice.message.annotations));
+        }
+        Some(OpenAIResponseContent::Parts(parts)) => {
+            for part in parts {
+                if part.part_type != "text" {
+                    continue;
+                }
+                if let Some(text) = part.text {
+                    content_blocks.extend(text_blocks_with_citations(text, &part.annotations));
+                }
+            }
+        }
+        None => {}
     }
 
     if content_blocks.is_empty() {
-        return Err(TranslateError::api_error("missing assistant content"));
+        match empty_content {
+            EmptyContentPolicy::Error => return Err(TranslateError::api_error("missing assistant content")),
+            EmptyContentPolicy::EmptyText => content_blocks.push(AnthropicContentBlock::Text {
+                text: String::new(),
+                cache_control: None,
+                citations: None,
+            }),
+            EmptyContentPolicy::Empty => {}
+        }
     }
 
     let stop_reason = match choice.finish_reason.as_deref() {
@@ -137,19 +488,11 @@ pub fn openai_to_anthropic(resp: OpenAIResponse) -> Result<AnthropicResponse, Tr
     }
     .to_string();
 
-    let usage = match resp.usage {
-        Some(u) => AnthropicUsage {
-            input_tokens: u.prompt_tokens,
-            output_tokens: u.completion_tokens,
-            cache_creation_input_tokens: 0,
-            cache_read_input_tokens: 0,
-        },
-        None => AnthropicUsage {
-            input_tokens: 0,
-            output_tokens: 0,
-            cache_creation_input_tokens: 0,
-            cache_read_input_tokens: 0,
-        },
+    let usage = AnthropicUsage {
+        input_tokens: resp.usage.as_ref().map_or(0, |u| u.prompt_tokens),
+        output_tokens: resp.usage.as_ref().map_or(0, |u| u.completion_tokens),
+        service_tier: resp.service_tier,
+        ..Default::default()
     };
 
     Ok(AnthropicResponse {
@@ -164,6 +507,126 @@ pub fn openai_to_anthropic(resp: OpenAIResponse) -> Result<AnthropicResponse, Tr
     })
 }
 
+/// `models.reasoning_only_text`: a reply with thinking and nothing else gets
+/// an empty text block after it.
+pub fn append_text_to_reasoning_only(resp: &mut AnthropicResponse) {
+    let thinking_only = !resp.content.is_empty()
+        && resp.content.iter().all(|block| {
+            matches!(
+                block,
+                AnthropicContentBlock::Thinking { .. } | AnthropicContentBlock::RedactedThinking { .. }
+            )
+        });
+    if thinking_only {
+        resp.content.push(AnthropicContentBlock::Text {
+            text: String::new(),
+            cache_control: None,
+            citations: None,
+        });
+    }
+}
+
+/// Backfills what some backends omit: a missing id becomes a `msg_` id and
+/// a missing model the model the request was sent for.
+pub fn normalize_response(resp: &mut OpenAIResponse, requested_model: &str) {
+    if resp.id.is_empty() {
+        resp.id = ids::message_id();
+    }
+    if resp.model.is_empty() {
+        resp.model = requested_model.to_string();
+    }
+}
+
+/// Splits text at downstream citation ranges (`url_citation`, or the
+/// gateway's `document_citation`) so each cited span becomes its own text
+/// block carrying Anthropic citation fields. Overlapping or out-of-range
+/// annotations are ignored.
+fn text_blocks_with_citations(
+    text: String,
+    annotations: &[OpenAIAnnotation],
+) -> Vec<AnthropicContentBlock> {
+    let mut ranges: Vec<(usize, usize, &OpenAIAnnotation)> = annotations
+        .iter()
+        .filter_map(|a| match a.annotation_type.as_str() {
+            "url_citation" => a.url_citation.as_ref().map(|c| (c.start_index, c.end_index, a)),
+            "document_citation" => a.document_citation.as_ref().map(|c| (c.start_index, c.end_index, a)),
+            _ => None,
+        })
+        .collect();
+    if ranges.is_empty() {
+        return vec![AnthropicContentBlock::Text {
+            text,
+            cache_control: None,
+            citations: None,
+        }];
+    }
+    ranges.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let chars: Vec<char> = text.chars().collect();
+    let slice = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let mut blocks = Vec::new();
+    let mut cursor = 0;
+    let mut i = 0;
+    while i < ranges.len() {
+        let (start, end, _) = ranges[i];
+        if start < cursor || start >= end || end > chars.len() {
+            i += 1;
+            continue;
+        }
+        if start > cursor {
+            blocks.push(AnthropicContentBlock::Text {
+                text: slice(cursor, start),
+                cache_control: None,
+                citations: None,
+            });
+        }
+        let cited_text = slice(start, end);
+        let mut citations = Vec::new();
+        while i < ranges.len() && ranges[i].0 == start && ranges[i].1 == end {
+            citations.extend(citation_json(ranges[i].2, &cited_text));
+            i += 1;
+        }
+        blocks.push(AnthropicContentBlock::Text {
+            text: cited_text,
+            cache_control: None,
+            citations: Some(citations),
+        });
+        cursor = end;
+    }
+    if cursor < chars.len() || blocks.is_empty() {
+        blocks.push(AnthropicContentBlock::Text {
+            text: slice(cursor, chars.len()),
+            cache_control: None,
+            citations: None,
+        });
+    }
+    blocks
+}
+
+/// Source document offsets are unknown for `document_citation`, so
+/// `char_location` reports them as 0.
+fn citation_json(annotation: &OpenAIAnnotation, cited_text: &str) -> Option<Value> {
+    if let Some(c) = annotation.url_citation.as_ref() {
+        return Some(json!({
+            "type": "web_search_result_location",
+            "url": c.url,
+            "title": c.title,
+            "cited_text": cited_text,
+            "encrypted_index": "",
+        }));
+    }
+    annotation.document_citation.as_ref().map(|c| {
+        json!({
+            "type": "char_location",
+            "cited_text": cited_text,
+            "document_index": c.document_index,
+            "document_title": c.title,
+            "start_char_index": 0,
+            "end_char_index": 0,
+        })
+    })
+}
+
 pub fn openai_models_to_anthropic(
     resp: OpenAIModelsResponse,
     model_display_map: &std::collections::HashMap<String, String>,
@@ -270,6 +733,7 @@ fn convert_message(
             let mut messages: Vec<OpenAIMessage> = Vec::new();
             let mut parts: Vec<OpenAIContentPart> = Vec::new();
             let mut thinking_text: Option<String> = None;
+            let mut tool_result_images: Vec<OpenAIContentPart> = Vec::new();
 
             let mut flush_parts = |messages: &mut Vec<OpenAIMessage>, parts: &mut Vec<OpenAIContentPart>, thinking_text: &Option<String>| {
                 if parts.is_empty() {
@@ -338,28 +802,25 @@ fn convert_message(
                     AnthropicContentBlock::ToolResult {
                         tool_use_id,
                         content,
-                        ..
+                        is_error,
                     } => {
                         flush_parts(&mut messages, &mut parts, &thinking_text);
-                        let text = match content {
-                            Value::String(s) => s,
-                            other => serde_json::to_string(&other).map_err(|e| {
-                                TranslateError::invalid_request(format!(
-                                    "tool_result content invalid: {}",
-                                    e
-                                ))
-                            })?,
-                        };
+                        let mut text =
+                            tool_result_content(content, config, &mut tool_result_images)?;
+                        if is_error == Some(true) {
+                            text = mark_tool_error(text, config);
+                        }
                         messages.push(OpenAIMessage {
                             role: "tool".to_string(),
                             content: Some(OpenAIMessageContent::Text(text)),
                             tool_calls: None,
-                            tool_call_id: Some(tool_use_id),
+                            tool_call_id: Some(tool_ids::to_downstream(&tool_use_id)),
                             reasoning_content: None,
                         });
                     }
                     AnthropicContentBlock::ToolUse { id, name, input } => {
                         flush_parts(&mut messages, &mut parts, &thinking_text);
+                        let id = tool_ids::to_downstream(&id);
                         if role != "assistant" {
                             return Err(TranslateError::invalid_request(
                                 "tool_use must be in assistant role",
@@ -411,12 +872,82 @@ fn convert_message(
                 }
             }
 
+            if !tool_result_images.is_empty() {
+                // Tool messages cannot carry images; attach them in the user
+                // turn that follows the contiguous tool results.
+                tool_result_images.append(&mut parts);
+                parts = tool_result_images;
+            }
             flush_parts(&mut messages, &mut parts, &thinking_text);
             Ok(messages)
         }
     }
 }
 
+/// Flattens `tool_result` content into text for the tool message. Nested image
+/// blocks are collected into `images` (or replaced by a placeholder when
+/// images are not allowed) instead of being serialized into the text.
+fn tool_result_content(
+    content: Value,
+    config: &Config,
+    images: &mut Vec<OpenAIContentPart>,
+) -> Result<String, TranslateError> {
+    let blocks = match content {
+        Value::String(s) => return Ok(s),
+        Value::Array(blocks) => blocks,
+        other => {
+            return serde_json::to_string(&other).map_err(|e| {
+                TranslateError::invalid_request(format!("tool_result content invalid: {}", e))
+            });
+        }
+    };
+    let mut texts: Vec<String> = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("text") => {
+                texts.push(block.get("text").and_then(Value::as_str).unwrap_or_default().to_string());
+            }
+            Some("image") if !config.models.allow_images => {
+                texts.push("[image omitted]".to_string());
+            }
+            Some("image") => {
+                let source = block.get("source").cloned().unwrap_or(Value::Null);
+                let url = match source.get("type").and_then(Value::as_str) {
+                    Some("url") => source.get("url").and_then(Value::as_str).map(str::to_string),
+                    _ => match (
+                        source.get("media_type").and_then(Value::as_str),
+                        source.get("data").and_then(Value::as_str),
+                    ) {
+                        (Some(media_type), Some(data)) => {
+                            Some(format!("data:{};base64,{}", media_type, data))
+                        }
+                        _ => None,
+                    },
+                };
+                let url = url.ok_or_else(|| {
+                    TranslateError::invalid_request("tool_result image source invalid")
+                })?;
+                images.push(OpenAIContentPart::ImageUrl {
+                    image_url: OpenAIImageUrl { url, detail: None },
+                });
+                texts.push(format!("[image {} attached below]", images.len()));
+            }
+            _ => texts.push(block.to_string()),
+        }
+    }
+    Ok(texts.join("\n"))
+}
+
+/// Marks a failed tool result per `models.tool_error`, since OpenAI tool
+/// messages carry no error flag.
+fn mark_tool_error(text: String, config: &Config) -> String {
+    match config.tool_error_mode() {
+        ToolErrorMode::Prefix => format!("{}{}", config.models.tool_error_prefix, text),
+        ToolErrorMode::Json => json!({"is_error": true, "content": text}).to_string(),
+        ToolErrorMode::Drop => text,
+    }
+}
+
 fn extract_system_text(system: AnthropicSystem) -> Result<String, TranslateError> {
     match system {
         AnthropicSystem::Text(s) => Ok(s),
@@ -437,18 +968,37 @@ fn extract_system_text(system: AnthropicSystem) -> Result<String, TranslateError
     }
 }
 
-fn anthropic_tools_to_openai_tools(tools: Vec<AnthropicTool>) -> Vec<OpenAITool> {
-    tools
-        .into_iter()
-        .map(|tool| OpenAITool {
-            tool_type: "function".to_string(),
-            function: OpenAIFunctionDef {
-                name: tool.name,
-                description: tool.description,
-                parameters: tool.input_schema,
+/// Custom tools become function tools; built-in tools follow
+/// `models.builtin_tools`. `None` when no tool is left.
+fn anthropic_tools_to_openai_tools(
+    tools: Vec<AnthropicTool>,
+    config: &Config,
+) -> Result<Option<Vec<OpenAITool>>, TranslateError> {
+    let mut out = Vec::with_capacity(tools.len());
+    for tool in tools {
+        let tool = match tool {
+            AnthropicTool::Custom(tool) => OpenAITool {
+                tool_type: "function".to_string(),
+                function: OpenAIFunctionDef {
+                    name: tool.name,
+                    description: tool.description,
+                    parameters: tool.input_schema,
+                },
             },
-        })
-        .collect()
+            AnthropicTool::BuiltIn(tool) => {
+                let unsupported = || {
+                    TranslateError::invalid_request(format!("tool type not supported: {}", tool.tool_type))
+                };
+                match config.builtin_tool_policy() {
+                    BuiltInToolPolicy::Reject => return Err(unsupported()),
+                    BuiltInToolPolicy::Strip => continue,
+                    BuiltInToolPolicy::Map => builtin_tools::to_function(&tool).ok_or_else(unsupported)?,
+                }
+            }
+        };
+        out.push(tool);
+    }
+    Ok((!out.is_empty()).then_some(out))
 }
 
 fn anthropic_tool_choice_to_openai(choice: AnthropicToolChoice) -> OpenAIToolChoice {
@@ -500,6 +1050,9 @@ mod tests {
         Config {
             server: crate::config::ServerConfig {
                 bind_addr: "127.0.0.1:0".to_string(),
+                cors: None,
+                signing: None,
+                ip_access: None,
             },
             downstream: crate::config::DownstreamConfig {
                 base_url: "https://api.openai.com".to_string(),
@@ -508,10 +1061,34 @@ mod tests {
                 anthropic_beta: None,
                 connect_timeout_ms: 5000,
                 read_timeout_ms: 30000,
+                first_byte_timeout_ms: None,
+                stream_retries: 0,
+                stream_retry_backoff_ms: 200,
                 pool_max_idle_per_host: 64,
+                pool_idle_timeout_secs: 90,
+                pool_recycle_secs: None,
+                stream: Default::default(),
+                provider: "openai".to_string(),
+                provider_endpoints: Default::default(),
+                endpoints: Vec::new(),
+                endpoint_health: Default::default(),
+                stream_parsing: "strict".to_string(),
+                max_malformed_chunks: 3,
+                user_agent: None,
+                headers: Default::default(),
+                oauth: None,
+                api_keys: Vec::new(),
+                key_rotation: "round_robin".to_string(),
+                warmup: None,
+                max_tokens_field: "max_completion_tokens".to_string(),
             },
             anthropic: crate::config::AnthropicConfig {
                 forward_mode: "passthrough".to_string(),
+                vertex: None,
+                passthrough_unknown: false,
+                sse_normalize: false,
+                passthrough_rewrite: false,
+                rewrite: Default::default(),
             },
             models: crate::config::ModelsConfig {
                 model_map: Default::default(),
@@ -525,13 +1102,61 @@ mod tests {
                 output_strict: true,
                 allow_images: true,
                 document_policy: "reject".to_string(),
+                prefill_mode: "none".to_string(),
+                tool_error: "prefix".to_string(),
+                tool_error_prefix: "[tool error] ".to_string(),
+                builtin_tools: "reject".to_string(),
+                empty_content: "error".to_string(),
+                reasoning_only_text: false,
+                sanitize_messages: false,
+                normalize_tool_ids: false,
+                context: Default::default(),
+                system_prompt: Default::default(),
                 models_override: None,
+                vllm_params: Default::default(),
+                overrides: Default::default(),
+                forward_fields: Vec::new(),
+                system_role: std::collections::HashMap::new(),
+                json_schema_support: std::collections::HashMap::new(),
+                capabilities: std::collections::HashMap::new(),
+                builtin_capabilities: false,
+                pricing: Default::default(),
+                provider_map: Default::default(),
+            },
+            limits: crate::config::LimitsConfig {
+                max_inflight: 64,
+                max_duration_ms: None,
+                max_duration_models: std::collections::HashMap::new(),
+                max_duration_keys: std::collections::HashMap::new(),
+                output_tokens_per_sec: None,
+                output_tokens_per_sec_keys: Default::default(),
+                input_tokens_per_minute: None,
+                input_tokens_per_minute_keys: Default::default(),
             },
-            limits: crate::config::LimitsConfig { max_inflight: 64 },
+            admin: crate::config::AdminConfig::default(),
+            usage: crate::config::UsageConfig::default(),
+            postgres: None,
+            compaction: Default::default(),
+            auxiliary: Default::default(),
+            compression: Default::default(),
+            tokenizers: Default::default(),
+            shadow: None,
+            experiments: Vec::new(),
+            routing: Default::default(),
+            canary: None,
+            scoring: None,
+            prompts: Default::default(),
+            alerts: None,
+            billing: None,
+            streaming: Default::default(),
             observability: crate::config::ObservabilityConfig {
                 service_name: "llm-gateway".to_string(),
-                dump_downstream: false,
+                capture: "full".to_string(),
                 audit_log: crate::config::AuditLogConfig::default(),
+                debug_capture: crate::config::DebugCaptureConfig::default(),
+                transcripts: crate::config::TranscriptsConfig::default(),
+                access_log: crate::config::AccessLogConfig::default(),
+                tracing: crate::config::TracingConfig::default(),
                 logging: crate::config::LoggingConfig {
                     level: "info".to_string(),
                     format: "text".to_string(),
@@ -548,6 +1173,8 @@ mod tests {
                     secret_key: "".to_string(),
                     timeout_ms: 5000,
                 },
+                langfuse: crate::config::LangfuseConfig::default(),
+                metrics: crate::config::MetricsConfig::default(),
                 exporters: crate::config::ExportersConfig {
                     tracing: "otlp_grpc".to_string(),
                     metrics: "otlp_grpc".to_string(),
@@ -575,6 +1202,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
@@ -615,6 +1244,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let err = anthropic_to_openai(req, &base_config()).expect_err("should reject");
@@ -629,7 +1260,8 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
-                    content: Some("Hi".to_string()),
+                    annotations: Vec::new(),
+                    content: Some(OpenAIResponseContent::Text("Hi".to_string())),
                     tool_calls: None,
                     reasoning_content: None,
                 },
@@ -640,9 +1272,10 @@ mod tests {
                 completion_tokens: 7,
                 total_tokens: 12,
             }),
+            service_tier: Some("priority".to_string()),
         };
 
-        let out = openai_to_anthropic(resp).expect("translate ok");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
         assert_eq!(out.id, "chatcmpl-123");
         assert_eq!(out.model, "gpt-4o-mini");
         assert_eq!(out.role, "assistant");
@@ -654,6 +1287,73 @@ mod tests {
         }
         assert_eq!(out.usage.input_tokens, 5);
         assert_eq!(out.usage.output_tokens, 7);
+        assert_eq!(out.usage.service_tier.as_deref(), Some("priority"));
+    }
+
+    #[test]
+    fn openai_to_anthropic_maps_url_citations() {
+        let raw = json!({
+            "id": "chatcmpl-cite",
+            "model": "gpt-4o-search-preview",
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "Rust 1.0 shipped in 2015. Édition 2024 is out.",
+                    "annotations": [
+                        {"type": "url_citation", "url_citation": {
+                            "start_index": 0, "end_index": 25,
+                            "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+                            "title": "Announcing Rust 1.0"
+                        }},
+                        {"type": "url_citation", "url_citation": {
+                            "start_index": 26, "end_index": 46,
+                            "url": "https://doc.rust-lang.org/edition-guide/"
+                        }}
+                    ]
+                },
+                "finish_reason": "stop"
+            }]
+        });
+        let resp: OpenAIResponse = serde_json::from_value(raw).expect("parse");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
+        let content = serde_json::to_value(&out.content).unwrap();
+        assert_eq!(content.as_array().unwrap().len(), 3);
+        assert_eq!(content[0]["text"], "Rust 1.0 shipped in 2015.");
+        assert_eq!(content[0]["citations"][0]["type"], "web_search_result_location");
+        assert_eq!(content[0]["citations"][0]["title"], "Announcing Rust 1.0");
+        assert_eq!(content[1]["text"], " ");
+        assert!(content[1].get("citations").is_none());
+        assert_eq!(content[2]["text"], "Édition 2024 is out.");
+        assert_eq!(content[2]["citations"][0]["cited_text"], "Édition 2024 is out.");
+    }
+
+    #[test]
+    fn openai_to_anthropic_preserves_text_parts() {
+        let raw = json!({
+            "id": "chatcmpl-parts",
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": [
+                        {"type": "text", "text": "first"},
+                        {"type": "text", "text": "second"}
+                    ]
+                },
+                "finish_reason": "stop"
+            }]
+        });
+        let resp: OpenAIResponse = serde_json::from_value(raw).expect("parse");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
+        let texts: Vec<_> = out
+            .content
+            .iter()
+            .map(|block| match block {
+                AnthropicContentBlock::Text { text, .. } => text.as_str(),
+                _ => panic!("unexpected block"),
+            })
+            .collect();
+        assert_eq!(texts, vec!["first", "second"]);
     }
 
     #[test]
@@ -684,6 +1384,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
@@ -703,16 +1405,18 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
-                    content: Some("Hi".to_string()),
+                    annotations: Vec::new(),
+                    content: Some(OpenAIResponseContent::Text("Hi".to_string())),
                     tool_calls: None,
                     reasoning_content: None,
                 },
                 finish_reason: Some("length".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let out = openai_to_anthropic(resp).expect("translate ok");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
         assert_eq!(out.stop_reason, "max_tokens");
 
         let resp_tool = OpenAIResponse {
@@ -721,16 +1425,18 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
-                    content: Some("".to_string()),
+                    annotations: Vec::new(),
+                    content: Some(OpenAIResponseContent::Text("".to_string())),
                     tool_calls: None,
                     reasoning_content: None,
                 },
                 finish_reason: Some("tool_calls".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let out_tool = openai_to_anthropic(resp_tool).expect("translate ok");
+        let out_tool = openai_to_anthropic(resp_tool, EmptyContentPolicy::Error).expect("translate ok");
         assert_eq!(out_tool.stop_reason, "tool_use");
     }
 
@@ -741,9 +1447,10 @@ mod tests {
             model: "gpt-4o-mini".to_string(),
             choices: vec![],
             usage: None,
+            service_tier: None,
         };
 
-        let err = openai_to_anthropic(resp).expect_err("should fail");
+        let err = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect_err("should fail");
         assert_eq!(err.error_type, "api_error");
     }
 
@@ -755,6 +1462,7 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
+                    annotations: Vec::new(),
                     content: None,
                     tool_calls: None,
                     reasoning_content: None,
@@ -762,12 +1470,41 @@ mod tests {
                 finish_reason: Some("stop".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let err = openai_to_anthropic(resp).expect_err("should fail");
+        let err = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect_err("should fail");
         assert_eq!(err.error_type, "api_error");
     }
 
+    #[test]
+    fn openai_to_anthropic_empty_content_policy() {
+        let resp = || OpenAIResponse {
+            id: "chatcmpl-empty".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            choices: vec![OpenAIChoice {
+                message: OpenAIChoiceMessage {
+                    role: "assistant".to_string(),
+                    annotations: Vec::new(),
+                    content: None,
+                    tool_calls: None,
+                    reasoning_content: None,
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+            usage: None,
+            service_tier: None,
+        };
+
+        let out = openai_to_anthropic(resp(), EmptyContentPolicy::EmptyText).expect("translate ok");
+        assert_eq!(out.stop_reason, "end_turn");
+        assert!(matches!(out.content.as_slice(), [AnthropicContentBlock::Text { text, .. }] if text.is_empty()));
+
+        let out = openai_to_anthropic(resp(), EmptyContentPolicy::Empty).expect("translate ok");
+        assert_eq!(out.stop_reason, "end_turn");
+        assert!(out.content.is_empty());
+    }
+
     #[test]
     fn anthropic_system_blocks_rejects_non_text() {
         let req = AnthropicRequest {
@@ -790,6 +1527,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let err = anthropic_to_openai(req, &base_config()).expect_err("should reject");
@@ -815,6 +1554,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
@@ -844,6 +1585,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let err = anthropic_to_openai(req, &base_config()).expect_err("should reject");
@@ -865,17 +1608,19 @@ mod tests {
             top_k: None,
             stop_sequences: None,
             stream: Some(false),
-            tools: Some(vec![AnthropicTool {
+            tools: Some(vec![AnthropicTool::Custom(AnthropicCustomTool {
                 name: "get_weather".to_string(),
                 description: Some("Get weather".to_string()),
                 input_schema: serde_json::json!({"type":"object","properties":{"location":{"type":"string"}}}),
-            }]),
+            })]),
             tool_choice: Some(AnthropicToolChoice {
                 choice_type: "tool".to_string(),
                 name: Some("get_weather".to_string()),
             }),
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
@@ -910,6 +1655,8 @@ mod tests {
                 schema: serde_json::json!({"type":"object"}),
             }),
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
@@ -929,6 +1676,7 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
+                    annotations: Vec::new(),
                     content: None,
                     tool_calls: Some(vec![OpenAIToolCall {
                         id: "call_1".to_string(),
@@ -943,9 +1691,10 @@ mod tests {
                 finish_reason: Some("tool_calls".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let out = openai_to_anthropic(resp).expect("translate ok");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
         assert_eq!(out.stop_reason, "tool_use");
         match &out.content[0] {
             AnthropicContentBlock::ToolUse { name, .. } => assert_eq!(name, "get_weather"),
@@ -983,6 +1732,8 @@ mod tests {
             tool_choice: None,
             output_format: None,
             thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
         };
 
         let out = anthropic_to_openai(req, &base_config()).expect("ok");
@@ -1003,26 +1754,60 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
-                    content: Some("Hi".to_string()),
+                    annotations: Vec::new(),
+                    content: Some(OpenAIResponseContent::Text("Hi".to_string())),
                     tool_calls: None,
-                    reasoning_content: Some(serde_json::json!({
-                        "type": "thinking",
-                        "thinking": "Step",
-                        "signature": "sig"
+                    reasoning_content: Some(OpenAIReasoning::Structured(OpenAIReasoningContent {
+                        kind: "thinking".to_string(),
+                        thinking: "Step".to_string(),
+                        signature: "sig".to_string(),
                     })),
                 },
                 finish_reason: Some("stop".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let out = openai_to_anthropic(resp).expect("translate ok");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
         match &out.content[0] {
             AnthropicContentBlock::Thinking { thinking, .. } => assert_eq!(thinking, "Step"),
             _ => panic!("expected thinking block"),
         }
     }
 
+    #[test]
+    fn reasoning_only_reply_keeps_the_thinking_block() {
+        let resp = OpenAIResponse {
+            id: "chatcmpl-think-only".to_string(),
+            model: "qwen3".to_string(),
+            choices: vec![OpenAIChoice {
+                message: OpenAIChoiceMessage {
+                    role: "assistant".to_string(),
+                    annotations: Vec::new(),
+                    content: None,
+                    tool_calls: None,
+                    reasoning_content: Some(OpenAIReasoning::Text("Trace".to_string())),
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+            usage: None,
+            service_tier: None,
+        };
+
+        let mut out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
+        assert_eq!(out.stop_reason, "end_turn");
+        assert!(matches!(out.content.as_slice(), [AnthropicContentBlock::Thinking { .. }]));
+
+        append_text_to_reasoning_only(&mut out);
+        assert!(matches!(
+            out.content.as_slice(),
+            [AnthropicContentBlock::Thinking { .. }, AnthropicContentBlock::Text { text, .. }] if text.is_empty()
+        ));
+        append_text_to_reasoning_only(&mut out);
+        assert_eq!(out.content.len(), 2);
+    }
+
     #[test]
     fn openai_reasoning_string_to_anthropic_thinking() {
         let resp = OpenAIResponse {
@@ -1031,16 +1816,18 @@ mod tests {
             choices: vec![OpenAIChoice {
                 message: OpenAIChoiceMessage {
                     role: "assistant".to_string(),
-                    content: Some("Hi".to_string()),
+                    annotations: Vec::new(),
+                    content: Some(OpenAIResponseContent::Text("Hi".to_string())),
                     tool_calls: None,
-                    reasoning_content: Some(serde_json::Value::String("Trace".to_string())),
+                    reasoning_content: Some(OpenAIReasoning::Text("Trace".to_string())),
                 },
                 finish_reason: Some("stop".to_string()),
             }],
             usage: None,
+            service_tier: None,
         };
 
-        let out = openai_to_anthropic(resp).expect("translate ok");
+        let out = openai_to_anthropic(resp, EmptyContentPolicy::Error).expect("translate ok");
         assert_eq!(out.content.len(), 2);
         match &out.content[0] {
             AnthropicContentBlock::Thinking { thinking, signature } => {
@@ -1051,6 +1838,379 @@ mod tests {
         }
     }
 
+    #[test]
+    fn anthropic_to_openai_merges_vllm_params() {
+        let mut config = base_config();
+        config.models.vllm_params.insert(
+            "qwen3".to_string(),
+            serde_json::Map::from_iter([
+                ("min_p".to_string(), serde_json::json!(0.05)),
+                ("repetition_penalty".to_string(), serde_json::json!(1.1)),
+            ]),
+        );
+        let req = AnthropicRequest {
+            model: "qwen3".to_string(),
+            max_tokens: 16,
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("Ping".to_string()),
+            }],
+            system: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: Some(false),
+            tools: None,
+            tool_choice: None,
+            output_format: None,
+            thinking: None,
+            vllm_params: Some(serde_json::Map::from_iter([
+                ("repetition_penalty".to_string(), serde_json::json!(1.3)),
+                ("guided_json".to_string(), serde_json::json!({"type":"object"})),
+            ])),
+            extra: Default::default(),
+        };
+
+        let out = anthropic_to_openai(req, &config).expect("translate ok");
+        let body = serde_json::to_value(&out).expect("serialize");
+        assert_eq!(body["min_p"], serde_json::json!(0.05));
+        assert_eq!(body["repetition_penalty"], serde_json::json!(1.3));
+        assert_eq!(body["guided_json"], serde_json::json!({"type":"object"}));
+    }
+
+    #[test]
+    fn anthropic_to_openai_maps_system_role_per_model() {
+        let mut config = base_config();
+        config.models.system_role.insert("o3".to_string(), "developer".to_string());
+        config.models.system_role.insert("gemma".to_string(), "user".to_string());
+        for (model, role) in [("o3", "developer"), ("gemma", "user"), ("gpt-4o", "system")] {
+            let req: AnthropicRequest = serde_json::from_value(json!({
+                "model": model,
+                "max_tokens": 16,
+                "system": "Be brief.",
+                "messages": [{"role": "user", "content": "Ping"}]
+            }))
+            .expect("parse");
+            let out = anthropic_to_openai(req, &config).expect("translate ok");
+            assert_eq!(out.messages.len(), 2, "{}", model);
+            assert_eq!(out.system_messages, 1);
+            assert_eq!(out.messages[0].role, role);
+            assert!(matches!(
+                &out.messages[0].content,
+                Some(OpenAIMessageContent::Text(text)) if text == "Be brief."
+            ));
+        }
+    }
+
+    #[test]
+    fn anthropic_to_openai_composes_one_system_message() {
+        let mut config = base_config();
+        config.models.system_prompt.prefix = Some("Policy.".to_string());
+        config.models.system_prompt.suffix = Some("Answer in English.".to_string());
+        let request = |system: Value| -> AnthropicRequest {
+            serde_json::from_value(json!({
+                "model": "gpt-4o",
+                "max_tokens": 16,
+                "system": system,
+                "messages": [{"role": "user", "content": "Ping"}]
+            }))
+            .expect("parse")
+        };
+
+        let out = anthropic_to_openai(request(json!("Be brief.")), &config).expect("translate ok");
+        assert_eq!(out.messages.len(), 2);
+        assert!(matches!(
+            &out.messages[0].content,
+            Some(OpenAIMessageContent::Text(text)) if text == "Policy.\n\nBe brief.\n\nAnswer in English."
+        ));
+
+        let out = anthropic_to_openai(request(Value::Null), &config).expect("translate ok");
+        assert!(matches!(
+            &out.messages[0].content,
+            Some(OpenAIMessageContent::Text(text)) if text == "Policy.\n\nAnswer in English."
+        ));
+
+        config.models.system_prompt.max_bytes = Some(32);
+        let err = anthropic_to_openai(request(json!("Be brief.")), &config).expect_err("too long");
+        assert_eq!(err.error_type, "invalid_request_error");
+    }
+
+    #[test]
+    fn anthropic_to_openai_rejects_unknown_vllm_param() {
+        let req = AnthropicRequest {
+            model: "qwen3".to_string(),
+            max_tokens: 16,
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("Ping".to_string()),
+            }],
+            system: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: Some(false),
+            tools: None,
+            tool_choice: None,
+            output_format: None,
+            thinking: None,
+            vllm_params: Some(serde_json::Map::from_iter([(
+                "model".to_string(),
+                serde_json::json!("other"),
+            )])),
+            extra: Default::default(),
+        };
+
+        let err = anthropic_to_openai(req, &base_config()).expect_err("should reject");
+        assert_eq!(err.error_type, "invalid_request_error");
+    }
+
+    #[test]
+    fn anthropic_to_openai_applies_model_overrides() {
+        let mut config = base_config();
+        config.models.overrides.insert(
+            "gpt-4o-mini".to_string(),
+            serde_json::json!({
+                "temperature": 0.2,
+                "reasoning_effort": "low",
+                "stream_options": null,
+                "chat_template_kwargs": {"enable_thinking": false}
+            }),
+        );
+        let req = AnthropicRequest {
+            model: "gpt-4o-mini".to_string(),
+            max_tokens: 16,
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("Ping".to_string()),
+            }],
+            system: None,
+            temperature: Some(0.9),
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: Some(true),
+            tools: None,
+            tool_choice: None,
+            output_format: None,
+            thinking: None,
+            vllm_params: None,
+            extra: Default::default(),
+        };
+
+        let out = anthropic_to_openai(req, &config).expect("translate ok");
+        assert_eq!(out.temperature, Some(0.2));
+        assert_eq!(out.reasoning_effort.as_deref(), Some("low"));
+        assert!(out.stream_options.is_none());
+        assert_eq!(out.stream, Some(true));
+        assert_eq!(
+            out.extra.get("chat_template_kwargs"),
+            Some(&serde_json::json!({"enable_thinking": false}))
+        );
+    }
+
+    #[test]
+    fn tool_result_images_move_to_following_user_message() {
+        let req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 16,
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": [
+                        {"type": "text", "text": "screenshot taken"},
+                        {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAA"}}
+                    ]},
+                    {"type": "text", "text": "What do you see?"}
+                ]
+            }]
+        }))
+        .expect("parse");
+
+        let out = anthropic_to_openai(req, &base_config()).expect("translate ok");
+        assert_eq!(out.messages.len(), 2);
+        assert_eq!(out.messages[0].role, "tool");
+        match &out.messages[0].content {
+            Some(OpenAIMessageContent::Text(text)) => {
+                assert_eq!(text, "screenshot taken\n[image 1 attached below]");
+            }
+            other => panic!("unexpected tool content: {:?}", other),
+        }
+        assert_eq!(out.messages[1].role, "user");
+        match &out.messages[1].content {
+            Some(OpenAIMessageContent::Parts(parts)) => {
+                assert!(matches!(&parts[0], OpenAIContentPart::ImageUrl { image_url } if image_url.url == "data:image/png;base64,AAA"));
+                assert!(matches!(&parts[1], OpenAIContentPart::Text { text } if text == "What do you see?"));
+            }
+            other => panic!("unexpected user content: {:?}", other),
+        }
+
+        let mut config = base_config();
+        config.models.allow_images = false;
+        let req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 16,
+            "messages": [{
+                "role": "user",
+                "content": [{"type": "tool_result", "tool_use_id": "call_1", "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAA"}}
+                ]}]
+            }]
+        }))
+        .expect("parse");
+        let out = anthropic_to_openai(req, &config).expect("translate ok");
+        assert_eq!(out.messages.len(), 1);
+        assert!(matches!(&out.messages[0].content, Some(OpenAIMessageContent::Text(text)) if text == "[image omitted]"));
+    }
+
+    #[test]
+    fn tool_result_is_error_marks_tool_message() {
+        let request = || -> AnthropicRequest {
+            serde_json::from_value(json!({
+                "model": "gpt-4o-mini",
+                "max_tokens": 16,
+                "messages": [{
+                    "role": "user",
+                    "content": [
+                        {"type": "tool_result", "tool_use_id": "call_1", "content": "file not found", "is_error": true},
+                        {"type": "tool_result", "tool_use_id": "call_2", "content": "ok", "is_error": false}
+                    ]
+                }]
+            }))
+            .expect("parse")
+        };
+        let tool_texts = |config: &Config| -> Vec<String> {
+            anthropic_to_openai(request(), config)
+                .expect("translate ok")
+                .messages
+                .into_iter()
+                .map(|msg| match msg.content {
+                    Some(OpenAIMessageContent::Text(text)) => text,
+                    other => panic!("unexpected tool content: {:?}", other),
+                })
+                .collect()
+        };
+
+        let mut config = base_config();
+        assert_eq!(tool_texts(&config), ["[tool error] file not found", "ok"]);
+        config.models.tool_error = "json".to_string();
+        assert_eq!(tool_texts(&config), [r#"{"content":"file not found","is_error":true}"#, "ok"]);
+        config.models.tool_error = "drop".to_string();
+        assert_eq!(tool_texts(&config), ["file not found", "ok"]);
+    }
+
+    #[test]
+    fn assistant_prefill_modes() {
+        let request = || -> AnthropicRequest {
+            serde_json::from_value(json!({
+                "model": "gpt-4o-mini",
+                "max_tokens": 16,
+                "messages": [
+                    {"role": "user", "content": "List three colors as JSON."},
+                    {"role": "assistant", "content": "{\"colors\": ["}
+                ]
+            }))
+            .expect("parse")
+        };
+        assert_eq!(assistant_prefill(&request()).as_deref(), Some("{\"colors\": ["));
+
+        let mut config = base_config();
+        config.models.prefill_mode = "continue".to_string();
+        let out = anthropic_to_openai(request(), &config).expect("translate ok");
+        assert_eq!(out.messages.last().unwrap().role, "assistant");
+        assert_eq!(out.extra.get("continue_final_message"), Some(&json!(true)));
+        assert_eq!(out.extra.get("add_generation_prompt"), Some(&json!(false)));
+
+        config.models.prefill_mode = "suffix".to_string();
+        let out = anthropic_to_openai(request(), &config).expect("translate ok");
+        assert_eq!(out.messages.len(), 1);
+        match &out.messages[0].content {
+            Some(OpenAIMessageContent::Text(text)) => {
+                assert!(text.starts_with("List three colors as JSON.\n\n"));
+                assert!(text.ends_with("{\"colors\": ["));
+            }
+            other => panic!("unexpected content: {:?}", other),
+        }
+        assert!(out.extra.get("continue_final_message").is_none());
+
+        let mut resp = AnthropicResponse {
+            id: "msg".to_string(),
+            response_type: "message".to_string(),
+            role: "assistant".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            content: vec![AnthropicContentBlock::Text {
+                text: "{\"colors\": [\"red\"]}".to_string(),
+                cache_control: None,
+                citations: None,
+            }],
+            stop_reason: "end_turn".to_string(),
+            stop_sequence: None,
+            usage: AnthropicUsage::default(),
+        };
+        strip_prefill_echo(&mut resp, "{\"colors\": [");
+        assert!(matches!(&resp.content[0], AnthropicContentBlock::Text { text, .. } if text == "\"red\"]}"));
+    }
+
+    #[test]
+    fn sanitize_messages_repairs_alternation() {
+        let req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "gpt-4o-mini",
+            "max_tokens": 16,
+            "messages": [
+                {"role": "user", "content": "Hi"},
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Checking."},
+                    {"type": "tool_use", "id": "call_1", "name": "weather", "input": {"city": "Paris"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "Here you go"},
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "sunny"},
+                    {"type": "tool_result", "tool_use_id": "call_9", "content": "stale"}
+                ]}
+            ]
+        }))
+        .expect("parse");
+        let mut config = base_config();
+        config.models.sanitize_messages = true;
+        let out = anthropic_to_openai(req, &config).expect("translate ok");
+        let roles: Vec<_> = out.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, vec!["user", "assistant", "tool", "user"]);
+        assert!(matches!(&out.messages[0].content, Some(OpenAIMessageContent::Text(text)) if text == "Hi\n\nWeather in Paris?"));
+        assert!(matches!(&out.messages[1].content, Some(OpenAIMessageContent::Text(text)) if text == "Checking."));
+        assert_eq!(out.messages[1].tool_calls.as_ref().map(Vec::len), Some(1));
+        assert_eq!(out.messages[2].tool_call_id.as_deref(), Some("call_1"));
+    }
+
+    #[test]
+    fn anthropic_version_header_parsing() {
+        assert_eq!(
+            AnthropicVersion::from_header(None).expect("default"),
+            AnthropicVersion::V2023_06_01
+        );
+        assert_eq!(
+            AnthropicVersion::from_header(Some("2023-01-01")).expect("legacy"),
+            AnthropicVersion::V2023_01_01
+        );
+        let err = AnthropicVersion::from_header(Some("2022-01-01")).expect_err("unsupported");
+        assert_eq!(err.error_type, "invalid_request_error");
+    }
+
+    #[test]
+    fn legacy_anthropic_version_strips_cache_usage() {
+        let mut body = serde_json::json!({
+            "usage": {
+                "input_tokens": 1,
+                "output_tokens": 2,
+                "cache_creation_input_tokens": 0,
+                "cache_read_input_tokens": 0
+            }
+        });
+        AnthropicVersion::V2023_01_01.shape_response(&mut body);
+        assert_eq!(body["usage"], serde_json::json!({"input_tokens": 1, "output_tokens": 2}));
+    }
+
     #[test]
     fn openai_models_to_anthropic_mapping() {
         let resp = OpenAIModelsResponse {
@@ -1072,4 +2232,20 @@ mod tests {
         assert_eq!(out.data[0].display_name, "GPT-4o Mini");
         assert!(out.data[0].created_at.ends_with('Z'));
     }
+
+    #[test]
+    fn normalize_response_backfills_id_and_model() {
+        let mut resp: OpenAIResponse = serde_json::from_value(json!({
+            "choices": [{"message": {"role": "assistant", "content": "hi"}, "finish_reason": "stop"}]
+        }))
+        .expect("parse without id and model");
+        normalize_response(&mut resp, "qwen3");
+        assert!(resp.id.starts_with("msg_"), "{}", resp.id);
+        assert_eq!(resp.model, "qwen3");
+
+        let id = resp.id.clone();
+        resp.model = "qwen3-32b".to_string();
+        normalize_response(&mut resp, "qwen3");
+        assert_eq!((resp.id.as_str(), resp.model.as_str()), (id.as_str(), "qwen3-32b"));
+    }
 }
@@ -0,0 +1,346 @@
+use opentelemetry::KeyValue;
+use serde_json::Value;
+
+use crate::builtin_tools;
+use crate::config::{BuiltInToolPolicy, Config, DocumentPolicy, ToolErrorMode};
+use crate::metrics::Metrics;
+use crate::models::{OpenAIRequest, OpenAIResponse, ANTHROPIC_REQUEST_FIELDS};
+
+/// Lists the lossy translation events of a `translate` response, e.g.
+/// `top_k_dropped, choices_dropped`.
+pub const WARNINGS_HEADER: &str = "x-gateway-warnings";
+
+/// Events that lose something the client asked for; these become warnings.
+const LOSSY: [&str; 14] = [
+    "image_omitted",
+    "document_stripped",
+    "document_text_only",
+    "thinking_dropped",
+    "tool_choice_downgraded",
+    "top_k_dropped",
+    "metadata_dropped",
+    "cache_control_dropped",
+    "choices_dropped",
+    "tool_error_dropped",
+    "builtin_tool_stripped",
+    "unknown_field_dropped",
+    "output_format_downgraded",
+    "max_tokens_clamped",
+];
+
+/// A translation path a request or response went through in `translate`
+/// mode: a conversion, a downgrade or a drop. Counted in
+/// `ai.gateway.translation_events` so operators can see what their traffic
+/// relies on before enabling stricter policies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranslationEvent {
+    pub kind: &'static str,
+    pub count: u64,
+    /// E.g. the reasoning effort a thinking budget mapped to.
+    pub detail: Option<String>,
+}
+
+impl TranslationEvent {
+    fn new(kind: &'static str, count: u64) -> Self {
+        Self {
+            kind,
+            count,
+            detail: None,
+        }
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        LOSSY.contains(&self.kind)
+    }
+}
+
+/// Warning names for the lossy events, in order and without duplicates.
+pub fn warnings(events: &[TranslationEvent]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for event in events.iter().filter(|event| event.is_lossy()) {
+        if !out.iter().any(|kind| kind == event.kind) {
+            out.push(event.kind.to_string());
+        }
+    }
+    out
+}
+
+/// Events for the raw Anthropic `payload` and the request it became.
+pub fn request_events(payload: &Value, req: &OpenAIRequest, config: &Config) -> Vec<TranslationEvent> {
+    let mut events = Vec::new();
+    let mut images = 0;
+    let mut omitted_images = 0;
+    let mut documents = 0;
+    let mut tool_errors = 0;
+    let blocks = payload
+        .get("messages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message.get("content").and_then(Value::as_array))
+        .flatten();
+    for block in blocks {
+        match block.get("type").and_then(Value::as_str) {
+            Some("image") => images += 1,
+            Some("document") => documents += 1,
+            Some("tool_result") => {
+                if block.get("is_error").and_then(Value::as_bool) == Some(true) {
+                    tool_errors += 1;
+                }
+                let nested = block
+                    .get("content")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter(|part| part.get("type").and_then(Value::as_str) == Some("image"))
+                    .count();
+                if config.models.allow_images {
+                    images += nested;
+                } else {
+                    omitted_images += nested;
+                }
+            }
+            _ => {}
+        }
+    }
+    if images > 0 {
+        events.push(TranslationEvent::new("image_converted", images as u64));
+    }
+    if omitted_images > 0 {
+        events.push(TranslationEvent::new("image_omitted", omitted_images as u64));
+    }
+    match config.document_policy() {
+        Ok(DocumentPolicy::Strip) if documents > 0 => {
+            events.push(TranslationEvent::new("document_stripped", documents as u64));
+        }
+        Ok(DocumentPolicy::TextOnly) if documents > 0 => {
+            events.push(TranslationEvent::new("document_text_only", documents as u64));
+        }
+        _ => {}
+    }
+    if tool_errors > 0 {
+        let kind = match config.tool_error_mode() {
+            ToolErrorMode::Drop => "tool_error_dropped",
+            _ => "tool_error_marked",
+        };
+        events.push(TranslationEvent::new(kind, tool_errors));
+    }
+
+    let thinking_enabled = payload.pointer("/thinking/type").and_then(Value::as_str) == Some("enabled");
+    match req.reasoning_effort.as_ref() {
+        Some(effort) if thinking_enabled => events.push(TranslationEvent {
+            detail: Some(effort.clone()),
+            ..TranslationEvent::new("thinking_mapped", 1)
+        }),
+        None if thinking_enabled => events.push(TranslationEvent::new("thinking_dropped", 1)),
+        _ => {}
+    }
+    if payload.pointer("/tool_choice/type").and_then(Value::as_str) == Some("any") {
+        events.push(TranslationEvent {
+            detail: Some("auto".to_string()),
+            ..TranslationEvent::new("tool_choice_downgraded", 1)
+        });
+    }
+    let builtin_tools = payload
+        .get("tools")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|tool| tool.get("type").and_then(Value::as_str).and_then(builtin_tools::family).is_some())
+        .count();
+    if builtin_tools > 0 {
+        let kind = match config.builtin_tool_policy() {
+            BuiltInToolPolicy::Map => "builtin_tool_mapped",
+            _ => "builtin_tool_stripped",
+        };
+        events.push(TranslationEvent::new(kind, builtin_tools as u64));
+    }
+    if payload
+        .get("max_tokens")
+        .and_then(Value::as_u64)
+        .is_some_and(|max_tokens| max_tokens > req.max_completion_tokens as u64)
+    {
+        events.push(TranslationEvent::new("max_tokens_clamped", 1));
+    }
+    if let Some(stop) = req.stop.as_ref().filter(|stop| !stop.is_empty()) {
+        events.push(TranslationEvent::new("stop_sequences_forwarded", stop.len() as u64));
+    }
+    if payload.get("top_k").is_some_and(|top_k| !top_k.is_null()) && !req.extra.contains_key("top_k") {
+        events.push(TranslationEvent::new("top_k_dropped", 1));
+    }
+    if payload.get("metadata").is_some_and(|metadata| !metadata.is_null()) && !req.extra.contains_key("metadata") {
+        events.push(TranslationEvent::new("metadata_dropped", 1));
+    }
+    let unknown: Vec<&String> = payload
+        .as_object()
+        .into_iter()
+        .flat_map(|obj| obj.keys())
+        .filter(|field| !ANTHROPIC_REQUEST_FIELDS.contains(&field.as_str()) && field.as_str() != "metadata")
+        .collect();
+    let forwarded = unknown
+        .iter()
+        .filter(|field| config.models.forward_fields.contains(field))
+        .count();
+    if forwarded > 0 {
+        events.push(TranslationEvent::new("unknown_field_forwarded", forwarded as u64));
+    }
+    if unknown.len() > forwarded {
+        events.push(TranslationEvent::new("unknown_field_dropped", (unknown.len() - forwarded) as u64));
+    }
+    let cache_controls = cache_control_count(payload);
+    if cache_controls > 0 {
+        events.push(TranslationEvent::new("cache_control_dropped", cache_controls));
+    }
+    events
+}
+
+/// `cache_control` markers on system blocks, message blocks and tools; the
+/// downstream has no prompt caching to hand them to.
+fn cache_control_count(payload: &Value) -> u64 {
+    let system = payload.get("system").and_then(Value::as_array).into_iter().flatten();
+    let tools = payload.get("tools").and_then(Value::as_array).into_iter().flatten();
+    let blocks = payload
+        .get("messages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|message| message.get("content").and_then(Value::as_array))
+        .flatten();
+    system
+        .chain(tools)
+        .chain(blocks)
+        .filter(|item| item.get("cache_control").is_some_and(|c| !c.is_null()))
+        .count() as u64
+}
+
+/// `output_format` was sent as `json_object` plus a schema instruction, so
+/// the downstream did not enforce the schema; `repaired` when the gateway
+/// had to trim the output to its JSON object.
+pub fn output_format_fallback(repaired: bool) -> Vec<TranslationEvent> {
+    let mut events = vec![TranslationEvent::new("output_format_downgraded", 1)];
+    if repaired {
+        events.push(TranslationEvent::new("output_format_repaired", 1));
+    }
+    events
+}
+
+/// Only the first choice becomes the Anthropic response.
+pub fn response_events(resp: &OpenAIResponse) -> Vec<TranslationEvent> {
+    match resp.choices.len() {
+        0 | 1 => Vec::new(),
+        n => vec![TranslationEvent::new("choices_dropped", n as u64 - 1)],
+    }
+}
+
+pub fn record(metrics: &Metrics, events: &[TranslationEvent]) {
+    for event in events {
+        let mut attrs = vec![KeyValue::new("event", event.kind)];
+        if let Some(detail) = event.detail.as_ref() {
+            attrs.push(KeyValue::new("detail", detail.clone()));
+        }
+        metrics.translation_events.add(event.count, &attrs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AnthropicRequest;
+    use crate::translate::anthropic_to_openai;
+    use serde_json::json;
+
+    #[test]
+    fn reports_conversions_downgrades_and_drops() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nmodels:\n  document_policy: strip\n  thinking_map:\n    4096: high\nlimits: {}\nobservability: {}\n",
+        )
+        .expect("config");
+        let payload = json!({
+            "model": "claude",
+            "max_tokens": 64,
+            "stop_sequences": ["END"],
+            "top_k": 5,
+            "metadata": {"user_id": "u-1"},
+            "system": [{"type": "text", "text": "sys", "cache_control": {"type": "ephemeral"}}],
+            "thinking": {"type": "enabled", "budget_tokens": 8000},
+            "tools": [{"name": "t", "input_schema": {"type": "object"}}],
+            "tool_choice": {"type": "any"},
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AA=="}},
+                    {"type": "document", "source": {"type": "text", "media_type": "text/plain", "data": "doc"}},
+                    {"type": "text", "text": "hi", "cache_control": {"type": "ephemeral"}}
+                ]}
+            ]
+        });
+        let req: AnthropicRequest = serde_json::from_value(payload.clone()).expect("request");
+        let openai = anthropic_to_openai(req, &config).expect("translate");
+        let detail = |kind, detail: &str| TranslationEvent {
+            detail: Some(detail.to_string()),
+            ..TranslationEvent::new(kind, 1)
+        };
+        let events = request_events(&payload, &openai, &config);
+        assert_eq!(
+            events,
+            [
+                TranslationEvent::new("image_converted", 1),
+                TranslationEvent::new("document_stripped", 1),
+                detail("thinking_mapped", "high"),
+                detail("tool_choice_downgraded", "auto"),
+                TranslationEvent::new("stop_sequences_forwarded", 1),
+                TranslationEvent::new("top_k_dropped", 1),
+                TranslationEvent::new("metadata_dropped", 1),
+                TranslationEvent::new("cache_control_dropped", 2),
+            ]
+        );
+        assert_eq!(
+            warnings(&events),
+            [
+                "document_stripped",
+                "tool_choice_downgraded",
+                "top_k_dropped",
+                "metadata_dropped",
+                "cache_control_dropped"
+            ]
+        );
+
+        let resp: OpenAIResponse = serde_json::from_value(json!({
+            "id": "r", "model": "m",
+            "choices": [
+                {"index": 0, "message": {"role": "assistant", "content": "a"}, "finish_reason": "stop"},
+                {"index": 1, "message": {"role": "assistant", "content": "b"}, "finish_reason": "stop"}
+            ]
+        }))
+        .expect("response");
+        assert_eq!(response_events(&resp), [TranslationEvent::new("choices_dropped", 1)]);
+    }
+
+    #[test]
+    fn forwards_listed_unknown_fields_and_reports_the_rest() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream: {}\nmodels:\n  forward_fields: [service_tier]\nlimits: {}\nobservability: {}\n",
+        )
+        .expect("config");
+        let payload = json!({
+            "model": "claude",
+            "max_tokens": 64,
+            "service_tier": "auto",
+            "container": "c-1",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let req: AnthropicRequest = serde_json::from_value(payload.clone()).expect("request");
+        assert_eq!(req.extra.len(), 2);
+        let openai = anthropic_to_openai(req, &config).expect("translate");
+        assert_eq!(openai.extra.get("service_tier"), Some(&json!("auto")));
+        assert!(!openai.extra.contains_key("container"));
+        let events = request_events(&payload, &openai, &config);
+        assert_eq!(
+            events,
+            [
+                TranslationEvent::new("unknown_field_forwarded", 1),
+                TranslationEvent::new("unknown_field_dropped", 1),
+            ]
+        );
+        assert_eq!(warnings(&events), ["unknown_field_dropped"]);
+    }
+}
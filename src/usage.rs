@@ -0,0 +1,227 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::access_log::RequestSummary;
+use crate::config::ModelPricing;
+use crate::pg_store::PgUsageStore;
+use crate::usage_sqlite::SqliteUsageStore;
+
+/// Persistent mirror of the in-memory ring buffer.
+#[derive(Clone)]
+pub enum UsageBackend {
+    Sqlite(SqliteUsageStore),
+    Postgres(PgUsageStore),
+}
+
+impl UsageBackend {
+    fn record(&self, summary: &RequestSummary) {
+        match self {
+            UsageBackend::Sqlite(store) => store.record(summary),
+            UsageBackend::Postgres(store) => store.record(summary),
+        }
+    }
+
+    async fn since(&self, ts_ms: u128) -> Result<Vec<RequestSummary>, String> {
+        match self {
+            UsageBackend::Sqlite(store) => store.since(ts_ms).await,
+            UsageBackend::Postgres(store) => store.since(ts_ms).await,
+        }
+    }
+}
+
+/// Keeps the most recent request summaries in memory for usage reporting,
+/// optionally mirrored to SQLite or Postgres so history survives restarts.
+#[derive(Clone)]
+pub struct UsageStore {
+    entries: Arc<Mutex<VecDeque<RequestSummary>>>,
+    capacity: usize,
+    backend: Option<UsageBackend>,
+}
+
+impl UsageStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity.min(1024)))),
+            capacity,
+            backend: None,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: UsageBackend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn record(&self, summary: RequestSummary) {
+        if let Some(backend) = self.backend.as_ref() {
+            backend.record(&summary);
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(summary);
+    }
+
+    pub async fn since(&self, ts_ms: u128) -> Vec<RequestSummary> {
+        if let Some(backend) = self.backend.as_ref() {
+            match backend.since(ts_ms).await {
+                Ok(rows) => return rows,
+                Err(err) => tracing::warn!("{}, falling back to in-memory usage", err),
+            }
+        }
+        self.recent(ts_ms)
+    }
+
+    /// In-memory summaries since `ts_ms`, never touching the persistent backend.
+    pub fn recent(&self, ts_ms: u128) -> Vec<RequestSummary> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .filter(|s| s.ts_ms >= ts_ms)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct UsageGroup {
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Parses a window such as `30m`, `24h`, `7d` or `3600s` into milliseconds;
+/// windows past `u64::MAX` milliseconds are invalid.
+pub fn parse_window(window: &str) -> Result<u128, String> {
+    let window = window.trim();
+    let invalid = || format!("invalid window: {}", window);
+    let (value, unit_ms) = [('s', 1_000), ('m', 60_000), ('h', 3_600_000), ('d', 86_400_000)]
+        .into_iter()
+        .find_map(|(unit, unit_ms)| Some((window.strip_suffix(unit)?, unit_ms)))
+        .ok_or_else(invalid)?;
+    let value: u128 = value.parse().map_err(|_| invalid())?;
+    if value == 0 {
+        return Err(invalid());
+    }
+    value
+        .checked_mul(unit_ms)
+        .filter(|ms| *ms <= u64::MAX as u128)
+        .ok_or_else(invalid)
+}
+
+pub fn is_error(summary: &RequestSummary) -> bool {
+    summary.status >= 400 || summary.error_type.is_some()
+}
+
+pub fn summary_cost(summary: &RequestSummary, pricing: &HashMap<String, ModelPricing>) -> f64 {
+    summary
+        .model
+        .as_ref()
+        .and_then(|model| pricing.get(model))
+        .map(|price| {
+            price.cost(
+                summary.input_tokens.unwrap_or(0),
+                summary.output_tokens.unwrap_or(0),
+            )
+        })
+        .unwrap_or(0.0)
+}
+
+pub fn aggregate(
+    entries: &[RequestSummary],
+    pricing: &HashMap<String, ModelPricing>,
+) -> Vec<UsageGroup> {
+    let mut groups: BTreeMap<(Option<String>, Option<String>), UsageGroup> = BTreeMap::new();
+    for summary in entries {
+        let key = (summary.key_id.clone(), summary.model.clone());
+        let group = groups.entry(key).or_insert_with(|| UsageGroup {
+            key_id: summary.key_id.clone(),
+            model: summary.model.clone(),
+            ..Default::default()
+        });
+        group.requests += 1;
+        if is_error(summary) {
+            group.errors += 1;
+        }
+        group.input_tokens += summary.input_tokens.unwrap_or(0);
+        group.output_tokens += summary.output_tokens.unwrap_or(0);
+        group.cost_usd += summary
+            .cost_usd
+            .unwrap_or_else(|| summary_cost(summary, pricing));
+    }
+    groups.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(key: &str, model: &str, input: u64, output: u64, status: u16) -> RequestSummary {
+        let mut s = RequestSummary::new(
+            "req",
+            "/v1/messages",
+            "POST",
+            "translate",
+            &axum::http::HeaderMap::new(),
+        );
+        s.key_id = Some(key.to_string());
+        s.model = Some(model.to_string());
+        s.input_tokens = Some(input);
+        s.output_tokens = Some(output);
+        s.status = status;
+        s
+    }
+
+    #[test]
+    fn parse_window_units() {
+        assert_eq!(parse_window("24h").unwrap(), 86_400_000);
+        assert_eq!(parse_window("30m").unwrap(), 1_800_000);
+        assert_eq!(parse_window("7d").unwrap(), 604_800_000);
+        assert!(parse_window("0h").is_err());
+        assert!(parse_window("h").is_err());
+        assert!(parse_window("10w").is_err());
+        assert!(parse_window("10é").is_err());
+        assert!(parse_window("é").is_err());
+        assert!(parse_window("4000000000000000000000000000000d").is_err());
+        assert!(parse_window(&format!("{}s", u64::MAX)).is_err());
+        assert_eq!(parse_window(&format!("{}s", u64::MAX / 1_000)).unwrap(), u64::MAX as u128 / 1_000 * 1_000);
+    }
+
+    #[test]
+    fn aggregate_groups_by_key_and_model() {
+        let pricing = HashMap::from([(
+            "gpt-4o".to_string(),
+            ModelPricing {
+                input_per_mtok: 2.0,
+                output_per_mtok: 8.0,
+            },
+        )]);
+        let entries = vec![
+            summary("key-a", "gpt-4o", 1_000_000, 0, 200),
+            summary("key-a", "gpt-4o", 0, 500_000, 502),
+            summary("key-b", "gpt-4o", 10, 10, 200),
+        ];
+        let groups = aggregate(&entries, &pricing);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].key_id.as_deref(), Some("key-a"));
+        assert_eq!(groups[0].requests, 2);
+        assert_eq!(groups[0].errors, 1);
+        assert!((groups[0].cost_usd - 6.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn ring_buffer_drops_oldest() {
+        let store = UsageStore::new(2);
+        for model in ["a", "b", "c"] {
+            store.record(summary("key", model, 1, 1, 200));
+        }
+        let models: Vec<_> = store.since(0).await.into_iter().filter_map(|s| s.model).collect();
+        assert_eq!(models, vec!["b", "c"]);
+    }
+}
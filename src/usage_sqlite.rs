@@ -0,0 +1,179 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::Row;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::access_log::RequestSummary;
+
+const WRITE_BATCH_SIZE: usize = 256;
+
+/// Persists request summaries to an embedded SQLite database. Inserts are
+/// batched on a background task so the request path never waits on disk.
+#[derive(Clone)]
+pub struct SqliteUsageStore {
+    pool: SqlitePool,
+    sender: mpsc::Sender<RequestSummary>,
+}
+
+impl SqliteUsageStore {
+    pub async fn open(path: &str) -> Result<Self, String> {
+        if let Some(parent) = std::path::Path::new(path).parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("usage sqlite dir error: {}", e))?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .map_err(|e| format!("usage sqlite open error: {}", e))?;
+        sqlx::migrate!("migrations/sqlite")
+            .run(&pool)
+            .await
+            .map_err(|e| format!("usage sqlite migrate error: {}", e))?;
+
+        let (tx, mut rx) = mpsc::channel::<RequestSummary>(4096);
+        let writer_pool = pool.clone();
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+            while rx.recv_many(&mut batch, WRITE_BATCH_SIZE).await > 0 {
+                if let Err(err) = insert_batch(&writer_pool, &batch).await {
+                    tracing::error!("usage sqlite write error: {}", err);
+                }
+                batch.clear();
+            }
+        });
+        Ok(Self { pool, sender: tx })
+    }
+
+    pub fn record(&self, summary: &RequestSummary) {
+        if self.sender.try_send(summary.clone()).is_err() {
+            tracing::warn!(request_id = %summary.request_id, "usage sqlite channel full, record dropped");
+        }
+    }
+
+    pub async fn since(&self, ts_ms: u128) -> Result<Vec<RequestSummary>, String> {
+        let rows = sqlx::query(
+            "SELECT request_id, ts_ms, route, method, mode, key_id, model, stream, status, \
+             error_type, input_tokens, output_tokens, cache_hit, cost_usd, latency_ms, ttfb_ms, \
+             downstream_endpoint FROM request_summaries WHERE ts_ms >= ? ORDER BY ts_ms",
+        )
+        .bind(ts_ms as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("usage sqlite query error: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| RequestSummary {
+                ts_ms: row.get::<i64, _>("ts_ms") as u128,
+                request_id: row.get("request_id"),
+                route: row.get("route"),
+                method: row.get("method"),
+                mode: row.get("mode"),
+                key_id: row.get("key_id"),
+                model: row.get("model"),
+                stream: row.get("stream"),
+                status: row.get::<i64, _>("status") as u16,
+                error_type: row.get("error_type"),
+                input_tokens: row.get::<Option<i64>, _>("input_tokens").map(|v| v as u64),
+                output_tokens: row.get::<Option<i64>, _>("output_tokens").map(|v| v as u64),
+                cache_hit: row.get("cache_hit"),
+                service_tier: None,
+                cost_usd: row.get("cost_usd"),
+                latency_ms: row.get::<i64, _>("latency_ms") as u64,
+                ttfb_ms: row.get::<Option<i64>, _>("ttfb_ms").map(|v| v as u64),
+                downstream_start: None,
+                downstream_ttfb_ms: None,
+                downstream_ms: None,
+                downstream_endpoint: row.get("downstream_endpoint"),
+                experiment: None,
+                trace_id: None,
+                span_id: None,
+                prompt_text: None,
+                output_text: None,
+            })
+            .collect())
+    }
+}
+
+async fn insert_batch(pool: &SqlitePool, batch: &[RequestSummary]) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for summary in batch {
+        sqlx::query(
+            "INSERT INTO request_summaries (request_id, ts_ms, route, method, mode, key_id, \
+             model, stream, status, error_type, input_tokens, output_tokens, cache_hit, cost_usd, \
+             latency_ms, ttfb_ms, downstream_endpoint) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&summary.request_id)
+        .bind(summary.ts_ms as i64)
+        .bind(&summary.route)
+        .bind(&summary.method)
+        .bind(&summary.mode)
+        .bind(&summary.key_id)
+        .bind(&summary.model)
+        .bind(summary.stream)
+        .bind(summary.status as i64)
+        .bind(&summary.error_type)
+        .bind(summary.input_tokens.map(|v| v as i64))
+        .bind(summary.output_tokens.map(|v| v as i64))
+        .bind(summary.cache_hit)
+        .bind(summary.cost_usd)
+        .bind(summary.latency_ms as i64)
+        .bind(summary.ttfb_ms.map(|v| v as i64))
+        .bind(&summary.downstream_endpoint)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!("llm-gateway-usage-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("usage.db");
+        let path = path.to_str().unwrap();
+        let store = SqliteUsageStore::open(path).await.expect("open");
+        let mut summary = RequestSummary::new(
+            "req-1",
+            "/v1/messages",
+            "POST",
+            "translate",
+            &axum::http::HeaderMap::new(),
+        );
+        summary.model = Some("gpt-4o".to_string());
+        summary.input_tokens = Some(12);
+        summary.cost_usd = Some(0.5);
+        store.record(&summary);
+
+        let mut rows = Vec::new();
+        for _ in 0..50 {
+            rows = store.since(0).await.expect("query");
+            if !rows.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        drop(store);
+
+        let reopened = SqliteUsageStore::open(path).await.expect("reopen");
+        let rows_after = reopened.since(0).await.expect("query");
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows_after.len(), 1);
+        assert_eq!(rows_after[0].request_id, "req-1");
+        assert_eq!(rows_after[0].input_tokens, Some(12));
+        assert_eq!(rows_after[0].cost_usd, Some(0.5));
+    }
+}
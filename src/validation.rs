@@ -0,0 +1,325 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::models::AnthropicRequest;
+
+/// Parses an Anthropic request body. When deserializing fails the body is
+/// walked against the request schema so the error names the first offending
+/// field, e.g. `messages.3.content.1.source.media_type: expected string,
+/// found integer`; serde's own message (which loses the path inside
+/// untagged and tagged enums) is the fallback.
+pub fn parse_request(payload: &Value) -> Result<AnthropicRequest, String> {
+    AnthropicRequest::deserialize(payload).map_err(|err| match check_request(payload) {
+        Err(field) => field.to_string(),
+        Ok(()) => format!("invalid request: {}", err),
+    })
+}
+
+/// The first field that does not match the request schema.
+#[derive(Debug, PartialEq)]
+pub struct FieldError {
+    /// Dotted path with array indices, empty for the body itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+type Checked<T = ()> = Result<T, FieldError>;
+
+pub fn check_request(payload: &Value) -> Checked {
+    let body = object(payload, "")?;
+    string(required(body, "model", "")?, "model")?;
+    unsigned(required(body, "max_tokens", "")?, "max_tokens")?;
+    for (i, message) in array(required(body, "messages", "")?, "messages")?.iter().enumerate() {
+        check_message(message, &format!("messages.{}", i))?;
+    }
+    if let Some(system) = optional(body, "system") {
+        check_system(system, "system")?;
+    }
+    for field in ["temperature", "top_p"] {
+        if let Some(value) = optional(body, field) {
+            number(value, field)?;
+        }
+    }
+    if let Some(top_k) = optional(body, "top_k") {
+        unsigned(top_k, "top_k")?;
+    }
+    if let Some(stops) = optional(body, "stop_sequences") {
+        for (i, stop) in array(stops, "stop_sequences")?.iter().enumerate() {
+            string(stop, &format!("stop_sequences.{}", i))?;
+        }
+    }
+    if let Some(stream) = optional(body, "stream") {
+        boolean(stream, "stream")?;
+    }
+    if let Some(tools) = optional(body, "tools") {
+        for (i, tool) in array(tools, "tools")?.iter().enumerate() {
+            check_tool(tool, &format!("tools.{}", i))?;
+        }
+    }
+    if let Some(choice) = optional(body, "tool_choice") {
+        let choice = object(choice, "tool_choice")?;
+        string(required(choice, "type", "tool_choice")?, "tool_choice.type")?;
+        optional_string(choice, "name", "tool_choice")?;
+    }
+    if let Some(format) = optional(body, "output_format") {
+        let format = object(format, "output_format")?;
+        string(required(format, "type", "output_format")?, "output_format.type")?;
+        required(format, "schema", "output_format")?;
+    }
+    if let Some(thinking) = optional(body, "thinking") {
+        let thinking = object(thinking, "thinking")?;
+        string(required(thinking, "type", "thinking")?, "thinking.type")?;
+        if let Some(budget) = optional(thinking, "budget_tokens") {
+            unsigned(budget, "thinking.budget_tokens")?;
+        }
+    }
+    if let Some(params) = optional(body, "vllm_params") {
+        object(params, "vllm_params")?;
+    }
+    Ok(())
+}
+
+fn check_message(message: &Value, path: &str) -> Checked {
+    let message = object(message, path)?;
+    string(required(message, "role", path)?, &child(path, "role"))?;
+    let content_path = child(path, "content");
+    match required(message, "content", path)? {
+        Value::String(_) => Ok(()),
+        Value::Array(blocks) => {
+            for (i, block) in blocks.iter().enumerate() {
+                check_block(block, &format!("{}.{}", content_path, i))?;
+            }
+            Ok(())
+        }
+        other => Err(expected("string or array", other, &content_path)),
+    }
+}
+
+fn check_block(block: &Value, path: &str) -> Checked {
+    let fields = object(block, path)?;
+    let type_path = child(path, "type");
+    let block_type = required(fields, "type", path)?;
+    let Some(block_type) = block_type.as_str() else {
+        return Err(expected("string", block_type, &type_path));
+    };
+    match block_type {
+        "text" => {
+            string(required(fields, "text", path)?, &child(path, "text"))?;
+            if let Some(citations) = optional(fields, "citations") {
+                array(citations, &child(path, "citations"))?;
+            }
+        }
+        "image" | "document" => {
+            let source_path = child(path, "source");
+            let source = object(required(fields, "source", path)?, &source_path)?;
+            string(required(source, "type", &source_path)?, &child(&source_path, "type"))?;
+            optional_string(source, "media_type", &source_path)?;
+            optional_string(source, "data", &source_path)?;
+        }
+        "tool_result" => {
+            string(required(fields, "tool_use_id", path)?, &child(path, "tool_use_id"))?;
+            required(fields, "content", path)?;
+            if let Some(is_error) = optional(fields, "is_error") {
+                boolean(is_error, &child(path, "is_error"))?;
+            }
+        }
+        "tool_use" => {
+            string(required(fields, "id", path)?, &child(path, "id"))?;
+            string(required(fields, "name", path)?, &child(path, "name"))?;
+            required(fields, "input", path)?;
+        }
+        "thinking" => {
+            string(required(fields, "thinking", path)?, &child(path, "thinking"))?;
+            string(required(fields, "signature", path)?, &child(path, "signature"))?;
+        }
+        "redacted_thinking" => {
+            string(required(fields, "data", path)?, &child(path, "data"))?;
+        }
+        other => {
+            return Err(FieldError {
+                path: type_path,
+                message: format!("unknown content block type `{}`", other),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn check_system(system: &Value, path: &str) -> Checked {
+    match system {
+        Value::String(_) => Ok(()),
+        Value::Array(blocks) => {
+            for (i, block) in blocks.iter().enumerate() {
+                let block_path = format!("{}.{}", path, i);
+                let block = object(block, &block_path)?;
+                string(required(block, "type", &block_path)?, &child(&block_path, "type"))?;
+                optional_string(block, "text", &block_path)?;
+            }
+            Ok(())
+        }
+        other => Err(expected("string or array", other, path)),
+    }
+}
+
+/// Custom tools carry an `input_schema`; built-in ones a versioned `type`.
+fn check_tool(tool: &Value, path: &str) -> Checked {
+    let tool = object(tool, path)?;
+    string(required(tool, "name", path)?, &child(path, "name"))?;
+    if tool.contains_key("input_schema") || !tool.contains_key("type") {
+        optional_string(tool, "description", path)?;
+        required(tool, "input_schema", path)?;
+    } else {
+        string(required(tool, "type", path)?, &child(path, "type"))?;
+    }
+    Ok(())
+}
+
+fn child(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn expected(what: &str, found: &Value, path: &str) -> FieldError {
+    FieldError {
+        path: path.to_string(),
+        message: format!("expected {}, found {}", what, kind(found)),
+    }
+}
+
+fn required<'a>(fields: &'a Map<String, Value>, key: &str, path: &str) -> Checked<&'a Value> {
+    fields.get(key).ok_or_else(|| FieldError {
+        path: child(path, key),
+        message: "field required".to_string(),
+    })
+}
+
+/// Missing and `null` are both "not set", as for the `Option` fields.
+fn optional<'a>(fields: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    fields.get(key).filter(|value| !value.is_null())
+}
+
+fn optional_string(fields: &Map<String, Value>, key: &str, path: &str) -> Checked {
+    match optional(fields, key) {
+        Some(value) => string(value, &child(path, key)),
+        None => Ok(()),
+    }
+}
+
+fn object<'a>(value: &'a Value, path: &str) -> Checked<&'a Map<String, Value>> {
+    value.as_object().ok_or_else(|| expected("object", value, path))
+}
+
+fn array<'a>(value: &'a Value, path: &str) -> Checked<&'a Vec<Value>> {
+    value.as_array().ok_or_else(|| expected("array", value, path))
+}
+
+fn string(value: &Value, path: &str) -> Checked {
+    value.is_string().then_some(()).ok_or_else(|| expected("string", value, path))
+}
+
+fn boolean(value: &Value, path: &str) -> Checked {
+    value.is_boolean().then_some(()).ok_or_else(|| expected("boolean", value, path))
+}
+
+fn number(value: &Value, path: &str) -> Checked {
+    value.is_number().then_some(()).ok_or_else(|| expected("number", value, path))
+}
+
+fn unsigned(value: &Value, path: &str) -> Checked {
+    match value.as_u64() {
+        Some(n) if n <= u32::MAX as u64 => Ok(()),
+        Some(_) => Err(FieldError {
+            path: path.to_string(),
+            message: format!("must be at most {}", u32::MAX),
+        }),
+        None => Err(expected("non-negative integer", value, path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(payload: Value) -> String {
+        parse_request(&payload).unwrap_err()
+    }
+
+    #[test]
+    fn errors_name_the_field_path_and_expected_type() {
+        let image = json!({"type": "image", "source": {"type": "base64", "media_type": 5, "data": "AA=="}});
+        let text = json!({"role": "user", "content": "hi"});
+        let message = json!({"role": "user", "content": [{"type": "text", "text": "hi"}, image]});
+        let payload = json!({
+            "model": "claude-test",
+            "max_tokens": 16,
+            "messages": [text.clone(), text.clone(), text, message],
+        });
+        assert_eq!(
+            error(payload),
+            "messages.3.content.1.source.media_type: expected string, found integer"
+        );
+
+        assert_eq!(
+            error(json!({"model": "m", "messages": []})),
+            "max_tokens: field required"
+        );
+        assert_eq!(
+            error(json!({"model": "m", "max_tokens": -1, "messages": []})),
+            "max_tokens: expected non-negative integer, found integer"
+        );
+        assert_eq!(
+            error(json!({"model": "m", "max_tokens": 1, "messages": [{"role": "user", "content": 3}]})),
+            "messages.0.content: expected string or array, found integer"
+        );
+        assert_eq!(
+            error(json!({"model": "m", "max_tokens": 1, "messages": [{"role": "user", "content": [{"type": "video"}]}]})),
+            "messages.0.content.0.type: unknown content block type `video`"
+        );
+        assert_eq!(
+            error(json!({"model": "m", "max_tokens": 1, "messages": [], "tools": [{"name": "t"}]})),
+            "tools.0.input_schema: field required"
+        );
+        assert_eq!(error(json!([])), "expected object, found array");
+    }
+
+    #[test]
+    fn valid_requests_still_parse() {
+        let payload = json!({
+            "model": "claude-test",
+            "max_tokens": 16,
+            "system": [{"type": "text", "text": "be brief"}],
+            "messages": [{"role": "user", "content": "hi"}],
+            "tools": [{"type": "bash_20250124", "name": "bash"}],
+            "temperature": null,
+        });
+        assert!(parse_request(&payload).is_ok());
+        assert_eq!(check_request(&payload), Ok(()));
+    }
+}
@@ -0,0 +1,155 @@
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::config::VertexConfig;
+
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const TOKEN_REFRESH_MARGIN_SECS: u64 = 60;
+
+#[derive(Clone)]
+pub struct VertexAuth {
+    source: Arc<TokenSource>,
+    cached: Arc<Mutex<Option<CachedToken>>>,
+    client: reqwest::Client,
+}
+
+enum TokenSource {
+    Static(String),
+    ServiceAccount(ServiceAccountKey),
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default)]
+    token_uri: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JwtClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: u64,
+}
+
+impl VertexAuth {
+    pub fn from_config(config: &VertexConfig, client: reqwest::Client) -> Result<Self, String> {
+        let source = match (config.access_token.as_deref(), config.credentials_path.as_deref()) {
+            (Some(token), _) => TokenSource::Static(token.to_string()),
+            (None, Some(path)) => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("vertex credentials read error: {}", e))?;
+                let key: ServiceAccountKey = serde_json::from_str(&content)
+                    .map_err(|e| format!("vertex credentials invalid: {}", e))?;
+                EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+                    .map_err(|e| format!("vertex credentials private_key invalid: {}", e))?;
+                TokenSource::ServiceAccount(key)
+            }
+            (None, None) => {
+                return Err("vertex requires access_token or credentials_path".to_string())
+            }
+        };
+        Ok(Self {
+            source: Arc::new(source),
+            cached: Arc::new(Mutex::new(None)),
+            client,
+        })
+    }
+
+    pub async fn token(&self) -> Result<String, String> {
+        let key = match self.source.as_ref() {
+            TokenSource::Static(token) => return Ok(token.clone()),
+            TokenSource::ServiceAccount(key) => key,
+        };
+        let mut cached = self.cached.lock().await;
+        let now = unix_now();
+        if let Some(token) = cached.as_ref()
+            && token.expires_at > now + TOKEN_REFRESH_MARGIN_SECS
+        {
+            return Ok(token.access_token.clone());
+        }
+        let fresh = self.fetch_service_account_token(key, now).await?;
+        let access_token = fresh.access_token.clone();
+        *cached = Some(fresh);
+        Ok(access_token)
+    }
+
+    async fn fetch_service_account_token(
+        &self,
+        key: &ServiceAccountKey,
+        now: u64,
+    ) -> Result<CachedToken, String> {
+        let token_uri = key.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+        let claims = JwtClaims {
+            iss: &key.client_email,
+            scope: CLOUD_PLATFORM_SCOPE,
+            aud: token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let encoding_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .map_err(|e| format!("vertex credentials private_key invalid: {}", e))?;
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &encoding_key)
+            .map_err(|e| format!("vertex jwt sign error: {}", e))?;
+        let resp = self
+            .client
+            .post(token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| format!("vertex token request failed: {}", e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("vertex token request failed: {} {}", status, text));
+        }
+        let body: TokenResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("vertex token response invalid: {}", e))?;
+        Ok(CachedToken {
+            access_token: body.access_token,
+            expires_at: now + body.expires_in.unwrap_or(3600),
+        })
+    }
+}
+
+pub fn vertex_body(mut payload: Value, anthropic_version: &str) -> Value {
+    if let Some(obj) = payload.as_object_mut() {
+        obj.remove("model");
+        obj.entry("anthropic_version")
+            .or_insert_with(|| Value::String(anthropic_version.to_string()));
+    }
+    payload
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,93 @@
+use futures_util::future::join_all;
+use reqwest::Url;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, WarmupConfig};
+
+/// Distinct `scheme://host:port` origins of every configured downstream base
+/// URL: the default one, per-provider endpoints and endpoint replicas.
+pub fn origins(config: &Config) -> Vec<String> {
+    let downstream = &config.downstream;
+    let mut providers: Vec<_> = downstream.provider_endpoints.iter().collect();
+    providers.sort_by(|a, b| a.0.cmp(b.0));
+    let urls = std::iter::once(downstream.base_url.as_str())
+        .chain(providers.into_iter().map(|(_, endpoint)| endpoint.base_url.as_str()))
+        .chain(downstream.endpoints.iter().map(|endpoint| endpoint.base_url.as_str()));
+    let mut origins: Vec<String> = Vec::new();
+    for url in urls {
+        let Ok(url) = Url::parse(url) else {
+            continue;
+        };
+        let origin = url.origin().ascii_serialization();
+        if origin != "null" && !origins.contains(&origin) {
+            origins.push(origin);
+        }
+    }
+    origins
+}
+
+/// Opens `warmup.connections` connections per origin on each client, all
+/// at once, and returns when they finish or `timeout_ms` elapses. Failures
+/// are logged and never fail startup.
+pub async fn warm_up(warmup: &WarmupConfig, origins: &[String], clients: &[&reqwest::Client]) {
+    let started = Instant::now();
+    let requests = origins.iter().flat_map(|origin| {
+        clients
+            .iter()
+            .flat_map(move |client| (0..warmup.connections).map(move |_| probe(warmup, client, origin)))
+    });
+    let results = tokio::time::timeout(Duration::from_millis(warmup.timeout_ms), join_all(requests)).await;
+    match results {
+        Ok(results) => {
+            let failed = results.iter().filter(|ok| !**ok).count();
+            tracing::info!(
+                origins = origins.len(),
+                connections = results.len() - failed,
+                failed,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "downstream warmup done"
+            );
+        }
+        Err(_) => tracing::warn!(timeout_ms = warmup.timeout_ms, "downstream warmup timed out"),
+    }
+}
+
+async fn probe(warmup: &WarmupConfig, client: &reqwest::Client, origin: &str) -> bool {
+    let request = match warmup.probe_path.as_deref() {
+        Some(path) => client.get(format!("{}{}", origin, path)),
+        None => client.head(origin),
+    };
+    match request.send().await {
+        Ok(response) => {
+            if warmup.probe_path.is_some() {
+                tracing::info!(origin, status = response.status().as_u16(), "downstream warmup probe");
+            }
+            true
+        }
+        Err(err) => {
+            tracing::warn!(origin, error = %err, "downstream warmup failed");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_distinct_origins() {
+        let config = Config::from_yaml(
+            "server: {}\ndownstream:\n  base_url: https://api.example.com/v1\n  provider_endpoints:\n    mistral:\n      base_url: https://api.mistral.ai/v1\n  endpoints:\n    - base_url: https://api.example.com/v2\n    - base_url: http://10.0.0.2:8000/v1\nmodels: {}\nlimits: {}\nobservability: {}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            origins(&config),
+            vec![
+                "https://api.example.com".to_string(),
+                "https://api.mistral.ai".to_string(),
+                "http://10.0.0.2:8000".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! Golden transcript conformance: replays the recorded OpenAI streams in
+//! `tests/fixtures/conformance` through the translator and compares the
+//! Anthropic SSE output event by event. Run with
+//! `cargo test --features conformance`.
+
+#![cfg(feature = "conformance")]
+
+use llm_gateway::internal::{default_config, run_dir, Mode};
+use std::path::Path;
+
+#[tokio::test]
+async fn fixtures_match_golden_transcripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/conformance");
+    let outcomes = run_dir(default_config(), &dir, Mode::Check).await.expect("conformance run");
+    assert!(!outcomes.is_empty(), "no fixtures in {}", dir.display());
+    let failures: Vec<String> = outcomes
+        .iter()
+        .filter_map(|o| o.mismatch.as_ref().map(|m| format!("{}: {}", o.name, m)))
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
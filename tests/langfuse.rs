@@ -0,0 +1,84 @@
+//! Langfuse attribution end to end: the span a translated request emits
+//! carries the session and tags taken from the client's headers. Kept in
+//! its own test binary since it installs the global tracer provider.
+
+mod support;
+
+use opentelemetry::Value as AttributeValue;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use support::{start_gateway, translate_config, FakeDownstream, Script};
+
+#[derive(Debug, Default, Clone)]
+struct RecordingExporter(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for RecordingExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        self.0.lock().unwrap().extend(batch);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn request_span_carries_langfuse_session_and_tags() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let observability = "observability:\n  exporters:\n    tracing: langfuse_http\n  otlp_http:\n    base_url: \"http://127.0.0.1:9\"\n    public_key: \"pk\"\n    secret_key: \"sk\"\n  langfuse:\n    session_header: \"x-session-id\"\n";
+    let config = translate_config(&downstream, "").replace("observability: {}\n", observability);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    // Installed after the gateway so it replaces the provider `build` set up.
+    let spans = RecordingExporter::default();
+    let provider = SdkTracerProvider::builder().with_simple_exporter(spans.clone()).build();
+    opentelemetry::global::set_tracer_provider(provider);
+    downstream.push(Script::json(
+        200,
+        json!({
+            "id": "chatcmpl-1",
+            "model": "fake-model",
+            "choices": [{"message": {"role": "assistant", "content": "pong"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+        }),
+    ));
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .header("x-session-id", "conv-42")
+        .header("langfuse-tags", "prod, agent,prod")
+        .json(&json!({
+            "model": "claude-test",
+            "max_tokens": 64,
+            "metadata": {"user_id": "user-7"},
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 200);
+
+    let span = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let found = spans.0.lock().unwrap().iter().find(|span| span.name == "ai.gateway.request").cloned();
+            if let Some(span) = found {
+                return span;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("request span exported");
+    let attribute = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(attribute("langfuse.session.id"), Some(AttributeValue::from("conv-42")));
+    assert_eq!(attribute("langfuse.user.id"), Some(AttributeValue::from("user-7")));
+    assert_eq!(
+        attribute("langfuse.trace.tags").map(|value| value.to_string()),
+        Some("[\"prod\",\"agent\"]".to_string())
+    );
+    assert_eq!(attribute("langfuse.observation.type"), Some(AttributeValue::from("generation")));
+}
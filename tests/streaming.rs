@@ -0,0 +1,626 @@
+//! End-to-end streaming through the full router against a scripted fake
+//! downstream, asserting the Anthropic SSE transcript the client sees.
+
+mod support;
+
+use serde_json::{json, Value};
+use std::time::Duration;
+use support::{
+    anthropic_event, done, openai_chunk, openai_usage, post_stream, start_gateway, translate_config, FakeDownstream,
+    Script, Step,
+};
+
+fn stream_request() -> Value {
+    json!({
+        "model": "claude-test",
+        "max_tokens": 64,
+        "stream": true,
+        "messages": [{"role": "user", "content": "hi"}]
+    })
+}
+
+#[tokio::test]
+async fn translates_text_stream_into_anthropic_transcript() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let Some(gateway) = start_gateway(&translate_config(&downstream, "")).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "Hel"}), None),
+        Step::Delay(Duration::from_millis(20)),
+        openai_chunk(json!({"content": "lo"}), None),
+        openai_chunk(json!({}), Some("stop")),
+        openai_usage(9, 2),
+        done(),
+    ]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.status, 200);
+    assert_eq!(
+        transcript.names(),
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop"
+        ]
+    );
+    assert_eq!(transcript.text(), "Hello");
+    let message_delta = &transcript.events[5].1;
+    assert_eq!(message_delta["delta"]["stop_reason"], "end_turn");
+    assert_eq!(message_delta["usage"]["output_tokens"], 2);
+
+    let requests = downstream.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "/v1/chat/completions");
+    assert_eq!(requests[0].1["model"], "fake-model");
+    assert_eq!(requests[0].1["stream"], true);
+}
+
+#[tokio::test]
+async fn tool_call_followed_by_text_without_finish_reason_keeps_blocks_sequential() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let Some(gateway) = start_gateway(&translate_config(&downstream, "")).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(
+            json!({"tool_calls": [{"index": 0, "id": "call_1", "type": "function", "function": {"name": "lookup", "arguments": "{\"q\":\"rust\"}"}}]}),
+            None,
+        ),
+        openai_chunk(json!({"content": "Looking it up."}), None),
+        openai_chunk(json!({}), Some("tool_calls")),
+        done(),
+    ]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    let blocks: Vec<String> = transcript
+        .events
+        .iter()
+        .filter(|(name, _)| name == "content_block_start" || name == "content_block_stop")
+        .map(|(name, data)| format!("{}:{}", name.trim_start_matches("content_block_"), data["index"]))
+        .collect();
+    assert_eq!(blocks, ["start:0", "stop:0", "start:1", "stop:1"]);
+    assert_eq!(transcript.events[1].1["content_block"]["type"], "tool_use");
+    assert_eq!(transcript.last()["type"], "message_stop");
+}
+
+#[tokio::test]
+async fn malformed_frame_ends_stream_with_error_unless_lenient() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let script = || {
+        Script::sse(vec![
+            openai_chunk(json!({"content": "a"}), None),
+            Step::Raw("data: {not json\n\n".to_string()),
+            openai_chunk(json!({"content": "b"}), Some("stop")),
+            done(),
+        ])
+    };
+
+    let Some(strict) = start_gateway(&translate_config(&downstream, "")).await else { return };
+    downstream.push(script());
+    let transcript = post_stream(&strict, stream_request()).await;
+    assert_eq!(transcript.names().last(), Some(&"error"));
+    assert_eq!(transcript.last()["error"]["type"], "api_error");
+    assert_eq!(transcript.text(), "a");
+
+    let lenient_config = translate_config(&downstream, "").replace(
+        "  api_key: \"sk-fake\"\n",
+        "  api_key: \"sk-fake\"\n  stream_parsing: lenient\n",
+    );
+    let Some(lenient) = start_gateway(&lenient_config).await else { return };
+    downstream.push(script());
+    let transcript = post_stream(&lenient, stream_request()).await;
+    assert_eq!(transcript.text(), "ab");
+    assert_eq!(transcript.last()["type"], "message_stop");
+}
+
+#[tokio::test]
+async fn downstream_disconnect_mid_stream_ends_with_error_event() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let Some(gateway) = start_gateway(&translate_config(&downstream, "")).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"content": "partial"}), None),
+        Step::Delay(Duration::from_millis(10)),
+        Step::Disconnect,
+    ]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.text(), "partial");
+    assert_eq!(transcript.names().last(), Some(&"error"));
+    assert!(transcript.last()["error"]["message"].as_str().unwrap().contains("stream error"));
+    assert!(!transcript.names().contains(&"message_stop"));
+}
+
+#[tokio::test]
+async fn stalled_downstream_fails_with_timeout_before_any_event() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = translate_config(&downstream, "").replace(
+        "  api_key: \"sk-fake\"\n",
+        "  api_key: \"sk-fake\"\n  first_byte_timeout_ms: 100\n",
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::sse(vec![
+        Step::Delay(Duration::from_millis(500)),
+        openai_chunk(json!({"content": "late"}), Some("stop")),
+        done(),
+    ]));
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .json(&stream_request())
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 504);
+    let body: Value = resp.json().await.expect("json");
+    assert_eq!(body["error"]["type"], "timeout_error");
+
+    downstream.push(Script::sse(vec![
+        Step::Delay(Duration::from_millis(20)),
+        openai_chunk(json!({"content": "on time"}), Some("stop")),
+        done(),
+    ]));
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.text(), "on time");
+}
+
+#[tokio::test]
+async fn failed_attempt_is_retried_before_streaming() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = translate_config(&downstream, "").replace(
+        "  api_key: \"sk-fake\"\n",
+        "  api_key: \"sk-fake\"\n  stream_retries: 1\n  stream_retry_backoff_ms: 1\n",
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::json(503, json!({"error": {"message": "overloaded"}})));
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"content": "second try"}), Some("stop")),
+        done(),
+    ]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.status, 200);
+    assert_eq!(transcript.text(), "second try");
+    assert_eq!(transcript.names().iter().filter(|name| **name == "message_start").count(), 1);
+    let bodies = downstream.bodies();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0], bodies[1]);
+
+    // Retries used up: the downstream error reaches the client.
+    downstream.push(Script::json(503, json!({"error": {"message": "overloaded"}})));
+    downstream.push(Script::json(503, json!({"error": {"message": "overloaded"}})));
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.status, 502);
+    assert_eq!(downstream.bodies().len(), 4);
+}
+
+#[tokio::test]
+async fn passthrough_retries_stalled_stream_before_first_event() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = format!(
+        "server: {{}}\ndownstream:\n  base_url: \"{}\"\n  first_byte_timeout_ms: 100\n  stream_retries: 1\n  stream_retry_backoff_ms: 1\nmodels: {{}}\nlimits: {{}}\nobservability: {{}}\n",
+        downstream.url
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    let stop = anthropic_event("message_stop", json!({"type": "message_stop"}));
+    downstream.push(Script::sse(vec![Step::Delay(Duration::from_millis(500)), stop.clone()]));
+    downstream.push(Script::sse(vec![stop]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.status, 200);
+    assert_eq!(transcript.names(), vec!["message_stop"]);
+    let bodies = downstream.bodies();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0], bodies[1]);
+}
+
+#[tokio::test]
+async fn stream_read_idle_timeout_ends_a_stalled_stream() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = translate_config(&downstream, "").replace(
+        "  api_key: \"sk-fake\"\n",
+        "  api_key: \"sk-fake\"\n  stream:\n    read_idle_timeout_ms: 100\n",
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"content": "partial"}), None),
+        Step::Delay(Duration::from_millis(500)),
+        openai_chunk(json!({"content": " late"}), Some("stop")),
+        done(),
+    ]));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    assert_eq!(transcript.text(), "partial");
+    assert_eq!(transcript.names().last(), Some(&"error"));
+    assert!(!transcript.names().contains(&"message_stop"));
+}
+
+#[tokio::test]
+async fn passthrough_relays_anthropic_events_unchanged() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = format!(
+        "server: {{}}\ndownstream:\n  base_url: \"{}\"\nmodels: {{}}\nlimits: {{}}\nobservability: {{}}\n",
+        downstream.url
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    let events = [
+        ("message_start", json!({"type": "message_start", "message": {"id": "msg_1", "type": "message", "role": "assistant", "model": "claude-test", "content": [], "usage": {"input_tokens": 3, "output_tokens": 0}}})),
+        ("content_block_start", json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}})),
+        ("content_block_delta", json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "pong"}})),
+        ("content_block_stop", json!({"type": "content_block_stop", "index": 0})),
+        ("message_delta", json!({"type": "message_delta", "delta": {"stop_reason": "end_turn", "stop_sequence": null}, "usage": {"output_tokens": 1}})),
+        ("message_stop", json!({"type": "message_stop"})),
+    ];
+    downstream.push(Script::sse(
+        events.iter().map(|(name, data)| anthropic_event(name, data.clone())).collect(),
+    ));
+
+    let transcript = post_stream(&gateway, stream_request()).await;
+    let expected: Vec<(String, Value)> = events.into_iter().map(|(name, data)| (name.to_string(), data)).collect();
+    assert_eq!(transcript.events, expected);
+    assert_eq!(downstream.requests()[0].0, "/v1/messages");
+}
+
+#[tokio::test]
+async fn passthrough_forwards_request_bytes_verbatim() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = format!(
+        "server: {{}}\ndownstream:\n  base_url: \"{}\"\nmodels: {{}}\nlimits: {{}}\nobservability: {{}}\n",
+        downstream.url
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::json(200, json!({"type": "message", "content": []})));
+    let body = "{\n  \"model\": \"claude-test\",\n  \"messages\": [{\"role\": \"user\", \"content\": \"hi\"}],\n  \"max_tokens\": 64\n}";
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(downstream.bodies()[0], body.as_bytes());
+}
+
+#[tokio::test]
+async fn passthrough_rewrite_maps_model_without_reencoding() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let config = format!(
+        "server: {{}}\nanthropic:\n  passthrough_rewrite: true\ndownstream:\n  base_url: \"{}\"\nmodels:\n  model_map:\n    claude-test: claude-sonnet-4-5\nlimits: {{}}\nobservability: {{}}\n",
+        downstream.url
+    );
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::json(200, json!({"type": "message", "content": []})));
+    let body = "{\n  \"model\": \"claude-test\",\n  \"messages\": [{\"role\": \"user\", \"content\": \"hi\"}],\n  \"max_tokens\": 64\n}";
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 200);
+    let forwarded = body.replace("claude-test", "claude-sonnet-4-5");
+    assert_eq!(downstream.bodies()[0], forwarded.as_bytes());
+}
+
+/// Records written under `dir` so far, waiting for at least `count`.
+async fn read_records(dir: &std::path::Path, count: usize) -> Vec<Value> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let records: Vec<Value> = std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+                .flat_map(|text| text.lines().map(str::to_string).collect::<Vec<_>>())
+                .map(|line| serde_json::from_str::<Value>(&line).expect("record"))
+                .collect();
+            if records.len() >= count {
+                return records;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("records written")
+}
+
+#[tokio::test]
+async fn debug_header_captures_both_sse_sides_into_the_debug_file() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let dir = std::env::temp_dir().join(format!("llm-gateway-debug-e2e-{}", std::process::id()));
+    let observability = format!(
+        "observability:\n  audit_log:\n    enabled: true\n    path: \"{}\"\n    max_body_bytes: 64\n    stream_sse: client\n  debug_capture:\n    enabled: true\n    secret: \"s3cret\"\n    path: \"{}\"",
+        dir.join("audit").join("audit.jsonl").display(),
+        dir.join("debug").join("debug.jsonl").display()
+    );
+    let config = translate_config(&downstream, "").replace("observability: {}", &observability);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "pong"}), Some("stop")),
+        done(),
+    ]));
+
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .header("x-gateway-debug", "s3cret")
+        .json(&stream_request())
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 200);
+    let client_sse = resp.text().await.expect("body");
+
+    let debug = read_records(&dir.join("debug"), 1).await.remove(0);
+    let audit = read_records(&dir.join("audit"), 1).await.remove(0);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(debug["meta"]["debug"], true);
+    assert!(debug["request"]["headers"].get("x-gateway-debug").is_none());
+    assert!(debug["response"]["downstream_sse"].as_str().unwrap().contains("data: [DONE]"));
+    assert_eq!(debug["response"]["client_sse"], client_sse);
+    assert_eq!(debug["meta"]["body_truncated"], false);
+
+    // The audit copy keeps its own `stream_sse` and `max_body_bytes`.
+    assert!(audit["response"].get("downstream_sse").is_none());
+    assert!(audit["response"]["client_sse"].as_str().unwrap().len() <= 64);
+    assert_eq!(audit["meta"]["body_truncated"], true);
+}
+
+#[tokio::test]
+async fn failed_and_truncated_streams_are_audited_with_their_sse() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let dir = std::env::temp_dir().join(format!("llm-gateway-audit-sse-e2e-{}", std::process::id()));
+    let observability = format!(
+        "observability:\n  audit_log:\n    enabled: true\n    path: \"{}\"\n    stream_sse: both",
+        dir.join("audit.jsonl").display()
+    );
+    let config = translate_config(&downstream, "").replace("observability: {}", &observability);
+    let Some(gateway) = start_gateway(&config).await else { return };
+
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "a"}), None),
+        Step::Raw("data: {not json\n\n".to_string()),
+    ]));
+    let malformed = post_stream(&gateway, stream_request()).await;
+    assert_eq!(malformed.names().last(), Some(&"error"));
+    let records = read_records(&dir, 1).await;
+    let record = &records[0];
+    assert!(record["response"]["downstream_sse"].as_str().unwrap().contains("{not json"));
+    assert!(record["response"]["client_sse"].as_str().unwrap().contains("event: error"));
+
+    downstream.push(Script::sse(vec![openai_chunk(
+        json!({"role": "assistant", "content": "cut"}),
+        None,
+    )]));
+    let truncated = post_stream(&gateway, stream_request()).await;
+    assert_eq!(truncated.text(), "cut");
+    let records = read_records(&dir, 2).await;
+    let _ = std::fs::remove_dir_all(&dir);
+    let record = &records[1];
+    assert!(!record["response"]["downstream_sse"].as_str().unwrap().contains("[DONE]"));
+    assert!(record["response"]["client_sse"].as_str().unwrap().contains("\"cut\""));
+}
+
+#[tokio::test]
+async fn debug_capture_keeps_the_downstream_exchange_of_a_translated_request() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let dir = std::env::temp_dir().join(format!("llm-gateway-debug-json-e2e-{}", std::process::id()));
+    let observability = format!(
+        "observability:\n  debug_capture:\n    enabled: true\n    secret: \"s3cret\"\n    path: \"{}\"",
+        dir.join("debug.jsonl").display()
+    );
+    let config = translate_config(&downstream, "").replace("observability: {}", &observability);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    let completion = json!({
+        "id": "chatcmpl-1",
+        "model": "fake-model",
+        "choices": [{"message": {"role": "assistant", "content": "pong"}, "finish_reason": "stop"}]
+    });
+    downstream.push(Script::json(200, completion.clone()));
+
+    let mut request = stream_request();
+    request["stream"] = json!(false);
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .header("x-gateway-debug", "s3cret")
+        .json(&request)
+        .send()
+        .await
+        .expect("send");
+    assert_eq!(resp.status(), 200);
+
+    let record = read_records(&dir, 1).await.remove(0);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let exchange = &record["downstream"];
+    assert!(exchange["url"].as_str().unwrap().ends_with("/v1/chat/completions"));
+    assert_eq!(exchange["request_headers"]["authorization"], "[redacted]");
+    assert_eq!(exchange["request_body"], downstream.requests()[0].1);
+    assert_eq!(exchange["response_body"], completion);
+    assert_eq!(record["response"]["body"]["content"][0]["text"], "pong");
+}
+
+#[tokio::test]
+async fn stored_transcript_replays_the_client_stream_verbatim() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let dir = std::env::temp_dir().join(format!("llm-gateway-transcripts-e2e-{}", std::process::id()));
+    let observability = format!(
+        "observability:\n  transcripts:\n    enabled: true\n    sample_percent: 100\n    dir: \"{}\"",
+        dir.display()
+    );
+    let config = translate_config(&downstream, "admin:\n  token: \"admin-secret\"\n")
+        .replace("observability: {}", &observability);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "Hel"}), None),
+        Step::Delay(Duration::from_millis(20)),
+        openai_chunk(json!({"content": "lo"}), Some("stop")),
+        done(),
+    ]));
+
+    let client = reqwest::Client::new();
+    let original = client
+        .post(format!("{}/v1/messages", gateway))
+        .json(&stream_request())
+        .send()
+        .await
+        .expect("send")
+        .text()
+        .await
+        .expect("body");
+
+    let request_id = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let stored = std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json.gz").map(str::to_string))
+                .next();
+            if let Some(request_id) = stored {
+                return request_id;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("transcript stored");
+
+    let replayed = client
+        .get(format!("{}/admin/transcripts/{}?speed=0", gateway, request_id))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .expect("replay");
+    assert_eq!(replayed.status(), 200);
+    assert_eq!(replayed.text().await.expect("body"), original);
+
+    let missing = client
+        .get(format!("{}/admin/transcripts/req_missing", gateway))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .expect("replay");
+    assert_eq!(missing.status(), 404);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn summarize_policy_replaces_old_turns_but_keeps_the_system_prompt() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let context = "models:\n  context:\n    limits:\n      fake-model: 300\n    policy: summarize\n    summary_model: summary-model\n  system_role:\n    fake-model: developer\n";
+    let config = translate_config(&downstream, "").replace("models:\n", context);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::json(
+        200,
+        json!({
+            "id": "chatcmpl-summary",
+            "model": "summary-model",
+            "choices": [{"message": {"role": "assistant", "content": "They said hello."}, "finish_reason": "stop"}]
+        }),
+    ));
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "Done"}), Some("stop")),
+        done(),
+    ]));
+
+    let filler = "x".repeat(2000);
+    let transcript = post_stream(
+        &gateway,
+        json!({
+            "model": "claude-test",
+            "max_tokens": 16,
+            "stream": true,
+            "system": "Be brief.",
+            "messages": [
+                {"role": "user", "content": filler},
+                {"role": "assistant", "content": filler},
+                {"role": "user", "content": "second"},
+                {"role": "assistant", "content": "ok"},
+                {"role": "user", "content": "last"}
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(transcript.status, 200);
+    assert_eq!(transcript.text(), "Done");
+
+    let requests = downstream.requests();
+    assert_eq!(requests.len(), 2);
+    let summarized = requests[0].1["messages"][1]["content"].as_str().unwrap();
+    assert_eq!(requests[0].1["model"], "summary-model");
+    assert!(summarized.starts_with("user: xxx"));
+    assert!(!summarized.contains("Be brief."));
+
+    let messages = requests[1].1["messages"].as_array().unwrap();
+    let roles: Vec<&str> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+    assert_eq!(roles, ["developer", "developer", "user", "assistant", "user"]);
+    assert_eq!(messages[0]["content"], "Be brief.");
+    assert_eq!(messages[1]["content"], "Summary of the earlier conversation:\nThey said hello.");
+    assert_eq!(messages[2]["content"], "second");
+}
+
+#[tokio::test]
+async fn oauth_token_is_refreshed_after_a_401_and_kept_off_keyed_endpoints() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let oauth = format!(
+        "  api_key: \"sk-fake\"\n  oauth:\n    token_url: \"{url}/token\"\n    client_id: \"gateway\"\n    client_secret: \"secret\"\n  endpoints:\n    - base_url: \"{url}/keyed\"\n      api_key: \"sk-endpoint\"\n      models: [\"keyed-model\"]\n",
+        url = downstream.url
+    );
+    let config = translate_config(&downstream, "")
+        .replace("  api_key: \"sk-fake\"\n", &oauth)
+        .replace("    claude-test: fake-model\n", "    claude-test: fake-model\n    claude-keyed: keyed-model\n");
+    let Some(gateway) = start_gateway(&config).await else { return };
+    let token = |token: &str| Script::json(200, json!({"access_token": token, "expires_in": 3600}));
+    let reply = || {
+        Script::sse(vec![
+            openai_chunk(json!({"role": "assistant", "content": "ok"}), Some("stop")),
+            done(),
+        ])
+    };
+    downstream.push(token("tok-1"));
+    downstream.push(Script::json(401, json!({"error": {"message": "token expired"}})));
+    downstream.push(token("tok-2"));
+    downstream.push(reply());
+    downstream.push(reply());
+
+    assert_eq!(post_stream(&gateway, stream_request()).await.status, 200);
+    let mut keyed = stream_request();
+    keyed["model"] = json!("claude-keyed");
+    assert_eq!(post_stream(&gateway, keyed).await.status, 200);
+
+    let chat_calls: Vec<(String, Option<String>)> = downstream
+        .authorizations()
+        .into_iter()
+        .filter(|(path, _)| path.ends_with("/chat/completions"))
+        .collect();
+    assert_eq!(
+        chat_calls,
+        vec![
+            ("/v1/chat/completions".to_string(), Some("Bearer tok-1".to_string())),
+            ("/v1/chat/completions".to_string(), Some("Bearer tok-2".to_string())),
+            ("/keyed/v1/chat/completions".to_string(), Some("Bearer sk-endpoint".to_string())),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn failover_key_pool_retries_with_the_next_key() {
+    let Some(downstream) = FakeDownstream::start().await else { return };
+    let keys = "  api_keys:\n    - id: primary\n      key: \"sk-a\"\n    - id: backup\n      key: \"sk-b\"\n  key_rotation: failover\n";
+    let config = translate_config(&downstream, "").replace("  api_key: \"sk-fake\"\n", keys);
+    let Some(gateway) = start_gateway(&config).await else { return };
+    downstream.push(Script::json(401, json!({"error": {"message": "revoked"}})));
+    downstream.push(Script::sse(vec![
+        openai_chunk(json!({"role": "assistant", "content": "ok"}), Some("stop")),
+        done(),
+    ]));
+
+    assert_eq!(post_stream(&gateway, stream_request()).await.status, 200);
+    let keys: Vec<Option<String>> = downstream.authorizations().into_iter().map(|(_, key)| key).collect();
+    assert_eq!(keys, vec![Some("Bearer sk-a".to_string()), Some("Bearer sk-b".to_string())]);
+}
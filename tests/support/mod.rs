@@ -0,0 +1,296 @@
+//! Harness for the integration tests: a scripted fake downstream and the
+//! full gateway router served on loopback ports, plus an SSE transcript
+//! reader for the client side.
+
+#![allow(dead_code)]
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::Router;
+use llm_gateway::GatewayBuilder;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// One step of a scripted downstream response body.
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// A `data:` frame.
+    Data(String),
+    /// Bytes sent as they are, e.g. a malformed or partial frame.
+    Raw(String),
+    Delay(Duration),
+    /// Aborts the connection mid-body.
+    Disconnect,
+}
+
+/// The response to the next downstream call.
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn sse(steps: Vec<Step>) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/event-stream",
+            steps,
+        }
+    }
+
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            steps: vec![Step::Raw(body.to_string())],
+        }
+    }
+}
+
+/// An OpenAI chat completion chunk with one choice.
+pub fn openai_chunk(delta: Value, finish_reason: Option<&str>) -> Step {
+    Step::Data(
+        json!({
+            "id": "chatcmpl-fake",
+            "model": "fake-model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        })
+        .to_string(),
+    )
+}
+
+pub fn openai_usage(prompt_tokens: u64, completion_tokens: u64) -> Step {
+    Step::Data(
+        json!({
+            "id": "chatcmpl-fake",
+            "model": "fake-model",
+            "choices": [],
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "completion_tokens": completion_tokens,
+                "total_tokens": prompt_tokens + completion_tokens
+            }
+        })
+        .to_string(),
+    )
+}
+
+pub fn done() -> Step {
+    Step::Data("[DONE]".to_string())
+}
+
+/// A named Anthropic SSE event, for passthrough scripts.
+pub fn anthropic_event(event: &str, data: Value) -> Step {
+    Step::Raw(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// Answers every call with the next queued script and records the calls.
+#[derive(Clone)]
+pub struct FakeDownstream {
+    pub url: String,
+    scripts: Arc<Mutex<VecDeque<Script>>>,
+    requests: Arc<Mutex<Vec<(String, Value)>>>,
+    bodies: Arc<Mutex<Vec<Bytes>>>,
+    authorizations: Arc<Mutex<Vec<(String, Option<String>)>>>,
+}
+
+impl FakeDownstream {
+    /// `None` when the sandbox does not allow binding a loopback port.
+    pub async fn start() -> Option<Self> {
+        let scripts: Arc<Mutex<VecDeque<Script>>> = Default::default();
+        let requests: Arc<Mutex<Vec<(String, Value)>>> = Default::default();
+        let bodies: Arc<Mutex<Vec<Bytes>>> = Default::default();
+        let authorizations: Arc<Mutex<Vec<(String, Option<String>)>>> = Default::default();
+        let app = Router::new().fallback(respond).with_state((
+            scripts.clone(),
+            requests.clone(),
+            bodies.clone(),
+            authorizations.clone(),
+        ));
+        let url = serve(app).await?;
+        Some(Self {
+            url,
+            scripts,
+            requests,
+            bodies,
+            authorizations,
+        })
+    }
+
+    pub fn push(&self, script: Script) {
+        self.scripts.lock().unwrap().push_back(script);
+    }
+
+    /// Paths and JSON bodies of the calls received so far.
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Raw bodies of the calls received so far.
+    pub fn bodies(&self) -> Vec<Bytes> {
+        self.bodies.lock().unwrap().clone()
+    }
+
+    /// Paths and `Authorization` headers of the calls received so far.
+    pub fn authorizations(&self) -> Vec<(String, Option<String>)> {
+        self.authorizations.lock().unwrap().clone()
+    }
+}
+
+type FakeState = (
+    Arc<Mutex<VecDeque<Script>>>,
+    Arc<Mutex<Vec<(String, Value)>>>,
+    Arc<Mutex<Vec<Bytes>>>,
+    Arc<Mutex<Vec<(String, Option<String>)>>>,
+);
+
+async fn respond(
+    State((scripts, requests, bodies, authorizations)): State<FakeState>,
+    request: Request,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let authorization = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    authorizations.lock().unwrap().push((path.clone(), authorization));
+    let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap_or_default();
+    bodies.lock().unwrap().push(body.clone());
+    requests
+        .lock()
+        .unwrap()
+        .push((path, serde_json::from_slice(&body).unwrap_or(Value::Null)));
+    let Some(script) = scripts.lock().unwrap().pop_front() else {
+        return Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from("no script queued"))
+            .unwrap();
+    };
+    let frames = futures_util::stream::unfold(script.steps.into_iter(), |mut steps| async move {
+        loop {
+            let frame = match steps.next()? {
+                Step::Data(data) => Ok(Bytes::from(format!("data: {}\n\n", data))),
+                Step::Raw(raw) => Ok(Bytes::from(raw)),
+                Step::Delay(delay) => {
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                Step::Disconnect => Err(std::io::Error::other("scripted disconnect")),
+            };
+            return Some((frame, steps));
+        }
+    });
+    Response::builder()
+        .status(script.status)
+        .header(CONTENT_TYPE, script.content_type)
+        .body(Body::from_stream(frames))
+        .unwrap()
+}
+
+/// Serves the gateway built from `config` (YAML) on a loopback port.
+pub async fn start_gateway(config: &str) -> Option<String> {
+    let gateway = GatewayBuilder::from_yaml(config)
+        .expect("config")
+        .alerts(false)
+        .build()
+        .await
+        .expect("gateway");
+    serve(gateway.into_router()).await
+}
+
+/// A translate-mode config pointing at `downstream`; `extra` is appended
+/// as further top-level YAML.
+pub fn translate_config(downstream: &FakeDownstream, extra: &str) -> String {
+    format!(
+        "server: {{}}\nanthropic:\n  forward_mode: translate\ndownstream:\n  base_url: \"{}\"\n  api_key: \"sk-fake\"\nmodels:\n  model_map:\n    claude-test: fake-model\nlimits: {{}}\nobservability: {{}}\n{}",
+        downstream.url, extra
+    )
+}
+
+async fn serve(app: Router) -> Option<String> {
+    let listener = match tokio::net::TcpListener::bind("127.0.0.1:0").await {
+        Ok(listener) => listener,
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => return None,
+        Err(err) => panic!("bind failed: {}", err),
+    };
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    Some(format!("http://{}", addr))
+}
+
+/// The client's view of a streamed response.
+#[derive(Debug)]
+pub struct Transcript {
+    pub status: u16,
+    pub events: Vec<(String, Value)>,
+    /// Whether the body ended with a transport error.
+    pub aborted: bool,
+}
+
+impl Transcript {
+    pub fn names(&self) -> Vec<&str> {
+        self.events.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Concatenated `text_delta`s.
+    pub fn text(&self) -> String {
+        self.events
+            .iter()
+            .filter_map(|(_, data)| data.pointer("/delta/text").and_then(Value::as_str))
+            .collect()
+    }
+
+    pub fn last(&self) -> &Value {
+        &self.events.last().expect("no events").1
+    }
+}
+
+/// Posts `body` to the gateway's `/v1/messages` and reads the SSE reply.
+pub async fn post_stream(gateway: &str, body: Value) -> Transcript {
+    let mut resp = reqwest::Client::new()
+        .post(format!("{}/v1/messages", gateway))
+        .json(&body)
+        .send()
+        .await
+        .expect("send");
+    let status = resp.status().as_u16();
+    let mut raw = Vec::new();
+    let mut aborted = false;
+    loop {
+        match resp.chunk().await {
+            Ok(Some(chunk)) => raw.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(_) => {
+                aborted = true;
+                break;
+            }
+        }
+    }
+    let text = String::from_utf8_lossy(&raw).replace("\r\n", "\n");
+    let events = text
+        .split("\n\n")
+        .filter_map(|frame| {
+            let mut name = "message".to_string();
+            let mut data = String::new();
+            for line in frame.lines() {
+                if let Some(value) = line.strip_prefix("event:") {
+                    name = value.trim().to_string();
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push_str(value.trim_start());
+                }
+            }
+            (!data.is_empty()).then(|| (name, serde_json::from_str(&data).unwrap_or(Value::String(data))))
+        })
+        .collect();
+    Transcript { status, events, aborted }
+}